@@ -87,6 +87,35 @@ pub fn main() {
         },
     )
     .unwrap();
+    deploy_file(
+        "./node_modules/leaflet-draw/dist/leaflet.draw.js",
+        dist_dir.join("leaflet.draw.js"),
+    );
+    deploy_file(
+        "./node_modules/leaflet-draw/dist/leaflet.draw.css",
+        dist_dir.join("leaflet.draw.css"),
+    );
+    fs_extra::dir::copy(
+        "./node_modules/leaflet-draw/dist/images/",
+        dist_dir,
+        &dir::CopyOptions {
+            overwrite: true,
+            ..dir::CopyOptions::default()
+        },
+    )
+    .unwrap();
+    deploy_file(
+        "./node_modules/leaflet.markercluster/dist/leaflet.markercluster.js",
+        dist_dir.join("leaflet.markercluster.js"),
+    );
+    deploy_file(
+        "./node_modules/leaflet.markercluster/dist/MarkerCluster.css",
+        dist_dir.join("MarkerCluster.css"),
+    );
+    deploy_file(
+        "./node_modules/leaflet.markercluster/dist/MarkerCluster.Default.css",
+        dist_dir.join("MarkerCluster.Default.css"),
+    );
     deploy_file(
         "./vendored/MapCenterCoord/L.Control.MapCenterCoord.min.js",
         dist_dir.join("L.Control.MapCenterCoord.js"),
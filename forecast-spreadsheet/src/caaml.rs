@@ -0,0 +1,1240 @@
+//! Import of avalanche bulletins in the [CAAML](https://www.caaml.org/) format, so that the
+//! history of an avalanche center which previously published its bulletins elsewhere can be
+//! preserved when migrating. Supports CAAML v6 JSON documents, and CAAML v5 XML documents (the EAWS
+//! bulletin profile). Forecasts can also be exported as CAAML v6 JSON bulletins, see [`export`].
+//!
+//! CAAML describes the elevations affected by a danger rating or avalanche problem using bounds
+//! (e.g. above 2000m, or below the treeline), which are mapped onto the elevation bands of the
+//! forecast area, see [`ImportOptions::elevation_bands`]. Where a bulletin has separate ratings
+//! for different times of the day, the highest rating is used.
+
+use std::collections::HashMap;
+
+use eyre::{Context, ContextCompat};
+use indexmap::{IndexMap, IndexSet};
+use serde::Deserialize;
+use time::{format_description::well_known::Rfc3339, OffsetDateTime};
+use unic_langid::LanguageIdentifier;
+
+use crate::{
+    AreaId, Aspect, AspectElevation, AvalancheProblem, Distribution, ElevationBandId,
+    ElevationRange, Forecast, Forecaster, HazardRating, HazardRatingKind, HazardRatingValue,
+    ProblemKind, Sensitivity, Size, TimeOfDay, Trend, Version,
+};
+
+/// How long a bulletin is valid for when the document doesn't specify it.
+const DEFAULT_VALID_FOR: time::Duration = time::Duration::hours(24);
+/// Language of bulletins which don't specify one.
+const DEFAULT_LANGUAGE: &str = "en";
+
+/// Options for converting the bulletins into [`Forecast`]s.
+pub struct ImportOptions<'a> {
+    /// The area the bulletins are for.
+    pub area: AreaId,
+    /// Template version recorded in the forecasts.
+    pub template_version: Version,
+    /// Elevation bands of the area, usually those of a previous forecast. A danger rating or
+    /// avalanche problem applies to a band if the middle of the band is within its elevation
+    /// bounds. The treeline is taken to be the upper bound of the lowest band.
+    pub elevation_bands: &'a IndexMap<ElevationBandId, ElevationRange>,
+    /// Only import the bulletins which include this region id, e.g. `AT-07`.
+    pub region: Option<&'a str>,
+    /// Name of the forecaster for bulletins which don't include an author.
+    pub forecaster: Option<&'a str>,
+}
+
+/// A bound of the elevations affected by a danger rating or avalanche problem.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Bound {
+    Metres(i64),
+    Treeline,
+}
+
+impl Bound {
+    fn parse(value: &str) -> eyre::Result<Self> {
+        let value = value.trim();
+        if value.eq_ignore_ascii_case("treeline") {
+            return Ok(Self::Treeline);
+        }
+        value
+            .trim_end_matches('m')
+            .parse()
+            .map(Self::Metres)
+            .wrap_err_with(|| format!("Invalid elevation bound {value:?}"))
+    }
+
+    fn metres(self, treeline: Option<i64>) -> eyre::Result<i64> {
+        match self {
+            Self::Metres(metres) => Ok(metres),
+            Self::Treeline => {
+                treeline.wrap_err("Unable to determine the treeline elevation for the area")
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+struct Elevation {
+    lower: Option<Bound>,
+    upper: Option<Bound>,
+}
+
+#[derive(Debug)]
+struct DangerRating {
+    value: HazardRatingValue,
+    elevation: Elevation,
+}
+
+#[derive(Debug)]
+struct Problem {
+    kind: ProblemKind,
+    elevation: Elevation,
+    aspects: IndexSet<Aspect>,
+    time_of_day: Option<TimeOfDay>,
+    sensitivity: Option<Sensitivity>,
+    distribution: Option<Distribution>,
+    size: Option<Size>,
+    comment: Option<String>,
+}
+
+/// A bulletin read from either version of the format.
+#[derive(Debug, Default)]
+struct Bulletin {
+    regions: Vec<String>,
+    language: Option<String>,
+    publication_time: Option<OffsetDateTime>,
+    valid_start: Option<OffsetDateTime>,
+    valid_end: Option<OffsetDateTime>,
+    forecaster: Option<String>,
+    organisation: Option<String>,
+    danger_ratings: Vec<DangerRating>,
+    problems: Vec<Problem>,
+    highlights: Vec<String>,
+    weather_forecast: Option<String>,
+    weather_review: Option<String>,
+    tendency: Option<Trend>,
+    tendency_comment: Option<String>,
+}
+
+/// Convert the bulletins in a CAAML `document` into forecasts.
+pub fn import(document: &[u8], options: &ImportOptions) -> eyre::Result<Vec<Forecast>> {
+    let bulletins = match document.iter().find(|byte| !byte.is_ascii_whitespace()) {
+        Some(b'{') => parse_v6_json(document)?,
+        Some(b'<') => {
+            let document = std::str::from_utf8(document).wrap_err("Document is not UTF-8")?;
+            parse_v5_xml(document)?
+        }
+        _ => eyre::bail!("Expected a CAAML v6 JSON or CAAML v5 XML document"),
+    };
+    bulletins
+        .into_iter()
+        .filter(|bulletin| {
+            options
+                .region
+                .is_none_or(|region| bulletin.regions.iter().any(|id| id == region))
+        })
+        .map(|bulletin| convert_bulletin(bulletin, options))
+        .collect()
+}
+
+fn parse_time(value: &str) -> eyre::Result<OffsetDateTime> {
+    OffsetDateTime::parse(value.trim(), &Rfc3339)
+        .wrap_err_with(|| format!("Invalid time {value:?}"))
+}
+
+/// Convert the text of a bulletin to markdown, replacing the line breaks which are commonly
+/// included as HTML.
+fn format_text(text: &str) -> String {
+    text.replace("<br/>", "\n")
+        .replace("<br />", "\n")
+        .replace("<br>", "\n")
+        .trim()
+        .to_owned()
+}
+
+fn parse_danger_value(value: &str) -> eyre::Result<HazardRatingValue> {
+    Ok(match value.trim().to_lowercase().as_str() {
+        "1" | "low" => HazardRatingValue::Low,
+        "2" | "moderate" => HazardRatingValue::Moderate,
+        "3" | "considerable" => HazardRatingValue::Considerable,
+        "4" | "high" => HazardRatingValue::High,
+        "5" | "very_high" | "very high" => HazardRatingValue::Extreme,
+        "n/a" | "no_rating" | "no_snow" => HazardRatingValue::NoRating,
+        unknown => eyre::bail!("Unknown danger rating {unknown:?}"),
+    })
+}
+
+/// The kind of avalanche problem, `None` for the problem types which don't have an equivalent
+/// (e.g. `favourable_situation`).
+fn parse_problem_kind(value: &str) -> Option<ProblemKind> {
+    Some(
+        match value
+            .trim()
+            .to_lowercase()
+            .replace([' ', '-'], "_")
+            .as_str()
+        {
+            "new_snow" => ProblemKind::StormSlab,
+            "wind_slab" | "wind_drifted_snow" | "drifting_snow" | "wind_packed_snow" => {
+                ProblemKind::WindSlab
+            }
+            "persistent_weak_layers" | "old_snow" => ProblemKind::PersistentSlab,
+            "wet_snow" => ProblemKind::WetSlab,
+            "gliding_snow" => ProblemKind::Glide,
+            "cornices" => ProblemKind::Cornice,
+            _ => return None,
+        },
+    )
+}
+
+fn parse_time_period(value: &str) -> Option<TimeOfDay> {
+    match value {
+        "all_day" => Some(TimeOfDay::AllDay),
+        "earlier" => Some(TimeOfDay::Morning),
+        "later" => Some(TimeOfDay::Afternoon),
+        _ => None,
+    }
+}
+
+fn parse_stability(value: &str) -> Option<Sensitivity> {
+    match value {
+        "very_poor" => Some(Sensitivity::Touchy),
+        "poor" => Some(Sensitivity::Reactive),
+        "fair" => Some(Sensitivity::Stubborn),
+        "good" => Some(Sensitivity::Unreactive),
+        _ => None,
+    }
+}
+
+fn parse_frequency(value: &str) -> Option<Distribution> {
+    match value {
+        "many" => Some(Distribution::Widespread),
+        "some" => Some(Distribution::Specific),
+        "few" => Some(Distribution::Isolated),
+        _ => None,
+    }
+}
+
+fn parse_tendency(value: &str) -> Option<Trend> {
+    match value.trim().to_lowercase().as_str() {
+        "decreasing" => Some(Trend::Improving),
+        "steady" => Some(Trend::NoChange),
+        "increasing" => Some(Trend::Deteriorating),
+        _ => None,
+    }
+}
+
+#[derive(Deserialize)]
+struct V6Document {
+    bulletins: Vec<V6Bulletin>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct V6Bulletin {
+    publication_time: Option<String>,
+    valid_time: Option<V6ValidTime>,
+    lang: Option<String>,
+    #[serde(default)]
+    regions: Vec<V6Region>,
+    #[serde(default)]
+    danger_ratings: Vec<V6DangerRating>,
+    #[serde(default)]
+    avalanche_problems: Vec<V6AvalancheProblem>,
+    highlights: Option<String>,
+    avalanche_activity: Option<V6Texts>,
+    snowpack_structure: Option<V6Texts>,
+    weather_forecast: Option<V6Texts>,
+    weather_review: Option<V6Texts>,
+    #[serde(default)]
+    tendency: Vec<V6Tendency>,
+    source: Option<V6Source>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct V6ValidTime {
+    start_time: Option<String>,
+    end_time: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct V6Region {
+    #[serde(rename = "regionID")]
+    region_id: String,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(rename_all = "camelCase")]
+struct V6Elevation {
+    lower_bound: Option<String>,
+    upper_bound: Option<String>,
+}
+
+impl V6Elevation {
+    fn parse(&self) -> eyre::Result<Elevation> {
+        Ok(Elevation {
+            lower: Option::transpose(self.lower_bound.as_deref().map(Bound::parse))?,
+            upper: Option::transpose(self.upper_bound.as_deref().map(Bound::parse))?,
+        })
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct V6DangerRating {
+    main_value: String,
+    #[serde(default)]
+    elevation: V6Elevation,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct V6AvalancheProblem {
+    problem_type: String,
+    #[serde(default)]
+    elevation: V6Elevation,
+    #[serde(default)]
+    aspects: Vec<String>,
+    valid_time_period: Option<String>,
+    snowpack_stability: Option<String>,
+    frequency: Option<String>,
+    avalanche_size: Option<u8>,
+    comment: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct V6Texts {
+    highlights: Option<String>,
+    comment: Option<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct V6Tendency {
+    tendency_type: Option<String>,
+    comment: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct V6Source {
+    person: Option<V6Named>,
+    provider: Option<V6Named>,
+}
+
+#[derive(Deserialize)]
+struct V6Named {
+    name: Option<String>,
+}
+
+fn parse_v6_json(document: &[u8]) -> eyre::Result<Vec<Bulletin>> {
+    let document: V6Document =
+        serde_json::from_slice(document).wrap_err("Error parsing CAAML v6 JSON document")?;
+    document
+        .bulletins
+        .into_iter()
+        .map(|bulletin| {
+            let danger_ratings = bulletin
+                .danger_ratings
+                .iter()
+                .map(|rating| {
+                    Ok(DangerRating {
+                        value: parse_danger_value(&rating.main_value)?,
+                        elevation: rating.elevation.parse()?,
+                    })
+                })
+                .collect::<eyre::Result<_>>()?;
+            let problems = bulletin
+                .avalanche_problems
+                .iter()
+                .filter_map(|problem| {
+                    let kind = parse_problem_kind(&problem.problem_type)?;
+                    Some((kind, problem))
+                })
+                .map(|(kind, problem)| {
+                    Ok(Problem {
+                        kind,
+                        elevation: problem.elevation.parse()?,
+                        aspects: problem
+                            .aspects
+                            .iter()
+                            .filter_map(|aspect| aspect.parse().ok())
+                            .collect(),
+                        time_of_day: problem
+                            .valid_time_period
+                            .as_deref()
+                            .and_then(parse_time_period),
+                        sensitivity: problem
+                            .snowpack_stability
+                            .as_deref()
+                            .and_then(parse_stability),
+                        distribution: problem.frequency.as_deref().and_then(parse_frequency),
+                        size: problem
+                            .avalanche_size
+                            .and_then(|size| Size::try_from(size).ok()),
+                        comment: problem.comment.as_deref().map(format_text),
+                    })
+                })
+                .collect::<eyre::Result<_>>()?;
+            let mut highlights: Vec<String> = Vec::new();
+            highlights.extend(bulletin.highlights.as_deref().map(format_text));
+            for texts in [&bulletin.avalanche_activity, &bulletin.snowpack_structure]
+                .into_iter()
+                .flatten()
+            {
+                highlights.extend(texts.highlights.as_deref().map(format_text));
+                highlights.extend(texts.comment.as_deref().map(format_text));
+            }
+            let tendency = bulletin.tendency.first();
+            let source = bulletin.source.as_ref();
+            Ok(Bulletin {
+                regions: bulletin
+                    .regions
+                    .into_iter()
+                    .map(|region| region.region_id)
+                    .collect(),
+                language: bulletin.lang,
+                publication_time: Option::transpose(
+                    bulletin.publication_time.as_deref().map(parse_time),
+                )?,
+                valid_start: Option::transpose(
+                    bulletin
+                        .valid_time
+                        .as_ref()
+                        .and_then(|valid_time| valid_time.start_time.as_deref())
+                        .map(parse_time),
+                )?,
+                valid_end: Option::transpose(
+                    bulletin
+                        .valid_time
+                        .as_ref()
+                        .and_then(|valid_time| valid_time.end_time.as_deref())
+                        .map(parse_time),
+                )?,
+                forecaster: source
+                    .and_then(|source| source.person.as_ref())
+                    .and_then(|person| person.name.clone()),
+                organisation: source
+                    .and_then(|source| source.provider.as_ref())
+                    .and_then(|provider| provider.name.clone()),
+                danger_ratings,
+                problems,
+                highlights,
+                weather_forecast: bulletin
+                    .weather_forecast
+                    .and_then(|texts| texts.comment)
+                    .map(|text| format_text(&text)),
+                weather_review: bulletin
+                    .weather_review
+                    .and_then(|texts| texts.comment)
+                    .map(|text| format_text(&text)),
+                tendency: tendency
+                    .and_then(|tendency| tendency.tendency_type.as_deref())
+                    .and_then(parse_tendency),
+                tendency_comment: tendency
+                    .and_then(|tendency| tendency.comment.as_deref())
+                    .map(format_text),
+            })
+        })
+        .collect()
+}
+
+/// Child elements of the `node` with the local `name`.
+fn children<'a, 'input>(
+    node: roxmltree::Node<'a, 'input>,
+    name: &'static str,
+) -> impl Iterator<Item = roxmltree::Node<'a, 'input>> {
+    node.children()
+        .filter(move |child| child.is_element() && child.tag_name().name() == name)
+}
+
+fn child<'a, 'input>(
+    node: roxmltree::Node<'a, 'input>,
+    name: &'static str,
+) -> Option<roxmltree::Node<'a, 'input>> {
+    children(node, name).next()
+}
+
+/// The first descendant element of the `node` with the local `name`.
+fn descendant<'a, 'input>(
+    node: roxmltree::Node<'a, 'input>,
+    name: &'static str,
+) -> Option<roxmltree::Node<'a, 'input>> {
+    node.descendants()
+        .find(|descendant| descendant.is_element() && descendant.tag_name().name() == name)
+}
+
+/// The non-empty text of the `node`.
+fn text(node: roxmltree::Node) -> Option<String> {
+    node.text().map(format_text).filter(|text| !text.is_empty())
+}
+
+/// The value of the attribute with the local `name` (ignoring the namespace, e.g. `xlink:href`).
+fn attribute<'a>(node: roxmltree::Node<'a, '_>, name: &str) -> Option<&'a str> {
+    node.attributes()
+        .find(|attribute| attribute.name() == name)
+        .map(|attribute| attribute.value())
+}
+
+/// Parse a `validElevation` element, which either references a predefined range (e.g.
+/// `ElevationRange_2000Hi` for above 2000m, or `ElevationRange_TreelineLw` for below the
+/// treeline), or contains an `elevationRange` with its bounds.
+fn parse_v5_elevation(node: roxmltree::Node) -> eyre::Result<Elevation> {
+    if let Some(range) = descendant(node, "elevationRange") {
+        return Ok(Elevation {
+            lower: Option::transpose(
+                child(range, "beginPosition")
+                    .and_then(text)
+                    .map(|value| Bound::parse(&value)),
+            )?,
+            upper: Option::transpose(
+                child(range, "endPosition")
+                    .and_then(text)
+                    .map(|value| Bound::parse(&value)),
+            )?,
+        });
+    }
+    let Some(href) = attribute(node, "href") else {
+        return Ok(Elevation::default());
+    };
+    let range = href.trim_start_matches("ElevationRange_");
+    if let Some(bound) = range.strip_suffix("Hi") {
+        Ok(Elevation {
+            lower: Some(Bound::parse(bound.trim_end_matches('_'))?),
+            upper: None,
+        })
+    } else if let Some(bound) = range.strip_suffix("Lw") {
+        Ok(Elevation {
+            lower: None,
+            upper: Some(Bound::parse(bound.trim_end_matches('_'))?),
+        })
+    } else {
+        eyre::bail!("Unsupported elevation range {href:?}")
+    }
+}
+
+fn parse_v5_xml(document: &str) -> eyre::Result<Vec<Bulletin>> {
+    let document =
+        roxmltree::Document::parse(document).wrap_err("Error parsing CAAML v5 XML document")?;
+    document
+        .descendants()
+        .filter(|node| node.is_element() && node.tag_name().name() == "Bulletin")
+        .map(|node| {
+            let mut bulletin = Bulletin {
+                regions: children(node, "locRef")
+                    .filter_map(|loc_ref| attribute(loc_ref, "href"))
+                    .map(ToOwned::to_owned)
+                    .collect(),
+                language: attribute(node, "lang").map(ToOwned::to_owned),
+                ..Bulletin::default()
+            };
+            if let Some(meta_data) = descendant(node, "MetaData") {
+                bulletin.publication_time = Option::transpose(
+                    child(meta_data, "dateTimeReport")
+                        .and_then(text)
+                        .map(|value| parse_time(&value)),
+                )?;
+                bulletin.forecaster = descendant(meta_data, "Person")
+                    .and_then(|person| child(person, "name"))
+                    .and_then(text);
+                bulletin.organisation = descendant(meta_data, "Operation")
+                    .and_then(|operation| child(operation, "name"))
+                    .and_then(text);
+            }
+            if let Some(time_period) =
+                child(node, "validTime").and_then(|valid_time| child(valid_time, "TimePeriod"))
+            {
+                bulletin.valid_start = Option::transpose(
+                    child(time_period, "beginPosition")
+                        .and_then(text)
+                        .map(|value| parse_time(&value)),
+                )?;
+                bulletin.valid_end = Option::transpose(
+                    child(time_period, "endPosition")
+                        .and_then(text)
+                        .map(|value| parse_time(&value)),
+                )?;
+            }
+            let Some(measurements) = descendant(node, "BulletinMeasurements") else {
+                return Ok(bulletin);
+            };
+            for rating in measurements
+                .descendants()
+                .filter(|node| node.is_element() && node.tag_name().name() == "DangerRating")
+            {
+                let Some(value) = child(rating, "mainValue").and_then(text) else {
+                    continue;
+                };
+                bulletin.danger_ratings.push(DangerRating {
+                    value: parse_danger_value(&value)?,
+                    elevation: match child(rating, "validElevation") {
+                        Some(elevation) => parse_v5_elevation(elevation)?,
+                        None => Elevation::default(),
+                    },
+                });
+            }
+            for problem in measurements
+                .descendants()
+                .filter(|node| node.is_element() && node.tag_name().name() == "AvProblem")
+            {
+                let Some(kind) = child(problem, "type")
+                    .and_then(text)
+                    .and_then(|value| parse_problem_kind(&value))
+                else {
+                    continue;
+                };
+                bulletin.problems.push(Problem {
+                    kind,
+                    elevation: match child(problem, "validElevation") {
+                        Some(elevation) => parse_v5_elevation(elevation)?,
+                        None => Elevation::default(),
+                    },
+                    aspects: children(problem, "validAspect")
+                        .filter_map(|aspect| attribute(aspect, "href"))
+                        .filter_map(|href| href.trim_start_matches("AspectRange_").parse().ok())
+                        .collect(),
+                    time_of_day: None,
+                    sensitivity: None,
+                    distribution: None,
+                    size: None,
+                    comment: child(problem, "comment").and_then(text),
+                });
+            }
+            for name in [
+                "avActivityHighlights",
+                "avActivityComment",
+                "snowpackStructureHighlights",
+                "snowpackStructureComment",
+            ] {
+                bulletin
+                    .highlights
+                    .extend(child(measurements, name).and_then(text));
+            }
+            bulletin.weather_forecast = child(measurements, "wxSynopsisComment").and_then(text);
+            bulletin.tendency_comment = child(measurements, "tendencyComment").and_then(text);
+            bulletin.tendency = descendant(measurements, "tendency")
+                .and_then(|tendency| child(tendency, "type"))
+                .and_then(text)
+                .and_then(|value| parse_tendency(&value));
+            Ok(bulletin)
+        })
+        .collect()
+}
+
+/// Whether the `elevation` applies to the elevation band with the `range`.
+fn elevation_includes(
+    elevation: Elevation,
+    range: &ElevationRange,
+    treeline: Option<i64>,
+) -> eyre::Result<bool> {
+    // A point within the band, open ended bands use a point just inside their bound.
+    let band_elevation = match (range.lower, range.upper) {
+        (Some(lower), Some(upper)) => (lower + upper) / 2,
+        (Some(lower), None) => lower + 1,
+        (None, Some(upper)) => upper - 1,
+        (None, None) => return Ok(true),
+    };
+    let above_lower = match elevation.lower {
+        Some(lower) => band_elevation >= lower.metres(treeline)?,
+        None => true,
+    };
+    let below_upper = match elevation.upper {
+        Some(upper) => band_elevation <= upper.metres(treeline)?,
+        None => true,
+    };
+    Ok(above_lower && below_upper)
+}
+
+/// The highest of the danger `ratings`.
+fn highest_rating<'a>(
+    ratings: impl Iterator<Item = &'a DangerRating>,
+) -> Option<HazardRatingValue> {
+    ratings
+        .map(|rating| rating.value)
+        .max_by_key(|value| *value as u8)
+}
+
+/// Join the non-empty `texts` into a translated string in the `language`.
+fn translated(
+    language: &LanguageIdentifier,
+    texts: impl IntoIterator<Item = String>,
+) -> HashMap<LanguageIdentifier, String> {
+    let text = texts
+        .into_iter()
+        .filter(|text| !text.is_empty())
+        .collect::<Vec<_>>()
+        .join("\n\n");
+    if text.is_empty() {
+        HashMap::new()
+    } else {
+        HashMap::from([(language.clone(), text)])
+    }
+}
+
+fn convert_bulletin(bulletin: Bulletin, options: &ImportOptions) -> eyre::Result<Forecast> {
+    let language: LanguageIdentifier = bulletin
+        .language
+        .as_deref()
+        .unwrap_or(DEFAULT_LANGUAGE)
+        .parse()
+        .wrap_err("Invalid bulletin language")?;
+    let time = bulletin
+        .publication_time
+        .or(bulletin.valid_start)
+        .wrap_err("Bulletin has no publication time")?;
+    let valid_for = bulletin
+        .valid_end
+        .map(|valid_end| valid_end - time)
+        .filter(|valid_for| valid_for.is_positive())
+        .unwrap_or(DEFAULT_VALID_FOR);
+    let forecaster = Forecaster {
+        name: bulletin
+            .forecaster
+            .or_else(|| options.forecaster.map(ToOwned::to_owned))
+            .or_else(|| bulletin.organisation.clone())
+            .wrap_err("Bulletin has no author, specify the forecaster")?,
+        organisation: bulletin.organisation,
+    };
+    let treeline = options
+        .elevation_bands
+        .values()
+        .min_by_key(|range| range.lower.unwrap_or(i64::MIN))
+        .and_then(|range| range.upper);
+
+    let mut hazard_ratings = IndexMap::new();
+    hazard_ratings.insert(
+        HazardRatingKind::Overall,
+        HazardRating {
+            value: highest_rating(bulletin.danger_ratings.iter()),
+            trend: bulletin.tendency,
+            confidence: None,
+        },
+    );
+    for (band_id, range) in options.elevation_bands {
+        let mut matching = Vec::new();
+        for rating in &bulletin.danger_ratings {
+            if elevation_includes(rating.elevation, range, treeline)? {
+                matching.push(rating);
+            }
+        }
+        hazard_ratings.insert(
+            HazardRatingKind::ElevationSpecific(band_id.clone()),
+            HazardRating {
+                value: highest_rating(matching.into_iter()),
+                trend: None,
+                confidence: None,
+            },
+        );
+    }
+
+    let mut avalanche_problems = Vec::new();
+    for (i, problem) in bulletin.problems.into_iter().enumerate() {
+        let mut aspect_elevation = IndexMap::new();
+        for (band_id, range) in options.elevation_bands {
+            if elevation_includes(problem.elevation, range, treeline)? {
+                aspect_elevation.insert(
+                    band_id.clone(),
+                    AspectElevation {
+                        aspects: problem.aspects.clone(),
+                    },
+                );
+            }
+        }
+        avalanche_problems.push(AvalancheProblem {
+            kind: problem.kind,
+            aspect_elevation,
+            confidence: None,
+            trend: None,
+            size: problem.size,
+            distribution: problem.distribution,
+            time_of_day: problem.time_of_day,
+            sensitivity: problem.sensitivity,
+            description: translated(&language, problem.comment),
+            // CAAML lists the problems in order of importance.
+            is_primary: i == 0,
+        });
+    }
+
+    Ok(Forecast {
+        template_version: options.template_version,
+        area: options.area.clone(),
+        forecaster,
+        time,
+        recent_observations: translated(&language, bulletin.weather_review),
+        forecast_changes: translated(&language, bulletin.tendency_comment),
+        weather_forecast: translated(&language, bulletin.weather_forecast),
+        valid_for,
+        description: translated(&language, bulletin.highlights),
+        hazard_ratings,
+        avalanche_problems,
+        elevation_bands: options.elevation_bands.clone(),
+    })
+}
+
+/// Options for converting a [`Forecast`] into a CAAML v6 bulletin, see [`export`].
+pub struct ExportOptions<'a> {
+    /// Unique id of the bulletin, e.g. the name of the forecast file.
+    pub bulletin_id: &'a str,
+    /// Id of the region the forecast is for, as known by the recipient (e.g. `GE-01`).
+    pub region_id: &'a str,
+    /// Name of the region.
+    pub region_name: &'a str,
+    /// Language of the texts included in the bulletin, the texts are omitted if the forecast
+    /// doesn't have a translation in this language.
+    pub language: &'a LanguageIdentifier,
+}
+
+fn danger_value_str(value: HazardRatingValue) -> &'static str {
+    match value {
+        HazardRatingValue::NoRating => "no_rating",
+        HazardRatingValue::Low => "low",
+        HazardRatingValue::Moderate => "moderate",
+        HazardRatingValue::Considerable => "considerable",
+        HazardRatingValue::High => "high",
+        HazardRatingValue::Extreme => "very_high",
+    }
+}
+
+fn problem_kind_str(kind: ProblemKind) -> &'static str {
+    match kind {
+        ProblemKind::LooseDry | ProblemKind::StormSlab => "new_snow",
+        ProblemKind::WindSlab => "wind_slab",
+        ProblemKind::PersistentSlab | ProblemKind::DeepSlab => "persistent_weak_layers",
+        ProblemKind::LooseWet | ProblemKind::WetSlab => "wet_snow",
+        ProblemKind::Glide => "gliding_snow",
+        ProblemKind::Cornice => "cornices",
+    }
+}
+
+fn aspect_str(aspect: Aspect) -> &'static str {
+    match aspect {
+        Aspect::N => "N",
+        Aspect::NE => "NE",
+        Aspect::E => "E",
+        Aspect::SE => "SE",
+        Aspect::S => "S",
+        Aspect::SW => "SW",
+        Aspect::W => "W",
+        Aspect::NW => "NW",
+    }
+}
+
+fn time_period_str(time_of_day: TimeOfDay) -> &'static str {
+    match time_of_day {
+        TimeOfDay::AllDay => "all_day",
+        TimeOfDay::Morning => "earlier",
+        TimeOfDay::Afternoon => "later",
+    }
+}
+
+fn stability_str(sensitivity: Sensitivity) -> &'static str {
+    match sensitivity {
+        Sensitivity::Touchy => "very_poor",
+        Sensitivity::Reactive => "poor",
+        Sensitivity::Stubborn => "fair",
+        Sensitivity::Unreactive => "good",
+    }
+}
+
+fn frequency_str(distribution: Distribution) -> &'static str {
+    match distribution {
+        Distribution::Widespread => "many",
+        Distribution::Specific => "some",
+        Distribution::Isolated => "few",
+    }
+}
+
+fn tendency_str(trend: Trend) -> &'static str {
+    match trend {
+        Trend::Improving => "decreasing",
+        Trend::NoChange => "steady",
+        Trend::Deteriorating => "increasing",
+    }
+}
+
+/// The CAAML elevation of the band with the `range`, `None` if the band is unbounded.
+fn export_elevation(range: &ElevationRange) -> Option<serde_json::Value> {
+    let mut elevation = serde_json::Map::new();
+    if let Some(lower) = range.lower {
+        elevation.insert("lowerBound".to_owned(), lower.to_string().into());
+    }
+    if let Some(upper) = range.upper {
+        elevation.insert("upperBound".to_owned(), upper.to_string().into());
+    }
+    (!elevation.is_empty()).then_some(serde_json::Value::Object(elevation))
+}
+
+/// Insert the `value` into the `object` if it is present.
+fn insert_some(
+    object: &mut serde_json::Map<String, serde_json::Value>,
+    key: &str,
+    value: Option<impl Into<serde_json::Value>>,
+) {
+    if let Some(value) = value {
+        object.insert(key.to_owned(), value.into());
+    }
+}
+
+/// Convert the `forecast` into a CAAML v6 JSON document containing a single bulletin. The danger
+/// ratings and avalanche problems are given for each elevation band of the forecast.
+pub fn export(forecast: &Forecast, options: &ExportOptions) -> eyre::Result<serde_json::Value> {
+    let text = |texts: &HashMap<LanguageIdentifier, String>| {
+        texts
+            .get(options.language)
+            .filter(|text| !text.is_empty())
+            .cloned()
+    };
+    let format_time = |time: OffsetDateTime| {
+        time.format(&Rfc3339)
+            .wrap_err_with(|| format!("Error formatting time {time}"))
+    };
+
+    let mut danger_ratings = Vec::new();
+    for (band_id, range) in &forecast.elevation_bands {
+        let Some(value) = forecast
+            .hazard_ratings
+            .get(&HazardRatingKind::ElevationSpecific(band_id.clone()))
+            .and_then(|rating| rating.value)
+        else {
+            continue;
+        };
+        let mut rating = serde_json::Map::new();
+        rating.insert("mainValue".to_owned(), danger_value_str(value).into());
+        insert_some(&mut rating, "elevation", export_elevation(range));
+        danger_ratings.push(serde_json::Value::Object(rating));
+    }
+    let overall = forecast.hazard_ratings.get(&HazardRatingKind::Overall);
+    if danger_ratings.is_empty() {
+        if let Some(value) = overall.and_then(|rating| rating.value) {
+            danger_ratings.push(serde_json::json!({ "mainValue": danger_value_str(value) }));
+        }
+    }
+
+    // CAAML lists the problems in order of importance.
+    let problems = forecast
+        .avalanche_problems
+        .iter()
+        .filter(|problem| problem.is_primary)
+        .chain(
+            forecast
+                .avalanche_problems
+                .iter()
+                .filter(|problem| !problem.is_primary),
+        );
+    let mut avalanche_problems = Vec::new();
+    for problem in problems {
+        // A CAAML problem has a single elevation, so it is repeated for each band.
+        for (band_id, aspect_elevation) in &problem.aspect_elevation {
+            let mut caaml_problem = serde_json::Map::new();
+            caaml_problem.insert(
+                "problemType".to_owned(),
+                problem_kind_str(problem.kind).into(),
+            );
+            insert_some(
+                &mut caaml_problem,
+                "elevation",
+                forecast
+                    .elevation_bands
+                    .get(band_id)
+                    .and_then(export_elevation),
+            );
+            caaml_problem.insert(
+                "aspects".to_owned(),
+                aspect_elevation
+                    .aspects
+                    .iter()
+                    .map(|aspect| aspect_str(*aspect))
+                    .collect::<Vec<_>>()
+                    .into(),
+            );
+            insert_some(
+                &mut caaml_problem,
+                "validTimePeriod",
+                problem.time_of_day.map(time_period_str),
+            );
+            insert_some(
+                &mut caaml_problem,
+                "snowpackStability",
+                problem.sensitivity.map(stability_str),
+            );
+            insert_some(
+                &mut caaml_problem,
+                "frequency",
+                problem.distribution.map(frequency_str),
+            );
+            insert_some(
+                &mut caaml_problem,
+                "avalancheSize",
+                problem.size.map(|size| size as u8),
+            );
+            insert_some(&mut caaml_problem, "comment", text(&problem.description));
+            avalanche_problems.push(serde_json::Value::Object(caaml_problem));
+        }
+    }
+
+    let mut tendency = serde_json::Map::new();
+    insert_some(
+        &mut tendency,
+        "tendencyType",
+        overall.and_then(|rating| rating.trend).map(tendency_str),
+    );
+    insert_some(&mut tendency, "comment", text(&forecast.forecast_changes));
+
+    let mut source = serde_json::Map::new();
+    source.insert(
+        "person".to_owned(),
+        serde_json::json!({ "name": forecast.forecaster.name }),
+    );
+    if let Some(organisation) = &forecast.forecaster.organisation {
+        source.insert(
+            "provider".to_owned(),
+            serde_json::json!({ "name": organisation }),
+        );
+    }
+
+    let mut bulletin = serde_json::Map::new();
+    bulletin.insert("bulletinID".to_owned(), options.bulletin_id.into());
+    bulletin.insert("lang".to_owned(), options.language.language.as_str().into());
+    bulletin.insert(
+        "publicationTime".to_owned(),
+        format_time(forecast.time)?.into(),
+    );
+    bulletin.insert(
+        "validTime".to_owned(),
+        serde_json::json!({
+            "startTime": format_time(forecast.time)?,
+            "endTime": format_time(forecast.time + forecast.valid_for)?,
+        }),
+    );
+    bulletin.insert(
+        "regions".to_owned(),
+        serde_json::json!([{ "regionID": options.region_id, "name": options.region_name }]),
+    );
+    bulletin.insert("dangerRatings".to_owned(), danger_ratings.into());
+    bulletin.insert("avalancheProblems".to_owned(), avalanche_problems.into());
+    insert_some(&mut bulletin, "highlights", text(&forecast.description));
+    insert_some(
+        &mut bulletin,
+        "weatherForecast",
+        text(&forecast.weather_forecast).map(|comment| serde_json::json!({ "comment": comment })),
+    );
+    insert_some(
+        &mut bulletin,
+        "weatherReview",
+        text(&forecast.recent_observations)
+            .map(|comment| serde_json::json!({ "comment": comment })),
+    );
+    if !tendency.is_empty() {
+        bulletin.insert(
+            "tendency".to_owned(),
+            serde_json::json!([serde_json::Value::Object(tendency)]),
+        );
+    }
+    bulletin.insert("source".to_owned(), serde_json::Value::Object(source));
+
+    Ok(serde_json::json!({ "bulletins": [serde_json::Value::Object(bulletin)] }))
+}
+
+#[cfg(test)]
+mod test {
+    use std::path::Path;
+
+    use indexmap::IndexMap;
+
+    use super::{export, import, Bound, Elevation, ExportOptions, ImportOptions};
+    use crate::{
+        ElevationBandId, ElevationRange, HazardRatingKind, HazardRatingValue, ProblemKind, Version,
+    };
+
+    const CRATE_DIR: &str = env!("CARGO_MANIFEST_DIR");
+
+    fn elevation_bands() -> IndexMap<ElevationBandId, ElevationRange> {
+        IndexMap::from([
+            (
+                "high-alpine".into(),
+                ElevationRange {
+                    upper: None,
+                    lower: Some(2800),
+                },
+            ),
+            (
+                "alpine".into(),
+                ElevationRange {
+                    upper: Some(2800),
+                    lower: Some(1800),
+                },
+            ),
+            (
+                "sub-alpine".into(),
+                ElevationRange {
+                    upper: Some(1800),
+                    lower: None,
+                },
+            ),
+        ])
+    }
+
+    fn import_fixture(name: &str) -> Vec<crate::Forecast> {
+        let document =
+            std::fs::read(Path::new(CRATE_DIR).join("fixtures/caaml").join(name)).unwrap();
+        let elevation_bands = elevation_bands();
+        import(&document, &import_options(&elevation_bands)).unwrap()
+    }
+
+    fn import_options(
+        elevation_bands: &IndexMap<ElevationBandId, ElevationRange>,
+    ) -> ImportOptions<'_> {
+        ImportOptions {
+            area: "Gudauri".to_owned().into(),
+            template_version: Version {
+                major: 0,
+                minor: 3,
+                patch: 3,
+            },
+            elevation_bands,
+            region: None,
+            forecaster: Some("Imported"),
+        }
+    }
+
+    fn rating(forecast: &crate::Forecast, kind: HazardRatingKind) -> Option<HazardRatingValue> {
+        forecast.hazard_ratings.get(&kind).unwrap().value
+    }
+
+    #[test]
+    fn test_import_v6_json() {
+        let forecasts = import_fixture("bulletin.v6.json");
+        assert_eq!(forecasts.len(), 1);
+        let forecast = &forecasts[0];
+        assert_eq!(forecast.forecaster.name, "Jane Doe");
+        assert_eq!(forecast.valid_for, time::Duration::hours(25));
+        assert_eq!(
+            rating(forecast, HazardRatingKind::Overall),
+            Some(HazardRatingValue::Considerable)
+        );
+        assert_eq!(
+            rating(
+                forecast,
+                HazardRatingKind::ElevationSpecific("alpine".into())
+            ),
+            Some(HazardRatingValue::Considerable)
+        );
+        assert_eq!(
+            rating(
+                forecast,
+                HazardRatingKind::ElevationSpecific("sub-alpine".into())
+            ),
+            Some(HazardRatingValue::Moderate)
+        );
+        assert_eq!(forecast.avalanche_problems.len(), 1);
+        let problem = &forecast.avalanche_problems[0];
+        assert_eq!(problem.kind, ProblemKind::WindSlab);
+        assert!(problem.is_primary);
+        assert_eq!(
+            problem
+                .aspect_elevation
+                .keys()
+                .map(|band_id| band_id.as_str())
+                .collect::<Vec<_>>(),
+            ["high-alpine", "alpine"]
+        );
+        assert!(forecast
+            .description
+            .get(&"en".parse().unwrap())
+            .unwrap()
+            .contains("Fresh wind slabs"));
+    }
+
+    #[test]
+    fn test_import_v5_xml() {
+        let forecasts = import_fixture("bulletin.v5.xml");
+        assert_eq!(forecasts.len(), 1);
+        let forecast = &forecasts[0];
+        assert_eq!(forecast.forecaster.name, "Imported");
+        assert_eq!(
+            forecast.forecaster.organisation.as_deref(),
+            Some("Example Avalanche Center")
+        );
+        assert_eq!(
+            rating(
+                forecast,
+                HazardRatingKind::ElevationSpecific("high-alpine".into())
+            ),
+            Some(HazardRatingValue::High)
+        );
+        // Below the treeline, which is the upper bound of the sub-alpine band.
+        assert_eq!(
+            rating(
+                forecast,
+                HazardRatingKind::ElevationSpecific("sub-alpine".into())
+            ),
+            Some(HazardRatingValue::Moderate)
+        );
+        let problem = &forecast.avalanche_problems[0];
+        assert_eq!(problem.kind, ProblemKind::StormSlab);
+        assert_eq!(problem.aspect_elevation.len(), 3);
+        assert_eq!(
+            problem.aspect_elevation[&ElevationBandId::from("alpine")]
+                .aspects
+                .len(),
+            3
+        );
+    }
+
+    #[test]
+    fn test_export_round_trip() {
+        let forecast = import_fixture("bulletin.v6.json").remove(0);
+        let language = "en".parse().unwrap();
+        let document = export(
+            &forecast,
+            &ExportOptions {
+                bulletin_id: "Gudauri_2024-01-10T09:00_JD.en.caaml",
+                region_id: "GE-01",
+                region_name: "Gudauri",
+                language: &language,
+            },
+        )
+        .unwrap();
+        let bulletin = &document["bulletins"][0];
+        assert_eq!(bulletin["regions"][0]["regionID"], "GE-01");
+        assert_eq!(bulletin["dangerRatings"].as_array().unwrap().len(), 3);
+
+        let elevation_bands = elevation_bands();
+        let options = ImportOptions {
+            region: Some("GE-01"),
+            ..import_options(&elevation_bands)
+        };
+        let exported = serde_json::to_vec(&document).unwrap();
+        let reimported = import(&exported, &options).unwrap().remove(0);
+        assert_eq!(reimported.time, forecast.time);
+        assert_eq!(reimported.valid_for, forecast.valid_for);
+        assert_eq!(reimported.forecaster.name, forecast.forecaster.name);
+        for (kind, hazard_rating) in &forecast.hazard_ratings {
+            assert_eq!(
+                rating(&reimported, kind.clone()),
+                hazard_rating.value,
+                "{kind:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_elevation_includes() {
+        let range = ElevationRange {
+            upper: Some(2800),
+            lower: Some(1800),
+        };
+        let above = |metres| Elevation {
+            lower: Some(Bound::Metres(metres)),
+            upper: None,
+        };
+        assert!(super::elevation_includes(above(2000), &range, None).unwrap());
+        assert!(!super::elevation_includes(above(2500), &range, None).unwrap());
+        let below_treeline = Elevation {
+            lower: None,
+            upper: Some(Bound::Treeline),
+        };
+        assert!(super::elevation_includes(below_treeline, &range, None).is_err());
+        assert!(super::elevation_includes(below_treeline, &range, Some(2800)).unwrap());
+    }
+}
@@ -0,0 +1,137 @@
+//! The [EAWS matrix](https://www.avalanches.org/standards/eaws-matrix/), which suggests an
+//! avalanche danger level from the snowpack stability, the frequency of that stability, and the
+//! avalanche size. The suggested level is a starting point for the forecaster's assessment, not a
+//! replacement for it.
+
+use std::str::FromStr;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{HazardRatingValue, Size};
+
+/// Snowpack stability class.
+#[derive(Copy, Clone, Debug, Deserialize, Serialize, PartialEq, Eq, Hash)]
+#[serde(rename_all = "kebab-case")]
+pub enum Stability {
+    VeryPoor,
+    Poor,
+    Fair,
+    Good,
+}
+
+impl FromStr for Stability {
+    type Err = serde_json::Error;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        serde_json::from_str(s)
+    }
+}
+
+/// How frequently the snowpack stability class occurs in the terrain.
+#[derive(Copy, Clone, Debug, Deserialize, Serialize, PartialEq, Eq, Hash)]
+#[serde(rename_all = "kebab-case")]
+pub enum Frequency {
+    Many,
+    Some,
+    AFew,
+    NearlyNone,
+}
+
+impl FromStr for Frequency {
+    type Err = serde_json::Error;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        serde_json::from_str(s)
+    }
+}
+
+/// Danger levels indexed by stability (very poor, poor, fair), frequency (many, some, a few) and
+/// avalanche size (1 to 5).
+const MATRIX: [[[HazardRatingValue; 5]; 3]; 3] = {
+    use HazardRatingValue::{Considerable as C, Extreme as E, High as H, Low as L, Moderate as M};
+    [
+        // Very poor
+        [[M, C, H, E, E], [M, C, C, H, E], [L, M, C, C, H]],
+        // Poor
+        [[M, C, H, H, E], [L, M, C, H, H], [L, M, M, C, H]],
+        // Fair
+        [[L, M, C, C, H], [L, L, M, C, C], [L, L, M, M, C]],
+    ]
+};
+
+/// The danger level suggested by the EAWS matrix. When the stability is good, or triggering
+/// locations are nearly non-existent, the suggested danger level is always
+/// [`HazardRatingValue::Low`].
+pub fn danger_level(stability: Stability, frequency: Frequency, size: Size) -> HazardRatingValue {
+    let stability_index = match stability {
+        Stability::VeryPoor => 0,
+        Stability::Poor => 1,
+        Stability::Fair => 2,
+        Stability::Good => return HazardRatingValue::Low,
+    };
+    let frequency_index = match frequency {
+        Frequency::Many => 0,
+        Frequency::Some => 1,
+        Frequency::AFew => 2,
+        Frequency::NearlyNone => return HazardRatingValue::Low,
+    };
+    let size_index = size as usize - 1;
+    MATRIX[stability_index][frequency_index][size_index]
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{HazardRatingValue, Size};
+
+    use super::{danger_level, Frequency, Stability};
+
+    #[test]
+    fn test_danger_level() {
+        assert_eq!(
+            danger_level(Stability::VeryPoor, Frequency::Many, Size::Four),
+            HazardRatingValue::Extreme
+        );
+        assert_eq!(
+            danger_level(Stability::Poor, Frequency::Some, Size::Three),
+            HazardRatingValue::Considerable
+        );
+        assert_eq!(
+            danger_level(Stability::Fair, Frequency::AFew, Size::One),
+            HazardRatingValue::Low
+        );
+        assert_eq!(
+            danger_level(Stability::Good, Frequency::Many, Size::Five),
+            HazardRatingValue::Low
+        );
+        assert_eq!(
+            danger_level(Stability::VeryPoor, Frequency::NearlyNone, Size::Five),
+            HazardRatingValue::Low
+        );
+    }
+
+    /// The danger level never decreases when any of the inputs become more severe.
+    #[test]
+    fn test_danger_level_monotonic() {
+        let stabilities = [Stability::Fair, Stability::Poor, Stability::VeryPoor];
+        let frequencies = [Frequency::AFew, Frequency::Some, Frequency::Many];
+        let sizes = [Size::One, Size::Two, Size::Three, Size::Four, Size::Five];
+        let level = |s: usize, f: usize, z: usize| {
+            danger_level(stabilities[s], frequencies[f], sizes[z]) as u8
+        };
+        for s in 0..3 {
+            for f in 0..3 {
+                for z in 0..5 {
+                    if s < 2 {
+                        assert!(level(s, f, z) <= level(s + 1, f, z));
+                    }
+                    if f < 2 {
+                        assert!(level(s, f, z) <= level(s, f + 1, z));
+                    }
+                    if z < 4 {
+                        assert!(level(s, f, z) <= level(s, f, z + 1));
+                    }
+                }
+            }
+        }
+    }
+}
@@ -1,21 +1,34 @@
+// Parsing errors include the cell and its value, see `ParseCellError`.
+#![allow(clippy::result_large_err)]
+
 use std::{
     collections::HashMap, fmt::Display, io::Cursor, iter::repeat, num::ParseIntError, ops::Deref,
     str::FromStr,
 };
 
+pub mod caaml;
+pub mod eaws_matrix;
 pub mod options;
 pub mod position;
+pub mod registry;
 mod serde;
+pub mod source;
+mod warnings;
+pub mod writer;
 
 use ::serde::{Deserialize, Serialize};
-use calamine::{open_workbook_auto_from_rs, DataType, Reader, Sheets};
+use calamine::{open_workbook_auto_from_rs, DataType, Sheets};
 use eyre::{Context, ContextCompat, OptionExt};
 use indexmap::{IndexMap, IndexSet};
 use once_cell::sync::Lazy;
 use options::{HazardRatingInput, Options, TranslatedString};
-use position::SheetCellPosition;
+use position::{CellReference, CellReferenceResolveError};
+use source::CellSource;
 use time::{Date, Month, OffsetDateTime, PrimitiveDateTime, Time};
 use utils::serde::duration_seconds;
+use warnings::Warnings;
+
+pub use warnings::ParseWarning;
 
 static EXCEL_EPOCH: Lazy<PrimitiveDateTime> = Lazy::new(|| {
     Date::from_calendar_date(1899, Month::December, 30)
@@ -30,12 +43,13 @@ pub enum ParseCellErrorKind {
     IncorrectDataType,
     CellMissing,
     SheetMissing,
+    UnresolvedReference(CellReferenceResolveError),
     FromStr(Box<dyn std::error::Error + Send + Sync + 'static>),
 }
 
 pub struct ParseCellError {
     kind: ParseCellErrorKind,
-    position: SheetCellPosition,
+    position: CellReference,
     value: Option<DataType>,
     context: Option<Box<dyn Fn() -> String + Send + Sync + 'static>>,
 }
@@ -52,7 +66,7 @@ impl std::fmt::Debug for ParseCellError {
 }
 
 impl ParseCellError {
-    pub fn incorrect_data_type(position: SheetCellPosition, value: DataType) -> Self {
+    pub fn incorrect_data_type(position: CellReference, value: DataType) -> Self {
         Self {
             kind: ParseCellErrorKind::IncorrectDataType,
             position,
@@ -61,7 +75,7 @@ impl ParseCellError {
         }
     }
 
-    pub fn cell_missing(position: SheetCellPosition) -> Self {
+    pub fn cell_missing(position: CellReference) -> Self {
         Self {
             kind: ParseCellErrorKind::CellMissing,
             position,
@@ -70,7 +84,7 @@ impl ParseCellError {
         }
     }
 
-    pub fn sheet_missing(position: SheetCellPosition) -> Self {
+    pub fn sheet_missing(position: CellReference) -> Self {
         Self {
             kind: ParseCellErrorKind::SheetMissing,
             position,
@@ -79,7 +93,16 @@ impl ParseCellError {
         }
     }
 
-    pub fn calamine(position: SheetCellPosition, error: calamine::Error) -> Self {
+    pub fn unresolved_reference(position: CellReference, error: CellReferenceResolveError) -> Self {
+        Self {
+            kind: ParseCellErrorKind::UnresolvedReference(error),
+            position,
+            value: None,
+            context: None,
+        }
+    }
+
+    pub fn calamine(position: CellReference, error: calamine::Error) -> Self {
         Self {
             kind: ParseCellErrorKind::Calamine(error),
             position,
@@ -89,7 +112,7 @@ impl ParseCellError {
     }
 
     pub fn from_str_error<E: std::error::Error + Send + Sync + 'static>(
-        position: SheetCellPosition,
+        position: CellReference,
         value: DataType,
         error: E,
     ) -> Self {
@@ -111,7 +134,7 @@ impl ParseCellWithContext for ParseCellError {
         mut self,
         context: Box<dyn Fn() -> String + Send + Sync + 'static>,
     ) -> Self {
-        self.context = Some(context.into());
+        self.context = Some(context);
         self
     }
 }
@@ -129,6 +152,7 @@ impl std::error::Error for ParseCellError {
     fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
         match &self.kind {
             ParseCellErrorKind::Calamine(error) => Some(error),
+            ParseCellErrorKind::UnresolvedReference(error) => Some(error),
             _ => None,
         }
     }
@@ -144,14 +168,9 @@ impl Display for ParseCellError {
 
         match &self.kind {
             ParseCellErrorKind::IncorrectDataType => write!(f, "Incorrect data type"),
-            ParseCellErrorKind::CellMissing => write!(
-                f,
-                "The cell at position {0} does not exist",
-                self.position.position
-            ),
-            ParseCellErrorKind::SheetMissing => {
-                write!(f, "The sheet {0} does not exist", self.position.sheet)
-            }
+            ParseCellErrorKind::CellMissing => write!(f, "The cell does not exist"),
+            ParseCellErrorKind::SheetMissing => write!(f, "The sheet does not exist"),
+            ParseCellErrorKind::UnresolvedReference(error) => write!(f, "{error}"),
             ParseCellErrorKind::Calamine(error) => write!(f, "{error}"),
             ParseCellErrorKind::FromStr(error) => write!(f, "{error}"),
         }?;
@@ -164,7 +183,7 @@ impl Display for ParseCellError {
     }
 }
 
-#[derive(Debug, Serialize, Deserialize, Copy, Clone, PartialEq)]
+#[derive(Debug, Serialize, Deserialize, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub struct Version {
     pub major: u8,
     pub minor: u8,
@@ -297,7 +316,7 @@ impl Serialize for HazardRatingKind {
     {
         match self {
             HazardRatingKind::Overall => serializer.serialize_str("overall"),
-            HazardRatingKind::ElevationSpecific(e) => serializer.serialize_str(&*e),
+            HazardRatingKind::ElevationSpecific(e) => serializer.serialize_str(e),
         }
     }
 }
@@ -305,7 +324,7 @@ impl Serialize for HazardRatingKind {
 impl Display for HazardRatingKind {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
-            HazardRatingKind::ElevationSpecific(band_id) => f.write_str(&*band_id),
+            HazardRatingKind::ElevationSpecific(band_id) => f.write_str(band_id),
             HazardRatingKind::Overall => f.write_str("overall"),
         }
     }
@@ -394,9 +413,15 @@ pub struct AvalancheProblem {
     pub sensitivity: Option<Sensitivity>,
     #[serde(default)]
     pub description: HashMap<unic_langid::LanguageIdentifier, String>,
+    /// Whether this is the primary (dominant) problem of the forecast, which is always the first
+    /// problem in [`Forecast::avalanche_problems`], see [`Options::primary_avalanche_problem`].
+    #[serde(default)]
+    pub is_primary: bool,
 }
 
-#[derive(Copy, Clone, Debug, Deserialize, Serialize)]
+#[derive(
+    Copy, Clone, Debug, Deserialize, Serialize, PartialEq, Eq, Hash, enum_iterator::Sequence,
+)]
 #[serde(rename_all = "kebab-case")]
 pub enum ProblemKind {
     LooseDry,
@@ -588,36 +613,40 @@ pub struct Forecast {
     pub elevation_bands: IndexMap<ElevationBandId, ElevationRange>,
 }
 
+impl Forecast {
+    /// The primary (dominant) avalanche problem, see [`AvalancheProblem::is_primary`].
+    pub fn primary_avalanche_problem(&self) -> Option<&AvalancheProblem> {
+        self.avalanche_problems
+            .iter()
+            .find(|problem| problem.is_primary)
+    }
+}
+
 #[derive(Debug, Serialize, Clone, Deserialize)]
 pub struct Forecaster {
     pub name: String,
     pub organisation: Option<String>,
 }
 
-fn get_cell_value<RS>(
-    sheets: &mut Sheets<RS>,
-    position: &SheetCellPosition,
+fn get_cell_value<S>(
+    sheets: &mut S,
+    position: &CellReference,
 ) -> std::result::Result<DataType, ParseCellError>
 where
-    RS: std::io::Read + std::io::Seek,
+    S: CellSource,
 {
-    let sheet = sheets
-        .worksheet_range(&position.sheet)
-        .ok_or_else(|| ParseCellError::sheet_missing(position.clone()))?
-        .map_err(|error| ParseCellError::calamine(position.clone(), error))?;
-
-    Ok(sheet
-        .get_value(position.position.into())
-        .ok_or_else(|| ParseCellError::cell_missing(position.clone()))?
-        .clone())
+    let resolved = position
+        .resolve(sheets.defined_names())
+        .map_err(|error| ParseCellError::unresolved_reference(position.clone(), error))?;
+    sheets.cell_value(&resolved)
 }
 
-fn get_cell_value_bool<RS>(
-    sheets: &mut Sheets<RS>,
-    position: &SheetCellPosition,
+fn get_cell_value_bool<S>(
+    sheets: &mut S,
+    position: &CellReference,
 ) -> std::result::Result<bool, ParseCellError>
 where
-    RS: std::io::Read + std::io::Seek,
+    S: CellSource,
 {
     let value = get_cell_value(sheets, position)?;
     value
@@ -625,12 +654,12 @@ where
         .ok_or_else(|| ParseCellError::incorrect_data_type(position.clone(), value))
 }
 
-fn get_cell_value_string<T, RS>(
-    sheets: &mut Sheets<RS>,
-    position: &SheetCellPosition,
+fn get_cell_value_string<T, S>(
+    sheets: &mut S,
+    position: &CellReference,
 ) -> Result<Option<T>, ParseCellError>
 where
-    RS: std::io::Read + std::io::Seek,
+    S: CellSource,
     T: FromStr,
     <T as FromStr>::Err: std::error::Error + Send + Sync + 'static,
 {
@@ -660,12 +689,12 @@ fn decimal_day_to_time(time_day: f64) -> std::result::Result<Time, time::error::
     Time::from_hms_milli(hour as u8, minute as u8, second as u8, millisecond as u16)
 }
 
-fn get_cell_value_time<RS>(
-    sheets: &mut Sheets<RS>,
-    position: &SheetCellPosition,
+fn get_cell_value_time<S>(
+    sheets: &mut S,
+    position: &CellReference,
 ) -> std::result::Result<Time, ParseCellError>
 where
-    RS: std::io::Read + std::io::Seek,
+    S: CellSource,
 {
     let value = get_cell_value(sheets, position)?;
 
@@ -680,12 +709,12 @@ where
     }
 }
 
-fn get_cell_value_datetime<RS>(
-    sheets: &mut Sheets<RS>,
-    position: &SheetCellPosition,
+fn get_cell_value_datetime<S>(
+    sheets: &mut S,
+    position: &CellReference,
 ) -> std::result::Result<PrimitiveDateTime, ParseCellError>
 where
-    RS: std::io::Read + std::io::Seek,
+    S: CellSource,
 {
     let value = get_cell_value(sheets, position)?;
 
@@ -706,11 +735,19 @@ where
     }
 }
 
-fn required_value_missing<V: std::fmt::Display>(
-    value: V,
-    position: SheetCellPosition,
-) -> eyre::Error {
-    eyre::eyre!("Required value {value} missing from position {position}")
+#[derive(Debug, thiserror::Error)]
+#[error("Required value {value} missing from position {position}")]
+pub struct RequiredValueMissing {
+    pub value: String,
+    pub position: CellReference,
+}
+
+fn required_value_missing<V: std::fmt::Display>(value: V, position: CellReference) -> eyre::Error {
+    RequiredValueMissing {
+        value: value.to_string(),
+        position,
+    }
+    .into()
 }
 
 fn unable_to_map_value<V: std::fmt::Display, M: std::fmt::Display>(
@@ -720,8 +757,8 @@ fn unable_to_map_value<V: std::fmt::Display, M: std::fmt::Display>(
     eyre::eyre!("Unable to use map {map} to find a valid variant equal to value {value}")
 }
 
-fn map_translated_string<RS: std::io::Seek + std::io::Read>(
-    sheets: &mut Sheets<RS>,
+fn map_translated_string<S: CellSource>(
+    sheets: &mut S,
     translated_string: &TranslatedString,
     form_langauge: &unic_langid::LanguageIdentifier,
 ) -> Result<HashMap<unic_langid::LanguageIdentifier, String>, ParseCellError> {
@@ -738,7 +775,7 @@ fn map_translated_string<RS: std::io::Seek + std::io::Read>(
         }
 
         let position = translated_string.root.clone() + translation.position;
-        let value = if let Some(value) = get_cell_value_string::<String, RS>(sheets, &position)? {
+        let value = if let Some(value) = get_cell_value_string::<String, S>(sheets, &position)? {
             if value.is_empty() {
                 continue;
             } else {
@@ -754,21 +791,58 @@ fn map_translated_string<RS: std::io::Seek + std::io::Read>(
     Ok(translations)
 }
 
+/// Parse a forecast spreadsheet file, in any of the formats supported by [`calamine`] (xlsx, xls,
+/// xlsb or ods).
 pub fn parse_excel_spreadsheet(
     spreadsheet_bytes: &[u8],
     options: &Options,
 ) -> eyre::Result<Forecast> {
+    let mut sheets = open_spreadsheet(spreadsheet_bytes)?;
+    parse_sheets(&mut sheets, options, &mut Warnings::strict())
+}
+
+/// Parse the spreadsheet like [`parse_excel_spreadsheet`], but instead of failing when a field
+/// can't be parsed, skip the field and return a warning describing the problem. The template
+/// version, form language, area, time and validity period are still required.
+pub fn parse_excel_spreadsheet_lenient(
+    spreadsheet_bytes: &[u8],
+    options: &Options,
+) -> eyre::Result<(Forecast, Vec<ParseWarning>)> {
+    let mut sheets = open_spreadsheet(spreadsheet_bytes)?;
+    let mut warnings = Warnings::lenient();
+    let forecast = parse_sheets(&mut sheets, options, &mut warnings)?;
+    Ok((forecast, warnings.into_vec()))
+}
+
+/// Parse a forecast from the cell values of a [`CellSource`], such as [`source::ValueSheets`]
+/// fetched from the Google Sheets API.
+pub fn parse_cell_source<S: CellSource>(
+    source: &mut S,
+    options: &Options,
+) -> eyre::Result<Forecast> {
+    parse_sheets(source, options, &mut Warnings::strict())
+}
+
+fn open_spreadsheet(spreadsheet_bytes: &[u8]) -> eyre::Result<Sheets<Cursor<&[u8]>>> {
     let cursor = Cursor::new(spreadsheet_bytes);
-    // open_workbook_auto_from_rs(data)
-    let mut sheets: Sheets<_> = open_workbook_auto_from_rs(cursor)?;
+    Ok(open_workbook_auto_from_rs(cursor)?)
+}
 
-    let template_version: Version = get_cell_value_string(&mut sheets, &options.template_version)?
+fn parse_sheets<S>(
+    sheets: &mut S,
+    options: &Options,
+    warnings: &mut Warnings,
+) -> eyre::Result<Forecast>
+where
+    S: CellSource,
+{
+    let template_version: Version = get_cell_value_string(sheets, &options.template_version)?
         .ok_or_else(|| {
             required_value_missing("template_version", options.template_version.clone())
         })?;
 
     let form_language_name: String =
-        get_cell_value_string(&mut sheets, &options.form_language.position)?.ok_or_else(|| {
+        get_cell_value_string(sheets, &options.form_language.position)?.ok_or_else(|| {
             required_value_missing("form_language", options.form_language.position.clone())
         })?;
     let form_language = options
@@ -779,7 +853,7 @@ pub fn parse_excel_spreadsheet(
             "form_language.language_map is missing mapping for language {form_language_name}"
         ))?;
 
-    let area_name: String = get_cell_value_string(&mut sheets, &options.area.position)?
+    let area_name: String = get_cell_value_string(sheets, &options.area.position)?
         .ok_or_else(|| required_value_missing("area", options.area.position.clone()))?;
     let area = options
         .area
@@ -789,11 +863,25 @@ pub fn parse_excel_spreadsheet(
         .to_owned();
 
     let forecaster = {
-        let name =
-            get_cell_value_string(&mut sheets, &options.forecaster.name)?.ok_or_else(|| {
-                required_value_missing("forecaster.name", options.forecaster.name.clone())
-            })?;
-        let organisation = get_cell_value_string(&mut sheets, &options.forecaster.organisation)?;
+        let name = warnings.field(
+            "forecaster.name",
+            || {
+                get_cell_value_string(sheets, &options.forecaster.name)?.ok_or_else(|| {
+                    required_value_missing("forecaster.name", options.forecaster.name.clone())
+                })
+            },
+            String::new,
+        )?;
+        let organisation = warnings.field(
+            "forecaster.organisation",
+            || {
+                Ok(get_cell_value_string(
+                    sheets,
+                    &options.forecaster.organisation,
+                )?)
+            },
+            || None,
+        )?;
         Forecaster { name, organisation }
     };
 
@@ -803,8 +891,8 @@ pub fn parse_excel_spreadsheet(
                 date: date_position,
                 time: time_position,
             } => {
-                let date = get_cell_value_datetime(&mut sheets, &date_position)?;
-                let time = get_cell_value_time(&mut sheets, &time_position)?;
+                let date = get_cell_value_datetime(sheets, date_position)?;
+                let time = get_cell_value_time(sheets, time_position)?;
                 let tz = &options
                     .area_definitions
                     .get(&area)
@@ -819,30 +907,31 @@ pub fn parse_excel_spreadsheet(
         }
     };
 
-    let recent_observations: HashMap<unic_langid::LanguageIdentifier, String> = Option::transpose(
-        options
-            .recent_observations
-            .as_ref()
-            .map(|translated_string| {
-                map_translated_string(&mut sheets, translated_string, &form_language)
-            }),
-    )?
-    .unwrap_or_default();
-
-    let forecast_changes: HashMap<unic_langid::LanguageIdentifier, String> =
-        Option::transpose(options.forecast_changes.as_ref().map(|translated_string| {
-            map_translated_string(&mut sheets, translated_string, &form_language)
-        }))?
-        .unwrap_or_default();
-
-    let weather_forecast: HashMap<unic_langid::LanguageIdentifier, String> =
-        Option::transpose(options.weather_forecast.as_ref().map(|translated_string| {
-            map_translated_string(&mut sheets, translated_string, &form_language)
-        }))?
-        .unwrap_or_default();
+    let mut translated_string =
+        |field: &'static str,
+         translated_string: &Option<TranslatedString>|
+         -> eyre::Result<HashMap<unic_langid::LanguageIdentifier, String>> {
+            warnings.field(
+                field,
+                || {
+                    Ok(
+                        Option::transpose(translated_string.as_ref().map(|translated_string| {
+                            map_translated_string(sheets, translated_string, form_language)
+                        }))?
+                        .unwrap_or_default(),
+                    )
+                },
+                HashMap::new,
+            )
+        };
+    let recent_observations =
+        translated_string("recent_observations", &options.recent_observations)?;
+    let forecast_changes = translated_string("forecast_changes", &options.forecast_changes)?;
+    let weather_forecast = translated_string("weather_forecast", &options.weather_forecast)?;
+    let description = translated_string("description", &options.description)?;
 
     let valid_for = {
-        let value = get_cell_value(&mut sheets, &options.valid_for)?;
+        let value = get_cell_value(sheets, &options.valid_for)?;
         let days: f64 = match value {
             DataType::Int(i) => i as f64,
             DataType::Float(f) => f,
@@ -864,61 +953,157 @@ pub fn parse_excel_spreadsheet(
         time::Duration::milliseconds(ms as i64)
     };
 
-    let description: HashMap<unic_langid::LanguageIdentifier, String> =
-        Option::transpose(options.description.as_ref().map(|translated_string| {
-            map_translated_string(&mut sheets, translated_string, &form_language)
-        }))?
-        .unwrap_or_default();
+    let mut hazard_ratings = IndexMap::with_capacity(options.hazard_ratings.inputs.len());
+    for (kind, input) in &options.hazard_ratings.inputs {
+        let hazard_rating = warnings.field(
+            format!("hazard_ratings.{kind}"),
+            || {
+                extract_hazard_rating(kind, input, sheets, options)
+                    .map(Some)
+                    .wrap_err_with(|| format!("error extracting hazard rating {kind}: {input:?}"))
+            },
+            || None,
+        )?;
+        if let Some(hazard_rating) = hazard_rating {
+            hazard_ratings.insert(kind.clone(), hazard_rating);
+        }
+    }
 
-    let hazard_ratings = options
-        .hazard_ratings
-        .inputs
-        .iter()
-        .map(|(kind, input)| {
-            let kind = kind.clone();
-            match extract_hazard_rating(&kind, input, &mut sheets, options) {
-                Ok(hazard_rating) => Ok((kind, hazard_rating)),
-                Err(error) => Err(error)
-                    .wrap_err_with(|| format!("error extracting hazard rating {kind}: {input:?}")),
-            }
-        })
-        .collect::<eyre::Result<_>>()?;
+    let mut avalanche_problems: Vec<(usize, AvalancheProblem)> =
+        Vec::with_capacity(options.avalanche_problems.len());
+    for (i, problem) in options.avalanche_problems.iter().enumerate() {
+        let problem = warnings.field(
+            format!("avalanche_problems[{i}]"),
+            || {
+                extract_avalanch_problem(problem, options, sheets, form_language)
+                    .wrap_err_with(|| format!("Avalanche problem {i}"))
+            },
+            || None,
+        )?;
+        avalanche_problems.extend(problem.map(|problem| (i, problem)));
+    }
 
-    let avalanche_problems: Vec<AvalancheProblem> = options
-        .avalanche_problems
-        .iter()
-        .enumerate()
-        .map(|(i, problem)| {
-            extract_avalanch_problem(problem, options, &mut sheets, &form_language)
-                .wrap_err_with(|| format!("Avalanche problem {i}"))
-        })
-        .filter_map(std::result::Result::transpose)
-        .collect::<eyre::Result<_>>()?;
+    let primary_avalanche_problem = match &options.primary_avalanche_problem {
+        Some(position) => warnings.field(
+            "primary_avalanche_problem",
+            || extract_primary_avalanche_problem(sheets, position, options),
+            || None,
+        )?,
+        None => None,
+    };
+    let avalanche_problems =
+        order_avalanche_problems(avalanche_problems, primary_avalanche_problem);
 
-    let mut elevation_band_boundaries: Vec<i64> = get_cell_value(
-        &mut sheets,
-        &options.area.elevation_band_boundaries.position,
-    )
-    .context("Error getting elevation band boundaries value")?
-    .to_string()
-    .replace('m', "")
-    .split(",")
-    .map(|altitude| {
-        altitude
+    let elevation_bands = warnings.field(
+        "elevation_bands",
+        || extract_elevation_bands(sheets, options),
+        IndexMap::new,
+    )?;
+
+    Ok(Forecast {
+        template_version,
+        area,
+        forecaster,
+        time,
+        recent_observations,
+        forecast_changes,
+        weather_forecast,
+        valid_for,
+        description,
+        hazard_ratings,
+        avalanche_problems,
+        elevation_bands,
+    })
+}
+
+/// The index in [`Options::avalanche_problems`] of the primary avalanche problem, or `None` if
+/// the cell is empty.
+fn extract_primary_avalanche_problem<S>(
+    sheets: &mut S,
+    position: &CellReference,
+    options: &Options,
+) -> eyre::Result<Option<usize>>
+where
+    S: CellSource,
+{
+    let number: i64 = match get_cell_value(sheets, position)? {
+        DataType::Int(n) => n,
+        DataType::Float(f) => f as i64,
+        DataType::String(value) if value.trim().is_empty() => return Ok(None),
+        DataType::String(value) => value
             .trim()
             .parse()
-            .wrap_err_with(|| format!("Error parsing elevation band boundary {altitude}"))
-    })
-    .collect::<eyre::Result<_>>()?;
+            .wrap_err_with(|| format!("Unable to parse {value:?} as a number"))?,
+        DataType::Empty => return Ok(None),
+        unexpected => return Err(eyre::eyre!("unexpected data type {unexpected:?}")),
+    };
+    let count = options.avalanche_problems.len();
+    match usize::try_from(number) {
+        Ok(number) if (1..=count).contains(&number) => Ok(Some(number - 1)),
+        _ => Err(eyre::eyre!(
+            "Primary avalanche problem {number} is not between 1 and {count}"
+        )),
+    }
+}
+
+/// Designate the primary avalanche problem and move it to the start, the other problems keep the
+/// order they were entered in. Each problem is paired with its index in
+/// [`Options::avalanche_problems`], the problem with the `primary` index is the primary problem,
+/// otherwise (or if that problem isn't enabled) the first problem is.
+fn order_avalanche_problems(
+    problems: Vec<(usize, AvalancheProblem)>,
+    primary: Option<usize>,
+) -> Vec<AvalancheProblem> {
+    let primary_position = primary
+        .and_then(|primary| problems.iter().position(|(i, _)| *i == primary))
+        .unwrap_or(0);
+    let mut problems: Vec<AvalancheProblem> =
+        problems.into_iter().map(|(_, problem)| problem).collect();
+    if primary_position < problems.len() {
+        let mut primary = problems.remove(primary_position);
+        primary.is_primary = true;
+        problems.insert(0, primary);
+    }
+    problems
+}
+
+fn extract_elevation_bands<S>(
+    sheets: &mut S,
+    options: &Options,
+) -> eyre::Result<IndexMap<ElevationBandId, ElevationRange>>
+where
+    S: CellSource,
+{
+    let mut elevation_band_boundaries: Vec<i64> =
+        get_cell_value(sheets, &options.area.elevation_band_boundaries.position)
+            .context("Error getting elevation band boundaries value")?
+            .to_string()
+            .replace('m', "")
+            .split(",")
+            .map(|altitude| {
+                altitude
+                    .trim()
+                    .parse()
+                    .wrap_err_with(|| format!("Error parsing elevation band boundary {altitude}"))
+            })
+            .collect::<eyre::Result<_>>()?;
     if options.area.elevation_band_boundaries.reverse {
         elevation_band_boundaries.reverse();
     }
+    elevation_bands_from_boundaries(options, &elevation_band_boundaries)
+}
 
+/// The elevation bands of the schema in `options`, split at the `boundaries` (in metres, from
+/// lowest to highest).
+pub fn elevation_bands_from_boundaries(
+    options: &Options,
+    boundaries: &[i64],
+) -> eyre::Result<IndexMap<ElevationBandId, ElevationRange>> {
     let elevation_band_windows: Vec<Option<i64>> = std::iter::once(None)
-        .chain(elevation_band_boundaries.into_iter().map(Some))
+        .chain(boundaries.iter().copied().map(Some))
         .chain(std::iter::once(None))
         .collect();
-    let elevation_bands = elevation_band_windows
+    elevation_band_windows
         .windows(2)
         .enumerate()
         .map(|(i, window)| {
@@ -934,32 +1119,17 @@ pub fn parse_excel_spreadsheet(
 
             Ok((elevation_band_id.clone(), range))
         })
-        .collect::<eyre::Result<_>>()?;
-
-    Ok(Forecast {
-        template_version,
-        area,
-        forecaster,
-        time,
-        recent_observations,
-        forecast_changes,
-        weather_forecast,
-        valid_for,
-        description,
-        hazard_ratings,
-        avalanche_problems,
-        elevation_bands,
-    })
+        .collect()
 }
 
-fn extract_avalanch_problem<RS>(
+fn extract_avalanch_problem<S>(
     problem: &options::AvalancheProblem,
     options: &Options,
-    sheets: &mut Sheets<RS>,
+    sheets: &mut S,
     form_langauge: &unic_langid::LanguageIdentifier,
 ) -> eyre::Result<Option<AvalancheProblem>>
 where
-    RS: std::io::Read + std::io::Seek,
+    S: CellSource,
 {
     let enabled_cell = problem.root.clone() + problem.enabled;
     let enabled = get_cell_value_bool(sheets, &enabled_cell)?;
@@ -971,7 +1141,7 @@ where
 
     let kind_cell = problem.root.clone() + problem.kind;
     let value: String = get_cell_value_string(sheets, &kind_cell)
-        .wrap_err_with(Box::new(move || format!("kind")))?
+        .wrap_err_with(Box::new(move || "kind".to_string()))?
         .ok_or_else(|| required_value_missing("avalanche_problem.kind", kind_cell.clone()))?;
 
     let kind = options
@@ -994,10 +1164,7 @@ where
             }
 
             let aspects_cell = problem.root.clone() + aspect_elevation.aspects;
-            let value: String = match get_cell_value_string(sheets, &aspects_cell)? {
-                Some(value) => value,
-                None => String::new(),
-            };
+            let value: String = get_cell_value_string(sheets, &aspects_cell)?.unwrap_or_default();
             let aspects = parse_aspects(&value).map_err(|error| {
                 ParseCellError::from_str_error(aspects_cell.clone(), DataType::String(value), error)
             })?;
@@ -1131,17 +1298,18 @@ where
         sensitivity,
         time_of_day,
         description,
+        is_primary: false,
     }))
 }
 
-fn extract_hazard_rating<RS>(
+fn extract_hazard_rating<S>(
     kind: &HazardRatingKind,
     input: &HazardRatingInput,
-    sheets: &mut Sheets<RS>,
+    sheets: &mut S,
     options: &Options,
 ) -> eyre::Result<HazardRating>
 where
-    RS: std::io::Read + std::io::Seek,
+    S: CellSource,
 {
     if let HazardRatingKind::ElevationSpecific(elevation_band) = kind {
         if !options.elevation_bands.contains(elevation_band) {
@@ -1210,11 +1378,13 @@ where
 mod tests {
     use std::path::Path;
 
-    const CRATE_DIR: &'static str = env!("CARGO_MANIFEST_DIR");
+    const CRATE_DIR: &str = env!("CARGO_MANIFEST_DIR");
 
     use crate::options::Options;
 
-    use super::parse_excel_spreadsheet;
+    use super::{
+        order_avalanche_problems, parse_excel_spreadsheet, parse_excel_spreadsheet_lenient,
+    };
 
     #[test]
     fn test_parse_excel_spreadsheet_gudauri() {
@@ -1249,4 +1419,116 @@ mod tests {
             insta::assert_json_snapshot!(&forecast);
         });
     }
+
+    #[test]
+    fn test_parse_ods_spreadsheet() {
+        let fixtures = Path::new(CRATE_DIR).join("fixtures");
+        let options: Options = serde_json::from_str(
+            &std::fs::read_to_string(fixtures.join("options/options.gudauri.0.3.1.json")).unwrap(),
+        )
+        .unwrap();
+        let xlsx_bytes =
+            std::fs::read(fixtures.join("forecasts/Gudauri_2023_02_07T19 00_LS.xlsx")).unwrap();
+        let ods_bytes =
+            std::fs::read(fixtures.join("forecasts/Gudauri_2023_02_07T19 00_LS.ods")).unwrap();
+        let xlsx_forecast = parse_excel_spreadsheet(&xlsx_bytes, &options).unwrap();
+        let ods_forecast = parse_excel_spreadsheet(&ods_bytes, &options).unwrap();
+        assert_eq!(
+            serde_json::to_value(&ods_forecast).unwrap(),
+            serde_json::to_value(&xlsx_forecast).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_parse_excel_spreadsheet_lenient() {
+        let fixtures = Path::new(CRATE_DIR).join("fixtures");
+        let spreadsheet_bytes =
+            std::fs::read(fixtures.join("forecasts/Gudauri_2023_02_07T19 00_LS.xlsx")).unwrap();
+        let mut options: Options = serde_json::from_str(
+            &std::fs::read_to_string(fixtures.join("options/options.gudauri.0.3.1.json")).unwrap(),
+        )
+        .unwrap();
+        let forecast = parse_excel_spreadsheet(&spreadsheet_bytes, &options).unwrap();
+        let (lenient_forecast, warnings) =
+            parse_excel_spreadsheet_lenient(&spreadsheet_bytes, &options).unwrap();
+        assert!(warnings.is_empty());
+        assert_eq!(
+            serde_json::to_value(&lenient_forecast).unwrap(),
+            serde_json::to_value(&forecast).unwrap()
+        );
+
+        // Point the forecaster name at a cell outside of the form.
+        options.forecaster.name = "Form!ZZ999".parse().unwrap();
+        assert!(parse_excel_spreadsheet(&spreadsheet_bytes, &options).is_err());
+        let (lenient_forecast, warnings) =
+            parse_excel_spreadsheet_lenient(&spreadsheet_bytes, &options).unwrap();
+        assert_eq!(lenient_forecast.forecaster.name, "");
+        assert_eq!(
+            lenient_forecast.avalanche_problems.len(),
+            forecast.avalanche_problems.len()
+        );
+        assert_eq!(warnings.len(), 1);
+        assert_eq!(warnings[0].field, "forecaster.name");
+        assert_eq!(
+            warnings[0].position.as_ref().map(ToString::to_string),
+            Some("Form!ZZ999".to_owned())
+        );
+    }
+
+    #[test]
+    fn test_order_avalanche_problems() {
+        let fixtures = Path::new(CRATE_DIR).join("fixtures");
+        let spreadsheet_bytes =
+            std::fs::read(fixtures.join("forecasts/Gudauri_2023_02_07T19 00_LS.xlsx")).unwrap();
+        let options: Options = serde_json::from_str(
+            &std::fs::read_to_string(fixtures.join("options/options.gudauri.0.3.1.json")).unwrap(),
+        )
+        .unwrap();
+        let forecast = parse_excel_spreadsheet(&spreadsheet_bytes, &options).unwrap();
+        assert_eq!(
+            forecast
+                .primary_avalanche_problem()
+                .map(|problem| problem.kind),
+            forecast
+                .avalanche_problems
+                .first()
+                .map(|problem| problem.kind)
+        );
+        let problems = || {
+            forecast
+                .avalanche_problems
+                .iter()
+                .cloned()
+                .map(|mut problem| {
+                    problem.is_primary = false;
+                    problem
+                })
+                // Problems entered in the second, fourth and fifth inputs.
+                .zip([1, 3, 4])
+                .map(|(problem, i)| (i, problem))
+                .collect::<Vec<_>>()
+        };
+        let kinds = |primary: Option<usize>| {
+            order_avalanche_problems(problems(), primary)
+                .into_iter()
+                .map(|problem| (problem.kind, problem.is_primary))
+                .collect::<Vec<_>>()
+        };
+        let [first, second, third] = [0, 1, 2].map(|i| forecast.avalanche_problems[i].kind);
+
+        assert_eq!(
+            kinds(None),
+            [(first, true), (second, false), (third, false)]
+        );
+        assert_eq!(
+            kinds(Some(3)),
+            [(second, true), (first, false), (third, false)]
+        );
+        // The primary problem isn't enabled.
+        assert_eq!(
+            kinds(Some(0)),
+            [(first, true), (second, false), (third, false)]
+        );
+        assert!(order_avalanche_problems(Vec::new(), Some(0)).is_empty());
+    }
 }
@@ -5,9 +5,10 @@ use serde::Deserialize;
 use unic_langid::LanguageIdentifier;
 
 use crate::{
-    position::CellPosition, serde::string, AreaId, Confidence, Distribution, ElevationBandId,
-    HazardRatingKind, HazardRatingValue, ProblemKind, Sensitivity, SheetCellPosition, TimeOfDay,
-    Trend, Version,
+    position::{CellPosition, CellReference},
+    serde::string,
+    AreaId, Confidence, Distribution, ElevationBandId, HazardRatingKind, HazardRatingValue,
+    ProblemKind, Sensitivity, TimeOfDay, Trend, Version,
 };
 
 #[derive(Deserialize)]
@@ -20,14 +21,14 @@ pub struct Translation {
 
 #[derive(Deserialize)]
 pub struct TranslatedString {
-    pub root: SheetCellPosition,
+    pub root: CellReference,
     #[serde(default)]
     pub translations: HashMap<unic_langid::LanguageIdentifier, Translation>,
 }
 
 #[derive(Deserialize)]
 pub struct FormLanguage {
-    pub position: SheetCellPosition,
+    pub position: CellReference,
     /// Maps the form language value to a language identifier
     pub language_map: HashMap<String, LanguageIdentifier>,
 }
@@ -38,7 +39,7 @@ pub struct Options {
     #[serde(with = "string")]
     pub schema_version: Version,
     pub form_language: FormLanguage,
-    pub template_version: SheetCellPosition,
+    pub template_version: CellReference,
     pub area: Area,
     pub area_definitions: IndexMap<AreaId, AreaDefinition>,
     pub forecaster: Forecaster,
@@ -46,16 +47,106 @@ pub struct Options {
     pub recent_observations: Option<TranslatedString>,
     pub forecast_changes: Option<TranslatedString>,
     pub weather_forecast: Option<TranslatedString>,
-    pub valid_for: SheetCellPosition,
+    pub valid_for: CellReference,
     pub description: Option<TranslatedString>,
     pub hazard_ratings: HazardRatings,
     pub avalanche_problems: Vec<AvalancheProblem>,
+    /// Cell containing the number of the primary (dominant) avalanche problem, where `1` is the
+    /// first problem in [`Options::avalanche_problems`]. When not specified (or the cell is
+    /// empty), the first avalanche problem is the primary problem.
+    pub primary_avalanche_problem: Option<CellReference>,
     /// Set of elevation band ids, that needs to match the order and number of
     /// elevation boundaries in [`Area::elevation_band_boundaries`].
     pub elevation_bands: IndexSet<ElevationBandId>,
     pub terms: Terms,
 }
 
+/// Problems with the referential integrity of an [`Options`] schema, found by
+/// [`Options::validate`].
+#[derive(Debug, thiserror::Error)]
+#[error(
+    "Invalid forecast spreadsheet schema (version {schema_version}):\n{}",
+    .problems.iter().map(|problem| format!("  - {problem}")).collect::<Vec<_>>().join("\n")
+)]
+pub struct ValidationError {
+    pub schema_version: Version,
+    pub problems: Vec<String>,
+}
+
+impl Options {
+    /// Check that the references within this schema are consistent, so that mistakes are
+    /// reported when the schema is loaded, instead of when a spreadsheet fails to parse:
+    ///
+    /// + Elevation specific hazard ratings reference bands declared in `elevation_bands`.
+    /// + The `aspect_elevation` of each avalanche problem has the bands in `elevation_bands`.
+    /// + `form_language.language_map` is not empty (its values are already parsed as language
+    ///   identifiers when the schema is deserialized).
+    /// + Each area in `area.map` has an entry in `area_definitions`.
+    pub fn validate(&self) -> Result<(), ValidationError> {
+        let mut problems = Vec::new();
+        let declared_bands = self
+            .elevation_bands
+            .iter()
+            .map(|band| band.as_str())
+            .collect::<Vec<_>>()
+            .join(", ");
+
+        for kind in self.hazard_ratings.inputs.keys() {
+            if let HazardRatingKind::ElevationSpecific(band) = kind {
+                if !self.elevation_bands.contains(band) {
+                    problems.push(format!(
+                        "hazard_ratings.inputs.{kind} references elevation band {:?} which is not declared in elevation_bands [{declared_bands}]",
+                        band.as_str()
+                    ));
+                }
+            }
+        }
+
+        for (i, problem) in self.avalanche_problems.iter().enumerate() {
+            for band in problem.aspect_elevation.keys() {
+                if !self.elevation_bands.contains(band) {
+                    problems.push(format!(
+                        "avalanche_problems[{i}].aspect_elevation references elevation band {:?} which is not declared in elevation_bands [{declared_bands}]",
+                        band.as_str()
+                    ));
+                }
+            }
+            for band in &self.elevation_bands {
+                if !problem.aspect_elevation.contains_key(band) {
+                    problems.push(format!(
+                        "avalanche_problems[{i}].aspect_elevation is missing elevation band {:?}",
+                        band.as_str()
+                    ));
+                }
+            }
+        }
+
+        if self.form_language.language_map.is_empty() {
+            problems.push("form_language.language_map is empty".to_owned());
+        }
+
+        let mut areas: Vec<(&String, &AreaId)> = self.area.map.iter().collect();
+        areas.sort_by_key(|(name, _)| *name);
+        for (name, area) in areas {
+            if !self.area_definitions.contains_key(area) {
+                problems.push(format!(
+                    "area.map.{name} references area {:?} which has no entry in area_definitions",
+                    area.as_str()
+                ));
+            }
+        }
+
+        if problems.is_empty() {
+            Ok(())
+        } else {
+            Err(ValidationError {
+                schema_version: self.schema_version,
+                problems,
+            })
+        }
+    }
+}
+
 mod timezone_from_string {
     pub fn deserialize<'de, D>(deserializer: D) -> Result<&'static time_tz::Tz, D::Error>
     where
@@ -113,7 +204,7 @@ pub struct AspectElevation {
 
 #[derive(Deserialize)]
 pub struct AvalancheProblem {
-    pub root: SheetCellPosition,
+    pub root: CellReference,
     /// Whether this avalanche problem is specified/enabled.
     pub enabled: CellPosition,
     pub kind: CellPosition,
@@ -131,8 +222,8 @@ pub struct AvalancheProblem {
 #[serde(untagged)]
 pub enum Time {
     DateAndTime {
-        date: SheetCellPosition,
-        time: SheetCellPosition,
+        date: CellReference,
+        time: CellReference,
     },
 }
 
@@ -150,7 +241,7 @@ pub struct ElevationRange {
 #[derive(Deserialize, Debug)]
 pub struct HazardRatingInput {
     /// Root position of the hazard rating block.
-    pub root: SheetCellPosition,
+    pub root: CellReference,
     /// Position of the hazard rating value cell relative to `root`.
     pub value: CellPosition,
     /// Position of the trend cell relative to `root`.
@@ -163,13 +254,13 @@ pub struct HazardRatingInput {
 /// `elevation_bands.len() - 1` for example "2000m,4000m"
 #[derive(Deserialize)]
 pub struct ElevationBandBoundaries {
-    pub position: SheetCellPosition,
+    pub position: CellReference,
     pub reverse: bool,
 }
 
 #[derive(Deserialize)]
 pub struct Area {
-    pub position: SheetCellPosition,
+    pub position: CellReference,
     /// A map from area name to area identifier.
     pub map: HashMap<String, AreaId>,
     pub elevation_band_boundaries: ElevationBandBoundaries,
@@ -177,13 +268,58 @@ pub struct Area {
 
 #[derive(Deserialize)]
 pub struct Language {
-    pub position: SheetCellPosition,
+    pub position: CellReference,
     /// A map from language name (in the spreadsheet) to language identifier.
     pub map: HashMap<String, unic_langid::LanguageIdentifier>,
 }
 
 #[derive(Deserialize)]
 pub struct Forecaster {
-    pub name: SheetCellPosition,
-    pub organisation: SheetCellPosition,
+    pub name: CellReference,
+    pub organisation: CellReference,
+}
+
+#[cfg(test)]
+mod test {
+    use std::path::Path;
+
+    use crate::ElevationBandId;
+
+    use super::Options;
+
+    const CRATE_DIR: &str = env!("CARGO_MANIFEST_DIR");
+
+    fn gudauri_options() -> Options {
+        let fixtures = Path::new(CRATE_DIR).join("fixtures");
+        serde_json::from_str(
+            &std::fs::read_to_string(fixtures.join("options/options.gudauri.0.3.1.json")).unwrap(),
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_validate() {
+        let mut options = gudauri_options();
+        options.validate().unwrap();
+
+        options
+            .elevation_bands
+            .shift_remove(&ElevationBandId::from("high-alpine"));
+        options
+            .area
+            .map
+            .insert("Kazbegi".to_owned(), "kazbegi".to_owned().into());
+        options.form_language.language_map.clear();
+        let error = options.validate().unwrap_err();
+        insta::assert_snapshot!(error.to_string(), @r###"
+        Invalid forecast spreadsheet schema (version 0.3.1):
+          - hazard_ratings.inputs.high-alpine references elevation band "high-alpine" which is not declared in elevation_bands [sub-alpine, alpine]
+          - avalanche_problems[0].aspect_elevation references elevation band "high-alpine" which is not declared in elevation_bands [sub-alpine, alpine]
+          - avalanche_problems[1].aspect_elevation references elevation band "high-alpine" which is not declared in elevation_bands [sub-alpine, alpine]
+          - avalanche_problems[2].aspect_elevation references elevation band "high-alpine" which is not declared in elevation_bands [sub-alpine, alpine]
+          - avalanche_problems[3].aspect_elevation references elevation band "high-alpine" which is not declared in elevation_bands [sub-alpine, alpine]
+          - form_language.language_map is empty
+          - area.map.Kazbegi references area "kazbegi" which has no entry in area_definitions
+        "###);
+    }
 }
@@ -101,9 +101,9 @@ impl<'de> Deserialize<'de> for CellPosition {
     }
 }
 
-impl Into<(u32, u32)> for CellPosition {
-    fn into(self) -> (u32, u32) {
-        (self.row, self.column)
+impl From<CellPosition> for (u32, u32) {
+    fn from(position: CellPosition) -> Self {
+        (position.row, position.column)
     }
 }
 
@@ -238,6 +238,187 @@ impl Serialize for SheetCellPosition {
     }
 }
 
+/// A reference to a cell in a spreadsheet, either by its position, or by an Excel defined name
+/// (named range) with an optional offset. Defined names are resolved using the workbook being
+/// parsed (see [`CellReference::resolve`]), so templates can move cells without changing the
+/// schema.
+///
+/// Serialized as a [`SheetCellPosition`] (e.g. `Form!B2`), a defined name (e.g. `ForecasterName`)
+/// or a defined name with an offset (e.g. `ProblemOne+C2`).
+#[derive(Clone, PartialEq, Eq)]
+pub enum CellReference {
+    Position(SheetCellPosition),
+    Name {
+        name: String,
+        /// Offset relative to the first cell of the defined name.
+        offset: CellPosition,
+    },
+}
+
+impl CellReference {
+    /// Resolve the position of this cell reference using the `defined_names` of a workbook,
+    /// which are pairs of name and formula (e.g. `("ForecasterName", "Form!$B$3")`).
+    pub fn resolve(
+        &self,
+        defined_names: &[(String, String)],
+    ) -> Result<SheetCellPosition, CellReferenceResolveError> {
+        match self {
+            Self::Position(position) => Ok(position.clone()),
+            Self::Name { name, offset } => {
+                let (_, formula) = defined_names
+                    .iter()
+                    .find(|(defined_name, _)| defined_name.eq_ignore_ascii_case(name))
+                    .ok_or_else(|| CellReferenceResolveError::UndefinedName(name.clone()))?;
+                let position = parse_defined_name_formula(formula).map_err(|error| {
+                    CellReferenceResolveError::InvalidFormula {
+                        name: name.clone(),
+                        formula: formula.clone(),
+                        error,
+                    }
+                })?;
+                Ok(position + *offset)
+            }
+        }
+    }
+}
+
+/// Parse the position of the first cell referenced by a defined name formula, e.g.
+/// `'Forecast Form'!$B$2:$D$4` (Excel) or `$'Forecast Form'.$B$2:.$D$4` (OpenDocument).
+fn parse_defined_name_formula(
+    formula: &str,
+) -> Result<SheetCellPosition, SheetCellPositionParseError> {
+    let formula = formula.trim_start_matches('=');
+    let first_cell = formula.split(':').next().unwrap_or(formula);
+    let (sheet, cell) = first_cell
+        .rsplit_once('!')
+        .or_else(|| first_cell.rsplit_once('.'))
+        .ok_or(SheetCellPositionParseError::InvalidFormat)?;
+    let sheet = sheet.trim_start_matches('$');
+    let sheet = match sheet
+        .strip_prefix('\'')
+        .and_then(|sheet| sheet.strip_suffix('\''))
+    {
+        Some(sheet) => sheet.replace("''", "'"),
+        None => sheet.to_string(),
+    };
+    let position = cell.replace('$', "").parse::<CellPosition>()?;
+    Ok(SheetCellPosition { sheet, position })
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum CellReferenceResolveError {
+    #[error("The defined name {0} does not exist in the workbook")]
+    UndefinedName(String),
+    #[error("Unable to parse formula {formula:?} of defined name {name}")]
+    InvalidFormula {
+        name: String,
+        formula: String,
+        #[source]
+        error: SheetCellPositionParseError,
+    },
+}
+
+impl From<SheetCellPosition> for CellReference {
+    fn from(value: SheetCellPosition) -> Self {
+        Self::Position(value)
+    }
+}
+
+impl Add<CellPosition> for CellReference {
+    type Output = Self;
+
+    fn add(self, rhs: CellPosition) -> Self::Output {
+        match self {
+            Self::Position(position) => Self::Position(position + rhs),
+            Self::Name { name, offset } => Self::Name {
+                name,
+                offset: offset + rhs,
+            },
+        }
+    }
+}
+
+/// Whether the `name` is a valid Excel defined name.
+fn is_valid_defined_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars
+        .next()
+        .is_some_and(|c| c.is_alphabetic() || c == '_' || c == '\\')
+        && chars.all(|c| c.is_alphanumeric() || c == '_' || c == '.' || c == '\\')
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum CellReferenceParseError {
+    #[error("Invalid defined name {0:?}")]
+    InvalidName(String),
+    #[error(transparent)]
+    SheetCellPositionParseError(#[from] SheetCellPositionParseError),
+    #[error(transparent)]
+    CellPositionParseError(#[from] CellPositionParseError),
+}
+
+impl FromStr for CellReference {
+    type Err = CellReferenceParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.contains('!') {
+            return Ok(Self::Position(s.parse()?));
+        }
+
+        let (name, offset) = match s.split_once('+') {
+            Some((name, offset)) => (name, offset.parse::<CellPosition>()?),
+            None => (s, CellPosition { column: 0, row: 0 }),
+        };
+
+        if !is_valid_defined_name(name) {
+            return Err(CellReferenceParseError::InvalidName(name.to_string()));
+        }
+
+        Ok(Self::Name {
+            name: name.to_string(),
+            offset,
+        })
+    }
+}
+
+impl<'de> Deserialize<'de> for CellReference {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let s = String::deserialize(deserializer)?;
+        s.parse::<CellReference>().map_err(de::Error::custom)
+    }
+}
+
+impl Display for CellReference {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Position(position) => write!(f, "{position}"),
+            Self::Name { name, offset } if *offset == (CellPosition { column: 0, row: 0 }) => {
+                write!(f, "{name}")
+            }
+            Self::Name { name, offset } => write!(f, "{name}+{offset}"),
+        }
+    }
+}
+
+impl Debug for CellReference {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self)
+    }
+}
+
+impl Serialize for CellReference {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let s = self.to_string();
+        serializer.serialize_str(&s)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -363,4 +544,66 @@ mod tests {
             assert_eq!(position, deserialized);
         }
     }
+
+    #[test]
+    fn test_cell_reference_from_str() {
+        let cases = vec![
+            (
+                "Form!B2",
+                CellReference::Position("Form!B2".parse().unwrap()),
+            ),
+            (
+                "ForecasterName",
+                CellReference::Name {
+                    name: "ForecasterName".to_string(),
+                    offset: CellPosition { column: 0, row: 0 },
+                },
+            ),
+            (
+                "Problem_1+C2",
+                CellReference::Name {
+                    name: "Problem_1".to_string(),
+                    offset: CellPosition { column: 2, row: 1 },
+                },
+            ),
+        ];
+
+        for (input, expected) in cases {
+            let reference = input.parse::<CellReference>().unwrap();
+            assert_eq!(reference, expected);
+            assert_eq!(reference.to_string(), input);
+        }
+
+        for input in ["", "1Name", "Forecaster Name", "Name+", "Form!"] {
+            assert!(input.parse::<CellReference>().is_err(), "{input}");
+        }
+    }
+
+    #[test]
+    fn test_cell_reference_resolve() {
+        let defined_names = vec![
+            ("ForecasterName".to_string(), "Form!$B$3".to_string()),
+            ("ValidFor".to_string(), "$Form.$A$14".to_string()),
+            (
+                "ProblemOne".to_string(),
+                "'Avalanche Problems'!$C$10:$H$20".to_string(),
+            ),
+        ];
+        let resolve = |reference: &str| {
+            reference
+                .parse::<CellReference>()
+                .unwrap()
+                .resolve(&defined_names)
+                .map(|position| position.to_string())
+        };
+
+        assert_eq!(resolve("Form!A1").unwrap(), "Form!A1");
+        assert_eq!(resolve("forecastername").unwrap(), "Form!B3");
+        assert_eq!(resolve("ValidFor").unwrap(), "Form!A14");
+        assert_eq!(resolve("ProblemOne+B3").unwrap(), "Avalanche Problems!D12");
+        assert_eq!(
+            resolve("Missing").unwrap_err().to_string(),
+            "The defined name Missing does not exist in the workbook"
+        );
+    }
 }
@@ -0,0 +1,198 @@
+//! Selecting the [`Options`] schema to parse a spreadsheet with, based on the version of the
+//! template that the spreadsheet was created from. This allows spreadsheets created from older
+//! versions of the template to continue to be parsed after the template has changed.
+
+use crate::{
+    get_cell_value_string, open_spreadsheet, options::Options, parse_sheets, source::CellSource,
+    warnings::Warnings, Forecast, ParseWarning, Version,
+};
+
+/// The template version of a spreadsheet does not match any of the schemas in the
+/// [`SchemaRegistry`].
+#[derive(Debug, thiserror::Error)]
+#[error("Unsupported forecast template version {version}, supported versions: {}", display_versions(.supported))]
+pub struct UnsupportedVersionError {
+    pub version: Version,
+    /// The [`Options::schema_version`] of each schema in the registry.
+    pub supported: Vec<Version>,
+}
+
+fn display_versions(versions: &[Version]) -> String {
+    versions
+        .iter()
+        .map(ToString::to_string)
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// A collection of schemas for different versions of the forecast spreadsheet template.
+pub struct SchemaRegistry {
+    schemas: Vec<Options>,
+}
+
+/// Whether a schema with `schema_version` can be used to parse a spreadsheet created from the
+/// template with `template_version`. Following semantic versioning, templates with a newer patch
+/// version (or minor version, after `1.0.0`) only contain compatible changes.
+fn is_compatible(schema_version: &Version, template_version: &Version) -> bool {
+    schema_version.major == template_version.major
+        && (template_version.major != 0 || schema_version.minor == template_version.minor)
+        && schema_version <= template_version
+}
+
+impl SchemaRegistry {
+    pub fn new(schemas: Vec<Options>) -> Self {
+        Self { schemas }
+    }
+
+    pub fn schemas(&self) -> &[Options] {
+        &self.schemas
+    }
+
+    /// The schema with the newest [`Options::schema_version`].
+    pub fn latest(&self) -> Option<&Options> {
+        self.schemas
+            .iter()
+            .max_by_key(|schema| schema.schema_version)
+    }
+
+    /// Select the schema to parse a spreadsheet created from the template with
+    /// `template_version`, which is the newest compatible schema.
+    pub fn select(&self, template_version: &Version) -> Result<&Options, UnsupportedVersionError> {
+        self.schemas
+            .iter()
+            .filter(|schema| is_compatible(&schema.schema_version, template_version))
+            .max_by_key(|schema| schema.schema_version)
+            .ok_or_else(|| UnsupportedVersionError {
+                version: *template_version,
+                supported: self
+                    .schemas
+                    .iter()
+                    .map(|schema| schema.schema_version)
+                    .collect(),
+            })
+    }
+
+    /// Read the template version of the spreadsheet, and parse it using the selected schema (see
+    /// [`SchemaRegistry::select`]).
+    pub fn parse_excel_spreadsheet(&self, spreadsheet_bytes: &[u8]) -> eyre::Result<Forecast> {
+        let mut sheets = open_spreadsheet(spreadsheet_bytes)?;
+        let schema = self.select_for_source(&mut sheets)?;
+        parse_sheets(&mut sheets, schema, &mut Warnings::strict())
+    }
+
+    /// Like [`SchemaRegistry::parse_excel_spreadsheet`], but parses leniently, see
+    /// [`crate::parse_excel_spreadsheet_lenient`].
+    pub fn parse_excel_spreadsheet_lenient(
+        &self,
+        spreadsheet_bytes: &[u8],
+    ) -> eyre::Result<(Forecast, Vec<ParseWarning>)> {
+        let mut sheets = open_spreadsheet(spreadsheet_bytes)?;
+        let schema = self.select_for_source(&mut sheets)?;
+        let mut warnings = Warnings::lenient();
+        let forecast = parse_sheets(&mut sheets, schema, &mut warnings)?;
+        Ok((forecast, warnings.into_vec()))
+    }
+
+    /// Like [`SchemaRegistry::parse_excel_spreadsheet`], but parses the cell values of a
+    /// [`CellSource`], see [`crate::parse_cell_source`].
+    pub fn parse_cell_source<S: CellSource>(&self, source: &mut S) -> eyre::Result<Forecast> {
+        let schema = self.select_for_source(source)?;
+        parse_sheets(source, schema, &mut Warnings::strict())
+    }
+
+    /// Read the template version of the spreadsheet, and select the schema to parse it with.
+    fn select_for_source<S>(&self, sheets: &mut S) -> eyre::Result<&Options>
+    where
+        S: CellSource,
+    {
+        // Schemas may specify different positions for the template version.
+        let mut template_version: Option<Version> = None;
+        for schema in &self.schemas {
+            if let Ok(Some(version)) =
+                get_cell_value_string::<Version, _>(sheets, &schema.template_version)
+            {
+                template_version = Some(version);
+                if self.select(&version).is_ok() {
+                    break;
+                }
+            }
+        }
+        let template_version = template_version
+            .ok_or_else(|| eyre::eyre!("Unable to read the template version of the spreadsheet"))?;
+
+        Ok(self.select(&template_version)?)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::path::Path;
+
+    use crate::{options::Options, Version};
+
+    use super::{is_compatible, SchemaRegistry};
+
+    const CRATE_DIR: &str = env!("CARGO_MANIFEST_DIR");
+
+    fn version(version: &str) -> Version {
+        version.parse().unwrap()
+    }
+
+    fn schema(schema_version: &str) -> Options {
+        let fixtures = Path::new(CRATE_DIR).join("fixtures");
+        let mut options: Options = serde_json::from_str(
+            &std::fs::read_to_string(fixtures.join("options/options.gudauri.0.3.1.json")).unwrap(),
+        )
+        .unwrap();
+        options.schema_version = version(schema_version);
+        options
+    }
+
+    #[test]
+    fn test_is_compatible() {
+        assert!(is_compatible(&version("0.3.1"), &version("0.3.3")));
+        assert!(!is_compatible(&version("0.3.1"), &version("0.3.0")));
+        assert!(!is_compatible(&version("0.3.1"), &version("0.4.0")));
+        assert!(is_compatible(&version("1.0.0"), &version("1.2.0")));
+        assert!(!is_compatible(&version("1.0.0"), &version("2.0.0")));
+    }
+
+    #[test]
+    fn test_select() {
+        let registry = SchemaRegistry::new(vec![schema("0.2.0"), schema("0.3.0"), schema("0.3.1")]);
+        assert_eq!(
+            registry.select(&version("0.3.3")).unwrap().schema_version,
+            version("0.3.1")
+        );
+        assert_eq!(
+            registry.select(&version("0.2.5")).unwrap().schema_version,
+            version("0.2.0")
+        );
+        let error = registry.select(&version("0.1.0")).err().unwrap();
+        assert_eq!(
+            error.to_string(),
+            "Unsupported forecast template version 0.1.0, supported versions: 0.2.0, 0.3.0, 0.3.1"
+        );
+    }
+
+    #[test]
+    fn test_parse_excel_spreadsheet() {
+        let fixtures = Path::new(CRATE_DIR).join("fixtures");
+        let spreadsheet_bytes =
+            std::fs::read(fixtures.join("forecasts/Gudauri_2023_02_07T19 00_LS.xlsx")).unwrap();
+        let registry = SchemaRegistry::new(vec![schema("0.2.0"), schema("0.3.1")]);
+        let forecast = registry
+            .parse_excel_spreadsheet(&spreadsheet_bytes)
+            .unwrap();
+        assert_eq!(forecast.template_version, version("0.3.3"));
+
+        let registry = SchemaRegistry::new(vec![schema("0.2.0")]);
+        let error = registry
+            .parse_excel_spreadsheet(&spreadsheet_bytes)
+            .unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "Unsupported forecast template version 0.3.3, supported versions: 0.2.0"
+        );
+    }
+}
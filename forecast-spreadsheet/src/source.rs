@@ -0,0 +1,179 @@
+//! Sources of cell values which a forecast can be parsed from. Spreadsheet files (xlsx, xls and
+//! ods) are read using [`calamine::Sheets`], and values which were already fetched (e.g. from the
+//! Google Sheets API) can be provided using [`ValueSheets`].
+
+use std::collections::HashMap;
+
+use calamine::{DataType, Range, Reader, Sheets};
+
+use crate::{position::SheetCellPosition, ParseCellError};
+
+/// A source of cell values to parse a forecast from.
+pub trait CellSource {
+    /// The defined names (named ranges) of the workbook, pairs of name and formula, e.g.
+    /// `("ForecasterName", "Form!$B$3")`.
+    fn defined_names(&self) -> &[(String, String)];
+    /// Get the value of the cell at `position`.
+    fn cell_value(&mut self, position: &SheetCellPosition) -> Result<DataType, ParseCellError>;
+}
+
+impl<RS> CellSource for Sheets<RS>
+where
+    RS: std::io::Read + std::io::Seek,
+{
+    fn defined_names(&self) -> &[(String, String)] {
+        Reader::defined_names(self)
+    }
+
+    fn cell_value(&mut self, position: &SheetCellPosition) -> Result<DataType, ParseCellError> {
+        let sheet = self
+            .worksheet_range(&position.sheet)
+            .ok_or_else(|| ParseCellError::sheet_missing(position.clone().into()))?
+            .map_err(|error| ParseCellError::calamine(position.clone().into(), error))?;
+
+        // The range of a sheet only spans the cells which have values, so the empty cells around
+        // them are not included.
+        Ok(sheet
+            .get_value(position.position.into())
+            .cloned()
+            .unwrap_or(DataType::Empty))
+    }
+}
+
+/// Cell values which have already been read from a spreadsheet, grouped by sheet.
+///
+/// Sources such as the Google Sheets API omit trailing empty rows and columns, so cells outside
+/// of the values of an existing sheet are treated as empty instead of missing.
+#[derive(Debug, Default, Clone)]
+pub struct ValueSheets {
+    sheets: HashMap<String, Range<DataType>>,
+    defined_names: Vec<(String, String)>,
+}
+
+impl ValueSheets {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Insert a sheet with the values of its `rows`, starting from cell `A1`.
+    pub fn insert_sheet(&mut self, name: impl Into<String>, rows: Vec<Vec<DataType>>) {
+        let height = rows.len() as u32;
+        let width = rows.iter().map(Vec::len).max().unwrap_or(0) as u32;
+        let range = if height == 0 || width == 0 {
+            Range::empty()
+        } else {
+            let mut range = Range::new((0, 0), (height - 1, width - 1));
+            for (row, values) in rows.into_iter().enumerate() {
+                for (column, value) in values.into_iter().enumerate() {
+                    range.set_value((row as u32, column as u32), value);
+                }
+            }
+            range
+        };
+        self.sheets.insert(name.into(), range);
+    }
+
+    /// Insert a defined name with its `formula`, e.g. `Form!$B$3`.
+    pub fn insert_defined_name(&mut self, name: impl Into<String>, formula: impl Into<String>) {
+        self.defined_names.push((name.into(), formula.into()));
+    }
+}
+
+impl CellSource for ValueSheets {
+    fn defined_names(&self) -> &[(String, String)] {
+        &self.defined_names
+    }
+
+    fn cell_value(&mut self, position: &SheetCellPosition) -> Result<DataType, ParseCellError> {
+        let sheet = self
+            .sheets
+            .get(&position.sheet)
+            .ok_or_else(|| ParseCellError::sheet_missing(position.clone().into()))?;
+        Ok(sheet
+            .get_value(position.position.into())
+            .cloned()
+            .unwrap_or(DataType::Empty))
+    }
+}
+
+/// Convert a cell value returned by the Google Sheets API (using the `UNFORMATTED_VALUE` value
+/// render option and the `SERIAL_NUMBER` date time render option) into a [`DataType`].
+pub fn data_type_from_json(value: &serde_json::Value) -> DataType {
+    match value {
+        serde_json::Value::Null => DataType::Empty,
+        serde_json::Value::Bool(value) => DataType::Bool(*value),
+        serde_json::Value::Number(number) => number
+            .as_f64()
+            .map(DataType::Float)
+            .unwrap_or_else(|| DataType::String(number.to_string())),
+        serde_json::Value::String(value) if value.is_empty() => DataType::Empty,
+        serde_json::Value::String(value) => DataType::String(value.clone()),
+        value @ (serde_json::Value::Array(_) | serde_json::Value::Object(_)) => {
+            DataType::String(value.to_string())
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{io::Cursor, path::Path};
+
+    use calamine::{open_workbook_auto_from_rs, DataType, Reader};
+    use serde_json::json;
+
+    use crate::{options::Options, parse_cell_source, parse_excel_spreadsheet};
+
+    use super::{data_type_from_json, ValueSheets};
+
+    const CRATE_DIR: &str = env!("CARGO_MANIFEST_DIR");
+
+    #[test]
+    fn test_data_type_from_json() {
+        assert_eq!(data_type_from_json(&json!(null)), DataType::Empty);
+        assert_eq!(data_type_from_json(&json!("")), DataType::Empty);
+        assert_eq!(data_type_from_json(&json!(true)), DataType::Bool(true));
+        assert_eq!(
+            data_type_from_json(&json!(44964.5)),
+            DataType::Float(44964.5)
+        );
+        assert_eq!(
+            data_type_from_json(&json!("Gudauri")),
+            DataType::String("Gudauri".to_owned())
+        );
+    }
+
+    /// Parsing the values of the sheets (as they would be returned by the Google Sheets API)
+    /// produces the same forecast as parsing the spreadsheet file.
+    #[test]
+    fn test_parse_value_sheets() {
+        let fixtures = Path::new(CRATE_DIR).join("fixtures");
+        let options: Options = serde_json::from_str(
+            &std::fs::read_to_string(fixtures.join("options/options.gudauri.0.3.1.json")).unwrap(),
+        )
+        .unwrap();
+        let spreadsheet_bytes =
+            std::fs::read(fixtures.join("forecasts/Gudauri_2023_02_07T19 00_LS.xlsx")).unwrap();
+
+        let mut sheets = open_workbook_auto_from_rs(Cursor::new(&spreadsheet_bytes)).unwrap();
+        let mut values = ValueSheets::new();
+        for sheet_name in sheets.sheet_names().to_owned() {
+            let range = sheets.worksheet_range(&sheet_name).unwrap().unwrap();
+            let (start_row, start_column) = range.start().unwrap_or((0, 0));
+            let rows = std::iter::repeat_n(Vec::new(), start_row as usize)
+                .chain(range.rows().map(|row| {
+                    std::iter::repeat_n(DataType::Empty, start_column as usize)
+                        .chain(row.iter().cloned())
+                        .collect()
+                }))
+                .collect();
+            values.insert_sheet(sheet_name, rows);
+        }
+
+        let expected = parse_excel_spreadsheet(&spreadsheet_bytes, &options).unwrap();
+        let forecast = parse_cell_source(&mut values, &options).unwrap();
+        assert_eq!(
+            serde_json::to_value(&forecast).unwrap(),
+            serde_json::to_value(&expected).unwrap()
+        );
+    }
+}
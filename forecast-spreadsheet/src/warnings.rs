@@ -0,0 +1,74 @@
+//! Warnings produced when parsing a spreadsheet in lenient mode (see
+//! [`crate::parse_excel_spreadsheet_lenient`]), where fields that fail to parse are skipped
+//! instead of aborting the whole parse.
+
+use serde::Serialize;
+
+use crate::{position::CellReference, ParseCellError, RequiredValueMissing};
+
+/// A field of the forecast which could not be parsed, and was left empty or skipped.
+#[derive(Debug, Clone, Serialize)]
+pub struct ParseWarning {
+    /// Path to the field of [`crate::Forecast`], e.g. `avalanche_problems[1]`.
+    pub field: String,
+    /// Position of the cell which caused the problem, if it is known.
+    pub position: Option<CellReference>,
+    /// Description of the problem.
+    pub reason: String,
+}
+
+impl ParseWarning {
+    fn new(field: String, error: &eyre::Error) -> Self {
+        let position = error.chain().find_map(|error| {
+            error
+                .downcast_ref::<ParseCellError>()
+                .map(|error| error.position.clone())
+                .or_else(|| {
+                    error
+                        .downcast_ref::<RequiredValueMissing>()
+                        .map(|error| error.position.clone())
+                })
+        });
+        Self {
+            field,
+            position,
+            reason: format!("{error:#}"),
+        }
+    }
+}
+
+/// Either collects warnings for fields which fail to parse (lenient mode), or returns the errors
+/// (strict mode).
+pub(crate) struct Warnings(Option<Vec<ParseWarning>>);
+
+impl Warnings {
+    pub fn strict() -> Self {
+        Self(None)
+    }
+
+    pub fn lenient() -> Self {
+        Self(Some(Vec::new()))
+    }
+
+    /// Parse the `field` using `parse`. In lenient mode an error is recorded as a warning and the
+    /// `default` value is used instead.
+    pub fn field<T>(
+        &mut self,
+        field: impl Into<String>,
+        parse: impl FnOnce() -> eyre::Result<T>,
+        default: impl FnOnce() -> T,
+    ) -> eyre::Result<T> {
+        match (parse(), &mut self.0) {
+            (Ok(value), _) => Ok(value),
+            (Err(error), Some(warnings)) => {
+                warnings.push(ParseWarning::new(field.into(), &error));
+                Ok(default())
+            }
+            (Err(error), None) => Err(error),
+        }
+    }
+
+    pub fn into_vec(self) -> Vec<ParseWarning> {
+        self.0.unwrap_or_default()
+    }
+}
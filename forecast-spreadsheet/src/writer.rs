@@ -0,0 +1,381 @@
+//! Generate forecast spreadsheets (xlsx) which can be parsed using an [`Options`] schema,
+//! optionally pre-filled from a previous [`Forecast`] so that forecasters can start from
+//! yesterday's forecast instead of an empty form.
+//!
+//! The generated spreadsheet only contains the values at the positions specified by the schema,
+//! it does not include the formatting, data validation or formulas of the original template.
+
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+
+use eyre::ContextCompat;
+use rust_xlsxwriter::Workbook;
+use serde::Serialize;
+use time::OffsetDateTime;
+use time_tz::OffsetDateTimeExt;
+
+use crate::{
+    options::{Options, TranslatedString},
+    position::{CellPosition, CellReference},
+    AvalancheProblem, Forecast, EXCEL_EPOCH,
+};
+
+#[derive(Debug, Clone, PartialEq)]
+enum CellValue {
+    String(String),
+    Number(f64),
+    Bool(bool),
+}
+
+/// The values of the cells to write, grouped by sheet.
+#[derive(Default)]
+struct Cells {
+    sheets: BTreeMap<String, Vec<(CellPosition, CellValue)>>,
+    /// Defined names referenced by the schema, which can't be resolved because the generated
+    /// spreadsheet has no defined names.
+    unresolved: BTreeSet<String>,
+}
+
+impl Cells {
+    fn set(&mut self, reference: &CellReference, value: CellValue) {
+        match reference {
+            CellReference::Position(position) => self
+                .sheets
+                .entry(position.sheet.clone())
+                .or_default()
+                .push((position.position, value)),
+            CellReference::Name { name, .. } => {
+                self.unresolved.insert(name.clone());
+            }
+        }
+    }
+
+    fn set_string(&mut self, position: &CellReference, value: impl Into<String>) {
+        self.set(position, CellValue::String(value.into()))
+    }
+
+    fn set_number(&mut self, position: &CellReference, value: f64) {
+        self.set(position, CellValue::Number(value))
+    }
+
+    fn set_bool(&mut self, position: &CellReference, value: bool) {
+        self.set(position, CellValue::Bool(value))
+    }
+
+    fn into_xlsx(self) -> eyre::Result<Vec<u8>> {
+        if !self.unresolved.is_empty() {
+            eyre::bail!(
+                "Unable to write cells referenced by defined names ({}), the schema must use cell positions",
+                self.unresolved.into_iter().collect::<Vec<_>>().join(", ")
+            );
+        }
+        let mut workbook = Workbook::new();
+        for (sheet, cells) in self.sheets {
+            let worksheet = workbook.add_worksheet();
+            worksheet.set_name(&sheet)?;
+            for (position, value) in cells {
+                let column: u16 = position.column.try_into()?;
+                match value {
+                    CellValue::String(value) => {
+                        worksheet.write_string(position.row, column, &value)?;
+                    }
+                    CellValue::Number(value) => {
+                        worksheet.write_number(position.row, column, value)?;
+                    }
+                    CellValue::Bool(value) => {
+                        worksheet.write_boolean(position.row, column, value)?;
+                    }
+                }
+            }
+        }
+        Ok(workbook.save_to_buffer()?)
+    }
+}
+
+/// Find the name of the `value` in the `terms` (a map from name in the spreadsheet to value). If
+/// there are multiple names for the value (e.g. in different languages), the first name in
+/// alphabetical order is used.
+fn term_name<'a, T: Serialize>(
+    terms: &'a HashMap<String, T>,
+    value: &T,
+    terms_name: &str,
+) -> eyre::Result<&'a str> {
+    let value = serde_json::to_value(value)?;
+    terms
+        .iter()
+        .filter(|(_, term)| serde_json::to_value(term).ok().as_ref() == Some(&value))
+        .map(|(name, _)| name.as_str())
+        .min()
+        .wrap_err_with(|| format!("{terms_name} is missing a name for value {value}"))
+}
+
+/// Convert a local date and time to an Excel serial date (days since the Excel epoch).
+fn excel_serial(time: time::PrimitiveDateTime) -> f64 {
+    let days = time.date().to_julian_day() - EXCEL_EPOCH.to_julian_day();
+    let seconds = time.time().as_hms();
+    let day_fraction =
+        (seconds.0 as f64 * 3600.0 + seconds.1 as f64 * 60.0 + seconds.2 as f64) / 86400.0;
+    days as f64 + day_fraction
+}
+
+/// Generate a forecast spreadsheet for the schema `options`. When a `previous` forecast is
+/// provided, the area, forecaster, elevation bands, hazard ratings and avalanche problems are
+/// carried forward from it, and the forecast time is set to when the previous forecast expires.
+pub fn write_spreadsheet(options: &Options, previous: Option<&Forecast>) -> eyre::Result<Vec<u8>> {
+    let mut cells = Cells::default();
+
+    cells.set_string(
+        &options.template_version,
+        options.schema_version.to_string(),
+    );
+
+    let form_language_name = options
+        .form_language
+        .language_map
+        .keys()
+        .min()
+        .wrap_err("form_language.language_map is empty")?;
+    let form_language = &options.form_language.language_map[form_language_name];
+    cells.set_string(&options.form_language.position, form_language_name);
+
+    // Translations for languages other than the form language are disabled, the forecaster
+    // enables them when they are filled in.
+    let disable_translations = |cells: &mut Cells, translated_string: &TranslatedString| {
+        for (language, translation) in &translated_string.translations {
+            if let (Some(enabled), true) = (translation.enabled, language != form_language) {
+                cells.set_bool(&(translated_string.root.clone() + enabled), false);
+            }
+        }
+    };
+    for translated_string in [
+        &options.recent_observations,
+        &options.forecast_changes,
+        &options.weather_forecast,
+        &options.description,
+    ]
+    .into_iter()
+    .flatten()
+    {
+        disable_translations(&mut cells, translated_string);
+    }
+
+    let Some(previous) = previous else {
+        for problem in &options.avalanche_problems {
+            cells.set_bool(&(problem.root.clone() + problem.enabled), false);
+        }
+        return cells.into_xlsx();
+    };
+
+    let area_name = options
+        .area
+        .map
+        .iter()
+        .filter(|(_, area)| **area == previous.area)
+        .map(|(name, _)| name)
+        .min()
+        .wrap_err_with(|| format!("area.map is missing a name for area {}", previous.area))?;
+    cells.set_string(&options.area.position, area_name);
+
+    cells.set_string(&options.forecaster.name, &previous.forecaster.name);
+    if let Some(organisation) = &previous.forecaster.organisation {
+        cells.set_string(&options.forecaster.organisation, organisation);
+    }
+
+    let time_zone = options
+        .area_definitions
+        .get(&previous.area)
+        .wrap_err_with(|| format!("no area definition specified for area {}", previous.area))?
+        .time_zone;
+    let next_time: OffsetDateTime = (previous.time + previous.valid_for).to_timezone(time_zone);
+    let next_time = time::PrimitiveDateTime::new(next_time.date(), next_time.time());
+    match &options.time {
+        crate::options::Time::DateAndTime { date, time } => {
+            cells.set_number(date, excel_serial(next_time).floor());
+            cells.set_number(time, excel_serial(next_time).fract());
+        }
+    }
+    cells.set_number(
+        &options.valid_for,
+        previous.valid_for.as_seconds_f64() / 86400.0,
+    );
+
+    let mut elevation_band_boundaries: Vec<String> = previous
+        .elevation_bands
+        .values()
+        .filter_map(|range| range.upper)
+        .map(|upper| format!("{upper}m"))
+        .collect();
+    if options.area.elevation_band_boundaries.reverse {
+        elevation_band_boundaries.reverse();
+    }
+    cells.set_string(
+        &options.area.elevation_band_boundaries.position,
+        elevation_band_boundaries.join(","),
+    );
+
+    for (kind, input) in &options.hazard_ratings.inputs {
+        let Some(rating) = previous.hazard_ratings.get(kind) else {
+            continue;
+        };
+        if let Some(value) = &rating.value {
+            let name = term_name(&options.terms.hazard_rating, value, "terms.hazard_rating")?;
+            cells.set_string(&(input.root.clone() + input.value), name);
+        }
+        if let (Some(position), Some(trend)) = (input.trend, &rating.trend) {
+            let name = term_name(&options.terms.trend, trend, "terms.trend")?;
+            cells.set_string(&(input.root.clone() + position), name);
+        }
+        if let (Some(position), Some(confidence)) = (input.confidence, &rating.confidence) {
+            let name = term_name(&options.terms.confidence, confidence, "terms.confidence")?;
+            cells.set_string(&(input.root.clone() + position), name);
+        }
+    }
+
+    for (i, problem_input) in options.avalanche_problems.iter().enumerate() {
+        let enabled = problem_input.root.clone() + problem_input.enabled;
+        match previous.avalanche_problems.get(i) {
+            Some(problem) => {
+                cells.set_bool(&enabled, true);
+                write_avalanche_problem(
+                    &mut cells,
+                    options,
+                    problem_input,
+                    problem,
+                    form_language,
+                )?;
+            }
+            None => cells.set_bool(&enabled, false),
+        }
+    }
+    if let Some(position) = &options.primary_avalanche_problem {
+        if let Some(i) = previous
+            .avalanche_problems
+            .iter()
+            .position(|problem| problem.is_primary)
+        {
+            cells.set_number(position, (i + 1) as f64);
+        }
+    }
+
+    cells.into_xlsx()
+}
+
+fn write_avalanche_problem(
+    cells: &mut Cells,
+    options: &Options,
+    input: &crate::options::AvalancheProblem,
+    problem: &AvalancheProblem,
+    form_language: &unic_langid::LanguageIdentifier,
+) -> eyre::Result<()> {
+    let position = |relative: CellPosition| input.root.clone() + relative;
+
+    let kind = term_name(
+        &options.terms.avalanche_problem_kind,
+        &problem.kind,
+        "terms.avalanche_problem_kind",
+    )?;
+    cells.set_string(&position(input.kind), kind);
+
+    for (elevation_band, aspect_elevation_input) in &input.aspect_elevation {
+        let aspects: Vec<String> = problem
+            .aspect_elevation
+            .get(elevation_band)
+            .map(|aspect_elevation| {
+                aspect_elevation
+                    .aspects
+                    .iter()
+                    .map(|aspect| format!("{aspect:?}"))
+                    .collect()
+            })
+            .unwrap_or_default();
+        cells.set_bool(
+            &position(aspect_elevation_input.enabled),
+            !aspects.is_empty(),
+        );
+        cells.set_string(
+            &position(aspect_elevation_input.aspects),
+            aspects.join(", "),
+        );
+    }
+
+    if let (Some(relative), Some(value)) = (input.confidence, &problem.confidence) {
+        let name = term_name(&options.terms.confidence, value, "terms.confidence")?;
+        cells.set_string(&position(relative), name);
+    }
+    if let (Some(relative), Some(value)) = (input.sensitivity, &problem.sensitivity) {
+        let name = term_name(&options.terms.sensitivity, value, "terms.sensitivity")?;
+        cells.set_string(&position(relative), name);
+    }
+    if let (Some(relative), Some(value)) = (input.size, &problem.size) {
+        cells.set_number(&position(relative), f64::from(*value as u8));
+    }
+    if let (Some(relative), Some(value)) = (input.distribution, &problem.distribution) {
+        let name = term_name(&options.terms.distribution, value, "terms.distribution")?;
+        cells.set_string(&position(relative), name);
+    }
+    if let (Some(relative), Some(value)) = (input.time_of_day, &problem.time_of_day) {
+        let name = term_name(&options.terms.time_of_day, value, "terms.time_of_day")?;
+        cells.set_string(&position(relative), name);
+    }
+    if let (Some(relative), Some(value)) = (input.trend, &problem.trend) {
+        let name = term_name(&options.terms.trend, value, "terms.trend")?;
+        cells.set_string(&position(relative), name);
+    }
+
+    if let Some(translated_string) = &input.description {
+        for (language, translation) in &translated_string.translations {
+            let text = problem.description.get(language);
+            if let Some(text) = text {
+                cells.set_string(
+                    &(translated_string.root.clone() + translation.position),
+                    text,
+                );
+            }
+            if let (Some(enabled), true) = (translation.enabled, language != form_language) {
+                cells.set_bool(&(translated_string.root.clone() + enabled), text.is_some());
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use std::path::Path;
+
+    use crate::{options::Options, parse_excel_spreadsheet};
+
+    use super::write_spreadsheet;
+
+    const CRATE_DIR: &str = env!("CARGO_MANIFEST_DIR");
+
+    fn json<T: serde::Serialize>(value: &T) -> serde_json::Value {
+        serde_json::to_value(value).unwrap()
+    }
+
+    #[test]
+    fn test_write_spreadsheet_from_previous() {
+        let fixtures = Path::new(CRATE_DIR).join("fixtures");
+        let spreadsheet_bytes =
+            std::fs::read(fixtures.join("forecasts/Gudauri_2023_02_07T19 00_LS.xlsx")).unwrap();
+        let options: Options = serde_json::from_str(
+            &std::fs::read_to_string(fixtures.join("options/options.gudauri.0.3.1.json")).unwrap(),
+        )
+        .unwrap();
+        let previous = parse_excel_spreadsheet(&spreadsheet_bytes, &options).unwrap();
+
+        let written = write_spreadsheet(&options, Some(&previous)).unwrap();
+        let next = parse_excel_spreadsheet(&written, &options).unwrap();
+
+        assert_eq!(next.area, previous.area);
+        assert_eq!(next.forecaster.name, previous.forecaster.name);
+        assert_eq!(next.time, previous.time + previous.valid_for);
+        assert_eq!(next.valid_for, previous.valid_for);
+        assert_eq!(json(&next.elevation_bands), json(&previous.elevation_bands));
+        assert_eq!(json(&next.hazard_ratings), json(&previous.hazard_ratings));
+        assert_eq!(
+            json(&next.avalanche_problems),
+            json(&previous.avalanche_problems)
+        );
+        assert!(next.recent_observations.is_empty());
+    }
+}
@@ -1,4 +1,3 @@
-use plotly::{Configuration, Image};
 use show_image::{create_window, ImageInfo, ImageView, WindowOptions};
 
 #[show_image::main]
@@ -13,7 +12,7 @@ pub fn main() -> eyre::Result<()> {
 
     let shape = pixels.shape();
     let view = ImageView::new(
-        ImageInfo::mono8(shape[0] as u32, shape[1] as u32),
+        ImageInfo::mono8(shape[1] as u32, shape[0] as u32),
         pixels.as_slice_memory_order().unwrap(),
     );
     let window = create_window("Mountains", WindowOptions::default()).unwrap();
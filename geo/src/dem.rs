@@ -0,0 +1,568 @@
+//! A digital elevation model (DEM) in geographic coordinates, and the terrain rasters derived
+//! from it.
+
+use std::path::Path;
+
+use ndarray::{s, Array2};
+
+use crate::{
+    polygon::{polygon_contains, Polygon},
+    terrain::{Aspect, RouteExposure, TerrainSummary, FLAT_SLOPE_DEGREES, STEEP_SLOPE_DEGREES},
+    track::Track,
+};
+
+/// Semi-major axis of the WGS84 ellipsoid in metres.
+pub(crate) const WGS84_A: f64 = 6_378_137.0;
+/// First eccentricity squared of the WGS84 ellipsoid.
+pub(crate) const WGS84_E2: f64 = 6.694_379_990_14e-3;
+
+/// The value of a cell in [`Dem::elevation_bands`] which has no elevation data.
+pub const NO_DATA: u8 = u8::MAX;
+
+/// The length in metres of a degree of `(longitude, latitude)` at `latitude`, using the radii of
+/// curvature of the WGS84 ellipsoid.
+fn metres_per_degree(latitude: f64) -> (f64, f64) {
+    let latitude = latitude.to_radians();
+    let w = 1.0 - WGS84_E2 * latitude.sin().powi(2);
+    let prime_vertical_radius = WGS84_A / w.sqrt();
+    let meridional_radius = WGS84_A * (1.0 - WGS84_E2) / w.powf(1.5);
+    (
+        prime_vertical_radius * latitude.cos() * 1f64.to_radians(),
+        meridional_radius * 1f64.to_radians(),
+    )
+}
+
+/// The approximate distance in metres between two nearby longitude, latitude positions, using
+/// the scale of the WGS84 ellipsoid at their mean latitude.
+pub fn distance_metres(from: (f64, f64), to: (f64, f64)) -> f64 {
+    let (east, north) = metres_per_degree((from.1 + to.1) / 2.0);
+    ((to.0 - from.0) * east).hypot((to.1 - from.1) * north)
+}
+
+/// A rectangle of longitude and latitude in degrees.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Bounds {
+    pub west: f64,
+    pub south: f64,
+    pub east: f64,
+    pub north: f64,
+}
+
+impl Bounds {
+    /// Whether the bounds overlap (or touch) the `other` bounds.
+    pub fn intersects(&self, other: &Bounds) -> bool {
+        self.west <= other.east
+            && other.west <= self.east
+            && self.south <= other.north
+            && other.south <= self.north
+    }
+}
+
+/// A digital elevation model in a geographic (longitude, latitude) coordinate system.
+#[derive(Debug, Clone)]
+pub struct Dem {
+    /// Elevations in metres indexed by `[row, column]`, row `0` is the northern edge. Cells
+    /// without data are `NaN`.
+    pub elevations: Array2<f32>,
+    /// Longitude of the western edge of the first column.
+    pub west: f64,
+    /// Latitude of the northern edge of the first row.
+    pub north: f64,
+    /// Width of a cell in degrees of longitude.
+    pub cell_width: f64,
+    /// Height of a cell in degrees of latitude.
+    pub cell_height: f64,
+}
+
+impl Dem {
+    /// Load a DEM from a GeoTIFF file, see [`crate::geotiff::load`].
+    pub fn load(path: impl AsRef<Path>) -> eyre::Result<Self> {
+        crate::geotiff::load(path)
+    }
+
+    pub fn rows(&self) -> usize {
+        self.elevations.nrows()
+    }
+
+    pub fn columns(&self) -> usize {
+        self.elevations.ncols()
+    }
+
+    pub fn bounds(&self) -> Bounds {
+        Bounds {
+            west: self.west,
+            south: self.north - self.rows() as f64 * self.cell_height,
+            east: self.west + self.columns() as f64 * self.cell_width,
+            north: self.north,
+        }
+    }
+
+    /// Longitude of the centre of the cells in `column`.
+    pub fn longitude(&self, column: usize) -> f64 {
+        self.west + (column as f64 + 0.5) * self.cell_width
+    }
+
+    /// Latitude of the centre of the cells in `row`.
+    pub fn latitude(&self, row: usize) -> f64 {
+        self.north - (row as f64 + 0.5) * self.cell_height
+    }
+
+    /// Crop the DEM to the cells which intersect `bounds`. Returns `None` if they don't overlap.
+    pub fn crop(&self, bounds: &Bounds) -> Option<Dem> {
+        let first_column = ((bounds.west - self.west) / self.cell_width)
+            .floor()
+            .max(0.0) as usize;
+        let last_column = (((bounds.east - self.west) / self.cell_width)
+            .ceil()
+            .max(0.0) as usize)
+            .min(self.columns());
+        let first_row = ((self.north - bounds.north) / self.cell_height)
+            .floor()
+            .max(0.0) as usize;
+        let last_row = (((self.north - bounds.south) / self.cell_height)
+            .ceil()
+            .max(0.0) as usize)
+            .min(self.rows());
+        if first_column >= last_column || first_row >= last_row {
+            return None;
+        }
+        Some(Dem {
+            elevations: self
+                .elevations
+                .slice(s![first_row..last_row, first_column..last_column])
+                .to_owned(),
+            west: self.west + first_column as f64 * self.cell_width,
+            north: self.north - first_row as f64 * self.cell_height,
+            cell_width: self.cell_width,
+            cell_height: self.cell_height,
+        })
+    }
+
+    /// The size `(width, height)` in metres of the cells in `row`, using the radii of curvature
+    /// of the WGS84 ellipsoid at the latitude of the row. Cells become narrower towards the
+    /// poles.
+    pub fn cell_size_metres(&self, row: usize) -> (f64, f64) {
+        let (east, north) = metres_per_degree(self.latitude(row));
+        (east * self.cell_width, north * self.cell_height)
+    }
+
+    /// The `(row, column)` of the cell containing the position, or `None` if it's outside the
+    /// DEM.
+    pub fn cell(&self, longitude: f64, latitude: f64) -> Option<(usize, usize)> {
+        let column = ((longitude - self.west) / self.cell_width).floor();
+        let row = ((self.north - latitude) / self.cell_height).floor();
+        if column < 0.0 || row < 0.0 {
+            return None;
+        }
+        let (row, column) = (row as usize, column as usize);
+        (row < self.rows() && column < self.columns()).then_some((row, column))
+    }
+
+    /// The elevation gradient `(dz/dx, dz/dy)` of a cell towards the east and north respectively
+    /// (metres per metre), using Horn's method. Neighbours outside the DEM or without data take
+    /// the elevation of the cell. Returns `None` if the cell has no data.
+    pub fn gradient(&self, row: usize, column: usize) -> Option<(f64, f64)> {
+        let centre = *self.elevations.get([row, column])?;
+        if centre.is_nan() {
+            return None;
+        }
+        let z = |row_offset: isize, column_offset: isize| -> f64 {
+            let elevation = row
+                .checked_add_signed(row_offset)
+                .zip(column.checked_add_signed(column_offset))
+                .and_then(|(row, column)| self.elevations.get([row, column]).copied())
+                .filter(|elevation| !elevation.is_nan())
+                .unwrap_or(centre);
+            f64::from(elevation)
+        };
+        let (width, height) = self.cell_size_metres(row);
+        let dz_dx = ((z(-1, 1) + 2.0 * z(0, 1) + z(1, 1))
+            - (z(-1, -1) + 2.0 * z(0, -1) + z(1, -1)))
+            / (8.0 * width);
+        // Rows increase towards the south.
+        let dz_dy = ((z(-1, -1) + 2.0 * z(-1, 0) + z(-1, 1))
+            - (z(1, -1) + 2.0 * z(1, 0) + z(1, 1)))
+            / (8.0 * height);
+        Some((dz_dx, dz_dy))
+    }
+
+    /// The slope angle of a cell in degrees from horizontal, or `None` if the cell has no data.
+    pub fn slope_degrees(&self, row: usize, column: usize) -> Option<f64> {
+        self.gradient(row, column).map(gradient_slope_degrees)
+    }
+
+    /// The slope angle of each cell in degrees from horizontal, cells without data are `NaN`.
+    pub fn slopes(&self) -> Array2<f32> {
+        Array2::from_shape_fn(self.elevations.dim(), |(row, column)| {
+            self.slope_degrees(row, column)
+                .map_or(f32::NAN, |slope| slope as f32)
+        })
+    }
+
+    /// The aspect of a cell, the direction that the slope faces downhill in degrees clockwise
+    /// from north. Returns `None` if the cell has no data or is perfectly flat.
+    pub fn aspect_degrees(&self, row: usize, column: usize) -> Option<f64> {
+        self.gradient(row, column).and_then(gradient_aspect_degrees)
+    }
+
+    /// The aspect of each cell in degrees clockwise from north, cells without data or which are
+    /// perfectly flat are `NaN`.
+    pub fn aspects(&self) -> Array2<f32> {
+        Array2::from_shape_fn(self.elevations.dim(), |(row, column)| {
+            self.aspect_degrees(row, column)
+                .map_or(f32::NAN, |aspect| aspect as f32)
+        })
+    }
+
+    /// Whether the centre of each cell is inside any of the polygons.
+    pub fn polygon_mask(&self, polygons: &[Polygon]) -> Array2<bool> {
+        Array2::from_shape_fn(self.elevations.dim(), |(row, column)| {
+            let (longitude, latitude) = (self.longitude(column), self.latitude(row));
+            polygons
+                .iter()
+                .any(|polygon| polygon_contains(polygon, longitude, latitude))
+        })
+    }
+
+    /// Summarise the terrain of the cells which are `true` in `mask` (see
+    /// [`Dem::polygon_mask`]) by the area in each elevation band (see [`Dem::elevation_bands`])
+    /// and aspect.
+    pub fn terrain_summary(&self, mask: &Array2<bool>, boundaries: &[f64]) -> TerrainSummary {
+        let bands = self.elevation_bands(boundaries);
+        let mut summary = TerrainSummary {
+            boundaries: boundaries.to_vec(),
+            aspect_area: vec![[0.0; 8]; boundaries.len() + 1],
+            flat_area: vec![0.0; boundaries.len() + 1],
+        };
+        for ((row, column), band) in bands.indexed_iter() {
+            if *band == NO_DATA || !mask.get((row, column)).copied().unwrap_or(false) {
+                continue;
+            }
+            let Some(gradient) = self.gradient(row, column) else {
+                continue;
+            };
+            let (width, height) = self.cell_size_metres(row);
+            let area = width * height;
+            let band = usize::from(*band);
+            let aspect = Some(gradient)
+                .filter(|gradient| gradient_slope_degrees(*gradient) >= FLAT_SLOPE_DEGREES)
+                .and_then(gradient_aspect_degrees);
+            match aspect {
+                Some(aspect) => {
+                    summary.aspect_area[band][Aspect::from_degrees(aspect) as usize] += area
+                }
+                None => summary.flat_area[band] += area,
+            }
+        }
+        summary
+    }
+
+    /// Measure the distance of the `tracks` in each elevation band (see
+    /// [`Dem::elevation_bands`]) and aspect. The tracks are sampled at intervals of half a cell,
+    /// each sample taking the elevation, slope and aspect of the cell it falls in.
+    pub fn route_exposure(&self, tracks: &[Track], boundaries: &[f64]) -> RouteExposure {
+        let mut exposure = RouteExposure {
+            boundaries: boundaries.to_vec(),
+            aspect_distance: vec![[0.0; 8]; boundaries.len() + 1],
+            steep_distance: vec![[0.0; 8]; boundaries.len() + 1],
+            flat_distance: vec![0.0; boundaries.len() + 1],
+            outside_distance: 0.0,
+            max_slope_degrees: 0.0,
+        };
+        let (cell_width, cell_height) = self.cell_size_metres(self.rows() / 2);
+        let interval = cell_width.min(cell_height) / 2.0;
+        for track in tracks {
+            for (from, to) in track.iter().zip(track.iter().skip(1)) {
+                let distance = distance_metres(*from, *to);
+                let samples = (distance / interval).ceil().max(1.0) as usize;
+                let sample_distance = distance / samples as f64;
+                for sample in 0..samples {
+                    let t = (sample as f64 + 0.5) / samples as f64;
+                    let longitude = from.0 + (to.0 - from.0) * t;
+                    let latitude = from.1 + (to.1 - from.1) * t;
+                    let cell = self.cell(longitude, latitude).and_then(|(row, column)| {
+                        let elevation = self.elevations[[row, column]];
+                        let gradient = self.gradient(row, column)?;
+                        Some((elevation, gradient))
+                    });
+                    let Some((elevation, gradient)) = cell else {
+                        exposure.outside_distance += sample_distance;
+                        continue;
+                    };
+                    let band = boundaries
+                        .iter()
+                        .take_while(|boundary| f64::from(elevation) >= **boundary)
+                        .count();
+                    let slope = gradient_slope_degrees(gradient);
+                    exposure.max_slope_degrees = exposure.max_slope_degrees.max(slope);
+                    let aspect = Some(gradient)
+                        .filter(|_| slope >= FLAT_SLOPE_DEGREES)
+                        .and_then(gradient_aspect_degrees)
+                        .map(Aspect::from_degrees);
+                    match aspect {
+                        Some(aspect) => {
+                            exposure.aspect_distance[band][aspect as usize] += sample_distance;
+                            if slope >= STEEP_SLOPE_DEGREES {
+                                exposure.steep_distance[band][aspect as usize] += sample_distance;
+                            }
+                        }
+                        None => exposure.flat_distance[band] += sample_distance,
+                    }
+                }
+            }
+        }
+        exposure
+    }
+
+    /// Classify each cell by elevation band. `boundaries` are the ascending elevations (metres)
+    /// which separate the bands: a cell below `boundaries[0]` is in band `0`, a cell between
+    /// `boundaries[0]` and `boundaries[1]` is in band `1`, and so on. Cells without data are
+    /// [`NO_DATA`].
+    pub fn elevation_bands(&self, boundaries: &[f64]) -> Array2<u8> {
+        self.elevations.mapv(|elevation| {
+            if elevation.is_nan() {
+                return NO_DATA;
+            }
+            let band = boundaries
+                .iter()
+                .take_while(|boundary| f64::from(elevation) >= **boundary)
+                .count();
+            band.min(usize::from(NO_DATA - 1)) as u8
+        })
+    }
+
+    /// Shade the terrain as lit by the sun at `azimuth` degrees (clockwise from north) and
+    /// `altitude` degrees above the horizon. Each cell is a brightness from `0` to `255`, cells
+    /// without data are `0`.
+    pub fn hillshade(&self, azimuth: f64, altitude: f64) -> Array2<u8> {
+        let zenith = (90.0 - altitude).to_radians();
+        // Anticlockwise from east, to match the aspect below.
+        let azimuth = (90.0 - azimuth).to_radians();
+        Array2::from_shape_fn(self.elevations.dim(), |(row, column)| {
+            let Some((dz_dx, dz_dy)) = self.gradient(row, column) else {
+                return 0;
+            };
+            let slope = dz_dx.hypot(dz_dy).atan();
+            // Direction of the downhill slope, anticlockwise from east.
+            let aspect = (-dz_dy).atan2(-dz_dx);
+            let shade =
+                zenith.cos() * slope.cos() + zenith.sin() * slope.sin() * (azimuth - aspect).cos();
+            (shade.clamp(0.0, 1.0) * 255.0).round() as u8
+        })
+    }
+}
+
+fn gradient_slope_degrees((dz_dx, dz_dy): (f64, f64)) -> f64 {
+    dz_dx.hypot(dz_dy).atan().to_degrees()
+}
+
+fn gradient_aspect_degrees((dz_dx, dz_dy): (f64, f64)) -> Option<f64> {
+    if dz_dx == 0.0 && dz_dy == 0.0 {
+        return None;
+    }
+    // The downhill direction is (-dz_dx, -dz_dy) towards (east, north).
+    Some((-dz_dx).atan2(-dz_dy).to_degrees().rem_euclid(360.0))
+}
+
+#[cfg(test)]
+mod test {
+    use ndarray::Array2;
+
+    use crate::Aspect;
+
+    use super::{distance_metres, Bounds, Dem, NO_DATA};
+
+    /// A DEM of 1 arc second cells on the equator, with the elevation rising by `rise` metres
+    /// per cell towards the east.
+    fn plane(rise: f32) -> Dem {
+        Dem {
+            elevations: Array2::from_shape_fn((5, 5), |(_, column)| column as f32 * rise),
+            west: 0.0,
+            north: 5.0 / 3600.0,
+            cell_width: 1.0 / 3600.0,
+            cell_height: 1.0 / 3600.0,
+        }
+    }
+
+    #[test]
+    fn test_elevation_bands() {
+        let mut dem = plane(10.0);
+        dem.elevations[[0, 0]] = f32::NAN;
+        let bands = dem.elevation_bands(&[15.0, 25.0]);
+        assert_eq!(bands.row(1).to_vec(), vec![0, 0, 1, 2, 2]);
+        assert_eq!(bands[[0, 0]], NO_DATA);
+    }
+
+    #[test]
+    fn test_cell_size_metres() {
+        let dem = plane(0.0);
+        let (width, height) = dem.cell_size_metres(0);
+        assert!((width - 30.92).abs() < 0.01, "{width}");
+        assert!((height - 30.715).abs() < 0.001, "{height}");
+
+        let dem = Dem {
+            north: 60.0,
+            ..plane(0.0)
+        };
+        let (width, height) = dem.cell_size_metres(0);
+        assert!((width - 15.50).abs() < 0.01, "{width}");
+        assert!((height - 30.948).abs() < 0.001, "{height}");
+    }
+
+    #[test]
+    fn test_gradient() {
+        let dem = plane(10.0);
+        let (dz_dx, dz_dy) = dem.gradient(2, 2).unwrap();
+        assert!((dz_dx - 10.0 / 30.92).abs() < 0.001, "{dz_dx}");
+        assert_eq!(dz_dy, 0.0);
+    }
+
+    #[test]
+    fn test_slopes() {
+        // Rising by the width of a cell across each cell.
+        let mut dem = plane(30.922);
+        dem.elevations[[0, 0]] = f32::NAN;
+        let slopes = dem.slopes();
+        assert!((slopes[[2, 2]] - 45.0).abs() < 0.01, "{}", slopes[[2, 2]]);
+        assert!(slopes[[0, 0]].is_nan());
+        assert_eq!(plane(0.0).slope_degrees(2, 2), Some(0.0));
+    }
+
+    #[test]
+    fn test_aspects() {
+        // Rising towards the east, so facing west.
+        let aspects = plane(10.0).aspects();
+        assert!(
+            (aspects[[2, 2]] - 270.0).abs() < 0.001,
+            "{}",
+            aspects[[2, 2]]
+        );
+        assert!(plane(0.0).aspects()[[2, 2]].is_nan());
+
+        // Rising towards the north, so facing south.
+        let dem = Dem {
+            elevations: Array2::from_shape_fn((5, 5), |(row, _)| (5 - row) as f32 * 10.0),
+            ..plane(0.0)
+        };
+        let aspect = dem.aspect_degrees(2, 2).unwrap();
+        assert!((aspect - 180.0).abs() < 0.001, "{aspect}");
+    }
+
+    #[test]
+    fn test_terrain_summary() {
+        let dem = plane(10.0);
+        let mask = dem.polygon_mask(&[vec![vec![
+            (0.0, 0.0),
+            (2.0 / 3600.0, 0.0),
+            (2.0 / 3600.0, 5.0 / 3600.0),
+            (0.0, 5.0 / 3600.0),
+            (0.0, 0.0),
+        ]]]);
+        // The first two columns.
+        assert_eq!(mask.iter().filter(|inside| **inside).count(), 10);
+
+        let summary = dem.terrain_summary(&mask, &[5.0]);
+        assert_eq!(summary.aspect_area.len(), 2);
+        let (width, height) = dem.cell_size_metres(2);
+        let cell_area = width * height;
+        assert!((summary.total_area() - 10.0 * cell_area).abs() < 1.0);
+        assert!((summary.area(0, Aspect::W) - 5.0 * cell_area).abs() < 1.0);
+        assert!((summary.area(1, Aspect::W) - 5.0 * cell_area).abs() < 1.0);
+        assert_eq!(summary.area(1, Aspect::E), 0.0);
+        assert_eq!(summary.flat_area, vec![0.0, 0.0]);
+
+        let flat = plane(0.0).terrain_summary(&mask, &[]);
+        assert!((flat.flat_area[0] - 10.0 * cell_area).abs() < 1.0);
+    }
+
+    #[test]
+    fn test_hillshade() {
+        let flat = plane(0.0).hillshade(315.0, 45.0);
+        assert_eq!(flat[[2, 2]], 180);
+
+        // The plane faces west, so it is lit by the sun from the west.
+        let dem = plane(10.0);
+        let west_sun = dem.hillshade(270.0, 45.0);
+        let east_sun = dem.hillshade(90.0, 45.0);
+        assert!(west_sun[[2, 2]] > flat[[2, 2]]);
+        assert!(east_sun[[2, 2]] < flat[[2, 2]]);
+    }
+
+    #[test]
+    fn test_cell() {
+        let dem = plane(0.0);
+        assert_eq!(dem.cell(0.5 / 3600.0, 4.5 / 3600.0), Some((0, 0)));
+        assert_eq!(dem.cell(3.5 / 3600.0, 0.5 / 3600.0), Some((4, 3)));
+        assert_eq!(dem.cell(-0.5 / 3600.0, 0.5 / 3600.0), None);
+        assert_eq!(dem.cell(0.5 / 3600.0, 5.5 / 3600.0), None);
+        assert_eq!(dem.cell(5.5 / 3600.0, 0.5 / 3600.0), None);
+    }
+
+    #[test]
+    fn test_distance_metres() {
+        let distance = distance_metres((0.0, 0.0), (1.0 / 3600.0, 0.0));
+        assert!((distance - 30.92).abs() < 0.01, "{distance}");
+        let distance = distance_metres((0.0, 0.0), (3.0 / 3600.0, 4.0 / 3600.0));
+        let expected = (3.0 * 30.92f64).hypot(4.0 * 30.715);
+        assert!((distance - expected).abs() < 0.1, "{distance}");
+    }
+
+    #[test]
+    fn test_route_exposure() {
+        // Approximately 33° slopes facing west.
+        let dem = plane(20.0);
+        let (width, _) = dem.cell_size_metres(2);
+        // Across the DEM along the middle row, then one cell beyond its eastern edge.
+        let track = vec![
+            (0.0, 2.5 / 3600.0),
+            (5.0 / 3600.0, 2.5 / 3600.0),
+            (6.0 / 3600.0, 2.5 / 3600.0),
+        ];
+        let exposure = dem.route_exposure(&[track], &[50.0]);
+        assert_eq!(exposure.aspect_distance.len(), 2);
+        assert!((exposure.total_distance() - 6.0 * width).abs() < 0.1);
+        assert!((exposure.outside_distance - width).abs() < 0.1);
+        let west: f64 = (0..2)
+            .map(|band| exposure.aspect_distance[band][Aspect::W as usize])
+            .sum();
+        assert!((west - 5.0 * width).abs() < 0.1, "{west}");
+        assert!(exposure.aspect_distance[0][Aspect::W as usize] > 0.0);
+        assert!(exposure.aspect_distance[1][Aspect::W as usize] > 0.0);
+        assert!(exposure.steep_distance[1][Aspect::W as usize] > 0.0);
+        assert!(
+            exposure.steep_distance[1][Aspect::W as usize]
+                <= exposure.aspect_distance[1][Aspect::W as usize]
+        );
+        assert!(
+            (32.0..34.0).contains(&exposure.max_slope_degrees),
+            "{}",
+            exposure.max_slope_degrees
+        );
+    }
+
+    #[test]
+    fn test_crop() {
+        let dem = plane(10.0);
+        let cropped = dem
+            .crop(&Bounds {
+                west: 1.5 / 3600.0,
+                south: 1.5 / 3600.0,
+                east: 3.5 / 3600.0,
+                north: 3.5 / 3600.0,
+            })
+            .unwrap();
+        assert_eq!(cropped.elevations.dim(), (3, 3));
+        assert_eq!(cropped.elevations[[0, 0]], 10.0);
+        assert!((cropped.west - 1.0 / 3600.0).abs() < 1e-12);
+        assert!((cropped.north - 4.0 / 3600.0).abs() < 1e-12);
+
+        assert!(dem
+            .crop(&Bounds {
+                west: 1.0,
+                south: 1.0,
+                east: 2.0,
+                north: 2.0,
+            })
+            .is_none());
+    }
+}
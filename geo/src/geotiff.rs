@@ -1,10 +1,15 @@
 //! Code to read a GeoTIFF file using [`tiff`].
 
+// Names of the keys and their values follow the GeoTIFF specification.
+#![allow(clippy::enum_variant_names)]
+
 use eyre::{bail, Context, ContextCompat};
 use ndarray::Array2;
 use num_traits::{FromPrimitive, ToPrimitive};
 use std::{fs::File, path::Path};
-use tiff::tags::Tag;
+use tiff::{decoder::DecodingResult, tags::Tag};
+
+use crate::Dem;
 
 #[derive(Debug)]
 enum Value {
@@ -13,6 +18,7 @@ enum Value {
     Ascii(String),
 }
 
+#[allow(dead_code)]
 impl Value {
     pub fn as_short(&self) -> Option<u64> {
         if let Self::Short(value) = self {
@@ -356,6 +362,7 @@ enum GeographicCoordinateSystemType {
     GCSE_Sphere = 4035,
 }
 
+#[allow(dead_code)]
 impl GeographicCoordinateSystemType {
     pub fn to_code(&self) -> String {
         let code_number = ToPrimitive::to_i16(self).expect("Expect code to convert to u16");
@@ -376,6 +383,8 @@ enum AngularUnits {
     AngularDMS = 9107,
     AngularDMSHemisphere = 9108,
 }
+/// Keys which are not needed to load the DEM are still parsed, to validate the file.
+#[allow(dead_code)]
 #[derive(Debug)]
 enum GeoKey {
     /// This GeoKey defines the general type of model Coordinate system used, and to which the
@@ -444,6 +453,7 @@ enum GeoKey {
     GeogInvFlatteningGeoKey(f64),
 }
 
+#[allow(dead_code)]
 #[derive(Debug)]
 struct GeotiffHeader {
     key_directory_version: u64,
@@ -467,19 +477,26 @@ impl TryFrom<&[u64]> for GeotiffHeader {
     }
 }
 
-pub fn load(path: impl AsRef<Path>) -> eyre::Result<Array2<u8>> {
-    let f = File::open(path.as_ref())?;
+/// Elevations below this are treated as voids in the DEM (ASTER GDEM uses `-9999`, SRTM uses
+/// `-32768`).
+const MIN_ELEVATION: f32 = -1000.0;
+
+/// Load a single band GeoTIFF digital elevation model in a geographic (longitude, latitude)
+/// coordinate system.
+pub fn load(path: impl AsRef<Path>) -> eyre::Result<Dem> {
+    let path = path.as_ref();
+    let f = File::open(path).wrap_err_with(|| format!("Unable to open {path:?}"))?;
     let mut t = tiff::decoder::Decoder::new(f)?;
-    let ascii_params: String = t.get_tag_ascii_string(Tag::GeoAsciiParamsTag)?;
+    let ascii_params: String = t
+        .get_tag_ascii_string(Tag::GeoAsciiParamsTag)
+        .unwrap_or_default();
     let ascii_params_bytes = ascii_params.as_bytes();
-
-    let double_params: Vec<f64> = t.get_tag_f64_vec(Tag::GeoDoubleParamsTag)?;
-    println!("ascii_params: {ascii_params:?}");
-    println!("double_params: {double_params:?}");
+    let double_params: Vec<f64> = t
+        .get_tag_f64_vec(Tag::GeoDoubleParamsTag)
+        .unwrap_or_default();
 
     // http://geotiff.maptools.org/spec/geotiff2.4.html#2.4
     let key_directory = t.get_tag_u64_vec(Tag::GeoKeyDirectoryTag)?;
-    println!("tag:");
 
     if key_directory.len() % 4 != 0 {
         bail!("GeoKeyDirectoryTag has an invalid length");
@@ -489,7 +506,6 @@ pub fn load(path: impl AsRef<Path>) -> eyre::Result<Array2<u8>> {
         .next()
         .wrap_err("No header row in GeoKeyDirectoryTag")?;
     let header = GeotiffHeader::try_from(header)?;
-    dbg!(&header);
 
     let keys: Vec<GeoKey> = rows
         .map(|key| {
@@ -510,21 +526,17 @@ pub fn load(path: impl AsRef<Path>) -> eyre::Result<Array2<u8>> {
                     .wrap_err_with(|| format!("Unable to parse location as tag {location}"))?
                 {
                     Tag::GeoDoubleParamsTag => {
-                        let value: f64 = *double_params
-                            .get(
-                                usize::try_from(value_offset)
-                                    .context("Can't convert value_offset into usize")?,
-                            )
-                            .with_context(|| {
-                                format!("No double value for offset {value_offset}")
-                            })?;
+                        let value: f64 = *double_params.get(value_offset).with_context(|| {
+                            format!("No double value for offset {value_offset}")
+                        })?;
 
                         Value::Double(value)
                     }
                     Tag::GeoAsciiParamsTag => {
+                        // The count includes the `|` terminator.
                         let value_bytes = ascii_params_bytes
-                            .get(value_offset..count - 1)
-                            .wrap_err("Unable to get ascii bytest with key offset and count")?;
+                            .get(value_offset..value_offset + count.saturating_sub(1))
+                            .wrap_err("Unable to get ascii bytes with key offset and count")?;
 
                         let value = String::from_utf8(value_bytes.to_owned())
                             .context("ascii string is not valid utf8")?;
@@ -581,8 +593,6 @@ pub fn load(path: impl AsRef<Path>) -> eyre::Result<Array2<u8>> {
         bail!("expected {} keys, found {}", header.n_keys, keys.len());
     }
 
-    dbg!(&keys);
-
     let model_type = keys
         .iter()
         .find_map(|key| match key {
@@ -590,67 +600,65 @@ pub fn load(path: impl AsRef<Path>) -> eyre::Result<Array2<u8>> {
             _ => None,
         })
         .wrap_err("No model type")?;
-    let geographic_type = keys
-        .iter()
-        .find_map(|key| match key {
-            GeoKey::GeographicTypeGeoKey(geographic_type) => Some(geographic_type),
-            _ => None,
-        })
-        .wrap_err("No geographic coordinate system type")?;
-    dbg!(geographic_type);
+    if !matches!(model_type, ModelType::Geographic) {
+        bail!("Unsupported model type {model_type:?}, only geographic DEMs are supported");
+    }
+    let pixel_is_point = keys.iter().any(|key| {
+        matches!(
+            key,
+            GeoKey::GTRasterTypeGeoKey(RasterType::RasterPixelIsPoint)
+        )
+    });
 
+    // [I, J, K, X, Y, Z], raster position (I, J) is at model position (X, Y).
     let model_tie_point_tag = t.get_tag_f64_vec(Tag::ModelTiepointTag)?;
+    // [ScaleX, ScaleY, ScaleZ]
     let model_pixel_scale_tag = t.get_tag_f64_vec(Tag::ModelPixelScaleTag)?;
-    let width = t
+    if model_tie_point_tag.len() < 6 {
+        bail!("ModelTiepointTag has an invalid length");
+    }
+    if model_pixel_scale_tag.len() < 2 {
+        bail!("ModelPixelScaleTag has an invalid length");
+    }
+    let width: usize = t
         .get_tag_u64(Tag::ImageWidth)
-        .wrap_err("Unable to get image width")?;
-    let height = t
+        .wrap_err("Unable to get image width")?
+        .try_into()?;
+    let height: usize = t
         .get_tag_u64(Tag::ImageLength)
-        .wrap_err("Unable to get image length (height)")?;
-    dbg!(width);
-    dbg!(height);
-
-    // Project from CRS WGS84 to web mercator (I think?)
-    let from_proj =
-        proj4rs::Proj::from_proj_string(&format!("+proj=longlat +datum=WGS84 +no_defs +type=crs"))?;
-    let to_proj = proj4rs::Proj::from_proj_string("+proj=merc +a=6378137 +b=6378137 +lat_ts=0 +lon_0=0 +x_0=0 +y_0=0 +k=1 +units=m +nadgrids=@null +wktext +no_defs +type=crs")?;
-
-    dbg!(t.colortype()?);
-    let image = t.read_image()?;
-    let image = match image {
-        tiff::decoder::DecodingResult::I16(image) => image,
-        _ => todo!(),
+        .wrap_err("Unable to get image length (height)")?
+        .try_into()?;
+
+    let image: Vec<f32> = match t.read_image()? {
+        DecodingResult::I16(image) => image.into_iter().map(f32::from).collect(),
+        DecodingResult::U16(image) => image.into_iter().map(f32::from).collect(),
+        DecodingResult::I32(image) => image.into_iter().map(|value| value as f32).collect(),
+        DecodingResult::F32(image) => image,
+        DecodingResult::F64(image) => image.into_iter().map(|value| value as f32).collect(),
+        _ => bail!("Unsupported DEM sample format"),
     };
-
-    let (min, max) = image.iter().fold((i16::MAX, i16::MIN), |mut acc, pixel| {
-        if pixel < &acc.0 {
-            acc.0 = *pixel;
-        }
-        if pixel > &acc.1 {
-            acc.1 = *pixel;
-        }
-        acc
-    });
-
-    let array = Array2::from_shape_vec([width as usize, height as usize], image)?;
-
-    // Array2::from(image);
-
-    dbg!(&model_tie_point_tag);
-    dbg!(&model_pixel_scale_tag);
-
-    for ((x, y), value) in array.indexed_iter() {
-        let lat = (x as f64 * model_pixel_scale_tag[0]) + model_tie_point_tag[3];
-        let lon = (y as f64 * model_pixel_scale_tag[1]) + model_tie_point_tag[4];
-        let alt = *value as f64;
-        let mut point = (lat.to_radians(), lon.to_radians(), alt);
-        dbg!(point);
-        proj4rs::transform::transform(&from_proj, &to_proj, &mut point)?;
-        dbg!(point);
-    }
-
-    // Map to 256 range
-    let image =
-        array.mapv(|x| (((x as f64) - (min as f64)) * (256.0 / ((max - min) as f64))) as u8);
-    Ok(image)
+    let elevations = Array2::from_shape_vec((height, width), image)
+        .wrap_err("Image does not match its dimensions, only single band images are supported")?
+        .mapv_into(|elevation| {
+            if elevation < MIN_ELEVATION {
+                f32::NAN
+            } else {
+                elevation
+            }
+        });
+
+    let cell_width = model_pixel_scale_tag[0];
+    let cell_height = model_pixel_scale_tag[1];
+    // With pixel is point the tie point is at the centre of the cell rather than its corner.
+    let offset = if pixel_is_point { 0.5 } else { 0.0 };
+    let west = model_tie_point_tag[3] - (model_tie_point_tag[0] + offset) * cell_width;
+    let north = model_tie_point_tag[4] + (model_tie_point_tag[1] + offset) * cell_height;
+
+    Ok(Dem {
+        elevations,
+        west,
+        north,
+        cell_width,
+        cell_height,
+    })
 }
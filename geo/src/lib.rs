@@ -1,52 +1,112 @@
 use std::path::Path;
 
-// use gdal::raster::{ResampleAlg, StatisticsAll};
+use eyre::ContextCompat;
 use ndarray::Array2;
 
+mod dem;
 pub mod geotiff;
+pub mod mvt;
+mod polygon;
+mod projection;
+mod terrain;
+mod track;
 
+pub use dem::{distance_metres, Bounds, Dem, NO_DATA};
+pub use ndarray;
+pub use polygon::{
+    polygon_contains, polygons_bounds, ring_self_intersects, ring_signed_area, Polygon,
+};
+pub use projection::Projection;
+pub use terrain::{Aspect, RouteExposure, TerrainSummary, FLAT_SLOPE_DEGREES, STEEP_SLOPE_DEGREES};
+pub use track::{parse_tracks, Track};
+
+/// Render the elevations of the DEM at `path` as a greyscale image, scaled from the lowest
+/// (black) to the highest (white) elevation. Cells without data are black.
 pub fn render_elevations<P: AsRef<Path>>(path: P) -> eyre::Result<Array2<u8>> {
-    let image = geotiff::load(path)?;
-
-    // let path = path.as_ref();
-    // let dataset = gdal::Dataset::open(
-    //     path.as_os_str()
-    //         .to_str()
-    //         .wrap_err("Unable to convert path to str")?,
-    // )
-    // .wrap_err("Error opening")?;
-    // dbg!(dataset.raster_count());
-    // dbg!(dataset.raster_size());
-    //
-    // let scale = 1.0;
-    //
-    // let raster = dataset.rasterband(1).wrap_err("Error getting raster")?;
-    // let array_size = (
-    //     (raster.size().0 as f64 * scale) as usize,
-    //     (raster.size().1 as f64 * scale) as usize,
-    // );
-    // dbg!(array_size);
-    // let array = raster
-    //     .read_as_array::<i32>((0, 0), raster.size(), array_size, Some(ResampleAlg::Cubic))
-    //     .wrap_err("Error readig raster into array")?;
-    // dbg!(array.shape());
-    // let stats: StatisticsAll = raster
-    //     .get_statistics(true, true)?
-    //     .wrap_err("No statistics")?;
-    //
-    // let proj = dataset.projection();
-    // let xform = dataset.geo_transform()?;
-    // let spatial_ref = dataset.spatial_ref()?;
-    // dbg!(&spatial_ref);
-    // dbg!(&xform);
-    // dbg!(&proj);
-    // dbg!(&stats);
-
-    // Map to 256 range
-    // let image =
-    //     array.mapv(|x| (((x as f64) - (min as f64)) * (256.0 / ((max - min) as f64))) as u8);
-
-    // let image = array.mapv(|h| if h > 1000 && h < 2000 { 255u8 } else { 0u8 });
-
-    Ok(image)
+    let dem = Dem::load(path)?;
+    let (min, max) = dem
+        .elevations
+        .iter()
+        .filter(|elevation| !elevation.is_nan())
+        .fold((f32::MAX, f32::MIN), |(min, max), elevation| {
+            (min.min(*elevation), max.max(*elevation))
+        });
+    let range = (max - min).max(1.0);
+    Ok(dem.elevations.mapv(|elevation| {
+        if elevation.is_nan() {
+            0
+        } else {
+            ((elevation - min) * (255.0 / range)) as u8
+        }
+    }))
+}
+
+/// Classify the cells of the DEM at `dem_path` by elevation band, see [`Dem::elevation_bands`].
+pub fn elevation_bands<P: AsRef<Path>>(
+    dem_path: P,
+    boundaries: &[f64],
+) -> eyre::Result<Array2<u8>> {
+    Ok(Dem::load(dem_path)?.elevation_bands(boundaries))
+}
+
+/// Summarise the terrain inside `polygon` of the DEM at `dem_path` by aspect and elevation band,
+/// see [`Dem::terrain_summary`].
+pub fn terrain_summary<P: AsRef<Path>>(
+    dem_path: P,
+    polygon: &Polygon,
+    boundaries: &[f64],
+) -> eyre::Result<TerrainSummary> {
+    let polygons = std::slice::from_ref(polygon);
+    let bounds = polygons_bounds(polygons).wrap_err("Polygon has no positions")?;
+    let dem = Dem::load(dem_path)?
+        .crop(&bounds)
+        .wrap_err("DEM does not cover the polygon")?;
+    let mask = dem.polygon_mask(polygons);
+    Ok(dem.terrain_summary(&mask, boundaries))
+}
+
+#[cfg(test)]
+mod test {
+    use super::{terrain_summary, Dem};
+
+    const FIXTURE: &str = concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/fixtures/ASTGTMV003_N42E044_dem.tif"
+    );
+
+    #[test]
+    fn test_load_fixture() {
+        let dem = Dem::load(FIXTURE).unwrap();
+        let bounds = dem.bounds();
+        assert!((bounds.west - 44.0).abs() < 0.001, "{bounds:?}");
+        assert!((bounds.east - 45.0).abs() < 0.001, "{bounds:?}");
+        assert!((bounds.south - 42.0).abs() < 0.001, "{bounds:?}");
+        assert!((bounds.north - 43.0).abs() < 0.001, "{bounds:?}");
+
+        // Mount Kazbek (5054m) is in this tile.
+        let max = dem
+            .elevations
+            .iter()
+            .copied()
+            .filter(|elevation| !elevation.is_nan())
+            .fold(f32::MIN, f32::max);
+        assert!((4900.0..5200.0).contains(&max), "{max}");
+    }
+
+    #[test]
+    fn test_terrain_summary_fixture() {
+        // Around Gudauri.
+        let polygon = vec![vec![
+            (44.42, 42.42),
+            (44.52, 42.42),
+            (44.52, 42.52),
+            (44.42, 42.52),
+            (44.42, 42.42),
+        ]];
+        let summary = terrain_summary(FIXTURE, &polygon, &[1800.0, 2700.0]).unwrap();
+        // Approximately 8.2km x 11.1km.
+        let total_area = summary.total_area();
+        assert!((80e6..100e6).contains(&total_area), "{total_area}");
+        assert_eq!(summary.aspect_area.len(), 3);
+    }
 }
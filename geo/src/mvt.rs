@@ -0,0 +1,370 @@
+//! Encoding polygons as Mapbox Vector Tiles <https://github.com/mapbox/vector-tile-spec>, in the
+//! XYZ (slippy map) tiling scheme using the web mercator projection.
+
+use crate::{Bounds, Polygon};
+
+/// Size of the tile's coordinate space.
+const EXTENT: u32 = 4096;
+/// Distance outside the tile that geometry is kept, to avoid rendering artifacts at the edges.
+const BUFFER: f64 = 64.0;
+
+/// Rings of tile coordinates, the first is the exterior ring.
+type TilePolygon = Vec<Vec<(i32, i32)>>;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Point {
+    x: f64,
+    y: f64,
+}
+
+/// A tile in the XYZ (slippy map) tiling scheme.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TileId {
+    pub z: u8,
+    pub x: u32,
+    pub y: u32,
+}
+
+impl TileId {
+    /// Whether the tile exists at its zoom level, which is at most `max_zoom`.
+    pub fn is_valid(&self, max_zoom: u8) -> bool {
+        self.z <= max_zoom.min(31) && self.x < (1 << self.z) && self.y < (1 << self.z)
+    }
+
+    /// The longitude and latitude (WGS84) bounds of the tile.
+    pub fn bounds(&self) -> Bounds {
+        let n = f64::from(1u32 << self.z);
+        let longitude = |x: u32| f64::from(x) / n * 360.0 - 180.0;
+        let latitude = |y: u32| {
+            (std::f64::consts::PI * (1.0 - 2.0 * f64::from(y) / n))
+                .sinh()
+                .atan()
+                .to_degrees()
+        };
+        Bounds {
+            west: longitude(self.x),
+            south: latitude(self.y + 1),
+            east: longitude(self.x + 1),
+            north: latitude(self.y),
+        }
+    }
+
+    /// Project a longitude and latitude (WGS84) into this tile's coordinates using the web
+    /// mercator projection.
+    fn project(&self, longitude: f64, latitude: f64) -> Point {
+        let n = f64::from(1u32 << self.z);
+        let latitude = latitude.clamp(-85.051_128_78, 85.051_128_78).to_radians();
+        let x = (longitude + 180.0) / 360.0 * n;
+        let y =
+            (1.0 - (latitude.tan() + 1.0 / latitude.cos()).ln() / std::f64::consts::PI) / 2.0 * n;
+        Point {
+            x: (x - f64::from(self.x)) * f64::from(EXTENT),
+            y: (y - f64::from(self.y)) * f64::from(EXTENT),
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+enum Edge {
+    Left,
+    Right,
+    Top,
+    Bottom,
+}
+
+/// Clip a ring to the tile (including the buffer) using the Sutherland–Hodgman algorithm.
+fn clip_ring(ring: &[Point]) -> Vec<Point> {
+    let min = -BUFFER;
+    let max = f64::from(EXTENT) + BUFFER;
+    let mut output = ring.to_vec();
+    for edge in [Edge::Left, Edge::Right, Edge::Top, Edge::Bottom] {
+        let input = std::mem::take(&mut output);
+        if input.is_empty() {
+            break;
+        }
+        let inside = |point: Point| match edge {
+            Edge::Left => point.x >= min,
+            Edge::Right => point.x <= max,
+            Edge::Top => point.y >= min,
+            Edge::Bottom => point.y <= max,
+        };
+        let intersect = |a: Point, b: Point| match edge {
+            Edge::Left | Edge::Right => {
+                let x = if matches!(edge, Edge::Left) { min } else { max };
+                let t = (x - a.x) / (b.x - a.x);
+                Point {
+                    x,
+                    y: a.y + t * (b.y - a.y),
+                }
+            }
+            Edge::Top | Edge::Bottom => {
+                let y = if matches!(edge, Edge::Top) { min } else { max };
+                let t = (y - a.y) / (b.y - a.y);
+                Point {
+                    x: a.x + t * (b.x - a.x),
+                    y,
+                }
+            }
+        };
+        for (i, current) in input.iter().copied().enumerate() {
+            let previous = input[(i + input.len() - 1) % input.len()];
+            match (inside(current), inside(previous)) {
+                (true, true) => output.push(current),
+                (true, false) => {
+                    output.push(intersect(previous, current));
+                    output.push(current);
+                }
+                (false, true) => output.push(intersect(previous, current)),
+                (false, false) => {}
+            }
+        }
+    }
+    output
+}
+
+/// Twice the signed area of the ring using the surveyor's formula. Positive for a clockwise
+/// ring in tile coordinates (where the y axis points down).
+fn signed_area(ring: &[(i32, i32)]) -> i64 {
+    ring.iter()
+        .zip(ring.iter().cycle().skip(1))
+        .map(|(a, b)| i64::from(a.0) * i64::from(b.1) - i64::from(b.0) * i64::from(a.1))
+        .sum()
+}
+
+/// Convert a ring of longitude, latitude positions into integer tile coordinates, clipped to the
+/// tile. Returns [`None`] if nothing of the ring remains.
+fn tile_ring(tile: &TileId, ring: &[(f64, f64)], exterior: bool) -> Option<Vec<(i32, i32)>> {
+    let mut points: Vec<Point> = ring
+        .iter()
+        .map(|(longitude, latitude)| tile.project(*longitude, *latitude))
+        .collect();
+    // GeoJSON rings repeat the first position at the end, the closing point is implicit in the
+    // vector tile encoding.
+    if points.len() > 1 && points.first() == points.last() {
+        points.pop();
+    }
+    let mut ring: Vec<(i32, i32)> = Vec::new();
+    for point in clip_ring(&points) {
+        let point = (point.x.round() as i32, point.y.round() as i32);
+        if ring.last() != Some(&point) {
+            ring.push(point);
+        }
+    }
+    while ring.len() > 1 && ring.first() == ring.last() {
+        ring.pop();
+    }
+    let area = signed_area(&ring);
+    if ring.len() < 3 || area == 0 {
+        return None;
+    }
+    // Exterior rings must be clockwise, and interior rings anticlockwise.
+    if (area > 0) != exterior {
+        ring.reverse();
+    }
+    Some(ring)
+}
+
+fn command(id: u32, count: u32) -> u32 {
+    (id & 0x7) | (count << 3)
+}
+
+fn zigzag(value: i32) -> u32 {
+    ((value << 1) ^ (value >> 31)) as u32
+}
+
+/// Encode polygons (lists of rings in tile coordinates) into vector tile geometry commands.
+fn encode_polygons(polygons: &[TilePolygon]) -> Vec<u32> {
+    const MOVE_TO: u32 = 1;
+    const LINE_TO: u32 = 2;
+    const CLOSE_PATH: u32 = 7;
+    let mut geometry = Vec::new();
+    let mut cursor = (0, 0);
+    for ring in polygons.iter().flatten() {
+        for (i, point) in ring.iter().enumerate() {
+            match i {
+                0 => geometry.push(command(MOVE_TO, 1)),
+                1 => geometry.push(command(LINE_TO, (ring.len() - 1) as u32)),
+                _ => {}
+            }
+            geometry.push(zigzag(point.0 - cursor.0));
+            geometry.push(zigzag(point.1 - cursor.1));
+            cursor = *point;
+        }
+        geometry.push(command(CLOSE_PATH, 1));
+    }
+    geometry
+}
+
+/// Minimal protocol buffers encoding for the vector tile messages.
+mod protobuf {
+    const VARINT: u64 = 0;
+    const LENGTH_DELIMITED: u64 = 2;
+
+    pub fn write_varint(buffer: &mut Vec<u8>, mut value: u64) {
+        while value >= 0x80 {
+            buffer.push((value as u8 & 0x7f) | 0x80);
+            value >>= 7;
+        }
+        buffer.push(value as u8);
+    }
+
+    pub fn write_varint_field(buffer: &mut Vec<u8>, field: u64, value: u64) {
+        write_varint(buffer, (field << 3) | VARINT);
+        write_varint(buffer, value);
+    }
+
+    pub fn write_bytes_field(buffer: &mut Vec<u8>, field: u64, bytes: &[u8]) {
+        write_varint(buffer, (field << 3) | LENGTH_DELIMITED);
+        write_varint(buffer, bytes.len() as u64);
+        buffer.extend_from_slice(bytes);
+    }
+
+    pub fn write_packed_field(buffer: &mut Vec<u8>, field: u64, values: &[u32]) {
+        let mut packed = Vec::new();
+        for value in values {
+            write_varint(&mut packed, u64::from(*value));
+        }
+        write_bytes_field(buffer, field, &packed);
+    }
+}
+
+/// A feature of a tile layer, with a single string property.
+pub struct Feature<'a> {
+    pub polygons: &'a [Polygon],
+    pub value: &'a str,
+}
+
+/// Encode a vector tile with a layer named `layer` containing the `features` clipped to the
+/// tile, with each feature's value in the `key` property. Empty if none of the features are in
+/// the tile.
+pub fn encode_tile(tile: &TileId, layer: &str, key: &str, features: &[Feature]) -> Vec<u8> {
+    const POLYGON: u64 = 3;
+    let mut messages = Vec::new();
+    let mut values: Vec<&str> = Vec::new();
+    for feature in features {
+        let polygons: Vec<TilePolygon> = feature
+            .polygons
+            .iter()
+            .filter_map(|rings| {
+                let (exterior, interiors) = rings.split_first()?;
+                let mut polygon = vec![tile_ring(tile, exterior, true)?];
+                polygon.extend(
+                    interiors
+                        .iter()
+                        .filter_map(|interior| tile_ring(tile, interior, false)),
+                );
+                Some(polygon)
+            })
+            .collect();
+        if polygons.is_empty() {
+            continue;
+        }
+        let value_index = match values.iter().position(|value| *value == feature.value) {
+            Some(index) => index,
+            None => {
+                values.push(feature.value);
+                values.len() - 1
+            }
+        };
+        let mut message = Vec::new();
+        protobuf::write_varint_field(&mut message, 1, messages.len() as u64 + 1);
+        protobuf::write_packed_field(&mut message, 2, &[0, value_index as u32]);
+        protobuf::write_varint_field(&mut message, 3, POLYGON);
+        protobuf::write_packed_field(&mut message, 4, &encode_polygons(&polygons));
+        messages.push(message);
+    }
+
+    let mut tile_message = Vec::new();
+    if messages.is_empty() {
+        return tile_message;
+    }
+    let mut layer_message = Vec::new();
+    protobuf::write_varint_field(&mut layer_message, 15, 2);
+    protobuf::write_bytes_field(&mut layer_message, 1, layer.as_bytes());
+    for message in &messages {
+        protobuf::write_bytes_field(&mut layer_message, 2, message);
+    }
+    protobuf::write_bytes_field(&mut layer_message, 3, key.as_bytes());
+    for value in values {
+        let mut value_message = Vec::new();
+        protobuf::write_bytes_field(&mut value_message, 1, value.as_bytes());
+        protobuf::write_bytes_field(&mut layer_message, 4, &value_message);
+    }
+    protobuf::write_varint_field(&mut layer_message, 5, u64::from(EXTENT));
+    protobuf::write_bytes_field(&mut tile_message, 3, &layer_message);
+    tile_message
+}
+
+#[cfg(test)]
+mod test {
+    use super::{encode_polygons, encode_tile, signed_area, tile_ring, zigzag, Feature, TileId};
+
+    #[test]
+    fn test_zigzag() {
+        assert_eq!(zigzag(0), 0);
+        assert_eq!(zigzag(-1), 1);
+        assert_eq!(zigzag(1), 2);
+        assert_eq!(zigzag(-2), 3);
+    }
+
+    #[test]
+    fn test_encode_polygons() {
+        // Example from the vector tile specification.
+        let geometry = encode_polygons(&[vec![vec![(3, 6), (8, 12), (20, 34)]]]);
+        assert_eq!(geometry, vec![9, 6, 12, 18, 10, 12, 24, 44, 15]);
+    }
+
+    #[test]
+    fn test_tile_ring() {
+        let tile = TileId { z: 0, x: 0, y: 0 };
+        // Covering part of the north east quadrant, extending beyond the antimeridian.
+        let ring = [
+            (0.0, 0.0),
+            (0.0, 80.0),
+            (200.0, 80.0),
+            (200.0, 0.0),
+            (0.0, 0.0),
+        ];
+        let exterior = tile_ring(&tile, &ring, true).unwrap();
+        assert!(signed_area(&exterior) > 0);
+        assert!(exterior.iter().all(|(x, _)| *x <= 4096 + 64));
+        let interior = tile_ring(&tile, &ring, false).unwrap();
+        assert!(signed_area(&interior) < 0);
+
+        let outside = tile_ring(&TileId { z: 2, x: 0, y: 0 }, &ring, true);
+        assert!(outside.is_none());
+    }
+
+    #[test]
+    fn test_tile_bounds() {
+        let bounds = TileId { z: 1, x: 1, y: 0 }.bounds();
+        assert_eq!((bounds.west, bounds.east), (0.0, 180.0));
+        assert!(bounds.south.abs() < 1e-9, "{bounds:?}");
+        assert!((bounds.north - 85.051_128_78).abs() < 1e-6, "{bounds:?}");
+        assert!(!TileId { z: 1, x: 2, y: 0 }.is_valid(22));
+        assert!(!TileId { z: 15, x: 0, y: 0 }.is_valid(14));
+    }
+
+    #[test]
+    fn test_encode_tile() {
+        let polygons = [vec![vec![
+            (40.0, 40.0),
+            (50.0, 40.0),
+            (50.0, 50.0),
+            (40.0, 40.0),
+        ]]];
+        let features = [Feature {
+            polygons: &polygons,
+            value: "gudauri",
+        }];
+        let tile = TileId { z: 0, x: 0, y: 0 };
+        let data = encode_tile(&tile, "forecast_areas", "area", &features);
+        // Tile message with the layers field.
+        assert_eq!(data[0], (3 << 3) | 2);
+        assert!(data
+            .windows(b"gudauri".len())
+            .any(|window| window == b"gudauri"));
+        let tile = TileId { z: 10, x: 0, y: 0 };
+        assert!(encode_tile(&tile, "forecast_areas", "area", &features).is_empty());
+    }
+}
@@ -0,0 +1,199 @@
+//! Polygons in longitude, latitude coordinates.
+
+use crate::Bounds;
+
+/// Rings of longitude, latitude positions, the first is the exterior ring and the rest are
+/// holes.
+pub type Polygon = Vec<Vec<(f64, f64)>>;
+
+/// The bounds of all the positions in the polygons, or `None` if they have no positions.
+pub fn polygons_bounds(polygons: &[Polygon]) -> Option<Bounds> {
+    polygons
+        .iter()
+        .flatten()
+        .flatten()
+        .fold(None, |bounds, &(longitude, latitude)| {
+            Some(match bounds {
+                None => Bounds {
+                    west: longitude,
+                    south: latitude,
+                    east: longitude,
+                    north: latitude,
+                },
+                Some(bounds) => Bounds {
+                    west: bounds.west.min(longitude),
+                    south: bounds.south.min(latitude),
+                    east: bounds.east.max(longitude),
+                    north: bounds.north.max(latitude),
+                },
+            })
+        })
+}
+
+/// Whether the position is inside the ring, using ray casting.
+fn ring_contains(ring: &[(f64, f64)], longitude: f64, latitude: f64) -> bool {
+    let mut inside = false;
+    for (i, &(x_i, y_i)) in ring.iter().enumerate() {
+        let (x_j, y_j) = ring[(i + ring.len() - 1) % ring.len()];
+        if (y_i > latitude) != (y_j > latitude)
+            && longitude < (x_j - x_i) * (latitude - y_i) / (y_j - y_i) + x_i
+        {
+            inside = !inside;
+        }
+    }
+    inside
+}
+
+/// Whether the position is inside the polygon's exterior ring and outside its holes.
+pub fn polygon_contains(polygon: &Polygon, longitude: f64, latitude: f64) -> bool {
+    polygon
+        .iter()
+        .filter(|ring| ring_contains(ring, longitude, latitude))
+        .count()
+        % 2
+        == 1
+}
+
+/// The signed area of a closed ring in square degrees, using the shoelace formula. Positive when
+/// the ring is anticlockwise (with longitude to the right and latitude up).
+pub fn ring_signed_area(ring: &[(f64, f64)]) -> f64 {
+    ring.iter()
+        .zip(ring.iter().skip(1))
+        .map(|((x_a, y_a), (x_b, y_b))| x_a * y_b - x_b * y_a)
+        .sum::<f64>()
+        / 2.0
+}
+
+/// The orientation of the triangle `a`, `b`, `c`: positive when anticlockwise, negative when
+/// clockwise and zero when collinear.
+fn orientation(a: (f64, f64), b: (f64, f64), c: (f64, f64)) -> f64 {
+    (b.0 - a.0) * (c.1 - a.1) - (b.1 - a.1) * (c.0 - a.0)
+}
+
+/// Whether `point`, which is collinear with the segment `a`, `b`, lies on the segment.
+fn on_segment(a: (f64, f64), b: (f64, f64), point: (f64, f64)) -> bool {
+    point.0 >= a.0.min(b.0)
+        && point.0 <= a.0.max(b.0)
+        && point.1 >= a.1.min(b.1)
+        && point.1 <= a.1.max(b.1)
+}
+
+/// Whether the segments `a`, `b` and `c`, `d` intersect or touch.
+fn segments_intersect(a: (f64, f64), b: (f64, f64), c: (f64, f64), d: (f64, f64)) -> bool {
+    let o1 = orientation(a, b, c);
+    let o2 = orientation(a, b, d);
+    let o3 = orientation(c, d, a);
+    let o4 = orientation(c, d, b);
+    if ((o1 > 0.0 && o2 < 0.0) || (o1 < 0.0 && o2 > 0.0))
+        && ((o3 > 0.0 && o4 < 0.0) || (o3 < 0.0 && o4 > 0.0))
+    {
+        return true;
+    }
+    (o1 == 0.0 && on_segment(a, b, c))
+        || (o2 == 0.0 && on_segment(a, b, d))
+        || (o3 == 0.0 && on_segment(c, d, a))
+        || (o4 == 0.0 && on_segment(c, d, b))
+}
+
+/// Whether any two edges of a closed ring (the first position is repeated as the last) cross or
+/// touch, other than adjacent edges at their shared position. Edges are swept from west to east
+/// so that only edges with overlapping longitudes are compared.
+pub fn ring_self_intersects(ring: &[(f64, f64)]) -> bool {
+    let edges: Vec<((f64, f64), (f64, f64))> = ring
+        .iter()
+        .copied()
+        .zip(ring.iter().copied().skip(1))
+        .collect();
+    let mut order: Vec<usize> = (0..edges.len()).collect();
+    order.sort_by(|i, j| {
+        let (a, b) = edges[*i];
+        let (c, d) = edges[*j];
+        a.0.min(b.0).total_cmp(&c.0.min(d.0))
+    });
+    for (position, &i) in order.iter().enumerate() {
+        let (a, b) = edges[i];
+        let east = a.0.max(b.0);
+        for &j in &order[position + 1..] {
+            let (c, d) = edges[j];
+            if c.0.min(d.0) > east {
+                break;
+            }
+            let (first, second) = (i.min(j), i.max(j));
+            let next = second == first + 1;
+            let wraps = first == 0 && second == edges.len() - 1;
+            if next || wraps {
+                // Adjacent edges share a position, they only intersect if they overlap.
+                let (far, shared, other) = if next {
+                    (edges[first].0, edges[first].1, edges[second].1)
+                } else {
+                    (edges[first].1, edges[first].0, edges[second].0)
+                };
+                if orientation(far, shared, other) == 0.0
+                    && (on_segment(far, shared, other) || on_segment(shared, other, far))
+                {
+                    return true;
+                }
+                continue;
+            }
+            if segments_intersect(a, b, c, d) {
+                return true;
+            }
+        }
+    }
+    false
+}
+
+#[cfg(test)]
+mod test {
+    use crate::Bounds;
+
+    use super::{polygon_contains, polygons_bounds, ring_self_intersects, ring_signed_area};
+
+    #[test]
+    fn test_polygon_contains() {
+        let polygon = vec![
+            vec![(0.0, 0.0), (4.0, 0.0), (4.0, 4.0), (0.0, 4.0), (0.0, 0.0)],
+            vec![(1.0, 1.0), (2.0, 1.0), (2.0, 2.0), (1.0, 2.0), (1.0, 1.0)],
+        ];
+        assert!(polygon_contains(&polygon, 3.0, 3.0));
+        assert!(!polygon_contains(&polygon, 1.5, 1.5));
+        assert!(!polygon_contains(&polygon, 5.0, 3.0));
+    }
+
+    #[test]
+    fn test_polygons_bounds() {
+        let polygons = vec![
+            vec![vec![(0.0, 0.0), (4.0, 0.0), (4.0, 4.0), (0.0, 0.0)]],
+            vec![vec![(-1.0, 2.0), (1.0, 5.0), (0.0, 2.0), (-1.0, 2.0)]],
+        ];
+        assert_eq!(
+            polygons_bounds(&polygons),
+            Some(Bounds {
+                west: -1.0,
+                south: 0.0,
+                east: 4.0,
+                north: 5.0,
+            })
+        );
+        assert_eq!(polygons_bounds(&[]), None);
+    }
+
+    #[test]
+    fn test_ring_signed_area() {
+        let anticlockwise = [(0.0, 0.0), (2.0, 0.0), (2.0, 2.0), (0.0, 2.0), (0.0, 0.0)];
+        assert_eq!(ring_signed_area(&anticlockwise), 4.0);
+        let clockwise: Vec<(f64, f64)> = anticlockwise.iter().rev().copied().collect();
+        assert_eq!(ring_signed_area(&clockwise), -4.0);
+    }
+
+    #[test]
+    fn test_ring_self_intersects() {
+        let square = [(0.0, 0.0), (2.0, 0.0), (2.0, 2.0), (0.0, 2.0), (0.0, 0.0)];
+        assert!(!ring_self_intersects(&square));
+        let bow_tie = [(0.0, 0.0), (2.0, 2.0), (2.0, 0.0), (0.0, 2.0), (0.0, 0.0)];
+        assert!(ring_self_intersects(&bow_tie));
+        // Doubles back along its first edge.
+        let spike = [(0.0, 0.0), (2.0, 0.0), (1.0, 0.0), (1.0, 1.0), (0.0, 0.0)];
+        assert!(ring_self_intersects(&spike));
+    }
+}
@@ -0,0 +1,151 @@
+//! Conversion of projected coordinates to WGS84 longitude, latitude.
+
+use crate::dem::{WGS84_A, WGS84_E2};
+
+/// Scale factor on the central meridian of a UTM zone.
+const UTM_SCALE_FACTOR: f64 = 0.9996;
+const UTM_FALSE_EASTING: f64 = 500_000.0;
+/// False northing of the UTM zones in the southern hemisphere.
+const UTM_SOUTH_FALSE_NORTHING: f64 = 10_000_000.0;
+
+/// A projected coordinate reference system which can be converted to WGS84.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Projection {
+    /// Web Mercator (EPSG:3857).
+    WebMercator,
+    /// WGS84 Universal Transverse Mercator (EPSG:326xx in the northern hemisphere, EPSG:327xx
+    /// in the southern hemisphere).
+    Utm { zone: u8, north: bool },
+}
+
+impl Projection {
+    /// The projection for an EPSG code, or `None` if it's not supported.
+    pub fn from_epsg(code: u32) -> Option<Self> {
+        match code {
+            3857 | 900913 => Some(Self::WebMercator),
+            32601..=32660 => Some(Self::Utm {
+                zone: (code - 32600) as u8,
+                north: true,
+            }),
+            32701..=32760 => Some(Self::Utm {
+                zone: (code - 32700) as u8,
+                north: false,
+            }),
+            _ => None,
+        }
+    }
+
+    /// Convert projected `(x, y)` (easting, northing) metres to `(longitude, latitude)` degrees.
+    pub fn to_wgs84(&self, x: f64, y: f64) -> (f64, f64) {
+        match *self {
+            Self::WebMercator => (
+                (x / WGS84_A).to_degrees(),
+                (2.0 * (y / WGS84_A).exp().atan() - std::f64::consts::FRAC_PI_2).to_degrees(),
+            ),
+            Self::Utm { zone, north } => utm_to_wgs84(zone, north, x, y),
+        }
+    }
+}
+
+/// The inverse transverse Mercator projection, using the series from Snyder's "Map
+/// Projections: A Working Manual" (1987).
+fn utm_to_wgs84(zone: u8, north: bool, easting: f64, northing: f64) -> (f64, f64) {
+    let e2 = WGS84_E2;
+    let second_e2 = e2 / (1.0 - e2);
+    let central_meridian = f64::from(zone) * 6.0 - 183.0;
+    let x = easting - UTM_FALSE_EASTING;
+    let y = if north {
+        northing
+    } else {
+        northing - UTM_SOUTH_FALSE_NORTHING
+    };
+
+    let meridional_arc = y / UTM_SCALE_FACTOR;
+    let mu = meridional_arc
+        / (WGS84_A * (1.0 - e2 / 4.0 - 3.0 * e2.powi(2) / 64.0 - 5.0 * e2.powi(3) / 256.0));
+    let e1 = (1.0 - (1.0 - e2).sqrt()) / (1.0 + (1.0 - e2).sqrt());
+    // Latitude of the foot point on the central meridian.
+    let phi = mu
+        + (3.0 * e1 / 2.0 - 27.0 * e1.powi(3) / 32.0) * (2.0 * mu).sin()
+        + (21.0 * e1.powi(2) / 16.0 - 55.0 * e1.powi(4) / 32.0) * (4.0 * mu).sin()
+        + (151.0 * e1.powi(3) / 96.0) * (6.0 * mu).sin()
+        + (1097.0 * e1.powi(4) / 512.0) * (8.0 * mu).sin();
+
+    let w = 1.0 - e2 * phi.sin().powi(2);
+    let n = WGS84_A / w.sqrt();
+    let t = phi.tan().powi(2);
+    let c = second_e2 * phi.cos().powi(2);
+    let r = WGS84_A * (1.0 - e2) / w.powf(1.5);
+    let d = x / (n * UTM_SCALE_FACTOR);
+
+    let latitude = phi
+        - (n * phi.tan() / r)
+            * (d.powi(2) / 2.0
+                - (5.0 + 3.0 * t + 10.0 * c - 4.0 * c.powi(2) - 9.0 * second_e2) * d.powi(4)
+                    / 24.0
+                + (61.0 + 90.0 * t + 298.0 * c + 45.0 * t.powi(2)
+                    - 252.0 * second_e2
+                    - 3.0 * c.powi(2))
+                    * d.powi(6)
+                    / 720.0);
+    let longitude = (d - (1.0 + 2.0 * t + c) * d.powi(3) / 6.0
+        + (5.0 - 2.0 * c + 28.0 * t - 3.0 * c.powi(2) + 8.0 * second_e2 + 24.0 * t.powi(2))
+            * d.powi(5)
+            / 120.0)
+        / phi.cos();
+    (
+        central_meridian + longitude.to_degrees(),
+        latitude.to_degrees(),
+    )
+}
+
+#[cfg(test)]
+mod test {
+    use super::Projection;
+
+    fn assert_position(actual: (f64, f64), expected: (f64, f64)) {
+        assert!(
+            (actual.0 - expected.0).abs() < 1e-7 && (actual.1 - expected.1).abs() < 1e-7,
+            "{actual:?} != {expected:?}"
+        );
+    }
+
+    #[test]
+    fn test_from_epsg() {
+        assert_eq!(Projection::from_epsg(3857), Some(Projection::WebMercator));
+        assert_eq!(
+            Projection::from_epsg(32638),
+            Some(Projection::Utm {
+                zone: 38,
+                north: true
+            })
+        );
+        assert_eq!(
+            Projection::from_epsg(32719),
+            Some(Projection::Utm {
+                zone: 19,
+                north: false
+            })
+        );
+        assert_eq!(Projection::from_epsg(4326), None);
+        assert_eq!(Projection::from_epsg(32661), None);
+    }
+
+    #[test]
+    fn test_web_mercator_to_wgs84() {
+        assert_position(
+            Projection::WebMercator.to_wgs84(4953717.340, 5236173.784),
+            (44.5, 42.5),
+        );
+    }
+
+    #[test]
+    fn test_utm_to_wgs84() {
+        let zone_38n = Projection::from_epsg(32638).unwrap();
+        // On the central meridian.
+        assert_position(zone_38n.to_wgs84(500000.0, 4649776.225), (45.0, 42.0));
+        assert_position(zone_38n.to_wgs84(458916.887, 4705414.187), (44.5, 42.5));
+        let zone_19s = Projection::from_epsg(32719).unwrap();
+        assert_position(zone_19s.to_wgs84(360505.620, 6303362.288), (-70.5, -33.4));
+    }
+}
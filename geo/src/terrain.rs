@@ -0,0 +1,110 @@
+//! Classification of terrain by aspect and elevation band.
+
+/// Slopes flatter than this (degrees) are considered flat and have no aspect in a
+/// [`TerrainSummary`].
+pub const FLAT_SLOPE_DEGREES: f64 = 5.0;
+/// Slopes at least this steep (degrees) are steep enough to avalanche, see
+/// [`RouteExposure::steep_distance`].
+pub const STEEP_SLOPE_DEGREES: f64 = 30.0;
+
+/// The direction that a slope faces, in eight sectors of 45°.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Aspect {
+    N,
+    NE,
+    E,
+    SE,
+    S,
+    SW,
+    W,
+    NW,
+}
+
+impl Aspect {
+    pub const ALL: [Aspect; 8] = [
+        Aspect::N,
+        Aspect::NE,
+        Aspect::E,
+        Aspect::SE,
+        Aspect::S,
+        Aspect::SW,
+        Aspect::W,
+        Aspect::NW,
+    ];
+
+    /// The aspect sector containing `degrees` (clockwise from north).
+    pub fn from_degrees(degrees: f64) -> Self {
+        let index = ((degrees.rem_euclid(360.0) + 22.5) / 45.0) as usize % Self::ALL.len();
+        Self::ALL[index]
+    }
+}
+
+/// The area of terrain in each elevation band by aspect, see [`crate::Dem::terrain_summary`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct TerrainSummary {
+    /// The boundaries between the elevation bands, see [`crate::Dem::elevation_bands`].
+    pub boundaries: Vec<f64>,
+    /// The area (square metres) of terrain in each elevation band, indexed by [`Aspect`].
+    pub aspect_area: Vec<[f64; 8]>,
+    /// The area (square metres) of flat terrain in each elevation band, see
+    /// [`FLAT_SLOPE_DEGREES`].
+    pub flat_area: Vec<f64>,
+}
+
+impl TerrainSummary {
+    /// The area of terrain in the elevation band with the aspect.
+    pub fn area(&self, band: usize, aspect: Aspect) -> f64 {
+        self.aspect_area
+            .get(band)
+            .map_or(0.0, |areas| areas[aspect as usize])
+    }
+
+    /// The total area of the terrain, including flat terrain.
+    pub fn total_area(&self) -> f64 {
+        self.aspect_area.iter().flatten().sum::<f64>() + self.flat_area.iter().sum::<f64>()
+    }
+}
+
+/// The distance of a route in each elevation band by aspect, see
+/// [`crate::Dem::route_exposure`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct RouteExposure {
+    /// The boundaries between the elevation bands, see [`crate::Dem::elevation_bands`].
+    pub boundaries: Vec<f64>,
+    /// The distance (metres) of the route in each elevation band, indexed by [`Aspect`].
+    pub aspect_distance: Vec<[f64; 8]>,
+    /// The distance (metres) of the route in each elevation band on slopes of at least
+    /// [`STEEP_SLOPE_DEGREES`], indexed by [`Aspect`]. Included in `aspect_distance`.
+    pub steep_distance: Vec<[f64; 8]>,
+    /// The distance (metres) of the route in each elevation band on flat terrain, see
+    /// [`FLAT_SLOPE_DEGREES`].
+    pub flat_distance: Vec<f64>,
+    /// The distance (metres) of the route outside the DEM or over cells without data.
+    pub outside_distance: f64,
+    /// The steepest slope (degrees) that the route crosses.
+    pub max_slope_degrees: f64,
+}
+
+impl RouteExposure {
+    /// The total distance of the route, including the distance outside the DEM.
+    pub fn total_distance(&self) -> f64 {
+        self.aspect_distance.iter().flatten().sum::<f64>()
+            + self.flat_distance.iter().sum::<f64>()
+            + self.outside_distance
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::Aspect;
+
+    #[test]
+    fn test_aspect_from_degrees() {
+        assert_eq!(Aspect::from_degrees(0.0), Aspect::N);
+        assert_eq!(Aspect::from_degrees(350.0), Aspect::N);
+        assert_eq!(Aspect::from_degrees(22.6), Aspect::NE);
+        assert_eq!(Aspect::from_degrees(180.0), Aspect::S);
+        assert_eq!(Aspect::from_degrees(-90.0), Aspect::W);
+        assert_eq!(Aspect::from_degrees(315.0), Aspect::NW);
+    }
+}
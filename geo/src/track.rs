@@ -0,0 +1,180 @@
+//! Routes recorded or planned in GPS exchange format (GPX) or Keyhole markup language (KML)
+//! files.
+
+use eyre::{Context, ContextCompat};
+
+/// A line of longitude, latitude positions.
+pub type Track = Vec<(f64, f64)>;
+
+/// Parse the tracks and routes in a GPX or KML document. Each GPX track segment or route, and
+/// each KML `LineString` or `gx:Track` is a separate [`Track`]. Waypoints and placemark points
+/// are ignored.
+pub fn parse_tracks(xml: &str) -> eyre::Result<Vec<Track>> {
+    let document = roxmltree::Document::parse(xml).wrap_err("Invalid XML")?;
+    let root = document.root_element();
+    let tracks = match root.tag_name().name() {
+        "gpx" => parse_gpx(root)?,
+        "kml" => parse_kml(root)?,
+        name => eyre::bail!("Unsupported document {name:?}, expected GPX or KML"),
+    };
+    let tracks: Vec<Track> = tracks
+        .into_iter()
+        .filter(|track| !track.is_empty())
+        .collect();
+    if tracks.is_empty() {
+        eyre::bail!("Document contains no tracks or routes");
+    }
+    Ok(tracks)
+}
+
+fn parse_coordinate(value: &str, name: &str) -> eyre::Result<f64> {
+    value
+        .trim()
+        .parse()
+        .wrap_err_with(|| format!("Invalid {name} {value:?}"))
+}
+
+fn check_position(longitude: f64, latitude: f64) -> eyre::Result<(f64, f64)> {
+    if !(-180.0..=180.0).contains(&longitude) || !(-90.0..=90.0).contains(&latitude) {
+        eyre::bail!("Position ({longitude}, {latitude}) is out of range");
+    }
+    Ok((longitude, latitude))
+}
+
+fn parse_gpx(root: roxmltree::Node) -> eyre::Result<Vec<Track>> {
+    root.descendants()
+        .filter(|node| matches!(node.tag_name().name(), "trkseg" | "rte"))
+        .map(|line| {
+            line.children()
+                .filter(|node| matches!(node.tag_name().name(), "trkpt" | "rtept"))
+                .map(|point| {
+                    let attribute = |name: &str| {
+                        parse_coordinate(
+                            point
+                                .attribute(name)
+                                .wrap_err_with(|| format!("Point is missing {name}"))?,
+                            name,
+                        )
+                    };
+                    check_position(attribute("lon")?, attribute("lat")?)
+                })
+                .collect()
+        })
+        .collect()
+}
+
+/// Parse a `longitude,latitude[,altitude]` KML coordinate tuple, or the space separated
+/// `longitude latitude altitude` of a `gx:coord`.
+fn parse_kml_position(tuple: &str, separator: char) -> eyre::Result<(f64, f64)> {
+    let mut values = tuple.split(separator);
+    let longitude = parse_coordinate(
+        values
+            .next()
+            .wrap_err_with(|| format!("Invalid coordinates {tuple:?}"))?,
+        "longitude",
+    )?;
+    let latitude = parse_coordinate(
+        values
+            .next()
+            .wrap_err_with(|| format!("Invalid coordinates {tuple:?}"))?,
+        "latitude",
+    )?;
+    check_position(longitude, latitude)
+}
+
+fn parse_kml(root: roxmltree::Node) -> eyre::Result<Vec<Track>> {
+    let mut tracks = Vec::new();
+    for node in root.descendants() {
+        match node.tag_name().name() {
+            "LineString" => {
+                let coordinates = node
+                    .children()
+                    .find(|child| child.tag_name().name() == "coordinates")
+                    .and_then(|coordinates| coordinates.text())
+                    .unwrap_or_default();
+                tracks.push(
+                    coordinates
+                        .split_whitespace()
+                        .map(|tuple| parse_kml_position(tuple, ','))
+                        .collect::<eyre::Result<Track>>()?,
+                );
+            }
+            "Track" => tracks.push(
+                node.children()
+                    .filter(|child| child.tag_name().name() == "coord")
+                    .map(|coord| parse_kml_position(coord.text().unwrap_or_default().trim(), ' '))
+                    .collect::<eyre::Result<Track>>()?,
+            ),
+            _ => {}
+        }
+    }
+    Ok(tracks)
+}
+
+#[cfg(test)]
+mod test {
+    use super::parse_tracks;
+
+    #[test]
+    fn test_parse_gpx() {
+        let gpx = r#"<?xml version="1.0" encoding="UTF-8"?>
+<gpx version="1.1" creator="test" xmlns="http://www.topografix.com/GPX/1/1">
+  <wpt lat="42.5" lon="44.5"><name>Summit</name></wpt>
+  <trk>
+    <trkseg>
+      <trkpt lat="42.47" lon="44.48"><ele>2200</ele></trkpt>
+      <trkpt lat="42.48" lon="44.49"><ele>2500</ele></trkpt>
+    </trkseg>
+    <trkseg>
+      <trkpt lat="42.49" lon="44.50"/>
+    </trkseg>
+  </trk>
+  <rte>
+    <rtept lat="42.40" lon="44.40"/>
+  </rte>
+</gpx>"#;
+        assert_eq!(
+            parse_tracks(gpx).unwrap(),
+            vec![
+                vec![(44.48, 42.47), (44.49, 42.48)],
+                vec![(44.50, 42.49)],
+                vec![(44.40, 42.40)],
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_kml() {
+        let kml = r#"<?xml version="1.0" encoding="UTF-8"?>
+<kml xmlns="http://www.opengis.net/kml/2.2" xmlns:gx="http://www.google.com/kml/ext/2.2">
+  <Document>
+    <Placemark><Point><coordinates>44.5,42.5,0</coordinates></Point></Placemark>
+    <Placemark>
+      <LineString>
+        <coordinates>
+          44.48,42.47,2200 44.49,42.48,2500
+        </coordinates>
+      </LineString>
+    </Placemark>
+    <Placemark>
+      <gx:Track>
+        <when>2024-01-01T10:00:00Z</when>
+        <gx:coord>44.50 42.49 2600</gx:coord>
+      </gx:Track>
+    </Placemark>
+  </Document>
+</kml>"#;
+        assert_eq!(
+            parse_tracks(kml).unwrap(),
+            vec![vec![(44.48, 42.47), (44.49, 42.48)], vec![(44.50, 42.49)]]
+        );
+    }
+
+    #[test]
+    fn test_parse_tracks_invalid() {
+        assert!(parse_tracks("<html></html>").is_err());
+        assert!(parse_tracks("<gpx><trk><trkseg></trkseg></trk></gpx>").is_err());
+        assert!(parse_tracks(r#"<gpx><rte><rtept lat="95" lon="0"/></rte></gpx>"#).is_err());
+        assert!(parse_tracks(r#"<gpx><rte><rtept lat="x" lon="0"/></rte></gpx>"#).is_err());
+    }
+}
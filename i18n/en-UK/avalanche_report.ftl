@@ -11,6 +11,8 @@ check-forecast-archive-below = Please check the forecast archive below for the m
 forecast-area-heading = Forecast Area
 # The Gudauri forecast area name
 forecast-area-gudauri = Gudauri
+# Option to show the forecasts for all areas
+forecast-area-all = All Areas
 # The title used for the index page.
 index-title = Gudauri Avalanche Forecast
 latest-forecast-heading = Latest Forecast
@@ -232,6 +234,10 @@ confidence-high-about = The forecaster's confidence level is High about this par
 # Message with disclaimer and information at the start of the printed page.
 # REMINDER FOR PROGRAMMER: If this disclaimer message is updated, the DISCLAIMER_VERSION in disclaimer.rs should also be updated so the user needs to accept the updated disclaimer.
 disclaimer-message = Our avalanche forecasters are internationally qualified and experienced professionals, and data is provided by skilled observers. We encourage you to make your own observations and decisions, without relying solely on our forecast, since any forecast is a generalised 'best guess', and in certain cases it might be inaccurate. We can not be held liable for any actions you take in the backcountry that may result in injury, loss or death.
+# Notice displayed below forecast text that was automatically translated from the language it was written in.
+machine-translated-notice = This text was machine translated.
+# Notice displayed below forecast text which isn't available in the user's language, `language` is the name of the language it is displayed in.
+text-language-notice = (in { $language })
 # Field on the forecast page that specifies the person who created the forecast.
 forecast-forecaster = **Forecaster:** {$name}
 # Heading for the name of the person who created this forecast
@@ -242,6 +248,20 @@ forecast-issued-at = **Forecast issued at:** {$time}
 # Field on the forecast page that specifies the date and time that the
 # forecast is valid until
 forecast-valid-until = **Forecast valid until:** {$time}
+# Label for the date and time that the forecast was issued, used in the PDF bulletin.
+forecast-issued-at-label = Forecast issued at
+# Label for the date and time that the forecast is valid until, used in the PDF bulletin.
+forecast-valid-until-label = Forecast valid until
+# Label for the vertical axis of a snow profile diagram, the height above the ground in centimetres.
+snow-profile-height-label = Height (cm)
+# Label for the horizontal axis of a snow profile diagram, the hand hardness of the snow layers.
+snow-profile-hardness-label = Hand Hardness
+# Label for the axis of a snow profile diagram showing the snow temperature in degrees Celsius.
+snow-profile-temperature-label = Temperature (°C)
+# Short column heading for the grain type of each layer in a snow profile diagram.
+snow-profile-grain-type-label = Grain
+# Short column heading for the grain size in millimetres of each layer in a snow profile diagram.
+snow-profile-grain-size-label = Size (mm)
 # Caption for the aspect/elevation chart.
 aspect-elevation-chart-caption = Aspect/Elevation
 # Text inside the Back button.
@@ -280,6 +300,10 @@ about-description =
     welcome! Feel free to contact the team there if you wish to deploy
     something similar for your forecast region.
 wind-unit-label = Wind Unit:
+# Label for the select input to choose the unit used to display temperatures
+temperature-unit-label = Temperature Unit:
+# Label for the select input to choose the unit used to display elevations
+elevation-unit-label = Elevation Unit:
 sponsors-title = Sponsors
 # Label for the speed of the wind
 wind-speed-label = Wind Speed
@@ -297,6 +321,202 @@ weather-station-kudebi_top-label = Kudebi Weather Station (3000 m)
 weather-station-lower_gudauri-label = Lower Gudauri Station (1950 m)
 # Label for the weather station located at AltiHut at elevation of 3000 m
 weather-station-altihut-label = AltiHut Weather Station (3000 m)
+# Heading for the automated weather model forecast for the forecast area
+weather-model-heading = Weather Model Forecast
+# Explanation that the weather model forecast is automated and not reviewed by a forecaster
+weather-model-description = Automated forecast from the open-meteo.com weather model, it has not been reviewed by a forecaster.
+# Label for the date column of the weather model forecast
+weather-model-date-label = Date
+# Label for the total snowfall for the day
+weather-model-snowfall-label = Snowfall
+# Label for the range of the height of the freezing level for the day
+weather-model-freezing-level-label = Freezing Level
+# Label for the maximum wind at ridge-top elevation for the day
+weather-model-ridge-wind-label = Ridge-top Wind
+# Label for the precipitation rate panel of the weather station meteogram diagram
+meteogram-precipitation-label = Precipitation (mm/h)
+# Label for the time axis of the weather station meteogram diagram, times are in UTC
+meteogram-time-utc-label = Time (UTC)
+# Message displayed on the meteogram diagram when the weather station has no recent data
+meteogram-no-data-message = No recent data available
+# Label for the proportion of observations with calm wind (no direction) on the weather station wind rose diagram
+wind-rose-calm-label = Calm: { $percent }%
+# Caption for the weather station wind rose diagram, showing how often the wind blew from each direction over the last 24 hours
+wind-rose-caption = Wind Direction (last 24 hours)
+# Heading for the section displaying the latest images from the webcams
+webcams-heading = Webcams
+# Alternative text for the latest image from a webcam, $name is the name of the webcam
+webcam-image-alt = Latest image from the { $name } webcam
+# Label for the map overlay which colours the terrain of the forecast area by the hazard rating for each elevation band
+elevation-bands-overlay-label = Hazard by Elevation
+# Label for the map overlay which shades slopes steeper than 30°, 35° and 40°
+slope-overlay-label = Slope Angle (30°+, 35°+, 40°+)
+# Proportion of the forecast area's terrain that is in the elevations and aspects where the avalanche problem is found, calculated from a digital elevation model
+problem-terrain-percent = Approximately { $percent }% of the terrain in the forecast area is in the affected elevations and aspects.
+# Link to the page where users can upload a GPX or KML file of their route to see how much of it crosses the terrain affected by the avalanche problems in the forecast
+route-exposure-link = Check your route against this forecast
+# Warning shown when the forecasts can't currently be updated, the time is when they were last updated
+stale-data-warning = Unable to check for new forecasts, the forecasts shown may be out of date (last updated { $time }).
+# Warning shown on a forecast which will soon no longer be valid, followed by a countdown of the hours and minutes remaining
+forecast-expiring-soon-warning = This forecast expires soon.
+# Warning shown on a forecast which is no longer valid
+forecast-expired-warning = This forecast has expired and should not be relied upon.
+# Warning shown on a forecast when a newer forecast has been published for the same area, links to the latest forecasts
+forecast-superseded-warning = A newer forecast has been published for this area.
+# Title for the news page, which lists advisories and season summaries published outside of the forecasts
+news-title = News
+# Message on the news page when no posts have been published
+news-no-posts-message = No news has been published yet.
+# Title for the status page, which shows how up to date the forecasts and weather station data are
+status-title = Status
+# Message on the status page when the forecasts are being checked for updates normally
+status-operational-message = All systems are operating normally.
+# Heading for the table of the latest forecast for each area on the status page
+status-forecasts-heading = Forecasts
+# Label for the column with the time of the latest forecast for the area on the status page
+status-latest-forecast-label = Latest Forecast
+# Label for the column with when the forecast for the area was last updated on the status page
+status-updated-label = Last Updated
+# Label for the column with the name of the weather station on the status page
+status-weather-station-label = Weather Station
+# Label for the column with when the weather station last reported data on the status page
+status-last-reported-label = Last Reported
+# Shown on the status page when a weather station has not reported any recent data
+status-no-data-message = No recent data
+# Shown on the status page next to a weather station which has not reported data for a while
+status-stale-label = delayed
+# Title for the education pages, which explain the terms used in forecasts
+education-title = Learn
+# Introduction to the education pages
+education-intro-message = Learn about the avalanche problems and danger levels used in our forecasts.
+# Heading for the list of avalanche problem types on the education page
+education-problems-heading = Avalanche Problems
+# Heading for the list of danger levels on the education page
+education-danger-levels-heading = Avalanche Danger Scale
+# Heading for the description of what an avalanche problem is and how it forms
+education-what-is-it-heading = What is it?
+# Heading for advice on how to travel in terrain where the avalanche problem or danger level is present
+education-travel-advice-heading = Travel Advice
+# Heading for the typical avalanche sizes and distribution for a danger level
+education-size-distribution-heading = Avalanche Size and Distribution
+# Link from a forecast to the education page about an avalanche problem or danger level
+education-learn-more-link = Learn more
+# How loose dry avalanches form and behave
+education-problem-loose-dry-description = Loose dry avalanches start at a point and spread out as they descend, gathering snow in a fan shape. They occur in dry, cohesionless snow, usually during or soon after snowfall, or when cold temperatures cause the surface snow to lose its bonds. They are usually small, but can be dangerous in steep terrain or above terrain traps where even a small avalanche can carry you into rocks, trees or a gully.
+# Advice for travelling in terrain with loose dry avalanches
+education-problem-loose-dry-travel-advice = Avoid steep terrain with new snow, especially above cliffs, gullies and other terrain traps. Manage your sluff by moving across the slope, and watch for small point releases as a sign that the surface snow is unstable.
+# How loose wet avalanches form and behave
+education-problem-loose-wet-description = Loose wet avalanches start at a point and spread out as they descend, in snow that has been weakened by the presence of liquid water. They are caused by warming from the sun, warm air temperatures or rain, and are most common on sunny slopes in the afternoon. They move slowly but are heavy, and can entrain a lot of snow.
+# Advice for travelling in terrain with loose wet avalanches
+education-problem-loose-wet-travel-advice = Plan to be off and out from under steep sunny slopes before the snow surface becomes wet and soft. Roller balls, pinwheels and sinking deeply into wet snow are signs that the danger is increasing.
+# How storm slab avalanches form and behave
+education-problem-storm-slab-description = Storm slab avalanches are the release of a cohesive layer of new snow that has formed during a storm. They are most likely during and in the first days after heavy snowfall, until the new snow has had time to bond to the old snow surface.
+# Advice for travelling in terrain with storm slab avalanches
+education-problem-storm-slab-travel-advice = Give the new snow time to settle and stabilise before entering steep terrain. Watch for cracking around your skis or sled and recent avalanches, and stick to lower angle terrain during and after storms.
+# How wind slab avalanches form and behave
+education-problem-wind-slab-description = Wind slab avalanches are the release of a cohesive layer of snow that has been deposited by the wind. Wind slabs form on the leeward side of ridges and in gullies, and can be found on any slope where wind has drifted snow. They often feel stiff or hollow, and can have a smooth, rounded appearance.
+# Advice for travelling in terrain with wind slab avalanches
+education-problem-wind-slab-travel-advice = Identify and avoid areas of wind drifted snow, such as below ridges, in gullies and near cornices. Shooting cracks and hollow sounding snow are signs of a wind slab.
+# How wet slab avalanches form and behave
+education-problem-wet-slab-description = Wet slab avalanches are the release of a cohesive layer of snow that has lost its bond to a weaker layer because of liquid water in the snowpack. They are caused by prolonged warming or rain, and are very difficult to predict. They can be very destructive, and may run long distances.
+# Advice for travelling in terrain with wet slab avalanches
+education-problem-wet-slab-travel-advice = Avoid avalanche terrain and runout zones when the snowpack is wet and warming, particularly during the first significant warm spell of the season or after rain on snow.
+# How persistent slab avalanches form and behave
+education-problem-persistent-slab-description = Persistent slab avalanches are the release of a cohesive layer of snow over a persistent weak layer, such as buried surface hoar, depth hoar or facets. These weak layers can remain reactive for weeks or months after they are buried, and can be triggered from a distance or from thin areas of the slab. They are difficult to manage because signs of instability may be absent.
+# Advice for travelling in terrain with persistent slab avalanches
+education-problem-persistent-slab-travel-advice = Choose conservative terrain and keep a wide margin for error, as these avalanches can surprise even experienced people. Avoid thin or variable snowpack areas where triggering is more likely, and expose only one person at a time to avalanche terrain.
+# How deep slab avalanches form and behave
+education-problem-deep-slab-description = Deep slab avalanches are the release of a thick, cohesive layer of snow over a weak layer deep in the snowpack, usually near the ground. They are unlikely to be triggered, but when they are they can be very large and destructive. They are often triggered from shallow areas of the snowpack, or by large loads such as cornice falls or smaller avalanches.
+# Advice for travelling in terrain with deep slab avalanches
+education-problem-deep-slab-travel-advice = Avoid the slopes where deep slab avalanches are possible, including their runout zones, as tests and observations are unreliable indicators of the likelihood of triggering one.
+# How cornice avalanches form and behave
+education-problem-cornice-description = Cornices are overhanging ledges of wind drifted snow that form on the leeward side of ridges. A cornice can break off under the weight of a person, or naturally during warming or rapid growth, and can trigger an avalanche on the slope below.
+# Advice for travelling in terrain with cornices
+education-problem-cornice-travel-advice = Stay well back from the edge of ridges where cornices may be present, as they can break much further back than expected. Avoid travelling on slopes below cornices, especially during warm weather.
+# How glide avalanches form and behave
+education-problem-glide-description = Glide avalanches are the release of the entire snowpack as it slides on the ground, usually on smooth surfaces such as grass or rock slabs. They are often preceded by glide cracks opening in the snowpack, but can release at any time of day and are very difficult to predict.
+# Advice for travelling in terrain with glide avalanches
+education-problem-glide-travel-advice = Avoid spending time on or below slopes with glide cracks, as there is no reliable way to predict when they will release.
+# Advice for travelling in terrain when there is no avalanche danger rating
+education-danger-level-no-rating-travel-advice = Assess the snowpack and terrain for yourself, and look for other sources of information about current conditions.
+# Advice for travelling in terrain with a low avalanche danger rating
+education-danger-level-low-travel-advice = Travel is generally safe. Watch for unstable snow on isolated terrain features, and be careful above terrain traps.
+# Advice for travelling in terrain with a moderate avalanche danger rating
+education-danger-level-moderate-travel-advice = Evaluate the snow and terrain carefully, and identify the features of concern described in the forecast.
+# Advice for travelling in terrain with a considerable avalanche danger rating
+education-danger-level-considerable-travel-advice = Careful snowpack evaluation, cautious route-finding and conservative decision-making are essential. Most avalanche fatalities occur at this level.
+# Advice for travelling in terrain with a high avalanche danger rating
+education-danger-level-high-travel-advice = Travel in avalanche terrain is not recommended. Stay on low angle slopes well away from the runout zones of steeper terrain.
+# Advice for travelling in terrain with an extreme avalanche danger rating
+education-danger-level-extreme-travel-advice = Avoid all avalanche terrain, including runout zones which may be reached by very large avalanches.
+# Title for the search page, for searching forecasts, observations and news
+search-title = Search
+# Placeholder text for the search input
+search-placeholder = e.g. wind slab
+# Button to submit a search
+search-button = Search
+# Message shown when a search doesn't match anything
+search-no-results-message = Nothing was found matching your search.
+# Label for search results which are forecasts
+search-result-forecast-label = Forecast
+# Label for search results which are observations
+search-result-observation-label = Observation
+# Label for search results which are news posts
+search-result-news-label = News
+# Title of search results which are observations
+search-observation-title = Avalanche and Snowpack Observation
+# Title for the statistics page, which shows statistics about the forecasts and observations for each season
+statistics-title = Statistics
+# Label for selecting the season to show statistics for
+statistics-season-label = Season
+# Button to show the statistics for the selected season
+statistics-show-button = Show
+# Number of forecasts published during the season
+statistics-forecasts-count = Forecasts: { $count }
+# Number of observations submitted during the season
+statistics-observations-count = Observations: { $count }
+# Heading for the chart of the number of forecasts for each area
+statistics-forecasts-per-area-heading = Forecasts per Area
+# Heading for the chart of the number of forecasts with each overall danger rating
+statistics-danger-ratings-heading = Overall Danger Ratings
+# Heading for the chart of the number of forecasts including each avalanche problem
+statistics-problems-heading = Most Frequent Avalanche Problems
+# Message shown when there are no forecasts or observations to show statistics for
+statistics-no-data-message = There are no statistics available yet.
+# Link to download the statistics for all seasons as JSON
+statistics-json-link = Download as JSON
+# Title for the page comparing two forecasts
+forecast-compare-title = Forecast Comparison
+# Description of the two forecasts being compared
+forecast-compare-description = Changes between the forecast for { $area } issued { $from } and the forecast issued { $to }.
+# Heading for the changes in the hazard ratings between two forecasts
+forecast-compare-hazard-ratings-heading = Hazard Rating Changes
+# Heading for the avalanche problems which were added in the later forecast
+forecast-compare-problems-added-heading = New Avalanche Problems
+# Heading for the avalanche problems which were removed in the later forecast
+forecast-compare-problems-removed-heading = Avalanche Problems No Longer Present
+# Heading for the avalanche problems present in both forecasts
+forecast-compare-problems-continued-heading = Continuing Avalanche Problems
+# Shown when a characteristic of an avalanche problem didn't change between forecasts
+forecast-compare-no-change = No change
+# Shown when there is nothing in a list of changes between forecasts
+forecast-compare-none = None
+# Link to download the forecast comparison as JSON
+forecast-compare-json-link = Download as JSON
+# Label for the primary (dominant) avalanche problem of a forecast
+problem-primary-label = Primary Problem
+# Link in the embeddable forecast widget to the full forecast
+widget-view-forecast-link = View the full forecast
+# Shown in the embeddable forecast widget when there is no forecast for the area
+widget-no-forecast-message = There is currently no forecast available for this area.
+# Shown in the embeddable forecast widget when the latest forecast is no longer valid
+widget-expired-message = This forecast has expired.
+# Theme option which follows the theme preferred by the browser
+theme-auto = 🌓 Automatic
+# Theme option for a light colour theme
+theme-light = ☀️ Light
+# Theme option for a dark colour theme
+theme-dark = 🌙 Dark
 # Title for the donations section
 donations-title = Support/Donate
 # Description for the donations section
@@ -321,3 +541,130 @@ donation-description =
     ACCOUNT: GE78TB7640836120100005
     Address: 5 Luarsab Sharashidze Street, T'bilisi 0108, Georgia
     ```
+# Label for the short link to the forecast, for sharing
+short-link-label = Short link:
+# Link to a QR code image of the forecast's short link, for printing
+short-link-qr-code-link = QR code
+# Subject of the email sent when a new forecast is published for an area
+email-forecast-notification-subject = New avalanche forecast for { $area }
+# Footer of the forecast notification emails
+email-forecast-notification-footer = You are receiving this email because you subscribed to avalanche forecast notifications.
+# Link to unsubscribe from the notification emails
+email-unsubscribe-link = Unsubscribe
+# Subject of the email sent to the operators when a background task fails repeatedly
+email-alert-failing-subject = [{ $site }] { $task } is failing
+# Subject of the email sent to the operators when a failing background task recovers
+email-alert-recovered-subject = [{ $site }] { $task } has recovered
+email-alert-failing-message = { $task } has failed { $count } times in a row.
+email-alert-recovered-message = { $task } is working again.
+# Accessible label for the language selector
+language-select-label = Language
+# Names of the languages that forecasts may be written in, used in `text-language-notice`
+language-name-en = English
+language-name-ka = Georgian
+language-name-bg = Bulgarian
+language-name-cs = Czech
+language-name-it = Italian
+language-name-pl = Polish
+language-name-ru = Russian
+language-name-zh = Chinese
+# Accessible label for the navigation menu at the top of the pages
+navigation-label = Main menu
+# Navigation menu link to the index page with the current forecasts
+navigation-forecasts = Forecasts
+# Navigation menu link to the submitted field observations
+navigation-observations = Observations
+# Navigation menu link to the news posts
+navigation-news = News
+# Navigation menu link to the education pages about avalanche problems and the danger scale
+navigation-education = Learn
+# Navigation menu link to the statistics page
+navigation-statistics = Statistics
+# Navigation menu link to the search page
+navigation-search = Search
+# Navigation menu link to the page crediting the forecasters
+navigation-forecasters = Forecasters
+# Title of the page crediting the forecasters
+forecasters-title = Our Forecasters
+# Message on the forecasters page when no forecasters have been added
+forecasters-empty-message = No forecasters have been added yet.
+# Glossary term: the compass direction that a slope faces. Used to find the term in forecast text.
+glossary-aspect = aspect
+# Glossary definition of the term in glossary-aspect, shown as a tooltip.
+glossary-aspect-definition = The compass direction that a slope faces, e.g. a north facing slope.
+# Glossary term: a range of elevations that the forecast gives a danger rating for.
+glossary-elevation-band = elevation zone
+# Glossary definition of the term in glossary-elevation-band.
+glossary-elevation-band-definition = A range of elevations which the forecast rates separately, because the snowpack and weather differ with elevation.
+# Glossary term: a cohesive layer of snow.
+glossary-slab = slab
+# Glossary definition of the term in glossary-slab.
+glossary-slab-definition = A layer of snow which is stuck together, and can break away as a block from the weaker snow beneath it.
+# Glossary term: a layer of snow which a slab can slide on.
+glossary-weak-layer = weak layer
+# Glossary definition of the term in glossary-weak-layer.
+glossary-weak-layer-definition = A layer within the snowpack with poor bonding, which can collapse or fail under the weight of the snow above.
+# Glossary term: a weak layer which remains weak for a long time.
+glossary-persistent-weak-layer = persistent weak layer
+# Glossary definition of the term in glossary-persistent-weak-layer.
+glossary-persistent-weak-layer-definition = A weak layer which can remain weak for weeks or even the whole season, such as surface hoar or depth hoar.
+# Glossary term: snow transported by the wind onto a slope.
+glossary-wind-loading = wind loading
+# Glossary definition of the term in glossary-wind-loading.
+glossary-wind-loading-definition = Snow blown by the wind and deposited on the sheltered side of ridges and features, often forming slabs.
+# Glossary term: an overhanging drift of snow on a ridge.
+glossary-cornice = cornice
+# Glossary definition of the term in glossary-cornice.
+glossary-cornice-definition = An overhanging mass of wind-blown snow on the edge of a ridge, which can break off unexpectedly.
+# Glossary term: the sound of a weak layer collapsing.
+glossary-whumpf = whumpf
+# Glossary definition of the term in glossary-whumpf.
+glossary-whumpf-definition = The sound of a weak layer collapsing under the snowpack, a clear sign of unstable snow.
+# Glossary term: cracks which spread through the snow from where someone is standing.
+glossary-shooting-cracks = shooting cracks
+# Glossary definition of the term in glossary-shooting-cracks.
+glossary-shooting-cracks-definition = Cracks which shoot out through the snow surface around you, a clear sign that a slab could be triggered.
+# Glossary term: a terrain feature which makes being caught by an avalanche more serious.
+glossary-terrain-trap = terrain trap
+# Glossary definition of the term in glossary-terrain-trap.
+glossary-terrain-trap-definition = A feature such as a gully, cliff or trees, where the consequences of being caught by even a small avalanche are much more serious.
+# Glossary term: where an avalanche slows down and stops.
+glossary-runout = runout
+# Glossary definition of the term in glossary-runout.
+glossary-runout-definition = The area at the bottom of a slope where an avalanche slows down and its debris comes to rest.
+# Glossary term: feathery ice crystals which form on the snow surface.
+glossary-surface-hoar = surface hoar
+# Glossary definition of the term in glossary-surface-hoar.
+glossary-surface-hoar-definition = Feathery ice crystals which grow on the snow surface on clear, calm nights, and form a persistent weak layer once buried.
+# Glossary term: large weak crystals at the bottom of the snowpack.
+glossary-depth-hoar = depth hoar
+# Glossary definition of the term in glossary-depth-hoar.
+glossary-depth-hoar-definition = Large, poorly bonded crystals which grow near the ground in a shallow, cold snowpack, and form a persistent weak layer.
+# Glossary term: a hard layer of snow.
+glossary-crust = crust
+# Glossary definition of the term in glossary-crust.
+glossary-crust-definition = A hard layer of snow formed by melting and refreezing, rain or wind, which snow above it can slide on.
+# Title for the page with charts of the avalanches reported in observations during a season
+avalanche-activity-title = Avalanche Activity
+# Total number of avalanches reported in observations during the season
+avalanche-activity-count = Reported avalanches: { $count } ({ $natural } natural, { $triggered } triggered)
+# Heading for the chart of the number of avalanches reported on each day of the season
+avalanche-activity-days-heading = Avalanches by Day
+# Heading for the chart of the number of avalanches reported of each size
+avalanche-activity-sizes-heading = Avalanches by Size
+# Heading for the chart of the number of avalanches reported for each avalanche problem
+avalanche-activity-problems-heading = Avalanches by Avalanche Problem
+# Label for the axis of the avalanche activity charts showing the number of avalanches
+avalanche-activity-count-label = Avalanches
+# Legend label for avalanches which released naturally
+avalanche-activity-natural-label = Natural
+# Legend label for avalanches which were triggered by people or explosives
+avalanche-activity-triggered-label = Triggered
+# Label for avalanches which were reported without a size or avalanche problem
+avalanche-activity-unknown-label = Unknown
+# Message shown when no avalanches have been reported in observations
+avalanche-activity-no-data-message = No avalanches have been reported this season.
+# Link to download the avalanche activity for all seasons as JSON
+avalanche-activity-json-link = Download as JSON
+# Link to the avalanche activity page from the observations page
+observations-activity-link = Avalanche Activity
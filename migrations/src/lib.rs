@@ -14,19 +14,17 @@ pub const DATETIME_CONFIG: iso8601::EncodedConfig = iso8601::Config::DEFAULT
     })
     .encode();
 pub const DATETIME_FORMAT: Iso8601<DATETIME_CONFIG> = Iso8601;
+pub const DB_FILE_NAME: &str = "db.sqlite3";
 
 mod v2_analytics_time_format;
 mod v3_analytics_uri_parameters;
 
+type RustMigration =
+    Box<dyn Fn(sqlx::SqlitePool) -> Pin<Box<dyn std::future::Future<Output = eyre::Result<()>>>>>;
+
 enum MigrationKind {
     Sql(&'static str),
-    Rust(
-        Box<
-            dyn Fn(
-                sqlx::SqlitePool,
-            ) -> Pin<Box<dyn std::future::Future<Output = eyre::Result<()>>>>,
-        >,
-    ),
+    Rust(RustMigration),
 }
 
 impl MigrationKind {
@@ -52,6 +50,20 @@ fn blue(string: &str) -> String {
 }
 
 impl Migration {
+    /// Base64 encoded SHA-256 hash of the migration's SQL, `None` for rust migrations.
+    fn checksum(&self) -> Option<String> {
+        match self.kind {
+            MigrationKind::Sql(sql) => {
+                let mut hasher = Sha256::new();
+                hasher.update(sql);
+                let result = hasher.finalize();
+                let engine = base64::engine::general_purpose::STANDARD_NO_PAD;
+                Some(engine.encode(result))
+            }
+            MigrationKind::Rust(_) => None,
+        }
+    }
+
     #[tracing::instrument(skip_all, fields(version = self.version))]
     async fn run(&self, conn: &sqlx::SqlitePool) -> eyre::Result<()> {
         tracing::info!("Running migration {}", self.name);
@@ -127,6 +139,141 @@ fn list_migrations() -> Vec<Migration> {
             name: "analytics_not_null",
             kind: MigrationKind::Sql(include_str!("v8_analytics_not_null.sql")),
         },
+        Migration {
+            version: 9,
+            name: "observations",
+            kind: MigrationKind::Sql(include_str!("v9_observations.sql")),
+        },
+        Migration {
+            version: 10,
+            name: "observation_snow_profiles",
+            kind: MigrationKind::Sql(include_str!("v10_observation_snow_profiles.sql")),
+        },
+        Migration {
+            version: 11,
+            name: "weather_forecast_cache",
+            kind: MigrationKind::Sql(include_str!("v11_weather_forecast_cache.sql")),
+        },
+        Migration {
+            version: 12,
+            name: "forecast_area_tile_cache",
+            kind: MigrationKind::Sql(include_str!("v12_forecast_area_tile_cache.sql")),
+        },
+        Migration {
+            version: 13,
+            name: "users",
+            kind: MigrationKind::Sql(include_str!("v13_users.sql")),
+        },
+        Migration {
+            version: 14,
+            name: "published_files_cache",
+            kind: MigrationKind::Sql(include_str!("v14_published_files_cache.sql")),
+        },
+        Migration {
+            version: 15,
+            name: "analytics_visitors",
+            kind: MigrationKind::Sql(include_str!("v15_analytics_visitors.sql")),
+        },
+        Migration {
+            version: 16,
+            name: "machine_translation_cache",
+            kind: MigrationKind::Sql(include_str!("v16_machine_translation_cache.sql")),
+        },
+        Migration {
+            version: 17,
+            name: "uploaded_forecast_files",
+            kind: MigrationKind::Sql(include_str!("v17_uploaded_forecast_files.sql")),
+        },
+        Migration {
+            version: 18,
+            name: "database_maintenance",
+            kind: MigrationKind::Sql(include_str!("v18_database_maintenance.sql")),
+        },
+        Migration {
+            version: 19,
+            name: "backups",
+            kind: MigrationKind::Sql(include_str!("v19_backups.sql")),
+        },
+        Migration {
+            version: 20,
+            name: "backup_destinations",
+            kind: MigrationKind::Sql(include_str!("v20_backup_destinations.sql")),
+        },
+        Migration {
+            version: 21,
+            name: "news_posts",
+            kind: MigrationKind::Sql(include_str!("v21_news_posts.sql")),
+        },
+        Migration {
+            version: 22,
+            name: "pages",
+            kind: MigrationKind::Sql(include_str!("v22_pages.sql")),
+        },
+        Migration {
+            version: 23,
+            name: "search_index",
+            kind: MigrationKind::Sql(include_str!("v23_search_index.sql")),
+        },
+        Migration {
+            version: 24,
+            name: "api_keys",
+            kind: MigrationKind::Sql(include_str!("v24_api_keys.sql")),
+        },
+        Migration {
+            version: 25,
+            name: "short_links",
+            kind: MigrationKind::Sql(include_str!("v25_short_links.sql")),
+        },
+        Migration {
+            version: 26,
+            name: "aggregator_deliveries",
+            kind: MigrationKind::Sql(include_str!("v26_aggregator_deliveries.sql")),
+        },
+        Migration {
+            version: 27,
+            name: "user_preferences",
+            kind: MigrationKind::Sql(include_str!("v27_user_preferences.sql")),
+        },
+        Migration {
+            version: 28,
+            name: "published_files_cache_validators",
+            kind: MigrationKind::Sql(include_str!("v28_published_files_cache_validators.sql")),
+        },
+        Migration {
+            version: 29,
+            name: "forecast_files_store",
+            kind: MigrationKind::Sql(include_str!("v29_forecast_files_store.sql")),
+        },
+        Migration {
+            version: 30,
+            name: "blobs",
+            kind: MigrationKind::Sql(include_str!("v30_blobs.sql")),
+        },
+        Migration {
+            version: 31,
+            name: "forecasters",
+            kind: MigrationKind::Sql(include_str!("v31_forecasters.sql")),
+        },
+        Migration {
+            version: 32,
+            name: "observation_forecasts",
+            kind: MigrationKind::Sql(include_str!("v32_observation_forecasts.sql")),
+        },
+        Migration {
+            version: 33,
+            name: "admin_access_denied",
+            kind: MigrationKind::Sql(include_str!("v33_admin_access_denied.sql")),
+        },
+        Migration {
+            version: 34,
+            name: "forecast_area_tile_cache_eviction",
+            kind: MigrationKind::Sql(include_str!("v34_forecast_area_tile_cache_eviction.sql")),
+        },
+        Migration {
+            version: 35,
+            name: "analytics_visitor_salt",
+            kind: MigrationKind::Sql(include_str!("v35_analytics_visitor_salt.sql")),
+        },
     ]
 }
 
@@ -156,16 +303,7 @@ async fn current_migration(conn: &sqlx::SqlitePool) -> eyre::Result<Option<u32>>
 }
 
 async fn record_migration(conn: &sqlx::SqlitePool, migration: &Migration) -> eyre::Result<()> {
-    let checksum: Option<String> = match migration.kind {
-        MigrationKind::Sql(sql) => {
-            let mut hasher = Sha256::new();
-            hasher.update(sql);
-            let result = hasher.finalize();
-            let engine = base64::engine::general_purpose::STANDARD_NO_PAD;
-            Some(engine.encode(result))
-        }
-        _ => None,
-    };
+    let checksum: Option<String> = migration.checksum();
     let version = migration.version;
     sqlx::query(
         r#"
@@ -182,8 +320,119 @@ async fn record_migration(conn: &sqlx::SqlitePool, migration: &Migration) -> eyr
     Ok(())
 }
 
+/// A migration recorded in the `schema_history` table.
+struct AppliedMigration {
+    version: u32,
+    name: Option<String>,
+    applied_on: Option<String>,
+    checksum: Option<String>,
+}
+
+async fn applied_migrations(conn: &sqlx::SqlitePool) -> eyre::Result<Vec<AppliedMigration>> {
+    if current_migration(conn).await?.is_none() {
+        return Ok(Vec::new());
+    }
+    sqlx::query("SELECT version, name, applied_on, checksum FROM schema_history ORDER BY version")
+        .fetch_all(conn)
+        .await?
+        .into_iter()
+        .map(|row| {
+            Ok(AppliedMigration {
+                version: row.try_get("version")?,
+                name: row.try_get("name")?,
+                applied_on: row.try_get("applied_on")?,
+                checksum: row.try_get("checksum")?,
+            })
+        })
+        .collect()
+}
+
+/// The state of a migration, see [`status`].
+#[derive(Debug)]
+pub struct MigrationStatus {
+    pub version: u32,
+    pub name: String,
+    /// When the migration was applied, `None` if it is pending.
+    pub applied_on: Option<String>,
+    /// Whether the checksum recorded when the migration was applied matches the migration's
+    /// current SQL. `None` if it is pending, or is a rust migration.
+    pub checksum_matches: Option<bool>,
+}
+
+/// The state of all the migrations in the database, and any applied migrations which are unknown
+/// to this version of the application (e.g. applied by a newer version).
+pub async fn status(conn: &sqlx::SqlitePool) -> eyre::Result<Vec<MigrationStatus>> {
+    let migrations = list_migrations();
+    let applied = applied_migrations(conn)
+        .await
+        .wrap_err("Error obtaining applied migrations")?;
+    let mut statuses: Vec<MigrationStatus> = migrations
+        .iter()
+        .map(|migration| {
+            let applied = applied
+                .iter()
+                .find(|applied| applied.version == migration.version);
+            MigrationStatus {
+                version: migration.version,
+                name: migration.name.to_owned(),
+                applied_on: applied.and_then(|applied| applied.applied_on.clone()),
+                checksum_matches: applied.and_then(|applied| {
+                    Some(applied.checksum.as_deref()? == migration.checksum()?.as_str())
+                }),
+            }
+        })
+        .collect();
+    statuses.extend(
+        applied
+            .into_iter()
+            .filter(|applied| {
+                !migrations
+                    .iter()
+                    .any(|migration| migration.version == applied.version)
+            })
+            .map(|applied| MigrationStatus {
+                version: applied.version,
+                name: applied.name.unwrap_or_default(),
+                applied_on: applied.applied_on,
+                checksum_matches: None,
+            }),
+    );
+    Ok(statuses)
+}
+
+/// Check the already applied migrations against the migrations in this version of the
+/// application. A migration that was modified after it was applied is logged as a warning,
+/// because the database may not match what the migration now creates. A database with migrations
+/// unknown to this version of the application is an error, it can't safely be used.
+fn verify_applied(statuses: &[MigrationStatus], migrations: &[Migration]) -> eyre::Result<()> {
+    for status in statuses {
+        if status.applied_on.is_none() {
+            continue;
+        }
+        if !migrations
+            .iter()
+            .any(|migration| migration.version == status.version)
+        {
+            eyre::bail!(
+                "The database has migration v{} {:?} applied, which is unknown to this version of the application",
+                status.version,
+                status.name
+            );
+        }
+        if status.checksum_matches == Some(false) {
+            tracing::warn!(
+                "Migration v{} {:?} has been modified since it was applied to the database (checksum mismatch)",
+                status.version,
+                status.name
+            );
+        }
+    }
+    Ok(())
+}
+
 pub async fn run(conn: &sqlx::SqlitePool) -> eyre::Result<()> {
     let migrations: Vec<Migration> = list_migrations();
+    verify_applied(&status(conn).await?, &migrations)?;
 
     async fn run_migrations(
         mut current_migration_index: usize,
@@ -211,3 +460,42 @@ pub async fn run(conn: &sqlx::SqlitePool) -> eyre::Result<()> {
 
     Ok(())
 }
+
+#[cfg(test)]
+mod test {
+    use super::{list_migrations, run, status, verify_applied};
+
+    #[tokio::test]
+    async fn test_status() {
+        let conn = sqlx::SqlitePool::connect("sqlite::memory:").await.unwrap();
+        let pending = status(&conn).await.unwrap();
+        assert_eq!(pending.len(), list_migrations().len());
+        assert!(pending.iter().all(|status| status.applied_on.is_none()));
+
+        run(&conn).await.unwrap();
+        let applied = status(&conn).await.unwrap();
+        assert!(applied.iter().all(|status| status.applied_on.is_some()));
+        assert!(applied
+            .iter()
+            .all(|status| status.checksum_matches != Some(false)));
+
+        sqlx::query("UPDATE schema_history SET checksum='modified' WHERE version=1")
+            .execute(&conn)
+            .await
+            .unwrap();
+        let modified = status(&conn).await.unwrap();
+        assert_eq!(modified[1].checksum_matches, Some(false));
+        verify_applied(&modified, &list_migrations()).unwrap();
+
+        sqlx::query(
+            "INSERT INTO schema_history (version, name, applied_on) VALUES(1000, 'future', '2024-01-01T00:00:00.000Z')",
+        )
+        .execute(&conn)
+        .await
+        .unwrap();
+        let future = status(&conn).await.unwrap();
+        assert_eq!(future.last().unwrap().version, 1000);
+        assert!(verify_applied(&future, &list_migrations()).is_err());
+        assert!(run(&conn).await.is_err());
+    }
+}
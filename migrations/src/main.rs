@@ -1,10 +1,9 @@
 use std::path::PathBuf;
 
+use migrations::DB_FILE_NAME;
 use serde::{Deserialize, Serialize};
 use toml_env::AutoMapEnvArgs;
 
-pub const DB_FILE_NAME: &str = "db.sqlite3";
-
 #[derive(Serialize, Deserialize)]
 #[serde(default)]
 pub struct Options {
@@ -42,10 +41,31 @@ fn default_data_dir() -> PathBuf {
     "data".into()
 }
 
+/// Print the applied and pending migrations.
+async fn print_status(pool: &sqlx::SqlitePool) -> eyre::Result<()> {
+    let statuses = migrations::status(pool).await?;
+    for status in &statuses {
+        let state = match (&status.applied_on, status.checksum_matches) {
+            (None, _) => "pending".to_owned(),
+            (Some(applied_on), Some(false)) => format!("applied {applied_on} (checksum mismatch)"),
+            (Some(applied_on), _) => format!("applied {applied_on}"),
+        };
+        println!("v{:<4} {:<32} {state}", status.version, status.name);
+    }
+    let pending = statuses
+        .iter()
+        .filter(|status| status.applied_on.is_none())
+        .count();
+    println!("{} applied, {pending} pending", statuses.len() - pending);
+    Ok(())
+}
+
+/// Usage: `migrations [run|status]`, `run` is the default.
 #[tokio::main]
 pub async fn main() -> eyre::Result<()> {
     tracing_subscriber::fmt().init();
     color_eyre::install()?;
+    let command = std::env::args().nth(1);
     let options = Options::initialize().await?;
 
     let path = options.data_dir.join(DB_FILE_NAME);
@@ -65,7 +85,13 @@ pub async fn main() -> eyre::Result<()> {
     )
     .await?;
 
-    tracing::info!("Running migrations.");
-    migrations::run(&pool).await?;
+    match command.as_deref() {
+        None | Some("run") => {
+            tracing::info!("Running migrations.");
+            migrations::run(&pool).await?;
+        }
+        Some("status") => print_status(&pool).await?,
+        Some(command) => eyre::bail!("Unknown command {command:?}, expected `run` or `status`"),
+    }
     Ok(())
 }
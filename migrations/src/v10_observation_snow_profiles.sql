@@ -0,0 +1 @@
+ALTER TABLE observations ADD COLUMN snow_profile TEXT;
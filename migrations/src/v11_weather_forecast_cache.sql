@@ -0,0 +1,5 @@
+CREATE TABLE weather_forecast_cache (
+    area_id TEXT NOT NULL PRIMARY KEY,
+    fetched_at NUMERIC NOT NULL,
+    data TEXT NOT NULL
+);
@@ -0,0 +1,7 @@
+CREATE TABLE forecast_area_tile_cache (
+    z INTEGER NOT NULL,
+    x INTEGER NOT NULL,
+    y INTEGER NOT NULL,
+    data BLOB NOT NULL,
+    PRIMARY KEY (z, x, y)
+);
@@ -0,0 +1,16 @@
+CREATE TABLE users (
+    id TEXT NOT NULL PRIMARY KEY,
+    username TEXT NOT NULL UNIQUE,
+    password_hash TEXT NOT NULL,
+    role TEXT NOT NULL,
+    created_at NUMERIC NOT NULL
+);
+
+CREATE TABLE sessions (
+    id TEXT NOT NULL PRIMARY KEY,
+    user_id TEXT NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    created_at NUMERIC NOT NULL,
+    expires_at NUMERIC NOT NULL
+);
+
+CREATE INDEX sessions_user_id ON sessions(user_id);
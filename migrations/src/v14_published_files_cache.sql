@@ -0,0 +1,5 @@
+CREATE TABLE published_files_cache (
+    folder_id TEXT NOT NULL PRIMARY KEY,
+    fetched_at NUMERIC NOT NULL,
+    files JSON NOT NULL
+);
@@ -0,0 +1,5 @@
+CREATE TABLE analytics_visitors (
+    day TEXT NOT NULL,
+    visitor TEXT NOT NULL,
+    PRIMARY KEY (day, visitor)
+);
@@ -0,0 +1,8 @@
+CREATE TABLE machine_translation_cache (
+    source_language TEXT NOT NULL,
+    target_language TEXT NOT NULL,
+    source_text TEXT NOT NULL,
+    translated_text TEXT NOT NULL,
+    translated_at NUMERIC NOT NULL,
+    PRIMARY KEY (source_language, target_language, source_text)
+);
@@ -0,0 +1,8 @@
+CREATE TABLE IF NOT EXISTS uploaded_forecast_files (
+    id TEXT NOT NULL PRIMARY KEY,
+    name TEXT NOT NULL UNIQUE,
+    mime_type TEXT NOT NULL,
+    modified_time TEXT NOT NULL
+);
+ALTER TABLE forecast_files
+ADD COLUMN parse_error TEXT;
@@ -0,0 +1,10 @@
+CREATE TABLE database_maintenance (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    started_at NUMERIC NOT NULL,
+    duration_ms INTEGER NOT NULL,
+    manual INTEGER NOT NULL,
+    integrity_check TEXT,
+    size_before INTEGER,
+    size_after INTEGER,
+    error TEXT
+);
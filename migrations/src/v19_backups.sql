@@ -0,0 +1,10 @@
+CREATE TABLE backups (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    created_at NUMERIC NOT NULL,
+    size INTEGER NOT NULL,
+    entity_tag TEXT NOT NULL,
+    version_id TEXT,
+    md5 TEXT NOT NULL,
+    verified_at NUMERIC,
+    verification_error TEXT
+);
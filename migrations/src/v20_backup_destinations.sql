@@ -0,0 +1,4 @@
+ALTER TABLE backups
+ADD COLUMN destination TEXT;
+ALTER TABLE backups
+ADD COLUMN key TEXT;
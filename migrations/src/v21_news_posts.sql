@@ -0,0 +1,10 @@
+CREATE TABLE news_posts (
+    id TEXT NOT NULL PRIMARY KEY,
+    created_at NUMERIC NOT NULL,
+    updated_at NUMERIC NOT NULL,
+    published_at NUMERIC,
+    title TEXT NOT NULL,
+    body TEXT NOT NULL
+);
+
+CREATE INDEX news_posts_published_at ON news_posts(published_at);
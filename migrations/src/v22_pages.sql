@@ -0,0 +1,7 @@
+CREATE TABLE pages (
+    slug TEXT NOT NULL PRIMARY KEY,
+    updated_at NUMERIC NOT NULL,
+    title TEXT NOT NULL,
+    content TEXT NOT NULL,
+    template TEXT
+);
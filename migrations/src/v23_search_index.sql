@@ -0,0 +1,104 @@
+-- Full text search across the forecasts, observations and news posts, with a row for each
+-- language of each document. Kept up to date using the triggers below, which are also used to
+-- index the existing rows at the end of this migration.
+CREATE VIRTUAL TABLE search_index USING fts5(
+    kind UNINDEXED,
+    id UNINDEXED,
+    language UNINDEXED,
+    title,
+    content,
+    tokenize = 'unicode61 remove_diacritics 2'
+);
+
+-- Forecasts
+CREATE TRIGGER search_index_forecast_files_insert AFTER INSERT ON forecast_files
+WHEN new.parsed_forecast IS NOT NULL
+BEGIN
+INSERT INTO search_index(kind, id, language, title, content)
+SELECT 'forecast', new.google_drive_id, language.key, json_extract(new.parsed_forecast, '$.area'),
+    coalesce(json_extract(new.parsed_forecast, '$.description."' || language.key || '"'), '') || char(10)
+    || coalesce(json_extract(new.parsed_forecast, '$.forecast_changes."' || language.key || '"'), '') || char(10)
+    || coalesce(json_extract(new.parsed_forecast, '$.recent_observations."' || language.key || '"'), '') || char(10)
+    || coalesce(json_extract(new.parsed_forecast, '$.weather_forecast."' || language.key || '"'), '') || char(10)
+    || coalesce((
+        SELECT group_concat(json_extract(problem.value, '$.description."' || language.key || '"'), char(10))
+        FROM json_each(new.parsed_forecast, '$.avalanche_problems') AS problem
+    ), '')
+FROM (
+    SELECT key FROM json_each(new.parsed_forecast, '$.description')
+    UNION SELECT key FROM json_each(new.parsed_forecast, '$.forecast_changes')
+    UNION SELECT key FROM json_each(new.parsed_forecast, '$.recent_observations')
+    UNION SELECT key FROM json_each(new.parsed_forecast, '$.weather_forecast')
+) AS language;
+END;
+
+CREATE TRIGGER search_index_forecast_files_update AFTER UPDATE OF parsed_forecast ON forecast_files
+BEGIN
+DELETE FROM search_index WHERE kind = 'forecast' AND id = old.google_drive_id;
+INSERT INTO search_index(kind, id, language, title, content)
+SELECT 'forecast', new.google_drive_id, language.key, json_extract(new.parsed_forecast, '$.area'),
+    coalesce(json_extract(new.parsed_forecast, '$.description."' || language.key || '"'), '') || char(10)
+    || coalesce(json_extract(new.parsed_forecast, '$.forecast_changes."' || language.key || '"'), '') || char(10)
+    || coalesce(json_extract(new.parsed_forecast, '$.recent_observations."' || language.key || '"'), '') || char(10)
+    || coalesce(json_extract(new.parsed_forecast, '$.weather_forecast."' || language.key || '"'), '') || char(10)
+    || coalesce((
+        SELECT group_concat(json_extract(problem.value, '$.description."' || language.key || '"'), char(10))
+        FROM json_each(new.parsed_forecast, '$.avalanche_problems') AS problem
+    ), '')
+FROM (
+    SELECT key FROM json_each(new.parsed_forecast, '$.description')
+    UNION SELECT key FROM json_each(new.parsed_forecast, '$.forecast_changes')
+    UNION SELECT key FROM json_each(new.parsed_forecast, '$.recent_observations')
+    UNION SELECT key FROM json_each(new.parsed_forecast, '$.weather_forecast')
+) AS language
+WHERE new.parsed_forecast IS NOT NULL;
+END;
+
+CREATE TRIGGER search_index_forecast_files_delete AFTER DELETE ON forecast_files
+BEGIN
+DELETE FROM search_index WHERE kind = 'forecast' AND id = old.google_drive_id;
+END;
+
+-- Observations
+CREATE TRIGGER search_index_observations_insert AFTER INSERT ON observations
+BEGIN
+INSERT INTO search_index(kind, id, language, title, content)
+SELECT 'observation', new.id, '', '', new.description;
+END;
+
+CREATE TRIGGER search_index_observations_update AFTER UPDATE OF description ON observations
+BEGIN
+DELETE FROM search_index WHERE kind = 'observation' AND id = old.id;
+INSERT INTO search_index(kind, id, language, title, content)
+SELECT 'observation', new.id, '', '', new.description;
+END;
+
+CREATE TRIGGER search_index_observations_delete AFTER DELETE ON observations
+BEGIN
+DELETE FROM search_index WHERE kind = 'observation' AND id = old.id;
+END;
+
+-- News posts
+CREATE TRIGGER search_index_news_posts_insert AFTER INSERT ON news_posts
+BEGIN
+INSERT INTO search_index(kind, id, language, title, content)
+SELECT 'news', new.id, body.key, coalesce(json_extract(new.title, '$."' || body.key || '"'), ''), body.value
+FROM json_each(new.body) AS body;
+END;
+
+CREATE TRIGGER search_index_news_posts_update AFTER UPDATE OF title, body ON news_posts
+BEGIN
+DELETE FROM search_index WHERE kind = 'news' AND id = old.id;
+INSERT INTO search_index(kind, id, language, title, content)
+SELECT 'news', new.id, body.key, coalesce(json_extract(new.title, '$."' || body.key || '"'), ''), body.value
+FROM json_each(new.body) AS body;
+END;
+
+CREATE TRIGGER search_index_news_posts_delete AFTER DELETE ON news_posts
+BEGIN
+DELETE FROM search_index WHERE kind = 'news' AND id = old.id;
+END;
+
+UPDATE forecast_files SET parsed_forecast = parsed_forecast;
+UPDATE observations SET description = description;
+UPDATE news_posts SET title = title;
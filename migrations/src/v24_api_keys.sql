@@ -0,0 +1,22 @@
+-- Keys for the public API (`/api/v1`), only the SHA-256 hash of each key is stored.
+CREATE TABLE api_keys (
+    id TEXT NOT NULL PRIMARY KEY,
+    name TEXT NOT NULL,
+    key_hash TEXT NOT NULL UNIQUE,
+    -- The start of the key, shown to help identify which key is which.
+    key_prefix TEXT NOT NULL,
+    requests_per_hour INTEGER NOT NULL,
+    created_at NUMERIC NOT NULL,
+    last_used_at NUMERIC,
+    revoked_at NUMERIC
+);
+
+-- Number of requests made using each key per day (UTC, `YYYY-MM-DD`).
+CREATE TABLE api_key_usage (
+    api_key_id TEXT NOT NULL REFERENCES api_keys(id) ON DELETE CASCADE,
+    day TEXT NOT NULL,
+    requests INTEGER NOT NULL DEFAULT 0,
+    -- Requests rejected because the key exceeded its rate limit.
+    rate_limited INTEGER NOT NULL DEFAULT 0,
+    PRIMARY KEY (api_key_id, day)
+);
@@ -0,0 +1,6 @@
+-- Short tokens for the published forecast files, used in links at `/f/{token}`.
+CREATE TABLE short_links (
+    token TEXT NOT NULL PRIMARY KEY,
+    file_name TEXT NOT NULL UNIQUE,
+    created_at NUMERIC NOT NULL
+);
@@ -0,0 +1,12 @@
+-- Log of the forecasts pushed to external aggregators, shown at `/admin/aggregators`.
+CREATE TABLE aggregator_deliveries (
+    id TEXT NOT NULL PRIMARY KEY,
+    aggregator TEXT NOT NULL,
+    file_name TEXT NOT NULL,
+    created_at NUMERIC NOT NULL,
+    attempts INTEGER NOT NULL,
+    delivered_at NUMERIC,
+    error TEXT
+);
+
+CREATE INDEX aggregator_deliveries_created_at ON aggregator_deliveries(created_at);
@@ -0,0 +1,8 @@
+-- Preferences of logged in users, so that they follow the user between browsers. Anonymous
+-- users' preferences are only stored in a cookie.
+CREATE TABLE user_preferences (
+    user_id TEXT NOT NULL PRIMARY KEY REFERENCES users(id) ON DELETE CASCADE,
+    -- JSON serialized `UserPreferences`.
+    preferences TEXT NOT NULL,
+    updated_at NUMERIC NOT NULL
+);
@@ -0,0 +1,4 @@
+-- The `ETag` and `Last-Modified` headers of the listing response, used to only fetch the listing
+-- again when it has changed.
+ALTER TABLE published_files_cache ADD COLUMN etag TEXT;
+ALTER TABLE published_files_cache ADD COLUMN last_modified TEXT;
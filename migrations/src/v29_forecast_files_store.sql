@@ -0,0 +1,4 @@
+-- Files fetched from Google Drive are now stored in the `forecast_files` directory of the data
+-- directory, with an empty `file_blob`. They are fetched again when they are next needed. Uploaded
+-- files (which can't be fetched again) remain in the `file_blob`.
+UPDATE forecast_files SET file_blob=x'' WHERE google_drive_id NOT LIKE 'upload-%';
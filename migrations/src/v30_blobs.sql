@@ -0,0 +1,30 @@
+-- Content-addressed files stored in the `blobs` directory of the data directory, identified by
+-- the SHA-256 hash of their contents. `stored_at` is updated each time the blob is stored again, so
+-- that a blob which is about to be referenced isn't removed as garbage.
+CREATE TABLE blobs (
+    hash TEXT NOT NULL PRIMARY KEY,
+    size INTEGER NOT NULL,
+    stored_at NUMERIC NOT NULL
+);
+
+-- Files fetched from Google Drive, replacing the `forecast_files` directory of the data
+-- directory.
+ALTER TABLE forecast_files ADD COLUMN blob_hash TEXT REFERENCES blobs(hash);
+CREATE INDEX forecast_files_blob_hash ON forecast_files(blob_hash);
+
+-- The photos are moved out of the `data` column into the blob store when the application starts.
+ALTER TABLE observation_photos ADD COLUMN blob_hash TEXT REFERENCES blobs(hash);
+CREATE INDEX observation_photos_blob_hash ON observation_photos(blob_hash);
+
+-- PDFs generated for forecasts, which are generated again when the forecast file is modified,
+-- or by a different version of the application.
+CREATE TABLE generated_pdfs (
+    file_name TEXT NOT NULL,
+    language TEXT NOT NULL,
+    default_language TEXT NOT NULL,
+    modified_time NUMERIC NOT NULL,
+    version TEXT NOT NULL,
+    blob_hash TEXT NOT NULL REFERENCES blobs(hash),
+    PRIMARY KEY (file_name, language, default_language)
+);
+CREATE INDEX generated_pdfs_blob_hash ON generated_pdfs(blob_hash);
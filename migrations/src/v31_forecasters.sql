@@ -0,0 +1,13 @@
+-- The forecasters credited on the forecasts, identified by the initials used in the forecast
+-- file names (e.g. `LF` in `Gudauri_2023-01-24T17:00_LF.xlsx`).
+CREATE TABLE forecasters (
+    initials TEXT NOT NULL PRIMARY KEY,
+    updated_at NUMERIC NOT NULL,
+    name TEXT NOT NULL,
+    organisation TEXT,
+    -- Markdown for each language.
+    bio TEXT NOT NULL,
+    photo_content_type TEXT,
+    photo_blob_hash TEXT REFERENCES blobs(hash)
+);
+CREATE INDEX forecasters_photo_blob_hash ON forecasters(photo_blob_hash);
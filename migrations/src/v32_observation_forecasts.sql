@@ -0,0 +1,11 @@
+-- The forecast which was in force where and when each observation was made.
+CREATE TABLE observation_forecasts (
+    observation_id TEXT NOT NULL PRIMARY KEY REFERENCES observations(id) ON DELETE CASCADE,
+    linked_at NUMERIC NOT NULL,
+    -- NULL when the observation is outside of all the forecast areas.
+    forecast_area TEXT,
+    -- NULL when no forecast for the area had been published at the time of the observation.
+    forecast_file_name TEXT,
+    forecast_time NUMERIC
+);
+CREATE INDEX observation_forecasts_forecast_file_name ON observation_forecasts(forecast_file_name);
@@ -0,0 +1,10 @@
+-- Attempts to access administration pages from clients outside of the allowlist.
+CREATE TABLE admin_access_denied (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    time NUMERIC NOT NULL,
+    -- NULL when the client IP address is unknown.
+    client_ip TEXT,
+    path TEXT NOT NULL,
+    username TEXT NOT NULL
+);
+CREATE INDEX admin_access_denied_time ON admin_access_denied(time);
@@ -0,0 +1,12 @@
+-- The cached tiles are regenerated on demand, so they are dropped rather than migrated.
+DROP TABLE forecast_area_tile_cache;
+CREATE TABLE forecast_area_tile_cache (
+    z INTEGER NOT NULL,
+    x INTEGER NOT NULL,
+    y INTEGER NOT NULL,
+    data BLOB NOT NULL,
+    -- Used to evict the oldest tiles.
+    cached_at NUMERIC NOT NULL,
+    PRIMARY KEY (z, x, y)
+);
+CREATE INDEX forecast_area_tile_cache_cached_at ON forecast_area_tile_cache(cached_at);
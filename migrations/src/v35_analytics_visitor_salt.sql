@@ -0,0 +1,6 @@
+-- The salt used to identify unique visitors on each day, so that visitors are still counted once
+-- after a restart. Salts for previous days are deleted when the day's salt is created.
+CREATE TABLE analytics_visitor_salt (
+    day TEXT PRIMARY KEY NOT NULL,
+    salt BLOB NOT NULL
+);
@@ -0,0 +1,26 @@
+CREATE TABLE observations (
+    id TEXT NOT NULL PRIMARY KEY,
+    created_at NUMERIC NOT NULL,
+    observed_at NUMERIC NOT NULL,
+    latitude REAL NOT NULL,
+    longitude REAL NOT NULL,
+    elevation_metres INTEGER,
+    aspect TEXT,
+    avalanche_activity TEXT NOT NULL,
+    avalanche_size INTEGER,
+    avalanche_problem TEXT,
+    description TEXT NOT NULL,
+    observer_name TEXT,
+    status TEXT NOT NULL DEFAULT 'pending'
+);
+
+CREATE INDEX observations_status_observed_at ON observations(status, observed_at);
+
+CREATE TABLE observation_photos (
+    id TEXT NOT NULL PRIMARY KEY,
+    observation_id TEXT NOT NULL REFERENCES observations(id) ON DELETE CASCADE,
+    content_type TEXT NOT NULL,
+    data BLOB NOT NULL
+);
+
+CREATE INDEX observation_photos_observation_id ON observation_photos(observation_id);
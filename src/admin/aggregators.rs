@@ -0,0 +1,74 @@
+//! The log of the forecasts pushed to external aggregators, see [`crate::aggregators`].
+
+use axum::{
+    extract::{Path, State},
+    response::{IntoResponse, Redirect, Response},
+    routing::{get, post},
+    Extension, Router,
+};
+use http::StatusCode;
+use serde::Serialize;
+
+use crate::{
+    aggregators::{self, DeliveryRecord},
+    error::map_eyre_error,
+    options::AggregatorFormat,
+    state::AppState,
+    templates::TemplatesWithContext,
+};
+
+/// Number of recent deliveries shown.
+const RECENT_DELIVERIES: i64 = 100;
+
+pub fn router() -> Router<AppState> {
+    Router::new()
+        .route("/", get(index_handler))
+        .route("/deliveries/{id}/retry", post(retry_handler))
+}
+
+#[derive(Serialize)]
+struct Aggregator {
+    name: &'static str,
+    url: &'static str,
+    format: AggregatorFormat,
+}
+
+#[derive(Serialize)]
+struct Context {
+    aggregators: Vec<Aggregator>,
+    deliveries: Vec<DeliveryRecord>,
+}
+
+async fn index_handler(
+    State(state): State<AppState>,
+    Extension(templates): Extension<TemplatesWithContext>,
+) -> axum::response::Result<Response> {
+    let context = Context {
+        aggregators: state
+            .options
+            .aggregators
+            .iter()
+            .map(|aggregator| Aggregator {
+                name: &aggregator.name,
+                url: aggregator.url.as_str(),
+                format: aggregator.format,
+            })
+            .collect(),
+        deliveries: aggregators::recent_deliveries(&state.database, RECENT_DELIVERIES)
+            .await
+            .map_err(map_eyre_error)?,
+    };
+    Ok(templates
+        .render("admin/aggregators.html", &context)
+        .map_err(map_eyre_error)?)
+}
+
+async fn retry_handler(
+    Path(id): Path<String>,
+    State(state): State<AppState>,
+) -> axum::response::Result<Response> {
+    if !state.aggregators.retry(&id).await.map_err(map_eyre_error)? {
+        return Err(StatusCode::NOT_FOUND.into());
+    }
+    Ok(Redirect::to("/admin/aggregators").into_response())
+}
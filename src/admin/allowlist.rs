@@ -0,0 +1,112 @@
+//! Restricting the most sensitive administration pages to clients in the networks of the
+//! [`options::AdminAllowlist`], and recording the attempts which were denied.
+
+use axum::{
+    extract::{OriginalUri, Request},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use eyre::Context;
+use http::StatusCode;
+use serde::Serialize;
+
+use crate::{
+    auth::CurrentUser,
+    client_ip,
+    database::Database,
+    options, types,
+    webhooks::{Event, Webhooks},
+};
+
+/// A denied attempt to access an administration page.
+#[derive(Debug, Serialize)]
+pub struct DeniedAccess {
+    pub time: types::Time,
+    pub client_ip: Option<String>,
+    pub path: String,
+    pub username: String,
+}
+
+async fn record_denied(database: &Database, denied: &DeniedAccess) -> eyre::Result<()> {
+    sqlx::query!(
+        "INSERT INTO admin_access_denied(time, client_ip, path, username) VALUES($1, $2, $3, $4)",
+        denied.time,
+        denied.client_ip,
+        denied.path,
+        denied.username,
+    )
+    .execute(database)
+    .await
+    .wrap_err("Error recording denied admin access")?;
+    Ok(())
+}
+
+/// The most recent denied attempts, most recent first.
+pub async fn recent_denied(database: &Database, limit: i64) -> eyre::Result<Vec<DeniedAccess>> {
+    sqlx::query_as!(
+        DeniedAccess,
+        r#"SELECT time as "time: types::Time", client_ip, path, username FROM admin_access_denied ORDER BY time DESC LIMIT $1"#,
+        limit
+    )
+    .fetch_all(database)
+    .await
+    .wrap_err("Error listing denied admin access")
+}
+
+/// Middleware which only allows requests from clients in the `allowlist`. Denied attempts are
+/// recorded and sent to the `webhooks`. Needs to be applied after [`crate::auth::require_login`].
+pub async fn middleware(
+    allowlist: &'static options::AdminAllowlist,
+    webhooks: Webhooks,
+    request: Request,
+    next: Next,
+) -> Response {
+    let client_ip = client_ip::client_ip(
+        request.headers(),
+        request.extensions(),
+        allowlist.client_ip_header.as_deref(),
+    );
+    if client_ip.is_some_and(|ip| allowlist.allows(&ip)) {
+        return next.run(request).await;
+    }
+    let Some(Some(current_user)) = request.extensions().get::<Option<CurrentUser>>() else {
+        return StatusCode::UNAUTHORIZED.into_response();
+    };
+    let path = request
+        .extensions()
+        .get::<OriginalUri>()
+        .map(|original_uri| &original_uri.0)
+        .unwrap_or(request.uri())
+        .path()
+        .to_owned();
+    let denied = DeniedAccess {
+        time: types::Time::now_utc(),
+        client_ip: client_ip.map(|ip| ip.to_string()),
+        path,
+        username: current_user.user.username.clone(),
+    };
+    tracing::warn!(
+        "User {:?} was denied access to {} from {:?}",
+        denied.username,
+        denied.path,
+        denied.client_ip
+    );
+    match request.extensions().get::<Database>() {
+        Some(database) => {
+            if let Err(error) = record_denied(database, &denied).await {
+                tracing::error!("{error:?}");
+            }
+        }
+        None => tracing::error!("Expected extension Database to be available"),
+    }
+    webhooks.send(Event::AdminAccessDenied {
+        path: denied.path,
+        username: denied.username,
+        client_ip: denied.client_ip,
+    });
+    (
+        StatusCode::FORBIDDEN,
+        "Access to this page is not allowed from your network",
+    )
+        .into_response()
+}
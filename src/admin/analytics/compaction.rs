@@ -0,0 +1,57 @@
+//! Run the analytics compaction (see [`analytics::compact`]) manually, with a dry run to preview
+//! its effects.
+
+use axum::{extract::State, response::Response, Extension};
+use serde::Serialize;
+
+use crate::{
+    analytics::{self, CompactionReport},
+    error::map_eyre_error,
+    state::AppState,
+    templates::TemplatesWithContext,
+};
+
+#[derive(Serialize)]
+struct Context {
+    report: CompactionReport,
+    dry_run: bool,
+    window_seconds: i64,
+    keep_seconds: i64,
+}
+
+async fn compact(
+    state: &AppState,
+    templates: &TemplatesWithContext,
+    dry_run: bool,
+) -> axum::response::Result<Response> {
+    let window = state.options.analytics.compaction_window();
+    let keep = state.options.analytics.compaction_keep();
+    let report = analytics::compact(&state.database, window, keep, dry_run)
+        .await
+        .map_err(map_eyre_error)?;
+    let context = Context {
+        report,
+        dry_run,
+        window_seconds: window.whole_seconds(),
+        keep_seconds: keep.whole_seconds(),
+    };
+    Ok(templates
+        .render("admin/analytics/compaction.html", &context)
+        .map_err(map_eyre_error)?)
+}
+
+/// Report what compaction would do, without modifying the database.
+pub async fn dry_run_handler(
+    State(state): State<AppState>,
+    Extension(templates): Extension<TemplatesWithContext>,
+) -> axum::response::Result<Response> {
+    compact(&state, &templates, true).await
+}
+
+pub async fn run_handler(
+    State(state): State<AppState>,
+    Extension(templates): Extension<TemplatesWithContext>,
+) -> axum::response::Result<Response> {
+    tracing::info!("Running analytics compaction manually");
+    compact(&state, &templates, false).await
+}
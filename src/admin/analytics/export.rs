@@ -0,0 +1,143 @@
+//! Download the analytics entries for offline analysis.
+
+use std::fmt::Write;
+
+use axum::{
+    extract::State,
+    response::{IntoResponse, Response},
+    Json,
+};
+use http::header;
+use serde::Deserialize;
+use utils::serde::rfc3339_option;
+
+use crate::{
+    analytics::Analytics, database::Database, error::map_eyre_error, state::AppState, types,
+};
+
+#[derive(Debug, Default, Deserialize, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+pub enum Format {
+    #[default]
+    Csv,
+    Json,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct Query {
+    #[serde(with = "rfc3339_option")]
+    from: Option<time::OffsetDateTime>,
+    #[serde(with = "rfc3339_option")]
+    to: Option<time::OffsetDateTime>,
+    format: Format,
+}
+
+pub async fn handler(
+    axum::extract::Query(query): axum::extract::Query<Query>,
+    State(state): State<AppState>,
+) -> axum::response::Result<Response> {
+    let entries = get_entries(
+        &state.database,
+        query.from.map(Into::into),
+        query.to.map(Into::into),
+    )
+    .await
+    .map_err(map_eyre_error)?;
+    let response = match query.format {
+        Format::Csv => (
+            [
+                (header::CONTENT_TYPE, "text/csv; charset=utf-8"),
+                (
+                    header::CONTENT_DISPOSITION,
+                    "attachment; filename=\"analytics.csv\"",
+                ),
+            ],
+            to_csv(&entries),
+        )
+            .into_response(),
+        Format::Json => (
+            [(
+                header::CONTENT_DISPOSITION,
+                "attachment; filename=\"analytics.json\"",
+            )],
+            Json(entries),
+        )
+            .into_response(),
+    };
+    Ok(response)
+}
+
+/// Get the entries with a time within `from` and `to` (inclusive), ordered by time. Entries that
+/// have been compacted (see [`crate::analytics::compact`]) are included as they are stored.
+async fn get_entries(
+    database: &Database,
+    from: Option<types::Time>,
+    to: Option<types::Time>,
+) -> eyre::Result<Vec<Analytics>> {
+    Ok(sqlx::query_as!(
+        Analytics,
+        r#"SELECT id as "id!: _", uri, visits as "visits!: _", time as "time: types::Time" FROM analytics WHERE ($1 IS NULL OR time >= $1) AND ($2 IS NULL OR time <= $2) ORDER BY time ASC"#,
+        from,
+        to,
+    )
+    .fetch_all(database)
+    .await?)
+}
+
+/// Quote a CSV field if necessary, as per RFC 4180.
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_owned()
+    }
+}
+
+fn to_csv(entries: &[Analytics]) -> String {
+    let mut csv = String::from("id,uri,visits,time\r\n");
+    for entry in entries {
+        write!(
+            csv,
+            "{},{},{},{}\r\n",
+            entry.id,
+            csv_field(&entry.uri),
+            entry.visits,
+            entry.time
+        )
+        .expect("Writing to String should not fail");
+    }
+    csv
+}
+
+#[cfg(test)]
+mod test {
+    use uuid::Uuid;
+
+    use crate::analytics::Analytics;
+
+    use super::to_csv;
+
+    #[test]
+    fn test_to_csv() {
+        let entries = vec![
+            Analytics {
+                id: Uuid::nil(),
+                uri: "/forecasts/Gudauri_2023-01-24T17:00_LF.en.pdf".to_owned(),
+                visits: 3,
+                time: "2023-08-09T12:00:00Z".parse().unwrap(),
+            },
+            Analytics {
+                id: Uuid::nil(),
+                uri: "/a,\"b\"".to_owned(),
+                visits: 1,
+                time: "2023-08-09T13:00:00Z".parse().unwrap(),
+            },
+        ];
+        insta::assert_snapshot!(to_csv(&entries).replace("\r\n", "\n"), @r###"
+        id,uri,visits,time
+        00000000-0000-0000-0000-000000000000,/forecasts/Gudauri_2023-01-24T17:00_LF.en.pdf,3,2023-08-09T12:00:00.000Z
+        00000000-0000-0000-0000-000000000000,"/a,""b""",1,2023-08-09T13:00:00.000Z
+        "###);
+    }
+}
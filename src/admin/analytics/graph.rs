@@ -1,4 +1,10 @@
-use crate::{analytics::get_time_bounds, database::Database, types::Time};
+use std::collections::BTreeMap;
+
+use crate::{
+    analytics::{format_day, get_time_bounds},
+    database::Database,
+    types::Time,
+};
 use eyre::{Context, ContextCompat};
 use serde::{Deserialize, Serialize};
 use sqlx::Row;
@@ -14,13 +20,10 @@ async fn get_analytics_data(
     database: &Database,
     options: Options,
 ) -> eyre::Result<Vec<AnalyticsData>> {
-    match (options.from, options.to) {
-        (Some(from), Some(to)) => {
-            if *to < *from {
-                eyre::bail!("Invalid options to: {to} should not be less than from: {from}");
-            }
+    if let (Some(from), Some(to)) = (options.from, options.to) {
+        if *to < *from {
+            eyre::bail!("Invalid options to: {to} should not be less than from: {from}");
         }
-        _ => {}
     }
     let (min, max) = if let Some(time_bounds) = get_time_bounds(database).await? {
         time_bounds
@@ -90,22 +93,8 @@ fn graph_data(data: Vec<AnalyticsData>) -> eyre::Result<GraphData> {
     let mut graph_data = Vec::new();
     let (time_data, visits_data): (Vec<i64>, Vec<f64>) = data
         .iter()
-        .enumerate()
-        .map(|(_i, analytics)| Ok::<_, eyre::Error>((analytics.time, analytics.visits)))
-        .fold::<eyre::Result<(Vec<i64>, Vec<f64>)>, _>(
-            Ok(Default::default()),
-            |acc_result, result| match acc_result {
-                Ok(mut acc) => match result {
-                    Ok((timestamp, visits)) => {
-                        acc.0.push(timestamp);
-                        acc.1.push(visits.try_into()?);
-                        Ok(acc)
-                    }
-                    Err(error) => Err(error),
-                },
-                _ => acc_result,
-            },
-        )?;
+        .map(|analytics| (analytics.time, f64::from(analytics.visits)))
+        .unzip();
 
     graph_data.push(
         time_data
@@ -139,6 +128,53 @@ pub async fn graph_analytics(database: &Database, options: Options) -> eyre::Res
     Ok(Graph { data })
 }
 
+/// Graph the visits and the estimated unique visitors (see [`crate::analytics::Visitor`]) for
+/// each day. Unlike [`graph_analytics`] this is not filtered by uri, as visitors are counted for
+/// the whole site.
+pub async fn graph_daily_visitors(
+    database: &Database,
+    from: Option<Time>,
+    to: Option<Time>,
+) -> eyre::Result<Graph> {
+    let mut days: BTreeMap<String, (i64, i64)> = BTreeMap::new();
+    let visits = sqlx::query!(
+        r#"SELECT substr(time, 1, 10) as "day!: String", SUM(visits) as "visits!: i64" FROM analytics WHERE ($1 IS NULL OR time >= $1) AND ($2 IS NULL OR time <= $2) GROUP BY 1"#,
+        from,
+        to,
+    )
+    .fetch_all(database)
+    .await
+    .wrap_err("Error fetching daily visits")?;
+    for record in visits {
+        days.entry(record.day).or_default().0 = record.visits;
+    }
+
+    let from_day = from.map(|from| format_day(from.to_offset(time::UtcOffset::UTC).date()));
+    let to_day = to.map(|to| format_day(to.to_offset(time::UtcOffset::UTC).date()));
+    let visitors = sqlx::query!(
+        r#"SELECT day, COUNT(*) as "visitors!: i64" FROM analytics_visitors WHERE ($1 IS NULL OR day >= $1) AND ($2 IS NULL OR day <= $2) GROUP BY day"#,
+        from_day,
+        to_day,
+    )
+    .fetch_all(database)
+    .await
+    .wrap_err("Error fetching daily visitors")?;
+    for record in visitors {
+        days.entry(record.day).or_default().1 = record.visitors;
+    }
+
+    let format = time::macros::format_description!("[year]-[month]-[day]");
+    let mut data: GraphData = vec![Vec::new(), Vec::new(), Vec::new()];
+    for (day, (visits, visitors)) in days {
+        let day = time::Date::parse(&day, &format)
+            .wrap_err_with(|| format!("Error parsing analytics day {day:?}"))?;
+        data[0].push(day.midnight().assume_utc().unix_timestamp().into());
+        data[1].push(visits.into());
+        data[2].push(visitors.into());
+    }
+    Ok(Graph { data })
+}
+
 #[derive(Serialize)]
 pub struct Graph {
     pub data: GraphData,
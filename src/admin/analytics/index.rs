@@ -1,6 +1,6 @@
 use std::num::NonZeroU32;
 
-use crate::{database::Database, templates::render, types::Time};
+use crate::{api::keys, database::Database, templates::render, types::Time};
 use axum::{
     extract::State,
     response::{IntoResponse, Response},
@@ -144,6 +144,10 @@ struct AnalyticsPage {
     summaries_duration: SummariesDuration,
     batch_rate: NonZeroU32,
     graph: Graph,
+    /// See [`graph::graph_daily_visitors`].
+    daily_visitors_graph: Graph,
+    /// See [`keys::usage`].
+    api_key_usage: Vec<keys::ApiKeyUsage>,
     query: Query,
 }
 
@@ -280,29 +284,21 @@ pub async fn handler(
         duration_options
     };
 
-    match (query.from, query.to) {
-        (Some(from), Some(to)) => {
-            if to < from {
-                return Err(map_eyre_error(eyre::eyre!(
-                    "Invalid query parameters to: {to} should not be less than from: {from}"
-                ))
-                .into());
-            }
+    if let (Some(from), Some(to)) = (query.from, query.to) {
+        if to < from {
+            return Err(map_eyre_error(eyre::eyre!(
+                "Invalid query parameters to: {to} should not be less than from: {from}"
+            ))
+            .into());
         }
-        _ => {}
     }
 
     let from = from.map(Time::from);
     let to = to.map(Time::from);
 
-    let summaries = get_analytics(
-        &state.database,
-        from.map(Into::into),
-        to.map(Into::into),
-        query.uri_filter.clone(),
-    )
-    .await
-    .map_err(map_eyre_error)?;
+    let summaries = get_analytics(&state.database, from, to, query.uri_filter.clone())
+        .await
+        .map_err(map_eyre_error)?;
 
     let summaries_duration = SummariesDuration {
         duration_option,
@@ -323,11 +319,21 @@ pub async fn handler(
     .await
     .map_err(map_eyre_error)?;
 
+    let daily_visitors_graph = graph::graph_daily_visitors(&state.database, from, to)
+        .await
+        .map_err(map_eyre_error)?;
+
+    let api_key_usage = keys::usage(&state.database, from, to)
+        .await
+        .map_err(map_eyre_error)?;
+
     let page = AnalyticsPage {
         duration_options,
         summaries_duration,
-        batch_rate: state.options.analytics.event_batch_rate,
+        batch_rate: state.reloadable_options.load().analytics_event_batch_rate,
         graph,
+        daily_visitors_graph,
+        api_key_usage,
         query: query.clone(),
     };
 
@@ -2,9 +2,17 @@ use axum::{routing::get, Router};
 
 use crate::state::AppState;
 
+mod compaction;
+mod export;
 mod graph;
 mod index;
 
 pub fn router() -> Router<AppState> {
-    Router::new().route("/", get(index::handler))
+    Router::new()
+        .route("/", get(index::handler))
+        .route("/export", get(export::handler))
+        .route(
+            "/compaction",
+            get(compaction::dry_run_handler).post(compaction::run_handler),
+        )
 }
@@ -0,0 +1,130 @@
+//! Management of the keys for the public API, see [`crate::api::keys`].
+
+use std::num::NonZeroU32;
+
+use axum::{
+    extract::{Path, State},
+    response::{IntoResponse, Redirect, Response},
+    routing::{get, post},
+    Extension, Form, Router,
+};
+use http::StatusCode;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    api::keys::{self, ApiKey, ApiKeyId},
+    auth::CurrentUser,
+    database::Database,
+    error::map_eyre_error,
+    state::AppState,
+    templates::TemplatesWithContext,
+};
+
+pub fn router() -> Router<AppState> {
+    Router::new()
+        .route("/", get(index_handler).post(create_handler))
+        .route("/{id}/revoke", post(revoke_handler))
+}
+
+#[derive(Serialize)]
+struct CreatedKey {
+    name: String,
+    /// The key, which is only shown this one time.
+    key: String,
+}
+
+#[derive(Serialize)]
+struct Context {
+    api_keys: Vec<ApiKey>,
+    require_key: bool,
+    default_requests_per_hour: NonZeroU32,
+    created_key: Option<CreatedKey>,
+    error: Option<String>,
+}
+
+async fn render_index(
+    state: &AppState,
+    templates: &TemplatesWithContext,
+    database: &Database,
+    created_key: Option<CreatedKey>,
+    error: Option<String>,
+) -> axum::response::Result<Response> {
+    let status = if error.is_some() {
+        StatusCode::UNPROCESSABLE_ENTITY
+    } else {
+        StatusCode::OK
+    };
+    let context = Context {
+        api_keys: keys::list_api_keys(database)
+            .await
+            .map_err(map_eyre_error)?,
+        require_key: state.options.api.require_key,
+        default_requests_per_hour: state.options.api.default_requests_per_hour,
+        created_key,
+        error,
+    };
+    let response = templates
+        .render("admin/api_keys.html", &context)
+        .map_err(map_eyre_error)?;
+    Ok((status, response).into_response())
+}
+
+async fn index_handler(
+    State(state): State<AppState>,
+    Extension(templates): Extension<TemplatesWithContext>,
+    Extension(database): Extension<Database>,
+) -> axum::response::Result<Response> {
+    render_index(&state, &templates, &database, None, None).await
+}
+
+#[derive(Deserialize)]
+struct CreateForm {
+    name: String,
+    requests_per_hour: NonZeroU32,
+}
+
+async fn create_handler(
+    State(state): State<AppState>,
+    Extension(templates): Extension<TemplatesWithContext>,
+    Extension(database): Extension<Database>,
+    Extension(current_user): Extension<Option<CurrentUser>>,
+    Form(form): Form<CreateForm>,
+) -> axum::response::Result<Response> {
+    let current_user = current_user.ok_or(StatusCode::UNAUTHORIZED)?;
+    let name = form.name.trim();
+    if name.is_empty() {
+        let error = "Name must not be empty".to_owned();
+        return render_index(&state, &templates, &database, None, Some(error)).await;
+    }
+    let (api_key, key) = keys::create_api_key(&database, name, form.requests_per_hour)
+        .await
+        .map_err(map_eyre_error)?;
+    tracing::info!(
+        "User {:?} created API key {:?} ({}) for {:?}",
+        current_user.user.username,
+        api_key.key_prefix,
+        api_key.id,
+        api_key.name
+    );
+    let created_key = CreatedKey {
+        name: api_key.name,
+        key,
+    };
+    render_index(&state, &templates, &database, Some(created_key), None).await
+}
+
+async fn revoke_handler(
+    Path(id): Path<ApiKeyId>,
+    Extension(database): Extension<Database>,
+    Extension(current_user): Extension<Option<CurrentUser>>,
+) -> axum::response::Result<Response> {
+    let current_user = current_user.ok_or(StatusCode::UNAUTHORIZED)?;
+    if !keys::revoke_api_key(&database, &id)
+        .await
+        .map_err(map_eyre_error)?
+    {
+        return Err(StatusCode::NOT_FOUND.into());
+    }
+    tracing::info!("User {:?} revoked API key {id}", current_user.user.username);
+    Ok(Redirect::to("/admin/api-keys").into_response())
+}
@@ -0,0 +1,209 @@
+//! Verification and restoring of the database backups (see [`backup`]).
+
+use axum::{
+    extract::{Path, Query, State},
+    response::{IntoResponse, Response},
+    routing::{get, post},
+    Extension, Router,
+};
+use http::StatusCode;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    database::backup::{self, BackupRecord, BackupSelector},
+    error::map_eyre_error,
+    state::AppState,
+    templates::TemplatesWithContext,
+};
+
+pub fn router() -> Router<AppState> {
+    Router::new()
+        .route("/", get(index_handler))
+        .route("/{selector}/verify", post(verify_handler))
+        .route("/{selector}/restore", post(restore_handler))
+        .route("/restore/cancel", post(cancel_restore_handler))
+}
+
+#[derive(Serialize)]
+struct Destination {
+    /// See [`backup::Config::destination`].
+    name: Option<&'static str>,
+    kind: &'static str,
+}
+
+#[derive(Serialize)]
+struct Backup {
+    #[serde(flatten)]
+    record: BackupRecord,
+    /// Whether this particular backup can still be downloaded: local backups are saved to a new
+    /// file each time, but backups in a bucket overwrite the same object and need versioning.
+    restorable: bool,
+}
+
+#[derive(Serialize)]
+struct Context {
+    destinations: Vec<Destination>,
+    backups: Vec<Backup>,
+    restore_staged: bool,
+    message: Option<String>,
+    error: Option<String>,
+}
+
+async fn render_index(
+    state: &AppState,
+    templates: &TemplatesWithContext,
+    result: Option<Result<String, String>>,
+) -> axum::response::Result<Response> {
+    let status = if matches!(result, Some(Err(_))) {
+        StatusCode::UNPROCESSABLE_ENTITY
+    } else {
+        StatusCode::OK
+    };
+    let (message, error) = match result {
+        Some(Ok(message)) => (Some(message), None),
+        Some(Err(error)) => (None, Some(error)),
+        None => (None, None),
+    };
+    let destinations = backup::Config::all(
+        state.options,
+        state.client.clone(),
+        state.database.clone(),
+        state.blobs.clone(),
+    )
+    .into_iter()
+    .map(|config| Destination {
+        name: config.destination,
+        kind: match config.target {
+            backup::Target::S3(_) => "s3",
+            backup::Target::Local(_) => "local",
+        },
+    })
+    .collect();
+    let backups = backup::list_backups(&state.database)
+        .await
+        .map_err(map_eyre_error)?
+        .into_iter()
+        .map(|record| Backup {
+            restorable: record.version_id.is_some()
+                || record
+                    .key
+                    .as_deref()
+                    .is_some_and(|key| key.starts_with("db-")),
+            record,
+        })
+        .collect();
+    let context = Context {
+        destinations,
+        backups,
+        restore_staged: backup::is_restore_staged(&state.options.data_dir),
+        message,
+        error,
+    };
+    let response = templates
+        .render("admin/backups.html", &context)
+        .map_err(map_eyre_error)?;
+    Ok((status, response).into_response())
+}
+
+#[derive(Deserialize)]
+struct DestinationQuery {
+    /// See [`backup::Config::destination`], only used for [`BackupSelector::Latest`], recorded
+    /// backups use the destination they were saved to.
+    destination: Option<String>,
+}
+
+async fn config(
+    state: &AppState,
+    selector: BackupSelector,
+    query: DestinationQuery,
+) -> axum::response::Result<backup::Config> {
+    let destination = match selector {
+        BackupSelector::Latest => query.destination,
+        BackupSelector::Recorded(id) => {
+            backup::get_backup(&state.database, id)
+                .await
+                .map_err(map_eyre_error)?
+                .ok_or(StatusCode::NOT_FOUND)?
+                .destination
+        }
+    };
+    Ok(backup::Config::new(
+        state.options,
+        destination.as_deref(),
+        state.client.clone(),
+        state.database.clone(),
+        state.blobs.clone(),
+    )
+    .ok_or(StatusCode::NOT_FOUND)?)
+}
+
+fn parse_selector(selector: &str) -> axum::response::Result<BackupSelector> {
+    Ok(selector
+        .parse()
+        .map_err(|_| (StatusCode::BAD_REQUEST, "Invalid backup"))?)
+}
+
+async fn index_handler(
+    State(state): State<AppState>,
+    Extension(templates): Extension<TemplatesWithContext>,
+) -> axum::response::Result<Response> {
+    render_index(&state, &templates, None).await
+}
+
+async fn verify_handler(
+    Path(selector): Path<String>,
+    Query(query): Query<DestinationQuery>,
+    State(state): State<AppState>,
+    Extension(templates): Extension<TemplatesWithContext>,
+) -> axum::response::Result<Response> {
+    let selector = parse_selector(&selector)?;
+    let config = config(&state, selector, query).await?;
+    let result = match backup::verify_backup(&config, selector).await {
+        Ok(verified) => Ok(format!(
+            "Backup {selector:?} passed verification ({} bytes)",
+            verified.size
+        )),
+        Err(error) => {
+            tracing::error!("Backup {selector:?} failed verification: {error:?}");
+            Err(format!(
+                "Backup {selector:?} failed verification: {error:#}"
+            ))
+        }
+    };
+    render_index(&state, &templates, Some(result)).await
+}
+
+async fn restore_handler(
+    Path(selector): Path<String>,
+    Query(query): Query<DestinationQuery>,
+    State(state): State<AppState>,
+    Extension(templates): Extension<TemplatesWithContext>,
+) -> axum::response::Result<Response> {
+    let selector = parse_selector(&selector)?;
+    let config = config(&state, selector, query).await?;
+    let result = match backup::stage_restore(&config, selector, &state.options.data_dir).await {
+        Ok(()) => Ok(format!(
+            "Backup {selector:?} passed verification and will replace the database when the application restarts"
+        )),
+        Err(error) => {
+            tracing::error!("Error staging restore of backup {selector:?}: {error:?}");
+            Err(format!("Unable to restore backup {selector:?}: {error:#}"))
+        }
+    };
+    render_index(&state, &templates, Some(result)).await
+}
+
+async fn cancel_restore_handler(
+    State(state): State<AppState>,
+    Extension(templates): Extension<TemplatesWithContext>,
+) -> axum::response::Result<Response> {
+    backup::cancel_staged_restore(&state.options.data_dir)
+        .await
+        .map_err(map_eyre_error)?;
+    render_index(
+        &state,
+        &templates,
+        Some(Ok("Cancelled the staged restore".to_owned())),
+    )
+    .await
+}
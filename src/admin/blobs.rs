@@ -0,0 +1,84 @@
+//! Storage used by the blob store (see [`blob`]), and manual garbage collection.
+
+use axum::{
+    extract::State,
+    response::{IntoResponse, Response},
+    routing::{get, post},
+    Extension, Router,
+};
+use http::StatusCode;
+use serde::Serialize;
+
+use crate::{
+    database::blob::{self, Usage},
+    error::map_eyre_error,
+    state::AppState,
+    templates::TemplatesWithContext,
+};
+
+pub fn router() -> Router<AppState> {
+    Router::new()
+        .route("/", get(index_handler))
+        .route("/collect-garbage", post(collect_garbage_handler))
+}
+
+#[derive(Serialize)]
+struct Context {
+    usage: Usage,
+    message: Option<String>,
+    error: Option<String>,
+}
+
+async fn render_index(
+    state: &AppState,
+    templates: &TemplatesWithContext,
+    result: Option<Result<String, String>>,
+) -> axum::response::Result<Response> {
+    let status = if matches!(result, Some(Err(_))) {
+        StatusCode::INTERNAL_SERVER_ERROR
+    } else {
+        StatusCode::OK
+    };
+    let (message, error) = match result {
+        Some(Ok(message)) => (Some(message), None),
+        Some(Err(error)) => (None, Some(error)),
+        None => (None, None),
+    };
+    let context = Context {
+        usage: state.blobs.usage().await.map_err(map_eyre_error)?,
+        message,
+        error,
+    };
+    let response = templates
+        .render("admin/blobs.html", &context)
+        .map_err(map_eyre_error)?;
+    Ok((status, response).into_response())
+}
+
+async fn index_handler(
+    State(state): State<AppState>,
+    Extension(templates): Extension<TemplatesWithContext>,
+) -> axum::response::Result<Response> {
+    render_index(&state, &templates, None).await
+}
+
+async fn collect_garbage_handler(
+    State(state): State<AppState>,
+    Extension(templates): Extension<TemplatesWithContext>,
+) -> axum::response::Result<Response> {
+    tracing::info!("Collecting blob garbage manually");
+    let result = match state.blobs.collect_garbage().await {
+        Ok(blob::GarbageCollection {
+            removed_blobs,
+            removed_bytes,
+            removed_orphans,
+        }) => Ok(format!(
+            "Removed {removed_blobs} unreferenced blobs ({removed_bytes} bytes) and {removed_orphans} orphaned files"
+        )),
+        Err(error) => {
+            tracing::error!("Error collecting blob garbage: {error:?}");
+            Err(format!("Error collecting blob garbage: {error:#}"))
+        }
+    };
+    render_index(&state, &templates, Some(result)).await
+}
@@ -0,0 +1,21 @@
+//! Reload the [`ReloadableOptions`](crate::options::ReloadableOptions) (see
+//! [`options::reload`]) without restarting the server.
+
+use axum::{extract::State, response::Redirect, routing::post, Router};
+
+use crate::{error::map_eyre_error, options, state::AppState};
+
+pub fn router() -> Router<AppState> {
+    Router::new().route("/reload", post(reload_handler))
+}
+
+async fn reload_handler(State(state): State<AppState>) -> axum::response::Result<Redirect> {
+    tracing::info!("Reloading configuration from the admin page");
+    options::reload(
+        &state.reloadable_options,
+        state.forecast_spreadsheet_schemas.schemas(),
+    )
+    .await
+    .map_err(map_eyre_error)?;
+    Ok(Redirect::to("/admin"))
+}
@@ -1,40 +1,67 @@
 use axum::{
+    extract::DefaultBodyLimit,
     response::{IntoResponse, Redirect, Response},
     routing::{get, post},
     Extension, Router,
 };
 use eyre::ContextCompat;
+use http::StatusCode;
+use serde::Serialize;
 
 use crate::{
     database::Database,
     error::map_eyre_error,
-    forecast_areas::{upsert_forecast_area, ForecastArea},
+    forecast_areas::{
+        upsert_forecast_area,
+        validation::{ValidationErrors, ValidationIssue, MAX_GEOJSON_BYTES},
+        ForecastArea,
+    },
     state::AppState,
     templates::TemplatesWithContext,
 };
 
+#[derive(Serialize)]
+struct Context {
+    errors: Vec<ValidationIssue>,
+}
+
 pub fn router() -> Router<AppState> {
-    Router::new()
-        .route("/", get(get_handler))
-        .route("/", post(post_handler))
+    Router::new().route("/", get(get_handler)).route(
+        "/",
+        post(post_handler).layer(DefaultBodyLimit::max(MAX_GEOJSON_BYTES)),
+    )
 }
 
 async fn get_handler(
     Extension(templates): Extension<TemplatesWithContext>,
 ) -> axum::response::Result<Response> {
+    let context = Context { errors: Vec::new() };
     Ok(templates
-        .render("admin/forecast_areas/create.html", &())
+        .render("admin/forecast_areas/create.html", &context)
         .map_err(map_eyre_error)?)
 }
 
 async fn post_handler(
     Extension(database): Extension<Database>,
+    Extension(templates): Extension<TemplatesWithContext>,
     multipart: axum::extract::Multipart,
 ) -> axum::response::Result<Response> {
-    post_impl(&database, multipart)
-        .await
-        .map_err(map_eyre_error)?;
-    Ok(Redirect::to("../forecast-areas").into_response())
+    match post_impl(&database, multipart).await {
+        Ok(()) => Ok(Redirect::to("../forecast-areas").into_response()),
+        Err(error) => {
+            let errors = error
+                .downcast::<ValidationErrors>()
+                .map_err(map_eyre_error)?;
+            tracing::warn!("Invalid forecast area: {errors}");
+            let context = Context {
+                errors: errors.errors,
+            };
+            let response = templates
+                .render("admin/forecast_areas/create.html", &context)
+                .map_err(map_eyre_error)?;
+            Ok((StatusCode::UNPROCESSABLE_ENTITY, response).into_response())
+        }
+    }
 }
 
 pub async fn post_impl(
@@ -49,7 +76,7 @@ pub async fn post_impl(
                 id = Some(field.text().await?.into());
             }
             Some("geojson") => {
-                geojson = Some(serde_json::from_slice(&field.bytes().await?)?);
+                geojson = Some(parse_geojson(&field.bytes().await?)?);
             }
             _ => {}
         }
@@ -60,7 +87,16 @@ pub async fn post_impl(
         geojson: geojson.wrap_err("geojson field was not specified")?,
     };
 
-    upsert_forecast_area(database, forecast_area).await?;
+    let validated = upsert_forecast_area(database, forecast_area).await?;
+    for fix in &validated.fixes {
+        tracing::info!("Fixed forecast area geometry: {fix}");
+    }
 
     Ok(())
 }
+
+/// Parse an uploaded GeoJSON file, reporting invalid JSON as a [`ValidationErrors`].
+pub fn parse_geojson(bytes: &[u8]) -> eyre::Result<serde_json::Value> {
+    serde_json::from_slice(bytes)
+        .map_err(|error| ValidationErrors::single("", format!("Invalid JSON: {error}")).into())
+}
@@ -1,16 +1,21 @@
 use axum::{
-    extract,
+    extract::{self, DefaultBodyLimit},
     response::{IntoResponse, Redirect, Response},
     routing::{get, post},
     Extension, Router,
 };
 use eyre::ContextCompat;
+use http::StatusCode;
 use serde::{Deserialize, Serialize};
 
 use crate::{
     database::Database,
     error::map_eyre_error,
-    forecast_areas::{upsert_forecast_area, ForecastArea, ForecastAreaId},
+    forecast_areas::{
+        upsert_forecast_area,
+        validation::{ValidationErrors, ValidationIssue, MAX_GEOJSON_BYTES},
+        ForecastArea, ForecastAreaId,
+    },
     state::AppState,
     templates::TemplatesWithContext,
 };
@@ -23,12 +28,14 @@ struct PathParameters {
 #[derive(Serialize)]
 struct Context {
     forecast_area_id: ForecastAreaId,
+    errors: Vec<ValidationIssue>,
 }
 
 pub fn router() -> Router<AppState> {
-    Router::new()
-        .route("/", get(get_handler))
-        .route("/", post(post_handler))
+    Router::new().route("/", get(get_handler)).route(
+        "/",
+        post(post_handler).layer(DefaultBodyLimit::max(MAX_GEOJSON_BYTES)),
+    )
 }
 
 async fn get_handler(
@@ -37,6 +44,7 @@ async fn get_handler(
 ) -> axum::response::Result<Response> {
     let context = Context {
         forecast_area_id: path.forecast_area_id,
+        errors: Vec::new(),
     };
     Ok(templates
         .render("admin/forecast_areas/edit.html", &context)
@@ -46,12 +54,26 @@ async fn get_handler(
 async fn post_handler(
     extract::Path(path): extract::Path<PathParameters>,
     Extension(database): Extension<Database>,
+    Extension(templates): Extension<TemplatesWithContext>,
     multipart: axum::extract::Multipart,
 ) -> axum::response::Result<Response> {
-    post_impl(path.forecast_area_id, &database, multipart)
-        .await
-        .map_err(map_eyre_error)?;
-    Ok(Redirect::to("../../forecast-areas").into_response())
+    match post_impl(path.forecast_area_id.clone(), &database, multipart).await {
+        Ok(()) => Ok(Redirect::to("../../forecast-areas").into_response()),
+        Err(error) => {
+            let errors = error
+                .downcast::<ValidationErrors>()
+                .map_err(map_eyre_error)?;
+            tracing::warn!("Invalid forecast area {}: {errors}", path.forecast_area_id);
+            let context = Context {
+                forecast_area_id: path.forecast_area_id,
+                errors: errors.errors,
+            };
+            let response = templates
+                .render("admin/forecast_areas/edit.html", &context)
+                .map_err(map_eyre_error)?;
+            Ok((StatusCode::UNPROCESSABLE_ENTITY, response).into_response())
+        }
+    }
 }
 
 pub async fn post_impl(
@@ -61,11 +83,8 @@ pub async fn post_impl(
 ) -> eyre::Result<()> {
     let mut geojson = None;
     while let Some(field) = multipart.next_field().await? {
-        match field.name() {
-            Some("geojson") => {
-                geojson = Some(serde_json::from_slice(&field.bytes().await?)?);
-            }
-            _ => {}
+        if let Some("geojson") = field.name() {
+            geojson = Some(super::create::parse_geojson(&field.bytes().await?)?);
         }
     }
 
@@ -74,7 +93,10 @@ pub async fn post_impl(
         geojson: geojson.wrap_err("geojson field was not specified")?,
     };
 
-    upsert_forecast_area(database, forecast_area).await?;
+    let validated = upsert_forecast_area(database, forecast_area).await?;
+    for fix in &validated.fixes {
+        tracing::info!("Fixed forecast area geometry: {fix}");
+    }
 
     Ok(())
 }
@@ -0,0 +1,119 @@
+//! An interactive editor for drawing a forecast area's polygons and elevation band boundary
+//! lines on a map, using Leaflet.draw. The editor loads and saves the area's GeoJSON using the
+//! `geometry` endpoint.
+
+use axum::{
+    extract,
+    response::{IntoResponse, Response},
+    Extension, Json,
+};
+use http::StatusCode;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::{
+    database::Database,
+    error::map_eyre_error,
+    forecast_areas::{
+        delete_forecast_area, get_forecast_area, upsert_forecast_area,
+        validation::{ValidationErrors, ELEVATION_BAND_BOUNDARY_PROPERTY},
+        ForecastArea, ForecastAreaId,
+    },
+    templates::TemplatesWithContext,
+};
+
+#[derive(Deserialize)]
+pub struct PathParameters {
+    forecast_area_id: ForecastAreaId,
+}
+
+#[derive(Serialize)]
+struct Context {
+    /// `None` when creating a new forecast area.
+    forecast_area_id: Option<ForecastAreaId>,
+    elevation_band_boundary_property: &'static str,
+}
+
+fn render_editor(
+    templates: &TemplatesWithContext,
+    forecast_area_id: Option<ForecastAreaId>,
+) -> axum::response::Result<Response> {
+    let context = Context {
+        forecast_area_id,
+        elevation_band_boundary_property: ELEVATION_BAND_BOUNDARY_PROPERTY,
+    };
+    Ok(templates
+        .render("admin/forecast_areas/editor.html", &context)
+        .map_err(map_eyre_error)?)
+}
+
+/// Handler for `editor`, to draw a new forecast area.
+pub async fn new_handler(
+    Extension(templates): Extension<TemplatesWithContext>,
+) -> axum::response::Result<Response> {
+    render_editor(&templates, None)
+}
+
+/// Handler for `{forecast_area_id}/editor`.
+pub async fn handler(
+    extract::Path(path): extract::Path<PathParameters>,
+    Extension(templates): Extension<TemplatesWithContext>,
+) -> axum::response::Result<Response> {
+    render_editor(&templates, Some(path.forecast_area_id))
+}
+
+/// Handler for `GET {forecast_area_id}/geometry`, the area's GeoJSON.
+pub async fn get_geometry_handler(
+    extract::Path(path): extract::Path<PathParameters>,
+    Extension(database): Extension<Database>,
+) -> axum::response::Result<Response> {
+    let forecast_area = get_forecast_area(&database, &path.forecast_area_id)
+        .await
+        .map_err(map_eyre_error)?
+        .ok_or(StatusCode::NOT_FOUND)?;
+    Ok(Json(forecast_area.geojson).into_response())
+}
+
+/// Handler for `PUT {forecast_area_id}/geometry`, creating or replacing the area's GeoJSON
+/// after it has been validated. Responds with the normalized GeoJSON, or with the validation
+/// errors.
+pub async fn put_geometry_handler(
+    extract::Path(path): extract::Path<PathParameters>,
+    Extension(database): Extension<Database>,
+    Json(geojson): Json<Value>,
+) -> axum::response::Result<Response> {
+    let result = upsert_forecast_area(
+        &database,
+        ForecastArea {
+            id: path.forecast_area_id,
+            geojson,
+        },
+    )
+    .await;
+    match result {
+        Ok(validated) => {
+            for fix in &validated.fixes {
+                tracing::info!("Fixed forecast area geometry: {fix}");
+            }
+            Ok(Json(validated.geojson).into_response())
+        }
+        Err(error) => match error.downcast::<ValidationErrors>() {
+            Ok(errors) => {
+                tracing::warn!("Invalid forecast area geometry: {errors}");
+                Ok(errors.into_response())
+            }
+            Err(error) => Err(map_eyre_error(error).into()),
+        },
+    }
+}
+
+/// Handler for `DELETE {forecast_area_id}/geometry`, deleting the forecast area.
+pub async fn delete_geometry_handler(
+    extract::Path(path): extract::Path<PathParameters>,
+    Extension(database): Extension<Database>,
+) -> axum::response::Result<Response> {
+    delete_forecast_area(&database, &path.forecast_area_id)
+        .await
+        .map_err(map_eyre_error)?;
+    Ok(StatusCode::NO_CONTENT.into_response())
+}
@@ -1,14 +1,24 @@
-use axum::{routing::get, Router};
+use axum::{extract::DefaultBodyLimit, routing::get, Router};
 
-use crate::state::AppState;
+use crate::{forecast_areas::validation::MAX_GEOJSON_BYTES, state::AppState};
 
 mod create;
 mod edit;
+mod editor;
 mod index;
 
 pub fn router() -> Router<AppState> {
     Router::new()
         .route("/", get(index::handler))
         .nest("/create", create::router())
+        .route("/editor", get(editor::new_handler))
         .nest("/{forecast_area_id}/edit", edit::router())
+        .route("/{forecast_area_id}/editor", get(editor::handler))
+        .route(
+            "/{forecast_area_id}/geometry",
+            get(editor::get_geometry_handler)
+                .put(editor::put_geometry_handler)
+                .delete(editor::delete_geometry_handler)
+                .layer(DefaultBodyLimit::max(MAX_GEOJSON_BYTES)),
+        )
 }
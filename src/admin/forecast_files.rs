@@ -1,55 +1,977 @@
+//! Management of the cached forecast files (see [`crate::forecasts::get_forecast_data`]), and of
+//! forecast files uploaded directly instead of being published to Google Drive.
+
+use std::collections::{HashMap, HashSet};
+
 use axum::{
-    response::{Redirect, Response},
-    routing::get,
-    Extension, Router,
+    extract::{DefaultBodyLimit, Path, State},
+    response::{IntoResponse, Redirect, Response},
+    routing::{get, post},
+    Extension, Form, Router,
 };
-use serde::Serialize;
+use eyre::Context as _;
+use http::{header, StatusCode};
+use serde::{Deserialize, Serialize};
 
 use crate::{
+    database::Database,
     error::{map_eyre_error, map_std_error},
+    forecasts::{
+        canonical_forecast_name, load_file_blob, parse_forecast_file_blob,
+        parse_forecast_file_blob_with_schema, parse_forecast_name,
+        published::{list_uploaded_files, UPLOADED_ID_PREFIX},
+    },
+    google_drive::{ListFileMetadata, CAAML_MIME_TYPE, SPREADSHEET_MIME_TYPES},
+    short_links,
     state::AppState,
     templates::TemplatesWithContext,
-    types,
+    types, webhooks,
 };
 
+/// Maximum size of an uploaded forecast spreadsheet.
+const MAX_FORECAST_FILE_BYTES: usize = 10 * 1024 * 1024;
+/// Maximum size of an imported CAAML document, which may contain a whole season of bulletins.
+const MAX_CAAML_DOCUMENT_BYTES: usize = 50 * 1024 * 1024;
+
 pub fn router() -> Router<AppState> {
     Router::new()
         .route("/", get(index_handler))
         .route("/clear", get(clear_handler))
+        .route("/template", get(template_handler))
+        .route(
+            "/upload",
+            post(upload_handler).layer(DefaultBodyLimit::max(MAX_FORECAST_FILE_BYTES)),
+        )
+        .route(
+            "/validate",
+            get(validate_handler)
+                .post(validate_upload_handler)
+                .layer(DefaultBodyLimit::max(MAX_FORECAST_FILE_BYTES)),
+        )
+        .route(
+            "/import",
+            post(import_handler).layer(DefaultBodyLimit::max(MAX_CAAML_DOCUMENT_BYTES)),
+        )
+        .route("/{id}/reparse", post(reparse_handler))
+        .route("/{id}/rename", post(rename_handler))
+        .route("/{id}/delete", post(delete_handler))
 }
 
 #[derive(Serialize)]
 struct ForecastFileDetails {
     google_drive_id: String,
+    /// The name of the file, if it is still in the published files listing.
+    name: Option<String>,
+    uploaded: bool,
     time: Option<types::Time>,
+    schema_version: Option<String>,
+    parse_error: Option<String>,
+    /// The name following the naming convention, if it differs from the current name.
+    suggested_name: Option<String>,
 }
 
 #[derive(Serialize)]
 struct Context {
     forecast_files: Vec<ForecastFileDetails>,
+    schema_versions: Vec<String>,
+    /// Names of the areas used in forecast file names, which CAAML documents can be imported for.
+    areas: Vec<String>,
+    /// Number of elevation band boundaries to specify when importing a CAAML document.
+    elevation_boundaries: usize,
+    error: Option<String>,
+    import: Option<ImportSummary>,
+}
+
+/// The outcome of importing a CAAML document.
+#[derive(Serialize)]
+struct ImportSummary {
+    imported: usize,
+    skipped: Vec<SkippedBulletin>,
+}
+
+#[derive(Serialize)]
+struct SkippedBulletin {
+    name: String,
+    reason: String,
+}
+
+/// Extension of the file name for an uploaded file with the `mime_type`.
+fn file_extension(mime_type: &str) -> &'static str {
+    match mime_type {
+        "application/vnd.oasis.opendocument.spreadsheet" => "ods",
+        CAAML_MIME_TYPE => "caaml",
+        _ => "xlsx",
+    }
+}
+
+/// MIME type of an uploaded spreadsheet with the `file_name`.
+fn spreadsheet_mime_type(file_name: &str) -> Option<&'static str> {
+    let extension = file_name.rsplit_once('.')?.1.to_lowercase();
+    SPREADSHEET_MIME_TYPES
+        .iter()
+        .copied()
+        .find(|mime_type| file_extension(mime_type) == extension)
+}
+
+async fn render_index(
+    state: &AppState,
+    templates: &TemplatesWithContext,
+    error: Option<String>,
+) -> axum::response::Result<Response> {
+    render_index_with_import(state, templates, error, None).await
+}
+
+async fn render_index_with_import(
+    state: &AppState,
+    templates: &TemplatesWithContext,
+    error: Option<String>,
+    import: Option<ImportSummary>,
+) -> axum::response::Result<Response> {
+    let status = if error.is_some() {
+        StatusCode::UNPROCESSABLE_ENTITY
+    } else {
+        StatusCode::OK
+    };
+    // The listing may be unavailable while Google Drive is unreachable, the cached files can
+    // still be managed without their names.
+    let files: HashMap<String, ListFileMetadata> = match state.published_files.list_files().await {
+        Ok(files) => files
+            .into_iter()
+            .map(|file| (file.id.clone(), file))
+            .collect(),
+        Err(list_error) => {
+            tracing::warn!("Unable to list published files: {list_error:?}");
+            list_uploaded_files(&state.database)
+                .await
+                .map_err(map_eyre_error)?
+                .into_iter()
+                .map(|file| (file.id.clone(), file))
+                .collect()
+        }
+    };
+    let records = sqlx::query!(
+        r#"SELECT google_drive_id, json_extract(parsed_forecast, "$.time") as "time?: types::Time", parsed_forecast as "parsed_forecast: sqlx::types::Json<forecast_spreadsheet::Forecast>", schema_version, parse_error FROM forecast_files ORDER BY json_extract(parsed_forecast, "$.time") DESC"#
+    )
+    .fetch_all(&state.database)
+    .await
+    .map_err(map_std_error)?;
+    let forecast_files = records
+        .into_iter()
+        .map(|record| {
+            let file = files.get(&record.google_drive_id);
+            let suggested_name =
+                Option::zip(record.parsed_forecast, file).and_then(|(forecast, file)| {
+                    canonical_forecast_name(
+                        &forecast.0,
+                        state.forecast_spreadsheet_schema,
+                        file_extension(&file.mime_type),
+                    )
+                    .ok()
+                    .filter(|suggested_name| *suggested_name != file.name)
+                });
+            ForecastFileDetails {
+                uploaded: record.google_drive_id.starts_with(UPLOADED_ID_PREFIX),
+                name: file.map(|file| file.name.clone()),
+                google_drive_id: record.google_drive_id,
+                time: record.time,
+                schema_version: record.schema_version,
+                parse_error: record.parse_error,
+                suggested_name,
+            }
+        })
+        .collect();
+    let context = Context {
+        forecast_files,
+        schema_versions: state
+            .forecast_spreadsheet_schemas
+            .schemas()
+            .iter()
+            .map(|schema| schema.schema_version.to_string())
+            .collect(),
+        areas: {
+            let mut areas: Vec<String> = state
+                .forecast_spreadsheet_schema
+                .area
+                .map
+                .keys()
+                .cloned()
+                .collect();
+            areas.sort();
+            areas
+        },
+        elevation_boundaries: state
+            .forecast_spreadsheet_schema
+            .elevation_bands
+            .len()
+            .saturating_sub(1),
+        error,
+        import,
+    };
+    let response = templates
+        .render("admin/forecast_files.html", &context)
+        .map_err(map_eyre_error)?;
+    Ok((status, response).into_response())
 }
 
 pub async fn index_handler(
+    State(state): State<AppState>,
     Extension(templates): Extension<TemplatesWithContext>,
-    Extension(database): Extension<crate::database::Database>,
-) -> axum::response::Result<Response> {
-    let forecast_files = sqlx::query_as!(
-        ForecastFileDetails,
-        r#"SELECT google_drive_id, json_extract(parsed_forecast, "$.time") as "time?: types::Time" FROM forecast_files"#
-    ).fetch_all(&database).await.map_err(map_std_error)?;
-    let context = Context { forecast_files };
-    templates
-        .render("admin/forecast_files.html", &context)
-        .map_err(map_eyre_error)
-        .map_err(Into::into)
+) -> axum::response::Result<Response> {
+    render_index(&state, &templates, None).await
 }
 
 pub async fn clear_handler(
-    Extension(database): Extension<crate::database::Database>,
+    Extension(database): Extension<Database>,
 ) -> axum::response::Result<Redirect> {
-    sqlx::query!("DELETE FROM forecast_files")
+    // Only the files cached from Google Drive, the uploaded files (see `UPLOADED_ID_PREFIX`) have
+    // no other copy.
+    sqlx::query!("DELETE FROM forecast_files WHERE google_drive_id NOT LIKE 'upload-%'")
         .execute(&database)
         .await
         .map_err(map_std_error)?;
     Ok(Redirect::to("../forecast-files"))
 }
+
+/// Check that `name` follows the naming convention for a file with the `mime_type`, and
+/// that it isn't used by another published file (files are looked up by name).
+async fn validate_name(
+    state: &AppState,
+    name: &str,
+    mime_type: &str,
+    id: Option<&str>,
+) -> eyre::Result<Result<(), String>> {
+    if let Err(error) = parse_forecast_name(name, state.forecast_spreadsheet_schema) {
+        return Ok(Err(format!(
+            "Invalid name {name:?}, expected e.g. Gudauri_2023-01-24T17:00_LF.xlsx: {error:#}"
+        )));
+    }
+    let extension = file_extension(mime_type);
+    if !name.ends_with(&format!(".{extension}")) {
+        return Ok(Err(format!(
+            "Invalid name {name:?}, expected the extension .{extension}"
+        )));
+    }
+    let files = state.published_files.list_files().await?;
+    if files
+        .iter()
+        .any(|file| file.name == name && Some(file.id.as_str()) != id)
+    {
+        return Ok(Err(format!("A file named {name:?} already exists")));
+    }
+    Ok(Ok(()))
+}
+
+async fn upload_handler(
+    State(state): State<AppState>,
+    Extension(templates): Extension<TemplatesWithContext>,
+    mut multipart: axum::extract::Multipart,
+) -> axum::response::Result<Response> {
+    let mut file = None;
+    let mut name = None;
+    while let Some(field) = multipart.next_field().await.map_err(map_std_error)? {
+        match field.name() {
+            Some("file") => {
+                let file_name = field.file_name().unwrap_or_default().to_owned();
+                file = Some((file_name, field.bytes().await.map_err(map_std_error)?));
+            }
+            Some("name") => {
+                let text = field.text().await.map_err(map_std_error)?;
+                name = Some(text.trim().to_owned()).filter(|name| !name.is_empty());
+            }
+            _ => {}
+        }
+    }
+    let Some((file_name, file_blob)) = file.filter(|(_, file_blob)| !file_blob.is_empty()) else {
+        return render_index(&state, &templates, Some("No file was uploaded".to_owned())).await;
+    };
+    let Some(mime_type) = spreadsheet_mime_type(&file_name) else {
+        return render_index(
+            &state,
+            &templates,
+            Some(format!(
+                "Unsupported file {file_name:?}, expected an .xlsx or .ods spreadsheet"
+            )),
+        )
+        .await;
+    };
+    let forecast = match parse_forecast_file_blob(&file_blob, state.forecast_spreadsheet_schemas) {
+        Ok(forecast) => forecast,
+        Err(error) => {
+            return render_index(
+                &state,
+                &templates,
+                Some(format!("Error parsing {file_name:?}: {error:#}")),
+            )
+            .await;
+        }
+    };
+    // Use the name of the uploaded file if it already follows the naming convention.
+    let name = match name {
+        Some(name) => name,
+        None if parse_forecast_name(&file_name, state.forecast_spreadsheet_schema).is_ok() => {
+            file_name
+        }
+        None => canonical_forecast_name(
+            &forecast,
+            state.forecast_spreadsheet_schema,
+            file_extension(mime_type),
+        )
+        .map_err(map_eyre_error)?,
+    };
+    if let Err(error) = validate_name(&state, &name, mime_type, None)
+        .await
+        .map_err(map_eyre_error)?
+    {
+        return render_index(&state, &templates, Some(error)).await;
+    }
+
+    let id = format!("{UPLOADED_ID_PREFIX}{}", uuid::Uuid::new_v4());
+    // Truncated to whole seconds so that it is unchanged by being stored in the database, the
+    // listed modified time needs to match the cached file exactly (see `get_forecast_data`).
+    let modified_time: types::Time = time::OffsetDateTime::now_utc()
+        .replace_millisecond(0)
+        .wrap_err("Error truncating modified time")
+        .map_err(map_eyre_error)?
+        .into();
+    let blob_hash = state.blobs.put(file_blob).await.map_err(map_eyre_error)?;
+    let schema_version = state
+        .forecast_spreadsheet_schemas
+        .select(&forecast.template_version)
+        .ok()
+        .map(|schema| schema.schema_version.to_string());
+    let parsed_forecast = sqlx::types::Json(&forecast);
+    let mut transaction = state.database.begin().await.map_err(map_std_error)?;
+    sqlx::query!(
+        "INSERT INTO uploaded_forecast_files(id, name, mime_type, modified_time) VALUES($1, $2, $3, $4)",
+        id,
+        name,
+        mime_type,
+        modified_time,
+    )
+    .execute(&mut *transaction)
+    .await
+    .map_err(map_std_error)?;
+    sqlx::query!(
+        "INSERT INTO forecast_files(google_drive_id, last_modified, file_blob, blob_hash, parsed_forecast, schema_version) VALUES($1, $2, x'', $3, $4, $5)",
+        id,
+        modified_time,
+        blob_hash,
+        parsed_forecast,
+        schema_version,
+    )
+    .execute(&mut *transaction)
+    .await
+    .map_err(map_std_error)?;
+    transaction.commit().await.map_err(map_std_error)?;
+    tracing::info!("Uploaded forecast file {name:?} ({id})");
+    let base_url = state.options.base_url();
+    let short_url = match short_links::get_or_create_token(&state.database, &name).await {
+        Ok(token) => short_links::short_url(&base_url, &token),
+        Err(error) => {
+            tracing::warn!("Error creating short link for {name:?}: {error:?}");
+            None
+        }
+    };
+    state.aggregators.publish(&name).await;
+    if let Some(url) = webhooks::forecast_url(&base_url, &name) {
+        state.webhooks.send(webhooks::Event::ForecastPublished {
+            name,
+            url,
+            short_url,
+        });
+    }
+    Ok(Redirect::to("/admin/forecast-files").into_response())
+}
+
+/// The result of checking a draft forecast spreadsheet, see [`validate_upload_handler`].
+#[derive(Serialize)]
+struct ValidationReport {
+    template_version: String,
+    /// The schema which was selected to parse the spreadsheet.
+    schema_version: Option<String>,
+    forecast: forecast_spreadsheet::Forecast,
+    /// Fields which could not be parsed, and were left empty or skipped.
+    warnings: Vec<forecast_spreadsheet::ParseWarning>,
+    /// The name the forecast would be published under.
+    name: Option<String>,
+    /// The public URL of the forecast page.
+    url: Option<String>,
+    /// Problems with the name of the file, which would prevent it from being published.
+    name_problems: Vec<String>,
+}
+
+#[derive(Serialize, Default)]
+struct ValidateContext {
+    file_name: Option<String>,
+    report: Option<ValidationReport>,
+    error: Option<String>,
+}
+
+async fn render_validate(
+    templates: &TemplatesWithContext,
+    context: &ValidateContext,
+) -> axum::response::Result<Response> {
+    let status = if context.error.is_some() {
+        StatusCode::UNPROCESSABLE_ENTITY
+    } else {
+        StatusCode::OK
+    };
+    let response = templates
+        .render("admin/forecast_validate.html", context)
+        .map_err(map_eyre_error)?;
+    Ok((status, response).into_response())
+}
+
+async fn validate_handler(
+    Extension(templates): Extension<TemplatesWithContext>,
+) -> axum::response::Result<Response> {
+    render_validate(&templates, &ValidateContext::default()).await
+}
+
+/// Parse an uploaded draft forecast spreadsheet leniently, reporting what would be published
+/// without storing anything, so that problems can be fixed before the spreadsheet is placed in
+/// the Google Drive folder.
+async fn validate_upload_handler(
+    State(state): State<AppState>,
+    Extension(templates): Extension<TemplatesWithContext>,
+    mut multipart: axum::extract::Multipart,
+) -> axum::response::Result<Response> {
+    let mut file = None;
+    while let Some(field) = multipart.next_field().await.map_err(map_std_error)? {
+        if field.name() == Some("file") {
+            let file_name = field.file_name().unwrap_or_default().to_owned();
+            file = Some((file_name, field.bytes().await.map_err(map_std_error)?));
+        }
+    }
+    let Some((file_name, file_blob)) = file.filter(|(_, file_blob)| !file_blob.is_empty()) else {
+        return render_validate(
+            &templates,
+            &ValidateContext {
+                error: Some("No file was uploaded".to_owned()),
+                ..ValidateContext::default()
+            },
+        )
+        .await;
+    };
+    let error_context = |error: String| ValidateContext {
+        file_name: Some(file_name.clone()),
+        report: None,
+        error: Some(error),
+    };
+    let Some(mime_type) = spreadsheet_mime_type(&file_name) else {
+        return render_validate(
+            &templates,
+            &error_context(format!(
+                "Unsupported file {file_name:?}, expected an .xlsx or .ods spreadsheet"
+            )),
+        )
+        .await;
+    };
+    let (forecast, warnings) = match state
+        .forecast_spreadsheet_schemas
+        .parse_excel_spreadsheet_lenient(&file_blob)
+    {
+        Ok(parsed) => parsed,
+        Err(error) => {
+            return render_validate(
+                &templates,
+                &error_context(format!("Error parsing {file_name:?}: {error:#}")),
+            )
+            .await;
+        }
+    };
+
+    let mut name_problems = Vec::new();
+    let name = match parse_forecast_name(&file_name, state.forecast_spreadsheet_schema) {
+        Ok(details) => {
+            let area = state
+                .forecast_spreadsheet_schema
+                .area
+                .map
+                .get(&details.forecast.area);
+            if area != Some(&forecast.area) {
+                name_problems.push(format!(
+                    "The area in the name ({}) does not match the area of the forecast ({})",
+                    details.forecast.area, forecast.area
+                ));
+            }
+            if details.forecast.time != forecast.time {
+                name_problems.push(format!(
+                    "The time in the name ({}) does not match the time of the forecast ({})",
+                    details.forecast.time, forecast.time
+                ));
+            }
+            Some(file_name.clone())
+        }
+        Err(error) => {
+            name_problems.push(format!(
+                "The name does not follow the naming convention: {error:#}"
+            ));
+            match canonical_forecast_name(
+                &forecast,
+                state.forecast_spreadsheet_schema,
+                file_extension(mime_type),
+            ) {
+                Ok(name) => Some(name),
+                Err(error) => {
+                    name_problems.push(format!("Unable to suggest a name: {error:#}"));
+                    None
+                }
+            }
+        }
+    };
+    if let Some(name) = &name {
+        match validate_name(&state, name, mime_type, None).await {
+            Ok(Ok(())) => {}
+            Ok(Err(problem)) => name_problems.push(problem),
+            Err(error) => {
+                tracing::warn!("Unable to check the name of {name:?}: {error:?}");
+                name_problems.push(format!(
+                    "Unable to check for published files with the same name: {error:#}"
+                ));
+            }
+        }
+    }
+    let url = name
+        .as_ref()
+        .and_then(|name| webhooks::forecast_url(&state.options.base_url(), name))
+        .map(|url| url.to_string());
+
+    let report = ValidationReport {
+        template_version: forecast.template_version.to_string(),
+        schema_version: state
+            .forecast_spreadsheet_schemas
+            .select(&forecast.template_version)
+            .ok()
+            .map(|schema| schema.schema_version.to_string()),
+        forecast,
+        warnings,
+        name,
+        url,
+        name_problems,
+    };
+    render_validate(
+        &templates,
+        &ValidateContext {
+            file_name: Some(file_name),
+            report: Some(report),
+            error: None,
+        },
+    )
+    .await
+}
+
+/// Parse the comma separated elevation band boundaries in metres, e.g. `1800, 2700`, which are
+/// returned from lowest to highest.
+fn parse_elevation_boundaries(value: &str, expected: usize) -> Result<Vec<i64>, String> {
+    let mut boundaries = value
+        .split(',')
+        .map(|boundary| {
+            let boundary = boundary.trim();
+            boundary
+                .strip_suffix('m')
+                .unwrap_or(boundary)
+                .trim()
+                .parse::<i64>()
+                .map_err(|_| format!("Invalid elevation band boundary {boundary:?}"))
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    if boundaries.len() != expected {
+        return Err(format!(
+            "Expected {expected} elevation band boundaries, found {}",
+            boundaries.len()
+        ));
+    }
+    boundaries.sort_unstable();
+    Ok(boundaries)
+}
+
+/// The most recent parsed forecast for the `area`.
+async fn latest_forecast(
+    database: &Database,
+    area: &str,
+) -> eyre::Result<Option<forecast_spreadsheet::Forecast>> {
+    Ok(sqlx::query_scalar!(
+        r#"SELECT parsed_forecast as "parsed_forecast!: sqlx::types::Json<forecast_spreadsheet::Forecast>" FROM forecast_files WHERE json_extract(parsed_forecast, "$.area")=$1 ORDER BY json_extract(parsed_forecast, "$.time") DESC LIMIT 1"#,
+        area
+    )
+    .fetch_optional(database)
+    .await
+    .wrap_err("Error fetching latest forecast")?
+    .map(|forecast| forecast.0))
+}
+
+/// Import the forecasts from a CAAML document (see [`forecast_spreadsheet::caaml`]), each of which
+/// is stored as an uploaded file referencing the document, which is stored once. Bulletins which
+/// would replace an existing file are skipped and listed on the page. The elevation bands of the
+/// schema are split at the boundaries specified in the form, or otherwise at those of the area's
+/// most recent parsed forecast.
+async fn import_handler(
+    State(state): State<AppState>,
+    Extension(templates): Extension<TemplatesWithContext>,
+    mut multipart: axum::extract::Multipart,
+) -> axum::response::Result<Response> {
+    let mut document = None;
+    let mut area_name = None;
+    let mut region = None;
+    let mut forecaster = None;
+    let mut elevation_boundaries = None;
+    while let Some(field) = multipart.next_field().await.map_err(map_std_error)? {
+        let name = field.name().map(ToOwned::to_owned);
+        if name.as_deref() == Some("file") {
+            document = Some(field.bytes().await.map_err(map_std_error)?);
+            continue;
+        }
+        let text = field.text().await.map_err(map_std_error)?;
+        let value = Some(text.trim().to_owned()).filter(|value| !value.is_empty());
+        match name.as_deref() {
+            Some("area") => area_name = value,
+            Some("region") => region = value,
+            Some("forecaster") => forecaster = value,
+            Some("elevation_boundaries") => elevation_boundaries = value,
+            _ => {}
+        }
+    }
+    let Some(document) = document.filter(|document| !document.is_empty()) else {
+        return render_index(&state, &templates, Some("No file was uploaded".to_owned())).await;
+    };
+    let Some(area_id) = area_name
+        .as_ref()
+        .and_then(|area| state.forecast_spreadsheet_schema.area.map.get(area))
+    else {
+        return render_index(
+            &state,
+            &templates,
+            Some(format!("Unknown area {area_name:?}")),
+        )
+        .await;
+    };
+    let schema = state.forecast_spreadsheet_schema;
+    let elevation_bands = match elevation_boundaries {
+        Some(elevation_boundaries) => {
+            let expected = schema.elevation_bands.len().saturating_sub(1);
+            let boundaries = match parse_elevation_boundaries(&elevation_boundaries, expected) {
+                Ok(boundaries) => boundaries,
+                Err(error) => return render_index(&state, &templates, Some(error)).await,
+            };
+            forecast_spreadsheet::elevation_bands_from_boundaries(schema, &boundaries)
+                .map_err(map_eyre_error)?
+        }
+        None => match latest_forecast(&state.database, &area_id.to_string())
+            .await
+            .map_err(map_eyre_error)?
+        {
+            Some(latest) => latest.elevation_bands,
+            None => {
+                return render_index(
+                    &state,
+                    &templates,
+                    Some(format!(
+                        "No forecasts have been published for {area_id} to take the elevation bands from, specify the elevation band boundaries"
+                    )),
+                )
+                .await;
+            }
+        },
+    };
+    let options = forecast_spreadsheet::caaml::ImportOptions {
+        area: area_id.clone(),
+        template_version: schema.schema_version,
+        elevation_bands: &elevation_bands,
+        region: region.as_deref(),
+        forecaster: forecaster.as_deref(),
+    };
+    let forecasts = match forecast_spreadsheet::caaml::import(&document, &options) {
+        Ok(forecasts) => forecasts,
+        Err(error) => {
+            return render_index(
+                &state,
+                &templates,
+                Some(format!("Error importing CAAML document: {error:#}")),
+            )
+            .await;
+        }
+    };
+
+    let mut skipped = Vec::new();
+    let mut imports = Vec::new();
+    let mut names = HashSet::new();
+    for forecast in &forecasts {
+        // The language is included in the name so that the forecast is shown to users with that
+        // language selected.
+        let extension = match forecast.description.keys().next() {
+            Some(language) => format!("{language}.{}", file_extension(CAAML_MIME_TYPE)),
+            None => file_extension(CAAML_MIME_TYPE).to_owned(),
+        };
+        let name = canonical_forecast_name(forecast, schema, &extension).map_err(map_eyre_error)?;
+        if names.contains(&name) {
+            skipped.push(SkippedBulletin {
+                name,
+                reason: "Duplicate of another bulletin in the document".to_owned(),
+            });
+            continue;
+        }
+        if let Err(reason) = validate_name(&state, &name, CAAML_MIME_TYPE, None)
+            .await
+            .map_err(map_eyre_error)?
+        {
+            skipped.push(SkippedBulletin { name, reason });
+            continue;
+        }
+        names.insert(name.clone());
+        imports.push((name, forecast));
+    }
+
+    if !imports.is_empty() {
+        let modified_time: types::Time = time::OffsetDateTime::now_utc()
+            .replace_millisecond(0)
+            .wrap_err("Error truncating modified time")
+            .map_err(map_eyre_error)?
+            .into();
+        // Stored once, and referenced by each of the imported forecasts.
+        let blob_hash = state.blobs.put(document).await.map_err(map_eyre_error)?;
+        let mut transaction = state.database.begin().await.map_err(map_std_error)?;
+        for (name, forecast) in &imports {
+            let id = format!("{UPLOADED_ID_PREFIX}{}", uuid::Uuid::new_v4());
+            let parsed_forecast = sqlx::types::Json(forecast);
+            sqlx::query!(
+                "INSERT INTO uploaded_forecast_files(id, name, mime_type, modified_time) VALUES($1, $2, $3, $4)",
+                id,
+                name,
+                CAAML_MIME_TYPE,
+                modified_time,
+            )
+            .execute(&mut *transaction)
+            .await
+            .map_err(map_std_error)?;
+            sqlx::query!(
+                "INSERT INTO forecast_files(google_drive_id, last_modified, file_blob, blob_hash, parsed_forecast, schema_version) VALUES($1, $2, x'', $3, $4, NULL)",
+                id,
+                modified_time,
+                blob_hash,
+                parsed_forecast,
+            )
+            .execute(&mut *transaction)
+            .await
+            .map_err(map_std_error)?;
+        }
+        transaction.commit().await.map_err(map_std_error)?;
+    }
+    tracing::info!(
+        "Imported {} of {} forecasts from CAAML document for {area_id}",
+        imports.len(),
+        forecasts.len()
+    );
+    let summary = ImportSummary {
+        imported: imports.len(),
+        skipped,
+    };
+    render_index_with_import(&state, &templates, None, Some(summary)).await
+}
+
+/// Whether the file with `id` is a forecast imported from a CAAML document.
+async fn is_imported(database: &Database, id: &str) -> eyre::Result<bool> {
+    let mime_type = sqlx::query_scalar!(
+        "SELECT mime_type FROM uploaded_forecast_files WHERE id=$1",
+        id
+    )
+    .fetch_optional(database)
+    .await?;
+    Ok(mime_type.as_deref() == Some(CAAML_MIME_TYPE))
+}
+
+#[derive(Deserialize)]
+struct ReparseForm {
+    /// Parse using the schema with this version, instead of the one selected by the template
+    /// version of the spreadsheet.
+    #[serde(default)]
+    schema_version: String,
+}
+
+/// Parse the cached file again, recording the error if it fails. A forecast parsed with a schema
+/// other than the selected one is parsed again with the selected schema when it is next viewed.
+async fn reparse_handler(
+    Path(id): Path<String>,
+    State(state): State<AppState>,
+    Form(form): Form<ReparseForm>,
+) -> axum::response::Result<Response> {
+    let file_blob = load_file_blob(&id, &state.database, &state.blobs)
+        .await
+        .map_err(map_eyre_error)?
+        .ok_or(StatusCode::NOT_FOUND)?;
+    if is_imported(&state.database, &id)
+        .await
+        .map_err(map_eyre_error)?
+    {
+        // Imported forecasts aren't parsed using a schema.
+        return Err(StatusCode::BAD_REQUEST.into());
+    }
+
+    let result = if form.schema_version.is_empty() {
+        parse_forecast_file_blob(&file_blob, state.forecast_spreadsheet_schemas).and_then(
+            |forecast| {
+                let schema = state
+                    .forecast_spreadsheet_schemas
+                    .select(&forecast.template_version)?;
+                Ok((forecast, schema.schema_version))
+            },
+        )
+    } else {
+        match state
+            .forecast_spreadsheet_schemas
+            .schemas()
+            .iter()
+            .find(|schema| schema.schema_version.to_string() == form.schema_version)
+        {
+            Some(schema) => parse_forecast_file_blob_with_schema(&file_blob, schema)
+                .map(|forecast| (forecast, schema.schema_version)),
+            None => return Err(StatusCode::BAD_REQUEST.into()),
+        }
+    };
+
+    match result {
+        Ok((forecast, schema_version)) => {
+            let parsed_forecast = sqlx::types::Json(forecast);
+            let schema_version = schema_version.to_string();
+            sqlx::query!(
+                "UPDATE forecast_files SET parsed_forecast=$1, schema_version=$2, parse_error=NULL WHERE google_drive_id=$3",
+                parsed_forecast,
+                schema_version,
+                id
+            )
+            .execute(&state.database)
+            .await
+            .map_err(map_std_error)?;
+        }
+        Err(error) => {
+            tracing::warn!("Error re-parsing forecast file {id}: {error:?}");
+            let parse_error = format!("{error:#}");
+            sqlx::query!(
+                "UPDATE forecast_files SET parse_error=$1 WHERE google_drive_id=$2",
+                parse_error,
+                id
+            )
+            .execute(&state.database)
+            .await
+            .map_err(map_std_error)?;
+        }
+    }
+    Ok(Redirect::to("/admin/forecast-files").into_response())
+}
+
+#[derive(Deserialize)]
+struct RenameForm {
+    name: String,
+}
+
+/// Rename an uploaded file. Files in Google Drive need to be renamed there.
+async fn rename_handler(
+    Path(id): Path<String>,
+    State(state): State<AppState>,
+    Extension(templates): Extension<TemplatesWithContext>,
+    Form(form): Form<RenameForm>,
+) -> axum::response::Result<Response> {
+    let mime_type = sqlx::query_scalar!(
+        "SELECT mime_type FROM uploaded_forecast_files WHERE id=$1",
+        id
+    )
+    .fetch_optional(&state.database)
+    .await
+    .map_err(map_std_error)?
+    .ok_or(StatusCode::NOT_FOUND)?;
+    let name = form.name.trim();
+    if let Err(error) = validate_name(&state, name, &mime_type, Some(&id))
+        .await
+        .map_err(map_eyre_error)?
+    {
+        return render_index(&state, &templates, Some(error)).await;
+    }
+    sqlx::query!(
+        "UPDATE uploaded_forecast_files SET name=$1 WHERE id=$2",
+        name,
+        id
+    )
+    .execute(&state.database)
+    .await
+    .map_err(map_std_error)?;
+    Ok(Redirect::to("/admin/forecast-files").into_response())
+}
+
+/// Delete the cached file, which is fetched again from Google Drive when it is next viewed.
+/// Uploaded files are deleted entirely.
+async fn delete_handler(
+    Path(id): Path<String>,
+    Extension(database): Extension<Database>,
+) -> axum::response::Result<Response> {
+    let mut transaction = database.begin().await.map_err(map_std_error)?;
+    sqlx::query!("DELETE FROM uploaded_forecast_files WHERE id=$1", id)
+        .execute(&mut *transaction)
+        .await
+        .map_err(map_std_error)?;
+    sqlx::query!("DELETE FROM forecast_files WHERE google_drive_id=$1", id)
+        .execute(&mut *transaction)
+        .await
+        .map_err(map_std_error)?;
+    transaction.commit().await.map_err(map_std_error)?;
+    Ok(Redirect::to("/admin/forecast-files").into_response())
+}
+
+/// Download a new forecast spreadsheet, pre-filled from the most recent parsed forecast.
+pub async fn template_handler(State(state): State<AppState>) -> axum::response::Result<Response> {
+    let previous = sqlx::query_scalar!(
+        r#"SELECT parsed_forecast as "parsed_forecast!: sqlx::types::Json<forecast_spreadsheet::Forecast>" FROM forecast_files WHERE parsed_forecast IS NOT NULL ORDER BY json_extract(parsed_forecast, "$.time") DESC LIMIT 1"#
+    )
+    .fetch_optional(&state.database)
+    .await
+    .map_err(map_std_error)?
+    .map(|forecast| forecast.0);
+    let spreadsheet = forecast_spreadsheet::writer::write_spreadsheet(
+        state.forecast_spreadsheet_schema,
+        previous.as_ref(),
+    )
+    .wrap_err("Error writing forecast spreadsheet")
+    .map_err(map_eyre_error)?;
+    Ok((
+        [
+            (
+                header::CONTENT_TYPE,
+                "application/vnd.openxmlformats-officedocument.spreadsheetml.sheet",
+            ),
+            (
+                header::CONTENT_DISPOSITION,
+                "attachment; filename=\"forecast.xlsx\"",
+            ),
+        ],
+        spreadsheet,
+    )
+        .into_response())
+}
+
+#[cfg(test)]
+mod test {
+    use super::{file_extension, parse_elevation_boundaries, spreadsheet_mime_type};
+
+    #[test]
+    fn test_spreadsheet_mime_type() {
+        let xlsx = spreadsheet_mime_type("Gudauri_2023-01-24T17:00_LF.XLSX").unwrap();
+        assert_eq!(file_extension(xlsx), "xlsx");
+        let ods = spreadsheet_mime_type("forecast.ods").unwrap();
+        assert_eq!(file_extension(ods), "ods");
+        assert_eq!(spreadsheet_mime_type("forecast.pdf"), None);
+        assert_eq!(spreadsheet_mime_type("forecast"), None);
+    }
+
+    #[test]
+    fn test_parse_elevation_boundaries() {
+        assert_eq!(
+            parse_elevation_boundaries("1800, 2700", 2),
+            Ok(vec![1800, 2700])
+        );
+        assert_eq!(
+            parse_elevation_boundaries("2700m,1800m", 2),
+            Ok(vec![1800, 2700])
+        );
+        assert!(parse_elevation_boundaries("1800", 2).is_err());
+        assert!(parse_elevation_boundaries("1800, high", 2).is_err());
+    }
+}
@@ -0,0 +1,212 @@
+//! Editing the [`crate::forecasters`] directory.
+
+use axum::{
+    extract::{DefaultBodyLimit, Multipart, Path, State},
+    response::{IntoResponse, Redirect, Response},
+    routing::{get, post},
+    Extension, Router,
+};
+use http::StatusCode;
+use serde::Serialize;
+
+use crate::{
+    auth::CurrentUser,
+    database::Database,
+    error::{map_eyre_error, map_std_error},
+    forecasters::{self, Forecaster, ForecasterInitials, NewPhoto},
+    news::Translations,
+    observations::{MAX_PHOTO_BYTES, PHOTO_CONTENT_TYPES},
+    state::AppState,
+    templates::TemplatesWithContext,
+};
+
+/// Maximum size of the form, with the photo and the bio in each language.
+const MAX_FORM_BYTES: usize = MAX_PHOTO_BYTES + 1024 * 1024;
+
+pub fn router() -> Router<AppState> {
+    Router::new()
+        .route(
+            "/",
+            get(index_handler)
+                .post(save_handler)
+                .layer(DefaultBodyLimit::max(MAX_FORM_BYTES)),
+        )
+        .route("/new", get(new_handler))
+        .route("/{initials}", get(edit_handler))
+        .route("/{initials}/delete", post(delete_handler))
+}
+
+#[derive(Serialize)]
+struct IndexContext {
+    forecasters: Vec<Forecaster>,
+}
+
+async fn index_handler(
+    Extension(templates): Extension<TemplatesWithContext>,
+    Extension(database): Extension<Database>,
+) -> axum::response::Result<Response> {
+    let forecasters = forecasters::list_forecasters(&database)
+        .await
+        .map_err(map_eyre_error)?;
+    templates
+        .render("admin/forecasters.html", &IndexContext { forecasters })
+        .map_err(map_eyre_error)
+        .map_err(Into::into)
+}
+
+#[derive(Serialize)]
+struct EditContext {
+    /// `None` when creating a new forecaster.
+    forecaster: Option<Forecaster>,
+    photo_path: Option<String>,
+    error: Option<String>,
+}
+
+impl EditContext {
+    fn new(forecaster: Option<Forecaster>, error: Option<String>) -> Self {
+        Self {
+            photo_path: forecaster.as_ref().and_then(Forecaster::photo_path),
+            forecaster,
+            error,
+        }
+    }
+}
+
+async fn new_handler(
+    Extension(templates): Extension<TemplatesWithContext>,
+) -> axum::response::Result<Response> {
+    templates
+        .render("admin/forecaster.html", &EditContext::new(None, None))
+        .map_err(map_eyre_error)
+        .map_err(Into::into)
+}
+
+async fn edit_handler(
+    Path(initials): Path<ForecasterInitials>,
+    Extension(templates): Extension<TemplatesWithContext>,
+    Extension(database): Extension<Database>,
+) -> axum::response::Result<Response> {
+    let Some(forecaster) = forecasters::get_forecaster(&database, &initials)
+        .await
+        .map_err(map_eyre_error)?
+    else {
+        return Ok(StatusCode::NOT_FOUND.into_response());
+    };
+    templates
+        .render(
+            "admin/forecaster.html",
+            &EditContext::new(Some(forecaster), None),
+        )
+        .map_err(map_eyre_error)
+        .map_err(Into::into)
+}
+
+async fn save_handler(
+    Extension(templates): Extension<TemplatesWithContext>,
+    Extension(database): Extension<Database>,
+    Extension(current_user): Extension<Option<CurrentUser>>,
+    State(state): State<AppState>,
+    mut multipart: Multipart,
+) -> axum::response::Result<Response> {
+    let mut fields: Vec<(String, String)> = Vec::new();
+    let mut photo = None;
+    while let Some(field) = multipart.next_field().await.map_err(map_std_error)? {
+        let Some(name) = field.name().map(ToOwned::to_owned) else {
+            continue;
+        };
+        if name == "photo" {
+            // Browsers submit an empty file field when no file is selected.
+            if field.file_name().map(str::is_empty).unwrap_or(true) {
+                continue;
+            }
+            let content_type = field.content_type().unwrap_or_default().to_owned();
+            photo = Some((content_type, field.bytes().await.map_err(map_std_error)?));
+        } else {
+            fields.push((name, field.text().await.map_err(map_std_error)?));
+        }
+    }
+    let field = |name: &str| {
+        fields
+            .iter()
+            .find(|(field_name, _)| field_name == name)
+            .map(|(_, value)| value.trim())
+            .unwrap_or_default()
+    };
+    let initials = ForecasterInitials::from(field("initials").to_owned());
+    let name = field("name");
+    let organisation = Some(field("organisation")).filter(|organisation| !organisation.is_empty());
+    let remove_photo = field("remove_photo") == "on";
+    let bio: Translations = super::news::parse_translations(&fields, "bio-");
+
+    let error = if !initials.is_valid() {
+        Some("The initials may only contain letters and numbers".to_owned())
+    } else if name.is_empty() {
+        Some("The name is required".to_owned())
+    } else {
+        match &photo {
+            Some((content_type, _)) if !PHOTO_CONTENT_TYPES.contains(&content_type.as_str()) => {
+                Some(format!("Unsupported photo type {content_type:?}"))
+            }
+            Some((_, data)) if data.len() > MAX_PHOTO_BYTES => Some(format!(
+                "Photo exceeds maximum size of {MAX_PHOTO_BYTES} bytes"
+            )),
+            _ => None,
+        }
+    };
+    if let Some(error) = error {
+        let forecaster = forecasters::get_forecaster(&database, &initials)
+            .await
+            .map_err(map_eyre_error)?;
+        let mut response = templates
+            .render(
+                "admin/forecaster.html",
+                &EditContext::new(forecaster, Some(error)),
+            )
+            .map_err(map_eyre_error)?;
+        *response.status_mut() = StatusCode::UNPROCESSABLE_ENTITY;
+        return Ok(response);
+    }
+
+    let photo = match photo {
+        Some((content_type, data)) => Some(NewPhoto {
+            content_type,
+            blob_hash: state.blobs.put(data).await.map_err(map_eyre_error)?,
+        }),
+        None => None,
+    };
+    forecasters::save_forecaster(
+        &database,
+        &initials,
+        name,
+        organisation,
+        &bio,
+        photo,
+        remove_photo,
+    )
+    .await
+    .map_err(map_eyre_error)?;
+    if let Some(current_user) = current_user {
+        tracing::info!(
+            "User {:?} saved forecaster {initials}",
+            current_user.user.username
+        );
+    }
+    Ok(Redirect::to(&format!("/admin/forecasters/{initials}")).into_response())
+}
+
+async fn delete_handler(
+    Path(initials): Path<ForecasterInitials>,
+    Extension(database): Extension<Database>,
+    Extension(current_user): Extension<Option<CurrentUser>>,
+) -> axum::response::Result<Redirect> {
+    forecasters::delete_forecaster(&database, &initials)
+        .await
+        .map_err(map_eyre_error)?;
+    if let Some(current_user) = current_user {
+        tracing::info!(
+            "User {:?} deleted forecaster {initials}",
+            current_user.user.username
+        );
+    }
+    Ok(Redirect::to("/admin/forecasters"))
+}
@@ -0,0 +1,22 @@
+//! Run the database maintenance (see [`maintenance::perform_maintenance`]) manually.
+
+use axum::{extract::State, response::Redirect, routing::post, Router};
+
+use crate::{database::maintenance, error::map_eyre_error, state::AppState};
+
+pub fn router() -> Router<AppState> {
+    Router::new().route("/", post(run_handler))
+}
+
+async fn run_handler(State(state): State<AppState>) -> axum::response::Result<Redirect> {
+    tracing::info!("Running database maintenance manually");
+    maintenance::perform_maintenance(
+        &state.database,
+        &state.blobs,
+        &state.options.maintenance,
+        true,
+    )
+    .await
+    .map_err(map_eyre_error)?;
+    Ok(Redirect::to("/admin"))
+}
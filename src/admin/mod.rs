@@ -1,27 +1,223 @@
-use axum::{routing::get, Router};
-use secrecy::SecretString;
-use tower_http::auth::AsyncRequireAuthorizationLayer;
+use axum::{
+    extract::{Request, State},
+    middleware::{self, Next},
+    response::Response,
+    routing::get,
+    Extension, Router,
+};
+use serde::Serialize;
 
-use crate::{auth::MyBasicAuth, state::AppState, templates};
+use crate::{
+    auth::{self, CurrentUser},
+    database::{self, maintenance::MaintenanceReport},
+    error::map_eyre_error,
+    observations::linking::{self, NewObservations},
+    options,
+    state::AppState,
+    templates::TemplatesWithContext,
+    users::{Permission, Role},
+    webhooks::Webhooks,
+};
 
+use self::allowlist::DeniedAccess;
+
+mod aggregators;
+mod allowlist;
 mod analytics;
+mod api_keys;
+mod backups;
+mod blobs;
+mod configuration;
 mod forecast_areas;
 mod forecast_files;
+mod forecasters;
 mod logs;
+mod maintenance;
+mod news;
+mod observations;
+mod pages;
+mod templates;
+mod translations;
+mod users;
 
 pub struct Config {
     pub reporting: &'static axum_reporting::Options,
-    pub admin_password_hash: &'static SecretString,
+    /// See [`options::AdminAllowlist`].
+    pub allowlist: Option<&'static options::AdminAllowlist>,
+    pub webhooks: Webhooks,
+}
+
+/// Only allow the `router`'s routes to be accessed by users with the `permission`.
+fn with_permission(router: Router<AppState>, permission: Permission) -> Router<AppState> {
+    router.route_layer(middleware::from_fn(move |request: Request, next: Next| {
+        auth::require_permission(permission, request, next)
+    }))
+}
+
+/// Only allow the `router`'s routes to be accessed by clients in the [`Config::allowlist`], if it
+/// is specified.
+fn with_allowlist(router: Router<AppState>, config: &Config) -> Router<AppState> {
+    let Some(allowlist) = config.allowlist else {
+        return router;
+    };
+    let webhooks = config.webhooks.clone();
+    router.route_layer(middleware::from_fn(move |request: Request, next: Next| {
+        allowlist::middleware(allowlist, webhooks.clone(), request, next)
+    }))
 }
 
 pub fn router(config: Config) -> Router<AppState> {
     Router::new()
-        .route("/", get(templates::create_handler("admin/index.html")))
-        .nest("/analytics", analytics::router())
-        .nest("/logs", logs::router(config.reporting))
-        .nest("/forecast-areas", forecast_areas::router())
-        .nest("/forecast-files", forecast_files::router())
-        .layer(AsyncRequireAuthorizationLayer::new(MyBasicAuth::new(
-            config.admin_password_hash,
-        )))
+        .route("/", get(index_handler))
+        .nest(
+            "/analytics",
+            with_permission(analytics::router(), Permission::ViewAnalytics),
+        )
+        .nest(
+            "/logs",
+            with_allowlist(
+                with_permission(logs::router(config.reporting), Permission::ViewLogs),
+                &config,
+            ),
+        )
+        .nest(
+            "/forecast-areas",
+            with_permission(forecast_areas::router(), Permission::EditForecastAreas),
+        )
+        .nest(
+            "/forecast-files",
+            with_permission(forecast_files::router(), Permission::EditForecasts),
+        )
+        .nest(
+            "/forecasters",
+            with_permission(forecasters::router(), Permission::EditForecasts),
+        )
+        .nest(
+            "/aggregators",
+            with_permission(aggregators::router(), Permission::EditForecasts),
+        )
+        .nest(
+            "/api-keys",
+            with_permission(api_keys::router(), Permission::ManageApiKeys),
+        )
+        .nest(
+            "/backups",
+            with_allowlist(
+                with_permission(backups::router(), Permission::ManageDatabase),
+                &config,
+            ),
+        )
+        .nest(
+            "/blobs",
+            with_allowlist(
+                with_permission(blobs::router(), Permission::ManageDatabase),
+                &config,
+            ),
+        )
+        .nest(
+            "/configuration",
+            with_permission(configuration::router(), Permission::ManageConfiguration),
+        )
+        .nest(
+            "/templates",
+            with_permission(templates::router(), Permission::ManageConfiguration),
+        )
+        .nest(
+            "/maintenance",
+            with_allowlist(
+                with_permission(maintenance::router(), Permission::ManageDatabase),
+                &config,
+            ),
+        )
+        .nest(
+            "/news",
+            with_permission(news::router(), Permission::EditNews),
+        )
+        .nest(
+            "/observations",
+            with_permission(observations::router(), Permission::ModerateObservations),
+        )
+        .nest(
+            "/pages",
+            with_permission(pages::router(), Permission::EditPages),
+        )
+        .nest(
+            "/translations",
+            with_permission(translations::router(), Permission::EditTranslations),
+        )
+        .nest(
+            "/users",
+            with_permission(users::router(), Permission::ManageUsers),
+        )
+        .layer(middleware::from_fn(auth::require_login))
+}
+
+#[derive(Serialize)]
+struct IndexContext<'a> {
+    username: &'a str,
+    role: Role,
+    permissions: &'static [Permission],
+    /// Recent database maintenance runs, only for users who can manage the database.
+    maintenance: Vec<MaintenanceReport>,
+    /// Observations made since the latest forecast for each area, only for users who can moderate
+    /// observations.
+    new_observations: Vec<NewObservations>,
+    /// Recent attempts to access pages from clients outside of the [`options::AdminAllowlist`],
+    /// only for users who can manage the database.
+    denied_access: Vec<DeniedAccess>,
+}
+
+/// Number of recent database maintenance runs shown on the admin page.
+const MAINTENANCE_REPORTS: i64 = 5;
+/// Number of recent denied access attempts shown on the admin page.
+const DENIED_ACCESS_ATTEMPTS: i64 = 10;
+
+async fn index_handler(
+    State(state): State<AppState>,
+    Extension(templates): Extension<TemplatesWithContext>,
+    Extension(current_user): Extension<Option<CurrentUser>>,
+) -> axum::response::Result<Response> {
+    let current_user = current_user.ok_or(http::StatusCode::UNAUTHORIZED)?;
+    let role = current_user.user.role;
+    let maintenance = if role.has_permission(Permission::ManageDatabase) {
+        database::maintenance::recent_reports(&state.database, MAINTENANCE_REPORTS)
+            .await
+            .map_err(map_eyre_error)?
+    } else {
+        Vec::new()
+    };
+    let denied_access = if state.options.admin_allowlist.is_some()
+        && role.has_permission(Permission::ManageDatabase)
+    {
+        allowlist::recent_denied(&state.database, DENIED_ACCESS_ATTEMPTS)
+            .await
+            .map_err(map_eyre_error)?
+    } else {
+        Vec::new()
+    };
+    let new_observations = if state.options.features.observations
+        && role.has_permission(Permission::ModerateObservations)
+    {
+        // The published forecasts may be unavailable, which shouldn't prevent using the admin
+        // page.
+        linking::new_observations(&state.database, &state)
+            .await
+            .unwrap_or_else(|error| {
+                tracing::error!("Error counting new observations: {error:?}");
+                Vec::new()
+            })
+    } else {
+        Vec::new()
+    };
+    let context = IndexContext {
+        username: &current_user.user.username,
+        role,
+        permissions: role.permissions(),
+        maintenance,
+        new_observations,
+        denied_access,
+    };
+    Ok(templates
+        .render("admin/index.html", &context)
+        .map_err(map_eyre_error)?)
 }
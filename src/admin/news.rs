@@ -0,0 +1,187 @@
+//! Writing and publishing [`crate::news`] posts.
+
+use axum::{
+    extract::Path,
+    response::{IntoResponse, Redirect, Response},
+    routing::{get, post},
+    Extension, Form, Router,
+};
+use http::StatusCode;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    auth::CurrentUser,
+    database::Database,
+    error::map_eyre_error,
+    i18n::I18nLoader,
+    news::{self, NewsPostContext, NewsPostId, Translations},
+    state::AppState,
+    templates::TemplatesWithContext,
+};
+
+pub fn router() -> Router<AppState> {
+    Router::new()
+        .route("/", get(index_handler).post(save_handler))
+        .route("/new", get(new_handler))
+        .route("/{id}", get(edit_handler))
+        .route("/{id}/publish", post(publish_handler))
+        .route("/{id}/delete", post(delete_handler))
+}
+
+#[derive(Serialize)]
+struct IndexContext {
+    posts: Vec<NewsPostContext>,
+}
+
+async fn index_handler(
+    Extension(templates): Extension<TemplatesWithContext>,
+    Extension(database): Extension<Database>,
+    Extension(i18n): Extension<I18nLoader>,
+) -> axum::response::Result<Response> {
+    let posts = news::list_posts(&database, false)
+        .await
+        .map_err(map_eyre_error)?
+        .into_iter()
+        .map(|post| NewsPostContext::format(post, &i18n))
+        .collect();
+    templates
+        .render("admin/news.html", &IndexContext { posts })
+        .map_err(map_eyre_error)
+        .map_err(Into::into)
+}
+
+#[derive(Serialize, Default)]
+struct EditContext {
+    /// `None` when creating a new post.
+    post: Option<NewsPostContext>,
+    error: Option<String>,
+}
+
+async fn new_handler(
+    Extension(templates): Extension<TemplatesWithContext>,
+) -> axum::response::Result<Response> {
+    templates
+        .render("admin/news_post.html", &EditContext::default())
+        .map_err(map_eyre_error)
+        .map_err(Into::into)
+}
+
+async fn edit_handler(
+    Path(id): Path<NewsPostId>,
+    Extension(templates): Extension<TemplatesWithContext>,
+    Extension(database): Extension<Database>,
+    Extension(i18n): Extension<I18nLoader>,
+) -> axum::response::Result<Response> {
+    let Some(post) = news::get_post(&database, &id)
+        .await
+        .map_err(map_eyre_error)?
+    else {
+        return Ok(StatusCode::NOT_FOUND.into_response());
+    };
+    let context = EditContext {
+        post: Some(NewsPostContext::format(post, &i18n)),
+        error: None,
+    };
+    templates
+        .render("admin/news_post.html", &context)
+        .map_err(map_eyre_error)
+        .map_err(Into::into)
+}
+
+/// Parse the `{prefix}{language}` fields of a form (e.g. `title-en-UK`), ignoring languages which
+/// were left empty.
+pub(super) fn parse_translations(fields: &[(String, String)], prefix: &str) -> Translations {
+    fields
+        .iter()
+        .filter_map(|(name, value)| {
+            let language = name.strip_prefix(prefix)?.parse().ok()?;
+            let value = value.trim();
+            (!value.is_empty()).then(|| (language, value.to_owned()))
+        })
+        .collect()
+}
+
+async fn save_handler(
+    Extension(templates): Extension<TemplatesWithContext>,
+    Extension(database): Extension<Database>,
+    Extension(current_user): Extension<Option<CurrentUser>>,
+    Form(fields): Form<Vec<(String, String)>>,
+) -> axum::response::Result<Response> {
+    let id = NewsPostId::from(
+        fields
+            .iter()
+            .find(|(name, _)| name == "id")
+            .map(|(_, value)| value.trim().to_owned())
+            .unwrap_or_default(),
+    );
+    let title = parse_translations(&fields, "title-");
+    let body = parse_translations(&fields, "body-");
+
+    let error = if !id.is_valid() {
+        Some("The id may only contain lowercase letters, numbers and -".to_owned())
+    } else if title.is_empty() || title.len() != body.len() {
+        Some("Each language needs both a title and a body".to_owned())
+    } else {
+        None
+    };
+    if let Some(error) = error {
+        let context = EditContext {
+            post: None,
+            error: Some(error),
+        };
+        let mut response = templates
+            .render("admin/news_post.html", &context)
+            .map_err(map_eyre_error)?;
+        *response.status_mut() = StatusCode::UNPROCESSABLE_ENTITY;
+        return Ok(response);
+    }
+
+    news::save_post(&database, &id, &title, &body)
+        .await
+        .map_err(map_eyre_error)?;
+    if let Some(current_user) = current_user {
+        tracing::info!("User {:?} saved news post {id}", current_user.user.username);
+    }
+    Ok(Redirect::to(&format!("/admin/news/{id}")).into_response())
+}
+
+#[derive(Deserialize)]
+struct PublishForm {
+    published: bool,
+}
+
+async fn publish_handler(
+    Path(id): Path<NewsPostId>,
+    Extension(database): Extension<Database>,
+    Extension(current_user): Extension<Option<CurrentUser>>,
+    Form(form): Form<PublishForm>,
+) -> axum::response::Result<Redirect> {
+    news::set_published(&database, &id, form.published)
+        .await
+        .map_err(map_eyre_error)?;
+    if let Some(current_user) = current_user {
+        tracing::info!(
+            "User {:?} set news post {id} published to {}",
+            current_user.user.username,
+            form.published
+        );
+    }
+    Ok(Redirect::to("/admin/news"))
+}
+
+async fn delete_handler(
+    Path(id): Path<NewsPostId>,
+    Extension(database): Extension<Database>,
+    Extension(current_user): Extension<Option<CurrentUser>>,
+) -> axum::response::Result<Redirect> {
+    news::delete_post(&database, &id)
+        .await
+        .map_err(map_eyre_error)?;
+    if let Some(current_user) = current_user {
+        tracing::info!(
+            "User {:?} deleted news post {id}",
+            current_user.user.username
+        );
+    }
+    Ok(Redirect::to("/admin/news"))
+}
@@ -0,0 +1,199 @@
+use axum::{
+    extract::{Path, State},
+    response::{IntoResponse, Redirect, Response},
+    routing::{get, post},
+    Extension, Form, Router,
+};
+use http::StatusCode;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    auth::CurrentUser,
+    database::Database,
+    error::map_eyre_error,
+    i18n::{self, I18nLoader},
+    observations::{
+        self,
+        linking::{self, ForecastLink, Linker},
+        ObservationContext, ObservationId, ObservationStatus, PhotoId,
+    },
+    state::AppState,
+    templates::TemplatesWithContext,
+};
+
+pub fn router() -> Router<AppState> {
+    Router::new()
+        .route("/", get(index_handler))
+        .route("/review", get(review_handler))
+        .route("/{id}/status", post(status_handler))
+        .route("/{id}/delete", post(delete_handler))
+        .route("/photos/{id}", get(photo_handler))
+}
+
+#[derive(Serialize)]
+struct Context {
+    pending: Vec<ObservationContext>,
+    approved: Vec<ObservationContext>,
+    rejected: Vec<ObservationContext>,
+}
+
+async fn list_formatted(
+    status: ObservationStatus,
+    database: &Database,
+    i18n: &I18nLoader,
+) -> eyre::Result<Vec<ObservationContext>> {
+    let mut formatted = Vec::new();
+    for observation in observations::list_observations(database, status).await? {
+        formatted.push(ObservationContext::format(observation, database, i18n).await?);
+    }
+    Ok(formatted)
+}
+
+async fn index_handler(
+    Extension(templates): Extension<TemplatesWithContext>,
+    Extension(database): Extension<Database>,
+    Extension(i18n): Extension<I18nLoader>,
+) -> axum::response::Result<Response> {
+    let context = Context {
+        pending: list_formatted(ObservationStatus::Pending, &database, &i18n)
+            .await
+            .map_err(map_eyre_error)?,
+        approved: list_formatted(ObservationStatus::Approved, &database, &i18n)
+            .await
+            .map_err(map_eyre_error)?,
+        rejected: list_formatted(ObservationStatus::Rejected, &database, &i18n)
+            .await
+            .map_err(map_eyre_error)?,
+    };
+    templates
+        .render("admin/observations.html", &context)
+        .map_err(map_eyre_error)
+        .map_err(Into::into)
+}
+
+#[derive(Serialize)]
+struct ReviewGroup {
+    link: ForecastLink,
+    formatted_forecast_time: Option<String>,
+    observations: Vec<ObservationContext>,
+}
+
+#[derive(Serialize)]
+struct ReviewContext {
+    /// Pending observations grouped by the forecast which was in force, latest forecast first.
+    groups: Vec<ReviewGroup>,
+}
+
+async fn review_handler(
+    Extension(templates): Extension<TemplatesWithContext>,
+    Extension(database): Extension<Database>,
+    Extension(i18n): Extension<I18nLoader>,
+    State(state): State<AppState>,
+) -> axum::response::Result<Response> {
+    let pending = observations::list_observations(&database, ObservationStatus::Pending)
+        .await
+        .map_err(map_eyre_error)?;
+    let linker = Linker::load(&database, &state)
+        .await
+        .map_err(map_eyre_error)?;
+    let mut links = linking::update_links(&database, &linker, &pending)
+        .await
+        .map_err(map_eyre_error)?;
+    let mut groups: Vec<ReviewGroup> = Vec::new();
+    for observation in pending {
+        let link = links
+            .remove(&observation.id)
+            .unwrap_or_else(|| linker.link(&observation));
+        let observation = ObservationContext::format(observation, &database, &i18n)
+            .await
+            .map_err(map_eyre_error)?;
+        match groups.iter_mut().find(|group| group.link == link) {
+            Some(group) => group.observations.push(observation),
+            None => groups.push(ReviewGroup {
+                formatted_forecast_time: link
+                    .forecast_time
+                    .map(|time| i18n::format_time(time.into(), &i18n)),
+                link,
+                observations: vec![observation],
+            }),
+        }
+    }
+    // Observations without a forecast are last.
+    groups.sort_by_key(|group| std::cmp::Reverse(group.link.forecast_time.map(|time| *time)));
+    templates
+        .render("admin/observations_review.html", &ReviewContext { groups })
+        .map_err(map_eyre_error)
+        .map_err(Into::into)
+}
+
+/// Where to return to after modifying an observation, either the observations page or the review
+/// queue.
+fn return_path(redirect: Option<&str>) -> &str {
+    match redirect {
+        Some(redirect @ "/admin/observations/review") => redirect,
+        _ => "/admin/observations",
+    }
+}
+
+#[derive(Deserialize)]
+struct StatusForm {
+    status: ObservationStatus,
+    redirect: Option<String>,
+}
+
+async fn status_handler(
+    Path(id): Path<ObservationId>,
+    Extension(database): Extension<Database>,
+    Extension(current_user): Extension<Option<CurrentUser>>,
+    Form(form): Form<StatusForm>,
+) -> axum::response::Result<Redirect> {
+    observations::set_observation_status(&database, &id, form.status)
+        .await
+        .map_err(map_eyre_error)?;
+    if let Some(current_user) = current_user {
+        tracing::info!(
+            "User {:?} set the status of observation {id} to {:?}",
+            current_user.user.username,
+            form.status
+        );
+    }
+    Ok(Redirect::to(return_path(form.redirect.as_deref())))
+}
+
+#[derive(Deserialize)]
+struct DeleteForm {
+    redirect: Option<String>,
+}
+
+async fn delete_handler(
+    Path(id): Path<ObservationId>,
+    Extension(database): Extension<Database>,
+    Extension(current_user): Extension<Option<CurrentUser>>,
+    Form(form): Form<DeleteForm>,
+) -> axum::response::Result<Redirect> {
+    observations::delete_observation(&database, &id)
+        .await
+        .map_err(map_eyre_error)?;
+    if let Some(current_user) = current_user {
+        tracing::info!(
+            "User {:?} deleted observation {id}",
+            current_user.user.username
+        );
+    }
+    Ok(Redirect::to(return_path(form.redirect.as_deref())))
+}
+
+/// Serves photos regardless of the moderation status of their observation.
+async fn photo_handler(
+    Path(id): Path<PhotoId>,
+    Extension(database): Extension<Database>,
+    State(state): State<AppState>,
+) -> axum::response::Result<Response> {
+    match observations::get_photo(&database, &state.blobs, &id)
+        .await
+        .map_err(map_eyre_error)?
+    {
+        Some((photo, _)) => Ok(observations::photo_response(photo).map_err(map_eyre_error)?),
+        None => Ok(StatusCode::NOT_FOUND.into_response()),
+    }
+}
@@ -0,0 +1,168 @@
+//! Editing the content of [`crate::pages`].
+
+use axum::{
+    extract::Path,
+    response::{IntoResponse, Redirect, Response},
+    routing::{get, post},
+    Extension, Form, Router,
+};
+use http::StatusCode;
+use serde::Serialize;
+
+use crate::{
+    auth::CurrentUser,
+    database::Database,
+    error::map_eyre_error,
+    pages::{self, Page, PageSlug, DEFAULT_TEMPLATE},
+    state::AppState,
+    templates::TemplatesWithContext,
+};
+
+use super::news::parse_translations;
+
+pub fn router() -> Router<AppState> {
+    Router::new()
+        .route("/", get(index_handler).post(save_handler))
+        .route("/new", get(new_handler))
+        .route("/{slug}", get(edit_handler))
+        .route("/{slug}/delete", post(delete_handler))
+}
+
+#[derive(Serialize)]
+struct IndexContext {
+    pages: Vec<Page>,
+}
+
+async fn index_handler(
+    Extension(templates): Extension<TemplatesWithContext>,
+    Extension(database): Extension<Database>,
+) -> axum::response::Result<Response> {
+    let pages = pages::list_pages(&database).await.map_err(map_eyre_error)?;
+    templates
+        .render("admin/pages.html", &IndexContext { pages })
+        .map_err(map_eyre_error)
+        .map_err(Into::into)
+}
+
+#[derive(Serialize)]
+struct EditContext {
+    /// `None` when creating a new page.
+    page: Option<Page>,
+    /// Whether the page has been saved before, in which case its slug can't be changed.
+    existing: bool,
+    default_template: &'static str,
+    error: Option<String>,
+}
+
+impl EditContext {
+    fn new(page: Option<Page>, error: Option<String>) -> Self {
+        Self {
+            existing: page.is_some(),
+            page,
+            default_template: DEFAULT_TEMPLATE,
+            error,
+        }
+    }
+}
+
+async fn new_handler(
+    Extension(templates): Extension<TemplatesWithContext>,
+) -> axum::response::Result<Response> {
+    templates
+        .render("admin/page.html", &EditContext::new(None, None))
+        .map_err(map_eyre_error)
+        .map_err(Into::into)
+}
+
+async fn edit_handler(
+    Path(slug): Path<PageSlug>,
+    Extension(templates): Extension<TemplatesWithContext>,
+    Extension(database): Extension<Database>,
+) -> axum::response::Result<Response> {
+    let Some(page) = pages::get_page(&database, &slug)
+        .await
+        .map_err(map_eyre_error)?
+    else {
+        return Ok(StatusCode::NOT_FOUND.into_response());
+    };
+    templates
+        .render("admin/page.html", &EditContext::new(Some(page), None))
+        .map_err(map_eyre_error)
+        .map_err(Into::into)
+}
+
+fn field<'a>(fields: &'a [(String, String)], name: &str) -> &'a str {
+    fields
+        .iter()
+        .find(|(field_name, _)| field_name == name)
+        .map(|(_, value)| value.trim())
+        .unwrap_or_default()
+}
+
+async fn save_handler(
+    Extension(templates): Extension<TemplatesWithContext>,
+    Extension(database): Extension<Database>,
+    Extension(current_user): Extension<Option<CurrentUser>>,
+    Form(fields): Form<Vec<(String, String)>>,
+) -> axum::response::Result<Response> {
+    let slug = PageSlug::from(field(&fields, "slug").to_owned());
+    let template = Some(field(&fields, "template")).filter(|template| !template.is_empty());
+    let title = parse_translations(&fields, "title-");
+    let content = parse_translations(&fields, "content-");
+
+    let error = if !slug.is_valid() {
+        Some("The slug may only contain lowercase letters, numbers and -".to_owned())
+    } else if title.is_empty() || title.len() != content.len() {
+        Some("Each language needs both a title and content".to_owned())
+    } else if let Some(Err(error)) = template.map(|name| templates.environment.get_template(name)) {
+        Some(format!("Invalid template: {error}"))
+    } else {
+        None
+    };
+    if let Some(error) = error {
+        let existing = slug.is_valid()
+            && pages::get_page(&database, &slug)
+                .await
+                .map_err(map_eyre_error)?
+                .is_some();
+        // Shown again with the submitted values, so that they can be corrected.
+        let submitted = Page {
+            slug,
+            updated_at: time::OffsetDateTime::now_utc().into(),
+            title: sqlx::types::Json(title),
+            content: sqlx::types::Json(content),
+            template: template.map(ToOwned::to_owned),
+        };
+        let context = EditContext {
+            existing,
+            ..EditContext::new(Some(submitted), Some(error))
+        };
+        let mut response = templates
+            .render("admin/page.html", &context)
+            .map_err(map_eyre_error)?;
+        *response.status_mut() = StatusCode::UNPROCESSABLE_ENTITY;
+        return Ok(response);
+    }
+
+    pages::save_page(&database, &slug, &title, &content, template)
+        .await
+        .map_err(map_eyre_error)?;
+    if let Some(current_user) = current_user {
+        tracing::info!("User {:?} saved page {slug}", current_user.user.username);
+    }
+    Ok(Redirect::to(&format!("/admin/pages/{slug}")).into_response())
+}
+
+async fn delete_handler(
+    Path(slug): Path<PageSlug>,
+    Extension(database): Extension<Database>,
+    Extension(current_user): Extension<Option<CurrentUser>>,
+) -> axum::response::Result<Redirect> {
+    pages::delete_page(&database, &slug)
+        .await
+        .map_err(map_eyre_error)?;
+    if let Some(current_user) = current_user {
+        tracing::info!("User {:?} deleted page {slug}", current_user.user.username);
+    }
+    Ok(Redirect::to("/admin/pages"))
+}
@@ -0,0 +1,489 @@
+//! A sandbox for checking the templates overridden using the
+//! [`crate::options::Templates::directories`] before they are deployed. A template is rendered
+//! against a sample context (the latest forecast, or synthetic data) in a copy of the template
+//! environment, so errors are reported here instead of affecting the live pages.
+
+use axum::{
+    extract::{Path, Query, State},
+    response::{IntoResponse, Response},
+    routing::get,
+    Extension, Form, Json, Router,
+};
+use eyre::ContextCompat;
+use forecast_spreadsheet::{
+    AreaId, Aspect, AspectElevation, Confidence, Distribution, ElevationBandId, ElevationRange,
+    Forecast, Forecaster, HazardRating, HazardRatingKind, HazardRatingValue, ProblemKind,
+    Sensitivity, Size, TimeOfDay, Trend, Version,
+};
+use http::StatusCode;
+use indexmap::IndexMap;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+
+use crate::{
+    database::Database,
+    email::{self, AlertContext, Email, EmailTemplate, ForecastNotificationContext},
+    error::map_eyre_error,
+    forecasts::{
+        canonical_forecast_name, forecast_page_context, get_forecast_data, parse_forecast_name,
+        ForecastData, ForecastSpreadsheetSchema, RequestedForecastData,
+    },
+    i18n::I18nLoader,
+    state::AppState,
+    templates::{self, TemplatesWithContext},
+    user_preferences::UserPreferences,
+};
+
+/// Elevation bands of the synthetic forecast when the schema doesn't specify any.
+const DEFAULT_ELEVATION_BANDS: [&str; 3] = ["high-alpine", "alpine", "sub-alpine"];
+/// Elevation of the boundary between each of the synthetic forecast's elevation bands.
+const SYNTHETIC_ELEVATION_BOUNDARIES: [i64; 2] = [2800, 2400];
+
+pub fn router() -> Router<AppState> {
+    Router::new()
+        .route("/", get(index_handler))
+        .route("/preview", get(preview_handler).post(preview_post_handler))
+        .route("/samples/{sample}", get(sample_handler))
+        .route("/emails/{email}", get(email_preview_handler))
+}
+
+/// The context that a template is rendered against.
+#[derive(Serialize, Deserialize, Clone, Copy, Default, PartialEq, Eq, Debug)]
+#[serde(rename_all = "kebab-case")]
+enum Sample {
+    /// The latest published forecast.
+    #[default]
+    LatestForecast,
+    /// A forecast with fixed values for each of the fields.
+    SyntheticForecast,
+    /// No values other than the globals available to all templates.
+    Empty,
+}
+
+impl Sample {
+    const ALL: [Self; 3] = [Self::LatestForecast, Self::SyntheticForecast, Self::Empty];
+
+    /// The sample which matches the context that the template with `name` is rendered against.
+    fn for_template(name: &str) -> Self {
+        match name {
+            "forecast.html" | "area.html" => Self::LatestForecast,
+            _ => Self::Empty,
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct TemplateInfo {
+    name: String,
+    /// The directory containing the file which overrides the template, if it is overridden.
+    overridden_by: Option<String>,
+    /// Error loading or compiling the template that is currently used.
+    error: Option<String>,
+}
+
+#[derive(Serialize)]
+struct IndexContext {
+    /// The template directories, highest priority first.
+    directories: Vec<String>,
+    templates: Vec<TemplateInfo>,
+    samples: [Sample; 3],
+    emails: [EmailTemplate; 2],
+}
+
+async fn index_handler(
+    State(state): State<AppState>,
+    Extension(templates): Extension<TemplatesWithContext>,
+) -> axum::response::Result<Response> {
+    let options = &state.options.templates;
+    let template_infos = templates::template_names()
+        .into_iter()
+        .filter(|name| !name.starts_with("admin/"))
+        .map(|name| TemplateInfo {
+            overridden_by: templates::overriding_directory(options, &name)
+                .map(|directory| directory.display().to_string()),
+            error: templates
+                .environment
+                .get_template(&name)
+                .err()
+                .map(|error| format!("{error:#}")),
+            name,
+        })
+        .collect();
+    let context = IndexContext {
+        directories: options
+            .directories()
+            .map(|directory| directory.display().to_string())
+            .collect(),
+        templates: template_infos,
+        samples: Sample::ALL,
+        emails: EmailTemplate::ALL,
+    };
+    Ok(templates
+        .render("admin/templates.html", &context)
+        .map_err(map_eyre_error)?)
+}
+
+#[derive(Deserialize)]
+struct PreviewQuery {
+    name: String,
+}
+
+#[derive(Deserialize)]
+struct PreviewForm {
+    name: String,
+    source: String,
+    sample: Sample,
+}
+
+#[derive(Serialize)]
+struct PreviewContext {
+    name: String,
+    source: String,
+    sample: Sample,
+    samples: [Sample; 3],
+    /// The rendered template, shown in an iframe.
+    output: Option<String>,
+    error: Option<String>,
+}
+
+/// Only the embedded templates (which may be overridden) can be previewed.
+fn is_template(name: &str) -> bool {
+    templates::template_names()
+        .iter()
+        .any(|template| template == name)
+}
+
+/// The preview form for the template with `name`, pre-filled with its current source.
+async fn preview_handler(
+    Query(query): Query<PreviewQuery>,
+    State(state): State<AppState>,
+    Extension(templates): Extension<TemplatesWithContext>,
+) -> axum::response::Result<Response> {
+    if !is_template(&query.name) {
+        return Err(StatusCode::NOT_FOUND.into());
+    }
+    let Some(source) = templates::load_template(&state.options.templates, &query.name)
+        .map_err(|error| map_eyre_error(error.into()))?
+    else {
+        return Err(StatusCode::NOT_FOUND.into());
+    };
+    let context = PreviewContext {
+        sample: Sample::for_template(&query.name),
+        name: query.name,
+        source,
+        samples: Sample::ALL,
+        output: None,
+        error: None,
+    };
+    Ok(templates
+        .render("admin/template_preview.html", &context)
+        .map_err(map_eyre_error)?)
+}
+
+/// Render the submitted template source against the selected sample context.
+async fn preview_post_handler(
+    State(state): State<AppState>,
+    Extension(database): Extension<Database>,
+    Extension(templates): Extension<TemplatesWithContext>,
+    Extension(i18n): Extension<I18nLoader>,
+    Extension(preferences): Extension<UserPreferences>,
+    Form(form): Form<PreviewForm>,
+) -> axum::response::Result<Response> {
+    if !is_template(&form.name) {
+        return Err(StatusCode::NOT_FOUND.into());
+    }
+    let result = match sample_context(form.sample, &state, &database, &i18n, &preferences).await {
+        Ok(sample) => render_preview(&templates, &form.name, &form.source, &sample),
+        Err(error) => Err(error.wrap_err("Error creating the sample context")),
+    };
+    let (output, error) = match result {
+        Ok(output) => (Some(output), None),
+        Err(error) => (None, Some(format!("{error:#}"))),
+    };
+    let context = PreviewContext {
+        name: form.name,
+        source: form.source,
+        sample: form.sample,
+        samples: Sample::ALL,
+        output,
+        error,
+    };
+    Ok(templates
+        .render("admin/template_preview.html", &context)
+        .map_err(map_eyre_error)?)
+}
+
+/// The sample context as JSON, to see which values are available to the templates.
+async fn sample_handler(
+    Path(sample): Path<Sample>,
+    State(state): State<AppState>,
+    Extension(database): Extension<Database>,
+    Extension(i18n): Extension<I18nLoader>,
+    Extension(preferences): Extension<UserPreferences>,
+) -> axum::response::Result<Response> {
+    let context = sample_context(sample, &state, &database, &i18n, &preferences)
+        .await
+        .map_err(map_eyre_error)?;
+    Ok(Json(context).into_response())
+}
+
+#[derive(Deserialize)]
+struct EmailPreviewQuery {
+    /// Defaults to the first language in
+    /// [`crate::options::ReloadableOptions::default_language_order`].
+    lang: Option<unic_langid::LanguageIdentifier>,
+}
+
+#[derive(Serialize)]
+struct EmailPreviewContext {
+    email_template: EmailTemplate,
+    lang: Option<String>,
+    email: Option<Email>,
+    error: Option<String>,
+}
+
+/// Render the `email` template against sample data, in the requested language.
+async fn email_preview_handler(
+    Path(email_template): Path<EmailTemplate>,
+    Query(query): Query<EmailPreviewQuery>,
+    State(state): State<AppState>,
+    Extension(templates): Extension<TemplatesWithContext>,
+) -> axum::response::Result<Response> {
+    let lang = query.lang.or_else(|| {
+        state
+            .reloadable_options
+            .load()
+            .default_language_order
+            .first()
+            .cloned()
+    });
+    let result = render_sample_email(email_template, lang.as_ref(), &state);
+    let (email, error) = match result {
+        Ok(email) => (Some(email), None),
+        Err(error) => (None, Some(format!("{error:#}"))),
+    };
+    let context = EmailPreviewContext {
+        email_template,
+        lang: lang.map(|lang| lang.to_string()),
+        email,
+        error,
+    };
+    Ok(templates
+        .render("admin/email_preview.html", &context)
+        .map_err(map_eyre_error)?)
+}
+
+/// Render the `email_template` with sample data.
+fn render_sample_email(
+    email_template: EmailTemplate,
+    lang: Option<&unic_langid::LanguageIdentifier>,
+    state: &AppState,
+) -> eyre::Result<Email> {
+    let i18n = match lang {
+        Some(lang) => email::language_loader(state, lang),
+        None => state.i18n.clone(),
+    };
+    let schema = state.forecast_spreadsheet_schema;
+    match email_template {
+        EmailTemplate::ForecastNotification => {
+            let forecast = synthetic_forecast(schema)?;
+            let file_name = canonical_forecast_name(&forecast, schema, "xlsx")?;
+            let context =
+                ForecastNotificationContext::new(&forecast, &file_name, None, state, &i18n)?;
+            email::render(state, email_template, &i18n, &context)
+        }
+        EmailTemplate::Alert => {
+            let context = AlertContext {
+                site: state.options.base_url().to_string(),
+                task: "backup default".to_owned(),
+                consecutive_failures: Some(3),
+                error: Some("Sample error message".to_owned()),
+            };
+            email::render(state, email_template, &i18n, &context)
+        }
+    }
+}
+
+/// Render `source` as the template with `name` in a copy of the template environment, so that
+/// the live template is unaffected. Templates which it includes or extends are loaded as usual.
+fn render_preview(
+    templates: &TemplatesWithContext,
+    name: &str,
+    source: &str,
+    sample: &serde_json::Value,
+) -> eyre::Result<String> {
+    let mut environment = (*templates.environment).clone();
+    environment.add_template_owned(name.to_owned(), source.to_owned())?;
+    Ok(environment.get_template(name)?.render(sample)?)
+}
+
+async fn sample_context(
+    sample: Sample,
+    state: &AppState,
+    database: &Database,
+    i18n: &I18nLoader,
+    preferences: &UserPreferences,
+) -> eyre::Result<serde_json::Value> {
+    let (forecast, file_name, file_list) = match sample {
+        Sample::Empty => return Ok(json!({})),
+        Sample::LatestForecast => {
+            let file_list = state.published_files.list_files().await?;
+            let (file, _) = file_list
+                .iter()
+                .filter(|file| file.has_forecast_data())
+                .filter_map(|file| {
+                    let details =
+                        parse_forecast_name(&file.name, state.forecast_spreadsheet_schema).ok()?;
+                    Some((file, details.forecast.time))
+                })
+                .max_by_key(|(_, time)| *time)
+                .wrap_err("There are no published forecasts")?;
+            let forecast = match get_forecast_data(
+                file,
+                RequestedForecastData::Forecast,
+                &state.client,
+                database,
+                &state.blobs,
+                &state.options.google_drive,
+                state.forecast_spreadsheet_schemas,
+            )
+            .await?
+            {
+                ForecastData::Forecast(forecast) => forecast,
+                ForecastData::File(_) => eyre::bail!("Expected ForecastData::Forecast"),
+            };
+            let file_name = file.name.clone();
+            (forecast, file_name, file_list)
+        }
+        Sample::SyntheticForecast => {
+            let forecast = synthetic_forecast(state.forecast_spreadsheet_schema)?;
+            let file_name =
+                canonical_forecast_name(&forecast, state.forecast_spreadsheet_schema, "xlsx")?;
+            (forecast, file_name, Vec::new())
+        }
+    };
+    let area = forecast.area.to_string();
+    let reloadable_options = state.reloadable_options.load_full();
+    let forecast = forecast_page_context(
+        forecast,
+        &file_name,
+        &file_list,
+        state.options,
+        &reloadable_options,
+        &state.client,
+        database,
+        i18n,
+        preferences,
+        state.forecast_spreadsheet_schema,
+    )
+    .await?;
+    let mut context = serde_json::to_value(forecast)?;
+    // The additional context of `area.html`.
+    context["area_page"] = json!({
+        "name": crate::i18n::message_or(
+            i18n,
+            &format!("forecast-area-{}", area.to_lowercase()),
+            &area,
+        ),
+        "id": area,
+        "archive": [],
+    });
+    Ok(context)
+}
+
+/// A forecast for the first area in the `schema`, with a value for each of the fields.
+fn synthetic_forecast(schema: &ForecastSpreadsheetSchema) -> eyre::Result<Forecast> {
+    let area: AreaId = schema
+        .area_definitions
+        .keys()
+        .find(|id| schema.area.map.values().any(|area| area == *id))
+        .cloned()
+        .wrap_err("The forecast schema has no areas")?;
+    let band_ids: Vec<ElevationBandId> = if schema.elevation_bands.is_empty() {
+        DEFAULT_ELEVATION_BANDS
+            .iter()
+            .map(|id| ElevationBandId::from((*id).to_owned()))
+            .collect()
+    } else {
+        schema.elevation_bands.iter().cloned().collect()
+    };
+    let elevation_bands: IndexMap<ElevationBandId, ElevationRange> = band_ids
+        .iter()
+        .enumerate()
+        .map(|(i, id)| {
+            let range = ElevationRange {
+                upper: i
+                    .checked_sub(1)
+                    .and_then(|i| SYNTHETIC_ELEVATION_BOUNDARIES.get(i).copied()),
+                lower: SYNTHETIC_ELEVATION_BOUNDARIES.get(i).copied(),
+            };
+            (id.clone(), range)
+        })
+        .collect();
+    let values = [
+        HazardRatingValue::High,
+        HazardRatingValue::Considerable,
+        HazardRatingValue::Moderate,
+    ];
+    let mut hazard_ratings = IndexMap::new();
+    hazard_ratings.insert(
+        HazardRatingKind::Overall,
+        HazardRating {
+            value: Some(HazardRatingValue::Considerable),
+            trend: Some(Trend::NoChange),
+            confidence: Some(Confidence::Moderate),
+        },
+    );
+    for (i, id) in band_ids.iter().enumerate() {
+        hazard_ratings.insert(
+            HazardRatingKind::ElevationSpecific(id.clone()),
+            HazardRating {
+                value: Some(values.get(i).copied().unwrap_or(HazardRatingValue::Low)),
+                trend: Some(Trend::NoChange),
+                confidence: Some(Confidence::Moderate),
+            },
+        );
+    }
+    let language: unic_langid::LanguageIdentifier = "en".parse()?;
+    let text = |value: &str| [(language.clone(), value.to_owned())].into_iter().collect();
+    let aspect_elevation = band_ids
+        .iter()
+        .take(2)
+        .map(|id| {
+            let aspects = [Aspect::N, Aspect::NE, Aspect::E].into_iter().collect();
+            (id.clone(), AspectElevation { aspects })
+        })
+        .collect();
+    Ok(Forecast {
+        template_version: Version {
+            major: 0,
+            minor: 3,
+            patch: 3,
+        },
+        area,
+        forecaster: Forecaster {
+            name: "Sample Forecaster".to_owned(),
+            organisation: Some("Sample Organisation".to_owned()),
+        },
+        time: time::OffsetDateTime::now_utc().replace_nanosecond(0)?,
+        recent_observations: text("Sample recent observations."),
+        forecast_changes: text("Sample forecast changes."),
+        weather_forecast: text("Sample weather forecast."),
+        valid_for: time::Duration::hours(24),
+        description: text("Sample description."),
+        hazard_ratings,
+        avalanche_problems: vec![forecast_spreadsheet::AvalancheProblem {
+            kind: ProblemKind::WindSlab,
+            aspect_elevation,
+            confidence: Some(Confidence::Moderate),
+            trend: Some(Trend::Deteriorating),
+            size: Some(Size::try_from(2)?),
+            distribution: Some(Distribution::Specific),
+            time_of_day: Some(TimeOfDay::AllDay),
+            sensitivity: Some(Sensitivity::Reactive),
+            description: text("Sample avalanche problem description."),
+            is_primary: true,
+        }],
+        elevation_bands,
+    })
+}
@@ -0,0 +1,531 @@
+//! Editing the translations (see [`crate::i18n`]) in the browser, so that translators don't need
+//! access to the repository. Edited messages are written to the localization override directory
+//! ([`crate::options::I18n::directory`]), which is watched and reloaded automatically.
+
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+};
+
+use axum::{
+    extract::{self, State},
+    response::{IntoResponse, Redirect, Response},
+    routing::{get, post},
+    Extension, Form, Router,
+};
+use eyre::Context as _;
+use http::StatusCode;
+use i18n_embed::LanguageLoader;
+use serde::{Deserialize, Serialize};
+use unic_langid::LanguageIdentifier;
+
+use crate::{
+    auth::CurrentUser,
+    error::map_eyre_error,
+    i18n::{self, LocalizationsEmbed},
+    state::AppState,
+    templates::TemplatesWithContext,
+};
+
+pub fn router() -> Router<AppState> {
+    Router::new()
+        .route("/", get(index_handler))
+        .route("/{language}", post(update_handler))
+}
+
+/// A message in a Fluent localization resource.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+struct Message {
+    id: String,
+    /// The comment directly preceding the message, which usually describes its context.
+    comment: Option<String>,
+    /// The source of the message's value (including any attributes), without indentation.
+    value: String,
+}
+
+/// Whether the `line` starts a message or term definition, e.g. `index-title = ...`.
+fn message_id(line: &str) -> Option<&str> {
+    let (id, _) = line.split_once('=')?;
+    let id = id.trim_end();
+    let name = id.strip_prefix('-').unwrap_or(id);
+    let mut chars = name.chars();
+    (chars.next()?.is_ascii_alphabetic()
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_'))
+    .then_some(id)
+}
+
+/// Split the source of a Fluent resource into its messages. Group and resource comments are
+/// discarded.
+fn parse_messages(source: &str) -> Vec<Message> {
+    struct Pending {
+        id: String,
+        comment: Option<String>,
+        first_line: String,
+        lines: Vec<String>,
+    }
+
+    fn finish(pending: Pending) -> Message {
+        let mut lines = pending.lines;
+        while lines.last().is_some_and(|line| line.trim().is_empty()) {
+            lines.pop();
+        }
+        let indent = lines
+            .iter()
+            .filter(|line| !line.trim().is_empty())
+            .map(|line| line.len() - line.trim_start().len())
+            .min()
+            .unwrap_or(0);
+        let lines = lines
+            .iter()
+            .map(|line| line.get(indent..).unwrap_or("").trim_end());
+        let value = std::iter::once(pending.first_line.as_str())
+            .filter(|first_line| !first_line.is_empty())
+            .chain(lines)
+            .collect::<Vec<_>>()
+            .join("\n");
+        Message {
+            id: pending.id,
+            comment: pending.comment,
+            value,
+        }
+    }
+
+    let mut messages = Vec::new();
+    let mut comment: Vec<&str> = Vec::new();
+    let mut pending: Option<Pending> = None;
+    for line in source.lines() {
+        if line.starts_with(char::is_whitespace) || line.is_empty() {
+            match &mut pending {
+                Some(pending) => pending.lines.push(line.to_owned()),
+                None => comment.clear(),
+            }
+            continue;
+        }
+        messages.extend(pending.take().map(finish));
+        if let Some(text) = line.strip_prefix('#') {
+            if text.starts_with('#') {
+                // Group or resource comment.
+                comment.clear();
+            } else {
+                comment.push(text.strip_prefix(' ').unwrap_or(text));
+            }
+        } else if let Some(id) = message_id(line) {
+            let (_, first_line) = line.split_once('=').expect("Line contains =");
+            pending = Some(Pending {
+                id: id.to_owned(),
+                comment: (!comment.is_empty()).then(|| comment.join("\n")),
+                first_line: first_line.trim().to_owned(),
+                lines: Vec::new(),
+            });
+            comment.clear();
+        }
+    }
+    messages.extend(pending.take().map(finish));
+    messages
+}
+
+/// Serialize the `messages` as the source of a Fluent resource.
+fn serialize_messages(messages: &[Message]) -> String {
+    let mut source = String::new();
+    for message in messages {
+        if let Some(comment) = &message.comment {
+            for line in comment.lines() {
+                source.push_str(format!("# {line}").trim_end());
+                source.push('\n');
+            }
+        }
+        if message.value.contains('\n') {
+            source.push_str(&format!("{} =\n", message.id));
+            for line in message.value.lines() {
+                if !line.is_empty() {
+                    source.push_str("    ");
+                    source.push_str(line);
+                }
+                source.push('\n');
+            }
+        } else {
+            source.push_str(&format!("{} = {}\n", message.id, message.value));
+        }
+    }
+    source
+}
+
+/// Check that the `message` is valid Fluent syntax.
+fn validate_message(message: &Message) -> Result<(), String> {
+    let source = serialize_messages(std::slice::from_ref(message));
+    fluent_syntax::parser::parse(source.as_str())
+        .map(|_| ())
+        .map_err(|(_, errors)| {
+            let errors: Vec<String> = errors
+                .iter()
+                .map(|error| format!("{:?}", error.kind))
+                .collect();
+            format!(
+                "Invalid translation for {:?}: {}",
+                message.id,
+                errors.join(", ")
+            )
+        })
+}
+
+fn resource_file_name(state: &AppState) -> String {
+    format!("{}.ftl", state.i18n.domain())
+}
+
+/// The messages for the `language` which are embedded in the application.
+fn embedded_messages(language: &LanguageIdentifier, file_name: &str) -> Vec<Message> {
+    LocalizationsEmbed::get(&format!("{language}/{file_name}"))
+        .map(|file| parse_messages(&String::from_utf8_lossy(&file.data)))
+        .unwrap_or_default()
+}
+
+fn override_path(directory: &Path, language: &LanguageIdentifier, file_name: &str) -> PathBuf {
+    directory.join(language.to_string()).join(file_name)
+}
+
+/// The messages for the `language` in the override directory.
+async fn override_messages(
+    directory: Option<&Path>,
+    language: &LanguageIdentifier,
+    file_name: &str,
+) -> eyre::Result<Vec<Message>> {
+    let Some(directory) = directory else {
+        return Ok(Vec::new());
+    };
+    let path = override_path(directory, language, file_name);
+    match tokio::fs::read_to_string(&path).await {
+        Ok(source) => Ok(parse_messages(&source)),
+        Err(error) if error.kind() == std::io::ErrorKind::NotFound => Ok(Vec::new()),
+        Err(error) => Err(error).wrap_err_with(|| format!("Error reading {path:?}")),
+    }
+}
+
+/// The languages with translations, ordered by [`crate::Options::default_language_order`].
+fn available_languages(state: &AppState) -> eyre::Result<Vec<LanguageIdentifier>> {
+    let languages = state
+        .i18n
+        .available_languages(i18n::try_get_localizations()?)?;
+    Ok(i18n::order_languages(
+        languages,
+        &state.reloadable_options.load().default_language_order,
+        |a, b| a == b,
+    ))
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default)]
+struct Query {
+    /// Edit the translations for this language.
+    language: Option<LanguageIdentifier>,
+    /// Only show messages which are missing a translation.
+    missing: bool,
+}
+
+#[derive(Serialize)]
+struct Translation {
+    language: LanguageIdentifier,
+    value: Option<String>,
+    /// Whether the value comes from the override directory.
+    overridden: bool,
+}
+
+#[derive(Serialize)]
+struct Row {
+    id: String,
+    comment: Option<String>,
+    reference: String,
+    translations: Vec<Translation>,
+}
+
+#[derive(Serialize)]
+struct Context {
+    reference_language: LanguageIdentifier,
+    languages: Vec<LanguageIdentifier>,
+    /// The number of missing translations for each language.
+    missing_counts: Vec<(LanguageIdentifier, usize)>,
+    selected_language: Option<LanguageIdentifier>,
+    missing: bool,
+    /// Whether the translations can be edited (an override directory is configured).
+    editable: bool,
+    rows: Vec<Row>,
+    error: Option<String>,
+}
+
+/// A language with its embedded messages and the messages overriding them.
+type LanguageMessages = (
+    LanguageIdentifier,
+    HashMap<String, String>,
+    HashMap<String, String>,
+);
+
+async fn render_index(
+    state: &AppState,
+    templates: &TemplatesWithContext,
+    query: Query,
+    error: Option<String>,
+) -> eyre::Result<Response> {
+    let file_name = resource_file_name(state);
+    let directory = state.options.i18n.directory.as_deref();
+    let reference_language = state.i18n.fallback_language().clone();
+    let reference = embedded_messages(&reference_language, &file_name);
+
+    let languages = available_languages(state)?;
+    let mut language_messages: Vec<LanguageMessages> = Vec::with_capacity(languages.len());
+    for language in &languages {
+        let embedded = embedded_messages(language, &file_name)
+            .into_iter()
+            .map(|message| (message.id, message.value))
+            .collect();
+        let overrides = override_messages(directory, language, &file_name)
+            .await?
+            .into_iter()
+            .map(|message| (message.id, message.value))
+            .collect();
+        language_messages.push((language.clone(), embedded, overrides));
+    }
+
+    let missing_counts = language_messages
+        .iter()
+        .map(|(language, embedded, overrides)| {
+            let count = reference
+                .iter()
+                .filter(|message| {
+                    !embedded.contains_key(&message.id) && !overrides.contains_key(&message.id)
+                })
+                .count();
+            (language.clone(), count)
+        })
+        .collect();
+
+    let rows = reference
+        .into_iter()
+        .filter_map(|message| {
+            let translations: Vec<Translation> = language_messages
+                .iter()
+                .filter(|(language, _, _)| {
+                    query
+                        .language
+                        .as_ref()
+                        .is_none_or(|selected| selected == language)
+                })
+                .map(|(language, embedded, overrides)| {
+                    let overridden = overrides.get(&message.id);
+                    Translation {
+                        language: language.clone(),
+                        value: overridden.or(embedded.get(&message.id)).cloned(),
+                        overridden: overridden.is_some(),
+                    }
+                })
+                .collect();
+            if query.missing
+                && translations
+                    .iter()
+                    .all(|translation| translation.value.is_some())
+            {
+                return None;
+            }
+            Some(Row {
+                id: message.id,
+                comment: message.comment,
+                reference: message.value,
+                translations,
+            })
+        })
+        .collect();
+
+    let status = if error.is_some() {
+        StatusCode::UNPROCESSABLE_ENTITY
+    } else {
+        StatusCode::OK
+    };
+    let context = Context {
+        reference_language,
+        languages,
+        missing_counts,
+        selected_language: query.language,
+        missing: query.missing,
+        editable: directory.is_some(),
+        rows,
+        error,
+    };
+    let response = templates.render("admin/translations.html", &context)?;
+    Ok((status, response).into_response())
+}
+
+async fn index_handler(
+    extract::Query(query): extract::Query<Query>,
+    State(state): State<AppState>,
+    Extension(templates): Extension<TemplatesWithContext>,
+) -> axum::response::Result<Response> {
+    Ok(render_index(&state, &templates, query, None)
+        .await
+        .map_err(map_eyre_error)?)
+}
+
+#[derive(Deserialize)]
+struct UpdateForm {
+    id: String,
+    /// The new value of the message, an empty value removes the override.
+    value: String,
+}
+
+async fn update_handler(
+    extract::Path(language): extract::Path<LanguageIdentifier>,
+    State(state): State<AppState>,
+    Extension(templates): Extension<TemplatesWithContext>,
+    Extension(current_user): Extension<Option<CurrentUser>>,
+    Form(form): Form<UpdateForm>,
+) -> axum::response::Result<Response> {
+    let current_user = current_user.ok_or(StatusCode::UNAUTHORIZED)?;
+    let query = Query {
+        language: Some(language.clone()),
+        missing: false,
+    };
+    let Some(directory) = state.options.i18n.directory.clone() else {
+        let error = "Editing translations requires the i18n.directory option to be set".to_owned();
+        return Ok(render_index(&state, &templates, query, Some(error))
+            .await
+            .map_err(map_eyre_error)?);
+    };
+    if !available_languages(&state)
+        .map_err(map_eyre_error)?
+        .contains(&language)
+    {
+        return Err(StatusCode::NOT_FOUND.into());
+    }
+    let file_name = resource_file_name(&state);
+    let reference = embedded_messages(state.i18n.fallback_language(), &file_name);
+    let Some(reference_message) = reference.iter().find(|message| message.id == form.id) else {
+        return Err(StatusCode::NOT_FOUND.into());
+    };
+
+    // Normalize line endings submitted by browsers.
+    let value = form.value.replace("\r\n", "\n").trim().to_owned();
+    let mut messages = override_messages(Some(&directory), &language, &file_name)
+        .await
+        .map_err(map_eyre_error)?;
+    let position = messages.iter().position(|message| message.id == form.id);
+    if value.is_empty() {
+        if let Some(position) = position {
+            messages.remove(position);
+        }
+    } else {
+        let message = Message {
+            id: form.id.clone(),
+            comment: reference_message.comment.clone(),
+            value,
+        };
+        if let Err(error) = validate_message(&message) {
+            return Ok(render_index(&state, &templates, query, Some(error))
+                .await
+                .map_err(map_eyre_error)?);
+        }
+        match position {
+            Some(position) => messages[position] = message,
+            None => messages.push(message),
+        }
+    }
+
+    let path = override_path(&directory, &language, &file_name);
+    if let Some(parent) = path.parent() {
+        tokio::fs::create_dir_all(parent)
+            .await
+            .wrap_err_with(|| format!("Error creating directory {parent:?}"))
+            .map_err(map_eyre_error)?;
+    }
+    tokio::fs::write(&path, serialize_messages(&messages))
+        .await
+        .wrap_err_with(|| format!("Error writing {path:?}"))
+        .map_err(map_eyre_error)?;
+    tracing::info!(
+        "User {:?} updated translation {:?} for {language}",
+        current_user.user.username,
+        form.id
+    );
+    Ok(Redirect::to(&format!(
+        "/admin/translations?language={language}#{}",
+        form.id
+    ))
+    .into_response())
+}
+
+#[cfg(test)]
+mod test {
+    use super::{parse_messages, serialize_messages, validate_message, Message};
+
+    const SOURCE: &str = r#"## Resource comment
+
+# The title used for the index page.
+index-title = Gudauri Avalanche Forecast
+no-forecasts = No forecasts
+# A multiline message
+about =
+    First paragraph.
+
+    Second paragraph.
+        Indented.
+-term = Term
+"#;
+
+    #[test]
+    fn test_parse_messages() {
+        insta::assert_debug_snapshot!(parse_messages(SOURCE), @r###"
+        [
+            Message {
+                id: "index-title",
+                comment: Some(
+                    "The title used for the index page.",
+                ),
+                value: "Gudauri Avalanche Forecast",
+            },
+            Message {
+                id: "no-forecasts",
+                comment: None,
+                value: "No forecasts",
+            },
+            Message {
+                id: "about",
+                comment: Some(
+                    "A multiline message",
+                ),
+                value: "First paragraph.\n\nSecond paragraph.\n    Indented.",
+            },
+            Message {
+                id: "-term",
+                comment: None,
+                value: "Term",
+            },
+        ]
+        "###);
+    }
+
+    #[test]
+    fn test_serialize_round_trip() {
+        let messages = parse_messages(SOURCE);
+        let source = serialize_messages(&messages);
+        insta::assert_snapshot!(source, @r###"
+        # The title used for the index page.
+        index-title = Gudauri Avalanche Forecast
+        no-forecasts = No forecasts
+        # A multiline message
+        about =
+            First paragraph.
+
+            Second paragraph.
+                Indented.
+        -term = Term
+        "###);
+        assert_eq!(parse_messages(&source), messages);
+    }
+
+    #[test]
+    fn test_validate_message() {
+        let message = |value: &str| Message {
+            id: "test".to_owned(),
+            comment: None,
+            value: value.to_owned(),
+        };
+        assert!(validate_message(&message("Hello { $name }")).is_ok());
+        assert!(validate_message(&message("Hello { $name")).is_err());
+    }
+}
@@ -0,0 +1,210 @@
+//! Management of the user accounts, see [`crate::users`].
+
+use axum::{
+    extract::Path,
+    response::{IntoResponse, Redirect, Response},
+    routing::{get, post},
+    Extension, Form, Router,
+};
+use http::StatusCode;
+use secrecy::{ExposeSecret, SecretString};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    auth::CurrentUser,
+    database::Database,
+    error::map_eyre_error,
+    state::AppState,
+    templates::TemplatesWithContext,
+    types,
+    users::{self, Role, User, UserId},
+};
+
+/// Minimum length of a password.
+const MIN_PASSWORD_LENGTH: usize = 12;
+
+pub fn router() -> Router<AppState> {
+    Router::new()
+        .route("/", get(index_handler).post(create_handler))
+        .route("/{id}/update", post(update_handler))
+        .route("/{id}/delete", post(delete_handler))
+}
+
+#[derive(Serialize)]
+struct Context {
+    users: Vec<User>,
+    roles: Vec<Role>,
+    current_user_id: UserId,
+    error: Option<String>,
+}
+
+async fn render_index(
+    templates: &TemplatesWithContext,
+    database: &Database,
+    current_user: &CurrentUser,
+    error: Option<String>,
+) -> axum::response::Result<Response> {
+    let status = if error.is_some() {
+        StatusCode::UNPROCESSABLE_ENTITY
+    } else {
+        StatusCode::OK
+    };
+    let context = Context {
+        users: users::list_users(database).await.map_err(map_eyre_error)?,
+        roles: enum_iterator::all::<Role>().collect(),
+        current_user_id: current_user.user.id.clone(),
+        error,
+    };
+    let response = templates
+        .render("admin/users.html", &context)
+        .map_err(map_eyre_error)?;
+    Ok((status, response).into_response())
+}
+
+async fn index_handler(
+    Extension(templates): Extension<TemplatesWithContext>,
+    Extension(database): Extension<Database>,
+    Extension(current_user): Extension<Option<CurrentUser>>,
+) -> axum::response::Result<Response> {
+    let current_user = current_user.ok_or(StatusCode::UNAUTHORIZED)?;
+    render_index(&templates, &database, &current_user, None).await
+}
+
+fn check_password(password: &SecretString) -> Result<(), String> {
+    if password.expose_secret().chars().count() < MIN_PASSWORD_LENGTH {
+        return Err(format!(
+            "Password must be at least {MIN_PASSWORD_LENGTH} characters long"
+        ));
+    }
+    Ok(())
+}
+
+#[derive(Deserialize)]
+struct CreateForm {
+    username: String,
+    password: SecretString,
+    role: Role,
+}
+
+async fn create_handler(
+    Extension(templates): Extension<TemplatesWithContext>,
+    Extension(database): Extension<Database>,
+    Extension(current_user): Extension<Option<CurrentUser>>,
+    Form(form): Form<CreateForm>,
+) -> axum::response::Result<Response> {
+    let current_user = current_user.ok_or(StatusCode::UNAUTHORIZED)?;
+    let username = form.username.trim();
+    let error = if username.is_empty() {
+        Some("Username must not be empty".to_owned())
+    } else if users::get_user_by_username(&database, username)
+        .await
+        .map_err(map_eyre_error)?
+        .is_some()
+    {
+        Some(format!("User {username:?} already exists"))
+    } else {
+        check_password(&form.password).err()
+    };
+    if let Some(error) = error {
+        return render_index(&templates, &database, &current_user, Some(error)).await;
+    }
+
+    let user = User {
+        id: UserId::generate(),
+        username: username.to_owned(),
+        password_hash: users::hash_password(form.password)
+            .await
+            .map_err(map_eyre_error)?,
+        role: form.role,
+        created_at: types::Time::now_utc(),
+    };
+    users::insert_user(&database, &user)
+        .await
+        .map_err(map_eyre_error)?;
+    tracing::info!(
+        "User {:?} created user {:?} with role {:?}",
+        current_user.user.username,
+        user.username,
+        user.role
+    );
+    Ok(Redirect::to("/admin/users").into_response())
+}
+
+#[derive(Deserialize)]
+struct UpdateForm {
+    role: Role,
+    /// The password is only changed when this is not empty.
+    #[serde(default)]
+    password: Option<SecretString>,
+}
+
+async fn update_handler(
+    Path(id): Path<UserId>,
+    Extension(templates): Extension<TemplatesWithContext>,
+    Extension(database): Extension<Database>,
+    Extension(current_user): Extension<Option<CurrentUser>>,
+    Form(form): Form<UpdateForm>,
+) -> axum::response::Result<Response> {
+    let current_user = current_user.ok_or(StatusCode::UNAUTHORIZED)?;
+    let password = form
+        .password
+        .filter(|password| !password.expose_secret().is_empty());
+    let error = if id == current_user.user.id && form.role != Role::Admin {
+        Some("You cannot remove your own admin role".to_owned())
+    } else {
+        password
+            .as_ref()
+            .and_then(|password| check_password(password).err())
+    };
+    if let Some(error) = error {
+        return render_index(&templates, &database, &current_user, Some(error)).await;
+    }
+
+    let user = users::get_user(&database, &id)
+        .await
+        .map_err(map_eyre_error)?
+        .ok_or(StatusCode::NOT_FOUND)?;
+    if user.role != form.role {
+        users::update_user_role(&database, &id, form.role)
+            .await
+            .map_err(map_eyre_error)?;
+        tracing::info!(
+            "User {:?} changed the role of user {:?} to {:?}",
+            current_user.user.username,
+            user.username,
+            form.role
+        );
+    }
+    if let Some(password) = password {
+        let password_hash = users::hash_password(password)
+            .await
+            .map_err(map_eyre_error)?;
+        users::update_user_password(&database, &id, &password_hash)
+            .await
+            .map_err(map_eyre_error)?;
+        tracing::info!(
+            "User {:?} changed the password of user {:?}",
+            current_user.user.username,
+            user.username
+        );
+    }
+    Ok(Redirect::to("/admin/users").into_response())
+}
+
+async fn delete_handler(
+    Path(id): Path<UserId>,
+    Extension(templates): Extension<TemplatesWithContext>,
+    Extension(database): Extension<Database>,
+    Extension(current_user): Extension<Option<CurrentUser>>,
+) -> axum::response::Result<Response> {
+    let current_user = current_user.ok_or(StatusCode::UNAUTHORIZED)?;
+    if id == current_user.user.id {
+        let error = "You cannot delete your own user".to_owned();
+        return render_index(&templates, &database, &current_user, Some(error)).await;
+    }
+    users::delete_user(&database, &id)
+        .await
+        .map_err(map_eyre_error)?;
+    tracing::info!("User {:?} deleted user {id}", current_user.user.username);
+    Ok(Redirect::to("/admin/users").into_response())
+}
@@ -0,0 +1,340 @@
+//! Pushing newly published and updated forecasts to external aggregators (see
+//! [`options::Aggregator`]), such as avalanche.org or the EAWS, so that the forecast areas appear
+//! on their regional danger maps. Forecasts are queued using [`Aggregators::publish`] and sent in
+//! the background, retrying with an increasing delay if the request fails. Each delivery is
+//! recorded in the database, and shown at `/admin/aggregators` where failed deliveries can be
+//! retried.
+
+use std::{sync::Arc, time::Duration};
+
+use eyre::{Context, ContextCompat};
+use secrecy::ExposeSecret;
+use serde::Serialize;
+use tokio::sync::mpsc;
+use tracing::Instrument;
+
+use crate::{
+    api,
+    database::{blob::BlobStore, Database},
+    forecasts::{
+        get_forecast_data, parse_forecast_name, published::PublishedFiles, ForecastData,
+        ForecastSpreadsheetSchema, ForecastSpreadsheetSchemas, RequestedForecastData,
+    },
+    google_drive,
+    options::{self, AggregatorFormat, GoogleDrive},
+    shutdown::Shutdown,
+    types,
+};
+
+/// Delays before retrying a failed delivery.
+const RETRY_DELAYS: [Duration; 5] = [
+    Duration::from_secs(60),
+    Duration::from_secs(5 * 60),
+    Duration::from_secs(15 * 60),
+    Duration::from_secs(60 * 60),
+    Duration::from_secs(4 * 60 * 60),
+];
+/// Timeout for each request to an aggregator.
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+/// Language of the CAAML bulletin when the forecast file name doesn't specify one.
+const DEFAULT_LANGUAGE: &str = "en";
+
+/// A record of pushing a forecast to an aggregator.
+#[derive(Serialize, Debug)]
+pub struct DeliveryRecord {
+    pub id: String,
+    /// See [`options::Aggregator::name`].
+    pub aggregator: String,
+    pub file_name: String,
+    pub created_at: types::Time,
+    /// Number of attempts made so far.
+    pub attempts: i64,
+    /// `None` if the forecast has not (yet) been delivered.
+    pub delivered_at: Option<types::Time>,
+    /// The error from the most recent failed attempt.
+    pub error: Option<String>,
+}
+
+/// List the most recent deliveries, newest first.
+pub async fn recent_deliveries(
+    database: &Database,
+    limit: i64,
+) -> eyre::Result<Vec<DeliveryRecord>> {
+    Ok(sqlx::query_as!(
+        DeliveryRecord,
+        r#"SELECT
+            id,
+            aggregator,
+            file_name,
+            created_at as "created_at: types::Time",
+            attempts,
+            delivered_at as "delivered_at: types::Time",
+            error
+        FROM aggregator_deliveries ORDER BY created_at DESC LIMIT $1"#,
+        limit
+    )
+    .fetch_all(database)
+    .await?)
+}
+
+/// A delivery waiting to be sent.
+struct Delivery {
+    id: String,
+    aggregator: &'static options::Aggregator,
+    file_name: String,
+}
+
+pub struct Config {
+    pub aggregators: &'static [options::Aggregator],
+    pub client: reqwest::Client,
+    pub database: Database,
+    pub published_files: Arc<PublishedFiles>,
+    pub blobs: Arc<BlobStore>,
+    pub google_drive: &'static GoogleDrive,
+    /// The schema for the newest version of the forecast spreadsheet template.
+    pub forecast_spreadsheet_schema: &'static ForecastSpreadsheetSchema,
+    pub forecast_spreadsheet_schemas: &'static ForecastSpreadsheetSchemas,
+}
+
+/// Queue for the forecasts to be pushed to the aggregators, see [`spawn`]. Cheap to clone.
+#[derive(Clone)]
+pub struct Aggregators {
+    aggregators: &'static [options::Aggregator],
+    database: Database,
+    /// `None` when there are no aggregators configured.
+    tx: Option<mpsc::Sender<Delivery>>,
+}
+
+impl Aggregators {
+    /// Queue the forecast file with `file_name` to be pushed to each of the aggregators in the
+    /// background.
+    pub async fn publish(&self, file_name: &str) {
+        let Some(tx) = &self.tx else {
+            return;
+        };
+        for aggregator in self.aggregators {
+            let id = uuid::Uuid::new_v4().to_string();
+            let now = types::Time::now_utc();
+            if let Err(error) = sqlx::query!(
+                "INSERT INTO aggregator_deliveries VALUES($1, $2, $3, $4, 0, NULL, NULL)",
+                id,
+                aggregator.name,
+                file_name,
+                now,
+            )
+            .execute(&self.database)
+            .await
+            {
+                tracing::error!(
+                    "Error recording delivery to aggregator {:?}: {error}",
+                    aggregator.name
+                );
+                continue;
+            }
+            let delivery = Delivery {
+                id,
+                aggregator,
+                file_name: file_name.to_owned(),
+            };
+            if let Err(error) = tx.try_send(delivery) {
+                tracing::warn!("Unable to queue delivery to aggregator: {error}");
+            }
+        }
+    }
+
+    /// Queue the delivery with `id` to be sent again. Returns `false` if there is no such
+    /// delivery, or its aggregator is no longer configured.
+    pub async fn retry(&self, id: &str) -> eyre::Result<bool> {
+        let Some(tx) = &self.tx else {
+            return Ok(false);
+        };
+        let Some(record) = sqlx::query!(
+            "SELECT aggregator, file_name FROM aggregator_deliveries WHERE id=$1",
+            id
+        )
+        .fetch_optional(&self.database)
+        .await?
+        else {
+            return Ok(false);
+        };
+        let Some(aggregator) = self
+            .aggregators
+            .iter()
+            .find(|aggregator| aggregator.name == record.aggregator)
+        else {
+            return Ok(false);
+        };
+        tx.try_send(Delivery {
+            id: id.to_owned(),
+            aggregator,
+            file_name: record.file_name,
+        })
+        .map_err(|error| eyre::eyre!("Unable to queue delivery to aggregator: {error}"))?;
+        Ok(true)
+    }
+}
+
+/// Spawn the task sending the deliveries queued using the returned [`Aggregators`]. Deliveries
+/// which are waiting to be retried when `shutdown` is requested are abandoned, and can be retried
+/// from the admin page.
+pub fn spawn(config: Config, mut shutdown: Shutdown) -> Aggregators {
+    let aggregators = Aggregators {
+        aggregators: config.aggregators,
+        database: config.database.clone(),
+        tx: None,
+    };
+    if config.aggregators.is_empty() {
+        return aggregators;
+    }
+    let (tx, mut rx) = mpsc::channel::<Delivery>(100);
+    let config = Arc::new(config);
+    tokio::spawn(
+        async move {
+            loop {
+                let delivery = tokio::select! {
+                    delivery = rx.recv() => delivery,
+                    _ = shutdown.requested() => break,
+                };
+                let Some(delivery) = delivery else {
+                    return;
+                };
+                // Each delivery is sent in its own task so that one waiting to be retried doesn't
+                // hold up the others.
+                let config = config.clone();
+                let shutdown = shutdown.clone();
+                tokio::spawn(
+                    async move { deliver(&delivery, &config, shutdown).await }.in_current_span(),
+                );
+            }
+        }
+        .instrument(tracing::error_span!("aggregators")),
+    );
+    Aggregators {
+        tx: Some(tx),
+        ..aggregators
+    }
+}
+
+/// Send the `delivery`, waiting for each of the [`RETRY_DELAYS`] before retrying a failed
+/// attempt, recording the outcome of each attempt.
+async fn deliver(delivery: &Delivery, config: &Config, mut shutdown: Shutdown) {
+    let aggregator = &delivery.aggregator.name;
+    let file_name = &delivery.file_name;
+    let mut retry_delays = RETRY_DELAYS.iter();
+    loop {
+        let result = send(delivery, config).await;
+        if let Err(error) = record_attempt(&config.database, &delivery.id, &result).await {
+            tracing::error!("Error recording delivery {}: {error:?}", delivery.id);
+        }
+        let Err(error) = result else {
+            tracing::info!("Delivered forecast {file_name:?} to aggregator {aggregator:?}");
+            return;
+        };
+        metrics::counter!("aggregator_delivery_failures_total").increment(1);
+        let Some(delay) = retry_delays.next() else {
+            tracing::error!(
+                "Error delivering forecast {file_name:?} to aggregator {aggregator:?}: {error:?}"
+            );
+            return;
+        };
+        tracing::warn!(
+            "Error delivering forecast {file_name:?} to aggregator {aggregator:?}, retrying in {}: {error:?}",
+            humantime::format_duration(*delay)
+        );
+        tokio::select! {
+            _ = tokio::time::sleep(*delay) => {}
+            _ = shutdown.requested() => return,
+        }
+    }
+}
+
+async fn record_attempt(
+    database: &Database,
+    id: &str,
+    result: &eyre::Result<()>,
+) -> eyre::Result<()> {
+    let (delivered_at, error) = match result {
+        Ok(()) => (Some(types::Time::now_utc()), None),
+        Err(error) => (None, Some(format!("{error:#}"))),
+    };
+    sqlx::query!(
+        "UPDATE aggregator_deliveries SET attempts=attempts + 1, delivered_at=$1, error=$2 WHERE id=$3",
+        delivered_at,
+        error,
+        id
+    )
+    .execute(database)
+    .await?;
+    Ok(())
+}
+
+/// Build the payload for the forecast, and send it to the aggregator.
+async fn send(delivery: &Delivery, config: &Config) -> eyre::Result<()> {
+    let files = config.published_files.list_files().await?;
+    let file = google_drive::get_file_in_list(&delivery.file_name, &files)
+        .wrap_err("The forecast file is no longer published")?;
+    let forecast = match get_forecast_data(
+        file,
+        RequestedForecastData::Forecast,
+        &config.client,
+        &config.database,
+        &config.blobs,
+        config.google_drive,
+        config.forecast_spreadsheet_schemas,
+    )
+    .await?
+    {
+        ForecastData::Forecast(forecast) => forecast,
+        ForecastData::File(_) => eyre::bail!("Expected ForecastData::Forecast"),
+    };
+    let body = payload(delivery, forecast, config)?;
+
+    let mut request = config
+        .client
+        .post(delivery.aggregator.url.clone())
+        .timeout(REQUEST_TIMEOUT)
+        .header(http::header::CONTENT_TYPE, "application/json");
+    if let Some(token) = &delivery.aggregator.token {
+        request = request.bearer_auth(token.expose_secret());
+    }
+    request
+        .body(body)
+        .send()
+        .await?
+        .error_for_status()
+        .wrap_err("Status code of response is an error")?;
+    Ok(())
+}
+
+/// The request body for the `forecast`, in the format of the aggregator.
+fn payload(
+    delivery: &Delivery,
+    forecast: forecast_spreadsheet::Forecast,
+    config: &Config,
+) -> eyre::Result<Vec<u8>> {
+    match delivery.aggregator.format {
+        AggregatorFormat::Json => Ok(serde_json::to_vec(&api::v1::types::Forecast::new(
+            delivery.file_name.clone(),
+            forecast,
+        ))?),
+        AggregatorFormat::Caaml => {
+            let details =
+                parse_forecast_name(&delivery.file_name, config.forecast_spreadsheet_schema)?;
+            let area = &details.forecast.area;
+            let language = match details.language {
+                Some(language) => language,
+                None => DEFAULT_LANGUAGE.parse()?,
+            };
+            let document = forecast_spreadsheet::caaml::export(
+                &forecast,
+                &forecast_spreadsheet::caaml::ExportOptions {
+                    bulletin_id: &delivery.file_name,
+                    region_id: delivery.aggregator.regions.get(area).unwrap_or(area),
+                    region_name: area,
+                    language: &language,
+                },
+            )?;
+            Ok(serde_json::to_vec(&document)?)
+        }
+    }
+}
@@ -0,0 +1,242 @@
+//! Operational alerts, which notify the operators in a Slack or Matrix room when a background task
+//! (fetching weather station data, refreshing the published forecasts from Google Drive, backups)
+//! fails repeatedly, see [`crate::options::Alerts`]. Each task is alerted about once it has
+//! failed [`crate::options::Alerts::failure_threshold`] times in a row, then at most every
+//! [`crate::options::Alerts::repeat_minutes`] while it keeps failing, and again once it recovers.
+
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use eyre::Context;
+use secrecy::ExposeSecret;
+
+use crate::options;
+
+/// Maximum length of the error message included in an alert.
+const MAX_ERROR_LEN: usize = 500;
+
+/// Handle for reporting the outcome of background tasks. Cheap to clone, does nothing when
+/// alerts are not configured.
+#[derive(Clone)]
+pub struct Alerts {
+    inner: Option<Arc<Inner>>,
+}
+
+struct Inner {
+    options: &'static options::Alerts,
+    client: reqwest::Client,
+    /// Identifies the site in the messages, in case several sites alert the same room.
+    site: String,
+    tracker: Mutex<Tracker>,
+}
+
+impl Alerts {
+    pub fn new(
+        options: Option<&'static options::Alerts>,
+        client: reqwest::Client,
+        base_url: &url::Url,
+    ) -> Self {
+        let inner = options.map(|options| {
+            Arc::new(Inner {
+                options,
+                client,
+                site: base_url.host_str().unwrap_or(base_url.as_str()).to_owned(),
+                tracker: Mutex::new(Tracker::new(
+                    options.failure_threshold,
+                    Duration::from_secs(options.repeat_minutes * 60),
+                )),
+            })
+        });
+        Self { inner }
+    }
+
+    /// Record that the `task` (e.g. `backup default`) failed with the `error`.
+    pub fn failure(&self, task: &str, error: &eyre::Report) {
+        let Some(inner) = &self.inner else {
+            return;
+        };
+        let consecutive_failures = inner
+            .tracker
+            .lock()
+            .expect("Alerts tracker lock poisoned")
+            .failure(task, Instant::now());
+        if let Some(consecutive_failures) = consecutive_failures {
+            let mut error = format!("{error:#}");
+            if error.len() > MAX_ERROR_LEN {
+                let end = error.floor_char_boundary(MAX_ERROR_LEN);
+                error.truncate(end);
+                error.push('…');
+            }
+            inner.notify(format!(
+                "[{}] {task} has failed {consecutive_failures} times in a row: {error}",
+                inner.site
+            ));
+        }
+    }
+
+    /// Record that the `task` succeeded.
+    pub fn success(&self, task: &str) {
+        let Some(inner) = &self.inner else {
+            return;
+        };
+        let recovered = inner
+            .tracker
+            .lock()
+            .expect("Alerts tracker lock poisoned")
+            .success(task);
+        if recovered {
+            inner.notify(format!("[{}] {task} has recovered", inner.site));
+        }
+    }
+}
+
+impl Inner {
+    /// Send the `message` to the configured rooms in the background.
+    fn notify(self: &Arc<Self>, message: String) {
+        tracing::warn!("Sending alert: {message}");
+        let inner = self.clone();
+        tokio::spawn(async move {
+            if let Some(slack) = &inner.options.slack {
+                if let Err(error) = send_slack(&inner.client, slack, &message).await {
+                    tracing::error!("Error sending Slack alert: {error:?}");
+                }
+            }
+            if let Some(matrix) = &inner.options.matrix {
+                if let Err(error) = send_matrix(&inner.client, matrix, &message).await {
+                    tracing::error!("Error sending Matrix alert: {error:?}");
+                }
+            }
+        });
+    }
+}
+
+async fn send_slack(
+    client: &reqwest::Client,
+    slack: &options::SlackAlerts,
+    message: &str,
+) -> eyre::Result<()> {
+    client
+        .post(slack.webhook_url.expose_secret())
+        .json(&serde_json::json!({ "text": message }))
+        .send()
+        .await?
+        .error_for_status()
+        .wrap_err("Status code of response is an error")?;
+    Ok(())
+}
+
+async fn send_matrix(
+    client: &reqwest::Client,
+    matrix: &options::MatrixAlerts,
+    message: &str,
+) -> eyre::Result<()> {
+    let mut url = matrix.homeserver_url.clone();
+    url.path_segments_mut()
+        .map_err(|_| eyre::eyre!("Invalid Matrix homeserver url"))?
+        .pop_if_empty()
+        .extend([
+            "_matrix",
+            "client",
+            "v3",
+            "rooms",
+            &matrix.room_id,
+            "send",
+            "m.room.message",
+            &uuid::Uuid::new_v4().to_string(),
+        ]);
+    client
+        .put(url)
+        .bearer_auth(matrix.access_token.expose_secret())
+        .json(&serde_json::json!({ "msgtype": "m.text", "body": message }))
+        .send()
+        .await?
+        .error_for_status()
+        .wrap_err("Status code of response is an error")?;
+    Ok(())
+}
+
+#[derive(Default)]
+struct TaskState {
+    consecutive_failures: u32,
+    /// When the last alert about this task failing was sent.
+    alerted_at: Option<Instant>,
+}
+
+/// Decides when to alert about each task, using the number of consecutive failures and when the
+/// last alert was sent.
+struct Tracker {
+    failure_threshold: u32,
+    repeat_interval: Duration,
+    tasks: HashMap<String, TaskState>,
+}
+
+impl Tracker {
+    fn new(failure_threshold: u32, repeat_interval: Duration) -> Self {
+        Self {
+            failure_threshold,
+            repeat_interval,
+            tasks: HashMap::new(),
+        }
+    }
+
+    /// Record a failure of the `task`, returning the number of consecutive failures if an alert
+    /// should be sent.
+    fn failure(&mut self, task: &str, now: Instant) -> Option<u32> {
+        let state = self.tasks.entry(task.to_owned()).or_default();
+        state.consecutive_failures += 1;
+        if state.consecutive_failures < self.failure_threshold {
+            return None;
+        }
+        let repeat = state
+            .alerted_at
+            .is_none_or(|alerted_at| now.duration_since(alerted_at) >= self.repeat_interval);
+        if !repeat {
+            return None;
+        }
+        state.alerted_at = Some(now);
+        Some(state.consecutive_failures)
+    }
+
+    /// Record a success of the `task`, returning whether it has recovered after an alert was
+    /// sent.
+    fn success(&mut self, task: &str) -> bool {
+        self.tasks
+            .remove(task)
+            .is_some_and(|state| state.alerted_at.is_some())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::{Duration, Instant};
+
+    use super::Tracker;
+
+    #[test]
+    fn test_tracker() {
+        let mut tracker = Tracker::new(3, Duration::from_secs(60 * 60));
+        let start = Instant::now();
+        assert_eq!(tracker.failure("backup", start), None);
+        assert_eq!(tracker.failure("backup", start), None);
+        assert_eq!(tracker.failure("backup", start), Some(3));
+        // Deduplicated until the repeat interval has passed.
+        assert_eq!(
+            tracker.failure("backup", start + Duration::from_secs(60)),
+            None
+        );
+        assert_eq!(
+            tracker.failure("backup", start + Duration::from_secs(60 * 60)),
+            Some(5)
+        );
+        // Other tasks are tracked separately.
+        assert_eq!(tracker.failure("google drive", start), None);
+        assert!(!tracker.success("google drive"));
+
+        assert!(tracker.success("backup"));
+        assert!(!tracker.success("backup"));
+        assert_eq!(tracker.failure("backup", start), None);
+    }
+}
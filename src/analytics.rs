@@ -1,4 +1,8 @@
-use std::{collections::HashMap, num::NonZeroU32, sync::Arc};
+use std::{
+    collections::{HashMap, HashSet},
+    num::NonZeroU32,
+    sync::Arc,
+};
 
 use average::WeightedMean;
 use axum::{
@@ -6,22 +10,28 @@ use axum::{
     middleware::Next,
     response::Response,
 };
+use base64::Engine;
 use cronchik::CronSchedule;
 use eyre::Context;
-use futures::{lock::Mutex, StreamExt, TryStreamExt};
-use governor::{state::StreamRateLimitExt, Quota, RateLimiter};
-use http::StatusCode;
+use futures::{lock::Mutex, TryStreamExt};
+use governor::{DefaultDirectRateLimiter, Quota, RateLimiter};
+use hmac::{Hmac, Mac};
+use http::{header, StatusCode};
 use nonzero_ext::nonzero;
+use once_cell::sync::Lazy;
 use serde::Serialize;
+use sha2::Sha256;
 use time::{format_description::well_known::Rfc3339, Duration, OffsetDateTime};
 use tokio::sync::{mpsc, watch};
-use tokio_stream::wrappers::ReceiverStream;
 use tracing::Instrument;
 use uuid::Uuid;
 
 use crate::{
+    client_ip,
     database::Database,
     isbot::IsBot,
+    options,
+    shutdown::Shutdown,
     state::AppState,
     types::{self, Uri},
 };
@@ -37,6 +47,99 @@ pub struct Analytics {
 #[derive(Clone, Debug)]
 pub struct Event {
     uri: Uri,
+    visitor: Option<Visitor>,
+}
+
+/// An anonymous identifier for a visitor on a given day, used to estimate the number of unique
+/// visitors without cookies. See [`Visitor::new`].
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Visitor {
+    day: time::Date,
+    id: String,
+}
+
+type Salt = [u8; 32];
+
+/// Random salt used for identifying visitors, rotated daily, so that visitors cannot be
+/// identified or tracked across days. The salt for the current day is stored in the
+/// `analytics_visitor_salt` table so that visitors are still counted once after a restart, and
+/// kept here to avoid querying it for every request.
+static VISITOR_SALT: Lazy<std::sync::Mutex<Option<(time::Date, Salt)>>> =
+    Lazy::new(|| std::sync::Mutex::new(None));
+
+/// Get the salt for the `day`, creating it if it doesn't exist yet and deleting the salts for
+/// previous days.
+async fn visitor_salt(database: &Database, day: time::Date) -> eyre::Result<Salt> {
+    if let Some((salt_day, salt)) = *VISITOR_SALT.lock().expect("Visitor salt lock poisoned") {
+        if salt_day == day {
+            return Ok(salt);
+        }
+    }
+    let formatted_day = format_day(day);
+    let mut new_salt = [0; 32];
+    new_salt[..16].copy_from_slice(Uuid::new_v4().as_bytes());
+    new_salt[16..].copy_from_slice(Uuid::new_v4().as_bytes());
+    let new_salt = new_salt.to_vec();
+    let mut transaction = database.begin().await?;
+    sqlx::query!(
+        "DELETE FROM analytics_visitor_salt WHERE day < $1",
+        formatted_day
+    )
+    .execute(&mut *transaction)
+    .await
+    .wrap_err("Error deleting previous analytics visitor salts")?;
+    sqlx::query!(
+        "INSERT OR IGNORE INTO analytics_visitor_salt(day, salt) VALUES($1, $2)",
+        formatted_day,
+        new_salt
+    )
+    .execute(&mut *transaction)
+    .await
+    .wrap_err("Error inserting analytics visitor salt")?;
+    let salt: Salt = sqlx::query_scalar!(
+        "SELECT salt FROM analytics_visitor_salt WHERE day = $1",
+        formatted_day
+    )
+    .fetch_one(&mut *transaction)
+    .await
+    .wrap_err("Error fetching analytics visitor salt")?
+    .try_into()
+    .map_err(|_| eyre::eyre!("Analytics visitor salt has an invalid length"))?;
+    transaction.commit().await?;
+    *VISITOR_SALT.lock().expect("Visitor salt lock poisoned") = Some((day, salt));
+    Ok(salt)
+}
+
+impl Visitor {
+    /// Identify the visitor by a hash of their IP address and user agent, salted with the
+    /// [`VISITOR_SALT`] for the `day`.
+    async fn new(
+        database: &Database,
+        day: time::Date,
+        ip: &str,
+        user_agent: &str,
+    ) -> eyre::Result<Self> {
+        let salt = visitor_salt(database, day).await?;
+        Ok(Self::new_with_salt(day, &salt, ip, user_agent))
+    }
+
+    fn new_with_salt(day: time::Date, salt: &Salt, ip: &str, user_agent: &str) -> Self {
+        let mut mac =
+            Hmac::<Sha256>::new_from_slice(salt).expect("HMAC accepts keys of any length");
+        mac.update(ip.as_bytes());
+        mac.update(b"\n");
+        mac.update(user_agent.as_bytes());
+        // The first 16 bytes are plenty to avoid collisions between the visitors of a day.
+        let id = base64::engine::general_purpose::URL_SAFE_NO_PAD
+            .encode(&mac.finalize().into_bytes()[..16]);
+        Self { day, id }
+    }
+}
+
+/// Format a day as stored in the `analytics_visitors` table.
+pub fn format_day(day: time::Date) -> String {
+    day.format(time::macros::format_description!("[year]-[month]-[day]"))
+        .expect("Unable to format day")
 }
 
 #[derive(Debug, Serialize)]
@@ -92,10 +195,24 @@ pub async fn get_time_bounds(
 
 pub struct CompactionConfig {
     pub schedule: CronSchedule,
+    /// See [`compact`].
+    pub window: Duration,
+    /// See [`compact`].
+    pub keep: Duration,
     pub database: Database,
+    pub retention: Option<options::AnalyticsRetention>,
 }
 
-pub fn spawn_compaction_task(CompactionConfig { schedule, database }: CompactionConfig) {
+pub fn spawn_compaction_task(
+    CompactionConfig {
+        schedule,
+        window,
+        keep,
+        database,
+        retention,
+    }: CompactionConfig,
+    mut shutdown: Shutdown,
+) {
     let span = tracing::error_span!("analytics_compaction");
     tokio::spawn(
         async move {
@@ -105,29 +222,106 @@ pub fn spawn_compaction_task(CompactionConfig { schedule, database }: Compaction
                 let duration: std::time::Duration = (next_time - now)
                     .try_into()
                     .expect("Unable to convert duration");
-                let human_duration = humantime::format_duration(duration.clone());
+                let human_duration = humantime::format_duration(duration);
                 tracing::info!("Next analytics compaction in {human_duration}");
-                tokio::time::sleep(duration).await;
+                tokio::select! {
+                    _ = tokio::time::sleep(duration) => {}
+                    _ = shutdown.requested() => return,
+                }
 
-                if let Err(error) =
-                    compact(&database, time::Duration::days(1), time::Duration::days(7))
-                        .await
-                        .wrap_err("Error performing compaction")
+                if let Err(error) = compact(&database, window, keep, false)
+                    .await
+                    .wrap_err("Error performing compaction")
                 {
                     tracing::error!("{error:?}");
                 }
+
+                if let Some(retention) = &retention {
+                    if let Err(error) = apply_retention(&database, retention)
+                        .await
+                        .wrap_err("Error applying analytics retention policy")
+                    {
+                        tracing::error!("{error:?}");
+                    }
+                }
             }
         }
         .instrument(span),
     );
 }
 
+/// Apply the `retention` policy to the analytics entries.
+pub async fn apply_retention(
+    database: &Database,
+    retention: &options::AnalyticsRetention,
+) -> eyre::Result<()> {
+    let keep = Duration::days(retention.days.into());
+    match retention.action {
+        options::AnalyticsRetentionAction::Delete => {
+            let cutoff = OffsetDateTime::now_utc() - keep;
+            let cutoff_time = types::Time::from(cutoff);
+            let deleted = sqlx::query!("DELETE FROM analytics WHERE time < $1", cutoff_time)
+                .execute(database)
+                .await
+                .wrap_err("Error deleting analytics rows")?
+                .rows_affected();
+            let cutoff_day = format_day(cutoff.date());
+            sqlx::query!("DELETE FROM analytics_visitors WHERE day < $1", cutoff_day)
+                .execute(database)
+                .await
+                .wrap_err("Error deleting analytics visitor rows")?;
+            tracing::info!(
+                "Deleted {deleted} analytics entries older than {} days",
+                retention.days
+            );
+        }
+        options::AnalyticsRetentionAction::Aggregate => {
+            compact(database, Duration::days(30), keep, false).await?;
+        }
+    }
+    Ok(())
+}
+
+/// Summary of the changes made (or that would be made) by [`compact`].
+#[derive(Debug, Default, Serialize, Clone, Copy)]
+pub struct CompactionReport {
+    /// Number of entries which are merged.
+    pub merged_rows: usize,
+    /// Number of new entries replacing the merged entries.
+    pub new_rows: usize,
+    /// Approximate reduction in the size of the stored entries, in bytes.
+    pub estimated_bytes_saved: usize,
+}
+
+impl CompactionReport {
+    fn add(&mut self, operation: &CompactOperation) {
+        self.merged_rows += operation.delete.len();
+        self.new_rows += 1;
+        let deleted_bytes: usize = operation.delete.iter().map(estimated_row_size).sum();
+        self.estimated_bytes_saved +=
+            deleted_bytes.saturating_sub(estimated_row_size(&operation.new));
+    }
+}
+
+/// Approximate size of the stored entry (excluding the database's overhead).
+fn estimated_row_size(entry: &Analytics) -> usize {
+    // id (uuid as text) + uri + visits (integer) + time (as text).
+    36 + entry.uri.len() + 8 + 24
+}
+
 /// Compact analytics entries in the database.
 ///
 /// Older entries with the same key will be combined within some window. This results in a loss of resolution in the
-/// time domain, in exchange for a much smaller database.
-pub async fn compact(database: &Database, window: Duration, keep: Duration) -> eyre::Result<()> {
-    tracing::info!("Compacting analytics...");
+/// time domain, in exchange for a much smaller database. Entries within `keep` of the most recent entry are not
+/// compacted. When `dry_run` is `true` the database is not modified, and only the report is produced.
+pub async fn compact(
+    database: &Database,
+    window: Duration,
+    keep: Duration,
+    dry_run: bool,
+) -> eyre::Result<CompactionReport> {
+    tracing::info!("Compacting analytics (dry run: {dry_run})...");
+    let mut report = CompactionReport::default();
 
     let min = if let Some((min, _max)) = get_time_bounds(database)
         .await
@@ -136,7 +330,7 @@ pub async fn compact(database: &Database, window: Duration, keep: Duration) -> e
         min
     } else {
         // No analytics
-        return Ok(());
+        return Ok(report);
     };
 
     let last = match sqlx::query_as!(
@@ -149,7 +343,7 @@ pub async fn compact(database: &Database, window: Duration, keep: Duration) -> e
         Some(last) => last,
         None => {
             tracing::debug!("No analytics found to compact");
-            return Ok(());
+            return Ok(report);
         }
     };
 
@@ -178,17 +372,23 @@ pub async fn compact(database: &Database, window: Duration, keep: Duration) -> e
             from_time,
             to_time
         ).fetch(database).try_fold(HashMap::<String, Vec<Analytics>>::new(), |mut acc, item| async move {
-            let entries = acc.entry(item.uri.clone()).or_insert_with(|| Vec::new());
+            let entries = acc.entry(item.uri.clone()).or_insert_with(Vec::new);
             entries.push(item);
             Ok(acc)
         }).await.wrap_err("Error fetching range of analytics rows")?;
 
         let operations = compact_operations(map)?;
         tracing::debug!("{} operations", operations.len());
-        for CompactOperation { delete, new } in &operations {
-            tracing::debug!("Replacing {} entries with {new:?}", delete.len());
+        for operation in &operations {
+            tracing::debug!(
+                "Replacing {} entries with {:?}",
+                operation.delete.len(),
+                operation.new
+            );
+            report.add(operation);
         }
 
+        let operations = if dry_run { Vec::new() } else { operations };
         for CompactOperation { delete, new } in operations {
             let delete_ids: Vec<String> = delete
                 .into_iter()
@@ -234,16 +434,27 @@ pub async fn compact(database: &Database, window: Duration, keep: Duration) -> e
         from_time = to_time;
     }
 
-    tracing::info!("Finished compacting analytics!");
+    tracing::info!("Finished compacting analytics! {report:?}");
 
-    Ok(())
+    Ok(report)
 }
 
 async fn process_analytics_events(
     accumulator: EventsAccumulator,
     database: &Database,
 ) -> eyre::Result<()> {
-    for (uri, visits) in accumulator {
+    for Visitor { day, id } in accumulator.visitors {
+        let day = format_day(day);
+        sqlx::query!(
+            "INSERT OR IGNORE INTO analytics_visitors VALUES ($1, $2);",
+            day,
+            id,
+        )
+        .execute(database)
+        .await
+        .wrap_err("Error inserting analytics visitor row")?;
+    }
+    for (uri, visits) in accumulator.visits {
         let id = uuid::Uuid::new_v4();
         let time = types::Time::now_utc();
         sqlx::query!(
@@ -261,25 +472,55 @@ async fn process_analytics_events(
     Ok(())
 }
 
-type EventsAccumulator = HashMap<String, u32>;
+#[derive(Clone, Default)]
+struct EventsAccumulator {
+    /// Number of visits for each uri path.
+    visits: HashMap<String, u32>,
+    visitors: HashSet<Visitor>,
+}
+
+impl EventsAccumulator {
+    fn is_empty(&self) -> bool {
+        self.visits.is_empty() && self.visitors.is_empty()
+    }
+
+    fn clear(&mut self) {
+        self.visits.clear();
+        self.visitors.clear();
+    }
+}
 
 #[tracing::instrument(skip_all)]
 async fn process_accumulated_events(
     database: &Database,
-    rx: mpsc::Receiver<EventsAccumulator>,
-    batch_rate: NonZeroU32,
+    mut rx: mpsc::Receiver<EventsAccumulator>,
+    reloadable_options: options::ReloadableOptionsHandle,
+    mut shutdown: Shutdown,
 ) {
-    let limiter = RateLimiter::direct(Quota::per_hour(batch_rate).allow_burst(nonzero!(1u32)));
-
-    ReceiverStream::from(rx)
-        .ratelimit_stream(&limiter)
-        .for_each(|accumulator| async {
-            process_analytics_events(accumulator, database)
-                .await
-                .wrap_err("Error processing analytics events")
-                .unwrap_or_else(|error| tracing::error!("{error}"));
-        })
-        .await;
+    fn new_limiter(batch_rate: NonZeroU32) -> DefaultDirectRateLimiter {
+        RateLimiter::direct(Quota::per_hour(batch_rate).allow_burst(nonzero!(1u32)))
+    }
+    let mut batch_rate = reloadable_options.load().analytics_event_batch_rate;
+    let mut limiter = new_limiter(batch_rate);
+
+    while let Some(accumulator) = rx.recv().await {
+        // The rate may have been changed by reloading the options.
+        let current_batch_rate = reloadable_options.load().analytics_event_batch_rate;
+        if current_batch_rate != batch_rate {
+            batch_rate = current_batch_rate;
+            limiter = new_limiter(batch_rate);
+        }
+        // Don't wait for the rate limiter when shutting down, the batch needs to be written
+        // before exiting.
+        tokio::select! {
+            _ = limiter.until_ready() => {}
+            _ = shutdown.requested() => {}
+        }
+        process_analytics_events(accumulator, database)
+            .await
+            .wrap_err("Error processing analytics events")
+            .unwrap_or_else(|error| tracing::error!("{error}"));
+    }
 }
 
 /// Receive a notification that a batch of events in `events_accumulator` are ready for processing
@@ -303,7 +544,6 @@ async fn notify_received_events_for_processing(
                 if !events_accumulator.is_empty() {
                     permit.send(events_accumulator.clone());
                     events_accumulator.clear();
-                } else {
                 }
             }
             Err(error) => {
@@ -318,15 +558,24 @@ async fn notify_received_events_for_processing(
 /// to be submitted to the database in a rate-limited fashion in order to reduce write load during high
 /// traffic situations.
 ///
-/// `batch_rate` is the rate that batches can be submitted to the database (per hour).
+/// [`options::ReloadableOptions::analytics_event_batch_rate`] is the rate that batches can be
+/// submitted to the database (per hour).
+///
+/// When `shutdown` is requested, all the accumulated events are written to the database
+/// (ignoring the rate limit) before returning.
 #[tracing::instrument(skip_all)]
 pub async fn process_analytics(
     database: Database,
     mut rx: mpsc::Receiver<Event>,
-    batch_rate: NonZeroU32,
+    reloadable_options: options::ReloadableOptionsHandle,
+    mut shutdown: Shutdown,
 ) {
     fn accumulate_event(events_accumulator: &mut EventsAccumulator, event: Event) {
+        if let Some(visitor) = event.visitor {
+            events_accumulator.visitors.insert(visitor);
+        }
         events_accumulator
+            .visits
             // We intentionally only obtain the path section of the uri,
             // in order to avoid combinatorial explosion of uri parameters
             // in the database.
@@ -337,17 +586,25 @@ pub async fn process_analytics(
 
     // Care must be taken not to hold a lock on this over an await.
     let events_accumulator: Arc<Mutex<EventsAccumulator>> =
-        Arc::new(Mutex::new(EventsAccumulator::with_capacity(1)));
+        Arc::new(Mutex::new(EventsAccumulator::default()));
     let (batch_tx, batch_rx) = mpsc::channel::<EventsAccumulator>(1);
 
-    tokio::task::spawn(async move {
-        process_accumulated_events(&database, batch_rx, batch_rate).await;
+    let accumulated_database = database.clone();
+    let accumulated_shutdown = shutdown.clone();
+    let process_accumulated = tokio::task::spawn(async move {
+        process_accumulated_events(
+            &accumulated_database,
+            batch_rx,
+            reloadable_options,
+            accumulated_shutdown,
+        )
+        .await;
     });
 
     let (events_received_tx, events_received_rx) = watch::channel(());
     let batch_events_accumulator = events_accumulator.clone();
     let batch_events_received_rx = events_received_rx.clone();
-    tokio::task::spawn(async move {
+    let notify = tokio::task::spawn(async move {
         notify_received_events_for_processing(
             batch_tx,
             batch_events_accumulator,
@@ -356,7 +613,11 @@ pub async fn process_analytics(
         .await;
     });
     loop {
-        if let Some(event) = rx.recv().await {
+        let event = tokio::select! {
+            event = rx.recv() => event,
+            _ = shutdown.requested() => break,
+        };
+        if let Some(event) = event {
             let mut events_accumulator_guard = events_accumulator.lock().await;
             accumulate_event(&mut events_accumulator_guard, event);
             // Accumulate all events that may be present in the channel while we still hold the
@@ -373,6 +634,28 @@ pub async fn process_analytics(
             return;
         }
     }
+
+    tracing::info!("Writing accumulated analytics events before shutdown");
+    let mut events_accumulator_guard = events_accumulator.lock().await;
+    while let Ok(event) = rx.try_recv() {
+        accumulate_event(&mut events_accumulator_guard, event);
+    }
+    // Once the notifier has stopped, the batch sender is dropped and the batch processor can
+    // finish writing any batch that was already sent to it. Holding the accumulator lock ensures
+    // the notifier is not part way through sending a batch.
+    notify.abort();
+    let _ = notify.await;
+    let accumulator = std::mem::take(&mut *events_accumulator_guard);
+    drop(events_accumulator_guard);
+    if !accumulator.is_empty() {
+        process_analytics_events(accumulator, &database)
+            .await
+            .wrap_err("Error processing analytics events")
+            .unwrap_or_else(|error| tracing::error!("{error}"));
+    }
+    if let Err(error) = process_accumulated.await {
+        tracing::error!("Error waiting for analytics batch processor to finish: {error}");
+    }
 }
 
 /// Channel to use for transmiting analytics information from [`middleware()`] to
@@ -390,6 +673,29 @@ pub async fn middleware(state: State<AppState>, request: Request, next: Next) ->
         .get::<IsBot>()
         .expect("Expected extension IsBot to be available")
         .is_bot();
+    let visitor = match client_ip::client_ip(
+        request.headers(),
+        request.extensions(),
+        state.options.analytics.client_ip_header.as_deref(),
+    ) {
+        Some(ip) => {
+            let user_agent = request
+                .headers()
+                .get(header::USER_AGENT)
+                .and_then(|user_agent| user_agent.to_str().ok())
+                .unwrap_or_default();
+            Visitor::new(
+                &state.database,
+                OffsetDateTime::now_utc().date(),
+                &ip.to_string(),
+                user_agent,
+            )
+            .await
+            .inspect_err(|error| tracing::error!("Error identifying visitor: {error:?}"))
+            .ok()
+        }
+        None => None,
+    };
     let response = next.run(request).await;
     // Skip recording analytics if the request comes from a bot.
     if is_bot {
@@ -399,7 +705,7 @@ pub async fn middleware(state: State<AppState>, request: Request, next: Next) ->
         StatusCode::NOT_FOUND => "/404".parse().expect("unable to parse uri"),
         _ => uri,
     };
-    let event = Event { uri };
+    let event = Event { uri, visitor };
     state
         .analytics_sx
         .try_send(event)
@@ -421,7 +727,27 @@ mod test {
 
     use crate::types;
 
-    use super::{compact_operations, Analytics};
+    use super::{compact_operations, Analytics, Visitor};
+
+    #[test]
+    fn test_visitor() {
+        let day = time::macros::date!(2024 - 01 - 15);
+        let salt = [1; 32];
+        let visitor = Visitor::new_with_salt(day, &salt, "192.0.2.1", "Firefox");
+        assert_eq!(
+            visitor,
+            Visitor::new_with_salt(day, &salt, "192.0.2.1", "Firefox")
+        );
+        assert_ne!(
+            visitor,
+            Visitor::new_with_salt(day, &salt, "192.0.2.1", "Chrome")
+        );
+        assert_ne!(
+            visitor.id,
+            Visitor::new_with_salt(day, &[2; 32], "192.0.2.1", "Firefox").id
+        );
+        assert!(!visitor.id.contains("192.0.2.1"));
+    }
 
     #[test]
     fn test_compact_operations_empty() {
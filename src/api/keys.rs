@@ -0,0 +1,432 @@
+//! Keys for the public API, issued at `/admin/api-keys`. Only a SHA-256 hash of each key is
+//! stored, the key itself is only shown once when it is created. Requests provide a key using the
+//! `X-API-Key` header (or `Authorization: Bearer {key}`), which is required when
+//! [`crate::options::Api::require_key`] is enabled. Each key has its own rate limit, and the
+//! number of requests made using each key per day is shown on the analytics admin page (counted
+//! in memory and written to the database every minute).
+
+use std::{collections::HashMap, num::NonZeroU32, sync::Arc};
+
+use axum::{
+    extract::{Request, State},
+    middleware::Next,
+    response::Response,
+};
+use eyre::Context;
+use governor::{
+    clock::{Clock, DefaultClock},
+    DefaultDirectRateLimiter, Quota, RateLimiter,
+};
+use http::HeaderMap;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use tracing::Instrument;
+
+use crate::{database::Database, shutdown::Shutdown, state::AppState, types};
+
+use super::v1::ApiError;
+
+/// Prefix of every key, to make them easy to recognise (e.g. by secret scanners).
+pub const KEY_PREFIX: &str = "ar_";
+/// Number of characters at the start of a key which are stored in plain text to help identify
+/// which key is which.
+const DISPLAY_PREFIX_LEN: usize = KEY_PREFIX.len() + 8;
+/// Header used to provide the key.
+const API_KEY_HEADER: &str = "X-API-Key";
+/// Interval between writing the usage of the keys to the database, see [`UsageCounter`].
+const USAGE_FLUSH_INTERVAL: std::time::Duration = std::time::Duration::from_secs(60);
+
+#[derive(sqlx::Type, Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Hash)]
+#[serde(transparent)]
+#[sqlx(transparent)]
+pub struct ApiKeyId(String);
+
+impl ApiKeyId {
+    pub fn generate() -> Self {
+        Self(uuid::Uuid::new_v4().to_string())
+    }
+}
+
+impl std::fmt::Display for ApiKeyId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct ApiKey {
+    pub id: ApiKeyId,
+    /// Who or what the key was issued to.
+    pub name: String,
+    /// The start of the key, see [`DISPLAY_PREFIX_LEN`].
+    pub key_prefix: String,
+    pub requests_per_hour: i64,
+    pub created_at: types::Time,
+    pub last_used_at: Option<types::Time>,
+    /// Revoked keys can no longer be used, they are kept so that their usage remains visible.
+    pub revoked_at: Option<types::Time>,
+}
+
+/// Generate a new random key.
+pub fn generate_key() -> String {
+    format!(
+        "{KEY_PREFIX}{}{}",
+        uuid::Uuid::new_v4().simple(),
+        uuid::Uuid::new_v4().simple()
+    )
+}
+
+/// Keys are random with plenty of entropy, so unlike passwords a fast hash is sufficient, and
+/// allows keys to be looked up using their hash.
+pub fn hash_key(key: &str) -> String {
+    format!("{:x}", Sha256::digest(key.as_bytes()))
+}
+
+/// The part of the `key` which is stored in plain text, see [`DISPLAY_PREFIX_LEN`].
+pub fn key_prefix(key: &str) -> String {
+    key.chars().take(DISPLAY_PREFIX_LEN).collect()
+}
+
+/// Create a new key, returning the key, which is not stored.
+pub async fn create_api_key(
+    database: &Database,
+    name: &str,
+    requests_per_hour: NonZeroU32,
+) -> eyre::Result<(ApiKey, String)> {
+    let key = generate_key();
+    let key_hash = hash_key(&key);
+    let api_key = ApiKey {
+        id: ApiKeyId::generate(),
+        name: name.to_owned(),
+        key_prefix: key_prefix(&key),
+        requests_per_hour: requests_per_hour.get().into(),
+        created_at: types::Time::now_utc(),
+        last_used_at: None,
+        revoked_at: None,
+    };
+    sqlx::query!(
+        "INSERT INTO api_keys VALUES($1, $2, $3, $4, $5, $6, NULL, NULL)",
+        api_key.id,
+        api_key.name,
+        key_hash,
+        api_key.key_prefix,
+        api_key.requests_per_hour,
+        api_key.created_at,
+    )
+    .execute(database)
+    .await?;
+    Ok((api_key, key))
+}
+
+/// List all keys, most recently created first.
+pub async fn list_api_keys(database: &Database) -> eyre::Result<Vec<ApiKey>> {
+    Ok(sqlx::query_as!(
+        ApiKey,
+        r#"SELECT
+            id as "id: ApiKeyId",
+            name,
+            key_prefix,
+            requests_per_hour,
+            created_at as "created_at: types::Time",
+            last_used_at as "last_used_at: types::Time",
+            revoked_at as "revoked_at: types::Time"
+        FROM api_keys ORDER BY created_at DESC"#
+    )
+    .fetch_all(database)
+    .await?)
+}
+
+/// Get the key which has not been revoked matching the `key` provided in a request.
+async fn get_active_api_key(database: &Database, key: &str) -> eyre::Result<Option<ApiKey>> {
+    let key_hash = hash_key(key);
+    Ok(sqlx::query_as!(
+        ApiKey,
+        r#"SELECT
+            id as "id: ApiKeyId",
+            name,
+            key_prefix,
+            requests_per_hour,
+            created_at as "created_at: types::Time",
+            last_used_at as "last_used_at: types::Time",
+            revoked_at as "revoked_at: types::Time"
+        FROM api_keys WHERE key_hash=$1 AND revoked_at IS NULL"#,
+        key_hash
+    )
+    .fetch_optional(database)
+    .await?)
+}
+
+/// Revoke a key, returns `false` if there is no key with the `id` which has not been revoked.
+pub async fn revoke_api_key(database: &Database, id: &ApiKeyId) -> eyre::Result<bool> {
+    let now = types::Time::now_utc();
+    let result = sqlx::query!(
+        "UPDATE api_keys SET revoked_at=$1 WHERE id=$2 AND revoked_at IS NULL",
+        now,
+        id
+    )
+    .execute(database)
+    .await?;
+    Ok(result.rows_affected() > 0)
+}
+
+/// Requests made using a key on a day, which have not been written to the database yet.
+#[derive(Default)]
+struct PendingUsage {
+    requests: i64,
+    rate_limited: i64,
+    last_used_at: Option<types::Time>,
+}
+
+/// Usage of the keys counted in memory, and written to the database periodically by
+/// [`spawn_usage_flush_task`] rather than for every request. Cheap to clone.
+#[derive(Clone, Default)]
+pub struct UsageCounter(Arc<std::sync::Mutex<HashMap<(ApiKeyId, String), PendingUsage>>>);
+
+impl UsageCounter {
+    /// Count a request made using the key in the usage for the current day.
+    fn record(&self, id: &ApiKeyId, rate_limited: bool) {
+        let now = types::Time::now_utc();
+        let day = now.date().to_string();
+        let mut pending = self.0.lock().expect("API key usage lock poisoned");
+        let usage = pending.entry((id.clone(), day)).or_default();
+        if rate_limited {
+            usage.rate_limited += 1;
+        } else {
+            usage.requests += 1;
+        }
+        usage.last_used_at = Some(now);
+    }
+
+    /// Write the pending usage to the database in a single transaction.
+    async fn flush(&self, database: &Database) -> eyre::Result<()> {
+        let pending = std::mem::take(&mut *self.0.lock().expect("API key usage lock poisoned"));
+        if pending.is_empty() {
+            return Ok(());
+        }
+        let mut transaction = database.begin().await?;
+        for ((id, day), usage) in pending {
+            sqlx::query!(
+                "INSERT INTO api_key_usage VALUES($1, $2, $3, $4)
+                ON CONFLICT(api_key_id, day) DO UPDATE SET
+                    requests=requests + excluded.requests,
+                    rate_limited=rate_limited + excluded.rate_limited",
+                id,
+                day,
+                usage.requests,
+                usage.rate_limited,
+            )
+            .execute(&mut *transaction)
+            .await?;
+            sqlx::query!(
+                "UPDATE api_keys SET last_used_at=$1 WHERE id=$2",
+                usage.last_used_at,
+                id
+            )
+            .execute(&mut *transaction)
+            .await?;
+        }
+        transaction.commit().await?;
+        Ok(())
+    }
+}
+
+/// Spawn the task writing the usage counted by `usage` to the database every
+/// [`USAGE_FLUSH_INTERVAL`], and once more when `shutdown` is requested.
+pub fn spawn_usage_flush_task(usage: UsageCounter, database: Database, mut shutdown: Shutdown) {
+    tokio::spawn(
+        async move {
+            let mut interval = tokio::time::interval(USAGE_FLUSH_INTERVAL);
+            interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+            loop {
+                let shutting_down = tokio::select! {
+                    _ = interval.tick() => false,
+                    _ = shutdown.requested() => true,
+                };
+                if let Err(error) = usage
+                    .flush(&database)
+                    .await
+                    .wrap_err("Error recording API key usage")
+                {
+                    tracing::error!("{error:?}");
+                }
+                if shutting_down {
+                    return;
+                }
+            }
+        }
+        .instrument(tracing::error_span!("api_key_usage")),
+    );
+}
+
+/// Usage of a key over a period, shown on the analytics admin page.
+#[derive(Serialize, Debug)]
+pub struct ApiKeyUsage {
+    pub name: String,
+    pub key_prefix: String,
+    pub revoked: bool,
+    /// Number of requests which were accepted.
+    pub requests: i64,
+    /// Number of requests which were rejected by the rate limit.
+    pub rate_limited: i64,
+}
+
+/// Usage of the keys on the days between `from` and `to` (inclusive), most used first.
+pub async fn usage(
+    database: &Database,
+    from: Option<types::Time>,
+    to: Option<types::Time>,
+) -> eyre::Result<Vec<ApiKeyUsage>> {
+    let from = from.map(|from| from.date().to_string());
+    let to = to.map(|to| to.date().to_string());
+    Ok(sqlx::query_as!(
+        ApiKeyUsage,
+        r#"SELECT
+            api_keys.name,
+            api_keys.key_prefix,
+            api_keys.revoked_at IS NOT NULL as "revoked!: bool",
+            SUM(api_key_usage.requests) as "requests!: i64",
+            SUM(api_key_usage.rate_limited) as "rate_limited!: i64"
+        FROM api_key_usage
+        JOIN api_keys ON api_keys.id = api_key_usage.api_key_id
+        WHERE ($1 IS NULL OR api_key_usage.day >= $1) AND ($2 IS NULL OR api_key_usage.day <= $2)
+        GROUP BY api_keys.id
+        ORDER BY 4 DESC"#,
+        from,
+        to,
+    )
+    .fetch_all(database)
+    .await?)
+}
+
+/// A rate limiter for each key, created when the key is first used.
+#[derive(Clone, Default)]
+pub struct RateLimiters(Arc<std::sync::Mutex<HashMap<ApiKeyId, KeyRateLimiter>>>);
+
+struct KeyRateLimiter {
+    requests_per_hour: NonZeroU32,
+    limiter: DefaultDirectRateLimiter,
+}
+
+impl RateLimiters {
+    /// Check whether the key may make another request, returning how long to wait if it has
+    /// exceeded its rate limit.
+    fn check(
+        &self,
+        id: &ApiKeyId,
+        requests_per_hour: NonZeroU32,
+    ) -> Result<(), std::time::Duration> {
+        let mut limiters = self.0.lock().expect("Rate limiters lock poisoned");
+        let limiter = limiters
+            .entry(id.clone())
+            .or_insert_with(|| KeyRateLimiter {
+                requests_per_hour,
+                limiter: RateLimiter::direct(Quota::per_hour(requests_per_hour)),
+            });
+        if limiter.requests_per_hour != requests_per_hour {
+            *limiter = KeyRateLimiter {
+                requests_per_hour,
+                limiter: RateLimiter::direct(Quota::per_hour(requests_per_hour)),
+            };
+        }
+        limiter
+            .limiter
+            .check()
+            .map_err(|not_until| not_until.wait_time_from(DefaultClock::default().now()))
+    }
+}
+
+/// The key provided in the request headers, if any.
+fn request_key(headers: &HeaderMap) -> Option<&str> {
+    if let Some(key) = headers.get(API_KEY_HEADER) {
+        return key.to_str().ok();
+    }
+    headers
+        .get(http::header::AUTHORIZATION)?
+        .to_str()
+        .ok()?
+        .strip_prefix("Bearer ")
+}
+
+/// Check the key provided in the request (if any), enforcing its rate limit and recording its
+/// usage.
+pub async fn middleware(
+    State(state): State<AppState>,
+    request: Request,
+    next: Next,
+) -> Result<Response, ApiError> {
+    let Some(key) = request_key(request.headers()).map(|key| key.trim().to_owned()) else {
+        if state.options.api.require_key {
+            return Err(ApiError::Unauthorized(format!(
+                "An API key is required, provide it using the {API_KEY_HEADER} header"
+            )));
+        }
+        return Ok(next.run(request).await);
+    };
+    let api_key = get_active_api_key(&state.database, &key)
+        .await?
+        .ok_or_else(|| ApiError::Unauthorized("Invalid API key".to_owned()))?;
+    let requests_per_hour = u32::try_from(api_key.requests_per_hour)
+        .ok()
+        .and_then(NonZeroU32::new)
+        .ok_or_else(|| {
+            eyre::eyre!(
+                "Invalid requests_per_hour {} for API key {}",
+                api_key.requests_per_hour,
+                api_key.id
+            )
+        })?;
+    let result = state
+        .api_key_rate_limiters
+        .check(&api_key.id, requests_per_hour);
+    state.api_key_usage.record(&api_key.id, result.is_err());
+    result.map_err(ApiError::TooManyRequests)?;
+    Ok(next.run(request).await)
+}
+
+#[cfg(test)]
+mod test {
+    use http::HeaderMap;
+
+    use super::{
+        generate_key, hash_key, key_prefix, request_key, ApiKeyId, UsageCounter, KEY_PREFIX,
+    };
+
+    #[test]
+    fn test_generate_key() {
+        let key = generate_key();
+        assert!(key.starts_with(KEY_PREFIX));
+        assert_eq!(key.len(), KEY_PREFIX.len() + 64);
+        assert_ne!(key, generate_key());
+        assert_eq!(key_prefix(&key).len(), KEY_PREFIX.len() + 8);
+        assert_eq!(hash_key(&key), hash_key(&key));
+        assert_eq!(hash_key(&key).len(), 64);
+    }
+
+    #[test]
+    fn test_request_key() {
+        let mut headers = HeaderMap::new();
+        assert_eq!(request_key(&headers), None);
+        headers.insert("Authorization", "Bearer ar_abc".parse().unwrap());
+        assert_eq!(request_key(&headers), Some("ar_abc"));
+        headers.insert("X-API-Key", "ar_def".parse().unwrap());
+        assert_eq!(request_key(&headers), Some("ar_def"));
+    }
+
+    #[test]
+    fn test_usage_counter() {
+        let usage = UsageCounter::default();
+        let id = ApiKeyId::generate();
+        usage.record(&id, false);
+        usage.record(&id, false);
+        usage.record(&id, true);
+        usage.record(&ApiKeyId::generate(), false);
+        let pending = usage.0.lock().unwrap();
+        assert_eq!(pending.len(), 2);
+        let (_, key_usage) = pending
+            .iter()
+            .find(|((key_id, _), _)| *key_id == id)
+            .unwrap();
+        assert_eq!((key_usage.requests, key_usage.rate_limited), (2, 1));
+        assert!(key_usage.last_used_at.is_some());
+    }
+}
@@ -0,0 +1,17 @@
+//! The public JSON API.
+//!
+//! Unlike the `/json` and `/forecasts/{file}.json` endpoints (which serialize the internal
+//! types directly and may change at any time), the types exposed in this module are an
+//! intentional, versioned contract for third party consumers. Breaking changes require a new
+//! version module. Requests may be made using a key, see [`keys`].
+
+use axum::Router;
+
+use crate::{options::Features, state::AppState};
+
+pub mod keys;
+pub mod v1;
+
+pub fn router(features: Features) -> Router<AppState> {
+    Router::new().nest("/v1", v1::router(features))
+}
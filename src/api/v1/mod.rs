@@ -0,0 +1,470 @@
+//! Version 1 of the public JSON API.
+
+use axum::{
+    extract::{Path, Query, State},
+    response::{IntoResponse, Response},
+    routing::get,
+    Extension, Json, Router,
+};
+use axum_extra::routing::TypedPath;
+use http::{HeaderMap, StatusCode};
+use serde::Deserialize;
+use time_tz::TimeZone;
+use unic_langid::LanguageIdentifier;
+use utoipa::{IntoParams, OpenApi};
+
+use crate::{
+    database::Database,
+    forecast_areas,
+    forecasts::{
+        get_forecast_data, parse_forecast_name, ForecastData, ForecastsFilePath,
+        RequestedForecastData,
+    },
+    google_drive, i18n,
+    observations::activity::load_activity,
+    options::{Features, WeatherStationId},
+    state::AppState,
+};
+
+pub mod types;
+
+#[derive(OpenApi)]
+#[openapi(
+    info(
+        title = "avalanche-report",
+        description = "Public API for avalanche forecasts and weather station data."
+    ),
+    paths(
+        list_forecasts,
+        get_forecast,
+        list_areas,
+        list_languages,
+        list_next_publications,
+        get_weather_station,
+        list_avalanche_activity,
+        eaws_matrix
+    )
+)]
+pub struct ApiDoc;
+
+pub fn router(features: Features) -> Router<AppState> {
+    Router::new()
+        .route("/openapi.json", get(openapi_handler))
+        .route("/forecasts", get(list_forecasts))
+        .route("/forecasts/{id}", get(get_forecast))
+        .route("/areas", get(list_areas))
+        .route("/languages", get(list_languages))
+        .route("/next-publications", get(list_next_publications))
+        .merge(crate::feature(
+            features.weather,
+            Router::new().route("/weather-stations/{id}", get(get_weather_station)),
+        ))
+        .route("/avalanche-activity", get(list_avalanche_activity))
+        .route("/tools/eaws-matrix", get(eaws_matrix))
+}
+
+/// An error produced by an API handler, rendered as a JSON [`types::Error`].
+pub enum ApiError {
+    BadRequest(String),
+    NotFound(String),
+    /// A required API key is missing, or the key is invalid, see [`crate::api::keys`].
+    Unauthorized(String),
+    /// The API key has exceeded its rate limit, the request can be retried after this duration.
+    TooManyRequests(std::time::Duration),
+    Internal(eyre::Error),
+}
+
+impl From<eyre::Error> for ApiError {
+    fn from(error: eyre::Error) -> Self {
+        Self::Internal(error)
+    }
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        let (status, message) = match self {
+            ApiError::BadRequest(message) => (StatusCode::BAD_REQUEST, message),
+            ApiError::NotFound(message) => (StatusCode::NOT_FOUND, message),
+            ApiError::Unauthorized(message) => (StatusCode::UNAUTHORIZED, message),
+            ApiError::TooManyRequests(retry_after) => {
+                // Round up so that the request isn't retried too early.
+                let seconds = retry_after.as_secs() + u64::from(retry_after.subsec_nanos() > 0);
+                let message = format!("Rate limit exceeded, retry after {seconds} seconds");
+                return (
+                    StatusCode::TOO_MANY_REQUESTS,
+                    [(http::header::RETRY_AFTER, seconds.to_string())],
+                    Json(types::Error { message }),
+                )
+                    .into_response();
+            }
+            ApiError::Internal(error) => {
+                // Only log the details, they may contain internal information.
+                tracing::error!("{error:?}");
+                (
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    "Internal server error".to_owned(),
+                )
+            }
+        };
+        (status, Json(types::Error { message })).into_response()
+    }
+}
+
+type ApiResult<T> = Result<Json<T>, ApiError>;
+
+async fn openapi_handler() -> Json<utoipa::openapi::OpenApi> {
+    Json(ApiDoc::openapi())
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct ListForecastsQuery {
+    /// Only list forecasts for this area.
+    area: Option<String>,
+}
+
+/// List all published forecasts, most recent first.
+#[utoipa::path(
+    get,
+    path = "/api/v1/forecasts",
+    params(ListForecastsQuery),
+    responses(
+        (status = 200, body = Vec<types::ForecastSummary>),
+        (status = 500, body = types::Error),
+    )
+)]
+pub async fn list_forecasts(
+    Query(query): Query<ListForecastsQuery>,
+    State(state): State<AppState>,
+) -> ApiResult<Vec<types::ForecastSummary>> {
+    let file_list = state.published_files.list_files().await?;
+
+    let mut forecasts: Vec<types::ForecastSummary> = file_list
+        .into_iter()
+        .filter_map(|file| {
+            let details = match parse_forecast_name(&file.name, state.forecast_spreadsheet_schema) {
+                Ok(details) => details,
+                Err(error) => {
+                    tracing::warn!("Skipping file {:?} in API listing: {error}", file.name);
+                    return None;
+                }
+            };
+            if let Some(area) = &query.area {
+                if &details.forecast.area != area {
+                    return None;
+                }
+            }
+            let html_path = ForecastsFilePath {
+                file_name: file.name.clone(),
+            }
+            .to_uri()
+            .to_string();
+            Some(types::ForecastSummary {
+                has_data: file.has_forecast_data(),
+                id: file.name,
+                area: details.forecast.area,
+                time: details.forecast.time,
+                forecaster: details.forecast.forecaster,
+                language: details.language.map(|language| language.to_string()),
+                html_path,
+            })
+        })
+        .collect();
+    forecasts.sort_by_key(|forecast| std::cmp::Reverse(forecast.time));
+
+    Ok(Json(forecasts))
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct GetForecastQuery {
+    /// Only include the text of the forecast in this language (e.g. `ka-GE`), falling back to
+    /// the languages in the `Accept-Language` header, and then the site's default language. Use
+    /// `auto` to negotiate the language using only the `Accept-Language` header. By default the
+    /// text is included in all the available languages.
+    lang: Option<String>,
+}
+
+/// Get the structured data for a published forecast.
+#[utoipa::path(
+    get,
+    path = "/api/v1/forecasts/{id}",
+    params(
+        ("id" = String, Path, description = "Forecast identifier from the forecast listing"),
+        GetForecastQuery,
+    ),
+    responses(
+        (status = 200, body = types::Forecast),
+        (status = 404, body = types::Error),
+        (status = 500, body = types::Error),
+    )
+)]
+pub async fn get_forecast(
+    Path(id): Path<String>,
+    Query(query): Query<GetForecastQuery>,
+    State(state): State<AppState>,
+    Extension(database): Extension<Database>,
+    headers: HeaderMap,
+) -> ApiResult<types::Forecast> {
+    let requested_languages = match query.lang.as_deref() {
+        None => None,
+        Some(lang) => {
+            let mut requested_languages = Vec::new();
+            if lang != "auto" {
+                requested_languages.push(lang.parse::<LanguageIdentifier>().map_err(|error| {
+                    ApiError::BadRequest(format!("Invalid language {lang:?}: {error}"))
+                })?);
+            }
+            if let Some(accept_language) = headers.get(http::header::ACCEPT_LANGUAGE) {
+                requested_languages.extend(i18n::parse_accept_language(accept_language).0);
+            }
+            Some(requested_languages)
+        }
+    };
+
+    // Only files within the published folder may be accessed.
+    let file_list = state.published_files.list_files().await?;
+    let file_metadata = google_drive::get_file_in_list(&id, &file_list)
+        .ok_or_else(|| ApiError::NotFound(format!("No forecast found with id {id:?}")))?;
+    if !file_metadata.has_forecast_data() {
+        return Err(ApiError::NotFound(format!(
+            "Forecast {id:?} is not available as structured data"
+        )));
+    }
+
+    match get_forecast_data(
+        file_metadata,
+        RequestedForecastData::Forecast,
+        &state.client,
+        &database,
+        &state.blobs,
+        &state.options.google_drive,
+        state.forecast_spreadsheet_schemas,
+    )
+    .await?
+    {
+        ForecastData::Forecast(forecast) => {
+            let default_language = state
+                .reloadable_options
+                .load()
+                .default_language_order
+                .first()
+                .cloned()
+                .unwrap_or_default();
+            let localization =
+                requested_languages
+                    .as_deref()
+                    .map(|requested_languages| types::Localization {
+                        requested_languages,
+                        default_language: &default_language,
+                    });
+            Ok(Json(types::Forecast::new_localized(
+                id,
+                forecast,
+                localization.as_ref(),
+            )))
+        }
+        ForecastData::File(_) => Err(eyre::eyre!("Expected ForecastData::Forecast").into()),
+    }
+}
+
+/// List the forecast areas.
+#[utoipa::path(
+    get,
+    path = "/api/v1/areas",
+    responses(
+        (status = 200, body = Vec<types::Area>),
+        (status = 500, body = types::Error),
+    )
+)]
+pub async fn list_areas(
+    State(state): State<AppState>,
+    Extension(database): Extension<Database>,
+) -> ApiResult<Vec<types::Area>> {
+    let with_geojson: Vec<String> = forecast_areas::list_forecast_areas(&database)
+        .await?
+        .into_iter()
+        .map(|id| id.to_string())
+        .collect();
+    let areas = state
+        .forecast_spreadsheet_schema
+        .area_definitions
+        .iter()
+        .map(|(id, definition)| {
+            let id = id.to_string();
+            types::Area {
+                geojson_path: with_geojson
+                    .contains(&id)
+                    .then(|| format!("/forecast-areas/{id}/area.geojson")),
+                time_zone: definition.time_zone.name().to_owned(),
+                id,
+            }
+        })
+        .collect();
+    Ok(Json(areas))
+}
+
+/// List the languages which users can select, in the default order.
+#[utoipa::path(
+    get,
+    path = "/api/v1/languages",
+    responses(
+        (status = 200, body = Vec<types::Language>),
+    )
+)]
+pub async fn list_languages(State(state): State<AppState>) -> ApiResult<Vec<types::Language>> {
+    let languages = i18n::ordered_languages(
+        &state.options.languages,
+        &state.reloadable_options.load().default_language_order,
+        state.options.enabled_languages.as_deref(),
+    )
+    .into_iter()
+    .map(|language| types::Language {
+        id: language.id.to_string(),
+        native_name: language.native_name,
+        icon: language.icon,
+        direction: language.direction.into(),
+    })
+    .collect();
+    Ok(Json(languages))
+}
+
+/// List when the next forecast is expected to be published for each area that has a publication
+/// schedule.
+#[utoipa::path(
+    get,
+    path = "/api/v1/next-publications",
+    responses(
+        (status = 200, body = Vec<types::NextPublication>),
+    )
+)]
+pub async fn list_next_publications(
+    State(state): State<AppState>,
+) -> ApiResult<Vec<types::NextPublication>> {
+    let mut publications: Vec<types::NextPublication> = state
+        .options
+        .forecast_publication
+        .schedules
+        .iter()
+        .map(|(area, schedule)| types::NextPublication {
+            area: area.clone(),
+            time: schedule.schedule.next_time_from_now(),
+        })
+        .collect();
+    publications.sort_by(|a, b| a.area.cmp(&b.area));
+    Ok(Json(publications))
+}
+
+/// Get recent observations for a weather station.
+#[utoipa::path(
+    get,
+    path = "/api/v1/weather-stations/{id}",
+    params(("id" = String, Path, description = "Weather station identifier")),
+    responses(
+        (status = 200, body = types::WeatherStation),
+        (status = 404, body = types::Error),
+        (status = 500, body = types::Error),
+    )
+)]
+pub async fn get_weather_station(
+    Path(id): Path<WeatherStationId>,
+    State(state): State<AppState>,
+) -> ApiResult<types::WeatherStation> {
+    if !state
+        .current_weather
+        .available_weather_stations()
+        .contains(&id)
+    {
+        return Err(ApiError::NotFound(format!(
+            "No weather station found with id {id}"
+        )));
+    }
+    let observations = state
+        .current_weather
+        .current_weather(&id)
+        .await?
+        .into_iter()
+        .map(Into::into)
+        .collect();
+    Ok(Json(types::WeatherStation {
+        id: id.to_string(),
+        observations,
+    }))
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct AvalancheActivityQuery {
+    /// Only include the season which starts in this year.
+    season: Option<i32>,
+}
+
+/// Summaries of the avalanches reported in observations for each season, most recent season
+/// first. Only seasons with at least one reported avalanche are included.
+#[utoipa::path(
+    get,
+    path = "/api/v1/avalanche-activity",
+    params(AvalancheActivityQuery),
+    responses(
+        (status = 200, body = Vec<types::AvalancheActivity>),
+        (status = 404, body = types::Error),
+        (status = 500, body = types::Error),
+    )
+)]
+pub async fn list_avalanche_activity(
+    Query(query): Query<AvalancheActivityQuery>,
+    State(state): State<AppState>,
+    Extension(database): Extension<Database>,
+) -> ApiResult<Vec<types::AvalancheActivity>> {
+    if !state.options.features.observations {
+        return Err(ApiError::NotFound(
+            "Observations are not enabled".to_owned(),
+        ));
+    }
+    let activity = load_activity(&database)
+        .await?
+        .into_iter()
+        .filter(|activity| {
+            query
+                .season
+                .is_none_or(|season| activity.season.year() == season)
+        })
+        .map(Into::into)
+        .collect();
+    Ok(Json(activity))
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct EawsMatrixQuery {
+    stability: types::Stability,
+    frequency: types::Frequency,
+    /// Avalanche size (1-5).
+    size: u8,
+}
+
+/// Compute the danger level suggested by the
+/// [EAWS matrix](https://www.avalanches.org/standards/eaws-matrix/), for training purposes.
+#[utoipa::path(
+    get,
+    path = "/api/v1/tools/eaws-matrix",
+    params(EawsMatrixQuery),
+    responses(
+        (status = 200, body = types::EawsMatrixResult),
+        (status = 400, body = types::Error),
+    )
+)]
+pub async fn eaws_matrix(
+    Query(query): Query<EawsMatrixQuery>,
+) -> ApiResult<types::EawsMatrixResult> {
+    let size = forecast_spreadsheet::Size::try_from(query.size)
+        .map_err(|error| ApiError::BadRequest(error.to_string()))?;
+    let level = forecast_spreadsheet::eaws_matrix::danger_level(
+        query.stability.into(),
+        query.frequency.into(),
+        size,
+    );
+    Ok(Json(types::EawsMatrixResult {
+        level: level.into(),
+    }))
+}
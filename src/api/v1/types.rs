@@ -0,0 +1,655 @@
+//! Data transfer types for version 1 of the public API.
+//!
+//! These are deliberately decoupled from the internal types in [`forecast_spreadsheet`] and
+//! [`crate::forecasts`], conversions are performed explicitly with [`From`] implementations so
+//! that changes to the internal types show up as compile errors here rather than silently
+//! changing the API.
+
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+use time::OffsetDateTime;
+use unic_langid::LanguageIdentifier;
+use utoipa::ToSchema;
+
+use crate::{current_weather::WeatherDataItem, observations::activity::SeasonActivity};
+
+/// Free text which has been translated into multiple languages, keyed by language identifier
+/// (e.g. `en-UK`).
+pub type Translations = BTreeMap<String, String>;
+
+fn translations(value: std::collections::HashMap<LanguageIdentifier, String>) -> Translations {
+    value
+        .into_iter()
+        .map(|(language, text)| (language.to_string(), text))
+        .collect()
+}
+
+/// Free text in a forecast. Either all the available translations keyed by language identifier
+/// (e.g. `en-UK`), or when a language was requested using the `lang` query parameter, only the
+/// text in the negotiated language (`null` if it isn't available in that language).
+#[derive(Debug, Serialize, ToSchema)]
+#[serde(untagged)]
+pub enum Text {
+    Translations(BTreeMap<String, String>),
+    Localized(Option<String>),
+}
+
+/// The languages to select the forecast text in, see [`Text::Localized`].
+pub struct Localization<'a> {
+    /// The requested languages, in order of preference.
+    pub requested_languages: &'a [LanguageIdentifier],
+    /// Used when none of the `requested_languages` are available.
+    pub default_language: &'a LanguageIdentifier,
+}
+
+impl Text {
+    fn new(
+        value: std::collections::HashMap<LanguageIdentifier, String>,
+        localization: Option<&Localization>,
+    ) -> Self {
+        match localization {
+            Some(localization) => Self::Localized(
+                crate::i18n::negotiate_translated_string(
+                    localization.requested_languages,
+                    localization.default_language,
+                    &value,
+                )
+                .map(|(_, text)| text.to_owned()),
+            ),
+            None => Self::Translations(translations(value)),
+        }
+    }
+}
+
+/// An error returned by the API.
+#[derive(Debug, Serialize, ToSchema)]
+pub struct Error {
+    /// Human readable description of the error.
+    pub message: String,
+}
+
+/// A summary of a published forecast, as returned when listing forecasts.
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ForecastSummary {
+    /// Identifier of the forecast, used with `/api/v1/forecasts/{id}`.
+    pub id: String,
+    /// Name of the area that the forecast is for.
+    pub area: String,
+    /// The time that the forecast was published.
+    #[serde(with = "time::serde::rfc3339")]
+    pub time: OffsetDateTime,
+    /// Initials of the forecaster.
+    pub forecaster: String,
+    /// The language of this forecast file, if it is language specific.
+    pub language: Option<String>,
+    /// Whether structured forecast data is available via `/api/v1/forecasts/{id}`. Forecasts
+    /// published only as a document (e.g. PDF) do not have structured data.
+    pub has_data: bool,
+    /// Path to the human readable version of this forecast.
+    pub html_path: String,
+}
+
+/// A published avalanche forecast.
+#[derive(Debug, Serialize, ToSchema)]
+pub struct Forecast {
+    /// Identifier of the forecast.
+    pub id: String,
+    /// Identifier of the area that the forecast is for.
+    pub area: String,
+    pub forecaster: Forecaster,
+    /// The time that the forecast was published.
+    #[serde(with = "time::serde::rfc3339")]
+    pub time: OffsetDateTime,
+    /// The time until which this forecast is considered valid.
+    #[serde(with = "time::serde::rfc3339")]
+    pub valid_until: OffsetDateTime,
+    /// Whether the forecast is valid at the time of the request.
+    pub is_current: bool,
+    pub description: Text,
+    pub recent_observations: Text,
+    pub forecast_changes: Text,
+    pub weather_forecast: Text,
+    /// Elevation bands referenced by hazard ratings and avalanche problems, ordered from
+    /// highest to lowest.
+    pub elevation_bands: Vec<ElevationBand>,
+    pub hazard_ratings: Vec<HazardRating>,
+    pub avalanche_problems: Vec<AvalancheProblem>,
+}
+
+impl Forecast {
+    pub fn new(id: String, value: forecast_spreadsheet::Forecast) -> Self {
+        Self::new_localized(id, value, None)
+    }
+
+    /// Like [`Forecast::new`], but with the text only in the language selected by the
+    /// `localization` (if specified).
+    pub fn new_localized(
+        id: String,
+        value: forecast_spreadsheet::Forecast,
+        localization: Option<&Localization>,
+    ) -> Self {
+        let valid_until = value.time + value.valid_for;
+        Self {
+            id,
+            area: value.area.to_string(),
+            forecaster: value.forecaster.into(),
+            time: value.time,
+            valid_until,
+            is_current: OffsetDateTime::now_utc() <= valid_until,
+            description: Text::new(value.description, localization),
+            recent_observations: Text::new(value.recent_observations, localization),
+            forecast_changes: Text::new(value.forecast_changes, localization),
+            weather_forecast: Text::new(value.weather_forecast, localization),
+            elevation_bands: value
+                .elevation_bands
+                .into_iter()
+                .map(|(id, range)| ElevationBand {
+                    id: id.to_string(),
+                    lower: range.lower,
+                    upper: range.upper,
+                })
+                .collect(),
+            hazard_ratings: value
+                .hazard_ratings
+                .into_iter()
+                .map(|(kind, rating)| HazardRating {
+                    elevation_band: match kind {
+                        forecast_spreadsheet::HazardRatingKind::Overall => None,
+                        forecast_spreadsheet::HazardRatingKind::ElevationSpecific(band) => {
+                            Some(band.to_string())
+                        }
+                    },
+                    level: rating.value.map(Into::into),
+                    trend: rating.trend.map(Into::into),
+                    confidence: rating.confidence.map(Into::into),
+                })
+                .collect(),
+            avalanche_problems: value
+                .avalanche_problems
+                .into_iter()
+                .map(|problem| AvalancheProblem::new(problem, localization))
+                .collect(),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct Forecaster {
+    pub name: String,
+    pub organisation: Option<String>,
+}
+
+impl From<forecast_spreadsheet::Forecaster> for Forecaster {
+    fn from(value: forecast_spreadsheet::Forecaster) -> Self {
+        Self {
+            name: value.name,
+            organisation: value.organisation,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ElevationBand {
+    pub id: String,
+    /// Lower bound of the band in meters, `null` if unbounded.
+    pub lower: Option<i64>,
+    /// Upper bound of the band in meters, `null` if unbounded.
+    pub upper: Option<i64>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct HazardRating {
+    /// The elevation band that this rating applies to, or `null` for the overall rating.
+    pub elevation_band: Option<String>,
+    pub level: Option<HazardLevel>,
+    pub trend: Option<Trend>,
+    pub confidence: Option<Confidence>,
+}
+
+/// Level on the European avalanche danger scale.
+#[derive(Debug, Serialize, ToSchema, Clone, Copy)]
+#[serde(rename_all = "kebab-case")]
+pub enum HazardLevel {
+    NoRating,
+    Low,
+    Moderate,
+    Considerable,
+    High,
+    Extreme,
+}
+
+impl From<forecast_spreadsheet::HazardRatingValue> for HazardLevel {
+    fn from(value: forecast_spreadsheet::HazardRatingValue) -> Self {
+        use forecast_spreadsheet::HazardRatingValue;
+        match value {
+            HazardRatingValue::NoRating => Self::NoRating,
+            HazardRatingValue::Low => Self::Low,
+            HazardRatingValue::Moderate => Self::Moderate,
+            HazardRatingValue::Considerable => Self::Considerable,
+            HazardRatingValue::High => Self::High,
+            HazardRatingValue::Extreme => Self::Extreme,
+        }
+    }
+}
+
+/// Snowpack stability class used by the EAWS matrix.
+#[derive(Debug, Deserialize, ToSchema, Clone, Copy)]
+#[serde(rename_all = "kebab-case")]
+pub enum Stability {
+    VeryPoor,
+    Poor,
+    Fair,
+    Good,
+}
+
+impl From<Stability> for forecast_spreadsheet::eaws_matrix::Stability {
+    fn from(value: Stability) -> Self {
+        match value {
+            Stability::VeryPoor => Self::VeryPoor,
+            Stability::Poor => Self::Poor,
+            Stability::Fair => Self::Fair,
+            Stability::Good => Self::Good,
+        }
+    }
+}
+
+/// How frequently the snowpack stability class occurs, used by the EAWS matrix.
+#[derive(Debug, Deserialize, ToSchema, Clone, Copy)]
+#[serde(rename_all = "kebab-case")]
+pub enum Frequency {
+    Many,
+    Some,
+    AFew,
+    NearlyNone,
+}
+
+impl From<Frequency> for forecast_spreadsheet::eaws_matrix::Frequency {
+    fn from(value: Frequency) -> Self {
+        match value {
+            Frequency::Many => Self::Many,
+            Frequency::Some => Self::Some,
+            Frequency::AFew => Self::AFew,
+            Frequency::NearlyNone => Self::NearlyNone,
+        }
+    }
+}
+
+/// The danger level suggested by the EAWS matrix.
+#[derive(Debug, Serialize, ToSchema)]
+pub struct EawsMatrixResult {
+    pub level: HazardLevel,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct AvalancheProblem {
+    pub kind: ProblemKind,
+    /// The aspects where this problem is present, for each elevation band.
+    pub locations: Vec<ProblemLocation>,
+    pub confidence: Option<Confidence>,
+    pub trend: Option<Trend>,
+    /// Expected avalanche size (1-5).
+    pub size: Option<u8>,
+    pub distribution: Option<Distribution>,
+    pub time_of_day: Option<TimeOfDay>,
+    pub sensitivity: Option<Sensitivity>,
+    pub description: Text,
+    /// Whether this is the primary (dominant) problem of the forecast. The primary problem is
+    /// always listed first.
+    pub is_primary: bool,
+}
+
+impl AvalancheProblem {
+    fn new(
+        value: forecast_spreadsheet::AvalancheProblem,
+        localization: Option<&Localization>,
+    ) -> Self {
+        Self {
+            kind: value.kind.into(),
+            locations: value
+                .aspect_elevation
+                .into_iter()
+                .map(|(band, aspect_elevation)| ProblemLocation {
+                    elevation_band: band.to_string(),
+                    aspects: aspect_elevation
+                        .aspects
+                        .into_iter()
+                        .map(Into::into)
+                        .collect(),
+                })
+                .collect(),
+            confidence: value.confidence.map(Into::into),
+            trend: value.trend.map(Into::into),
+            size: value.size.map(|size| size as u8),
+            distribution: value.distribution.map(Into::into),
+            time_of_day: value.time_of_day.map(Into::into),
+            sensitivity: value.sensitivity.map(Into::into),
+            description: Text::new(value.description, localization),
+            is_primary: value.is_primary,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ProblemLocation {
+    pub elevation_band: String,
+    pub aspects: Vec<Aspect>,
+}
+
+#[derive(Debug, Serialize, ToSchema, Clone, Copy)]
+#[serde(rename_all = "kebab-case")]
+pub enum ProblemKind {
+    LooseDry,
+    LooseWet,
+    StormSlab,
+    WindSlab,
+    WetSlab,
+    PersistentSlab,
+    DeepSlab,
+    Cornice,
+    Glide,
+}
+
+impl From<forecast_spreadsheet::ProblemKind> for ProblemKind {
+    fn from(value: forecast_spreadsheet::ProblemKind) -> Self {
+        use forecast_spreadsheet::ProblemKind;
+        match value {
+            ProblemKind::LooseDry => Self::LooseDry,
+            ProblemKind::LooseWet => Self::LooseWet,
+            ProblemKind::StormSlab => Self::StormSlab,
+            ProblemKind::WindSlab => Self::WindSlab,
+            ProblemKind::WetSlab => Self::WetSlab,
+            ProblemKind::PersistentSlab => Self::PersistentSlab,
+            ProblemKind::DeepSlab => Self::DeepSlab,
+            ProblemKind::Cornice => Self::Cornice,
+            ProblemKind::Glide => Self::Glide,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, ToSchema, Clone, Copy)]
+pub enum Aspect {
+    N,
+    NE,
+    E,
+    SE,
+    S,
+    SW,
+    W,
+    NW,
+}
+
+impl From<forecast_spreadsheet::Aspect> for Aspect {
+    fn from(value: forecast_spreadsheet::Aspect) -> Self {
+        use forecast_spreadsheet::Aspect;
+        match value {
+            Aspect::N => Self::N,
+            Aspect::NE => Self::NE,
+            Aspect::E => Self::E,
+            Aspect::SE => Self::SE,
+            Aspect::S => Self::S,
+            Aspect::SW => Self::SW,
+            Aspect::W => Self::W,
+            Aspect::NW => Self::NW,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, ToSchema, Clone, Copy)]
+#[serde(rename_all = "kebab-case")]
+pub enum Trend {
+    Improving,
+    NoChange,
+    Deteriorating,
+}
+
+impl From<forecast_spreadsheet::Trend> for Trend {
+    fn from(value: forecast_spreadsheet::Trend) -> Self {
+        use forecast_spreadsheet::Trend;
+        match value {
+            Trend::Improving => Self::Improving,
+            Trend::NoChange => Self::NoChange,
+            Trend::Deteriorating => Self::Deteriorating,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, ToSchema, Clone, Copy)]
+#[serde(rename_all = "kebab-case")]
+pub enum Confidence {
+    Low,
+    Moderate,
+    High,
+}
+
+impl From<forecast_spreadsheet::Confidence> for Confidence {
+    fn from(value: forecast_spreadsheet::Confidence) -> Self {
+        use forecast_spreadsheet::Confidence;
+        match value {
+            Confidence::Low => Self::Low,
+            Confidence::Moderate => Self::Moderate,
+            Confidence::High => Self::High,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, ToSchema, Clone, Copy)]
+#[serde(rename_all = "kebab-case")]
+pub enum Distribution {
+    Isolated,
+    Specific,
+    Widespread,
+}
+
+impl From<forecast_spreadsheet::Distribution> for Distribution {
+    fn from(value: forecast_spreadsheet::Distribution) -> Self {
+        use forecast_spreadsheet::Distribution;
+        match value {
+            Distribution::Isolated => Self::Isolated,
+            Distribution::Specific => Self::Specific,
+            Distribution::Widespread => Self::Widespread,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, ToSchema, Clone, Copy)]
+#[serde(rename_all = "kebab-case")]
+pub enum TimeOfDay {
+    AllDay,
+    Morning,
+    Afternoon,
+}
+
+impl From<forecast_spreadsheet::TimeOfDay> for TimeOfDay {
+    fn from(value: forecast_spreadsheet::TimeOfDay) -> Self {
+        use forecast_spreadsheet::TimeOfDay;
+        match value {
+            TimeOfDay::AllDay => Self::AllDay,
+            TimeOfDay::Morning => Self::Morning,
+            TimeOfDay::Afternoon => Self::Afternoon,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, ToSchema, Clone, Copy)]
+#[serde(rename_all = "kebab-case")]
+pub enum Sensitivity {
+    Unreactive,
+    Stubborn,
+    Reactive,
+    Touchy,
+}
+
+impl From<forecast_spreadsheet::Sensitivity> for Sensitivity {
+    fn from(value: forecast_spreadsheet::Sensitivity) -> Self {
+        use forecast_spreadsheet::Sensitivity;
+        match value {
+            Sensitivity::Unreactive => Self::Unreactive,
+            Sensitivity::Stubborn => Self::Stubborn,
+            Sensitivity::Reactive => Self::Reactive,
+            Sensitivity::Touchy => Self::Touchy,
+        }
+    }
+}
+
+/// A forecast area.
+#[derive(Debug, Serialize, ToSchema)]
+pub struct Area {
+    pub id: String,
+    /// IANA time zone name used for forecast times in this area.
+    pub time_zone: String,
+    /// Path to a GeoJSON document describing the boundary of the area, if one has been defined.
+    pub geojson_path: Option<String>,
+}
+
+/// A language which users can select, in the order it is shown in the language selector.
+#[derive(Debug, Serialize, ToSchema)]
+pub struct Language {
+    /// Language tag, e.g. `ka-GE`, accepted by the `lang` parameter.
+    pub id: String,
+    /// Name of the language in the language itself.
+    pub native_name: String,
+    /// A flag emoji or the name of an icon shown next to the name, if configured.
+    pub icon: Option<String>,
+    pub direction: TextDirection,
+}
+
+/// Direction in which the text of a language is written.
+#[derive(Debug, Serialize, ToSchema)]
+#[serde(rename_all = "kebab-case")]
+pub enum TextDirection {
+    /// Left to right.
+    Ltr,
+    /// Right to left.
+    Rtl,
+}
+
+impl From<crate::options::TextDirection> for TextDirection {
+    fn from(value: crate::options::TextDirection) -> Self {
+        match value {
+            crate::options::TextDirection::Ltr => Self::Ltr,
+            crate::options::TextDirection::Rtl => Self::Rtl,
+        }
+    }
+}
+
+/// When the next forecast for an area is expected to be published.
+#[derive(Debug, Serialize, ToSchema)]
+pub struct NextPublication {
+    /// Name of the area, as used in [`ForecastSummary::area`].
+    pub area: String,
+    #[serde(with = "time::serde::rfc3339")]
+    pub time: OffsetDateTime,
+}
+
+/// The avalanches reported in the approved observations during a season, which starts in
+/// September.
+#[derive(Debug, Serialize, ToSchema)]
+pub struct AvalancheActivity {
+    /// The year that the season starts in.
+    pub season: i32,
+    /// e.g. `2023/24`.
+    pub label: String,
+    /// Number of observations which reported an avalanche.
+    pub avalanches: usize,
+    pub natural: usize,
+    pub triggered: usize,
+    /// Days (in UTC) with at least one avalanche, in chronological order.
+    pub days: Vec<AvalancheActivityDay>,
+    /// Every avalanche size (1-5), including sizes with no avalanches.
+    pub sizes: Vec<AvalancheActivitySize>,
+    /// Avalanches reported without a size.
+    pub unknown_size: usize,
+    /// Most frequent first, omitting problems with no avalanches.
+    pub problems: Vec<AvalancheActivityProblem>,
+    /// Avalanches reported without an avalanche problem.
+    pub unknown_problem: usize,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct AvalancheActivityDay {
+    /// In the format `YYYY-MM-DD`.
+    pub date: String,
+    pub natural: usize,
+    pub triggered: usize,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct AvalancheActivitySize {
+    pub size: u8,
+    pub count: usize,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct AvalancheActivityProblem {
+    pub kind: ProblemKind,
+    pub count: usize,
+}
+
+impl From<SeasonActivity> for AvalancheActivity {
+    fn from(value: SeasonActivity) -> Self {
+        Self {
+            season: value.season.year(),
+            label: value.label,
+            avalanches: value.avalanches,
+            natural: value.natural,
+            triggered: value.triggered,
+            days: value
+                .days
+                .into_iter()
+                .map(|day| AvalancheActivityDay {
+                    date: day.date.to_string(),
+                    natural: day.natural,
+                    triggered: day.triggered,
+                })
+                .collect(),
+            sizes: value
+                .sizes
+                .into_iter()
+                .map(|size| AvalancheActivitySize {
+                    size: size.size as u8,
+                    count: size.count,
+                })
+                .collect(),
+            unknown_size: value.unknown_size,
+            problems: value
+                .problems
+                .into_iter()
+                .map(|problem| AvalancheActivityProblem {
+                    kind: problem.kind.into(),
+                    count: problem.count,
+                })
+                .collect(),
+            unknown_problem: value.unknown_problem,
+        }
+    }
+}
+
+/// Recent observations from a weather station.
+#[derive(Debug, Serialize, ToSchema)]
+pub struct WeatherStation {
+    pub id: String,
+    pub observations: Vec<WeatherObservation>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct WeatherObservation {
+    #[serde(with = "time::serde::rfc3339")]
+    pub time: OffsetDateTime,
+    pub temperature_celsius: Option<f64>,
+    pub wind_direction_degrees: Option<f64>,
+    pub wind_speed_ms: Option<f64>,
+    pub humidity_percent: Option<f64>,
+}
+
+impl From<WeatherDataItem> for WeatherObservation {
+    fn from(value: WeatherDataItem) -> Self {
+        Self {
+            time: value.time,
+            temperature_celsius: value.temperature_celcius,
+            wind_direction_degrees: value.wind_direction_degrees,
+            wind_speed_ms: value.wind_speed_ms,
+            humidity_percent: value.humidity_percent,
+        }
+    }
+}
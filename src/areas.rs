@@ -0,0 +1,200 @@
+//! A page for each forecast area at `/areas/{area}` which always shows the latest forecast for
+//! that area, along with links to its previous forecasts. Unlike `/forecasts/{file_name}` this URL
+//! doesn't change when a new forecast is published, so it can be shared as the canonical link for
+//! the area.
+
+use axum::{
+    extract::{Path, State},
+    response::{IntoResponse, Response},
+    routing::get,
+    Extension, Router,
+};
+use axum_extra::routing::TypedPath;
+use http::StatusCode;
+use i18n_embed::LanguageLoader;
+use serde::Serialize;
+
+use crate::{
+    database::Database,
+    error::map_eyre_error,
+    forecasts::{
+        forecast_page_context, get_forecast_data, parse_forecast_name, ForecastContext,
+        ForecastData, ForecastFileDetails, ForecastsFilePath, RequestedForecastData,
+    },
+    google_drive::ListFileMetadata,
+    i18n::{self, I18nLoader},
+    state::AppState,
+    templates::TemplatesWithContext,
+    user_preferences::UserPreferences,
+};
+
+pub fn router() -> Router<AppState> {
+    Router::new().route("/{area}", get(handler))
+}
+
+/// A link to a previous forecast for the area.
+#[derive(Serialize)]
+struct ArchivedForecastContext {
+    formatted_time: String,
+    path: String,
+}
+
+#[derive(Serialize)]
+struct AreaPageContext {
+    /// Name of the area used in the forecast file names.
+    id: String,
+    /// Localized name of the area.
+    name: String,
+    /// Previous forecasts for the area, newest first.
+    archive: Vec<ArchivedForecastContext>,
+}
+
+#[derive(Serialize)]
+struct Context {
+    #[serde(flatten)]
+    forecast: ForecastContext,
+    area_page: AreaPageContext,
+}
+
+/// The published files for the `area`, one for each forecast (preferring the file in the current
+/// language), newest first.
+fn area_forecast_files<'a>(
+    area: &str,
+    files: &'a [ListFileMetadata],
+    state: &AppState,
+    i18n: &I18nLoader,
+) -> Vec<(ForecastFileDetails, &'a ListFileMetadata)> {
+    let mut forecast_files: Vec<(ForecastFileDetails, &ListFileMetadata)> = Vec::new();
+    let current_language = i18n.current_language();
+    for file in files {
+        let Ok(details) = parse_forecast_name(&file.name, state.forecast_spreadsheet_schema) else {
+            continue;
+        };
+        if details.forecast.area != area {
+            continue;
+        }
+        let in_current_language = details
+            .language
+            .as_ref()
+            .is_none_or(|language| language.language == current_language.language);
+        match forecast_files
+            .iter_mut()
+            .find(|(other, _)| other.forecast.time == details.forecast.time)
+        {
+            Some(existing) => {
+                // Forecasts with data can be rendered as a page, so they take precedence over other
+                // files for the same forecast.
+                let replace = (file.has_forecast_data() && !existing.1.has_forecast_data())
+                    || (file.has_forecast_data() == existing.1.has_forecast_data()
+                        && in_current_language);
+                if replace {
+                    *existing = (details, file);
+                }
+            }
+            None => forecast_files.push((details, file)),
+        }
+    }
+    forecast_files.sort_by_key(|(file, _)| std::cmp::Reverse(file.forecast.time));
+    forecast_files
+}
+
+async fn handler(
+    Path(area): Path<String>,
+    State(state): State<AppState>,
+    Extension(database): Extension<Database>,
+    Extension(templates): Extension<TemplatesWithContext>,
+    Extension(i18n): Extension<I18nLoader>,
+    Extension(preferences): Extension<UserPreferences>,
+) -> axum::response::Result<Response> {
+    let Some(area) = state
+        .forecast_spreadsheet_schema
+        .area
+        .map
+        .keys()
+        .find(|id| id.eq_ignore_ascii_case(&area))
+        .cloned()
+    else {
+        return Ok(StatusCode::NOT_FOUND.into_response());
+    };
+    Ok(
+        handler_impl(area, &state, &database, &templates, &i18n, &preferences)
+            .await
+            .map_err(map_eyre_error)?,
+    )
+}
+
+async fn handler_impl(
+    area: String,
+    state: &AppState,
+    database: &Database,
+    templates: &TemplatesWithContext,
+    i18n: &I18nLoader,
+    preferences: &UserPreferences,
+) -> eyre::Result<Response> {
+    let file_list = state.published_files.list_files().await?;
+    let forecast_files = area_forecast_files(&area, &file_list, state, i18n);
+    let Some(latest_index) = forecast_files
+        .iter()
+        .position(|(_, file)| file.has_forecast_data())
+    else {
+        return Ok(StatusCode::NOT_FOUND.into_response());
+    };
+    let latest = forecast_files[latest_index].1;
+
+    let forecast = match get_forecast_data(
+        latest,
+        RequestedForecastData::Forecast,
+        &state.client,
+        database,
+        &state.blobs,
+        &state.options.google_drive,
+        state.forecast_spreadsheet_schemas,
+    )
+    .await?
+    {
+        ForecastData::Forecast(forecast) => forecast,
+        ForecastData::File(_) => eyre::bail!("Expected ForecastData::Forecast"),
+    };
+    let reloadable_options = state.reloadable_options.load_full();
+    let mut forecast = forecast_page_context(
+        forecast,
+        &latest.name,
+        &file_list,
+        state.options,
+        &reloadable_options,
+        &state.client,
+        database,
+        i18n,
+        preferences,
+        state.forecast_spreadsheet_schema,
+    )
+    .await?;
+    forecast.external_weather = forecast.external_weather.with_area(Some(area.clone()));
+
+    let archive = forecast_files
+        .iter()
+        .enumerate()
+        .filter(|(i, _)| *i != latest_index)
+        .map(|(_, (details, file))| ArchivedForecastContext {
+            formatted_time: i18n::format_time(details.forecast.time, i18n),
+            path: ForecastsFilePath {
+                file_name: file.name.clone(),
+            }
+            .to_uri()
+            .to_string(),
+        })
+        .collect();
+    let context = Context {
+        forecast,
+        area_page: AreaPageContext {
+            name: i18n::message_or(
+                i18n,
+                &format!("forecast-area-{}", area.to_lowercase()),
+                &area,
+            ),
+            id: area,
+            archive,
+        },
+    };
+    templates.render("area.html", &context)
+}
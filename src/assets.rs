@@ -0,0 +1,96 @@
+//! Content hashed urls for the embedded `/dist` and `/static` files, so that browsers can cache
+//! them indefinitely (see [`IMMUTABLE_CACHE_CONTROL`]) and still fetch the new version when they
+//! change. Templates obtain the url for a file using the `asset` function (see [`hashed_url`]),
+//! e.g. `{{ asset("/dist/leaflet.js") }}` renders as `/dist/leaflet.0123456789abcdef.js`.
+
+use std::path::Path;
+
+use rust_embed::EmbeddedFile;
+
+use crate::{DistDir, StaticDir};
+
+/// `Cache-Control` header value for files requested using their hashed path.
+pub const IMMUTABLE_CACHE_CONTROL: &str = "public, max-age=31536000, immutable";
+
+/// Number of hex characters of the file's SHA-256 hash used in the hashed path.
+const HASH_LEN: usize = 16;
+
+/// The hash of the `file`'s contents used in its hashed path.
+pub fn content_hash(file: &EmbeddedFile) -> String {
+    file.metadata.sha256_hash()[..HASH_LEN / 2]
+        .iter()
+        .map(|byte| format!("{byte:02x}"))
+        .collect()
+}
+
+/// The content hashed url for an embedded file `url` (e.g. `/dist/leaflet.js`). Returns `url`
+/// unchanged if it isn't an embedded file, or if it has been overridden by a file in the
+/// `static_files_directory` (see [`crate::options::StaticFiles`]).
+pub fn hashed_url(url: &str, static_files_directory: Option<&Path>) -> String {
+    if let Some(path) = url.strip_prefix("/dist/") {
+        if let Some(file) = DistDir::get(path) {
+            return format!("/dist/{}", insert_hash(path, &content_hash(&file)));
+        }
+    } else if let Some(path) = url.strip_prefix("/static/") {
+        let overridden =
+            static_files_directory.is_some_and(|directory| directory.join(path).is_file());
+        if !overridden {
+            if let Some(file) = StaticDir::get(path) {
+                return format!("/static/{}", insert_hash(path, &content_hash(&file)));
+            }
+        }
+    }
+    url.to_owned()
+}
+
+/// Insert the `hash` before the extension of the file name in `path`, e.g. `map/map.js` becomes
+/// `map/map.{hash}.js`.
+fn insert_hash(path: &str, hash: &str) -> String {
+    let file_start = path.rfind('/').map_or(0, |index| index + 1);
+    match path[file_start..].rfind('.') {
+        Some(dot) if dot > 0 => {
+            let dot = file_start + dot;
+            format!("{}.{hash}{}", &path[..dot], &path[dot..])
+        }
+        _ => format!("{path}.{hash}"),
+    }
+}
+
+/// Split a path produced by [`insert_hash`] into the original path and the hash, or `None` if
+/// the path doesn't contain a hash.
+pub fn split_hash(path: &str) -> Option<(String, &str)> {
+    fn is_hash(part: &str) -> bool {
+        part.len() == HASH_LEN && part.chars().all(|c| c.is_ascii_hexdigit())
+    }
+
+    let file_start = path.rfind('/').map_or(0, |index| index + 1);
+    let mut parts: Vec<&str> = path[file_start..].split('.').collect();
+    let index = match parts.len() {
+        0 | 1 => return None,
+        2 if is_hash(parts[1]) => 1,
+        len if len > 2 && is_hash(parts[len - 2]) => len - 2,
+        _ => return None,
+    };
+    let hash = parts.remove(index);
+    Some((format!("{}{}", &path[..file_start], parts.join(".")), hash))
+}
+
+#[cfg(test)]
+mod test {
+    use super::{insert_hash, split_hash};
+
+    #[test]
+    fn test_insert_and_split_hash() {
+        let hash = "0123456789abcdef";
+        for path in ["leaflet.js", "map/map.js", "images/icon.min.svg", "LICENSE"] {
+            let hashed = insert_hash(path, hash);
+            assert_eq!(split_hash(&hashed), Some((path.to_owned(), hash)));
+        }
+        assert_eq!(
+            insert_hash("map/map.js", hash),
+            "map/map.0123456789abcdef.js"
+        );
+        assert_eq!(split_hash("map/map.js"), None);
+        assert_eq!(split_hash("leaflet.min.js"), None);
+    }
+}
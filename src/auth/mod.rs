@@ -0,0 +1,668 @@
+//! Authentication of users (see [`crate::users`]) for accessing `/admin`. Users log in using
+//! the `/login` page, which creates a login session stored in the database and identified by a
+//! signed cookie. HTTP basic authentication is also accepted for the [`BASIC_AUTH_PATHS`], which
+//! is useful for scripts. Failed logins are throttled, see [`throttle`].
+
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+};
+
+use axum::{
+    extract::{OriginalUri, Request, State},
+    middleware::Next,
+    response::{IntoResponse, Redirect, Response},
+    routing::{get, post},
+    Extension, Form, Router,
+};
+use axum_extra::extract::{
+    cookie::{Cookie, SameSite},
+    CookieJar,
+};
+use base64::Engine;
+use hmac::{Hmac, Mac};
+use http::{header, HeaderMap, HeaderValue, StatusCode};
+use secrecy::{ExposeSecret, SecretString};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::{
+    client_ip,
+    database::Database,
+    error::map_eyre_error,
+    state::AppState,
+    templates::TemplatesWithContext,
+    types,
+    users::{self, Permission, User},
+};
+
+mod oidc;
+mod throttle;
+
+pub use throttle::LoginThrottle;
+
+const SESSION_COOKIE_NAME: &str = "session";
+/// How long a login session lasts before the user needs to log in again.
+const SESSION_DURATION: time::Duration = time::Duration::days(30);
+/// Where to go after logging in when no redirect was requested.
+const DEFAULT_LOGIN_REDIRECT: &str = "/admin";
+/// Paths (and the paths beneath them) where HTTP basic authentication is accepted, it is ignored
+/// elsewhere so that other requests don't need to verify the credentials.
+const BASIC_AUTH_PATHS: &[&str] = &["/admin", "/login", "/metrics"];
+
+/// Key used to sign the session cookie.
+#[derive(Clone)]
+pub struct SessionKey(Arc<[u8; 32]>);
+
+impl SessionKey {
+    /// Derive the key from the `secret`, or generate a random key when there is no secret (in
+    /// which case sessions end when the server restarts).
+    pub fn new(secret: Option<&SecretString>) -> Self {
+        let key: [u8; 32] = match secret {
+            Some(secret) => Sha256::digest(secret.expose_secret().as_bytes()).into(),
+            None => {
+                let mut key = [0; 32];
+                key[..16].copy_from_slice(uuid::Uuid::new_v4().as_bytes());
+                key[16..].copy_from_slice(uuid::Uuid::new_v4().as_bytes());
+                key
+            }
+        };
+        Self(Arc::new(key))
+    }
+
+    fn mac(&self, value: &str) -> Hmac<Sha256> {
+        let mut mac =
+            Hmac::<Sha256>::new_from_slice(&*self.0).expect("HMAC accepts keys of any length");
+        mac.update(value.as_bytes());
+        mac
+    }
+
+    /// Sign the `value`, producing `{value}.{signature}`.
+    fn sign(&self, value: &str) -> String {
+        let engine = base64::engine::general_purpose::URL_SAFE_NO_PAD;
+        let signature = engine.encode(self.mac(value).finalize().into_bytes());
+        format!("{value}.{signature}")
+    }
+
+    /// Verify a value signed using [`SessionKey::sign`], returning the original value if the
+    /// signature is valid.
+    fn verify<'a>(&self, signed: &'a str) -> Option<&'a str> {
+        let (value, signature) = signed.rsplit_once('.')?;
+        let engine = base64::engine::general_purpose::URL_SAFE_NO_PAD;
+        let signature = engine.decode(signature).ok()?;
+        self.mac(value).verify_slice(&signature).ok()?;
+        Some(value)
+    }
+}
+
+/// The logged in user, inserted into the request extensions by [`middleware`] as
+/// `Option<CurrentUser>`.
+#[derive(Clone, Debug)]
+pub struct CurrentUser {
+    pub user: User,
+    /// `None` when authenticated using basic authentication.
+    session_id: Option<String>,
+}
+
+impl CurrentUser {
+    pub fn has_permission(&self, permission: Permission) -> bool {
+        self.user.role.has_permission(permission)
+    }
+}
+
+/// Basic authentication credentials which have been verified, so that clients which send them
+/// with every request (e.g. scripts) don't need the slow password hash verification each time.
+/// Cheap to clone.
+#[derive(Clone)]
+pub struct BasicAuthCache {
+    /// Key for hashing the passwords, so that they aren't kept in memory.
+    key: SessionKey,
+    /// For each username, the user's password hash when the password was verified (so that the
+    /// entry is no longer used when the password is changed) and the hashed password.
+    verified: Arc<Mutex<HashMap<String, VerifiedPassword>>>,
+}
+
+/// The user's password hash and the hashed password, see [`BasicAuthCache`].
+type VerifiedPassword = (String, Vec<u8>);
+
+impl Default for BasicAuthCache {
+    fn default() -> Self {
+        Self {
+            key: SessionKey::new(None),
+            verified: Arc::default(),
+        }
+    }
+}
+
+impl BasicAuthCache {
+    fn is_verified(&self, user: &User, password: &SecretString) -> bool {
+        let verified = self
+            .verified
+            .lock()
+            .expect("Basic auth cache lock poisoned");
+        verified
+            .get(&user.username)
+            .is_some_and(|(password_hash, password_mac)| {
+                *password_hash == user.password_hash
+                    && self
+                        .key
+                        .mac(password.expose_secret())
+                        .verify_slice(password_mac)
+                        .is_ok()
+            })
+    }
+
+    fn insert(&self, user: &User, password: &SecretString) {
+        let password_mac = self
+            .key
+            .mac(password.expose_secret())
+            .finalize()
+            .into_bytes()
+            .to_vec();
+        self.verified
+            .lock()
+            .expect("Basic auth cache lock poisoned")
+            .insert(
+                user.username.clone(),
+                (user.password_hash.clone(), password_mac),
+            );
+    }
+}
+
+struct BasicCredentials {
+    username: String,
+    password: SecretString,
+}
+
+fn parse_auth_header_credentials(header: &HeaderValue) -> Option<BasicCredentials> {
+    let header_str: &str = header.to_str().ok()?;
+    let credentials_base64: &str = header_str.split_once("Basic ")?.1;
+    let engine = base64::engine::general_purpose::STANDARD;
+    let credentials = String::from_utf8(engine.decode(credentials_base64).ok()?).ok()?;
+    let (username, password) = credentials.split_once(':')?;
+    Some(BasicCredentials {
+        username: username.to_string(),
+        password: SecretString::new(password.to_string()),
+    })
+}
+
+/// The user for a login session which has not yet expired.
+async fn session_user(database: &Database, session_id: &str) -> eyre::Result<Option<User>> {
+    let now = types::Time::now_utc();
+    Ok(sqlx::query_as!(
+        User,
+        r#"SELECT
+            users.id as "id: users::UserId",
+            users.username,
+            users.password_hash,
+            users.role as "role: users::Role",
+            users.created_at as "created_at: types::Time"
+        FROM sessions JOIN users ON users.id = sessions.user_id
+        WHERE sessions.id=$1 AND sessions.expires_at > $2"#,
+        session_id,
+        now,
+    )
+    .fetch_optional(database)
+    .await?)
+}
+
+/// Create a login session for the `user`, returning the session id. Expired sessions are
+/// removed at the same time.
+async fn create_session(database: &Database, user: &User) -> eyre::Result<String> {
+    let session_id = uuid::Uuid::new_v4().to_string();
+    let now = time::OffsetDateTime::now_utc();
+    let created_at = now.format(&crate::database::DATETIME_FORMAT)?;
+    let expires_at = (now + SESSION_DURATION).format(&crate::database::DATETIME_FORMAT)?;
+    let mut transaction = database.begin().await?;
+    sqlx::query!("DELETE FROM sessions WHERE expires_at <= $1", created_at)
+        .execute(&mut *transaction)
+        .await?;
+    sqlx::query!(
+        "INSERT INTO sessions VALUES($1, $2, $3, $4)",
+        session_id,
+        user.id,
+        created_at,
+        expires_at,
+    )
+    .execute(&mut *transaction)
+    .await?;
+    transaction.commit().await?;
+    Ok(session_id)
+}
+
+/// The user of the login session in the cookie, if it is valid.
+async fn session_authenticate(
+    database: &Database,
+    session_key: &SessionKey,
+    headers: &HeaderMap,
+) -> eyre::Result<Option<CurrentUser>> {
+    let cookies = CookieJar::from_headers(headers);
+    let Some(session_id) = cookies
+        .get(SESSION_COOKIE_NAME)
+        .and_then(|cookie| session_key.verify(cookie.value()))
+    else {
+        return Ok(None);
+    };
+    Ok(session_user(database, session_id)
+        .await?
+        .map(|user| CurrentUser {
+            user,
+            session_id: Some(session_id.to_owned()),
+        }))
+}
+
+fn accepts_basic_auth(path: &str) -> bool {
+    BASIC_AUTH_PATHS.iter().any(|prefix| {
+        path.strip_prefix(prefix)
+            .is_some_and(|rest| rest.is_empty() || rest.starts_with('/'))
+    })
+}
+
+enum BasicAuthentication {
+    Anonymous,
+    Authenticated(CurrentUser),
+    /// Too many failed logins from the client or for the user, see [`LoginThrottle`].
+    Throttled,
+}
+
+async fn basic_authenticate(
+    state: &AppState,
+    headers: &HeaderMap,
+    client_ip: Option<&str>,
+) -> eyre::Result<BasicAuthentication> {
+    let Some(credentials) = headers
+        .get(header::AUTHORIZATION)
+        .and_then(parse_auth_header_credentials)
+    else {
+        return Ok(BasicAuthentication::Anonymous);
+    };
+    let user = users::get_user_by_username(&state.database, &credentials.username).await?;
+    if let Some(user) = &user {
+        if state
+            .basic_auth_cache
+            .is_verified(user, &credentials.password)
+        {
+            return Ok(BasicAuthentication::Authenticated(CurrentUser {
+                user: user.clone(),
+                session_id: None,
+            }));
+        }
+    }
+    if state
+        .login_throttle
+        .is_throttled(client_ip, &credentials.username)
+    {
+        return Ok(BasicAuthentication::Throttled);
+    }
+    let user = match user {
+        Some(user) => users::verify_password(&user, credentials.password.clone())
+            .await?
+            .then_some(user),
+        None => None,
+    };
+    match user {
+        Some(user) => {
+            state.basic_auth_cache.insert(&user, &credentials.password);
+            Ok(BasicAuthentication::Authenticated(CurrentUser {
+                user,
+                session_id: None,
+            }))
+        }
+        None => {
+            tracing::warn!(
+                "Failed basic authentication for user {:?}",
+                credentials.username
+            );
+            state
+                .login_throttle
+                .record_failure(client_ip, &credentials.username);
+            Ok(BasicAuthentication::Anonymous)
+        }
+    }
+}
+
+fn too_many_attempts_response() -> Response {
+    (
+        StatusCode::TOO_MANY_REQUESTS,
+        "Too many failed login attempts, try again later",
+    )
+        .into_response()
+}
+
+/// Middleware which inserts the `Option<CurrentUser>` request extension.
+pub async fn middleware(state: State<AppState>, mut request: Request, next: Next) -> Response {
+    let mut current_user =
+        match session_authenticate(&state.database, &state.session_key, request.headers()).await {
+            Ok(current_user) => current_user,
+            Err(error) => return map_eyre_error(error),
+        };
+    if current_user.is_none() && accepts_basic_auth(request.uri().path()) {
+        let client_ip = client_ip::client_ip(
+            request.headers(),
+            request.extensions(),
+            state.options.analytics.client_ip_header.as_deref(),
+        )
+        .map(|ip| ip.to_string());
+        match basic_authenticate(&state, request.headers(), client_ip.as_deref()).await {
+            Ok(BasicAuthentication::Anonymous) => {}
+            Ok(BasicAuthentication::Authenticated(user)) => current_user = Some(user),
+            Ok(BasicAuthentication::Throttled) => return too_many_attempts_response(),
+            Err(error) => return map_eyre_error(error),
+        }
+    }
+    request.extensions_mut().insert(current_user);
+    next.run(request).await
+}
+
+/// Response for a request which requires a logged in user. Browsers are redirected to the login
+/// page, other clients are asked for basic authentication.
+fn unauthenticated_response(request: &Request) -> Response {
+    let accepts_html = request
+        .headers()
+        .get(header::ACCEPT)
+        .and_then(|accept| accept.to_str().ok())
+        .is_some_and(|accept| accept.contains("text/html"));
+    if accepts_html {
+        let uri = request
+            .extensions()
+            .get::<OriginalUri>()
+            .map(|original_uri| &original_uri.0)
+            .unwrap_or(request.uri());
+        let redirect = uri
+            .path_and_query()
+            .map(|path_and_query| path_and_query.as_str())
+            .unwrap_or(DEFAULT_LOGIN_REDIRECT);
+        Redirect::to(&format!(
+            "/login?redirect={}",
+            urlencoding::encode(redirect)
+        ))
+        .into_response()
+    } else {
+        (
+            StatusCode::UNAUTHORIZED,
+            [(
+                header::WWW_AUTHENTICATE,
+                r#"Basic realm="User Visible Realm", charset="UTF-8""#,
+            )],
+        )
+            .into_response()
+    }
+}
+
+/// Middleware which only allows requests from a logged in user.
+pub async fn require_login(request: Request, next: Next) -> Response {
+    match request.extensions().get::<Option<CurrentUser>>() {
+        Some(Some(_)) => next.run(request).await,
+        _ => unauthenticated_response(&request),
+    }
+}
+
+/// Middleware which only allows requests from a logged in user with the `permission`.
+pub async fn require_permission(permission: Permission, request: Request, next: Next) -> Response {
+    match request.extensions().get::<Option<CurrentUser>>() {
+        Some(Some(current_user)) if current_user.has_permission(permission) => {
+            next.run(request).await
+        }
+        Some(Some(current_user)) => {
+            tracing::warn!(
+                "User {:?} does not have the {permission:?} permission",
+                current_user.user.username
+            );
+            (
+                StatusCode::FORBIDDEN,
+                "You do not have permission to access this page",
+            )
+                .into_response()
+        }
+        _ => unauthenticated_response(&request),
+    }
+}
+
+pub fn router() -> Router<AppState> {
+    Router::new()
+        .route("/login", get(login_handler).post(login_post_handler))
+        .route("/login/oidc", get(oidc::login_handler))
+        .route("/login/oidc/callback", get(oidc::callback_handler))
+        .route("/logout", post(logout_handler))
+}
+
+#[derive(Deserialize)]
+struct LoginQuery {
+    #[serde(default)]
+    redirect: Option<String>,
+}
+
+#[derive(Serialize)]
+struct LoginContext<'a> {
+    redirect: &'a str,
+    username: &'a str,
+    error: Option<&'a str>,
+    /// Name of the OpenID Connect provider, when logging in with it is available.
+    oidc_display_name: Option<&'static str>,
+}
+
+/// Render the login page, with an `error` if the login failed.
+fn render_login(
+    state: &AppState,
+    templates: &TemplatesWithContext,
+    redirect: &str,
+    username: &str,
+    error: Option<&str>,
+) -> axum::response::Result<Response> {
+    let context = LoginContext {
+        redirect,
+        username,
+        error,
+        oidc_display_name: state
+            .options
+            .oidc
+            .as_ref()
+            .map(|oidc| oidc.display_name.as_str()),
+    };
+    let response = templates
+        .render("login.html", &context)
+        .map_err(map_eyre_error)?;
+    let status = if error.is_some() {
+        StatusCode::UNAUTHORIZED
+    } else {
+        StatusCode::OK
+    };
+    Ok((status, response).into_response())
+}
+
+/// Whether the `redirect` is a relative URI with only a path (and query) on this site. Browsers
+/// treat `\` like `/` and ignore tabs and newlines, so e.g. `/\example.com` would redirect to
+/// another site.
+fn is_local_path(redirect: &str) -> bool {
+    !redirect.contains('\\')
+        && !redirect.chars().any(char::is_control)
+        && redirect.parse::<http::Uri>().is_ok_and(|uri| {
+            uri.scheme().is_none()
+                && uri.authority().is_none()
+                && uri.path().starts_with('/')
+                && !uri.path().starts_with("//")
+        })
+}
+
+/// Only allow redirecting to paths on this site after logging in.
+fn login_redirect(redirect: Option<String>) -> String {
+    redirect
+        .filter(|redirect| is_local_path(redirect))
+        .unwrap_or_else(|| DEFAULT_LOGIN_REDIRECT.to_owned())
+}
+
+/// Cookie attributes shared by the cookies used for logging in.
+fn login_cookie<'a>(
+    state: &AppState,
+    name: &'static str,
+    value: String,
+    max_age: time::Duration,
+) -> Cookie<'a> {
+    Cookie::build((name, value))
+        .path("/")
+        .http_only(true)
+        .same_site(SameSite::Lax)
+        .secure(state.options.base_url().scheme() == "https")
+        .max_age(max_age)
+        .build()
+}
+
+/// Create a login session for the authenticated `user`, and redirect to `redirect`.
+async fn log_in(
+    state: &AppState,
+    cookies: CookieJar,
+    user: &User,
+    redirect: &str,
+) -> eyre::Result<Response> {
+    let session_id = create_session(&state.database, user).await?;
+    tracing::info!("User {:?} logged in", user.username);
+    let cookie = login_cookie(
+        state,
+        SESSION_COOKIE_NAME,
+        state.session_key.sign(&session_id),
+        SESSION_DURATION,
+    );
+    Ok((cookies.add(cookie), Redirect::to(redirect)).into_response())
+}
+
+async fn login_handler(
+    State(state): State<AppState>,
+    axum::extract::Query(query): axum::extract::Query<LoginQuery>,
+    Extension(templates): Extension<TemplatesWithContext>,
+) -> axum::response::Result<Response> {
+    render_login(
+        &state,
+        &templates,
+        &login_redirect(query.redirect),
+        "",
+        None,
+    )
+}
+
+#[derive(Deserialize)]
+struct LoginForm {
+    username: String,
+    password: SecretString,
+    #[serde(default)]
+    redirect: Option<String>,
+}
+
+async fn login_post_handler(
+    State(state): State<AppState>,
+    Extension(templates): Extension<TemplatesWithContext>,
+    headers: HeaderMap,
+    extensions: http::Extensions,
+    cookies: CookieJar,
+    Form(form): Form<LoginForm>,
+) -> axum::response::Result<Response> {
+    let redirect = login_redirect(form.redirect);
+    let client_ip = client_ip::client_ip(
+        &headers,
+        &extensions,
+        state.options.analytics.client_ip_header.as_deref(),
+    )
+    .map(|ip| ip.to_string());
+    if state
+        .login_throttle
+        .is_throttled(client_ip.as_deref(), &form.username)
+    {
+        tracing::warn!("Throttled login for user {:?}", form.username);
+        let mut response = render_login(
+            &state,
+            &templates,
+            &redirect,
+            &form.username,
+            Some("Too many failed login attempts, try again later"),
+        )?;
+        *response.status_mut() = StatusCode::TOO_MANY_REQUESTS;
+        return Ok(response);
+    }
+    let user = users::get_user_by_username(&state.database, &form.username)
+        .await
+        .map_err(map_eyre_error)?;
+    let user = match user {
+        Some(user) => users::verify_password(&user, form.password)
+            .await
+            .map_err(map_eyre_error)?
+            .then_some(user),
+        None => None,
+    };
+    let Some(user) = user else {
+        tracing::warn!("Failed login for user {:?}", form.username);
+        state
+            .login_throttle
+            .record_failure(client_ip.as_deref(), &form.username);
+        return render_login(
+            &state,
+            &templates,
+            &redirect,
+            &form.username,
+            Some("Incorrect username or password"),
+        );
+    };
+
+    Ok(log_in(&state, cookies, &user, &redirect)
+        .await
+        .map_err(map_eyre_error)?)
+}
+
+async fn logout_handler(
+    Extension(database): Extension<Database>,
+    Extension(current_user): Extension<Option<CurrentUser>>,
+    cookies: CookieJar,
+) -> axum::response::Result<Response> {
+    if let Some(session_id) = current_user.and_then(|current_user| current_user.session_id) {
+        sqlx::query!("DELETE FROM sessions WHERE id=$1", session_id)
+            .execute(&database)
+            .await
+            .map_err(|error| map_eyre_error(error.into()))?;
+    }
+    let cookies = cookies.remove(Cookie::build(SESSION_COOKIE_NAME).path("/"));
+    Ok((cookies, Redirect::to("/")).into_response())
+}
+
+#[cfg(test)]
+mod test {
+    use secrecy::SecretString;
+
+    use super::{accepts_basic_auth, login_redirect, SessionKey, DEFAULT_LOGIN_REDIRECT};
+
+    #[test]
+    fn test_session_key() {
+        let key = SessionKey::new(Some(&SecretString::new("secret".to_owned())));
+        let signed = key.sign("session-id");
+        assert_eq!(key.verify(&signed), Some("session-id"));
+        assert_eq!(key.verify("session-id"), None);
+        assert_eq!(key.verify(&signed.replace("session-id", "other-id")), None);
+
+        let other_key = SessionKey::new(None);
+        assert_eq!(other_key.verify(&signed), None);
+    }
+
+    #[test]
+    fn test_accepts_basic_auth() {
+        assert!(accepts_basic_auth("/admin"));
+        assert!(accepts_basic_auth("/admin/logs"));
+        assert!(accepts_basic_auth("/metrics"));
+        assert!(!accepts_basic_auth("/administrator"));
+        assert!(!accepts_basic_auth("/"));
+        assert!(!accepts_basic_auth("/forecasts/file.xlsx"));
+    }
+
+    #[test]
+    fn test_login_redirect() {
+        let redirect = |redirect: &str| login_redirect(Some(redirect.to_owned()));
+        assert_eq!(redirect("/admin/logs?page=2"), "/admin/logs?page=2");
+        for invalid in [
+            "//example.com",
+            "/\\example.com",
+            "\\\\example.com",
+            "/\t/example.com",
+            "https://example.com/admin",
+            "admin",
+        ] {
+            assert_eq!(redirect(invalid), DEFAULT_LOGIN_REDIRECT, "{invalid:?}");
+        }
+        assert_eq!(login_redirect(None), DEFAULT_LOGIN_REDIRECT);
+    }
+}
@@ -0,0 +1,463 @@
+//! Logging in with an OpenID Connect provider (see [`crate::options::Oidc`]), using the
+//! authorization code flow with PKCE.
+//!
+//! The ID token is received directly from the provider's token endpoint over TLS, so its
+//! signature is not checked, only its claims, as permitted by
+//! <https://openid.net/specs/openid-connect-core-1_0.html#IDTokenValidation>.
+
+use axum::{
+    extract::{Query, State},
+    response::{IntoResponse, Redirect, Response},
+    Extension,
+};
+use axum_extra::extract::{cookie::Cookie, CookieJar};
+use base64::Engine;
+use eyre::ContextCompat;
+use http::StatusCode;
+use secrecy::ExposeSecret;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use tokio::sync::OnceCell;
+use url::Url;
+
+use super::{log_in, login_cookie, login_redirect, render_login, LoginQuery};
+use crate::{
+    error::map_eyre_error,
+    options::{Oidc, Options},
+    state::AppState,
+    templates::TemplatesWithContext,
+    types,
+    users::{self, User, UserId},
+};
+
+/// Cookie holding the [`LoginState`] while the user logs in with the provider.
+const LOGIN_STATE_COOKIE_NAME: &str = "oidc_login";
+/// How long the user has to log in with the provider.
+const LOGIN_STATE_DURATION: time::Duration = time::Duration::minutes(10);
+const SCOPES: &str = "openid email profile";
+
+/// The parts of the provider's discovery document which are used.
+#[derive(Deserialize, Debug)]
+struct ProviderMetadata {
+    issuer: String,
+    authorization_endpoint: Url,
+    token_endpoint: Url,
+}
+
+/// Only one provider can be configured, so its metadata is fetched once and kept for the life
+/// of the server.
+static PROVIDER_METADATA: OnceCell<ProviderMetadata> = OnceCell::const_new();
+
+async fn provider_metadata(
+    client: &reqwest::Client,
+    oidc: &Oidc,
+) -> eyre::Result<&'static ProviderMetadata> {
+    PROVIDER_METADATA
+        .get_or_try_init(|| async {
+            // The issuer URL may contain a path, so the discovery path is appended rather than
+            // joined.
+            let url = format!(
+                "{}/.well-known/openid-configuration",
+                oidc.issuer_url.as_str().trim_end_matches('/')
+            );
+            let metadata: ProviderMetadata = client
+                .get(&url)
+                .send()
+                .await?
+                .error_for_status()?
+                .json()
+                .await?;
+            if !same_issuer(&metadata.issuer, oidc.issuer_url.as_str()) {
+                eyre::bail!(
+                    "Issuer in discovery document {:?} does not match the configured issuer_url",
+                    metadata.issuer
+                );
+            }
+            Ok(metadata)
+        })
+        .await
+}
+
+/// Compare issuers, ignoring the scheme because Google's ID tokens may use the issuer
+/// `accounts.google.com`.
+fn same_issuer(a: &str, b: &str) -> bool {
+    let normalize = |issuer: &str| {
+        issuer
+            .trim_start_matches("https://")
+            .trim_end_matches('/')
+            .to_owned()
+    };
+    normalize(a) == normalize(b)
+}
+
+fn redirect_uri(options: &Options) -> eyre::Result<Url> {
+    Ok(options.base_url().join("login/oidc/callback")?)
+}
+
+/// Stored in a signed cookie between starting to log in and the provider redirecting back.
+#[derive(Serialize, Deserialize)]
+struct LoginState {
+    state: String,
+    nonce: String,
+    code_verifier: String,
+    redirect: String,
+}
+
+/// A random string with 244 bits of entropy, suitable for use as a PKCE code verifier.
+fn random_token() -> String {
+    format!(
+        "{}{}",
+        uuid::Uuid::new_v4().simple(),
+        uuid::Uuid::new_v4().simple()
+    )
+}
+
+fn code_challenge(code_verifier: &str) -> String {
+    base64::engine::general_purpose::URL_SAFE_NO_PAD.encode(Sha256::digest(code_verifier))
+}
+
+/// Handler for `/login/oidc`, which redirects to the provider to log in.
+pub async fn login_handler(
+    State(state): State<AppState>,
+    Query(query): Query<LoginQuery>,
+    Extension(templates): Extension<TemplatesWithContext>,
+    cookies: CookieJar,
+) -> axum::response::Result<Response> {
+    let Some(oidc) = &state.options.oidc else {
+        return Ok(StatusCode::NOT_FOUND.into_response());
+    };
+    let redirect = login_redirect(query.redirect);
+    let login_state = LoginState {
+        state: random_token(),
+        nonce: random_token(),
+        code_verifier: random_token(),
+        redirect,
+    };
+    match authorization_url(&state, oidc, &login_state).await {
+        Ok(url) => {
+            let value = base64::engine::general_purpose::URL_SAFE_NO_PAD
+                .encode(serde_json::to_vec(&login_state).expect("Unable to serialize login state"));
+            let cookie = login_cookie(
+                &state,
+                LOGIN_STATE_COOKIE_NAME,
+                state.session_key.sign(&value),
+                LOGIN_STATE_DURATION,
+            );
+            Ok((cookies.add(cookie), Redirect::to(url.as_str())).into_response())
+        }
+        Err(error) => {
+            tracing::error!("Error starting to log in with OpenID Connect: {error:?}");
+            let error = format!("Unable to log in with {}", oidc.display_name);
+            render_login(&state, &templates, &login_state.redirect, "", Some(&error))
+        }
+    }
+}
+
+async fn authorization_url(
+    state: &AppState,
+    oidc: &Oidc,
+    login_state: &LoginState,
+) -> eyre::Result<Url> {
+    let metadata = provider_metadata(&state.client, oidc).await?;
+    let mut url = metadata.authorization_endpoint.clone();
+    url.query_pairs_mut()
+        .append_pair("response_type", "code")
+        .append_pair("client_id", &oidc.client_id)
+        .append_pair("redirect_uri", redirect_uri(state.options)?.as_str())
+        .append_pair("scope", SCOPES)
+        .append_pair("state", &login_state.state)
+        .append_pair("nonce", &login_state.nonce)
+        .append_pair(
+            "code_challenge",
+            &code_challenge(&login_state.code_verifier),
+        )
+        .append_pair("code_challenge_method", "S256");
+    Ok(url)
+}
+
+#[derive(Deserialize)]
+pub struct CallbackQuery {
+    #[serde(default)]
+    code: Option<String>,
+    #[serde(default)]
+    state: Option<String>,
+    #[serde(default)]
+    error: Option<String>,
+    #[serde(default)]
+    error_description: Option<String>,
+}
+
+/// Handler for `/login/oidc/callback`, where the provider redirects to after the user has
+/// logged in.
+pub async fn callback_handler(
+    State(state): State<AppState>,
+    Query(query): Query<CallbackQuery>,
+    Extension(templates): Extension<TemplatesWithContext>,
+    cookies: CookieJar,
+) -> axum::response::Result<Response> {
+    let Some(oidc) = &state.options.oidc else {
+        return Ok(StatusCode::NOT_FOUND.into_response());
+    };
+    let login_state: Option<LoginState> = cookies
+        .get(LOGIN_STATE_COOKIE_NAME)
+        .and_then(|cookie| state.session_key.verify(cookie.value()))
+        .and_then(|value| {
+            base64::engine::general_purpose::URL_SAFE_NO_PAD
+                .decode(value)
+                .ok()
+        })
+        .and_then(|value| serde_json::from_slice(&value).ok());
+    let cookies = cookies.remove(Cookie::build(LOGIN_STATE_COOKIE_NAME).path("/"));
+    let redirect = login_redirect(login_state.as_ref().map(|s| s.redirect.clone()));
+
+    match authenticate(&state, oidc, query, login_state).await {
+        Ok(user) => Ok(log_in(&state, cookies, &user, &redirect)
+            .await
+            .map_err(map_eyre_error)?),
+        Err(error) => {
+            tracing::warn!("Failed to log in with OpenID Connect: {error:?}");
+            let error = format!("Unable to log in with {}: {error}", oidc.display_name);
+            let response = render_login(&state, &templates, &redirect, "", Some(&error))?;
+            Ok((cookies, response).into_response())
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct TokenResponse {
+    id_token: String,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(untagged)]
+enum Audience {
+    One(String),
+    Many(Vec<String>),
+}
+
+impl Audience {
+    fn contains(&self, client_id: &str) -> bool {
+        match self {
+            Self::One(audience) => audience == client_id,
+            Self::Many(audiences) => audiences.iter().any(|audience| audience == client_id),
+        }
+    }
+}
+
+/// The claims of an ID token which are used.
+#[derive(Deserialize, Debug)]
+struct IdTokenClaims {
+    iss: String,
+    aud: Audience,
+    /// Expiry time in seconds since the unix epoch.
+    exp: i64,
+    #[serde(default)]
+    nonce: Option<String>,
+    #[serde(default)]
+    email: Option<String>,
+    #[serde(default)]
+    email_verified: Option<bool>,
+}
+
+/// Decode the claims of an ID token (a JWT) without checking its signature.
+fn decode_id_token(id_token: &str) -> eyre::Result<IdTokenClaims> {
+    let payload = id_token
+        .split('.')
+        .nth(1)
+        .wrap_err("ID token is not a JWT")?;
+    let payload =
+        base64::engine::general_purpose::URL_SAFE_NO_PAD.decode(payload.trim_end_matches('='))?;
+    Ok(serde_json::from_slice(&payload)?)
+}
+
+/// Check the claims of an ID token, returning the user's email address (in lowercase).
+fn validate_claims(
+    claims: &IdTokenClaims,
+    oidc: &Oidc,
+    issuer: &str,
+    nonce: &str,
+    now_unix_seconds: i64,
+) -> eyre::Result<String> {
+    if !same_issuer(&claims.iss, issuer) {
+        eyre::bail!("ID token was issued by {:?}", claims.iss);
+    }
+    if !claims.aud.contains(&oidc.client_id) {
+        eyre::bail!("ID token is not for this client");
+    }
+    if claims.exp <= now_unix_seconds {
+        eyre::bail!("ID token has expired");
+    }
+    if claims.nonce.as_deref() != Some(nonce) {
+        eyre::bail!("ID token nonce does not match");
+    }
+    let email = claims
+        .email
+        .as_deref()
+        .wrap_err("No email address was provided")?
+        .to_lowercase();
+    if claims.email_verified != Some(true) {
+        eyre::bail!("Email address {email:?} has not been verified");
+    }
+    if !oidc.allowed_domains.is_empty() {
+        let domain = email.rsplit_once('@').map(|(_, domain)| domain);
+        if !oidc
+            .allowed_domains
+            .iter()
+            .any(|allowed| domain.is_some_and(|domain| domain.eq_ignore_ascii_case(allowed)))
+        {
+            eyre::bail!("Email address {email:?} is not in an allowed domain");
+        }
+    }
+    Ok(email)
+}
+
+/// Exchange the authorization code for an ID token, and find (or create, see
+/// [`Oidc::default_role`]) the user with the username matching its email address.
+async fn authenticate(
+    state: &AppState,
+    oidc: &Oidc,
+    query: CallbackQuery,
+    login_state: Option<LoginState>,
+) -> eyre::Result<User> {
+    if let Some(error) = query.error {
+        eyre::bail!("{}", query.error_description.unwrap_or(error));
+    }
+    let login_state = login_state.wrap_err("The login has expired, please try again")?;
+    if query.state.as_deref() != Some(login_state.state.as_str()) {
+        eyre::bail!("State does not match");
+    }
+    let code = query.code.wrap_err("No authorization code was provided")?;
+    let metadata = provider_metadata(&state.client, oidc).await?;
+    let redirect_uri = redirect_uri(state.options)?;
+    let token: TokenResponse = state
+        .client
+        .post(metadata.token_endpoint.clone())
+        .form(&[
+            ("grant_type", "authorization_code"),
+            ("code", &code),
+            ("redirect_uri", redirect_uri.as_str()),
+            ("client_id", &oidc.client_id),
+            ("client_secret", oidc.client_secret.expose_secret()),
+            ("code_verifier", &login_state.code_verifier),
+        ])
+        .send()
+        .await?
+        .error_for_status()?
+        .json()
+        .await?;
+    let claims = decode_id_token(&token.id_token)?;
+    let email = validate_claims(
+        &claims,
+        oidc,
+        &metadata.issuer,
+        &login_state.nonce,
+        time::OffsetDateTime::now_utc().unix_timestamp(),
+    )?;
+
+    if let Some(user) = users::get_user_by_username(&state.database, &email).await? {
+        return Ok(user);
+    }
+    let role = oidc
+        .default_role
+        .wrap_err_with(|| format!("There is no user {email:?}"))?;
+    let user = User {
+        id: UserId::generate(),
+        username: email,
+        password_hash: String::new(),
+        role,
+        created_at: types::Time::now_utc(),
+    };
+    users::insert_user(&state.database, &user).await?;
+    tracing::info!(
+        "Created user {:?} with role {role:?} from OpenID Connect login",
+        user.username
+    );
+    Ok(user)
+}
+
+#[cfg(test)]
+mod test {
+    use base64::Engine;
+    use secrecy::SecretString;
+    use serde_json::json;
+
+    use super::{code_challenge, decode_id_token, validate_claims};
+    use crate::options::Oidc;
+
+    fn oidc(allowed_domains: &[&str]) -> Oidc {
+        Oidc {
+            issuer_url: "https://accounts.google.com".parse().unwrap(),
+            client_id: "client".to_owned(),
+            client_secret: SecretString::new("secret".to_owned()),
+            display_name: "Google".to_owned(),
+            allowed_domains: allowed_domains.iter().map(|d| d.to_string()).collect(),
+            default_role: None,
+        }
+    }
+
+    fn id_token(claims: serde_json::Value) -> String {
+        let engine = base64::engine::general_purpose::URL_SAFE_NO_PAD;
+        format!(
+            "{}.{}.signature",
+            engine.encode(r#"{"alg":"RS256"}"#),
+            engine.encode(claims.to_string())
+        )
+    }
+
+    #[test]
+    fn test_code_challenge() {
+        // Example from RFC 7636 Appendix B.
+        assert_eq!(
+            code_challenge("dBjftJeZ4CVP-mB92K27uhbUJU1p1r_wW1gFWFOEjXk"),
+            "E9Melhoa2OwvFrEMTJguCHaoeK1t8URWbuGJSstw-cM"
+        );
+    }
+
+    #[test]
+    fn test_validate_claims() {
+        let claims = json!({
+            "iss": "https://accounts.google.com",
+            "aud": "client",
+            "exp": 1000,
+            "nonce": "nonce",
+            "email": "Forecaster@Example.org",
+            "email_verified": true,
+        });
+        let issuer = "https://accounts.google.com";
+        let decoded = decode_id_token(&id_token(claims.clone())).unwrap();
+        assert_eq!(
+            validate_claims(&decoded, &oidc(&[]), issuer, "nonce", 999).unwrap(),
+            "forecaster@example.org"
+        );
+        assert_eq!(
+            validate_claims(&decoded, &oidc(&["example.org"]), issuer, "nonce", 999).unwrap(),
+            "forecaster@example.org"
+        );
+
+        let error = |oidc, nonce, now| {
+            validate_claims(&decoded, &oidc, issuer, nonce, now)
+                .unwrap_err()
+                .to_string()
+        };
+        assert_eq!(error(oidc(&[]), "nonce", 1000), "ID token has expired");
+        assert_eq!(
+            error(oidc(&[]), "other", 999),
+            "ID token nonce does not match"
+        );
+        assert_eq!(
+            error(oidc(&["example.com"]), "nonce", 999),
+            r#"Email address "forecaster@example.org" is not in an allowed domain"#
+        );
+
+        let mut unverified = claims.clone();
+        unverified["email_verified"] = json!(false);
+        unverified["aud"] = json!(["other", "client"]);
+        let decoded = decode_id_token(&id_token(unverified)).unwrap();
+        assert_eq!(
+            validate_claims(&decoded, &oidc(&[]), issuer, "nonce", 999)
+                .unwrap_err()
+                .to_string(),
+            r#"Email address "forecaster@example.org" has not been verified"#
+        );
+    }
+}
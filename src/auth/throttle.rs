@@ -0,0 +1,99 @@
+//! Throttling of failed logins (using the login page or HTTP basic authentication) for each client
+//! IP address and each username, to slow down guessing passwords.
+
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+/// Number of failed logins after which further logins are refused until the [`WINDOW`] has
+/// passed.
+const MAX_FAILURES: u32 = 10;
+/// Period after the first of a series of failed logins in which they are counted.
+const WINDOW: Duration = Duration::from_secs(15 * 60);
+
+struct Failures {
+    count: u32,
+    since: Instant,
+}
+
+impl Failures {
+    fn is_expired(&self, now: Instant) -> bool {
+        now.duration_since(self.since) >= WINDOW
+    }
+}
+
+/// Failed logins for each client IP address and username, cheap to clone.
+#[derive(Clone, Default)]
+pub struct LoginThrottle(Arc<Mutex<HashMap<String, Failures>>>);
+
+fn keys(client_ip: Option<&str>, username: &str) -> impl Iterator<Item = String> {
+    client_ip
+        .map(|client_ip| format!("ip:{client_ip}"))
+        .into_iter()
+        .chain(std::iter::once(format!("user:{username}")))
+}
+
+impl LoginThrottle {
+    /// Whether logins from the `client_ip`, or for the `username`, are refused because of too many
+    /// recent failures.
+    pub fn is_throttled(&self, client_ip: Option<&str>, username: &str) -> bool {
+        self.is_throttled_at(client_ip, username, Instant::now())
+    }
+
+    fn is_throttled_at(&self, client_ip: Option<&str>, username: &str, now: Instant) -> bool {
+        let failures = self.0.lock().expect("Login throttle lock poisoned");
+        keys(client_ip, username).any(|key| {
+            failures
+                .get(&key)
+                .is_some_and(|failures| !failures.is_expired(now) && failures.count >= MAX_FAILURES)
+        })
+    }
+
+    pub fn record_failure(&self, client_ip: Option<&str>, username: &str) {
+        self.record_failure_at(client_ip, username, Instant::now())
+    }
+
+    fn record_failure_at(&self, client_ip: Option<&str>, username: &str, now: Instant) {
+        let mut failures = self.0.lock().expect("Login throttle lock poisoned");
+        failures.retain(|_, failures| !failures.is_expired(now));
+        for key in keys(client_ip, username) {
+            failures
+                .entry(key)
+                .or_insert(Failures {
+                    count: 0,
+                    since: now,
+                })
+                .count += 1;
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::{Duration, Instant};
+
+    use super::{LoginThrottle, MAX_FAILURES, WINDOW};
+
+    #[test]
+    fn test_login_throttle() {
+        let throttle = LoginThrottle::default();
+        let now = Instant::now();
+        for _ in 0..MAX_FAILURES - 1 {
+            throttle.record_failure_at(Some("203.0.113.7"), "admin", now);
+        }
+        assert!(!throttle.is_throttled_at(Some("203.0.113.7"), "admin", now));
+        throttle.record_failure_at(Some("203.0.113.7"), "admin", now);
+        assert!(throttle.is_throttled_at(Some("203.0.113.7"), "admin", now));
+        // Throttled by the client IP address, or by the username.
+        assert!(throttle.is_throttled_at(Some("203.0.113.7"), "other", now));
+        assert!(throttle.is_throttled_at(Some("198.51.100.2"), "admin", now));
+        assert!(!throttle.is_throttled_at(Some("198.51.100.2"), "other", now));
+
+        let later = now + WINDOW + Duration::from_secs(1);
+        assert!(!throttle.is_throttled_at(Some("203.0.113.7"), "admin", later));
+        throttle.record_failure_at(None, "other", later);
+        assert_eq!(throttle.0.lock().unwrap().len(), 1);
+    }
+}
@@ -0,0 +1,82 @@
+//! The IP address of the client making a request, used for analytics, throttling failed logins and
+//! the [`crate::options::AdminAllowlist`]. When running behind a proxy the address is read from a
+//! header set by the proxy, such as [`crate::options::Analytics::client_ip_header`].
+
+use std::net::{IpAddr, SocketAddr};
+
+use axum::extract::ConnectInfo;
+
+/// Parse the client IP address from the value of a client IP header. `X-Forwarded-For` contains a
+/// list of addresses, the last was added by the proxy, the others could have been set by the
+/// client.
+fn parse_client_ip(value: &str) -> Option<IpAddr> {
+    value.rsplit(',').next()?.trim().parse().ok()
+}
+
+/// The IP address of the client, from the `client_ip_header` if it is configured, otherwise the
+/// address of the connection (in the request `extensions`).
+pub fn client_ip(
+    headers: &http::HeaderMap,
+    extensions: &http::Extensions,
+    client_ip_header: Option<&str>,
+) -> Option<IpAddr> {
+    let ip = match client_ip_header {
+        Some(client_ip_header) => headers
+            .get(client_ip_header)
+            .and_then(|value| value.to_str().ok())
+            .and_then(parse_client_ip),
+        None => extensions
+            .get::<ConnectInfo<SocketAddr>>()
+            .map(|ConnectInfo(address)| address.ip()),
+    }?;
+    // IPv4 clients of a dual stack listener have IPv4-mapped IPv6 addresses.
+    Some(ip.to_canonical())
+}
+
+#[cfg(test)]
+mod test {
+    use std::net::SocketAddr;
+
+    use axum::extract::ConnectInfo;
+
+    use super::{client_ip, parse_client_ip};
+
+    #[test]
+    fn test_parse_client_ip() {
+        assert_eq!(
+            parse_client_ip("203.0.113.7"),
+            Some("203.0.113.7".parse().unwrap())
+        );
+        assert_eq!(
+            parse_client_ip("10.0.0.1, 198.51.100.2, 203.0.113.7"),
+            Some("203.0.113.7".parse().unwrap())
+        );
+        assert_eq!(
+            parse_client_ip("2001:db8::1"),
+            Some("2001:db8::1".parse().unwrap())
+        );
+        assert_eq!(parse_client_ip("unknown"), None);
+    }
+
+    #[test]
+    fn test_client_ip() {
+        let mut headers = http::HeaderMap::new();
+        headers.insert("x-forwarded-for", "10.0.0.1, 203.0.113.7".parse().unwrap());
+        let mut extensions = http::Extensions::new();
+        extensions.insert(ConnectInfo(
+            "[::ffff:198.51.100.2]:443".parse::<SocketAddr>().unwrap(),
+        ));
+        assert_eq!(
+            client_ip(&headers, &extensions, Some("X-Forwarded-For")),
+            Some("203.0.113.7".parse().unwrap())
+        );
+        assert_eq!(
+            client_ip(&headers, &extensions, Some("Fly-Client-IP")),
+            None
+        );
+        assert_eq!(
+            client_ip(&headers, &extensions, None),
+            Some("198.51.100.2".parse().unwrap())
+        );
+    }
+}
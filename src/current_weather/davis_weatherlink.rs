@@ -0,0 +1,89 @@
+//! Current weather from the Davis WeatherLink v2 API <https://weatherlink.github.io/v2-api/>.
+
+use eyre::{Context, ContextCompat};
+use secrecy::ExposeSecret;
+use serde::Deserialize;
+
+use crate::options::DavisWeatherLinkSource;
+
+use super::{farenheit_to_celcius, mph_to_ms, WeatherDataItem};
+
+#[derive(Debug, Deserialize)]
+pub struct CurrentResponse {
+    pub sensors: Vec<Sensor>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Sensor {
+    #[serde(default)]
+    pub data: Vec<SensorData>,
+}
+
+/// A record of sensor data. The available fields depend on the sensor's data structure type,
+/// older devices use the `*_out` names.
+#[derive(Debug, Deserialize)]
+pub struct SensorData {
+    /// Unix timestamp in seconds.
+    pub ts: i64,
+    /// Temperature in Fahrenheit.
+    #[serde(default, alias = "temp_out")]
+    pub temp: Option<f64>,
+    /// Relative humidity in percent.
+    #[serde(default, alias = "hum_out")]
+    pub hum: Option<f64>,
+    /// Wind speed in miles per hour.
+    #[serde(default, alias = "wind_speed")]
+    pub wind_speed_last: Option<f64>,
+    /// Wind direction in degrees.
+    #[serde(default, alias = "wind_dir")]
+    pub wind_dir_last: Option<f64>,
+    /// Rain rate in millimetres per hour.
+    #[serde(default)]
+    pub rain_rate_last_mm: Option<f64>,
+}
+
+impl TryFrom<SensorData> for WeatherDataItem {
+    type Error = eyre::Error;
+
+    fn try_from(value: SensorData) -> Result<Self, Self::Error> {
+        Ok(Self {
+            time: time::OffsetDateTime::from_unix_timestamp(value.ts)
+                .wrap_err_with(|| format!("Invalid timestamp {}", value.ts))?,
+            temperature_celcius: value.temp.map(farenheit_to_celcius),
+            wind_direction_degrees: value.wind_dir_last,
+            wind_speed_ms: value.wind_speed_last.map(mph_to_ms),
+            humidity_percent: value.hum,
+            precipitation_rate_mm_per_hour: value.rain_rate_last_mm,
+        })
+    }
+}
+
+/// Query the current conditions for the station, from the sensor that measures the outdoor
+/// temperature.
+pub async fn query_current(
+    client: &reqwest::Client,
+    source: &DavisWeatherLinkSource,
+) -> eyre::Result<WeatherDataItem> {
+    let station_id = &source.station_id;
+    let response = client
+        .get(format!(
+            "https://api.weatherlink.com/v2/current/{station_id}"
+        ))
+        .query(&[("api-key", source.api_key.expose_secret())])
+        .header("X-Api-Secret", source.api_secret.expose_secret())
+        .send()
+        .await?
+        .error_for_status()
+        .wrap_err("Status code of response is an error")?
+        .json::<CurrentResponse>()
+        .await
+        .wrap_err("Error deserializing response body")?;
+
+    response
+        .sensors
+        .into_iter()
+        .flat_map(|sensor| sensor.data)
+        .find(|data| data.temp.is_some())
+        .wrap_err("No sensor with outdoor temperature data found")?
+        .try_into()
+}
@@ -0,0 +1,230 @@
+//! Weather history from the Ecowitt cloud API <https://doc.ecowitt.net/>.
+
+use std::collections::BTreeMap;
+
+use eyre::Context;
+use secrecy::ExposeSecret;
+use serde::{Deserialize, Serialize};
+
+use crate::options::EcowittSource;
+
+use super::WeatherDataItem;
+
+/// Ecowitt API unit id for degrees Celsius.
+const TEMPERATURE_UNIT_CELCIUS: u8 = 1;
+/// Ecowitt API unit id for metres per second.
+const WIND_SPEED_UNIT_MS: u8 = 6;
+/// Ecowitt API unit id for millimetres (per hour for rain rate).
+const RAINFALL_UNIT_MM: u8 = 12;
+
+#[derive(Serialize)]
+struct HistoryQuery<'a> {
+    application_key: &'a str,
+    api_key: &'a str,
+    mac: &'a str,
+    start_date: String,
+    end_date: String,
+    cycle_type: &'static str,
+    call_back: &'static str,
+    temp_unitid: u8,
+    wind_speed_unitid: u8,
+    rainfall_unitid: u8,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct HistoryResponse {
+    pub code: i64,
+    pub msg: String,
+    #[serde(default)]
+    pub data: Option<HistoryData>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct HistoryData {
+    #[serde(default)]
+    pub outdoor: Option<OutdoorData>,
+    #[serde(default)]
+    pub wind: Option<WindData>,
+    #[serde(default)]
+    pub rainfall: Option<RainfallData>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct OutdoorData {
+    #[serde(default)]
+    pub temperature: Option<Series>,
+    #[serde(default)]
+    pub humidity: Option<Series>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct WindData {
+    #[serde(default)]
+    pub wind_speed: Option<Series>,
+    #[serde(default)]
+    pub wind_direction: Option<Series>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct RainfallData {
+    #[serde(default)]
+    pub rain_rate: Option<Series>,
+}
+
+/// A series of values keyed by unix timestamp in seconds. Both keys and values are strings.
+#[derive(Debug, Default, Deserialize)]
+pub struct Series {
+    #[serde(default)]
+    pub list: BTreeMap<String, String>,
+}
+
+impl Series {
+    fn values(series: Option<&Series>) -> eyre::Result<BTreeMap<i64, f64>> {
+        series
+            .map(|series| {
+                series
+                    .list
+                    .iter()
+                    .map(|(time, value)| {
+                        Ok((
+                            time.parse::<i64>()
+                                .wrap_err_with(|| format!("Invalid timestamp {time:?}"))?,
+                            value
+                                .parse::<f64>()
+                                .wrap_err_with(|| format!("Invalid value {value:?}"))?,
+                        ))
+                    })
+                    .collect()
+            })
+            .unwrap_or_else(|| Ok(BTreeMap::new()))
+    }
+}
+
+impl HistoryData {
+    /// Combine the series into [`WeatherDataItem`]s, one for each timestamp, ordered from most
+    /// recent to oldest to match the other sources.
+    pub fn into_items(self) -> eyre::Result<Vec<WeatherDataItem>> {
+        let outdoor = self.outdoor.unwrap_or_default();
+        let wind = self.wind.unwrap_or_default();
+        let temperature = Series::values(outdoor.temperature.as_ref())?;
+        let humidity = Series::values(outdoor.humidity.as_ref())?;
+        let wind_speed = Series::values(wind.wind_speed.as_ref())?;
+        let wind_direction = Series::values(wind.wind_direction.as_ref())?;
+        let rain_rate = Series::values(
+            self.rainfall
+                .as_ref()
+                .and_then(|rainfall| rainfall.rain_rate.as_ref()),
+        )?;
+
+        let mut timestamps: Vec<i64> = temperature
+            .keys()
+            .chain(humidity.keys())
+            .chain(wind_speed.keys())
+            .chain(wind_direction.keys())
+            .chain(rain_rate.keys())
+            .copied()
+            .collect();
+        timestamps.sort_unstable();
+        timestamps.dedup();
+
+        timestamps
+            .into_iter()
+            .rev()
+            .map(|timestamp| {
+                Ok(WeatherDataItem {
+                    time: time::OffsetDateTime::from_unix_timestamp(timestamp)
+                        .wrap_err_with(|| format!("Invalid timestamp {timestamp}"))?,
+                    temperature_celcius: temperature.get(&timestamp).copied(),
+                    wind_direction_degrees: wind_direction.get(&timestamp).copied(),
+                    wind_speed_ms: wind_speed.get(&timestamp).copied(),
+                    humidity_percent: humidity.get(&timestamp).copied(),
+                    precipitation_rate_mm_per_hour: rain_rate.get(&timestamp).copied(),
+                })
+            })
+            .collect()
+    }
+}
+
+/// Query the last 24 hours of data for the device.
+pub async fn query_history(
+    client: &reqwest::Client,
+    source: &EcowittSource,
+) -> eyre::Result<Vec<WeatherDataItem>> {
+    let format = time::macros::format_description!("[year]-[month]-[day] [hour]:[minute]:[second]");
+    let end = time::OffsetDateTime::now_utc();
+    let start = end - time::Duration::DAY;
+    let query = HistoryQuery {
+        application_key: source.application_key.expose_secret(),
+        api_key: source.api_key.expose_secret(),
+        mac: &source.device_mac_address,
+        start_date: start.format(&format)?,
+        end_date: end.format(&format)?,
+        cycle_type: "auto",
+        call_back: "outdoor,wind,rainfall",
+        temp_unitid: TEMPERATURE_UNIT_CELCIUS,
+        wind_speed_unitid: WIND_SPEED_UNIT_MS,
+        rainfall_unitid: RAINFALL_UNIT_MM,
+    };
+    let response = client
+        .get("https://api.ecowitt.net/api/v3/device/history")
+        .query(&query)
+        .send()
+        .await?
+        .error_for_status()
+        .wrap_err("Status code of response is an error")?
+        .json::<HistoryResponse>()
+        .await
+        .wrap_err("Error deserializing response body")?;
+
+    if response.code != 0 {
+        eyre::bail!(
+            "Ecowitt API returned error code {}: {}",
+            response.code,
+            response.msg
+        );
+    }
+
+    response.data.unwrap_or_default().into_items()
+}
+
+#[cfg(test)]
+mod test {
+    use super::HistoryResponse;
+
+    #[test]
+    fn test_history_into_items() {
+        let response: HistoryResponse = serde_json::from_str(
+            r#"{
+                "code": 0,
+                "msg": "success",
+                "time": "1700003600",
+                "data": {
+                    "outdoor": {
+                        "temperature": {
+                            "unit": "℃",
+                            "list": { "1700000000": "-2.5", "1700000300": "-2.1" }
+                        },
+                        "humidity": {
+                            "unit": "%",
+                            "list": { "1700000000": "80", "1700000300": "81" }
+                        }
+                    },
+                    "wind": {
+                        "wind_speed": {
+                            "unit": "m/s",
+                            "list": { "1700000000": "3.4" }
+                        }
+                    }
+                }
+            }"#,
+        )
+        .unwrap();
+        let items = response.data.unwrap().into_items().unwrap();
+        assert_eq!(items.len(), 2);
+        assert_eq!(items[0].time.unix_timestamp(), 1700000300);
+        assert_eq!(items[0].temperature_celcius, Some(-2.1));
+        assert_eq!(items[0].wind_speed_ms, None);
+        assert_eq!(items[1].humidity_percent, Some(80.0));
+        assert_eq!(items[1].wind_speed_ms, Some(3.4));
+    }
+}
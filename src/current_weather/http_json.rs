@@ -0,0 +1,339 @@
+//! Weather data from an arbitrary HTTP JSON API, normalized using the field mapping in
+//! [`HttpJsonSource`].
+
+use std::str::FromStr;
+
+use eyre::{Context, ContextCompat};
+use secrecy::ExposeSecret;
+use serde_json::Value;
+
+use crate::options::{HttpJsonFields, HttpJsonSource, SpeedUnit, TemperatureUnit, TimeFormat};
+
+use super::{farenheit_to_celcius, mph_to_ms, WeatherDataItem};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Segment {
+    Member(String),
+    Index(usize),
+    Wildcard,
+}
+
+/// A path to values within a JSON document, using a subset of JSONPath syntax.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct JsonPath(Vec<Segment>);
+
+impl FromStr for JsonPath {
+    type Err = eyre::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut rest = s
+            .trim()
+            .strip_prefix('$')
+            .wrap_err_with(|| format!("JSON path {s:?} should start with `$`"))?;
+        let mut segments = Vec::new();
+        while !rest.is_empty() {
+            if let Some(member) = rest.strip_prefix('.') {
+                let end = member.find(['.', '[']).unwrap_or(member.len());
+                if end == 0 {
+                    eyre::bail!("Empty member name in JSON path {s:?}");
+                }
+                segments.push(Segment::Member(member[..end].to_owned()));
+                rest = &member[end..];
+            } else if let Some(index) = rest.strip_prefix('[') {
+                let end = index
+                    .find(']')
+                    .wrap_err_with(|| format!("Unclosed `[` in JSON path {s:?}"))?;
+                let segment = match &index[..end] {
+                    "*" => Segment::Wildcard,
+                    index => Segment::Index(
+                        index
+                            .parse()
+                            .wrap_err_with(|| format!("Invalid index in JSON path {s:?}"))?,
+                    ),
+                };
+                segments.push(segment);
+                rest = &index[end + 1..];
+            } else {
+                eyre::bail!("Unexpected {rest:?} in JSON path {s:?}");
+            }
+        }
+        Ok(Self(segments))
+    }
+}
+
+impl JsonPath {
+    /// Select all the values in `value` matching this path.
+    pub fn select<'a>(&self, value: &'a Value) -> Vec<&'a Value> {
+        let mut selected = vec![value];
+        for segment in &self.0 {
+            selected = selected
+                .into_iter()
+                .flat_map(|value| -> Vec<&'a Value> {
+                    match segment {
+                        Segment::Member(name) => value.get(name).into_iter().collect(),
+                        Segment::Index(index) => value.get(index).into_iter().collect(),
+                        Segment::Wildcard => match value {
+                            Value::Array(values) => values.iter().collect(),
+                            Value::Object(values) => values.values().collect(),
+                            _ => Vec::new(),
+                        },
+                    }
+                })
+                .collect();
+        }
+        selected
+    }
+
+    /// Select the first value matching this path, ignoring `null`.
+    pub fn select_first<'a>(&self, value: &'a Value) -> Option<&'a Value> {
+        self.select(value)
+            .into_iter()
+            .find(|value| !value.is_null())
+    }
+}
+
+/// Read a number which may be encoded as a JSON number or a string.
+fn as_number(value: &Value) -> eyre::Result<f64> {
+    match value {
+        Value::Number(number) => number
+            .as_f64()
+            .wrap_err_with(|| format!("Unable to represent {number} as f64")),
+        Value::String(string) => string
+            .trim()
+            .parse()
+            .wrap_err_with(|| format!("Invalid number {string:?}")),
+        _ => eyre::bail!("Expected a number, found {value}"),
+    }
+}
+
+fn parse_time(value: &Value, format: TimeFormat) -> eyre::Result<time::OffsetDateTime> {
+    Ok(match format {
+        TimeFormat::Rfc3339 => {
+            let string = value
+                .as_str()
+                .wrap_err_with(|| format!("Expected a string, found {value}"))?;
+            time::OffsetDateTime::parse(string, &time::format_description::well_known::Rfc3339)
+                .wrap_err_with(|| format!("Invalid RFC 3339 time {string:?}"))?
+        }
+        TimeFormat::UnixSeconds => {
+            time::OffsetDateTime::from_unix_timestamp(as_number(value)? as i64)?
+        }
+        TimeFormat::UnixMilliseconds => time::OffsetDateTime::from_unix_timestamp_nanos(
+            (as_number(value)? as i128) * 1_000_000,
+        )?,
+    })
+}
+
+fn temperature_celcius(value: f64, unit: TemperatureUnit) -> f64 {
+    match unit {
+        TemperatureUnit::Celsius => value,
+        TemperatureUnit::Fahrenheit => farenheit_to_celcius(value),
+    }
+}
+
+fn speed_ms(value: f64, unit: SpeedUnit) -> f64 {
+    match unit {
+        SpeedUnit::MetresPerSecond => value,
+        SpeedUnit::KilometresPerHour => value / 3.6,
+        SpeedUnit::MilesPerHour => mph_to_ms(value),
+        SpeedUnit::Knots => value * 0.514444,
+    }
+}
+
+/// [`HttpJsonFields`] with the paths parsed.
+struct FieldMapping {
+    time: (JsonPath, TimeFormat),
+    temperature: Option<(JsonPath, TemperatureUnit)>,
+    humidity: Option<JsonPath>,
+    wind_speed: Option<(JsonPath, SpeedUnit)>,
+    wind_direction: Option<JsonPath>,
+    precipitation_rate: Option<JsonPath>,
+}
+
+impl TryFrom<&HttpJsonFields> for FieldMapping {
+    type Error = eyre::Error;
+
+    fn try_from(fields: &HttpJsonFields) -> Result<Self, Self::Error> {
+        Ok(Self {
+            time: (fields.time.path.parse()?, fields.time.format),
+            temperature: fields
+                .temperature
+                .as_ref()
+                .map(|field| Ok::<_, eyre::Error>((field.path.parse()?, field.unit)))
+                .transpose()?,
+            humidity: fields
+                .humidity
+                .as_deref()
+                .map(JsonPath::from_str)
+                .transpose()?,
+            wind_speed: fields
+                .wind_speed
+                .as_ref()
+                .map(|field| Ok::<_, eyre::Error>((field.path.parse()?, field.unit)))
+                .transpose()?,
+            wind_direction: fields
+                .wind_direction
+                .as_deref()
+                .map(JsonPath::from_str)
+                .transpose()?,
+            precipitation_rate: fields
+                .precipitation_rate
+                .as_deref()
+                .map(JsonPath::from_str)
+                .transpose()?,
+        })
+    }
+}
+
+/// Check that the paths in the `source` are valid, see [`JsonPath`].
+pub fn validate_source(source: &HttpJsonSource) -> eyre::Result<()> {
+    source
+        .records
+        .parse::<JsonPath>()
+        .wrap_err("Invalid records path")?;
+    FieldMapping::try_from(&source.fields).wrap_err("Invalid field path")?;
+    Ok(())
+}
+
+impl FieldMapping {
+    fn number(path: Option<&JsonPath>, record: &Value) -> eyre::Result<Option<f64>> {
+        path.and_then(|path| path.select_first(record))
+            .map(as_number)
+            .transpose()
+    }
+
+    fn read_record(&self, record: &Value) -> eyre::Result<WeatherDataItem> {
+        let (time_path, time_format) = &self.time;
+        let time = time_path
+            .select_first(record)
+            .wrap_err("Record is missing the time field")?;
+        Ok(WeatherDataItem {
+            time: parse_time(time, *time_format)?,
+            temperature_celcius: Self::number(
+                self.temperature.as_ref().map(|(path, _)| path),
+                record,
+            )
+            .wrap_err("Error reading temperature")?
+            .zip(self.temperature.as_ref())
+            .map(|(value, (_, unit))| temperature_celcius(value, *unit)),
+            wind_direction_degrees: Self::number(self.wind_direction.as_ref(), record)
+                .wrap_err("Error reading wind direction")?,
+            wind_speed_ms: Self::number(self.wind_speed.as_ref().map(|(path, _)| path), record)
+                .wrap_err("Error reading wind speed")?
+                .zip(self.wind_speed.as_ref())
+                .map(|(value, (_, unit))| speed_ms(value, *unit)),
+            humidity_percent: Self::number(self.humidity.as_ref(), record)
+                .wrap_err("Error reading humidity")?,
+            precipitation_rate_mm_per_hour: Self::number(self.precipitation_rate.as_ref(), record)
+                .wrap_err("Error reading precipitation rate")?,
+        })
+    }
+}
+
+/// Convert a JSON `response` into [`WeatherDataItem`]s, most recent first.
+pub fn read_response(
+    response: &Value,
+    records: &str,
+    fields: &HttpJsonFields,
+) -> eyre::Result<Vec<WeatherDataItem>> {
+    let records_path: JsonPath = records.parse()?;
+    let mapping = FieldMapping::try_from(fields)?;
+    let mut items = records_path
+        .select(response)
+        .into_iter()
+        .map(|record| mapping.read_record(record))
+        .collect::<eyre::Result<Vec<_>>>()?;
+    items.sort_by_key(|item| std::cmp::Reverse(item.time));
+    Ok(items)
+}
+
+pub async fn query(
+    client: &reqwest::Client,
+    source: &HttpJsonSource,
+) -> eyre::Result<Vec<WeatherDataItem>> {
+    let end = time::OffsetDateTime::now_utc();
+    let start = end - time::Duration::DAY;
+    let url = source
+        .url
+        .replace("{start}", &start.unix_timestamp().to_string())
+        .replace("{end}", &end.unix_timestamp().to_string());
+    let mut request = client.get(url);
+    for header in &source.headers {
+        request = request.header(&header.name, header.value.expose_secret());
+    }
+    let response = request
+        .send()
+        .await?
+        .error_for_status()
+        .wrap_err("Status code of response is an error")?
+        .json::<Value>()
+        .await
+        .wrap_err("Error deserializing response body")?;
+    read_response(&response, &source.records, &source.fields)
+}
+
+#[cfg(test)]
+mod test {
+    use serde_json::json;
+
+    use crate::options::{
+        HttpJsonField, HttpJsonFields, HttpJsonTimeField, SpeedUnit, TemperatureUnit, TimeFormat,
+    };
+
+    use super::{read_response, JsonPath};
+
+    #[test]
+    fn test_parse_json_path() {
+        assert!("$".parse::<JsonPath>().unwrap().0.is_empty());
+        assert!("$.data.observations[*].temp".parse::<JsonPath>().is_ok());
+        assert!("$.data[0]".parse::<JsonPath>().is_ok());
+        assert!("data".parse::<JsonPath>().is_err());
+        assert!("$.data[".parse::<JsonPath>().is_err());
+        assert!("$..data".parse::<JsonPath>().is_err());
+    }
+
+    #[test]
+    fn test_select() {
+        let value = json!({ "data": [{ "a": 1 }, { "a": 2 }, { "b": 3 }] });
+        let path: JsonPath = "$.data[*].a".parse().unwrap();
+        assert_eq!(path.select(&value), vec![&json!(1), &json!(2)]);
+        let path: JsonPath = "$.data[2].b".parse().unwrap();
+        assert_eq!(path.select_first(&value), Some(&json!(3)));
+    }
+
+    #[test]
+    fn test_read_response() {
+        let response = json!({
+            "observations": [
+                { "ts": 1700000000, "temp_f": 32.0, "wind": { "kph": "36" }, "rh": 80 },
+                { "ts": 1700000600, "temp_f": 50.0, "wind": { "kph": null }, "rh": 75 },
+            ]
+        });
+        let fields = HttpJsonFields {
+            time: HttpJsonTimeField {
+                path: "$.ts".to_owned(),
+                format: TimeFormat::UnixSeconds,
+            },
+            temperature: Some(HttpJsonField {
+                path: "$.temp_f".to_owned(),
+                unit: TemperatureUnit::Fahrenheit,
+            }),
+            humidity: Some("$.rh".to_owned()),
+            wind_speed: Some(HttpJsonField {
+                path: "$.wind.kph".to_owned(),
+                unit: SpeedUnit::KilometresPerHour,
+            }),
+            wind_direction: None,
+            precipitation_rate: None,
+        };
+        let items = read_response(&response, "$.observations[*]", &fields).unwrap();
+        assert_eq!(items.len(), 2);
+        assert_eq!(items[0].time.unix_timestamp(), 1700000600);
+        assert_eq!(items[0].temperature_celcius, Some(10.0));
+        assert_eq!(items[0].wind_speed_ms, None);
+        assert_eq!(items[1].temperature_celcius, Some(0.0));
+        assert!((items[1].wind_speed_ms.unwrap() - 10.0).abs() < 1e-9);
+        assert_eq!(items[1].humidity_percent, Some(80.0));
+        assert_eq!(items[1].wind_direction_degrees, None);
+    }
+}
@@ -0,0 +1,116 @@
+//! Endpoint for weather stations that push their data, configured with
+//! [`crate::options::PushSource`].
+
+use std::sync::Arc;
+
+use axum::{
+    extract::{Path, State},
+    response::{IntoResponse, Response},
+    Json,
+};
+use http::{header::AUTHORIZATION, HeaderMap, StatusCode};
+use secrecy::ExposeSecret;
+use serde::Deserialize;
+
+use crate::{error::map_eyre_error, options::WeatherStationSource};
+
+use super::{accumulate_history, store_cache, CurrentWeatherService, PathParams, WeatherDataItem};
+
+/// Maximum number of items accepted in a single request.
+const MAX_ITEMS: usize = 1000;
+
+/// Either a single item or a batch of items.
+#[derive(Deserialize)]
+#[serde(untagged)]
+pub enum IngestPayload {
+    One(WeatherDataItem),
+    Many(Vec<WeatherDataItem>),
+}
+
+impl IngestPayload {
+    fn into_items(self) -> Vec<WeatherDataItem> {
+        match self {
+            IngestPayload::One(item) => vec![item],
+            IngestPayload::Many(items) => items,
+        }
+    }
+}
+
+/// Compare in constant time to avoid leaking information about the token through timing.
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |acc, (a, b)| acc | (a ^ b)) == 0
+}
+
+fn bearer_token(headers: &HeaderMap) -> Option<&str> {
+    headers
+        .get(AUTHORIZATION)?
+        .to_str()
+        .ok()?
+        .strip_prefix("Bearer ")
+}
+
+pub async fn handler(
+    Path(path): Path<PathParams>,
+    State(service): State<Arc<CurrentWeatherService>>,
+    headers: HeaderMap,
+    Json(payload): Json<IngestPayload>,
+) -> axum::response::Result<Response> {
+    let id = path.weather_station_id;
+    let source = match service
+        .weather_stations
+        .get(&id)
+        .map(|station| &station.source)
+    {
+        Some(WeatherStationSource::Push(source)) => source,
+        _ => return Ok(StatusCode::NOT_FOUND.into_response()),
+    };
+    let authorized = bearer_token(&headers)
+        .map(|token| constant_time_eq(token.as_bytes(), source.token.expose_secret().as_bytes()))
+        .unwrap_or(false);
+    if !authorized {
+        return Ok(StatusCode::UNAUTHORIZED.into_response());
+    }
+
+    let items = payload.into_items();
+    if items.len() > MAX_ITEMS {
+        return Ok((
+            StatusCode::PAYLOAD_TOO_LARGE,
+            format!("A maximum of {MAX_ITEMS} items can be submitted per request"),
+        )
+            .into_response());
+    }
+    tracing::debug!("Ingesting {} items for weather station {id}", items.len());
+    let data = accumulate_history(&service.database, &id, items)
+        .await
+        .map_err(map_eyre_error)?;
+    store_cache(&service.database, &id, data)
+        .await
+        .map_err(map_eyre_error)?;
+    Ok(StatusCode::NO_CONTENT.into_response())
+}
+
+#[cfg(test)]
+mod test {
+    use super::{constant_time_eq, IngestPayload};
+
+    #[test]
+    fn test_constant_time_eq() {
+        assert!(constant_time_eq(b"token", b"token"));
+        assert!(!constant_time_eq(b"token", b"tokem"));
+        assert!(!constant_time_eq(b"token", b"token1"));
+    }
+
+    #[test]
+    fn test_deserialize_payload() {
+        let one: IngestPayload = serde_json::from_str(
+            r#"{"time":"2024-01-24T13:00:00Z","temperature_celcius":-5.0,"wind_direction_degrees":null,"wind_speed_ms":3.0,"humidity_percent":90.0}"#,
+        )
+        .unwrap();
+        assert_eq!(one.into_items().len(), 1);
+        let many: IngestPayload = serde_json::from_str(
+            r#"[{"time":"2024-01-24T13:00:00Z","temperature_celcius":-5.0,"wind_direction_degrees":null,"wind_speed_ms":null,"humidity_percent":null},{"time":"2024-01-24T13:05:00Z","temperature_celcius":-5.1,"wind_direction_degrees":null,"wind_speed_ms":null,"humidity_percent":null}]"#,
+        )
+        .unwrap();
+        assert_eq!(many.into_items().len(), 2);
+    }
+}
@@ -5,7 +5,7 @@ use std::collections::HashMap;
 use axum::{
     extract::{Path, State},
     response::{ErrorResponse, IntoResponse, Response},
-    routing::get,
+    routing::{get, post},
     Extension, Json, Router,
 };
 use eyre::{bail, Context, ContextCompat};
@@ -14,14 +14,39 @@ use serde::{Deserialize, Serialize};
 use tracing::Instrument;
 
 use crate::{
+    alerts::Alerts,
     database::Database,
     error::map_eyre_error,
-    options::{AmbientWeatherSource, WeatherStation, WeatherStationId},
+    options::{AmbientWeatherSource, WeatherStation, WeatherStationId, WeatherStationSource},
+    shutdown::Shutdown,
     state::AppState,
     templates::{render, TemplatesWithContext},
-    user_preferences::{UserPreferences, WindUnit},
+    user_preferences::{TemperatureUnit, UserPreferences, WindUnit},
 };
 
+mod davis_weatherlink;
+mod ecowitt;
+mod http_json;
+mod ingest;
+
+/// How long to keep accumulated data for sources that only provide current conditions.
+const ACCUMULATED_HISTORY_DURATION: time::Duration = time::Duration::DAY;
+/// Interval between fetching the data for all the weather stations, see
+/// [`CurrentWeatherCacheServiceConfig::interval`].
+pub const CACHE_INTERVAL: std::time::Duration = std::time::Duration::from_secs(60);
+/// Time between fetching the data for each weather station, see
+/// [`CurrentWeatherCacheServiceConfig::each_station_interval`].
+pub const CACHE_EACH_STATION_INTERVAL: std::time::Duration = std::time::Duration::from_secs(2);
+
+/// Check the configuration of a weather station's `source` which can be checked without making
+/// any requests.
+pub fn validate_source(source: &WeatherStationSource) -> eyre::Result<()> {
+    match source {
+        WeatherStationSource::HttpJson(source) => http_json::validate_source(source),
+        _ => Ok(()),
+    }
+}
+
 #[derive(Clone, Debug, Deserialize)]
 pub struct QueryDeviceDataResponseItem {
     #[serde(default, skip_serializing_if = "Option::is_none")]
@@ -84,6 +109,8 @@ pub struct WeatherDataItem {
     pub wind_direction_degrees: Option<f64>,
     pub wind_speed_ms: Option<f64>,
     pub humidity_percent: Option<f64>,
+    #[serde(default)]
+    pub precipitation_rate_mm_per_hour: Option<f64>,
 }
 
 fn farenheit_to_celcius(temperature: f64) -> f64 {
@@ -94,6 +121,10 @@ fn mph_to_ms(speed: f64) -> f64 {
     speed * 0.44704
 }
 
+fn inches_to_mm(length: f64) -> f64 {
+    length * 25.4
+}
+
 impl TryFrom<QueryDeviceDataResponseItem> for WeatherDataItem {
     type Error = eyre::Error;
 
@@ -104,6 +135,7 @@ impl TryFrom<QueryDeviceDataResponseItem> for WeatherDataItem {
             wind_direction_degrees: value.winddir,
             wind_speed_ms: value.windspeedmph.map(mph_to_ms),
             humidity_percent: value.humidity,
+            precipitation_rate_mm_per_hour: value.hourlyrainin.map(inches_to_mm),
         })
     }
 }
@@ -140,25 +172,46 @@ impl CurrentWeatherService {
         self.weather_stations.keys().cloned().collect()
     }
 
+    /// Weather stations located in the forecast `area`, including those which don't belong to
+    /// any particular area. All weather stations if `area` is `None`.
+    pub fn area_weather_stations(&self, area: Option<&str>) -> Vec<WeatherStationId> {
+        self.weather_stations
+            .iter()
+            .filter(|(_, station)| match (area, &station.area) {
+                (Some(area), Some(station_area)) => station_area.eq_ignore_ascii_case(area),
+                _ => true,
+            })
+            .map(|(id, _)| id.clone())
+            .collect()
+    }
+
     pub async fn current_weather(
         &self,
         id: &WeatherStationId,
     ) -> eyre::Result<Vec<WeatherDataItem>> {
-        // Type override to workaround https://github.com/launchbadge/sqlx/issues/1979
-        Ok(sqlx::query_as!(
-            CurrentWeatherCache,
-            r#"SELECT weather_station_id, data as "data!: sqlx::types::Json<Vec<WeatherDataItem>>" FROM current_weather_cache WHERE weather_station_id = ?"#,
-            id,
-        )
-        .fetch_all(&self.database)
-        .await
-        .wrap_err("Error fetching current weather cache item")?
-        .into_iter()
-        .flat_map(|row| row.data.0)
-        .collect())
+        get_cached_data(&self.database, id).await
     }
 }
 
+/// Get the cached weather data for the station, most recent first.
+pub async fn get_cached_data(
+    database: &Database,
+    id: &WeatherStationId,
+) -> eyre::Result<Vec<WeatherDataItem>> {
+    // Type override to workaround https://github.com/launchbadge/sqlx/issues/1979
+    Ok(sqlx::query_as!(
+        CurrentWeatherCache,
+        r#"SELECT weather_station_id, data as "data!: sqlx::types::Json<Vec<WeatherDataItem>>" FROM current_weather_cache WHERE weather_station_id = ?"#,
+        id,
+    )
+    .fetch_all(database)
+    .await
+    .wrap_err("Error fetching current weather cache item")?
+    .into_iter()
+    .flat_map(|row| row.data.0)
+    .collect())
+}
+
 #[derive(Deserialize)]
 pub struct PathParams {
     weather_station_id: WeatherStationId,
@@ -175,6 +228,7 @@ pub fn router() -> Router<AppState> {
             "/available-weather-stations",
             get(available_weather_stations_handler),
         )
+        .route("/ingest/{weather_station_id}", post(ingest::handler))
 }
 
 pub struct CurrentWeatherCacheServiceConfig {
@@ -183,6 +237,8 @@ pub struct CurrentWeatherCacheServiceConfig {
     pub weather_stations: &'static HashMap<WeatherStationId, WeatherStation>,
     pub client: reqwest::Client,
     pub database: Database,
+    /// Notified when fetching the data for a weather station fails repeatedly.
+    pub alerts: Alerts,
 }
 
 /// Service for fetching and caching current weather data to avoid API limits and improve
@@ -196,6 +252,48 @@ pub struct CurrentWeatherCache {
     pub data: sqlx::types::Json<Vec<WeatherDataItem>>,
 }
 
+async fn store_cache(
+    database: &Database,
+    id: &WeatherStationId,
+    data: Vec<WeatherDataItem>,
+) -> eyre::Result<()> {
+    let current_weather = CurrentWeatherCache {
+        weather_station_id: id.clone(),
+        data: sqlx::types::Json(data),
+    };
+
+    sqlx::query!(
+        "INSERT INTO current_weather_cache VALUES($1, $2) ON CONFLICT(weather_station_id) DO UPDATE SET data=excluded.data",
+        current_weather.weather_station_id,
+        current_weather.data,
+    ).execute(database).await?;
+    Ok(())
+}
+
+/// Add the `new` items to the previously cached data for the station, for sources which don't
+/// provide a history. Items replace any cached items with the same time, and data older than
+/// [`ACCUMULATED_HISTORY_DURATION`] before the most recent item is discarded.
+async fn accumulate_history(
+    database: &Database,
+    id: &WeatherStationId,
+    new: Vec<WeatherDataItem>,
+) -> eyre::Result<Vec<WeatherDataItem>> {
+    let cached = get_cached_data(database, id).await?;
+
+    let mut data: Vec<WeatherDataItem> = cached
+        .into_iter()
+        .filter(|item| !new.iter().any(|new_item| new_item.time == item.time))
+        .collect();
+    data.extend(new);
+    // Most recent first, matching the order of the other sources.
+    data.sort_by_key(|item| std::cmp::Reverse(item.time));
+    if let Some(latest) = data.first().map(|item| item.time) {
+        let oldest = latest - ACCUMULATED_HISTORY_DURATION;
+        data.retain(|item| item.time >= oldest);
+    }
+    Ok(data)
+}
+
 impl CurrentWeatherCacheService {
     pub fn try_new(config: CurrentWeatherCacheServiceConfig) -> eyre::Result<Self> {
         if config
@@ -223,28 +321,46 @@ impl CurrentWeatherCacheService {
                 .ambient_weather_query_device_data(source)
                 .await
                 .wrap_err("Error querying ambient weather device data")?,
+            crate::options::WeatherStationSource::DavisWeatherLink(source) => {
+                let current = davis_weatherlink::query_current(&self.config.client, source)
+                    .await
+                    .wrap_err("Error querying Davis WeatherLink current conditions")?;
+                accumulate_history(&self.config.database, id, vec![current]).await?
+            }
+            crate::options::WeatherStationSource::Ecowitt(source) => {
+                ecowitt::query_history(&self.config.client, source)
+                    .await
+                    .wrap_err("Error querying Ecowitt device history")?
+            }
+            crate::options::WeatherStationSource::HttpJson(source) => {
+                http_json::query(&self.config.client, source)
+                    .await
+                    .wrap_err_with(|| format!("Error querying weather data from {}", source.url))?
+            }
+            // Data is pushed to the ingest endpoint.
+            crate::options::WeatherStationSource::Push(_) => return Ok(()),
         };
-        let current_weather = CurrentWeatherCache {
-            weather_station_id: id.clone(),
-            data: sqlx::types::Json(weather_data),
-        };
-
-        sqlx::query!(
-            "INSERT INTO current_weather_cache VALUES($1, $2) ON CONFLICT(weather_station_id) DO UPDATE SET data=excluded.data",
-            current_weather.weather_station_id,
-            current_weather.data,
-        ).execute(&self.config.database).await?;
-        Ok(())
+        store_cache(&self.config.database, id, weather_data).await
     }
 
     async fn fetch_and_cache_current_weather(&self) -> eyre::Result<()> {
         loop {
             let before_requests_time = tokio::time::Instant::now();
             for (id, station) in self.config.weather_stations {
-                if let Err(error) = self.fetch_and_update_station(id, station).await {
-                    tracing::error!(
-                        "Error fetching and updating weather data for station {id}: {error:?}"
-                    );
+                if station.source.is_push() {
+                    continue;
+                }
+                let task = format!("weather station {id}");
+                match self.fetch_and_update_station(id, station).await {
+                    Ok(()) => self.config.alerts.success(&task),
+                    Err(error) => {
+                        metrics::counter!("weather_station_fetch_failures_total", "weather_station" => id.to_string())
+                            .increment(1);
+                        tracing::error!(
+                            "Error fetching and updating weather data for station {id}: {error:?}"
+                        );
+                        self.config.alerts.failure(&task, &error);
+                    }
                 }
                 tokio::time::sleep(self.config.each_station_interval).await;
             }
@@ -288,22 +404,30 @@ impl CurrentWeatherCacheService {
             .collect()
     }
 
-    pub fn spawn(self) {
+    /// Spawn the service, which stops when `shutdown` is requested. There is no pending work to
+    /// finish, so any requests in progress are cancelled.
+    pub fn spawn(self, mut shutdown: Shutdown) {
         tokio::spawn(
             async move {
                 tracing::info!("Spawned current weather cache service");
                 loop {
                     let before_requests_time = std::time::Instant::now();
-                    if let Err(error) = self.fetch_and_cache_current_weather().await {
+                    let result = tokio::select! {
+                        result = self.fetch_and_cache_current_weather() => result,
+                        _ = shutdown.requested() => return,
+                    };
+                    if let Err(error) = result {
                         tracing::error!("Error fetching and caching current weather: {error:?}")
                     };
                     let after_requests_time = std::time::Instant::now();
                     let requests_duration = after_requests_time - before_requests_time;
-                    tokio::time::sleep(std::time::Duration::max(
-                        self.config.interval - requests_duration,
-                        self.config.each_station_interval,
-                    ))
-                    .await;
+                    tokio::select! {
+                        _ = tokio::time::sleep(std::time::Duration::max(
+                            self.config.interval - requests_duration,
+                            self.config.each_station_interval,
+                        )) => {}
+                        _ = shutdown.requested() => return,
+                    }
                 }
             }
             .instrument(tracing::error_span!("current_weather_cache")),
@@ -315,21 +439,25 @@ impl CurrentWeatherCacheService {
 pub struct CurrentWeatherContext {
     pub weather_stations: HashMap<WeatherStationId, Vec<WeatherDataItem>>,
     pub wind_unit: WindUnit,
+    pub temperature_unit: TemperatureUnit,
 }
 
 impl CurrentWeatherContext {
     pub async fn from_service(
         service: &CurrentWeatherService,
         wind_unit: WindUnit,
+        temperature_unit: TemperatureUnit,
+        area: Option<&str>,
     ) -> eyre::Result<Self> {
         let mut weather_stations = HashMap::new();
-        for id in service.available_weather_stations() {
+        for id in service.area_weather_stations(area) {
             let data = service.current_weather(&id).await?;
             weather_stations.insert(id, data);
         }
         Ok(Self {
             weather_stations,
             wind_unit,
+            temperature_unit,
         })
     }
 }
@@ -338,6 +466,10 @@ impl CurrentWeatherContext {
 #[serde(default)]
 pub struct Query {
     wind_unit: Option<WindUnit>,
+    temperature_unit: Option<TemperatureUnit>,
+    /// Only show the weather stations for this forecast area, see
+    /// [`CurrentWeatherService::area_weather_stations`].
+    area: Option<String>,
 }
 
 pub async fn handler(
@@ -353,6 +485,11 @@ pub async fn handler(
             .wind_unit
             .or(preferences.wind_unit)
             .unwrap_or_default(),
+        query
+            .temperature_unit
+            .or(preferences.temperature_unit)
+            .unwrap_or_default(),
+        query.area.as_deref(),
     )
     .await
     .map_err(map_eyre_error)?;
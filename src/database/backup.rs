@@ -1,64 +1,313 @@
-use std::time::Duration;
+use std::{
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::Duration,
+};
 
+use aes_gcm::{
+    aead::{Aead, AeadCore, KeyInit, OsRng},
+    Aes256Gcm, Nonce,
+};
 use base64::Engine;
+use cronchik::CronSchedule;
 use eyre::{bail, Context, ContextCompat};
 use humansize::format_size;
 use md5::{Digest, Md5};
 use rusty_s3::{Credentials, S3Action, UrlStyle};
 use secrecy::{ExposeSecret, SecretString};
+use serde::Serialize;
+use sqlx::{
+    sqlite::{SqliteConnectOptions, SqliteConnection},
+    Connection,
+};
 use time::OffsetDateTime;
+use tokio::io::AsyncWriteExt;
 use tracing::Instrument;
 
-use crate::options;
+use crate::{alerts::Alerts, options, shutdown::Shutdown, types};
 
-use super::{Database, DB_FILE_NAME};
+use super::{
+    blob::{self, BlobHash, BlobStore},
+    Database, DB_FILE_NAME,
+};
+
+/// Name of the file in the data directory containing a verified backup which replaces the
+/// database the next time the application starts, see [`apply_staged_restore`].
+pub const RESTORE_FILE_NAME: &str = "db.sqlite3.restore";
+
+/// Extension added to the name of encrypted backups.
+const ENCRYPTED_EXTENSION: &str = "enc";
+
+/// Header at the start of encrypted backups, followed by the nonce and the ciphertext.
+const ENCRYPTED_HEADER: &[u8] = b"avalanche-report-backup-v1\n";
+
+/// Prefix of the keys (or directory of a local backup) where the blobs which can't be recreated
+/// (see [`blob::backed_up_hashes`]) are stored alongside the database backups. Blobs are never
+/// modified, so each one is only uploaded once, and is shared by all the backups at the
+/// destination.
+const BLOBS_PREFIX: &str = "blobs";
+
+/// Prevents backups and restores from running at the same time.
+static BACKUP_LOCK: tokio::sync::Mutex<()> = tokio::sync::Mutex::const_new(());
 
 #[derive(Debug)]
 struct BackupInfo {
     size: u64,
     entity_tag: String,
     version_id: Option<String>,
+    /// Only used in the debug log of the backup.
+    #[allow(dead_code)]
     expiration: Option<String>,
+    /// Base64 encoded MD5 hash of the backup file.
+    md5: String,
+    /// The object key or file name of the backup.
+    key: String,
 }
 
-async fn perform_backup(config: &Config) -> eyre::Result<BackupInfo> {
-    tracing::info!("Starting backup to s3...");
-    let options::Backup {
-        s3_endpoint,
-        s3_bucket_name,
-        s3_bucket_region,
-        aws_access_key_id,
-        ..
-    } = &config.backup;
-    let client = &config.client;
-    let database = &config.database;
+/// A backup recorded in the `backups` table.
+#[derive(Debug, Serialize)]
+pub struct BackupRecord {
+    pub id: i64,
+    pub created_at: types::Time,
+    pub size: i64,
+    pub entity_tag: String,
+    pub version_id: Option<String>,
+    /// Base64 encoded MD5 hash of the backup file.
+    pub md5: String,
+    pub verified_at: Option<types::Time>,
+    pub verification_error: Option<String>,
+    /// See [`Config::destination`].
+    pub destination: Option<String>,
+    /// The object key or file name of the backup, `None` for backups recorded before this was
+    /// added, which used [`DB_FILE_NAME`].
+    pub key: Option<String>,
+}
+
+/// Which backup to verify or restore.
+#[derive(Debug, Clone, Copy)]
+pub enum BackupSelector {
+    /// The most recent backup at the destination, which may not be recorded in the database (e.g.
+    /// when restoring into a new deployment).
+    Latest,
+    /// A backup recorded in the database, by id.
+    Recorded(i64),
+}
+
+impl std::str::FromStr for BackupSelector {
+    type Err = std::num::ParseIntError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "latest" => Ok(Self::Latest),
+            _ => s.parse().map(Self::Recorded),
+        }
+    }
+}
+
+/// Where backups are sent.
+#[derive(Debug, Clone, Copy)]
+pub enum Target {
+    S3(&'static options::Backup),
+    Local(&'static options::LocalBackup),
+}
+
+impl Target {
+    fn schedule(self) -> &'static CronSchedule {
+        match self {
+            Target::S3(backup) => &backup.schedule,
+            Target::Local(backup) => &backup.schedule,
+        }
+    }
+
+    fn encryption_key(self) -> Option<&'static SecretString> {
+        match self {
+            Target::S3(backup) => backup.encryption_key.as_ref(),
+            Target::Local(backup) => backup.encryption_key.as_ref(),
+        }
+    }
+}
+
+pub struct Config {
+    pub client: reqwest::Client,
+    pub database: Database,
+    pub blobs: Arc<BlobStore>,
+    /// The name of the destination in [`options::Options::backup_destinations`], or `None` for
+    /// [`options::Options::backup`].
+    pub destination: Option<&'static str>,
+    pub target: Target,
+}
 
-    let bucket = rusty_s3::Bucket::new(
-        s3_endpoint.clone(),
+impl Config {
+    /// The config for the `destination` (see [`Config::destination`]), if it is configured.
+    pub fn new(
+        options: &'static options::Options,
+        destination: Option<&str>,
+        client: reqwest::Client,
+        database: Database,
+        blobs: Arc<BlobStore>,
+    ) -> Option<Self> {
+        let (destination, target) = match destination {
+            None => (None, Target::S3(options.backup.as_ref()?)),
+            Some(destination) => {
+                let (name, backup_destination) =
+                    options.backup_destinations.get_key_value(destination)?;
+                let target = match backup_destination {
+                    options::BackupDestination::S3(backup) => Target::S3(backup),
+                    options::BackupDestination::Local(backup) => Target::Local(backup),
+                };
+                (Some(name.as_str()), target)
+            }
+        };
+        Some(Self {
+            client,
+            database,
+            blobs,
+            destination,
+            target,
+        })
+    }
+
+    /// The configs for all the configured destinations.
+    pub fn all(
+        options: &'static options::Options,
+        client: reqwest::Client,
+        database: Database,
+        blobs: Arc<BlobStore>,
+    ) -> Vec<Self> {
+        std::iter::once(None)
+            .chain(
+                options
+                    .backup_destinations
+                    .keys()
+                    .map(|name| Some(name.as_str())),
+            )
+            .filter_map(|destination| {
+                Self::new(
+                    options,
+                    destination,
+                    client.clone(),
+                    database.clone(),
+                    blobs.clone(),
+                )
+            })
+            .collect()
+    }
+
+    /// Name of the destination for logging.
+    fn name(&self) -> &str {
+        self.destination.unwrap_or("default")
+    }
+}
+
+fn bucket(backup: &options::Backup) -> eyre::Result<rusty_s3::Bucket> {
+    Ok(rusty_s3::Bucket::new(
+        backup.s3_endpoint.clone(),
         UrlStyle::VirtualHost,
-        s3_bucket_name,
-        s3_bucket_region,
-    )?;
+        backup.s3_bucket_name.clone(),
+        backup.s3_bucket_region.clone(),
+    )?)
+}
 
-    let credentials = Credentials::new(
-        aws_access_key_id,
-        &*config.aws_secret_access_key.expose_secret(),
-    );
+fn credentials(backup: &options::Backup) -> Credentials {
+    Credentials::new(
+        &backup.aws_access_key_id,
+        backup.aws_secret_access_key.expose_secret(),
+    )
+}
 
-    let head_bucket = bucket.head_bucket(Some(&credentials));
-    let response = client
-        .head(head_bucket.sign(Duration::from_secs(60 * 60)))
-        .send()
-        .await?;
-    if response.status() == reqwest::StatusCode::NOT_FOUND {
-        bail!("Unable to perform backup, the bucket {s3_bucket_name} does not exist in the region {s3_bucket_region}")
+fn cipher(encryption_key: &SecretString) -> eyre::Result<Aes256Gcm> {
+    let key = base64::engine::general_purpose::STANDARD
+        .decode(encryption_key.expose_secret().trim())
+        .wrap_err("Backup encryption key is not valid base64")?;
+    Aes256Gcm::new_from_slice(&key).map_err(|_| {
+        eyre::eyre!(
+            "Backup encryption key needs to be 32 bytes, not {}",
+            key.len()
+        )
+    })
+}
+
+/// Check that the `encryption_key` is a valid AES-256 key.
+pub fn validate_encryption_key(encryption_key: &SecretString) -> eyre::Result<()> {
+    cipher(encryption_key).map(|_| ())
+}
+
+/// Encrypt the `plaintext` of a backup using AES-256-GCM with the `encryption_key`.
+fn encrypt(plaintext: &[u8], encryption_key: &SecretString) -> eyre::Result<Vec<u8>> {
+    let cipher = cipher(encryption_key)?;
+    let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+    let ciphertext = cipher
+        .encrypt(&nonce, plaintext)
+        .map_err(|_| eyre::eyre!("Error encrypting backup"))?;
+    let mut encrypted = Vec::with_capacity(ENCRYPTED_HEADER.len() + nonce.len() + ciphertext.len());
+    encrypted.extend_from_slice(ENCRYPTED_HEADER);
+    encrypted.extend_from_slice(&nonce);
+    encrypted.extend_from_slice(&ciphertext);
+    Ok(encrypted)
+}
+
+/// Decrypt a backup encrypted with [`encrypt`].
+fn decrypt(encrypted: &[u8], encryption_key: &SecretString) -> eyre::Result<Vec<u8>> {
+    let cipher = cipher(encryption_key)?;
+    let encrypted = encrypted
+        .strip_prefix(ENCRYPTED_HEADER)
+        .wrap_err("Backup is not encrypted")?;
+    const NONCE_LENGTH: usize = 12;
+    if encrypted.len() < NONCE_LENGTH {
+        bail!("Encrypted backup is truncated");
     }
+    let (nonce, ciphertext) = encrypted.split_at(NONCE_LENGTH);
+    cipher
+        .decrypt(Nonce::from_slice(nonce), ciphertext)
+        .map_err(|_| eyre::eyre!("Error decrypting backup, the encryption key may be incorrect"))
+}
 
-    let backup_dir = tokio::task::spawn_blocking(|| {
-        tempfile::tempdir().wrap_err("Error creating temporary directory")
+/// Base64 encoded MD5 hash of the file at `path`.
+async fn md5_file(path: &Path) -> eyre::Result<String> {
+    let path = path.to_owned();
+    tokio::task::spawn_blocking::<_, eyre::Result<String>>(|| {
+        let mut file = std::fs::File::open(path)?;
+        let mut hasher = Md5::new();
+        std::io::copy(&mut file, &mut hasher)?;
+        let hash = hasher.finalize();
+        let engine = base64::engine::general_purpose::STANDARD;
+        Ok(engine.encode(hash))
     })
-    .await??;
-    let backup_file = backup_dir.path().join(DB_FILE_NAME);
+    .await
+    .wrap_err("Error joining md5sum task")?
+    .wrap_err("Error performing md5sum")
+}
+
+/// Hashes of the blobs which need to be backed up (see [`blob::backed_up_hashes`]) for the
+/// database file at `path`. Databases from before the blob store was added have none.
+async fn database_file_blob_hashes(path: &Path) -> eyre::Result<Vec<BlobHash>> {
+    let options = SqliteConnectOptions::new().filename(path).read_only(true);
+    let mut connection = SqliteConnection::connect_with(&options)
+        .await
+        .wrap_err("Error opening backup database")?;
+    let has_blobs: bool = sqlx::query_scalar(
+        "SELECT COUNT(*) > 0 FROM pragma_table_info('observation_photos') WHERE name = 'blob_hash'",
+    )
+    .fetch_one(&mut connection)
+    .await?;
+    let hashes = if has_blobs {
+        blob::backed_up_hashes(&mut connection).await
+    } else {
+        Ok(Vec::new())
+    };
+    connection.close().await?;
+    hashes
+}
+
+/// Save a copy of the database to `backup_dir`, encrypting it if the `target` has an encryption
+/// key. Returns the path to the file, and the hashes of the blobs it references which need to be
+/// backed up.
+async fn prepare_backup_file(
+    database: &Database,
+    target: Target,
+    backup_dir: &Path,
+) -> eyre::Result<(PathBuf, Vec<BlobHash>)> {
+    let backup_file = backup_dir.join(DB_FILE_NAME);
     let backup_file_query = backup_file.clone();
     sqlx::query("VACUUM main INTO ?1")
         .bind(
@@ -69,27 +318,218 @@ async fn perform_backup(config: &Config) -> eyre::Result<BackupInfo> {
         .execute(database)
         .await
         .wrap_err("Error performing VACUUM query")?;
+    let blob_hashes = database_file_blob_hashes(&backup_file).await?;
+
+    let Some(encryption_key) = target.encryption_key() else {
+        return Ok((backup_file, blob_hashes));
+    };
+    // The whole database is encrypted in memory.
+    let plaintext = tokio::fs::read(&backup_file).await?;
+    let encrypted = tokio::task::spawn_blocking(move || encrypt(&plaintext, encryption_key))
+        .await
+        .wrap_err("Error joining encryption task")??;
+    tokio::fs::remove_file(&backup_file).await?;
+    let encrypted_file = backup_dir.join(format!("{DB_FILE_NAME}.{ENCRYPTED_EXTENSION}"));
+    tokio::fs::write(&encrypted_file, encrypted).await?;
+    Ok((encrypted_file, blob_hashes))
+}
+
+/// Object key (or path relative to the local backup directory) of the backed up blob with the
+/// `hash`.
+fn blob_key(hash: &BlobHash, encrypted: bool) -> String {
+    if encrypted {
+        format!("{BLOBS_PREFIX}/{hash}.{ENCRYPTED_EXTENSION}")
+    } else {
+        format!("{BLOBS_PREFIX}/{hash}")
+    }
+}
+
+/// Whether the object with the `key` exists in the bucket.
+async fn exists_s3(
+    backup: &options::Backup,
+    client: &reqwest::Client,
+    key: &str,
+) -> eyre::Result<bool> {
+    let bucket = bucket(backup)?;
+    let credentials = credentials(backup);
+    let url = bucket
+        .head_object(Some(&credentials), key)
+        .sign(Duration::from_secs(60 * 60));
+    let response = client.head(url).send().await?;
+    if response.status() == reqwest::StatusCode::NOT_FOUND {
+        return Ok(false);
+    }
+    response
+        .error_for_status()
+        .wrap_err_with(|| format!("Error checking for {key:?}"))?;
+    Ok(true)
+}
+
+async fn put_s3(
+    backup: &options::Backup,
+    client: &reqwest::Client,
+    key: &str,
+    contents: Vec<u8>,
+) -> eyre::Result<()> {
+    let bucket = bucket(backup)?;
+    let credentials = credentials(backup);
+    let url = bucket
+        .put_object(Some(&credentials), key)
+        .sign(Duration::from_secs(60 * 60));
+    client
+        .put(url)
+        .body(contents)
+        .send()
+        .await?
+        .error_for_status()
+        .wrap_err_with(|| format!("Error uploading {key:?}"))?;
+    Ok(())
+}
+
+async fn get_s3(
+    backup: &options::Backup,
+    client: &reqwest::Client,
+    key: &str,
+) -> eyre::Result<Vec<u8>> {
+    let bucket = bucket(backup)?;
+    let credentials = credentials(backup);
+    let url = bucket
+        .get_object(Some(&credentials), key)
+        .sign(Duration::from_secs(60 * 60));
+    let response = client
+        .get(url)
+        .send()
+        .await?
+        .error_for_status()
+        .wrap_err_with(|| format!("Error downloading {key:?}"))?;
+    Ok(response.bytes().await?.to_vec())
+}
 
-    let mut put_object = bucket.put_object(Some(&credentials), DB_FILE_NAME);
+/// Upload the blobs with the `hashes` which aren't already at the destination, encrypting them
+/// if the target has an encryption key. Returns the number of blobs uploaded.
+async fn backup_blobs(config: &Config, hashes: &[BlobHash]) -> eyre::Result<usize> {
+    let encryption_key = config.target.encryption_key();
+    let mut uploaded = 0;
+    for hash in hashes {
+        let key = blob_key(hash, encryption_key.is_some());
+        let exists = match config.target {
+            Target::S3(backup) => exists_s3(backup, &config.client, &key).await?,
+            Target::Local(backup) => tokio::fs::try_exists(backup.directory.join(&key)).await?,
+        };
+        if exists {
+            continue;
+        }
+        let Some(contents) = config.blobs.read(hash).await? else {
+            tracing::warn!("Unable to back up blob {hash}, it is missing from the blob store");
+            continue;
+        };
+        let contents = match encryption_key {
+            Some(encryption_key) => {
+                tokio::task::spawn_blocking(move || encrypt(&contents, encryption_key))
+                    .await
+                    .wrap_err("Error joining encryption task")??
+            }
+            None => contents,
+        };
+        match config.target {
+            Target::S3(backup) => put_s3(backup, &config.client, &key, contents).await?,
+            Target::Local(backup) => {
+                // Written to a temporary file first, so that a partially written blob is never
+                // kept.
+                let path = backup.directory.join(&key);
+                let path_tmp = backup.directory.join(format!("{key}.tmp"));
+                tokio::fs::create_dir_all(
+                    path.parent()
+                        .wrap_err("Expected blob path to have a parent")?,
+                )
+                .await?;
+                tokio::fs::write(&path_tmp, contents).await?;
+                tokio::fs::rename(&path_tmp, &path).await?;
+            }
+        }
+        uploaded += 1;
+    }
+    Ok(uploaded)
+}
+
+/// Download the blobs referenced by the `verified` backup which are missing from the blob store.
+/// Returns the number of blobs restored.
+async fn restore_blobs(config: &Config, verified: &VerifiedBackup) -> eyre::Result<usize> {
+    let hashes = database_file_blob_hashes(&verified.path).await?;
+    let mut restored = 0;
+    for hash in hashes {
+        if config.blobs.contains(&hash).await? {
+            continue;
+        }
+        let key = blob_key(&hash, verified.encrypted);
+        let contents = match config.target {
+            Target::S3(backup) => get_s3(backup, &config.client, &key).await?,
+            Target::Local(backup) => tokio::fs::read(backup.directory.join(&key))
+                .await
+                .wrap_err_with(|| format!("Error reading backed up blob {key:?}"))?,
+        };
+        let contents = if verified.encrypted {
+            let encryption_key = config
+                .target
+                .encryption_key()
+                .wrap_err("Blob is encrypted, but no encryption key is configured")?;
+            tokio::task::spawn_blocking(move || decrypt(&contents, encryption_key))
+                .await
+                .wrap_err("Error joining decryption task")??
+        } else {
+            contents
+        };
+        config.blobs.restore(&hash, &contents).await?;
+        restored += 1;
+    }
+    Ok(restored)
+}
+
+/// Name of the backup file or object for the `target`.
+fn backup_key(target: Target) -> String {
+    match target.encryption_key() {
+        Some(_) => format!("{DB_FILE_NAME}.{ENCRYPTED_EXTENSION}"),
+        None => DB_FILE_NAME.to_owned(),
+    }
+}
+
+async fn upload_s3(
+    backup: &options::Backup,
+    client: &reqwest::Client,
+    backup_file: &Path,
+    md5sum: &str,
+) -> eyre::Result<BackupInfo> {
+    let options::Backup {
+        s3_bucket_name,
+        s3_bucket_region,
+        ..
+    } = backup;
+
+    let bucket = bucket(backup)?;
+    let credentials = credentials(backup);
+
+    let head_bucket = bucket.head_bucket(Some(&credentials));
+    let response = client
+        .head(head_bucket.sign(Duration::from_secs(60 * 60)))
+        .send()
+        .await?;
+    if response.status() == reqwest::StatusCode::NOT_FOUND {
+        bail!("Unable to perform backup, the bucket {s3_bucket_name} does not exist in the region {s3_bucket_region}")
+    }
+
+    let key = backup_file
+        .file_name()
+        .and_then(|file_name| file_name.to_str())
+        .wrap_err("Invalid backup file name")?
+        .to_owned();
+    let mut put_object = bucket.put_object(Some(&credentials), &key);
     let headers = put_object.headers_mut();
     // headers.insert("Content-Type", "application/vnd.sqlite3");
     //
     let meta = tokio::fs::metadata(&backup_file).await?;
     let content_length = meta.len().to_string();
     headers.insert("content-length", &content_length);
-    let backup_file_md5 = backup_file.clone();
-    let md5sum = tokio::task::spawn_blocking::<_, eyre::Result<String>>(|| {
-        let mut file = std::fs::File::open(backup_file_md5)?;
-        let mut hasher = Md5::new();
-        std::io::copy(&mut file, &mut hasher)?;
-        let hash = hasher.finalize();
-        let engine = base64::engine::general_purpose::STANDARD;
-        Ok(engine.encode(&hash))
-    })
-    .await
-    .wrap_err("Error joining md5sum task")?
-    .wrap_err("Error performing md5sum")?;
-    headers.insert("content-md5", &md5sum);
+    headers.insert("content-md5", md5sum);
 
     let url = put_object.sign(Duration::from_secs(5 * 60 * 60));
     let file = tokio::fs::File::open(backup_file).await?;
@@ -97,7 +537,7 @@ async fn perform_backup(config: &Config) -> eyre::Result<BackupInfo> {
     let request = client
         .put(url)
         .header("content-length", content_length)
-        .header("content-md5", &md5sum);
+        .header("content-md5", md5sum);
 
     let response = request.body(file).send().await?;
     let status = response.status();
@@ -109,7 +549,9 @@ async fn perform_backup(config: &Config) -> eyre::Result<BackupInfo> {
 
     let headers = response.headers();
 
-    let info = BackupInfo {
+    Ok(BackupInfo {
+        md5: md5sum.to_owned(),
+        key,
         size: meta.len(),
         entity_tag: headers
             .get("ETag")
@@ -129,29 +571,459 @@ async fn perform_backup(config: &Config) -> eyre::Result<BackupInfo> {
                 .map(reqwest::header::HeaderValue::to_str),
         )?
         .map(ToOwned::to_owned),
+    })
+}
+
+/// Names of the backup files in the local backup directory, oldest first.
+async fn list_local_backup_files(backup: &options::LocalBackup) -> eyre::Result<Vec<String>> {
+    let mut entries = tokio::fs::read_dir(&backup.directory)
+        .await
+        .wrap_err_with(|| format!("Error reading backup directory {:?}", backup.directory))?;
+    let mut file_names = Vec::new();
+    while let Some(entry) = entries.next_entry().await? {
+        if let Ok(file_name) = entry.file_name().into_string() {
+            if file_name.starts_with("db-") && !file_name.ends_with(".tmp") {
+                file_names.push(file_name);
+            }
+        }
+    }
+    // The names contain the time of the backup, so they sort chronologically.
+    file_names.sort();
+    Ok(file_names)
+}
+
+async fn save_local(
+    backup: &options::LocalBackup,
+    backup_file: &Path,
+    md5sum: &str,
+) -> eyre::Result<BackupInfo> {
+    tokio::fs::create_dir_all(&backup.directory)
+        .await
+        .wrap_err_with(|| format!("Error creating backup directory {:?}", backup.directory))?;
+    let format = time::macros::format_description!("[year][month][day]T[hour][minute][second]Z");
+    let time = OffsetDateTime::now_utc().format(&format)?;
+    let extension = backup_file
+        .file_name()
+        .and_then(|file_name| file_name.to_str())
+        .and_then(|file_name| file_name.strip_prefix(DB_FILE_NAME))
+        .unwrap_or_default();
+    let key = format!("db-{time}.sqlite3{extension}");
+    // Copied to a temporary file first, so that a partially copied backup is never kept.
+    let path = backup.directory.join(&key);
+    let path_tmp = backup.directory.join(format!("{key}.tmp"));
+    let size = tokio::fs::copy(backup_file, &path_tmp)
+        .await
+        .wrap_err("Error copying backup to the backup directory")?;
+    tokio::fs::rename(&path_tmp, &path).await?;
+
+    let file_names = list_local_backup_files(backup).await?;
+    let remove = file_names.len().saturating_sub(backup.keep.max(1));
+    for file_name in &file_names[..remove] {
+        tracing::info!("Removing old backup {file_name:?}");
+        tokio::fs::remove_file(backup.directory.join(file_name)).await?;
+    }
+
+    let md5 = base64::engine::general_purpose::STANDARD.decode(md5sum)?;
+    Ok(BackupInfo {
+        size,
+        entity_tag: md5.iter().map(|byte| format!("{byte:02x}")).collect(),
+        version_id: None,
+        expiration: None,
+        md5: md5sum.to_owned(),
+        key,
+    })
+}
+
+async fn perform_backup(config: &Config) -> eyre::Result<BackupInfo> {
+    let _guard = BACKUP_LOCK.lock().await;
+    let destination = config.name();
+    tracing::info!("Starting backup to {destination}...");
+
+    let backup_dir = tokio::task::spawn_blocking(|| {
+        tempfile::tempdir().wrap_err("Error creating temporary directory")
+    })
+    .await??;
+    let (backup_file, blob_hashes) =
+        prepare_backup_file(&config.database, config.target, backup_dir.path()).await?;
+    let md5sum = md5_file(&backup_file).await?;
+
+    let info = match config.target {
+        Target::S3(backup) => upload_s3(backup, &config.client, &backup_file, &md5sum).await?,
+        Target::Local(backup) => save_local(backup, &backup_file, &md5sum).await?,
     };
+    let uploaded_blobs = backup_blobs(config, &blob_hashes)
+        .await
+        .wrap_err("Error backing up blobs")?;
+    tracing::info!(
+        "Backed up {uploaded_blobs} new blobs of {} to {destination}",
+        blob_hashes.len()
+    );
 
     let backup_size = format_size(info.size, humansize::BINARY);
     tracing::debug!("{info:#?}");
     tracing::info!(
-        "Completed backup to s3! (entity: \"{}\", version: \"{}\", size: {backup_size})",
+        "Completed backup to {destination}! (key: \"{}\", entity: \"{}\", version: \"{}\", size: {backup_size})",
+        info.key,
         info.entity_tag,
         info.version_id.clone().unwrap_or_default()
     );
     Ok(info)
 }
 
-pub struct Config {
-    pub client: reqwest::Client,
-    pub backup: &'static options::Backup,
-    pub aws_secret_access_key: &'static SecretString,
-    pub database: Database,
+/// Record the backup in the `backups` table, returning its id.
+async fn record_backup(config: &Config, info: &BackupInfo) -> eyre::Result<i64> {
+    let created_at = types::Time::now_utc();
+    let size: i64 = info.size.try_into()?;
+    let id = sqlx::query_scalar!(
+        "INSERT INTO backups(created_at, size, entity_tag, version_id, md5, destination, key) VALUES($1, $2, $3, $4, $5, $6, $7) RETURNING id",
+        created_at,
+        size,
+        info.entity_tag,
+        info.version_id,
+        info.md5,
+        config.destination,
+        info.key,
+    )
+    .fetch_one(&config.database)
+    .await
+    .wrap_err("Error recording backup")?;
+    Ok(id)
+}
+
+/// The recorded backups, newest first.
+pub async fn list_backups(database: &Database) -> eyre::Result<Vec<BackupRecord>> {
+    sqlx::query_as!(
+        BackupRecord,
+        r#"SELECT id as "id!", created_at as "created_at: types::Time", size, entity_tag, version_id, md5, verified_at as "verified_at: types::Time", verification_error, destination, key FROM backups ORDER BY id DESC"#
+    )
+    .fetch_all(database)
+    .await
+    .wrap_err("Error listing backups")
 }
 
-pub fn spawn_backup_task(config: Config) {
-    let span = tracing::error_span!("backup");
+pub async fn get_backup(database: &Database, id: i64) -> eyre::Result<Option<BackupRecord>> {
+    sqlx::query_as!(
+        BackupRecord,
+        r#"SELECT id as "id!", created_at as "created_at: types::Time", size, entity_tag, version_id, md5, verified_at as "verified_at: types::Time", verification_error, destination, key FROM backups WHERE id=$1"#,
+        id
+    )
+    .fetch_optional(database)
+    .await
+    .wrap_err("Error getting backup")
+}
+
+/// A downloaded backup which passed verification, stored in a temporary directory which is
+/// deleted when this is dropped.
+pub struct VerifiedBackup {
+    _dir: tempfile::TempDir,
+    path: PathBuf,
+    /// Whether the backup (and so its blobs) was encrypted.
+    encrypted: bool,
+    pub size: u64,
+}
+
+/// Check that the database file at `path` can be opened, passes `PRAGMA integrity_check` and is
+/// an `avalanche-report` database.
+async fn check_database_file(path: &Path) -> eyre::Result<()> {
+    let options = SqliteConnectOptions::new().filename(path).read_only(true);
+    let mut connection = SqliteConnection::connect_with(&options)
+        .await
+        .wrap_err("Error opening backup database")?;
+    let integrity_check: Vec<String> = sqlx::query_scalar("PRAGMA integrity_check")
+        .fetch_all(&mut connection)
+        .await
+        .wrap_err("Error performing integrity check")?;
+    let schema_version: Option<i64> = sqlx::query_scalar("SELECT MAX(version) FROM schema_history")
+        .fetch_one(&mut connection)
+        .await
+        .wrap_err("Backup database has no schema history")?;
+    connection.close().await?;
+    if integrity_check != ["ok"] {
+        bail!(
+            "Backup database integrity check failed: {}",
+            integrity_check.join("\n")
+        );
+    }
+    if schema_version.is_none() {
+        bail!("Backup database has no migrations applied");
+    }
+    Ok(())
+}
+
+/// Download the backup with the `key` (and `version_id`) from the bucket to `path`, returning its
+/// hash and ETag.
+async fn download_s3(
+    backup: &options::Backup,
+    client: &reqwest::Client,
+    key: &str,
+    version_id: Option<&str>,
+    path: &Path,
+) -> eyre::Result<(md5::digest::Output<Md5>, String)> {
+    let bucket = bucket(backup)?;
+    let credentials = credentials(backup);
+    let mut get_object = bucket.get_object(Some(&credentials), key);
+    if let Some(version_id) = version_id {
+        get_object
+            .query_mut()
+            .insert("versionId", version_id.to_owned());
+    }
+    let url = get_object.sign(Duration::from_secs(60 * 60));
+    let mut response = client
+        .get(url)
+        .send()
+        .await?
+        .error_for_status()
+        .wrap_err("Error downloading backup")?;
+    let entity_tag = response
+        .headers()
+        .get("ETag")
+        .wrap_err("Expected ETag header to be in the response")?
+        .to_str()?
+        .replace('"', "");
+
+    let mut file = tokio::fs::File::create(path).await?;
+    let mut hasher = Md5::new();
+    while let Some(chunk) = response.chunk().await? {
+        hasher.update(&chunk);
+        file.write_all(&chunk).await?;
+    }
+    file.sync_all().await?;
+    Ok((hasher.finalize(), entity_tag))
+}
+
+/// Download the selected backup and verify it: its MD5 hash needs to match the recorded hash (or
+/// the ETag for the latest backup in a bucket, which is the MD5 hash for objects uploaded in a
+/// single part), and the (decrypted) database needs to pass [`check_database_file`].
+async fn download_and_verify(
+    config: &Config,
+    selector: BackupSelector,
+) -> eyre::Result<VerifiedBackup> {
+    let (key, version_id, expected_md5) = match selector {
+        BackupSelector::Latest => {
+            let key = match config.target {
+                Target::S3(_) => backup_key(config.target),
+                Target::Local(backup) => list_local_backup_files(backup)
+                    .await?
+                    .pop()
+                    .wrap_err("No backups found in the backup directory")?,
+            };
+            (key, None, None)
+        }
+        BackupSelector::Recorded(id) => {
+            let record = get_backup(&config.database, id)
+                .await?
+                .wrap_err_with(|| format!("No backup recorded with id {id}"))?;
+            if record.destination.as_deref() != config.destination {
+                bail!(
+                    "Backup {id} was not saved to the destination {}",
+                    config.name()
+                );
+            }
+            (
+                record.key.unwrap_or_else(|| DB_FILE_NAME.to_owned()),
+                record.version_id,
+                Some(record.md5),
+            )
+        }
+    };
+
+    let dir = tokio::task::spawn_blocking(|| {
+        tempfile::tempdir().wrap_err("Error creating temporary directory")
+    })
+    .await??;
+    let downloaded = dir.path().join("download");
+    let (hash, entity_tag) = match config.target {
+        Target::S3(backup) => {
+            let (hash, entity_tag) = download_s3(
+                backup,
+                &config.client,
+                &key,
+                version_id.as_deref(),
+                &downloaded,
+            )
+            .await?;
+            (hash, Some(entity_tag))
+        }
+        Target::Local(backup) => {
+            tokio::fs::copy(backup.directory.join(&key), &downloaded)
+                .await
+                .wrap_err_with(|| format!("Error reading backup {key:?}"))?;
+            let md5 = md5_file(&downloaded).await?;
+            let hash = base64::engine::general_purpose::STANDARD.decode(md5)?;
+            (md5::digest::Output::<Md5>::clone_from_slice(&hash), None)
+        }
+    };
+
+    match (expected_md5, entity_tag) {
+        (Some(expected_md5), _) => {
+            let md5 = base64::engine::general_purpose::STANDARD.encode(hash);
+            if md5 != expected_md5 {
+                bail!("Backup MD5 hash {md5} does not match the recorded hash {expected_md5}");
+            }
+        }
+        // Multipart uploads have an ETag which isn't the MD5 hash of the object.
+        (None, Some(entity_tag)) if entity_tag.contains('-') => {
+            tracing::warn!("Unable to verify the hash of backup with ETag {entity_tag}")
+        }
+        (None, Some(entity_tag)) => {
+            let md5 = format!("{hash:x}");
+            if md5 != entity_tag {
+                bail!("Backup MD5 hash {md5} does not match the ETag {entity_tag}");
+            }
+        }
+        (None, None) => {}
+    }
+
+    let path = dir.path().join(DB_FILE_NAME);
+    let encrypted = key.ends_with(&format!(".{ENCRYPTED_EXTENSION}"));
+    if encrypted {
+        let encryption_key = config
+            .target
+            .encryption_key()
+            .wrap_err("Backup is encrypted, but no encryption key is configured")?;
+        let encrypted = tokio::fs::read(&downloaded).await?;
+        let plaintext = tokio::task::spawn_blocking(move || decrypt(&encrypted, encryption_key))
+            .await
+            .wrap_err("Error joining decryption task")??;
+        tokio::fs::write(&path, plaintext).await?;
+        tokio::fs::remove_file(&downloaded).await?;
+    } else {
+        tokio::fs::rename(&downloaded, &path).await?;
+    }
+    check_database_file(&path).await?;
+    let size = tokio::fs::metadata(&path).await?.len();
+
+    Ok(VerifiedBackup {
+        _dir: dir,
+        path,
+        encrypted,
+        size,
+    })
+}
+
+/// Download and verify the selected backup (see [`download_and_verify`]), and record the result
+/// for a recorded backup.
+pub async fn verify_backup(
+    config: &Config,
+    selector: BackupSelector,
+) -> eyre::Result<VerifiedBackup> {
+    let result = download_and_verify(config, selector).await;
+    if let BackupSelector::Recorded(id) = selector {
+        let verified_at = types::Time::now_utc();
+        let verification_error = result.as_ref().err().map(|error| format!("{error:#}"));
+        sqlx::query!(
+            "UPDATE backups SET verified_at=$1, verification_error=$2 WHERE id=$3",
+            verified_at,
+            verification_error,
+            id
+        )
+        .execute(&config.database)
+        .await
+        .wrap_err("Error recording backup verification")?;
+    }
+    result
+}
+
+/// Verify the selected backup, and stage it to replace the database in the `data_dir` the next
+/// time the application starts (see [`apply_staged_restore`]). The database can't be safely
+/// replaced while the application is using it. The backed up blobs it references are restored
+/// into the blob store straight away, after the restore is staged so that they aren't removed as
+/// garbage in the meantime (see [`BlobStore::collect_garbage`]).
+pub async fn stage_restore(
+    config: &Config,
+    selector: BackupSelector,
+    data_dir: &Path,
+) -> eyre::Result<()> {
+    let _guard = BACKUP_LOCK.lock().await;
+    tracing::info!(
+        "Staging restore of backup {selector:?} from {}",
+        config.name()
+    );
+    let verified = verify_backup(config, selector).await?;
+    // Copied to a temporary file first, so that a partially copied backup is never applied.
+    let restore_file = data_dir.join(RESTORE_FILE_NAME);
+    let restore_file_tmp = data_dir.join(format!("{RESTORE_FILE_NAME}.tmp"));
+    tokio::fs::copy(&verified.path, &restore_file_tmp)
+        .await
+        .wrap_err("Error copying backup to the data directory")?;
+    tokio::fs::rename(&restore_file_tmp, &restore_file).await?;
+    let restored_blobs = match restore_blobs(config, &verified).await {
+        Ok(restored_blobs) => restored_blobs,
+        Err(error) => {
+            tokio::fs::remove_file(&restore_file).await?;
+            return Err(error.wrap_err("Error restoring blobs, the staged restore was cancelled"));
+        }
+    };
+    tracing::info!("Restored {restored_blobs} blobs from the backup");
+    tracing::info!(
+        "Staged restore of backup {selector:?} ({}), it will be applied when the application restarts",
+        format_size(verified.size, humansize::BINARY)
+    );
+    Ok(())
+}
+
+/// Whether a restore has been staged with [`stage_restore`].
+pub fn is_restore_staged(data_dir: &Path) -> bool {
+    data_dir.join(RESTORE_FILE_NAME).exists()
+}
+
+pub async fn cancel_staged_restore(data_dir: &Path) -> eyre::Result<()> {
+    let _guard = BACKUP_LOCK.lock().await;
+    let restore_file = data_dir.join(RESTORE_FILE_NAME);
+    if restore_file.exists() {
+        tokio::fs::remove_file(&restore_file).await?;
+        tracing::info!("Cancelled staged restore");
+    }
+    Ok(())
+}
+
+/// Replace the database in the `data_dir` with the backup staged by [`stage_restore`], if there
+/// is one. This needs to be called before the database is opened. The replaced database is kept
+/// alongside it with a `.pre-restore-{timestamp}` suffix.
+pub fn apply_staged_restore(data_dir: &Path) -> eyre::Result<()> {
+    let restore_file = data_dir.join(RESTORE_FILE_NAME);
+    if !restore_file.exists() {
+        return Ok(());
+    }
+    let suffix = format!("pre-restore-{}", OffsetDateTime::now_utc().unix_timestamp());
+    for extension in ["", "-wal", "-shm"] {
+        let file_name = format!("{DB_FILE_NAME}{extension}");
+        let path = data_dir.join(&file_name);
+        if path.exists() {
+            let replaced = data_dir.join(format!("{file_name}.{suffix}"));
+            std::fs::rename(&path, &replaced)
+                .wrap_err_with(|| format!("Error moving {path:?} to {replaced:?}"))?;
+        }
+    }
+    std::fs::rename(&restore_file, data_dir.join(DB_FILE_NAME))
+        .wrap_err("Error moving the staged restore into place")?;
+    tracing::warn!("Restored the database from the staged backup, the previous database was kept with the suffix {suffix:?}");
+    Ok(())
+}
+
+/// Record the completed backup, and verify that it can be restored.
+async fn verify_completed_backup(config: &Config, info: &BackupInfo) {
+    let id = match record_backup(config, info).await {
+        Ok(id) => id,
+        Err(error) => {
+            tracing::error!("{error:?}");
+            return;
+        }
+    };
+    match verify_backup(config, BackupSelector::Recorded(id)).await {
+        Ok(_) => tracing::info!("Verified backup {id}"),
+        Err(error) => tracing::error!("Backup {id} failed verification: {error:?}"),
+    }
+}
+
+/// Spawn the task performing backups on the schedule for `config`. When `shutdown` is requested a
+/// backup that is in progress is completed, but no further backups are started. Repeated failures
+/// are reported to `alerts`.
+pub fn spawn_backup_task(config: Config, mut shutdown: Shutdown, alerts: Alerts) {
+    let span = tracing::error_span!("backup", destination = config.name());
     tokio::spawn(
         async move {
+            let task = format!("backup {}", config.name());
             let mut initial = true;
             loop {
                 'retry: loop {
@@ -162,25 +1034,88 @@ pub fn spawn_backup_task(config: Config) {
                             "Error performing recurring backup"
                         }
                     }) {
-                        Ok(_) => break 'retry,
-                        Err(error) => tracing::error!("{error:?}"),
+                        Ok(info) => {
+                            alerts.success(&task);
+                            verify_completed_backup(&config, &info).await;
+                            break 'retry;
+                        }
+                        Err(error) => {
+                            tracing::error!("{error:?}");
+                            alerts.failure(&task, &error);
+                        }
                     }
                     tracing::warn!("Retrying in 30 seconds...");
-                    tokio::time::sleep(Duration::from_secs(30)).await;
+                    tokio::select! {
+                        _ = tokio::time::sleep(Duration::from_secs(30)) => {}
+                        _ = shutdown.requested() => return,
+                    }
                     tracing::warn!("Retrying..");
                 }
 
-                let next_time = config.backup.schedule.next_time_from_now();
+                let next_time = config.target.schedule().next_time_from_now();
                 let now = OffsetDateTime::now_utc();
                 let duration: Duration = (next_time - now)
                     .try_into()
                     .expect("Unable to convert duration");
-                let human_duration = humantime::format_duration(duration.clone());
+                let human_duration = humantime::format_duration(duration);
                 tracing::info!("Next backup in {human_duration}");
-                tokio::time::sleep(duration).await;
+                tokio::select! {
+                    _ = tokio::time::sleep(duration) => {}
+                    _ = shutdown.requested() => return,
+                }
                 initial = false;
             }
         }
         .instrument(span),
     );
 }
+
+#[cfg(test)]
+mod test {
+    use secrecy::SecretString;
+
+    use super::{apply_staged_restore, decrypt, encrypt, BackupSelector, RESTORE_FILE_NAME};
+    use crate::database::DB_FILE_NAME;
+
+    #[test]
+    fn test_backup_selector() {
+        assert!(matches!("latest".parse(), Ok(BackupSelector::Latest)));
+        assert!(matches!("12".parse(), Ok(BackupSelector::Recorded(12))));
+        assert!("other".parse::<BackupSelector>().is_err());
+    }
+
+    #[test]
+    fn test_encryption() {
+        let key = SecretString::new("MDEyMzQ1Njc4OWFiY2RlZjAxMjM0NTY3ODlhYmNkZWY=".to_owned());
+        let other_key =
+            SecretString::new("ZmVkY2JhOTg3NjU0MzIxMGZlZGNiYTk4NzY1NDMyMTA=".to_owned());
+        let encrypted = encrypt(b"SQLite format 3\0", &key).unwrap();
+        assert!(!encrypted
+            .windows(b"SQLite".len())
+            .any(|window| window == b"SQLite"));
+        assert_eq!(decrypt(&encrypted, &key).unwrap(), b"SQLite format 3\0");
+        assert!(decrypt(&encrypted, &other_key).is_err());
+        assert!(decrypt(b"SQLite format 3\0", &key).is_err());
+        assert!(encrypt(b"", &SecretString::new("c2hvcnQ=".to_owned())).is_err());
+    }
+
+    #[test]
+    fn test_apply_staged_restore() {
+        let data_dir = tempfile::tempdir().unwrap();
+        let db_file = data_dir.path().join(DB_FILE_NAME);
+        std::fs::write(&db_file, "current").unwrap();
+        apply_staged_restore(data_dir.path()).unwrap();
+        assert_eq!(std::fs::read_to_string(&db_file).unwrap(), "current");
+
+        std::fs::write(data_dir.path().join(RESTORE_FILE_NAME), "restored").unwrap();
+        apply_staged_restore(data_dir.path()).unwrap();
+        assert_eq!(std::fs::read_to_string(&db_file).unwrap(), "restored");
+        assert!(!data_dir.path().join(RESTORE_FILE_NAME).exists());
+        let replaced: Vec<String> = std::fs::read_dir(data_dir.path())
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+            .filter(|name| name.starts_with(&format!("{DB_FILE_NAME}.pre-restore-")))
+            .collect();
+        assert_eq!(replaced.len(), 1);
+    }
+}
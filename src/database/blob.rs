@@ -1 +1,429 @@
+//! Content-addressed storage for large files (blobs), in the `blobs` directory of the `data_dir`,
+//! so that they can be streamed to and from disk instead of being held in memory or stored in the
+//! database. Blobs are identified by the SHA-256 hash of their contents, so identical files are
+//! only stored once, and each blob is recorded in the `blobs` table. Tables reference blobs with
+//! a `blob_hash` column:
+//!
+//! + `forecast_files`: files fetched from Google Drive, which are fetched again if their blob is
+//!   missing, and files uploaded or imported from `/admin/forecast-files`, which are the only copy
+//!   and so are included in the backups.
+//! + `observation_photos`: the only copy of the photos, so these blobs are included in the
+//!   backups (see [`super::backup`]).
+//! + `generated_pdfs`: PDFs generated for forecasts, which are generated again if their blob is
+//!   missing.
+//! + `forecasters`: the forecasters' photos (`photo_blob_hash`), which are also included in the
+//!   backups.
+//!
+//! Blobs which are no longer referenced are removed by [`BlobStore::collect_garbage`], which is
+//! performed with the database maintenance, or manually from `/admin/blobs`.
 
+use std::{
+    collections::HashSet,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use bytes::Bytes;
+use eyre::{Context, ContextCompat};
+use futures::{Stream, TryStreamExt};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use tokio::io::AsyncWriteExt;
+
+use crate::types;
+
+use super::Database;
+
+/// Name of the directory in the `data_dir` where the blobs are stored.
+const DIRECTORY_NAME: &str = "blobs";
+/// Blobs stored more recently than this are not removed by [`BlobStore::collect_garbage`], so
+/// that a blob which has been stored, but not yet referenced, isn't removed.
+const GARBAGE_GRACE_PERIOD: Duration = Duration::from_secs(60 * 60);
+
+/// Hex encoded SHA-256 hash of the contents of a blob.
+#[derive(sqlx::Type, Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Hash)]
+#[serde(transparent)]
+#[sqlx(transparent)]
+pub struct BlobHash(String);
+
+impl BlobHash {
+    pub fn of(contents: &[u8]) -> Self {
+        Self(format!("{:x}", Sha256::digest(contents)))
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl std::fmt::Display for BlobHash {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+/// Storage used by the blobs referenced from one table.
+#[derive(Serialize, Debug)]
+pub struct ReferenceUsage {
+    /// Name of the referencing table.
+    pub table: &'static str,
+    /// Number of rows referencing a blob.
+    pub references: i64,
+    /// Number of distinct blobs referenced, less than `references` when identical files have
+    /// been deduplicated.
+    pub blobs: i64,
+    pub bytes: i64,
+}
+
+/// Storage used by the blobs, see [`BlobStore::usage`].
+#[derive(Serialize, Debug)]
+pub struct Usage {
+    pub references: Vec<ReferenceUsage>,
+    /// Blobs which are no longer referenced, and will be removed by the next
+    /// [`BlobStore::collect_garbage`].
+    pub unreferenced_blobs: i64,
+    pub unreferenced_bytes: i64,
+    pub total_blobs: i64,
+    pub total_bytes: i64,
+}
+
+/// The result of [`BlobStore::collect_garbage`].
+#[derive(Serialize, Debug, Default)]
+pub struct GarbageCollection {
+    /// Number of unreferenced blobs removed.
+    pub removed_blobs: u64,
+    pub removed_bytes: u64,
+    /// Number of files removed which weren't recorded in the `blobs` table, e.g. left behind by
+    /// an interrupted write.
+    pub removed_orphans: u64,
+}
+
+/// See the [module documentation](self).
+pub struct BlobStore {
+    data_dir: PathBuf,
+    directory: PathBuf,
+    database: Database,
+    /// Held for reading while storing a blob, and for writing while collecting garbage, so that a
+    /// blob can't be removed while it's being stored again.
+    lock: tokio::sync::RwLock<()>,
+}
+
+impl BlobStore {
+    /// Use the `blobs` directory of the `data_dir`, creating it if it doesn't exist.
+    pub fn new(data_dir: &Path, database: Database) -> eyre::Result<Self> {
+        let directory = data_dir.join(DIRECTORY_NAME);
+        crate::fs::create_dir_if_not_exists(&directory)?;
+        Ok(Self {
+            data_dir: data_dir.to_owned(),
+            directory,
+            database,
+            lock: tokio::sync::RwLock::new(()),
+        })
+    }
+
+    /// Path of the blob with the `hash`, which may not exist. Blobs are spread over
+    /// subdirectories by the first two characters of their hash to keep the directories small.
+    pub fn path(&self, hash: &BlobHash) -> PathBuf {
+        self.directory.join(&hash.0[..2]).join(&hash.0)
+    }
+
+    pub async fn contains(&self, hash: &BlobHash) -> eyre::Result<bool> {
+        Ok(tokio::fs::try_exists(self.path(hash)).await?)
+    }
+
+    /// Read the whole blob with the `hash`, `None` if it isn't stored.
+    pub async fn read(&self, hash: &BlobHash) -> eyre::Result<Option<Vec<u8>>> {
+        match tokio::fs::read(self.path(hash)).await {
+            Ok(contents) => Ok(Some(contents)),
+            Err(error) if error.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(error) => Err(error).wrap_err_with(|| format!("Error reading blob {hash}")),
+        }
+    }
+
+    /// Store the `contents`, returning their hash.
+    pub async fn put(&self, contents: Bytes) -> eyre::Result<BlobHash> {
+        self.put_stream(futures::stream::iter([Ok::<_, std::io::Error>(contents)]))
+            .await
+    }
+
+    /// Store the chunks from the `stream` as they are received, returning the hash of the
+    /// contents. The blob is written to a temporary file first, so that readers never see a
+    /// partially written blob.
+    pub async fn put_stream<E>(
+        &self,
+        stream: impl Stream<Item = Result<Bytes, E>>,
+    ) -> eyre::Result<BlobHash>
+    where
+        E: std::error::Error + Send + Sync + 'static,
+    {
+        let _guard = self.lock.read().await;
+        let tmp_path = self.directory.join(format!("{}.tmp", uuid::Uuid::new_v4()));
+        let result = async {
+            let mut file = tokio::fs::File::create(&tmp_path).await?;
+            let mut hasher = Sha256::new();
+            let mut size: u64 = 0;
+            let mut stream = std::pin::pin!(stream);
+            while let Some(chunk) = stream.try_next().await? {
+                hasher.update(&chunk);
+                size += chunk.len() as u64;
+                file.write_all(&chunk).await?;
+            }
+            file.sync_all().await?;
+            let hash = BlobHash(format!("{:x}", hasher.finalize()));
+            let path = self.path(&hash);
+            tokio::fs::create_dir_all(
+                path.parent()
+                    .wrap_err("Expected blob path to have a parent")?,
+            )
+            .await?;
+            tokio::fs::rename(&tmp_path, &path).await?;
+            eyre::Ok((hash, size))
+        }
+        .await;
+        let (hash, size) = match result {
+            Ok(stored) => stored,
+            Err(error) => {
+                let _ = tokio::fs::remove_file(&tmp_path).await;
+                return Err(error.wrap_err("Error storing blob"));
+            }
+        };
+
+        let size: i64 = size.try_into()?;
+        let stored_at = types::Time::now_utc();
+        sqlx::query!(
+            "INSERT INTO blobs(hash, size, stored_at) VALUES($1, $2, $3) ON CONFLICT(hash) DO UPDATE SET stored_at=excluded.stored_at",
+            hash,
+            size,
+            stored_at,
+        )
+        .execute(&self.database)
+        .await
+        .wrap_err("Error recording blob")?;
+        Ok(hash)
+    }
+
+    /// Write the `contents` of a blob which is recorded in the `blobs` table of a backup being
+    /// restored, checking that they match the `hash`.
+    pub async fn restore(&self, hash: &BlobHash, contents: &[u8]) -> eyre::Result<()> {
+        let _guard = self.lock.read().await;
+        if BlobHash::of(contents) != *hash {
+            eyre::bail!("Contents of blob {hash} don't match its hash");
+        }
+        let path = self.path(hash);
+        let tmp_path = self.directory.join(format!("{}.tmp", uuid::Uuid::new_v4()));
+        tokio::fs::create_dir_all(
+            path.parent()
+                .wrap_err("Expected blob path to have a parent")?,
+        )
+        .await?;
+        tokio::fs::write(&tmp_path, contents).await?;
+        tokio::fs::rename(&tmp_path, &path).await?;
+        Ok(())
+    }
+
+    /// The storage used by the blobs.
+    pub async fn usage(&self) -> eyre::Result<Usage> {
+        let forecast_files = sqlx::query!(
+            r#"SELECT
+                (SELECT COUNT(*) FROM forecast_files WHERE blob_hash IS NOT NULL) as "references!: i64",
+                COUNT(*) as "blobs!: i64",
+                COALESCE(SUM(size), 0) as "bytes!: i64"
+            FROM blobs WHERE hash IN (SELECT blob_hash FROM forecast_files)"#
+        )
+        .fetch_one(&self.database)
+        .await?;
+        let observation_photos = sqlx::query!(
+            r#"SELECT
+                (SELECT COUNT(*) FROM observation_photos WHERE blob_hash IS NOT NULL) as "references!: i64",
+                COUNT(*) as "blobs!: i64",
+                COALESCE(SUM(size), 0) as "bytes!: i64"
+            FROM blobs WHERE hash IN (SELECT blob_hash FROM observation_photos)"#
+        )
+        .fetch_one(&self.database)
+        .await?;
+        let generated_pdfs = sqlx::query!(
+            r#"SELECT
+                (SELECT COUNT(*) FROM generated_pdfs) as "references!: i64",
+                COUNT(*) as "blobs!: i64",
+                COALESCE(SUM(size), 0) as "bytes!: i64"
+            FROM blobs WHERE hash IN (SELECT blob_hash FROM generated_pdfs)"#
+        )
+        .fetch_one(&self.database)
+        .await?;
+        let forecasters = sqlx::query!(
+            r#"SELECT
+                (SELECT COUNT(*) FROM forecasters WHERE photo_blob_hash IS NOT NULL) as "references!: i64",
+                COUNT(*) as "blobs!: i64",
+                COALESCE(SUM(size), 0) as "bytes!: i64"
+            FROM blobs WHERE hash IN (SELECT photo_blob_hash FROM forecasters)"#
+        )
+        .fetch_one(&self.database)
+        .await?;
+        let unreferenced = sqlx::query!(
+            r#"SELECT COUNT(*) as "blobs!: i64", COALESCE(SUM(size), 0) as "bytes!: i64"
+            FROM blobs
+            WHERE hash NOT IN (SELECT blob_hash FROM forecast_files WHERE blob_hash IS NOT NULL)
+                AND hash NOT IN (SELECT blob_hash FROM observation_photos WHERE blob_hash IS NOT NULL)
+                AND hash NOT IN (SELECT blob_hash FROM generated_pdfs)
+                AND hash NOT IN (SELECT photo_blob_hash FROM forecasters WHERE photo_blob_hash IS NOT NULL)"#
+        )
+        .fetch_one(&self.database)
+        .await?;
+        let total = sqlx::query!(
+            r#"SELECT COUNT(*) as "blobs!: i64", COALESCE(SUM(size), 0) as "bytes!: i64" FROM blobs"#
+        )
+        .fetch_one(&self.database)
+        .await?;
+
+        Ok(Usage {
+            references: vec![
+                ReferenceUsage {
+                    table: "forecast_files",
+                    references: forecast_files.references,
+                    blobs: forecast_files.blobs,
+                    bytes: forecast_files.bytes,
+                },
+                ReferenceUsage {
+                    table: "observation_photos",
+                    references: observation_photos.references,
+                    blobs: observation_photos.blobs,
+                    bytes: observation_photos.bytes,
+                },
+                ReferenceUsage {
+                    table: "generated_pdfs",
+                    references: generated_pdfs.references,
+                    blobs: generated_pdfs.blobs,
+                    bytes: generated_pdfs.bytes,
+                },
+                ReferenceUsage {
+                    table: "forecasters",
+                    references: forecasters.references,
+                    blobs: forecasters.blobs,
+                    bytes: forecasters.bytes,
+                },
+            ],
+            unreferenced_blobs: unreferenced.blobs,
+            unreferenced_bytes: unreferenced.bytes,
+            total_blobs: total.blobs,
+            total_bytes: total.bytes,
+        })
+    }
+
+    /// Remove the blobs which are no longer referenced (and weren't stored within the
+    /// [`GARBAGE_GRACE_PERIOD`]), and any files in the directory which aren't recorded blobs.
+    pub async fn collect_garbage(&self) -> eyre::Result<GarbageCollection> {
+        let _guard = self.lock.write().await;
+        let mut collection = GarbageCollection::default();
+        // The staged database may reference blobs which this one doesn't.
+        if super::backup::is_restore_staged(&self.data_dir) {
+            tracing::info!("Skipping blob garbage collection while a restore is staged");
+            return Ok(collection);
+        }
+        let cutoff: types::Time = (time::OffsetDateTime::now_utc() - GARBAGE_GRACE_PERIOD).into();
+        let removed = sqlx::query!(
+            r#"DELETE FROM blobs
+            WHERE stored_at < $1
+                AND hash NOT IN (SELECT blob_hash FROM forecast_files WHERE blob_hash IS NOT NULL)
+                AND hash NOT IN (SELECT blob_hash FROM observation_photos WHERE blob_hash IS NOT NULL)
+                AND hash NOT IN (SELECT blob_hash FROM generated_pdfs)
+                AND hash NOT IN (SELECT photo_blob_hash FROM forecasters WHERE photo_blob_hash IS NOT NULL)
+            RETURNING hash as "hash!: BlobHash", size"#,
+            cutoff
+        )
+        .fetch_all(&self.database)
+        .await
+        .wrap_err("Error removing unreferenced blobs")?;
+        for blob in removed {
+            remove_file_if_exists(&self.path(&blob.hash)).await?;
+            collection.removed_blobs += 1;
+            collection.removed_bytes += u64::try_from(blob.size).unwrap_or_default();
+        }
+
+        let recorded: HashSet<String> = sqlx::query_scalar!("SELECT hash FROM blobs")
+            .fetch_all(&self.database)
+            .await?
+            .into_iter()
+            .collect();
+        let mut directories = vec![self.directory.clone()];
+        while let Some(directory) = directories.pop() {
+            let mut entries = tokio::fs::read_dir(&directory).await?;
+            while let Some(entry) = entries.next_entry().await? {
+                if entry.file_type().await?.is_dir() {
+                    directories.push(entry.path());
+                    continue;
+                }
+                let file_name = entry.file_name();
+                if !recorded.contains(file_name.to_string_lossy().as_ref()) {
+                    tracing::debug!("Removing orphaned blob file {:?}", entry.path());
+                    remove_file_if_exists(&entry.path()).await?;
+                    collection.removed_orphans += 1;
+                }
+            }
+        }
+        tracing::info!("Collected blob garbage: {collection:?}");
+        Ok(collection)
+    }
+}
+
+async fn remove_file_if_exists(path: &Path) -> eyre::Result<()> {
+    match tokio::fs::remove_file(path).await {
+        Err(error) if error.kind() != std::io::ErrorKind::NotFound => {
+            Err(error).wrap_err_with(|| format!("Error removing blob file {path:?}"))
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Hashes of the blobs which can't be recreated, and so need to be included in the backups, in
+/// the database of the `connection` (which may be a backup being restored). Databases from
+/// before the `forecasters` table was added only reference observation photos and uploaded
+/// forecast files.
+pub async fn backed_up_hashes(
+    connection: &mut sqlx::SqliteConnection,
+) -> eyre::Result<Vec<BlobHash>> {
+    let mut hashes = sqlx::query_scalar!(
+        r#"SELECT DISTINCT blob_hash as "blob_hash!: BlobHash" FROM observation_photos WHERE blob_hash IS NOT NULL"#
+    )
+    .fetch_all(&mut *connection)
+    .await?;
+    hashes.extend(
+        sqlx::query_scalar!(
+            r#"SELECT DISTINCT blob_hash as "blob_hash!: BlobHash" FROM forecast_files WHERE google_drive_id LIKE 'upload-%' AND blob_hash IS NOT NULL"#
+        )
+        .fetch_all(&mut *connection)
+        .await?,
+    );
+    let has_forecasters: bool = sqlx::query_scalar(
+        "SELECT COUNT(*) > 0 FROM sqlite_master WHERE type = 'table' AND name = 'forecasters'",
+    )
+    .fetch_one(&mut *connection)
+    .await?;
+    if has_forecasters {
+        hashes.extend(
+            sqlx::query_scalar!(
+                r#"SELECT DISTINCT photo_blob_hash as "photo_blob_hash!: BlobHash" FROM forecasters WHERE photo_blob_hash IS NOT NULL"#
+            )
+            .fetch_all(&mut *connection)
+            .await?,
+        );
+    }
+    hashes.sort_by(|a, b| a.0.cmp(&b.0));
+    hashes.dedup();
+    Ok(hashes)
+}
+
+#[cfg(test)]
+mod test {
+    use super::BlobHash;
+
+    #[test]
+    fn test_blob_hash() {
+        let hash = BlobHash::of(b"hello");
+        assert_eq!(
+            hash.as_str(),
+            "2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824"
+        );
+        assert_eq!(hash, BlobHash::of(b"hello"));
+        assert_ne!(hash, BlobHash::of(b"hello "));
+    }
+}
@@ -0,0 +1,177 @@
+//! Database maintenance (see [`options::Maintenance`]), performed on a schedule or manually from
+//! the admin page. Each run is recorded in the `database_maintenance` table. Unreferenced blobs
+//! are also removed (see [`BlobStore::collect_garbage`]).
+
+use std::{sync::Arc, time::Instant};
+
+use eyre::Context;
+use serde::Serialize;
+use time::OffsetDateTime;
+use tracing::Instrument;
+
+use crate::{options, types};
+
+use super::{blob::BlobStore, Database};
+
+/// Prevents scheduled and manual maintenance from running at the same time.
+static MAINTENANCE_LOCK: tokio::sync::Mutex<()> = tokio::sync::Mutex::const_new(());
+
+/// The record of a maintenance run.
+#[derive(Debug, Serialize)]
+pub struct MaintenanceReport {
+    pub started_at: types::Time,
+    pub duration_ms: i64,
+    /// Whether the run was triggered manually instead of by the schedule.
+    pub manual: bool,
+    /// The result of `PRAGMA integrity_check`, `ok` if no problems were found.
+    pub integrity_check: Option<String>,
+    /// Size of the database in bytes before maintenance.
+    pub size_before: Option<i64>,
+    /// Size of the database in bytes after maintenance.
+    pub size_after: Option<i64>,
+    pub error: Option<String>,
+}
+
+/// Size of the database in bytes.
+async fn database_size(database: &Database) -> eyre::Result<i64> {
+    let page_count: i64 = sqlx::query_scalar("PRAGMA page_count")
+        .fetch_one(database)
+        .await?;
+    let page_size: i64 = sqlx::query_scalar("PRAGMA page_size")
+        .fetch_one(database)
+        .await?;
+    Ok(page_count * page_size)
+}
+
+/// Run the integrity check, `ANALYZE`, blob garbage collection and `VACUUM` (if enabled), filling
+/// in the `report`. Vacuum is skipped if the integrity check found problems, so that the damaged
+/// database file isn't rewritten.
+async fn run(
+    database: &Database,
+    blobs: &BlobStore,
+    vacuum: bool,
+    report: &mut MaintenanceReport,
+) -> eyre::Result<()> {
+    report.size_before = Some(database_size(database).await?);
+    let integrity_check: Vec<String> = sqlx::query_scalar("PRAGMA integrity_check")
+        .fetch_all(database)
+        .await
+        .wrap_err("Error performing integrity check")?;
+    let integrity_check = integrity_check.join("\n");
+    let integrity_ok = integrity_check == "ok";
+    report.integrity_check = Some(integrity_check);
+    if !integrity_ok {
+        tracing::error!(
+            "Database integrity check failed: {:?}",
+            report.integrity_check
+        );
+    }
+
+    sqlx::query("ANALYZE")
+        .execute(database)
+        .await
+        .wrap_err("Error performing ANALYZE")?;
+    blobs
+        .collect_garbage()
+        .await
+        .wrap_err("Error collecting blob garbage")?;
+    if vacuum && integrity_ok {
+        sqlx::query("VACUUM")
+            .execute(database)
+            .await
+            .wrap_err("Error performing VACUUM")?;
+    }
+    report.size_after = Some(database_size(database).await?);
+    Ok(())
+}
+
+/// Perform maintenance on the database and record the result.
+pub async fn perform_maintenance(
+    database: &Database,
+    blobs: &BlobStore,
+    maintenance: &options::Maintenance,
+    manual: bool,
+) -> eyre::Result<MaintenanceReport> {
+    let _guard = MAINTENANCE_LOCK.lock().await;
+    tracing::info!("Starting database maintenance");
+    let started_at = types::Time::now_utc();
+    let start = Instant::now();
+    let mut report = MaintenanceReport {
+        started_at,
+        duration_ms: 0,
+        manual,
+        integrity_check: None,
+        size_before: None,
+        size_after: None,
+        error: None,
+    };
+    if let Err(error) = run(database, blobs, maintenance.vacuum, &mut report).await {
+        tracing::error!("Error performing database maintenance: {error:?}");
+        report.error = Some(format!("{error:#}"));
+    }
+    report.duration_ms = start.elapsed().as_millis().try_into().unwrap_or(i64::MAX);
+
+    sqlx::query!(
+        "INSERT INTO database_maintenance(started_at, duration_ms, manual, integrity_check, size_before, size_after, error) VALUES($1, $2, $3, $4, $5, $6, $7)",
+        report.started_at,
+        report.duration_ms,
+        report.manual,
+        report.integrity_check,
+        report.size_before,
+        report.size_after,
+        report.error,
+    )
+    .execute(database)
+    .await
+    .wrap_err("Error recording database maintenance")?;
+    tracing::info!(
+        "Completed database maintenance in {}ms (size before: {:?}, size after: {:?})",
+        report.duration_ms,
+        report.size_before,
+        report.size_after
+    );
+    Ok(report)
+}
+
+/// The most recent maintenance runs, newest first.
+pub async fn recent_reports(
+    database: &Database,
+    limit: i64,
+) -> eyre::Result<Vec<MaintenanceReport>> {
+    sqlx::query_as!(
+        MaintenanceReport,
+        r#"SELECT started_at as "started_at: types::Time", duration_ms, manual as "manual: bool", integrity_check, size_before, size_after, error FROM database_maintenance ORDER BY started_at DESC LIMIT $1"#,
+        limit
+    )
+    .fetch_all(database)
+    .await
+    .wrap_err("Error listing database maintenance")
+}
+
+pub fn spawn_maintenance_task(
+    database: Database,
+    blobs: Arc<BlobStore>,
+    maintenance: &'static options::Maintenance,
+) {
+    let span = tracing::error_span!("database_maintenance");
+    tokio::spawn(
+        async move {
+            loop {
+                let next_time = maintenance.schedule.next_time_from_now();
+                let now = OffsetDateTime::now_utc();
+                let duration: std::time::Duration = (next_time - now)
+                    .try_into()
+                    .expect("Unable to convert duration");
+                let human_duration = humantime::format_duration(duration);
+                tracing::info!("Next database maintenance in {human_duration}");
+                tokio::time::sleep(duration).await;
+
+                if let Err(error) = perform_maintenance(&database, &blobs, maintenance, false).await
+                {
+                    tracing::error!("{error:?}");
+                }
+            }
+        }
+        .instrument(span),
+    );
+}
@@ -1,33 +1,25 @@
 use axum::extract::{Request, State};
 use axum::middleware::Next;
 use axum::response::Response;
-use nonzero_ext::nonzero;
+use eyre::Context;
 use std::path::Path;
-use time::format_description::well_known::iso8601::TimePrecision;
-use time::format_description::well_known::{iso8601, Iso8601};
 
 use crate::state::AppState;
 
 pub mod backup;
 pub mod blob;
-pub use migrations;
-
-pub const DATETIME_CONFIG: iso8601::EncodedConfig = iso8601::Config::DEFAULT
-    .set_time_precision(TimePrecision::Second {
-        decimal_digits: Some(nonzero!(3u8)),
-    })
-    .encode();
-pub const DATETIME_FORMAT: Iso8601<DATETIME_CONFIG> = Iso8601;
-pub const DB_FILE_NAME: &str = "db.sqlite3";
+pub mod maintenance;
+pub use migrations::{self, DATETIME_FORMAT, DB_FILE_NAME};
 
 pub async fn initialize(data_dir: &Path) -> eyre::Result<Database> {
+    backup::apply_staged_restore(data_dir).wrap_err("Error applying staged restore")?;
     let path = data_dir.join(DB_FILE_NAME);
     if path.exists() {
         tracing::info!("Using existing database: {path:?}");
     } else {
         tracing::info!("No existing database found, initializing new one: {path:?}");
         if !data_dir.exists() {
-            std::fs::create_dir_all(&data_dir)?;
+            std::fs::create_dir_all(data_dir)?;
         }
     }
 
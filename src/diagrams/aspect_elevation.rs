@@ -1,21 +1,18 @@
 use std::{
-    collections::{HashMap, HashSet},
-    fmt::Display,
+    collections::HashSet,
+    fmt::{Display, Write},
     str::FromStr,
     sync::Arc,
 };
 
 use axum::{
-    extract,
+    extract::{self, State},
     http::{header, HeaderMap},
     response::IntoResponse,
     Extension,
 };
 use eyre::Context;
 use i18n_embed::fluent::FluentLanguageLoader;
-use i18n_embed_fl::fl;
-use once_cell::sync::Lazy;
-use regex::{Captures, Regex};
 use resvg::{
     tiny_skia,
     usvg::{self, PostProcessingSteps},
@@ -23,11 +20,14 @@ use resvg::{
 use serde::{Deserialize, Serialize};
 
 use crate::{
-    error::{map_eyre_error, map_std_error},
-    i18n::I18nLoader,
+    error::map_eyre_error,
+    i18n::{self, I18nLoader},
 };
 
-use super::FONT_DB;
+use super::{
+    cache::{DiagramCache, DiagramKey},
+    encode_pixmap, escape_xml, image_headers, ImageFormat, FONT_DB,
+};
 
 #[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
 pub enum Aspect {
@@ -67,7 +67,6 @@ impl Aspect {
         }
     }
 
-    #[cfg(test)]
     fn enumerate() -> &'static [Self] {
         &[
             Aspect::N,
@@ -106,26 +105,92 @@ impl FromStr for Aspect {
     }
 }
 
-#[derive(Debug, Default)]
+/// The aspects affected in one elevation band of an [`AspectElevation`] diagram.
+#[derive(Debug, Clone)]
+pub struct Band {
+    /// The id of the elevation band (e.g. `alpine`), used for the default label.
+    pub id: String,
+    pub aspects: HashSet<Aspect>,
+    /// Label to use instead of the localized name of the elevation band.
+    pub text: Option<String>,
+}
+
+impl Band {
+    pub fn new(id: impl Into<String>) -> Self {
+        Self {
+            id: id.into(),
+            aspects: HashSet::default(),
+            text: None,
+        }
+    }
+}
+
+/// Maximum number of elevation bands that can be shown in the diagram.
+pub const MAX_BANDS: usize = 5;
+
+#[derive(Debug)]
 pub struct AspectElevation {
-    pub high_alpine: HashSet<Aspect>,
-    pub high_alpine_text: Option<String>,
-    pub alpine: HashSet<Aspect>,
-    pub alpine_text: Option<String>,
-    pub sub_alpine: HashSet<Aspect>,
-    pub sub_alpine_text: Option<String>,
+    /// The elevation bands, ordered from the highest (drawn in the centre) to the lowest (drawn
+    /// on the outside).
+    pub bands: Vec<Band>,
+}
+
+impl Default for AspectElevation {
+    fn default() -> Self {
+        Self {
+            bands: ["high-alpine", "alpine", "sub-alpine"]
+                .into_iter()
+                .map(Band::new)
+                .collect(),
+        }
+    }
 }
 
 impl AspectElevation {
     pub fn into_query(self) -> Query {
-        self.into()
+        self.query()
+    }
+
+    /// The query with the aspects in a consistent order.
+    fn query(&self) -> Query {
+        let bands = self
+            .bands
+            .iter()
+            .map(|band| {
+                let aspects = iter_to_comma_separated(
+                    Aspect::enumerate()
+                        .iter()
+                        .copied()
+                        .filter(|aspect| band.aspects.contains(aspect)),
+                );
+                format!("{}:{aspects}", band.id)
+            })
+            .collect::<Vec<_>>()
+            .join(";");
+        let labels = self.bands.iter().any(|band| band.text.is_some()).then(|| {
+            self.bands
+                .iter()
+                .map(|band| band.text.as_deref().unwrap_or_default())
+                .collect::<Vec<_>>()
+                .join(";")
+        });
+        Query {
+            bands: Some(bands),
+            labels,
+            ..Query::default()
+        }
+    }
+
+    /// The query for use in a [`DiagramKey`].
+    fn normalized_query(&self) -> String {
+        serde_urlencoded::to_string(self.query())
+            .expect("Unable to serialize aspect elevation query")
     }
 }
 
-fn comma_separated_to_vec(comma_separated: String) -> eyre::Result<HashSet<Aspect>> {
+fn comma_separated_to_vec(comma_separated: &str) -> eyre::Result<HashSet<Aspect>> {
     comma_separated
         .split(',')
-        .into_iter()
         .filter_map(|aspect_str| {
             let aspect_str = aspect_str.trim();
             if aspect_str.is_empty() {
@@ -145,154 +210,341 @@ fn iter_to_comma_separated(aspects: impl IntoIterator<Item = Aspect>) -> String
         .join(",")
 }
 
+/// Parse the `bands` query parameter, in the format `{id}:{aspects};{id}:{aspects}`, e.g.
+/// `high-alpine:N,NE;alpine:;sub-alpine:N`, with the corresponding `labels` separated by `;`.
+fn parse_bands(bands: &str, labels: Option<&str>) -> eyre::Result<Vec<Band>> {
+    let mut labels = labels.into_iter().flat_map(|labels| labels.split(';'));
+    let bands = bands
+        .split(';')
+        .filter(|band| !band.trim().is_empty())
+        .map(|band| {
+            let (id, aspects) = band.split_once(':').unwrap_or((band, ""));
+            let id = id.trim();
+            if id.is_empty() || !id.chars().all(|c| c.is_ascii_alphanumeric() || c == '-') {
+                eyre::bail!("Invalid elevation band id {id:?}");
+            }
+            let text = labels
+                .next()
+                .map(str::trim)
+                .filter(|label| !label.is_empty())
+                .map(ToOwned::to_owned);
+            Ok(Band {
+                id: id.to_owned(),
+                aspects: comma_separated_to_vec(aspects)
+                    .wrap_err_with(|| format!("Error deserializing aspects for {id}"))?,
+                text,
+            })
+        })
+        .collect::<eyre::Result<Vec<_>>>()?;
+    if bands.len() > MAX_BANDS {
+        eyre::bail!(
+            "Too many elevation bands ({}), the maximum is {MAX_BANDS}",
+            bands.len()
+        );
+    }
+    Ok(bands)
+}
+
 impl TryFrom<Query> for AspectElevation {
     type Error = eyre::Error;
 
     fn try_from(query: Query) -> Result<Self, Self::Error> {
-        let high_alpine = query
-            .high_alpine
-            .map(comma_separated_to_vec)
-            .unwrap_or(Ok(HashSet::default()))
-            .wrap_err("Error deserializing high_alpine")?;
-        let alpine = query
-            .alpine
-            .map(comma_separated_to_vec)
-            .unwrap_or(Ok(HashSet::default()))
-            .wrap_err("Error deserializing alpine")?;
-        let sub_alpine = query
-            .sub_alpine
-            .map(comma_separated_to_vec)
-            .unwrap_or(Ok(HashSet::default()))
-            .wrap_err("Error deserializing sub_alpine")?;
+        if let Some(bands) = &query.bands {
+            return Ok(Self {
+                bands: parse_bands(bands, query.labels.as_deref())?,
+            });
+        }
+
+        // Support the older query format, with the three elevation bands as separate parameters.
+        let legacy_band = |id: &str, aspects: Option<String>, text: Option<String>| {
+            Ok::<_, eyre::Error>(Band {
+                id: id.to_owned(),
+                aspects: aspects
+                    .as_deref()
+                    .map(comma_separated_to_vec)
+                    .unwrap_or(Ok(HashSet::default()))
+                    .wrap_err_with(|| format!("Error deserializing {id}"))?,
+                text,
+            })
+        };
         Ok(Self {
-            high_alpine,
-            high_alpine_text: query.high_alpine_text,
-            alpine,
-            alpine_text: query.alpine_text,
-            sub_alpine,
-            sub_alpine_text: query.sub_alpine_text,
+            bands: vec![
+                legacy_band("high-alpine", query.high_alpine, query.high_alpine_text)?,
+                legacy_band("alpine", query.alpine, query.alpine_text)?,
+                legacy_band("sub-alpine", query.sub_alpine, query.sub_alpine_text)?,
+            ],
         })
     }
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Default)]
 pub struct Query {
+    /// The elevation bands, see [`parse_bands`].
+    #[serde(skip_serializing_if = "Option::is_none")]
+    bands: Option<String>,
+    /// Labels for the elevation bands, separated by `;`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    labels: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     high_alpine: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     high_alpine_text: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     alpine: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     alpine_text: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     sub_alpine: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     sub_alpine_text: Option<String>,
 }
 
-impl From<AspectElevation> for Query {
-    fn from(value: AspectElevation) -> Self {
-        Self {
-            high_alpine: Some(iter_to_comma_separated(value.high_alpine)),
-            high_alpine_text: value.high_alpine_text,
-            alpine: Some(iter_to_comma_separated(value.alpine)),
-            alpine_text: value.alpine_text,
-            sub_alpine: Some(iter_to_comma_separated(value.sub_alpine)),
-            sub_alpine_text: value.sub_alpine_text,
+const SIZE: u32 = 400;
+/// Size of the compact diagram, see [`generate_compact_svg`].
+const COMPACT_SIZE: u32 = 96;
+const VIEW_BOX: f64 = 100.0;
+const CENTRE_X: f64 = 50.0;
+const CENTRE_Y: f64 = 48.0;
+/// Distance from the centre to the tip of each aspect sector in the lowest elevation band.
+const RADIUS: f64 = 38.0;
+/// Distance from the centre to the boundary between two aspect sectors, relative to [`RADIUS`].
+const BOUNDARY_RATIO: f64 = 0.85;
+/// Size of the highest elevation band relative to the lowest.
+const MIN_BAND_SCALE: f64 = 0.45;
+const FILLED_COLOUR: &str = "#276fdcff";
+const EMPTY_COLOUR: &str = "#ffffff";
+const OUTLINE_COLOUR: &str = "#000000";
+const LEGEND_COLOUR: &str = "#949494";
+const LEGEND_X: f64 = 74.0;
+const LEGEND_BOTTOM: f64 = 99.0;
+const LEGEND_HEIGHT: f64 = 9.0;
+
+/// Point at `radius` from the centre in the direction `degrees` clockwise from north.
+fn polar(degrees: f64, radius: f64) -> (f64, f64) {
+    let radians = degrees.to_radians();
+    (
+        CENTRE_X + radius * radians.sin(),
+        CENTRE_Y - radius * radians.cos(),
+    )
+}
+
+/// Scale of the elevation band at `index` of `n_bands`, the lowest band (last) is the largest.
+fn band_scale(index: usize, n_bands: usize) -> f64 {
+    if n_bands <= 1 {
+        1.0
+    } else {
+        MIN_BAND_SCALE + (1.0 - MIN_BAND_SCALE) * index as f64 / (n_bands - 1) as f64
+    }
+}
+
+const SHADOW_DEFS: &str = r##"<defs>
+<filter id="shadow" x="-20%" y="-20%" width="140%" height="140%">
+<feDropShadow dx="0.6" dy="0.6" stdDeviation="0.8" flood-color="#000000" flood-opacity="0.4"/>
+</filter>
+</defs>"##;
+
+/// Draw the elevation bands, each a ring of aspect sectors, from the lowest band (outside) to the
+/// highest (centre).
+fn write_bands(svg: &mut String, bands: &[Band]) {
+    let n_bands = bands.len();
+    for (index, band) in bands.iter().enumerate().rev() {
+        let scale = band_scale(index, n_bands);
+        let radius = RADIUS * scale;
+        let boundary_radius = radius * BOUNDARY_RATIO;
+        let filter = if index + 1 < n_bands {
+            r#" filter="url(#shadow)""#
+        } else {
+            ""
+        };
+        let band_id = escape_xml(&band.id);
+        writeln!(svg, r#"<g id="{band_id}"{filter}>"#).expect("Writing to String should not fail");
+        for (i, aspect) in Aspect::enumerate().iter().enumerate() {
+            let degrees = i as f64 * 45.0;
+            let (x0, y0) = polar(degrees - 22.5, boundary_radius);
+            let (x1, y1) = polar(degrees, radius);
+            let (x2, y2) = polar(degrees + 22.5, boundary_radius);
+            let colour = if band.aspects.contains(aspect) {
+                FILLED_COLOUR
+            } else {
+                EMPTY_COLOUR
+            };
+            writeln!(
+                svg,
+                r#"<path id="{band_id}-{}" d="M {CENTRE_X:.2} {CENTRE_Y:.2} L {x0:.2} {y0:.2} L {x1:.2} {y1:.2} L {x2:.2} {y2:.2} Z" fill="{colour}"/>"#,
+                aspect.svg_id(),
+            )
+            .expect("Writing to String should not fail");
+        }
+        let mut outline = String::new();
+        for i in 0..16 {
+            let degrees = i as f64 * 22.5;
+            let r = if i % 2 == 0 { radius } else { boundary_radius };
+            let (x, y) = polar(degrees, r);
+            let command = if i == 0 { "M" } else { "L" };
+            write!(outline, "{command} {x:.2} {y:.2} ").expect("Writing to String should not fail");
         }
+        writeln!(
+            svg,
+            r#"<path d="{outline}Z" fill="none" stroke="{OUTLINE_COLOUR}" stroke-width="0.4" stroke-linejoin="round"/>"#
+        )
+        .expect("Writing to String should not fail");
+        // Lines dividing the aspect sectors.
+        for i in 0..4 {
+            let degrees = 22.5 + i as f64 * 45.0;
+            let (x0, y0) = polar(degrees, boundary_radius);
+            let (x1, y1) = polar(degrees + 180.0, boundary_radius);
+            writeln!(
+                svg,
+                r#"<path d="M {x0:.2} {y0:.2} L {x1:.2} {y1:.2}" stroke="{OUTLINE_COLOUR}" stroke-width="0.2"/>"#
+            )
+            .expect("Writing to String should not fail");
+        }
+        writeln!(svg, "</g>").expect("Writing to String should not fail");
     }
 }
 
-const SVG_TEMPLATE: &str = include_str!("./aspect_elevation.svg");
-const FILLED_COLOUR: &str = "#276fdcff";
-static PATH_ID_RE: Lazy<Regex> = Lazy::new(|| {
-    Regex::new(
-        r#"<path\s*style="(?P<fill>fill:(?P<colour>#ffffff);)([^/])*id="(?P<id>[a-z\-]+)"\s*[/]>"#,
-    )
-    .expect("Unable to compile svg path id regex")
-});
+pub fn generate_svg(aspect_elevation: AspectElevation, i18n: Arc<FluentLanguageLoader>) -> String {
+    let bands = &aspect_elevation.bands;
+    let n_bands = bands.len();
+    let band_scale = |index: usize| band_scale(index, n_bands);
 
-static TEXT_RE: Lazy<Regex> = Lazy::new(|| {
-    Regex::new(
-        r#"<text([^/])*id="(?P<id>[a-z\-]+)"(.|\s)*?<tspan(.|\s)*?>(?P<text>.*?)<[/]tspan>(.|\s)*?<[/]text>"#
+    let mut svg = String::new();
+    writeln!(
+        svg,
+        r##"<svg xmlns="http://www.w3.org/2000/svg" width="{SIZE}" height="{SIZE}" viewBox="0 0 {VIEW_BOX} {VIEW_BOX}">
+{SHADOW_DEFS}"##
     )
-    .expect("Unable to compile svg text regex")
-});
-
-fn generate_svg(aspect_elevation: AspectElevation, i18n: Arc<FluentLanguageLoader>) -> String {
-    let high_alpine_ids = aspect_elevation.high_alpine.iter().map(|aspect| {
-        let id = aspect.svg_id();
-        format!("high-alpine-{id}")
-    });
-    let alpine_ids = aspect_elevation.alpine.iter().map(|aspect| {
-        let id = aspect.svg_id();
-        format!("alpine-{id}")
-    });
-    let sub_alpine_ids = aspect_elevation.sub_alpine.iter().map(|aspect| {
-        let id = aspect.svg_id();
-        format!("sub-alpine-{id}")
-    });
-
-    let colour_map: HashMap<String, &str> = high_alpine_ids
-        .chain(alpine_ids)
-        .chain(sub_alpine_ids)
-        .map(|id| (id, FILLED_COLOUR))
-        .collect();
-
-    let svg = PATH_ID_RE.replace_all(SVG_TEMPLATE, |captures: &Captures| {
-        let id = captures.name("id").unwrap().as_str();
-        let captured_string = captures.get(0).unwrap().as_str();
-        if let Some(colour) = colour_map.get(id) {
-            let fill_group_string = captures.name("fill").unwrap().as_str();
-            captured_string.replace(fill_group_string, &format!("fill:{colour};"))
+    .expect("Writing to String should not fail");
+
+    write_bands(&mut svg, bands);
+
+    // Compass labels.
+    for (i, aspect) in Aspect::enumerate().iter().enumerate() {
+        let degrees = i as f64 * 45.0;
+        let (font_size, radius) = if i % 2 == 0 {
+            (10.0, 44.0)
         } else {
-            captured_string.to_string()
-        }
-    });
-
-    TEXT_RE
-        .replace_all(&svg, |captures: &Captures| {
-            let id = captures.name("id").unwrap().as_str();
-            let captured_string = captures.get(0).unwrap().as_str();
-            let original_text = captures.name("text").unwrap().as_str();
-
-            match id {
-                "high-alpine-text" => {
-                    let high_alpine_text = aspect_elevation
-                        .high_alpine_text
-                        .to_owned()
-                        .unwrap_or_else(|| fl!(&*i18n, "elevation-band-high-alpine").to_string());
-                    captured_string.replace(original_text, &high_alpine_text)
-                }
-                "alpine-text" => {
-                    let alpine_text = aspect_elevation
-                        .alpine_text
-                        .to_owned()
-                        .unwrap_or_else(|| fl!(&*i18n, "elevation-band-alpine").to_string());
-                    captured_string.replace(original_text, &alpine_text)
-                }
-                "sub-alpine-text" => {
-                    let sub_alpine_text = aspect_elevation
-                        .sub_alpine_text
-                        .to_owned()
-                        .unwrap_or_else(|| fl!(&*i18n, "elevation-band-sub-alpine").to_string());
-                    captured_string.replace(original_text, &sub_alpine_text)
-                }
-                _ => captured_string.to_string(),
-            }
-        })
-        .to_string()
+            (8.0, 46.0)
+        };
+        let (x, y) = polar(degrees, radius);
+        let y = y + font_size * 0.35;
+        writeln!(
+            svg,
+            r##"<text x="{x:.2}" y="{y:.2}" text-anchor="middle" font-family="Noto Sans" font-weight="bold" font-size="{font_size}" fill="#000000">{aspect}</text>"##
+        )
+        .expect("Writing to String should not fail");
+    }
+
+    // Legend, with a line from each label to its elevation band.
+    let spacing = if n_bands <= 1 {
+        LEGEND_HEIGHT
+    } else {
+        f64::min(4.2, LEGEND_HEIGHT / (n_bands - 1) as f64)
+    };
+    let font_size = f64::min(3.7, spacing * 0.9);
+    for (index, band) in bands.iter().enumerate() {
+        let y = LEGEND_BOTTOM - spacing * (n_bands - 1 - index) as f64;
+        let inner_scale = if index == 0 {
+            0.0
+        } else {
+            band_scale(index - 1)
+        };
+        let line_radius = RADIUS * BOUNDARY_RATIO * (band_scale(index) + inner_scale) / 2.0;
+        let (x0, y0) = polar(160.0, line_radius);
+        let line_y = y - font_size * 0.35;
+        let label = match &band.text {
+            Some(text) => text.clone(),
+            None => i18n::message_or(&i18n, &format!("elevation-band-{}", band.id), &band.id),
+        };
+        let label = escape_xml(&label);
+        let line_end_x = LEGEND_X - 0.8;
+        let line_x = LEGEND_X - 4.0;
+        writeln!(
+            svg,
+            r#"<path d="M {x0:.2} {y0:.2} L {line_x:.2} {line_y:.2} L {line_end_x:.2} {line_y:.2}" fill="none" stroke="{LEGEND_COLOUR}" stroke-width="0.3"/>
+<text x="{LEGEND_X:.2}" y="{y:.2}" font-family="Noto Sans" font-size="{font_size:.2}" fill="{LEGEND_COLOUR}">{label}</text>"#
+        )
+        .expect("Writing to String should not fail");
+    }
+
+    writeln!(svg, "</svg>").expect("Writing to String should not fail");
+    svg
+}
+
+/// A small version of the diagram for the forecast summary cards, with only the north label and
+/// no legend, cropped to the rose.
+pub fn generate_compact_svg(aspect_elevation: &AspectElevation) -> String {
+    let font_size = 10.0;
+    let (x, y) = polar(0.0, 44.0);
+    let y = y + font_size * 0.35;
+    let min_x = CENTRE_X - 44.0;
+    let view_box_size = 88.0;
+    let mut svg = String::new();
+    writeln!(
+        svg,
+        r##"<svg xmlns="http://www.w3.org/2000/svg" width="{COMPACT_SIZE}" height="{COMPACT_SIZE}" viewBox="{min_x} 0 {view_box_size} {view_box_size}">
+{SHADOW_DEFS}"##
+    )
+    .expect("Writing to String should not fail");
+    write_bands(&mut svg, &aspect_elevation.bands);
+    writeln!(
+        svg,
+        r##"<text x="{x:.2}" y="{y:.2}" text-anchor="middle" font-family="Noto Sans" font-weight="bold" font-size="{font_size}" fill="#000000">{}</text>
+</svg>"##,
+        Aspect::N
+    )
+    .expect("Writing to String should not fail");
+    svg
 }
 
 pub async fn svg_handler(
     extract::Query(query): extract::Query<Query>,
     Extension(i18n): Extension<I18nLoader>,
+    State(cache): State<Arc<DiagramCache>>,
+) -> axum::response::Result<impl IntoResponse> {
+    let mut headers = HeaderMap::new();
+    headers.insert(header::CONTENT_TYPE, "image/svg+xml".parse().unwrap());
+    let aspect_elevation = AspectElevation::try_from(query).map_err(map_eyre_error)?;
+    let key = DiagramKey::new(
+        "aspect_elevation.svg",
+        aspect_elevation.normalized_query(),
+        &i18n,
+    );
+    let svg_data = cache
+        .get_or_render(key, move || {
+            Ok(generate_svg(aspect_elevation, i18n).into_bytes())
+        })
+        .await
+        .map_err(map_eyre_error)?;
+    Ok((headers, svg_data))
+}
+
+pub async fn compact_svg_handler(
+    extract::Query(query): extract::Query<Query>,
+    Extension(i18n): Extension<I18nLoader>,
+    State(cache): State<Arc<DiagramCache>>,
 ) -> axum::response::Result<impl IntoResponse> {
     let mut headers = HeaderMap::new();
-    // headers.insert(header::CONTENT_TYPE, "image/svg+xml".parse().unwrap());
     headers.insert(header::CONTENT_TYPE, "image/svg+xml".parse().unwrap());
     let aspect_elevation = AspectElevation::try_from(query).map_err(map_eyre_error)?;
-    Ok((headers, generate_svg(aspect_elevation, i18n)))
+    let key = DiagramKey::new(
+        "aspect_elevation_compact.svg",
+        aspect_elevation.normalized_query(),
+        &i18n,
+    );
+    let svg_data = cache
+        .get_or_render(key, move || {
+            Ok(generate_compact_svg(&aspect_elevation).into_bytes())
+        })
+        .await
+        .map_err(map_eyre_error)?;
+    Ok((headers, svg_data))
 }
 
-fn generate_png(
+fn generate_image(
     aspect_elevation: AspectElevation,
     i18n: Arc<FluentLanguageLoader>,
+    format: ImageFormat,
 ) -> eyre::Result<Vec<u8>> {
     let svg = generate_svg(aspect_elevation, i18n);
     let options = usvg::Options::default();
@@ -311,90 +563,150 @@ fn generate_png(
         resvg::tiny_skia::Transform::default(),
         &mut pixmap.as_mut(),
     );
-    pixmap.encode_png().map_err(eyre::Error::from)
+    encode_pixmap(&pixmap, format)
 }
 
 pub async fn png_handler(
     extract::Query(aspect_elevation_query): extract::Query<Query>,
     Extension(i18n): Extension<I18nLoader>,
+    State(cache): State<Arc<DiagramCache>>,
+    request_headers: HeaderMap,
 ) -> axum::response::Result<impl IntoResponse> {
-    let mut headers = HeaderMap::new();
-    headers.insert(header::CONTENT_TYPE, "image/png".parse().unwrap());
+    let format = ImageFormat::negotiate(&request_headers);
     let aspect_elevation =
         AspectElevation::try_from(aspect_elevation_query).map_err(map_eyre_error)?;
-    let png_data = tokio::task::spawn_blocking(move || {
-        generate_png(aspect_elevation, i18n).wrap_err("Error generating png")
-    })
-    .await
-    .map_err(map_std_error)?
-    .map_err(map_eyre_error)?;
-    Ok((headers, png_data))
+    let key = DiagramKey::new(
+        "aspect_elevation.png",
+        aspect_elevation.normalized_query(),
+        &i18n,
+    )
+    .with_image_format(format);
+    let image_data = cache
+        .get_or_render(key, move || {
+            generate_image(aspect_elevation, i18n, format).wrap_err("Error generating image")
+        })
+        .await
+        .map_err(map_eyre_error)?;
+    Ok((image_headers(&image_data), image_data))
 }
 
 #[cfg(test)]
 mod test {
     use std::collections::HashSet;
 
-    use crate::i18n::{self, load_available_languages, I18nLoader};
+    use resvg::usvg;
 
-    use super::{generate_svg, Aspect, AspectElevation};
+    use crate::i18n::test_loader;
 
-    use once_cell::sync::Lazy;
-    use unic_langid::LanguageIdentifier;
-
-    static LOADER: Lazy<I18nLoader> = Lazy::new(|| {
-        let (loader, _) = i18n::initialize(&crate::options::I18n::default()).unwrap();
-        load_available_languages(&loader, &["en-UK".parse::<LanguageIdentifier>().unwrap()])
-            .unwrap();
-        loader
-    });
+    use super::{generate_compact_svg, generate_svg, Aspect, AspectElevation, Band, Query};
 
     #[test]
     fn test_generate_svg_empty() {
-        let svg = generate_svg(AspectElevation::default(), LOADER.clone());
+        let svg = generate_svg(AspectElevation::default(), test_loader());
         insta::assert_snapshot!(svg);
     }
 
     #[test]
     fn test_generate_svg_all_aspects() {
-        let all_aspects: HashSet<Aspect> = Aspect::enumerate().into_iter().cloned().collect();
-        let svg = generate_svg(
-            AspectElevation {
-                high_alpine: all_aspects.clone(),
-                alpine: all_aspects.clone(),
-                sub_alpine: all_aspects.clone(),
-                ..AspectElevation::default()
-            },
-            LOADER.clone(),
-        );
+        let all_aspects: HashSet<Aspect> = Aspect::enumerate().iter().copied().collect();
+        let mut aspect_elevation = AspectElevation::default();
+        for band in &mut aspect_elevation.bands {
+            band.aspects = all_aspects.clone();
+        }
+        let svg = generate_svg(aspect_elevation, test_loader());
         insta::assert_snapshot!(svg);
     }
 
     #[test]
     fn test_generate_svg_alpine_n_w() {
-        let svg = generate_svg(
-            AspectElevation {
-                high_alpine: HashSet::default(),
-                alpine: vec![Aspect::N, Aspect::NW].into_iter().collect(),
-                sub_alpine: HashSet::default(),
-                ..AspectElevation::default()
-            },
-            LOADER.clone(),
-        );
+        let mut aspect_elevation = AspectElevation::default();
+        aspect_elevation.bands[1].aspects = vec![Aspect::N, Aspect::NW].into_iter().collect();
+        let svg = generate_svg(aspect_elevation, test_loader());
         insta::assert_snapshot!(svg);
     }
 
     #[test]
     fn test_generate_svg_text() {
-        let svg = generate_svg(
-            AspectElevation {
-                high_alpine_text: Some("Test High Alpine".to_owned()),
-                alpine_text: Some("Test Alpine".to_owned()),
-                sub_alpine_text: Some("Test Sub Alpine".to_owned()),
-                ..AspectElevation::default()
-            },
-            LOADER.clone(),
-        );
+        let mut aspect_elevation = AspectElevation::default();
+        for (band, text) in aspect_elevation.bands.iter_mut().zip([
+            "Test High Alpine",
+            "Test Alpine",
+            "Test Sub Alpine",
+        ]) {
+            band.text = Some(text.to_owned());
+        }
+        let svg = generate_svg(aspect_elevation, test_loader());
         insta::assert_snapshot!(svg);
     }
+
+    #[test]
+    fn test_generate_svg_five_bands() {
+        let bands = ["summit", "high-alpine", "alpine", "sub-alpine", "valley"]
+            .into_iter()
+            .enumerate()
+            .map(|(i, id)| Band {
+                aspects: Aspect::enumerate()
+                    .iter()
+                    .copied()
+                    .skip(i)
+                    .take(3)
+                    .collect(),
+                ..Band::new(id)
+            })
+            .collect();
+        let svg = generate_svg(AspectElevation { bands }, test_loader());
+        insta::assert_snapshot!(svg);
+    }
+
+    #[test]
+    fn test_generate_compact_svg() {
+        let mut aspect_elevation = AspectElevation::default();
+        aspect_elevation.bands[1].aspects = vec![Aspect::N, Aspect::NW].into_iter().collect();
+        let svg = generate_compact_svg(&aspect_elevation);
+        assert!(
+            svg.starts_with(r#"<svg xmlns="http://www.w3.org/2000/svg" width="96" height="96""#)
+        );
+        assert!(svg.contains(r##"<path id="alpine-n" d="##));
+        assert_eq!(svg.matches(r##"fill="#276fdcff""##).count(), 2);
+        // Only the north label, and no legend.
+        assert_eq!(svg.matches("<text").count(), 1);
+        assert!(usvg::Tree::from_str(&svg, &usvg::Options::default()).is_ok());
+    }
+
+    #[test]
+    fn test_query() {
+        let query: Query =
+            serde_urlencoded::from_str("bands=high-alpine:N,ne;low:&labels=;Low%20%26%20Valley")
+                .unwrap();
+        let aspect_elevation = AspectElevation::try_from(query).unwrap();
+        assert_eq!(aspect_elevation.bands.len(), 2);
+        assert_eq!(aspect_elevation.bands[0].id, "high-alpine");
+        assert_eq!(
+            aspect_elevation.bands[0].aspects,
+            [Aspect::N, Aspect::NE].into_iter().collect()
+        );
+        assert_eq!(aspect_elevation.bands[0].text, None);
+        assert_eq!(aspect_elevation.bands[1].id, "low");
+        assert!(aspect_elevation.bands[1].aspects.is_empty());
+        assert_eq!(
+            aspect_elevation.bands[1].text.as_deref(),
+            Some("Low & Valley")
+        );
+        assert_eq!(
+            aspect_elevation.normalized_query(),
+            "bands=high-alpine%3AN%2CNE%3Blow%3A&labels=%3BLow+%26+Valley"
+        );
+
+        let legacy: Query = serde_urlencoded::from_str("high_alpine=N&sub_alpine=S,SW").unwrap();
+        let aspect_elevation = AspectElevation::try_from(legacy).unwrap();
+        assert_eq!(
+            aspect_elevation.normalized_query(),
+            "bands=high-alpine%3AN%3Balpine%3A%3Bsub-alpine%3AS%2CSW"
+        );
+
+        let invalid: Query = serde_urlencoded::from_str("bands=a<b:N").unwrap();
+        assert!(AspectElevation::try_from(invalid).is_err());
+        let too_many: Query = serde_urlencoded::from_str("bands=a:;b:;c:;d:;e:;f:").unwrap();
+        assert!(AspectElevation::try_from(too_many).is_err());
+    }
 }
@@ -0,0 +1,335 @@
+//! Bar charts of the avalanches reported in observations during a season (see
+//! [`crate::observations::activity`]), by day (stacked by natural and triggered avalanches), by
+//! size, and by avalanche problem.
+
+use std::fmt::Write;
+
+use axum::{
+    extract,
+    http::{header, HeaderMap},
+    response::IntoResponse,
+    Extension,
+};
+use i18n_embed_fl::fl;
+use serde::Deserialize;
+
+use crate::{
+    database::Database,
+    error::map_eyre_error,
+    forecasts::variant_id,
+    i18n::I18nLoader,
+    observations::activity::{load_activity, select_season, SeasonActivity},
+    statistics::Season,
+};
+
+use super::{tick_step, write_text};
+
+const WIDTH: f64 = 760.0;
+const HEIGHT: f64 = 400.0;
+const PLOT_LEFT: f64 = 60.0;
+const PLOT_RIGHT: f64 = 740.0;
+const PLOT_TOP: f64 = 50.0;
+const PLOT_BOTTOM: f64 = 300.0;
+const NATURAL_COLOUR: &str = "#2c5aa0";
+const TRIGGERED_COLOUR: &str = "#d40000";
+const BAR_COLOUR: &str = "#1e3a5f";
+const GRID_COLOUR: &str = "#dddddd";
+
+/// Which of the season's summaries to chart.
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum Chart {
+    Days,
+    Sizes,
+    Problems,
+}
+
+#[derive(Deserialize)]
+pub struct Query {
+    chart: Chart,
+    /// The year that the season starts in, defaults to the most recent season with activity.
+    season: Option<Season>,
+}
+
+/// A bar, divided into a segment for each series of the chart.
+struct Bar {
+    /// Not shown when empty.
+    label: String,
+    values: Vec<usize>,
+}
+
+/// The bars of the `chart`, along with the colour and legend label of each series.
+fn chart_bars(
+    activity: &SeasonActivity,
+    chart: Chart,
+    i18n: &I18nLoader,
+) -> (Vec<Bar>, Vec<(&'static str, String)>) {
+    match chart {
+        Chart::Days => {
+            let (Some(first), Some(last)) = (activity.days.first(), activity.days.last()) else {
+                return (Vec::new(), Vec::new());
+            };
+            // Include the days without avalanches, so that the time axis is linear.
+            let days = (last.date - first.date).whole_days() + 1;
+            let label_step = tick_step(days as f64) as i64;
+            let date_format = time::macros::format_description!("[day]/[month]");
+            let bars = (0..days)
+                .map(|index| {
+                    let date = first.date + time::Duration::days(index);
+                    let values = activity
+                        .days
+                        .iter()
+                        .find(|day| day.date == date)
+                        .map(|day| vec![day.natural, day.triggered])
+                        .unwrap_or_else(|| vec![0, 0]);
+                    let label = if index % label_step == 0 {
+                        date.format(&date_format).unwrap_or_default()
+                    } else {
+                        String::new()
+                    };
+                    Bar { label, values }
+                })
+                .collect();
+            let series = vec![
+                (
+                    NATURAL_COLOUR,
+                    fl!(&**i18n, "avalanche-activity-natural-label"),
+                ),
+                (
+                    TRIGGERED_COLOUR,
+                    fl!(&**i18n, "avalanche-activity-triggered-label"),
+                ),
+            ];
+            (bars, series)
+        }
+        Chart::Sizes => {
+            let mut bars: Vec<Bar> = activity
+                .sizes
+                .iter()
+                .map(|size| Bar {
+                    label: fl!(&**i18n, "avalanche-size-n", size = size.size),
+                    values: vec![size.count],
+                })
+                .collect();
+            if activity.unknown_size > 0 {
+                bars.push(Bar {
+                    label: fl!(&**i18n, "avalanche-activity-unknown-label"),
+                    values: vec![activity.unknown_size],
+                });
+            }
+            (bars, vec![(BAR_COLOUR, String::new())])
+        }
+        Chart::Problems => {
+            let mut bars: Vec<Bar> = activity
+                .problems
+                .iter()
+                .map(|problem| Bar {
+                    label: i18n.get(&format!("problem-type-{}", variant_id(&problem.kind))),
+                    values: vec![problem.count],
+                })
+                .collect();
+            if activity.unknown_problem > 0 {
+                bars.push(Bar {
+                    label: fl!(&**i18n, "avalanche-activity-unknown-label"),
+                    values: vec![activity.unknown_problem],
+                });
+            }
+            (bars, vec![(BAR_COLOUR, String::new())])
+        }
+    }
+}
+
+pub fn generate_svg(activity: &SeasonActivity, chart: Chart, i18n: &I18nLoader) -> String {
+    let (bars, series) = chart_bars(activity, chart, i18n);
+
+    let mut svg = String::new();
+    writeln!(
+        svg,
+        r##"<?xml version="1.0" encoding="UTF-8" standalone="no"?>
+<svg width="{WIDTH}" height="{HEIGHT}" viewBox="0 0 {WIDTH} {HEIGHT}" version="1.1" xmlns="http://www.w3.org/2000/svg">
+<rect x="0" y="0" width="{WIDTH}" height="{HEIGHT}" style="fill:#ffffff"/>"##
+    )
+    .expect("Writing to String should not fail");
+
+    // Horizontal grid lines and count labels.
+    let max = bars
+        .iter()
+        .map(|bar| bar.values.iter().sum::<usize>())
+        .max()
+        .unwrap_or(0)
+        .max(1) as f64;
+    let step = tick_step(max);
+    let top = (max / step).ceil() * step;
+    let y_for_count = |count: f64| PLOT_BOTTOM - (count / top) * (PLOT_BOTTOM - PLOT_TOP);
+    let ticks = (top / step).round() as i64;
+    for tick in (0..=ticks).map(|i| i as f64 * step) {
+        let y = y_for_count(tick);
+        writeln!(
+            svg,
+            r#"<line x1="{PLOT_LEFT}" y1="{y:.1}" x2="{PLOT_RIGHT}" y2="{y:.1}" style="stroke:{GRID_COLOUR};stroke-width:1"/>"#
+        )
+        .expect("Writing to String should not fail");
+        write_text(
+            &mut svg,
+            PLOT_LEFT - 6.0,
+            y + 4.0,
+            "end",
+            11,
+            "#000000",
+            &format!("{tick}"),
+        );
+    }
+    write_text(
+        &mut svg,
+        PLOT_LEFT,
+        PLOT_TOP - 16.0,
+        "start",
+        13,
+        "#000000",
+        &fl!(&**i18n, "avalanche-activity-count-label"),
+    );
+
+    // Legend, only for charts with multiple series.
+    if series.len() > 1 {
+        for (index, (colour, label)) in series.iter().enumerate() {
+            let x = PLOT_RIGHT - 120.0 * (series.len() - index) as f64;
+            writeln!(
+                svg,
+                r#"<rect x="{x:.1}" y="{y:.1}" width="12" height="12" style="fill:{colour}"/>"#,
+                y = PLOT_TOP - 27.0,
+            )
+            .expect("Writing to String should not fail");
+            write_text(
+                &mut svg,
+                x + 16.0,
+                PLOT_TOP - 16.0,
+                "start",
+                11,
+                "#000000",
+                label,
+            );
+        }
+    }
+
+    // Bars, with their labels rotated so that they don't overlap.
+    let slot = (PLOT_RIGHT - PLOT_LEFT) / bars.len().max(1) as f64;
+    let width = (slot * 0.8).max(1.0);
+    for (index, bar) in bars.iter().enumerate() {
+        let x = PLOT_LEFT + index as f64 * slot + (slot - width) / 2.0;
+        let mut base = 0;
+        for (value, (colour, _)) in bar.values.iter().zip(&series) {
+            if *value == 0 {
+                continue;
+            }
+            let y_top = y_for_count((base + value) as f64);
+            let y_bottom = y_for_count(base as f64);
+            writeln!(
+                svg,
+                r#"<rect x="{x:.1}" y="{y_top:.1}" width="{width:.1}" height="{height:.1}" style="fill:{colour}"/>"#,
+                height = y_bottom - y_top,
+            )
+            .expect("Writing to String should not fail");
+            base += value;
+        }
+        if !bar.label.is_empty() {
+            let label_x = x + width / 2.0;
+            let label_y = PLOT_BOTTOM + 14.0;
+            writeln!(
+                svg,
+                r#"<g transform="rotate(-40 {label_x:.1} {label_y:.1})">"#
+            )
+            .expect("Writing to String should not fail");
+            write_text(&mut svg, label_x, label_y, "end", 11, "#000000", &bar.label);
+            svg.push_str("</g>\n");
+        }
+    }
+    writeln!(
+        svg,
+        r#"<line x1="{PLOT_LEFT}" y1="{PLOT_BOTTOM}" x2="{PLOT_RIGHT}" y2="{PLOT_BOTTOM}" style="stroke:#000000;stroke-width:1"/>"#
+    )
+    .expect("Writing to String should not fail");
+
+    if activity.avalanches == 0 {
+        write_text(
+            &mut svg,
+            (PLOT_LEFT + PLOT_RIGHT) / 2.0,
+            (PLOT_TOP + PLOT_BOTTOM) / 2.0,
+            "middle",
+            16,
+            "#000000",
+            &fl!(&**i18n, "avalanche-activity-no-data-message"),
+        );
+    }
+
+    svg.push_str("</svg>\n");
+    svg
+}
+
+pub async fn svg_handler(
+    extract::Query(query): extract::Query<Query>,
+    Extension(database): Extension<Database>,
+    Extension(i18n): Extension<I18nLoader>,
+) -> axum::response::Result<impl IntoResponse> {
+    let activity = load_activity(&database).await.map_err(map_eyre_error)?;
+    let activity = select_season(activity, query.season).unwrap_or_else(|| {
+        SeasonActivity::empty(
+            query
+                .season
+                .unwrap_or_else(|| Season::containing(time::OffsetDateTime::now_utc())),
+        )
+    });
+    let mut headers = HeaderMap::new();
+    headers.insert(header::CONTENT_TYPE, "image/svg+xml".parse().unwrap());
+    Ok((headers, generate_svg(&activity, query.chart, &i18n)))
+}
+
+#[cfg(test)]
+mod test {
+    use resvg::usvg;
+    use time::macros::{date, datetime};
+
+    use crate::{
+        i18n::test_loader,
+        observations::activity::{DayActivity, SeasonActivity},
+        statistics::Season,
+    };
+
+    use super::{generate_svg, Chart};
+
+    #[test]
+    fn test_generate_svg() {
+        let i18n = test_loader();
+        let mut activity =
+            SeasonActivity::empty(Season::containing(datetime!(2024-01-01 0:00 UTC)));
+        let svg = generate_svg(&activity, Chart::Days, &i18n);
+        assert!(svg.contains("No avalanches"));
+        assert!(usvg::Tree::from_str(&svg, &usvg::Options::default()).is_ok());
+
+        activity.avalanches = 3;
+        activity.natural = 2;
+        activity.triggered = 1;
+        activity.days = vec![
+            DayActivity {
+                date: date!(2024 - 01 - 05),
+                natural: 1,
+                triggered: 0,
+            },
+            DayActivity {
+                date: date!(2024 - 01 - 07),
+                natural: 1,
+                triggered: 1,
+            },
+        ];
+        activity.sizes[1].count = 3;
+        let svg = generate_svg(&activity, Chart::Days, &i18n);
+        // A segment for each series on the 7th, none on the 6th.
+        assert_eq!(svg.matches("<rect").count(), 1 + 2 + 3);
+        assert!(svg.contains("05/01"));
+        assert!(usvg::Tree::from_str(&svg, &usvg::Options::default()).is_ok());
+
+        let svg = generate_svg(&activity, Chart::Sizes, &i18n);
+        // Fluent isolates the placeable.
+        assert!(svg.contains("Size \u{2068}2\u{2069}"));
+        assert!(usvg::Tree::from_str(&svg, &usvg::Options::default()).is_ok());
+    }
+}
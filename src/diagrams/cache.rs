@@ -0,0 +1,141 @@
+//! An in-memory least recently used cache for rendered diagrams. The same diagrams are
+//! requested many times with identical parameters (each forecast page includes several), and
+//! rendering a PNG is comparatively slow.
+
+use std::sync::{Arc, Mutex};
+
+use bytes::Bytes;
+use i18n_embed::LanguageLoader;
+use indexmap::IndexMap;
+
+use crate::i18n::I18nLoader;
+
+use super::ImageFormat;
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct DiagramKey {
+    /// The diagram and its format, e.g. `aspect_elevation.png`.
+    pub diagram: &'static str,
+    /// The diagram's parameters, normalized so that equivalent queries have the same key.
+    pub query: String,
+    /// The language the diagram is rendered in.
+    pub language: String,
+    /// The format negotiated for a raster diagram, see [`ImageFormat::negotiate`].
+    pub image_format: Option<ImageFormat>,
+}
+
+impl DiagramKey {
+    pub fn new(diagram: &'static str, query: String, i18n: &I18nLoader) -> Self {
+        Self {
+            diagram,
+            query,
+            language: i18n.current_language().to_string(),
+            image_format: None,
+        }
+    }
+
+    /// The key for a raster diagram rendered in the `image_format`.
+    pub fn with_image_format(self, image_format: ImageFormat) -> Self {
+        Self {
+            image_format: Some(image_format),
+            ..self
+        }
+    }
+}
+
+pub struct DiagramCache {
+    capacity: usize,
+    /// Entries ordered from least to most recently used.
+    entries: Mutex<IndexMap<DiagramKey, Bytes>>,
+}
+
+impl DiagramCache {
+    /// Create a cache holding up to `capacity` diagrams, a `capacity` of `0` disables caching.
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            entries: Mutex::new(IndexMap::with_capacity(capacity)),
+        }
+    }
+
+    pub fn get(&self, key: &DiagramKey) -> Option<Bytes> {
+        let mut entries = self.entries.lock().expect("Diagram cache lock poisoned");
+        let (key, value) = entries.shift_remove_entry(key)?;
+        entries.insert(key, value.clone());
+        Some(value)
+    }
+
+    pub fn insert(&self, key: DiagramKey, value: Bytes) {
+        if self.capacity == 0 {
+            return;
+        }
+        let mut entries = self.entries.lock().expect("Diagram cache lock poisoned");
+        entries.shift_remove(&key);
+        while entries.len() >= self.capacity {
+            entries.shift_remove_index(0);
+        }
+        entries.insert(key, value);
+    }
+
+    /// Get the diagram from the cache, or render it on a blocking thread using `render` and
+    /// cache the result.
+    pub async fn get_or_render<F>(
+        self: &Arc<Self>,
+        key: DiagramKey,
+        render: F,
+    ) -> eyre::Result<Bytes>
+    where
+        F: FnOnce() -> eyre::Result<Vec<u8>> + Send + 'static,
+    {
+        if let Some(value) = self.get(&key) {
+            metrics::counter!("diagram_cache_hits_total").increment(1);
+            return Ok(value);
+        }
+        metrics::counter!("diagram_cache_misses_total").increment(1);
+        let value = Bytes::from(tokio::task::spawn_blocking(render).await??);
+        self.insert(key, value.clone());
+        Ok(value)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use bytes::Bytes;
+
+    use super::{DiagramCache, DiagramKey};
+
+    fn key(query: &str) -> DiagramKey {
+        DiagramKey {
+            diagram: "aspect_elevation.png",
+            query: query.to_owned(),
+            language: "en-UK".to_owned(),
+            image_format: None,
+        }
+    }
+
+    #[test]
+    fn test_least_recently_used_eviction() {
+        let cache = DiagramCache::new(2);
+        cache.insert(key("a"), Bytes::from_static(b"a"));
+        cache.insert(key("b"), Bytes::from_static(b"b"));
+        // Using "a" makes "b" the least recently used.
+        assert_eq!(cache.get(&key("a")), Some(Bytes::from_static(b"a")));
+        cache.insert(key("c"), Bytes::from_static(b"c"));
+        assert_eq!(cache.get(&key("b")), None);
+        assert_eq!(cache.get(&key("a")), Some(Bytes::from_static(b"a")));
+        assert_eq!(cache.get(&key("c")), Some(Bytes::from_static(b"c")));
+
+        let language = DiagramKey {
+            language: "ka-GE".to_owned(),
+            ..key("a")
+        };
+        assert_eq!(cache.get(&language), None);
+    }
+
+    #[test]
+    fn test_disabled() {
+        let cache = DiagramCache::new(0);
+        cache.insert(key("a"), Bytes::from_static(b"a"));
+        assert_eq!(cache.get(&key("a")), None);
+    }
+}
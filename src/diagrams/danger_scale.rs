@@ -0,0 +1,184 @@
+//! A banner showing the five levels of the European avalanche danger scale, with the forecast
+//! level highlighted, alongside the elevation band it applies to. Used in the index summary and
+//! the PDF bulletin.
+
+use std::{fmt::Write, sync::Arc};
+
+use axum::{
+    extract::{self, State},
+    http::{header, HeaderMap},
+    response::IntoResponse,
+    Extension,
+};
+use eyre::Context;
+use serde::{Deserialize, Serialize};
+
+use crate::{error::map_eyre_error, i18n::I18nLoader};
+
+use super::{
+    cache::{DiagramCache, DiagramKey},
+    elevation_hazard::{ElevationBand, HazardLevel},
+    escape_xml,
+};
+
+const WIDTH: u32 = 500;
+const HEIGHT: u32 = 120;
+const SCALE_X: f64 = 115.0;
+const SCALE_STEP: f64 = 77.5;
+const LEVELS: [HazardLevel; 5] = [
+    HazardLevel::Low,
+    HazardLevel::Moderate,
+    HazardLevel::Considerable,
+    HazardLevel::High,
+    HazardLevel::Extreme,
+];
+
+#[derive(Serialize, Deserialize)]
+pub struct Query {
+    /// The elevation band that the level applies to, or the whole mountain if not specified.
+    pub elevation_band: Option<ElevationBand>,
+    pub hazard_level: HazardLevel,
+}
+
+impl Query {
+    /// The query for use in a [`DiagramKey`].
+    fn normalized(&self) -> String {
+        serde_urlencoded::to_string(self).expect("Unable to serialize danger scale query")
+    }
+}
+
+pub fn generate_svg(query: &Query, i18n: &I18nLoader) -> String {
+    let level_id = query.hazard_level.id();
+    let level_label = i18n.get(&format!("avalanche-hazard-{level_id}"));
+    let label = match query.elevation_band {
+        Some(band) => {
+            let band_id = band.id();
+            format!(
+                "{}: {level_label}",
+                i18n.get(&format!("elevation-band-{band_id}"))
+            )
+        }
+        None => level_label,
+    };
+    generate_svg_with_label(query.elevation_band, query.hazard_level, &label)
+}
+
+/// Generate the banner with a caption `label` instead of the localized name of the elevation
+/// band and level.
+pub fn generate_svg_with_label(
+    elevation_band: Option<ElevationBand>,
+    hazard_level: HazardLevel,
+    label: &str,
+) -> String {
+    let mut svg = String::new();
+    writeln!(
+        svg,
+        r##"<svg xmlns="http://www.w3.org/2000/svg" width="{WIDTH}" height="{HEIGHT}" viewBox="0 0 {WIDTH} {HEIGHT}">"##
+    )
+    .expect("Writing to String should not fail");
+
+    // The mountain, divided into the three elevation bands.
+    let band_colour = |band: ElevationBand| match elevation_band {
+        Some(selected) if selected != band => "#ffffffff",
+        _ => hazard_level.colour_hex(),
+    };
+    let high_alpine = band_colour(ElevationBand::HighAlpine);
+    let alpine = band_colour(ElevationBand::Alpine);
+    let sub_alpine = band_colour(ElevationBand::SubAlpine);
+    write!(
+        svg,
+        r##"<path d="M 50,10 L 33.33,43.33 L 66.67,43.33 Z" style="fill:{high_alpine}"/>
+<path d="M 33.33,43.33 L 16.67,76.67 L 83.33,76.67 L 66.67,43.33 Z" style="fill:{alpine}"/>
+<path d="M 16.67,76.67 L 0,110 L 100,110 L 83.33,76.67 Z" style="fill:{sub_alpine}"/>
+<path d="M 50,10 L 0,110 L 100,110 Z M 33.33,43.33 H 66.67 M 16.67,76.67 H 83.33" style="fill:none;stroke:#000000;stroke-width:2;stroke-linejoin:round"/>
+"##
+    )
+    .expect("Writing to String should not fail");
+
+    // The danger scale, with the current level highlighted.
+    for (i, level) in LEVELS.iter().enumerate() {
+        let x = SCALE_X + i as f64 * SCALE_STEP;
+        let text_x = x + 37.5;
+        let colour = level.colour_hex();
+        let number = i + 1;
+        let selected = *level == hazard_level;
+        let (opacity, stroke, stroke_width) = if selected {
+            (1.0, "#000000", 3)
+        } else {
+            (0.25, "#999999", 1)
+        };
+        let text_colour = match level {
+            HazardLevel::Extreme if selected => "#ffffff",
+            _ => "#000000",
+        };
+        write!(
+            svg,
+            r##"<rect x="{x}" y="10" width="75" height="60" style="fill:{colour};fill-opacity:{opacity};stroke:{stroke};stroke-width:{stroke_width}"/>
+<text x="{text_x}" y="50" text-anchor="middle" font-family="Noto Sans" font-size="28" fill="{text_colour}" fill-opacity="{opacity}">{number}</text>
+"##
+        )
+        .expect("Writing to String should not fail");
+    }
+
+    let label = escape_xml(label);
+    writeln!(
+        svg,
+        r##"<text x="{SCALE_X}" y="100" font-family="Noto Sans" font-size="22" fill="#000000">{label}</text>"##
+    )
+    .expect("Writing to String should not fail");
+    svg.push_str("</svg>\n");
+    svg
+}
+
+pub async fn svg_handler(
+    extract::Query(query): extract::Query<Query>,
+    Extension(i18n): Extension<I18nLoader>,
+    State(cache): State<Arc<DiagramCache>>,
+) -> axum::response::Result<impl IntoResponse> {
+    let mut headers = HeaderMap::new();
+    headers.insert(header::CONTENT_TYPE, "image/svg+xml".parse().unwrap());
+    let key = DiagramKey::new("danger_scale.svg", query.normalized(), &i18n);
+    let svg_data = cache
+        .get_or_render(key, move || Ok(generate_svg(&query, &i18n).into_bytes()))
+        .await
+        .map_err(map_eyre_error)?;
+    Ok((headers, svg_data))
+}
+
+pub async fn png_handler(
+    extract::Query(query): extract::Query<Query>,
+    Extension(i18n): Extension<I18nLoader>,
+    State(cache): State<Arc<DiagramCache>>,
+    request_headers: HeaderMap,
+) -> axum::response::Result<impl IntoResponse> {
+    let format = super::ImageFormat::negotiate(&request_headers);
+    let key =
+        DiagramKey::new("danger_scale.png", query.normalized(), &i18n).with_image_format(format);
+    let image_data = cache
+        .get_or_render(key, move || {
+            super::render_image(&generate_svg(&query, &i18n), format)
+                .wrap_err("Error generating image")
+        })
+        .await
+        .map_err(map_eyre_error)?;
+    Ok((super::image_headers(&image_data), image_data))
+}
+
+#[cfg(test)]
+mod test {
+    use crate::diagrams::elevation_hazard::{ElevationBand, HazardLevel};
+
+    use super::generate_svg_with_label;
+
+    #[test]
+    fn test_generate_svg() {
+        let svg = generate_svg_with_label(
+            Some(ElevationBand::Alpine),
+            HazardLevel::Considerable,
+            "Alpine: Considerable & rising",
+        );
+        assert!(svg.contains("Alpine: Considerable &amp; rising"));
+        assert_eq!(svg.matches("fill-opacity:1;").count(), 1);
+        resvg::usvg::Tree::from_str(&svg, &resvg::usvg::Options::default()).unwrap();
+    }
+}
@@ -1,22 +1,26 @@
 use axum::{
-    extract,
+    extract::{self, State},
     http::{header, HeaderMap},
     response::IntoResponse,
     Extension,
 };
-use eyre::Context;
-use i18n_embed::fluent::FluentLanguageLoader;
-use resvg::{tiny_skia, usvg};
-use serde::Deserialize;
+use eyre::WrapErr;
+use serde::{Deserialize, Serialize};
 
 use crate::{
-    error::{map_eyre_error, map_std_error},
+    error::map_eyre_error,
     i18n::I18nLoader,
+    user_preferences::{ElevationUnit, UserPreferences},
+};
+
+use super::{
+    cache::{DiagramCache, DiagramKey},
+    svg_template,
 };
 
 use std::sync::Arc;
 
-#[derive(Deserialize)]
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
 #[serde(rename_all = "snake_case")]
 pub enum ElevationBand {
     HighAlpine,
@@ -24,7 +28,29 @@ pub enum ElevationBand {
     SubAlpine,
 }
 
-#[derive(Deserialize)]
+impl ElevationBand {
+    /// The id of this band used in forecasts and localization messages, e.g.
+    /// `elevation-band-{id}`.
+    pub fn id(&self) -> &'static str {
+        match self {
+            ElevationBand::HighAlpine => "high-alpine",
+            ElevationBand::Alpine => "alpine",
+            ElevationBand::SubAlpine => "sub-alpine",
+        }
+    }
+
+    /// The band with the specified forecast elevation band `id`, if it is one of the bands that
+    /// can be drawn.
+    pub fn from_id(id: &str) -> Option<Self> {
+        [Self::HighAlpine, Self::Alpine, Self::SubAlpine]
+            .into_iter()
+            .find(|band| band.id() == id)
+    }
+}
+
+#[derive(
+    Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash, enum_iterator::Sequence,
+)]
 #[serde(rename_all = "kebab-case")]
 pub enum HazardLevel {
     NoRating,
@@ -38,8 +64,34 @@ pub enum HazardLevel {
 const WHITE: &str = "#ffffffff";
 const BLACK: &str = "#000000ff";
 
+impl From<forecast_spreadsheet::HazardRatingValue> for HazardLevel {
+    fn from(value: forecast_spreadsheet::HazardRatingValue) -> Self {
+        use forecast_spreadsheet::HazardRatingValue;
+        match value {
+            HazardRatingValue::NoRating => Self::NoRating,
+            HazardRatingValue::Low => Self::Low,
+            HazardRatingValue::Moderate => Self::Moderate,
+            HazardRatingValue::Considerable => Self::Considerable,
+            HazardRatingValue::High => Self::High,
+            HazardRatingValue::Extreme => Self::Extreme,
+        }
+    }
+}
+
 impl HazardLevel {
-    fn colour_hex(&self) -> &'static str {
+    /// The id of this level used in localization messages, e.g. `avalanche-hazard-{id}`.
+    pub fn id(&self) -> &'static str {
+        match self {
+            HazardLevel::NoRating => "no-rating",
+            HazardLevel::Low => "low",
+            HazardLevel::Moderate => "moderate",
+            HazardLevel::Considerable => "considerable",
+            HazardLevel::High => "high",
+            HazardLevel::Extreme => "extreme",
+        }
+    }
+
+    pub fn colour_hex(&self) -> &'static str {
         match self {
             HazardLevel::NoRating => "#ccccccff",
             HazardLevel::Low => "#57bb51ff",
@@ -51,107 +103,239 @@ impl HazardLevel {
     }
 }
 
-#[derive(Deserialize)]
+#[derive(Serialize, Deserialize)]
 pub struct Query {
     pub elevation_band: ElevationBand,
     pub hazard_level: HazardLevel,
+    /// Draw the elevation band with hatching instead of a solid fill, to distinguish an area
+    /// affected by an avalanche problem from a hazard rating.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub hatched: bool,
+    /// Show the localized name of the elevation band.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub label: bool,
+    /// Elevation of the lower boundary of the elevation band in metres, shown in the user's
+    /// preferred [`ElevationUnit`].
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub lower: Option<i64>,
+    /// Elevation of the upper boundary of the elevation band in metres.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub upper: Option<i64>,
 }
 
-pub fn generate_svg(query: Query, _i18n: Arc<FluentLanguageLoader>) -> String {
-    let high_alpine_colour = match query.elevation_band {
-        ElevationBand::HighAlpine => query.hazard_level.colour_hex(),
-        _ => WHITE,
-    };
-    let alpine_colour = match query.elevation_band {
-        ElevationBand::Alpine => query.hazard_level.colour_hex(),
-        _ => WHITE,
-    };
-    let sub_alpine_colour = match query.elevation_band {
-        ElevationBand::SubAlpine => query.hazard_level.colour_hex(),
-        _ => WHITE,
+/// How an elevation band is drawn in the `elevation_hazard.svg` template.
+#[derive(Serialize)]
+struct BandStyle {
+    colour: &'static str,
+    hatched: bool,
+}
+
+/// An elevation shown next to one of the lines between the elevation bands.
+#[derive(Serialize)]
+struct Boundary {
+    /// Position of the text baseline.
+    y: f64,
+    text: String,
+}
+
+/// Parameters for the `elevation_hazard.svg` template.
+#[derive(Serialize)]
+struct Context {
+    high_alpine: BandStyle,
+    alpine: BandStyle,
+    sub_alpine: BandStyle,
+    label: Option<String>,
+    boundaries: Vec<Boundary>,
+}
+
+/// Position of the line between the high alpine and alpine bands in the template.
+const HIGH_ALPINE_LINE_Y: f64 = 39.6;
+/// Position of the line between the alpine and sub alpine bands in the template.
+const ALPINE_LINE_Y: f64 = 68.3;
+/// Position of the bottom of the mountain in the template.
+const BASE_LINE_Y: f64 = 96.4;
+
+impl ElevationBand {
+    /// Positions of the lines at the top (if there is one) and bottom of this band in the
+    /// template.
+    fn line_positions(&self) -> (Option<f64>, f64) {
+        match self {
+            ElevationBand::HighAlpine => (None, HIGH_ALPINE_LINE_Y),
+            ElevationBand::Alpine => (Some(HIGH_ALPINE_LINE_Y), ALPINE_LINE_Y),
+            ElevationBand::SubAlpine => (Some(ALPINE_LINE_Y), BASE_LINE_Y),
+        }
+    }
+}
+
+/// Generate the diagram, with elevations in the `elevation_unit`.
+pub fn generate_svg(
+    query: Query,
+    i18n: &I18nLoader,
+    elevation_unit: ElevationUnit,
+) -> eyre::Result<String> {
+    let style = |band: ElevationBand| {
+        if band == query.elevation_band {
+            BandStyle {
+                colour: query.hazard_level.colour_hex(),
+                hatched: query.hatched,
+            }
+        } else {
+            BandStyle {
+                colour: WHITE,
+                hatched: false,
+            }
+        }
     };
+    let label = query
+        .label
+        .then(|| i18n.get(&format!("elevation-band-{}", query.elevation_band.id())));
 
-    format!(
-        r##"<?xml version="1.0" encoding="UTF-8" standalone="no"?>
-<!-- Created with Inkscape (http://www.inkscape.org/) -->
-
-<svg
-   width="400"
-   height="400"
-   viewBox="0 0 105.83333 105.83334"
-   version="1.1"
-   id="svg5"
-   xmlns="http://www.w3.org/2000/svg"
-   xmlns:svg="http://www.w3.org/2000/svg">
-  <defs
-     id="defs2" />
-  <g
-     id="layer1">
-    <path
-       style="fill:{high_alpine_colour};stroke:#000000;stroke-width:0.264583px;stroke-linecap:butt;stroke-linejoin:miter;stroke-opacity:1"
-       d="M 54.569168,4.0210426 34.735194,39.481727 73.493156,39.781862 Z"
-       id="high-alpine" />
-    <path
-       style="fill:{alpine_colour};stroke:#000000;stroke-width:0.264583px;stroke-linecap:butt;stroke-linejoin:miter;stroke-opacity:1"
-       d="M 17.818713,68.375259 88.758537,68.188805 73.493156,39.781862 34.735194,39.481727 Z"
-       id="alpine" />
-    <path
-       style="fill:{sub_alpine_colour};fill-opacity:1;stroke:none;stroke-width:0.980408;stroke-miterlimit:4;stroke-dasharray:none;stroke-opacity:1"
-       d="M 2.2299132,96.35494 17.818713,68.375259 88.758536,68.188803 103.88618,96.473276 Z"
-       id="sub-alpine" />
-    <path
-       style="fill:none;stroke:#000000;stroke-width:1.05833335;stroke-linecap:butt;stroke-linejoin:miter;stroke-miterlimit:4;stroke-dasharray:none;stroke-opacity:1"
-       d="M 2.2299132,96.35494 54.569167,4.0210425 103.88618,96.473276 Z"
-       id="path44" />
-    <path
-       style="fill:none;stroke:#000000;stroke-width:1.05833335;stroke-linecap:butt;stroke-linejoin:miter;stroke-miterlimit:4;stroke-dasharray:none;stroke-opacity:1"
-       d="M 17.818713,68.375259 88.758536,68.188803"
-       id="path1031" />
-    <path
-       style="fill:none;stroke:#000000;stroke-width:1.05833335;stroke-linecap:butt;stroke-linejoin:miter;stroke-miterlimit:4;stroke-dasharray:none;stroke-opacity:1"
-       d="m 34.735195,39.481726 38.757959,0.300136"
-       id="path1033" />
-  </g>
-</svg>
-"##
+    // Upper elevations are shown just above the line at the top of the band, and lower
+    // elevations just below the line at the bottom, so that they are always on a white
+    // background.
+    let (upper_line_y, lower_line_y) = query.elevation_band.line_positions();
+    let mut boundaries = Vec::new();
+    if let Some((upper, line_y)) = query.upper.zip(upper_line_y) {
+        boundaries.push(Boundary {
+            y: line_y - 1.5,
+            text: elevation_unit.format_metres(upper as f64, i18n),
+        });
+    }
+    if let Some(lower) = query.lower {
+        boundaries.push(Boundary {
+            y: lower_line_y + 5.5,
+            text: elevation_unit.format_metres(lower as f64, i18n),
+        });
+    }
+
+    svg_template::render(
+        "elevation_hazard.svg",
+        &Context {
+            high_alpine: style(ElevationBand::HighAlpine),
+            alpine: style(ElevationBand::Alpine),
+            sub_alpine: style(ElevationBand::SubAlpine),
+            label,
+            boundaries,
+        },
     )
 }
 
+impl Query {
+    /// The query for use in a [`DiagramKey`], the elevations depend on the `elevation_unit`.
+    fn normalized(&self, elevation_unit: ElevationUnit) -> String {
+        let query =
+            serde_urlencoded::to_string(self).expect("Unable to serialize elevation hazard query");
+        format!("{query}&elevation_unit={}", elevation_unit.symbol())
+    }
+}
+
 pub async fn svg_handler(
     extract::Query(query): extract::Query<Query>,
     Extension(i18n): Extension<I18nLoader>,
-) -> impl IntoResponse {
+    Extension(preferences): Extension<UserPreferences>,
+    State(cache): State<Arc<DiagramCache>>,
+) -> axum::response::Result<impl IntoResponse> {
     let mut headers = HeaderMap::new();
     headers.insert(header::CONTENT_TYPE, "image/svg+xml".parse().unwrap());
-    (headers, generate_svg(query, i18n))
-}
-
-fn generate_png(elevation_hazard: Query, i18n: Arc<FluentLanguageLoader>) -> eyre::Result<Vec<u8>> {
-    let svg = generate_svg(elevation_hazard, i18n);
-    let options = usvg::Options::default();
-    let tree = usvg::Tree::from_str(&svg, &options)?;
-    let pixmap_size = tree.size.to_int_size();
-    let mut pixmap = tiny_skia::Pixmap::new(pixmap_size.width(), pixmap_size.height())
-        .ok_or_else(|| eyre::eyre!("Unable to create pixmap"))?;
-    resvg::render(
-        &tree,
-        resvg::tiny_skia::Transform::default(),
-        &mut pixmap.as_mut(),
+    let elevation_unit = preferences.elevation_unit.unwrap_or_default();
+    let key = DiagramKey::new(
+        "elevation_hazard.svg",
+        query.normalized(elevation_unit),
+        &i18n,
     );
-    pixmap.encode_png().map_err(eyre::Error::from)
+    let svg_data = cache
+        .get_or_render(key, move || {
+            Ok(generate_svg(query, &i18n, elevation_unit)?.into_bytes())
+        })
+        .await
+        .map_err(map_eyre_error)?;
+    Ok((headers, svg_data))
 }
 
 pub async fn png_handler(
     extract::Query(elevation_hazard): extract::Query<Query>,
     Extension(i18n): Extension<I18nLoader>,
+    Extension(preferences): Extension<UserPreferences>,
+    State(cache): State<Arc<DiagramCache>>,
+    request_headers: HeaderMap,
 ) -> axum::response::Result<impl IntoResponse> {
-    let mut headers = HeaderMap::new();
-    headers.insert(header::CONTENT_TYPE, "image/png".parse().unwrap());
-    let png_data = tokio::task::spawn_blocking(move || {
-        generate_png(elevation_hazard, i18n).wrap_err("Error generating png")
-    })
-    .await
-    .map_err(map_std_error)?
-    .map_err(map_eyre_error)?;
-    Ok((headers, png_data))
+    let format = super::ImageFormat::negotiate(&request_headers);
+    let elevation_unit = preferences.elevation_unit.unwrap_or_default();
+    let key = DiagramKey::new(
+        "elevation_hazard.png",
+        elevation_hazard.normalized(elevation_unit),
+        &i18n,
+    )
+    .with_image_format(format);
+    let image_data = cache
+        .get_or_render(key, move || {
+            let svg = generate_svg(elevation_hazard, &i18n, elevation_unit)?;
+            super::render_image(&svg, format).wrap_err("Error generating image")
+        })
+        .await
+        .map_err(map_eyre_error)?;
+    Ok((super::image_headers(&image_data), image_data))
+}
+
+#[cfg(test)]
+mod test {
+    use resvg::usvg;
+
+    use crate::{i18n::test_loader, user_preferences::ElevationUnit};
+
+    use super::{generate_svg, ElevationBand, HazardLevel, Query};
+
+    #[test]
+    fn test_generate_svg() {
+        let query = Query {
+            elevation_band: ElevationBand::Alpine,
+            hazard_level: HazardLevel::Considerable,
+            hatched: false,
+            label: false,
+            lower: None,
+            upper: None,
+        };
+        let svg = generate_svg(query, &test_loader(), ElevationUnit::Metres).unwrap();
+        assert!(svg.starts_with("<?xml"));
+        assert!(svg.contains("fill:#fd923aff;"));
+        assert!(!svg.contains("<pattern"));
+        usvg::Tree::from_str(&svg, &usvg::Options::default()).unwrap();
+    }
+
+    #[test]
+    fn test_generate_svg_hatched() {
+        let query = Query {
+            elevation_band: ElevationBand::HighAlpine,
+            hazard_level: HazardLevel::High,
+            hatched: true,
+            label: false,
+            lower: None,
+            upper: None,
+        };
+        let svg = generate_svg(query, &test_loader(), ElevationUnit::Metres).unwrap();
+        assert!(svg.contains(
+            r#"<pattern
+       id="high-alpine-hatch""#
+        ));
+        assert!(svg.contains("fill:url(#high-alpine-hatch);"));
+        assert!(svg.contains(r##"fill="#fc3329ff""##));
+        usvg::Tree::from_str(&svg, &usvg::Options::default()).unwrap();
+    }
+
+    #[test]
+    fn test_generate_svg_label_and_elevations() {
+        let query = Query {
+            elevation_band: ElevationBand::Alpine,
+            hazard_level: HazardLevel::Moderate,
+            hatched: false,
+            label: true,
+            lower: Some(1800),
+            upper: Some(2600),
+        };
+        let svg = generate_svg(query, &test_loader(), ElevationUnit::Feet).unwrap();
+        assert!(svg.contains(r#"id="label">Alpine</text>"#));
+        assert!(svg.contains(">8,530 ft</text>"));
+        assert!(svg.contains(">5,906 ft</text>"));
+        usvg::Tree::from_str(&svg, &usvg::Options::default()).unwrap();
+    }
 }
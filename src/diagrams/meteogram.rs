@@ -0,0 +1,523 @@
+//! Meteogram diagrams of the cached weather station data, with panels for temperature, wind
+//! speed with direction barbs, and precipitation rate sharing a time axis (in UTC).
+
+use std::fmt::Write;
+
+use axum::{
+    extract,
+    http::{header, HeaderMap},
+    response::IntoResponse,
+    Extension,
+};
+use eyre::Context;
+use i18n_embed_fl::fl;
+use serde::Deserialize;
+
+use crate::{
+    current_weather::{get_cached_data, WeatherDataItem},
+    database::Database,
+    error::{map_eyre_error, map_std_error},
+    i18n::I18nLoader,
+    options::WeatherStationId,
+    user_preferences::UserPreferences,
+};
+
+use super::{tick_step, write_text};
+
+/// Maximum number of hours which can be displayed. Most sources only provide a limited history,
+/// in which case only the available data is displayed.
+const MAX_HOURS: u32 = 168;
+
+fn default_hours() -> u32 {
+    72
+}
+
+#[derive(Deserialize)]
+pub struct Query {
+    station: WeatherStationId,
+    /// Number of hours before now to display.
+    #[serde(default = "default_hours")]
+    hours: u32,
+}
+
+const WIDTH: f64 = 760.0;
+const HEIGHT: f64 = 540.0;
+const PLOT_LEFT: f64 = 70.0;
+const PLOT_RIGHT: f64 = 720.0;
+const TEMPERATURE_TOP: f64 = 40.0;
+const TEMPERATURE_BOTTOM: f64 = 200.0;
+const WIND_TOP: f64 = 240.0;
+const WIND_BOTTOM: f64 = 340.0;
+const BARBS_Y: f64 = 385.0;
+const PRECIPITATION_TOP: f64 = 420.0;
+const PRECIPITATION_BOTTOM: f64 = 490.0;
+const MAX_BARBS: i64 = 24;
+const BARB_LENGTH: f64 = 28.0;
+const TEMPERATURE_COLOUR: &str = "#d40000";
+const WIND_COLOUR: &str = "#2c5aa0";
+const PRECIPITATION_COLOUR: &str = "#3a9ad9";
+const GRID_COLOUR: &str = "#dddddd";
+
+fn ms_to_knots(speed: f64) -> f64 {
+    speed * 1.943844
+}
+
+/// The number of pennants (50 knots), full barbs (10 knots) and half barbs (5 knots) used to
+/// represent the wind speed, rounded to the nearest 5 knots.
+fn barb_components(knots: f64) -> (u32, u32, u32) {
+    let rounded = ((knots / 5.0).round() as u32) * 5;
+    (rounded / 50, (rounded % 50) / 10, (rounded % 10) / 5)
+}
+
+/// A vertical scale mapping values in `min..max` to `top..bottom`.
+struct Scale {
+    min: f64,
+    max: f64,
+    top: f64,
+    bottom: f64,
+}
+
+impl Scale {
+    /// Create a scale which covers the `values`, expanded to whole ticks and including at least
+    /// `default_min..default_max`.
+    fn new(
+        values: impl Iterator<Item = f64>,
+        default_min: f64,
+        default_max: f64,
+        top: f64,
+        bottom: f64,
+    ) -> Self {
+        let (min, max) = values.fold((default_min, default_max), |(min, max), value| {
+            (min.min(value), max.max(value))
+        });
+        let step = tick_step(max - min);
+        Self {
+            min: (min / step).floor() * step,
+            max: (max / step).ceil() * step,
+            top,
+            bottom,
+        }
+    }
+
+    fn y(&self, value: f64) -> f64 {
+        self.bottom - ((value - self.min) / (self.max - self.min)) * (self.bottom - self.top)
+    }
+
+    fn ticks(&self) -> impl Iterator<Item = f64> {
+        let step = tick_step(self.max - self.min);
+        let min = self.min;
+        let count = ((self.max - self.min) / step).round() as i64;
+        (0..=count).map(move |i| min + i as f64 * step)
+    }
+}
+
+/// Write the horizontal grid lines and labels for the `scale`, along with its `title`.
+fn write_axis(svg: &mut String, scale: &Scale, title: &str, colour: &str) {
+    for tick in scale.ticks() {
+        let y = scale.y(tick);
+        writeln!(
+            svg,
+            r#"<line x1="{PLOT_LEFT}" y1="{y:.1}" x2="{PLOT_RIGHT}" y2="{y:.1}" style="stroke:{GRID_COLOUR};stroke-width:1"/>"#
+        )
+        .expect("Writing to String should not fail");
+        write_text(
+            svg,
+            PLOT_LEFT - 6.0,
+            y + 4.0,
+            "end",
+            11,
+            "#000000",
+            &format!("{tick}"),
+        );
+    }
+    writeln!(
+        svg,
+        r#"<rect x="{PLOT_LEFT}" y="{top}" width="{width}" height="{height}" style="fill:none;stroke:#000000;stroke-width:1"/>"#,
+        top = scale.top,
+        width = PLOT_RIGHT - PLOT_LEFT,
+        height = scale.bottom - scale.top,
+    )
+    .expect("Writing to String should not fail");
+    write_text(svg, PLOT_LEFT, scale.top - 6.0, "start", 13, colour, title);
+}
+
+/// Write a line through the points, with gaps where values are missing.
+fn write_line(svg: &mut String, points: impl Iterator<Item = (f64, Option<f64>)>, colour: &str) {
+    let mut path = String::new();
+    let mut pen_down = false;
+    for (x, y) in points {
+        match y {
+            Some(y) => {
+                let command = if pen_down { 'L' } else { 'M' };
+                write!(path, "{command} {x:.1} {y:.1} ")
+                    .expect("Writing to String should not fail");
+                pen_down = true;
+            }
+            None => pen_down = false,
+        }
+    }
+    if !path.is_empty() {
+        writeln!(
+            svg,
+            r#"<path d="{path}" style="fill:none;stroke:{colour};stroke-width:2"/>"#
+        )
+        .expect("Writing to String should not fail");
+    }
+}
+
+/// Write a wind barb at `x`, `y` pointing towards the direction the wind is blowing from.
+fn write_barb(svg: &mut String, x: f64, y: f64, direction_degrees: f64, speed_ms: f64) {
+    let knots = ms_to_knots(speed_ms);
+    if knots < 2.5 {
+        writeln!(
+            svg,
+            r#"<circle cx="{x:.1}" cy="{y:.1}" r="4" style="fill:none;stroke:{WIND_COLOUR};stroke-width:1.5"/>"#
+        )
+        .expect("Writing to String should not fail");
+        return;
+    }
+    let (pennants, full, half) = barb_components(knots);
+    let tip = y - BARB_LENGTH;
+    let mut elements = format!(r#"<line x1="{x:.1}" y1="{y:.1}" x2="{x:.1}" y2="{tip:.1}"/>"#);
+    let mut position = tip;
+    for _ in 0..pennants {
+        write!(
+            elements,
+            r#"<polygon points="{x:.1},{position:.1} {px:.1},{py:.1} {x:.1},{end:.1}" style="fill:{WIND_COLOUR}"/>"#,
+            px = x + 10.0,
+            py = position + 3.0,
+            end = position + 6.0,
+        )
+        .expect("Writing to String should not fail");
+        position += 8.0;
+    }
+    for _ in 0..full {
+        write!(
+            elements,
+            r#"<line x1="{x:.1}" y1="{position:.1}" x2="{bx:.1}" y2="{by:.1}"/>"#,
+            bx = x + 10.0,
+            by = position - 5.0,
+        )
+        .expect("Writing to String should not fail");
+        position += 5.0;
+    }
+    if half > 0 {
+        // A lone half barb is set back from the tip so it isn't mistaken for a full barb.
+        if pennants == 0 && full == 0 {
+            position += 5.0;
+        }
+        write!(
+            elements,
+            r#"<line x1="{x:.1}" y1="{position:.1}" x2="{bx:.1}" y2="{by:.1}"/>"#,
+            bx = x + 5.0,
+            by = position - 2.5,
+        )
+        .expect("Writing to String should not fail");
+    }
+    writeln!(
+        svg,
+        r#"<g transform="rotate({direction_degrees:.0} {x:.1} {y:.1})" style="stroke:{WIND_COLOUR};stroke-width:1.5">{elements}</g>"#
+    )
+    .expect("Writing to String should not fail");
+}
+
+/// Generate the meteogram for the weather `data` between `start` and `end`, displaying values in
+/// the units selected in the user's `preferences`.
+pub fn generate_svg(
+    data: &[WeatherDataItem],
+    start: time::OffsetDateTime,
+    end: time::OffsetDateTime,
+    i18n: &I18nLoader,
+    preferences: &UserPreferences,
+) -> String {
+    let temperature_unit = preferences.temperature_unit.unwrap_or_default();
+    let wind_unit = preferences.wind_unit.unwrap_or_default();
+
+    let mut data: Vec<&WeatherDataItem> = data
+        .iter()
+        .filter(|item| item.time >= start && item.time <= end)
+        .collect();
+    data.sort_by_key(|item| item.time);
+
+    let duration_seconds = (end - start).as_seconds_f64().max(1.0);
+    let x_for_time = |time: time::OffsetDateTime| {
+        PLOT_LEFT + ((time - start).as_seconds_f64() / duration_seconds) * (PLOT_RIGHT - PLOT_LEFT)
+    };
+
+    let mut svg = String::new();
+    writeln!(
+        svg,
+        r##"<?xml version="1.0" encoding="UTF-8" standalone="no"?>
+<svg width="{WIDTH}" height="{HEIGHT}" viewBox="0 0 {WIDTH} {HEIGHT}" version="1.1" xmlns="http://www.w3.org/2000/svg">
+<rect x="0" y="0" width="{WIDTH}" height="{HEIGHT}" style="fill:#ffffff"/>"##
+    )
+    .expect("Writing to String should not fail");
+
+    // Vertical grid lines and time labels.
+    let hours = (end - start).whole_hours();
+    let step_hours = match hours {
+        ..=24 => 3,
+        25..=72 => 6,
+        _ => 12,
+    };
+    let first_label = start.replace_minute(0).and_then(|t| t.replace_second(0));
+    let mut label_time = first_label
+        .and_then(|t| t.replace_nanosecond(0))
+        .unwrap_or(start);
+    while label_time.hour() as i64 % step_hours != 0 || label_time < start {
+        label_time += time::Duration::HOUR;
+    }
+    let time_format = time::macros::format_description!("[hour]:[minute]");
+    let date_format = time::macros::format_description!("[day]/[month]");
+    while label_time <= end {
+        let x = x_for_time(label_time);
+        let midnight = label_time.hour() == 0;
+        writeln!(
+            svg,
+            r#"<line x1="{x:.1}" y1="{TEMPERATURE_TOP}" x2="{x:.1}" y2="{PRECIPITATION_BOTTOM}" style="stroke:{colour};stroke-width:1"/>"#,
+            colour = if midnight { "#999999" } else { GRID_COLOUR },
+        )
+        .expect("Writing to String should not fail");
+        if let Ok(label) = label_time.format(&time_format) {
+            write_text(
+                &mut svg,
+                x,
+                PRECIPITATION_BOTTOM + 16.0,
+                "middle",
+                11,
+                "#000000",
+                &label,
+            );
+        }
+        if midnight {
+            if let Ok(label) = label_time.format(&date_format) {
+                write_text(
+                    &mut svg,
+                    x,
+                    PRECIPITATION_BOTTOM + 30.0,
+                    "middle",
+                    11,
+                    "#000000",
+                    &label,
+                );
+            }
+        }
+        label_time += time::Duration::hours(step_hours);
+    }
+    write_text(
+        &mut svg,
+        (PLOT_LEFT + PLOT_RIGHT) / 2.0,
+        HEIGHT - 6.0,
+        "middle",
+        12,
+        "#000000",
+        &fl!(&**i18n, "meteogram-time-utc-label"),
+    );
+
+    // Temperature.
+    let temperature = Scale::new(
+        data.iter()
+            .filter_map(|item| item.temperature_celcius)
+            .map(|value| temperature_unit.convert_celsius(value)),
+        temperature_unit.convert_celsius(-5.0),
+        temperature_unit.convert_celsius(5.0),
+        TEMPERATURE_TOP,
+        TEMPERATURE_BOTTOM,
+    );
+    write_axis(
+        &mut svg,
+        &temperature,
+        &format!(
+            "{} ({})",
+            fl!(&**i18n, "atmospheric-temperature-label"),
+            temperature_unit.symbol()
+        ),
+        TEMPERATURE_COLOUR,
+    );
+    // Freezing level.
+    let freezing = temperature_unit.convert_celsius(0.0);
+    if temperature.min < freezing && temperature.max > freezing {
+        let y = temperature.y(freezing);
+        writeln!(
+            svg,
+            r#"<line x1="{PLOT_LEFT}" y1="{y:.1}" x2="{PLOT_RIGHT}" y2="{y:.1}" style="stroke:#000000;stroke-width:1;stroke-dasharray:4,3"/>"#
+        )
+        .expect("Writing to String should not fail");
+    }
+    write_line(
+        &mut svg,
+        data.iter().map(|item| {
+            (
+                x_for_time(item.time),
+                item.temperature_celcius
+                    .map(|value| temperature.y(temperature_unit.convert_celsius(value))),
+            )
+        }),
+        TEMPERATURE_COLOUR,
+    );
+
+    // Wind speed and direction.
+    let wind = Scale::new(
+        data.iter()
+            .filter_map(|item| item.wind_speed_ms.map(|speed| wind_unit.convert_ms(speed))),
+        0.0,
+        // 20 km/h
+        wind_unit.convert_ms(20.0 / 3.6),
+        WIND_TOP,
+        WIND_BOTTOM,
+    );
+    write_axis(
+        &mut svg,
+        &wind,
+        &format!(
+            "{} ({})",
+            fl!(&**i18n, "wind-speed-label"),
+            wind_unit.symbol()
+        ),
+        WIND_COLOUR,
+    );
+    write_line(
+        &mut svg,
+        data.iter().map(|item| {
+            (
+                x_for_time(item.time),
+                item.wind_speed_ms
+                    .map(|value| wind.y(wind_unit.convert_ms(value))),
+            )
+        }),
+        WIND_COLOUR,
+    );
+    let barb_interval = time::Duration::seconds((duration_seconds as i64 / MAX_BARBS).max(1));
+    let mut next_barb_time = start;
+    for item in &data {
+        if item.time < next_barb_time {
+            continue;
+        }
+        if let (Some(direction), Some(speed)) = (item.wind_direction_degrees, item.wind_speed_ms) {
+            write_barb(&mut svg, x_for_time(item.time), BARBS_Y, direction, speed);
+            next_barb_time = item.time + barb_interval;
+        }
+    }
+
+    // Precipitation rate, as bars covering the time since the previous item.
+    let precipitation = Scale::new(
+        data.iter()
+            .filter_map(|item| item.precipitation_rate_mm_per_hour),
+        0.0,
+        2.0,
+        PRECIPITATION_TOP,
+        PRECIPITATION_BOTTOM,
+    );
+    write_axis(
+        &mut svg,
+        &precipitation,
+        &fl!(&**i18n, "meteogram-precipitation-label"),
+        PRECIPITATION_COLOUR,
+    );
+    let mut previous_time: Option<time::OffsetDateTime> = None;
+    for item in &data {
+        if let Some(rate) = item
+            .precipitation_rate_mm_per_hour
+            .filter(|rate| *rate > 0.0)
+        {
+            let x_end = x_for_time(item.time);
+            let x_start = previous_time
+                .map(|time| x_for_time(time.max(item.time - time::Duration::HOUR)))
+                .unwrap_or(x_end - 2.0);
+            let y = precipitation.y(rate);
+            writeln!(
+                svg,
+                r#"<rect x="{x_start:.1}" y="{y:.1}" width="{width:.1}" height="{height:.1}" style="fill:{PRECIPITATION_COLOUR}"/>"#,
+                width = (x_end - x_start).max(1.0),
+                height = PRECIPITATION_BOTTOM - y,
+            )
+            .expect("Writing to String should not fail");
+        }
+        previous_time = Some(item.time);
+    }
+
+    if data.is_empty() {
+        write_text(
+            &mut svg,
+            (PLOT_LEFT + PLOT_RIGHT) / 2.0,
+            (TEMPERATURE_TOP + TEMPERATURE_BOTTOM) / 2.0,
+            "middle",
+            16,
+            "#000000",
+            &fl!(&**i18n, "meteogram-no-data-message"),
+        );
+    }
+
+    svg.push_str("</svg>\n");
+    svg
+}
+
+async fn generate_for_query(
+    query: Query,
+    database: &Database,
+    i18n: &I18nLoader,
+    preferences: &UserPreferences,
+) -> eyre::Result<String> {
+    let data = get_cached_data(database, &query.station).await?;
+    let end = time::OffsetDateTime::now_utc();
+    let start = end - time::Duration::hours(query.hours.clamp(1, MAX_HOURS).into());
+    Ok(generate_svg(&data, start, end, i18n, preferences))
+}
+
+pub async fn svg_handler(
+    extract::Query(query): extract::Query<Query>,
+    Extension(database): Extension<Database>,
+    Extension(i18n): Extension<I18nLoader>,
+    Extension(preferences): Extension<UserPreferences>,
+) -> axum::response::Result<impl IntoResponse> {
+    let mut headers = HeaderMap::new();
+    headers.insert(header::CONTENT_TYPE, "image/svg+xml".parse().unwrap());
+    let svg = generate_for_query(query, &database, &i18n, &preferences)
+        .await
+        .map_err(map_eyre_error)?;
+    Ok((headers, svg))
+}
+
+pub async fn png_handler(
+    extract::Query(query): extract::Query<Query>,
+    Extension(database): Extension<Database>,
+    Extension(i18n): Extension<I18nLoader>,
+    Extension(preferences): Extension<UserPreferences>,
+    request_headers: HeaderMap,
+) -> axum::response::Result<impl IntoResponse> {
+    let format = super::ImageFormat::negotiate(&request_headers);
+    let svg = generate_for_query(query, &database, &i18n, &preferences)
+        .await
+        .map_err(map_eyre_error)?;
+    let image_data = tokio::task::spawn_blocking(move || {
+        super::render_image(&svg, format).wrap_err("Error generating image")
+    })
+    .await
+    .map_err(map_std_error)?
+    .map_err(map_eyre_error)?;
+    Ok((super::image_headers(&image_data), image_data))
+}
+
+#[cfg(test)]
+mod test {
+    use super::{barb_components, Scale};
+
+    #[test]
+    fn test_barb_components() {
+        assert_eq!(barb_components(0.0), (0, 0, 0));
+        assert_eq!(barb_components(4.0), (0, 0, 1));
+        assert_eq!(barb_components(15.0), (0, 1, 1));
+        assert_eq!(barb_components(27.6), (0, 3, 0));
+        assert_eq!(barb_components(65.0), (1, 1, 1));
+    }
+
+    #[test]
+    fn test_scale() {
+        let scale = Scale::new([-7.3, 3.1].into_iter(), -5.0, 5.0, 0.0, 100.0);
+        assert_eq!(scale.min, -8.0);
+        assert_eq!(scale.max, 6.0);
+        assert_eq!(scale.y(-8.0), 100.0);
+        assert_eq!(scale.y(6.0), 0.0);
+        assert_eq!(scale.ticks().count(), 8);
+    }
+}
@@ -1,29 +1,249 @@
-use axum::{routing::get, Router};
+use std::fmt::Write;
+
+use axum::{
+    http::{header, HeaderMap, HeaderValue},
+    routing::get,
+    Router,
+};
 use once_cell::sync::Lazy;
+use resvg::{
+    tiny_skia,
+    usvg::{self, PostProcessingSteps},
+};
 use usvg_text_layout::fontdb;
 
+use crate::state::AppState;
+
 pub mod aspect_elevation;
-mod elevation_hazard;
+pub mod avalanche_activity;
+pub mod cache;
+pub mod danger_scale;
+pub mod elevation_hazard;
+pub mod meteogram;
 pub mod probability;
+pub mod problem_icon;
 pub mod size;
+pub mod snow_profile;
+mod svg_template;
+pub mod wind_rose;
 
-pub fn router<S>() -> Router<S>
-where
-    S: Clone + Send + Sync + 'static,
-{
+pub fn router() -> Router<AppState> {
     Router::new()
         .route("/elevation_hazard.svg", get(elevation_hazard::svg_handler))
         .route("/elevation_hazard.png", get(elevation_hazard::png_handler))
         .route("/aspect_elevation.svg", get(aspect_elevation::svg_handler))
         .route("/aspect_elevation.png", get(aspect_elevation::png_handler))
+        .route(
+            "/aspect_elevation_compact.svg",
+            get(aspect_elevation::compact_svg_handler),
+        )
+        .route(
+            "/avalanche_activity.svg",
+            get(avalanche_activity::svg_handler),
+        )
+        .route("/danger_scale.svg", get(danger_scale::svg_handler))
+        .route("/danger_scale.png", get(danger_scale::png_handler))
         .route("/size.svg", get(size::svg_handler))
         .route("/probability.svg", get(probability::svg_handler))
+        .route("/problem_icon.svg", get(problem_icon::svg_handler))
+        .route("/problem_icon.png", get(problem_icon::png_handler))
+        .route("/snow_profile.svg", get(snow_profile::svg_handler))
+        .route("/snow_profile.png", get(snow_profile::png_handler))
+        .route("/meteogram.svg", get(meteogram::svg_handler))
+        .route("/meteogram.png", get(meteogram::png_handler))
+        .route("/wind_rose.svg", get(wind_rose::svg_handler))
+        .route("/wind_rose.png", get(wind_rose::png_handler))
 }
 
 const FONT_DATA: &[u8] = include_bytes!("./fonts/noto/NotoSans-RegularWithGeorgian.ttf");
-static FONT_DB: Lazy<fontdb::Database> = Lazy::new(|| {
+pub static FONT_DB: Lazy<fontdb::Database> = Lazy::new(|| {
     let mut db = fontdb::Database::new();
     db.load_font_data(FONT_DATA.to_vec());
     db.set_sans_serif_family("Noto Sans");
     db
 });
+
+/// Raster image formats for the `.png` diagram endpoints, which respond with WebP when the client
+/// accepts it (see [`ImageFormat::negotiate`]) because it is considerably smaller.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ImageFormat {
+    Png,
+    WebP,
+}
+
+/// Diagrams with more pixels than this are always encoded as PNG. Lossless WebP encoding is
+/// comparatively slow for large images, and is limited to 16383 pixels in each dimension.
+const MAX_WEBP_PIXELS: u32 = 2048 * 2048;
+
+impl ImageFormat {
+    /// [`ImageFormat::WebP`] if the request's `Accept` header includes `image/webp` (and doesn't
+    /// reject it with `q=0`), otherwise [`ImageFormat::Png`].
+    pub fn negotiate(headers: &HeaderMap) -> Self {
+        let accepts_webp = headers
+            .get_all(header::ACCEPT)
+            .iter()
+            .filter_map(|value| value.to_str().ok())
+            .flat_map(|value| value.split(','))
+            .any(|media_range| {
+                let mut parts = media_range.split(';');
+                let media_type = parts.next().unwrap_or_default().trim();
+                let rejected = parts.any(|parameter| {
+                    parameter
+                        .trim()
+                        .strip_prefix("q=")
+                        .and_then(|q| q.trim().parse::<f32>().ok())
+                        .is_some_and(|q| q == 0.0)
+                });
+                media_type.eq_ignore_ascii_case("image/webp") && !rejected
+            });
+        if accepts_webp {
+            Self::WebP
+        } else {
+            Self::Png
+        }
+    }
+
+    /// The format of the encoded image `data`, identified using its signature.
+    pub fn of(data: &[u8]) -> Self {
+        if data.starts_with(b"RIFF") && data.get(8..12) == Some(b"WEBP".as_slice()) {
+            Self::WebP
+        } else {
+            Self::Png
+        }
+    }
+
+    pub fn content_type(self) -> &'static str {
+        match self {
+            Self::Png => "image/png",
+            Self::WebP => "image/webp",
+        }
+    }
+}
+
+/// Response headers for a diagram encoded using [`encode_pixmap`]. The format depends on the
+/// request's `Accept` header, so `Vary` is set for any caches between the server and the client.
+pub fn image_headers(data: &[u8]) -> HeaderMap {
+    let mut headers = HeaderMap::new();
+    headers.insert(
+        header::CONTENT_TYPE,
+        HeaderValue::from_static(ImageFormat::of(data).content_type()),
+    );
+    headers.insert(header::VARY, HeaderValue::from_static("accept"));
+    headers
+}
+
+/// Encode the rendered `pixmap` using the `format`, falling back to PNG for WebP images larger
+/// than [`MAX_WEBP_PIXELS`].
+pub fn encode_pixmap(pixmap: &tiny_skia::Pixmap, format: ImageFormat) -> eyre::Result<Vec<u8>> {
+    match format {
+        ImageFormat::WebP if pixmap.width() * pixmap.height() <= MAX_WEBP_PIXELS => {
+            let mut rgba = Vec::with_capacity(pixmap.data().len());
+            for pixel in pixmap.pixels() {
+                let color = pixel.demultiply();
+                rgba.extend_from_slice(&[color.red(), color.green(), color.blue(), color.alpha()]);
+            }
+            let mut data = Vec::new();
+            image_webp::WebPEncoder::new(&mut data).encode(
+                &rgba,
+                pixmap.width(),
+                pixmap.height(),
+                image_webp::ColorType::Rgba8,
+            )?;
+            Ok(data)
+        }
+        _ => pixmap.encode_png().map_err(eyre::Error::from),
+    }
+}
+
+/// Render an SVG document to PNG, converting any text into paths using the embedded fonts.
+pub fn render_png(svg: &str) -> eyre::Result<Vec<u8>> {
+    render_image(svg, ImageFormat::Png)
+}
+
+/// Render an SVG document to an image in the `format` (see [`encode_pixmap`]), converting any
+/// text into paths using the embedded fonts.
+pub fn render_image(svg: &str, format: ImageFormat) -> eyre::Result<Vec<u8>> {
+    let options = usvg::Options::default();
+    let mut tree = usvg::Tree::from_str(svg, &options)?;
+    tree.postprocess(
+        PostProcessingSteps {
+            convert_text_into_paths: true,
+        },
+        &FONT_DB,
+    );
+    let pixmap_size = tree.size.to_int_size();
+    let mut pixmap = tiny_skia::Pixmap::new(pixmap_size.width(), pixmap_size.height())
+        .ok_or_else(|| eyre::eyre!("Unable to create pixmap"))?;
+    resvg::render(&tree, tiny_skia::Transform::default(), &mut pixmap.as_mut());
+    encode_pixmap(&pixmap, format)
+}
+
+/// Escape text for inclusion in an SVG document.
+pub fn escape_xml(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Choose a spacing for axis ticks that results in no more than about 10 ticks.
+fn tick_step(range: f64) -> f64 {
+    [1.0, 2.0, 5.0, 10.0, 20.0, 25.0, 50.0, 100.0, 200.0, 500.0]
+        .into_iter()
+        .find(|step| range / step <= 10.0)
+        .unwrap_or(1000.0)
+}
+
+/// Write a `<text>` element using the embedded font.
+fn write_text(svg: &mut String, x: f64, y: f64, anchor: &str, size: u32, fill: &str, text: &str) {
+    let text = escape_xml(text);
+    writeln!(
+        svg,
+        r#"<text x="{x:.1}" y="{y:.1}" text-anchor="{anchor}" font-family="Noto Sans" font-size="{size}" fill="{fill}">{text}</text>"#
+    )
+    .expect("Writing to String should not fail");
+}
+
+#[cfg(test)]
+mod test {
+    use axum::http::{header, HeaderMap};
+
+    use super::{render_image, ImageFormat};
+
+    fn accept(value: &str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(header::ACCEPT, value.parse().unwrap());
+        headers
+    }
+
+    #[test]
+    fn test_negotiate_image_format() {
+        assert_eq!(ImageFormat::negotiate(&HeaderMap::new()), ImageFormat::Png);
+        assert_eq!(
+            ImageFormat::negotiate(&accept("image/avif,image/webp,*/*;q=0.8")),
+            ImageFormat::WebP
+        );
+        assert_eq!(
+            ImageFormat::negotiate(&accept("image/webp;q=0, image/png")),
+            ImageFormat::Png
+        );
+        assert_eq!(ImageFormat::negotiate(&accept("*/*")), ImageFormat::Png);
+    }
+
+    #[test]
+    fn test_render_image() {
+        let svg = r#"<svg xmlns="http://www.w3.org/2000/svg" width="10" height="10"><rect width="5" height="5" fill="red"/></svg>"#;
+        for format in [ImageFormat::Png, ImageFormat::WebP] {
+            let data = render_image(svg, format).unwrap();
+            assert_eq!(ImageFormat::of(&data), format);
+        }
+    }
+}
@@ -1,12 +1,10 @@
-use std::collections::HashMap;
-
 use axum::{extract, response::IntoResponse, Extension};
 use http::{header, HeaderMap};
-use once_cell::sync::Lazy;
-use regex::{Captures, Regex};
 use serde::{Deserialize, Serialize};
 
-use crate::{forecasts::probability::Probability, i18n::I18nLoader};
+use crate::{error::map_eyre_error, forecasts::probability::Probability, i18n::I18nLoader};
+
+use super::svg_template;
 
 pub struct ProbabilityBar {
     probability: Probability,
@@ -20,67 +18,37 @@ impl From<Query> for ProbabilityBar {
     }
 }
 
-const SVG_TEMPLATE: &str = include_str!("./probability.svg");
 const FILLED_COLOUR: &str = "#276fdcff";
 const TRANSPARENT_COLOUR: &str = "#00000000";
-static PATH_ID_RE: Lazy<Regex> = Lazy::new(|| {
-    Regex::new(r#"<rect\s*style="(?P<style>fill:(?P<fill>.*);)([^/])*id="(?P<id>.+)""#)
-        .expect("Unable to compile svg path id regex")
-});
 
-static TEXT_RE: Lazy<Regex> = Lazy::new(|| {
-    Regex::new(
-        r#"<text([^>])*id="(?P<id>.+)"(.|\s)*?fill:(?P<fill>#000000)(.|\s)*?<tspan(.|\s)*?>(?P<text>.*?)<[/]tspan>(.|\s)*?<[/]text>"#
-    )
-    .expect("Unable to compile svg text regex")
-});
+/// Parameters for the `probability.svg` template.
+#[derive(Serialize)]
+struct Context {
+    very_likely: &'static str,
+    likely: &'static str,
+    possible: &'static str,
+    unlikely: &'static str,
+    high_text: String,
+    low_text: String,
+}
 
-fn generate_svg(probability_bar: ProbabilityBar, i18n: I18nLoader) -> String {
-    let colour_map: HashMap<&'static str, (Probability, &str)> =
-        enum_iterator::all::<Probability>()
-            .map(|probability| {
-                let colour = if probability == probability_bar.probability {
-                    FILLED_COLOUR
-                } else {
-                    TRANSPARENT_COLOUR
-                };
-                (probability.id(), (probability, colour))
-            })
-            .collect();
-    let svg = PATH_ID_RE.replace_all(SVG_TEMPLATE, |captures: &Captures| {
-        let id = captures.name("id").unwrap().as_str();
-        let captured_string = captures.get(0).unwrap().as_str();
-        if let Some((_size, colour)) = colour_map.get(id) {
-            let style_group_string = captures.name("style").unwrap().as_str();
-            captured_string.replace(style_group_string, &format!("fill:{colour};"))
+fn generate_svg(probability_bar: ProbabilityBar, i18n: I18nLoader) -> eyre::Result<String> {
+    let fill = |probability: Probability| {
+        if probability == probability_bar.probability {
+            FILLED_COLOUR
         } else {
-            captured_string.to_string()
+            TRANSPARENT_COLOUR
         }
-    });
-
-    let text_map: HashMap<&'static str, String> = vec![
-        (
-            "high_text",
-            i18n_embed_fl::fl!(i18n, "avalanche-probability-high"),
-        ),
-        (
-            "low_text",
-            i18n_embed_fl::fl!(i18n, "avalanche-probability-low"),
-        ),
-    ]
-    .into_iter()
-    .collect();
-
-    TEXT_RE
-        .replace_all(&svg, |captures: &Captures| {
-            let id = captures.name("id").unwrap().as_str();
-            let captured_string = captures.get(0).unwrap().as_str();
-            let original_text = captures.name("text").unwrap().as_str();
-
-            let new_text = text_map.get(id).expect("Expected id to be present");
-            captured_string.replace(original_text, new_text)
-        })
-        .to_string()
+    };
+    let context = Context {
+        very_likely: fill(Probability::VeryLikely),
+        likely: fill(Probability::Likely),
+        possible: fill(Probability::Possible),
+        unlikely: fill(Probability::Unlikely),
+        high_text: i18n_embed_fl::fl!(i18n, "avalanche-probability-high"),
+        low_text: i18n_embed_fl::fl!(i18n, "avalanche-probability-low"),
+    };
+    svg_template::render("probability.svg", &context)
 }
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -95,5 +63,30 @@ pub async fn svg_handler(
     let mut headers = HeaderMap::new();
     headers.insert(header::CONTENT_TYPE, "image/svg+xml".parse().unwrap());
     let probability_bar = ProbabilityBar::from(query);
-    Ok((headers, generate_svg(probability_bar, i18n)))
+    let svg = generate_svg(probability_bar, i18n).map_err(map_eyre_error)?;
+    Ok((headers, svg))
+}
+
+#[cfg(test)]
+mod test {
+    use resvg::usvg;
+
+    use crate::{forecasts::probability::Probability, i18n::test_loader};
+
+    use super::{generate_svg, ProbabilityBar, FILLED_COLOUR};
+
+    #[test]
+    fn test_generate_svg() {
+        let svg = generate_svg(
+            ProbabilityBar {
+                probability: Probability::Likely,
+            },
+            test_loader(),
+        )
+        .unwrap();
+        assert_eq!(svg.matches(&format!("fill:{FILLED_COLOUR};")).count(), 1);
+        assert!(svg.contains(">High</tspan>"));
+        assert!(!svg.contains("{{"));
+        usvg::Tree::from_str(&svg, &usvg::Options::default()).unwrap();
+    }
 }
@@ -0,0 +1,191 @@
+//! Icons for the avalanche problem types, for reuse in templates, the PDF forecast and the social
+//! media integrations. Each icon is drawn from an embedded SVG fragment in the `problem_icon`
+//! directory on top of a common slope, using the requested colour.
+
+use std::{fmt::Write, sync::Arc};
+
+use axum::{
+    extract::{self, State},
+    http::{header, HeaderMap},
+    response::IntoResponse,
+    Extension,
+};
+use eyre::Context;
+use forecast_spreadsheet::ProblemKind;
+use serde::{Deserialize, Serialize};
+
+use crate::{error::map_eyre_error, forecasts::variant_id, i18n::I18nLoader};
+
+use super::{
+    cache::{DiagramCache, DiagramKey},
+    escape_xml,
+};
+
+pub const DEFAULT_COLOUR: &str = "#1e3a5f";
+const DEFAULT_WIDTH: u32 = 100;
+const MAX_WIDTH: u32 = 1024;
+
+/// A colour in hexadecimal notation, e.g. `#1e3a5f`.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(try_from = "String", into = "String")]
+pub struct Colour(String);
+
+impl TryFrom<String> for Colour {
+    type Error = String;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        let digits = value.strip_prefix('#').unwrap_or(&value);
+        if matches!(digits.len(), 3 | 4 | 6 | 8) && digits.chars().all(|c| c.is_ascii_hexdigit()) {
+            Ok(Self(format!("#{digits}")))
+        } else {
+            Err(format!(
+                "Invalid colour {value:?}, expected a hexadecimal colour e.g. \"#1e3a5f\""
+            ))
+        }
+    }
+}
+
+impl From<Colour> for String {
+    fn from(colour: Colour) -> Self {
+        colour.0
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct Query {
+    pub kind: ProblemKind,
+    /// Colour of the avalanche drawn on the slope, defaults to [`DEFAULT_COLOUR`].
+    pub colour: Option<Colour>,
+    /// Whether to include the localized name of the problem type below the icon.
+    #[serde(default)]
+    pub label: bool,
+    /// Width of the icon in pixels, defaults to [`DEFAULT_WIDTH`].
+    pub width: Option<u32>,
+}
+
+impl Query {
+    /// The query for use in a [`DiagramKey`].
+    fn normalized(&self) -> String {
+        serde_urlencoded::to_string(self).expect("Unable to serialize problem icon query")
+    }
+}
+
+fn icon_fragment(kind: ProblemKind) -> &'static str {
+    match kind {
+        ProblemKind::LooseDry => include_str!("./problem_icon/loose-dry.svg"),
+        ProblemKind::LooseWet => include_str!("./problem_icon/loose-wet.svg"),
+        ProblemKind::StormSlab => include_str!("./problem_icon/storm-slab.svg"),
+        ProblemKind::WindSlab => include_str!("./problem_icon/wind-slab.svg"),
+        ProblemKind::WetSlab => include_str!("./problem_icon/wet-slab.svg"),
+        ProblemKind::PersistentSlab => include_str!("./problem_icon/persistent-slab.svg"),
+        ProblemKind::DeepSlab => include_str!("./problem_icon/deep-slab.svg"),
+        ProblemKind::Cornice => include_str!("./problem_icon/cornice.svg"),
+        ProblemKind::Glide => include_str!("./problem_icon/glide.svg"),
+    }
+}
+
+/// The SVG elements drawing the icon for the problem `kind` in a `0 0 100 100` view box, for
+/// embedding the icon in other diagrams.
+pub fn icon_elements(kind: ProblemKind, colour: &str) -> String {
+    let mut elements = r##"<path d="M 5,90 L 95,90 L 95,20 Z" fill="#d9d9d9" stroke="#555555" stroke-width="2" stroke-linejoin="round"/>
+"##
+    .to_owned();
+    elements.push_str(&icon_fragment(kind).replace("{colour}", colour));
+    elements
+}
+
+pub fn generate_svg(query: &Query, i18n: &I18nLoader) -> String {
+    let colour = query
+        .colour
+        .as_ref()
+        .map(|colour| colour.0.as_str())
+        .unwrap_or(DEFAULT_COLOUR);
+    let view_height = if query.label { 120 } else { 100 };
+    let width = query.width.unwrap_or(DEFAULT_WIDTH).clamp(1, MAX_WIDTH);
+    let height = width * view_height / 100;
+
+    let mut svg = String::new();
+    writeln!(
+        svg,
+        r##"<svg xmlns="http://www.w3.org/2000/svg" width="{width}" height="{height}" viewBox="0 0 100 {view_height}">"##
+    )
+    .expect("Writing to String should not fail");
+    svg.push_str(&icon_elements(query.kind, colour));
+    if query.label {
+        let kind_id = variant_id(&query.kind);
+        let label = escape_xml(&i18n.get(&format!("problem-type-{kind_id}")));
+        writeln!(
+            svg,
+            r##"<text x="50" y="112" text-anchor="middle" font-family="Noto Sans" font-size="11" fill="#000000">{label}</text>"##
+        )
+        .expect("Writing to String should not fail");
+    }
+    svg.push_str("</svg>\n");
+    svg
+}
+
+pub async fn svg_handler(
+    extract::Query(query): extract::Query<Query>,
+    Extension(i18n): Extension<I18nLoader>,
+    State(cache): State<Arc<DiagramCache>>,
+) -> axum::response::Result<impl IntoResponse> {
+    let mut headers = HeaderMap::new();
+    headers.insert(header::CONTENT_TYPE, "image/svg+xml".parse().unwrap());
+    let key = DiagramKey::new("problem_icon.svg", query.normalized(), &i18n);
+    let svg_data = cache
+        .get_or_render(key, move || Ok(generate_svg(&query, &i18n).into_bytes()))
+        .await
+        .map_err(map_eyre_error)?;
+    Ok((headers, svg_data))
+}
+
+pub async fn png_handler(
+    extract::Query(query): extract::Query<Query>,
+    Extension(i18n): Extension<I18nLoader>,
+    State(cache): State<Arc<DiagramCache>>,
+    request_headers: HeaderMap,
+) -> axum::response::Result<impl IntoResponse> {
+    let format = super::ImageFormat::negotiate(&request_headers);
+    let key =
+        DiagramKey::new("problem_icon.png", query.normalized(), &i18n).with_image_format(format);
+    let image_data = cache
+        .get_or_render(key, move || {
+            super::render_image(&generate_svg(&query, &i18n), format)
+                .wrap_err("Error generating image")
+        })
+        .await
+        .map_err(map_eyre_error)?;
+    Ok((super::image_headers(&image_data), image_data))
+}
+
+#[cfg(test)]
+mod test {
+    use super::Colour;
+
+    #[test]
+    fn test_colour() {
+        assert_eq!(
+            Colour::try_from("#1E3A5F".to_owned()).unwrap(),
+            Colour("#1E3A5F".to_owned())
+        );
+        assert_eq!(
+            Colour::try_from("fff".to_owned()).unwrap(),
+            Colour("#fff".to_owned())
+        );
+        assert!(Colour::try_from("red".to_owned()).is_err());
+        assert!(Colour::try_from("#fff\"/><script>".to_owned()).is_err());
+    }
+
+    /// Every problem type has an icon which is a valid SVG document.
+    #[test]
+    fn test_icon_fragments() {
+        for kind in enum_iterator::all::<forecast_spreadsheet::ProblemKind>() {
+            let svg = format!(
+                r#"<svg xmlns="http://www.w3.org/2000/svg" viewBox="0 0 100 100">{}</svg>"#,
+                super::icon_fragment(kind).replace("{colour}", super::DEFAULT_COLOUR)
+            );
+            resvg::usvg::Tree::from_str(&svg, &resvg::usvg::Options::default())
+                .unwrap_or_else(|error| panic!("Invalid icon for {kind:?}: {error}"));
+        }
+    }
+}
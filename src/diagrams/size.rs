@@ -3,13 +3,13 @@ use forecast_spreadsheet::Size;
 use http::{header, HeaderMap};
 use i18n_embed::fluent::FluentLanguageLoader;
 use i18n_embed_fl::fl;
-use once_cell::sync::Lazy;
-use regex::{Captures, Regex};
 use serde::{Deserialize, Serialize};
 
-use std::{collections::HashMap, sync::Arc};
+use std::sync::Arc;
 
-use crate::i18n::I18nLoader;
+use crate::{error::map_eyre_error, i18n::I18nLoader};
+
+use super::svg_template;
 
 #[derive(Serialize, Deserialize, Debug)]
 pub struct Query {
@@ -27,79 +27,54 @@ impl From<Query> for SizeBar {
     }
 }
 
-const SVG_TEMPLATE: &str = include_str!("./size.svg");
 const FILLED_COLOUR: &str = "#276fdcff";
 const TRANSPARENT_COLOUR: &str = "#00000000";
-static PATH_ID_RE: Lazy<Regex> = Lazy::new(|| {
-    Regex::new(r#"<rect\s*style="(?P<style>fill:(?P<fill>.*);)([^/])*id="(?P<id>.+)""#)
-        .expect("Unable to compile svg path id regex")
-});
-
-static TEXT_RE: Lazy<Regex> = Lazy::new(|| {
-    Regex::new(
-        r#"<text([^>])*id="(?P<id>.+)"(.|\s)*?<tspan(.|\s)*?>(?P<text>.*?)<[/]tspan>(.|\s)*?<[/]text>"#
-    )
-    .expect("Unable to compile svg text regex")
-});
 
 /// Bound the size to range `[1, 4]`.
 fn restrict_size(size: Size) -> Size {
     Size::try_from(u8::min(size as u8, 4)).expect("Valid size")
 }
 
-fn generate_svg(size_bar: SizeBar, i18n: Arc<FluentLanguageLoader>) -> String {
-    let colour_map: HashMap<String, (Size, &str)> = enum_iterator::all::<Size>()
-        .filter_map(|size| {
-            if size > Size::Four {
-                return None;
-            }
-            let colour = if size > size_bar.size {
-                TRANSPARENT_COLOUR
-            } else {
-                FILLED_COLOUR
-            };
-            let id = format!("size{size}");
-            Some((id, (size, colour)))
-        })
-        .collect();
-    let svg = PATH_ID_RE.replace_all(SVG_TEMPLATE, |captures: &Captures| {
-        let id = captures.name("id").unwrap().as_str();
-        let captured_string = captures.get(0).unwrap().as_str();
-        if let Some((_size, colour)) = colour_map.get(id) {
-            let style_group_string = captures.name("style").unwrap().as_str();
-            captured_string.replace(style_group_string, &format!("fill:{colour};"))
+/// One of the segments of the bar in the `size.svg` template.
+#[derive(Serialize)]
+struct Segment {
+    fill: &'static str,
+    /// Label for the segment, only shown for the selected size.
+    text: String,
+}
+
+#[derive(Serialize)]
+struct Context {
+    size1: Segment,
+    size2: Segment,
+    size3: Segment,
+    size4: Segment,
+}
+
+fn generate_svg(size_bar: SizeBar, i18n: Arc<FluentLanguageLoader>) -> eyre::Result<String> {
+    let selected = restrict_size(size_bar.size);
+    let segment = |size: Size| {
+        let fill = if size > size_bar.size {
+            TRANSPARENT_COLOUR
+        } else {
+            FILLED_COLOUR
+        };
+        let text = if size != selected {
+            String::new()
+        } else if size >= Size::Four {
+            fl!(&*i18n, "avalanche-size-4plus")
         } else {
-            captured_string.to_string()
-        }
-    });
-    let text_map: HashMap<String, (Size, String)> = enum_iterator::all::<Size>()
-        .map(|size| {
-            let message = if size >= Size::Four {
-                fl!(&*i18n, "avalanche-size-4plus").to_owned()
-            } else {
-                fl!(&*i18n, "avalanche-size-n", size = size.to_string()).to_owned()
-            };
-            let size = restrict_size(size);
-            let id = format!("size{size}text");
-            (id, (size, message))
-        })
-        .collect();
-
-    TEXT_RE
-        .replace_all(&svg, |captures: &Captures| {
-            let id = captures.name("id").unwrap().as_str();
-            let captured_string = captures.get(0).unwrap().as_str();
-            let original_text = captures.name("text").unwrap().as_str();
-
-            let value = text_map.get(id).expect("Expected id to be present");
-            let new_text = if value.0 == restrict_size(size_bar.size) {
-                &value.1
-            } else {
-                ""
-            };
-            captured_string.replace(original_text, new_text)
-        })
-        .to_string()
+            fl!(&*i18n, "avalanche-size-n", size = size.to_string())
+        };
+        Segment { fill, text }
+    };
+    let context = Context {
+        size1: segment(Size::One),
+        size2: segment(Size::Two),
+        size3: segment(Size::Three),
+        size4: segment(Size::Four),
+    };
+    svg_template::render("size.svg", &context)
 }
 
 pub async fn svg_handler(
@@ -109,5 +84,28 @@ pub async fn svg_handler(
     let mut headers = HeaderMap::new();
     headers.insert(header::CONTENT_TYPE, "image/svg+xml".parse().unwrap());
     let size_bar = SizeBar::from(query);
-    Ok((headers, generate_svg(size_bar, i18n)))
+    let svg = generate_svg(size_bar, i18n).map_err(map_eyre_error)?;
+    Ok((headers, svg))
+}
+
+#[cfg(test)]
+mod test {
+    use forecast_spreadsheet::Size;
+    use resvg::usvg;
+
+    use crate::i18n::test_loader;
+
+    use super::{generate_svg, SizeBar, FILLED_COLOUR, TRANSPARENT_COLOUR};
+
+    #[test]
+    fn test_generate_svg() {
+        let svg = generate_svg(SizeBar { size: Size::Two }, test_loader()).unwrap();
+        assert_eq!(svg.matches(&format!("fill:{FILLED_COLOUR};")).count(), 2);
+        assert_eq!(
+            svg.matches(&format!("fill:{TRANSPARENT_COLOUR};")).count(),
+            2
+        );
+        assert!(!svg.contains("{{"));
+        usvg::Tree::from_str(&svg, &usvg::Options::default()).unwrap();
+    }
 }
@@ -0,0 +1,565 @@
+//! Snowpack profile diagrams, following the graphical conventions of the International
+//! Classification for Seasonal Snow on the Ground (ICSSG): height above the ground on the
+//! vertical axis, hand hardness increasing towards the left, the temperature profile plotted
+//! against a top axis with 0°C on the right, and layers coloured by their grain type.
+
+use std::fmt::Write;
+
+use axum::{
+    extract,
+    http::{header, HeaderMap},
+    response::IntoResponse,
+    Extension,
+};
+use eyre::Context;
+use i18n_embed_fl::fl;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    error::{map_eyre_error, map_std_error},
+    i18n::I18nLoader,
+};
+
+use super::{tick_step, write_text};
+
+/// Maximum snow height, well above the deepest recorded snowpacks.
+const MAX_HEIGHT_CM: f64 = 2000.0;
+/// Minimum snow temperature, well below the coldest recorded snowpacks.
+const MIN_TEMPERATURE_CELSIUS: f64 = -60.0;
+/// Maximum number of layers, and of temperature measurements.
+const MAX_MEASUREMENTS: usize = 200;
+
+/// Hand hardness index of a snow layer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Hardness {
+    /// Fist.
+    F,
+    /// Four fingers.
+    #[serde(rename = "4F")]
+    FourFingers,
+    /// One finger.
+    #[serde(rename = "1F")]
+    OneFinger,
+    /// Pencil.
+    P,
+    /// Knife.
+    K,
+    /// Ice.
+    I,
+}
+
+impl Hardness {
+    pub const ALL: [Hardness; 6] = [
+        Hardness::F,
+        Hardness::FourFingers,
+        Hardness::OneFinger,
+        Hardness::P,
+        Hardness::K,
+        Hardness::I,
+    ];
+
+    pub fn id(&self) -> &'static str {
+        match self {
+            Hardness::F => "F",
+            Hardness::FourFingers => "4F",
+            Hardness::OneFinger => "1F",
+            Hardness::P => "P",
+            Hardness::K => "K",
+            Hardness::I => "I",
+        }
+    }
+
+    /// Index in the range `[1, 6]`.
+    fn index(&self) -> u32 {
+        *self as u32 + 1
+    }
+}
+
+/// Main grain shape class of a snow layer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum GrainType {
+    /// Precipitation particles.
+    PP,
+    /// Machine made snow.
+    MM,
+    /// Decomposing and fragmented precipitation particles.
+    DF,
+    /// Rounded grains.
+    RG,
+    /// Faceted crystals.
+    FC,
+    /// Depth hoar.
+    DH,
+    /// Surface hoar.
+    SH,
+    /// Melt forms.
+    MF,
+    /// Ice formations.
+    IF,
+}
+
+impl GrainType {
+    pub const ALL: [GrainType; 9] = [
+        GrainType::PP,
+        GrainType::MM,
+        GrainType::DF,
+        GrainType::RG,
+        GrainType::FC,
+        GrainType::DH,
+        GrainType::SH,
+        GrainType::MF,
+        GrainType::IF,
+    ];
+
+    pub fn id(&self) -> &'static str {
+        match self {
+            GrainType::PP => "PP",
+            GrainType::MM => "MM",
+            GrainType::DF => "DF",
+            GrainType::RG => "RG",
+            GrainType::FC => "FC",
+            GrainType::DH => "DH",
+            GrainType::SH => "SH",
+            GrainType::MF => "MF",
+            GrainType::IF => "IF",
+        }
+    }
+
+    /// The ICSSG colour for this grain type.
+    pub fn colour_hex(&self) -> &'static str {
+        match self {
+            GrainType::PP => "#00ff00",
+            GrainType::MM => "#ffd700",
+            GrainType::DF => "#228b22",
+            GrainType::RG => "#ffb6c1",
+            GrainType::FC => "#add8e6",
+            GrainType::DH => "#0000ff",
+            GrainType::SH => "#ff00ff",
+            GrainType::MF => "#ff0000",
+            GrainType::IF => "#00ffff",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Layer {
+    /// Height of the top of the layer above the ground.
+    pub top_cm: f64,
+    /// Height of the bottom of the layer above the ground.
+    pub bottom_cm: f64,
+    pub hardness: Hardness,
+    pub grain_type: GrainType,
+    pub grain_size_mm: Option<f64>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TemperatureMeasurement {
+    /// Height of the measurement above the ground.
+    pub height_cm: f64,
+    pub temperature_celsius: f64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SnowProfile {
+    /// Total snow height (HS). If not specified the top of the highest layer is used.
+    #[serde(default)]
+    pub height_cm: Option<f64>,
+    #[serde(default)]
+    pub layers: Vec<Layer>,
+    #[serde(default)]
+    pub temperatures: Vec<TemperatureMeasurement>,
+}
+
+impl SnowProfile {
+    /// Total snow height used for the diagram.
+    pub fn total_height_cm(&self) -> f64 {
+        self.height_cm.unwrap_or_else(|| {
+            self.layers
+                .iter()
+                .map(|layer| layer.top_cm)
+                .fold(0.0, f64::max)
+        })
+    }
+
+    /// Check that the layers and temperatures are consistent with the snow height.
+    pub fn validate(&self) -> eyre::Result<()> {
+        let total_height_cm = self.total_height_cm();
+        if total_height_cm.is_nan() || total_height_cm <= 0.0 {
+            eyre::bail!("Snow profile height must be greater than 0");
+        }
+        if total_height_cm > MAX_HEIGHT_CM {
+            eyre::bail!("Snow profile height must not be greater than {MAX_HEIGHT_CM} cm");
+        }
+        if self.layers.len() > MAX_MEASUREMENTS || self.temperatures.len() > MAX_MEASUREMENTS {
+            eyre::bail!(
+                "Snow profile must not have more than {MAX_MEASUREMENTS} layers or temperature measurements"
+            );
+        }
+        for layer in &self.layers {
+            if layer.bottom_cm < 0.0
+                || layer.bottom_cm >= layer.top_cm
+                || layer.top_cm > total_height_cm
+            {
+                eyre::bail!(
+                    "Layer from {} cm to {} cm is outside of the snowpack height {total_height_cm} cm",
+                    layer.bottom_cm,
+                    layer.top_cm
+                );
+            }
+        }
+        for temperature in &self.temperatures {
+            if !(0.0..=total_height_cm).contains(&temperature.height_cm) {
+                eyre::bail!(
+                    "Temperature measurement at {} cm is outside of the snowpack height {total_height_cm} cm",
+                    temperature.height_cm
+                );
+            }
+            if !(MIN_TEMPERATURE_CELSIUS..=0.0).contains(&temperature.temperature_celsius) {
+                eyre::bail!(
+                    "Snow temperature {} °C must be between {MIN_TEMPERATURE_CELSIUS} °C and 0 °C",
+                    temperature.temperature_celsius
+                );
+            }
+        }
+        Ok(())
+    }
+
+    pub fn to_query(&self) -> eyre::Result<Query> {
+        Ok(Query {
+            data: serde_json::to_string(self)?,
+        })
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct Query {
+    /// [`SnowProfile`] serialized as JSON.
+    data: String,
+}
+
+impl TryFrom<Query> for SnowProfile {
+    type Error = eyre::Error;
+
+    fn try_from(query: Query) -> Result<Self, Self::Error> {
+        let profile: SnowProfile =
+            serde_json::from_str(&query.data).wrap_err("Error deserializing snow profile")?;
+        profile.validate()?;
+        Ok(profile)
+    }
+}
+
+const WIDTH: f64 = 520.0;
+const HEIGHT: f64 = 620.0;
+const PLOT_LEFT: f64 = 70.0;
+const PLOT_RIGHT: f64 = 380.0;
+const PLOT_TOP: f64 = 70.0;
+const PLOT_BOTTOM: f64 = 560.0;
+const GRAIN_TYPE_X: f64 = 420.0;
+const GRAIN_SIZE_X: f64 = 480.0;
+const TEMPERATURE_COLOUR: &str = "#d40000";
+
+pub fn generate_svg(profile: &SnowProfile, i18n: &I18nLoader) -> String {
+    let total_height_cm = profile.total_height_cm();
+    let plot_width = PLOT_RIGHT - PLOT_LEFT;
+    let plot_height = PLOT_BOTTOM - PLOT_TOP;
+    let y_for_height = |height_cm: f64| PLOT_BOTTOM - (height_cm / total_height_cm) * plot_height;
+    let x_for_hardness = |hardness: Hardness| {
+        PLOT_RIGHT - (hardness.index() as f64 / Hardness::ALL.len() as f64) * plot_width
+    };
+
+    let mut svg = String::new();
+    writeln!(
+        svg,
+        r##"<?xml version="1.0" encoding="UTF-8" standalone="no"?>
+<svg width="{WIDTH}" height="{HEIGHT}" viewBox="0 0 {WIDTH} {HEIGHT}" version="1.1" xmlns="http://www.w3.org/2000/svg">
+<rect x="0" y="0" width="{WIDTH}" height="{HEIGHT}" style="fill:#ffffff"/>"##
+    )
+    .expect("Writing to String should not fail");
+
+    // Layers, as bars extending left from the right hand axis in proportion to their hardness.
+    for layer in &profile.layers {
+        let x = x_for_hardness(layer.hardness);
+        let y = y_for_height(layer.top_cm);
+        let width = PLOT_RIGHT - x;
+        let height = y_for_height(layer.bottom_cm) - y;
+        let colour = layer.grain_type.colour_hex();
+        writeln!(
+            svg,
+            r##"<rect x="{x:.1}" y="{y:.1}" width="{width:.1}" height="{height:.1}" style="fill:{colour};stroke:#000000;stroke-width:1"/>"##
+        )
+        .expect("Writing to String should not fail");
+
+        let text_y = y + height / 2.0 + 5.0;
+        write_text(
+            &mut svg,
+            GRAIN_TYPE_X,
+            text_y,
+            "middle",
+            14,
+            "#000000",
+            layer.grain_type.id(),
+        );
+        if let Some(grain_size_mm) = layer.grain_size_mm {
+            write_text(
+                &mut svg,
+                GRAIN_SIZE_X,
+                text_y,
+                "middle",
+                14,
+                "#000000",
+                &format!("{grain_size_mm}"),
+            );
+        }
+    }
+
+    // Plot frame.
+    writeln!(
+        svg,
+        r##"<rect x="{PLOT_LEFT}" y="{PLOT_TOP}" width="{plot_width}" height="{plot_height}" style="fill:none;stroke:#000000;stroke-width:2"/>"##
+    )
+    .expect("Writing to String should not fail");
+
+    // Height axis.
+    let height_step = tick_step(total_height_cm);
+    let mut height_cm = 0.0;
+    while height_cm <= total_height_cm {
+        let y = y_for_height(height_cm);
+        writeln!(
+            svg,
+            r##"<line x1="{:.1}" y1="{y:.1}" x2="{PLOT_LEFT}" y2="{y:.1}" style="stroke:#000000;stroke-width:1"/>"##,
+            PLOT_LEFT - 6.0
+        )
+        .expect("Writing to String should not fail");
+        write_text(
+            &mut svg,
+            PLOT_LEFT - 10.0,
+            y + 5.0,
+            "end",
+            14,
+            "#000000",
+            &format!("{height_cm}"),
+        );
+        height_cm += height_step;
+    }
+    write_text(
+        &mut svg,
+        PLOT_LEFT - 10.0,
+        PLOT_TOP - 50.0,
+        "middle",
+        14,
+        "#000000",
+        &fl!(&**i18n, "snow-profile-height-label"),
+    );
+
+    // Hardness axis.
+    for hardness in Hardness::ALL {
+        write_text(
+            &mut svg,
+            x_for_hardness(hardness),
+            PLOT_BOTTOM + 22.0,
+            "middle",
+            14,
+            "#000000",
+            hardness.id(),
+        );
+    }
+    write_text(
+        &mut svg,
+        PLOT_LEFT + plot_width / 2.0,
+        PLOT_BOTTOM + 48.0,
+        "middle",
+        14,
+        "#000000",
+        &fl!(&**i18n, "snow-profile-hardness-label"),
+    );
+
+    // Grain columns.
+    write_text(
+        &mut svg,
+        GRAIN_TYPE_X,
+        PLOT_TOP - 10.0,
+        "middle",
+        12,
+        "#000000",
+        &fl!(&**i18n, "snow-profile-grain-type-label"),
+    );
+    write_text(
+        &mut svg,
+        GRAIN_SIZE_X,
+        PLOT_TOP - 10.0,
+        "middle",
+        12,
+        "#000000",
+        &fl!(&**i18n, "snow-profile-grain-size-label"),
+    );
+
+    // Temperature profile, plotted against the top axis with 0°C at the right.
+    if !profile.temperatures.is_empty() {
+        let coldest = profile
+            .temperatures
+            .iter()
+            .map(|temperature| temperature.temperature_celsius)
+            .fold(0.0, f64::min);
+        let temperature_step = tick_step(-coldest);
+        let axis_minimum = f64::min(
+            -temperature_step,
+            (coldest / temperature_step).floor() * temperature_step,
+        );
+        let x_for_temperature =
+            |temperature: f64| PLOT_RIGHT - (temperature / axis_minimum) * plot_width;
+
+        let mut temperature = 0.0;
+        while temperature >= axis_minimum {
+            let x = x_for_temperature(temperature);
+            writeln!(
+                svg,
+                r##"<line x1="{x:.1}" y1="{:.1}" x2="{x:.1}" y2="{PLOT_TOP}" style="stroke:{TEMPERATURE_COLOUR};stroke-width:1"/>"##,
+                PLOT_TOP - 6.0
+            )
+            .expect("Writing to String should not fail");
+            write_text(
+                &mut svg,
+                x,
+                PLOT_TOP - 10.0,
+                "middle",
+                14,
+                TEMPERATURE_COLOUR,
+                &format!("{temperature}"),
+            );
+            temperature -= temperature_step;
+        }
+        write_text(
+            &mut svg,
+            PLOT_LEFT + plot_width / 2.0,
+            PLOT_TOP - 34.0,
+            "middle",
+            14,
+            TEMPERATURE_COLOUR,
+            &fl!(&**i18n, "snow-profile-temperature-label"),
+        );
+
+        let mut temperatures = profile.temperatures.clone();
+        temperatures.sort_by(|a, b| a.height_cm.total_cmp(&b.height_cm));
+        let points = temperatures
+            .iter()
+            .map(|temperature| {
+                format!(
+                    "{:.1},{:.1}",
+                    x_for_temperature(temperature.temperature_celsius),
+                    y_for_height(temperature.height_cm)
+                )
+            })
+            .collect::<Vec<_>>()
+            .join(" ");
+        writeln!(
+            svg,
+            r##"<polyline points="{points}" style="fill:none;stroke:{TEMPERATURE_COLOUR};stroke-width:2"/>"##
+        )
+        .expect("Writing to String should not fail");
+    }
+
+    svg.push_str("</svg>\n");
+    svg
+}
+
+pub async fn svg_handler(
+    extract::Query(query): extract::Query<Query>,
+    Extension(i18n): Extension<I18nLoader>,
+) -> axum::response::Result<impl IntoResponse> {
+    let mut headers = HeaderMap::new();
+    headers.insert(header::CONTENT_TYPE, "image/svg+xml".parse().unwrap());
+    let profile = SnowProfile::try_from(query).map_err(map_eyre_error)?;
+    Ok((headers, generate_svg(&profile, &i18n)))
+}
+
+pub async fn png_handler(
+    extract::Query(query): extract::Query<Query>,
+    Extension(i18n): Extension<I18nLoader>,
+    request_headers: HeaderMap,
+) -> axum::response::Result<impl IntoResponse> {
+    let format = super::ImageFormat::negotiate(&request_headers);
+    let profile = SnowProfile::try_from(query).map_err(map_eyre_error)?;
+    let svg = generate_svg(&profile, &i18n);
+    let image_data = tokio::task::spawn_blocking(move || {
+        super::render_image(&svg, format).wrap_err("Error generating image")
+    })
+    .await
+    .map_err(map_std_error)?
+    .map_err(map_eyre_error)?;
+    Ok((super::image_headers(&image_data), image_data))
+}
+
+#[cfg(test)]
+mod test {
+    use super::{
+        GrainType, Hardness, Layer, SnowProfile, TemperatureMeasurement, MAX_MEASUREMENTS,
+    };
+
+    fn profile() -> SnowProfile {
+        SnowProfile {
+            height_cm: Some(120.0),
+            layers: vec![
+                Layer {
+                    top_cm: 120.0,
+                    bottom_cm: 100.0,
+                    hardness: Hardness::F,
+                    grain_type: GrainType::PP,
+                    grain_size_mm: Some(1.0),
+                },
+                Layer {
+                    top_cm: 100.0,
+                    bottom_cm: 0.0,
+                    hardness: Hardness::OneFinger,
+                    grain_type: GrainType::RG,
+                    grain_size_mm: None,
+                },
+            ],
+            temperatures: vec![
+                TemperatureMeasurement {
+                    height_cm: 120.0,
+                    temperature_celsius: -8.0,
+                },
+                TemperatureMeasurement {
+                    height_cm: 0.0,
+                    temperature_celsius: 0.0,
+                },
+            ],
+        }
+    }
+
+    #[test]
+    fn test_deserialize() {
+        let profile: SnowProfile = serde_json::from_str(
+            r#"{"layers":[{"top_cm":50,"bottom_cm":0,"hardness":"4F","grain_type":"FC","grain_size_mm":2}]}"#,
+        )
+        .unwrap();
+        assert_eq!(profile.layers[0].hardness, Hardness::FourFingers);
+        assert_eq!(profile.total_height_cm(), 50.0);
+        profile.validate().unwrap();
+    }
+
+    #[test]
+    fn test_validate() {
+        profile().validate().unwrap();
+
+        let mut invalid = profile();
+        invalid.layers[0].top_cm = 130.0;
+        assert!(invalid.validate().is_err());
+
+        let mut invalid = profile();
+        invalid.temperatures[0].temperature_celsius = 2.0;
+        assert!(invalid.validate().is_err());
+
+        let mut invalid = profile();
+        invalid.temperatures[0].temperature_celsius = -1e20;
+        assert!(invalid.validate().is_err());
+
+        let mut invalid = profile();
+        invalid.height_cm = Some(1e20);
+        assert!(invalid.validate().is_err());
+
+        let mut invalid = profile();
+        invalid.layers = vec![invalid.layers[0].clone(); MAX_MEASUREMENTS + 1];
+        assert!(invalid.validate().is_err());
+    }
+}
@@ -0,0 +1,30 @@
+//! Diagrams which are drawn using Inkscape (see the `*_inkscape.svg` sources), and exported as
+//! minijinja templates which are rendered with typed parameters, instead of manipulating the SVG
+//! directly.
+
+use minijinja::{AutoEscape, Environment};
+use once_cell::sync::Lazy;
+use serde::Serialize;
+
+static ENVIRONMENT: Lazy<Environment<'static>> = Lazy::new(|| {
+    let mut environment = Environment::new();
+    environment.set_auto_escape_callback(|_| AutoEscape::Html);
+    for (name, source) in [
+        (
+            "elevation_hazard.svg",
+            include_str!("./elevation_hazard.svg"),
+        ),
+        ("probability.svg", include_str!("./probability.svg")),
+        ("size.svg", include_str!("./size.svg")),
+    ] {
+        environment
+            .add_template(name, source)
+            .expect("Unable to add SVG template");
+    }
+    environment
+});
+
+/// Render the SVG template with the specified `name` using the `context`.
+pub fn render<S: Serialize>(name: &str, context: &S) -> eyre::Result<String> {
+    Ok(ENVIRONMENT.get_template(name)?.render(context)?)
+}
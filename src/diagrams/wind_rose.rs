@@ -0,0 +1,374 @@
+//! Wind rose diagrams of the cached weather station data, showing how often the wind blew from
+//! each direction, divided into speed classes.
+
+use std::fmt::Write;
+
+use axum::{
+    extract,
+    http::{header, HeaderMap},
+    response::IntoResponse,
+    Extension,
+};
+use eyre::Context;
+use i18n_embed_fl::fl;
+use serde::Deserialize;
+
+use crate::{
+    current_weather::{get_cached_data, WeatherDataItem},
+    database::Database,
+    error::{map_eyre_error, map_std_error},
+    i18n::I18nLoader,
+    options::WeatherStationId,
+    user_preferences::UserPreferences,
+};
+
+use super::{tick_step, write_text};
+
+/// Maximum number of hours which can be aggregated. Most sources only provide a limited history,
+/// in which case only the available data is used.
+const MAX_HOURS: u32 = 168;
+
+fn default_hours() -> u32 {
+    24
+}
+
+#[derive(Deserialize)]
+pub struct Query {
+    station: WeatherStationId,
+    /// Number of hours before now to aggregate.
+    #[serde(default = "default_hours")]
+    hours: u32,
+}
+
+const WIDTH: f64 = 560.0;
+const HEIGHT: f64 = 440.0;
+const CENTRE_X: f64 = 210.0;
+const CENTRE_Y: f64 = 220.0;
+const RADIUS: f64 = 170.0;
+const CALM_RADIUS: f64 = 14.0;
+const LEGEND_X: f64 = 420.0;
+const GRID_COLOUR: &str = "#dddddd";
+
+/// Number of direction sectors, each covering 22.5°.
+const SECTORS: usize = 16;
+/// Speeds (in m/s) below which the wind is considered calm, and has no direction.
+const CALM_MS: f64 = 0.5;
+/// Lower bounds (in m/s) of the wind speed classes.
+const SPEED_CLASSES_MS: [f64; 5] = [CALM_MS, 3.0, 6.0, 10.0, 15.0];
+const SPEED_CLASS_COLOURS: [&str; 5] = ["#c6dbef", "#6baed6", "#2171b5", "#08306b", "#6a1b9a"];
+
+/// Counts of observations by direction sector and speed class.
+#[derive(Debug, Default, PartialEq)]
+pub struct WindRose {
+    /// Counts indexed by sector (starting from north, clockwise) and speed class.
+    pub counts: [[u32; SPEED_CLASSES_MS.len()]; SECTORS],
+    /// Observations with a speed below [`CALM_MS`].
+    pub calm: u32,
+    /// Total number of observations with a wind speed.
+    pub total: u32,
+}
+
+impl WindRose {
+    /// Aggregate the weather `data` between `start` and `end`.
+    pub fn new(
+        data: &[WeatherDataItem],
+        start: time::OffsetDateTime,
+        end: time::OffsetDateTime,
+    ) -> Self {
+        let mut rose = Self::default();
+        for item in data
+            .iter()
+            .filter(|item| item.time >= start && item.time <= end)
+        {
+            let Some(speed) = item.wind_speed_ms else {
+                continue;
+            };
+            if speed < CALM_MS {
+                rose.calm += 1;
+                rose.total += 1;
+                continue;
+            }
+            let Some(direction) = item.wind_direction_degrees else {
+                continue;
+            };
+            let sector_width = 360.0 / SECTORS as f64;
+            let sector = ((direction.rem_euclid(360.0) + sector_width / 2.0) / sector_width)
+                as usize
+                % SECTORS;
+            let class = SPEED_CLASSES_MS
+                .iter()
+                .rposition(|lower| speed >= *lower)
+                .unwrap_or(0);
+            rose.counts[sector][class] += 1;
+            rose.total += 1;
+        }
+        rose
+    }
+
+    /// The proportion (in percent) of observations in each sector.
+    fn sector_percent(&self, sector: usize) -> f64 {
+        if self.total == 0 {
+            return 0.0;
+        }
+        self.counts[sector].iter().sum::<u32>() as f64 * 100.0 / self.total as f64
+    }
+}
+
+/// The point at `radius` from the centre in the compass `direction_degrees`.
+fn point(radius: f64, direction_degrees: f64) -> (f64, f64) {
+    let radians = direction_degrees.to_radians();
+    (
+        CENTRE_X + radius * radians.sin(),
+        CENTRE_Y - radius * radians.cos(),
+    )
+}
+
+/// Generate the wind rose for the weather `data` between `start` and `end`, displaying speeds in
+/// the units selected in the user's `preferences`.
+pub fn generate_svg(
+    data: &[WeatherDataItem],
+    start: time::OffsetDateTime,
+    end: time::OffsetDateTime,
+    i18n: &I18nLoader,
+    preferences: &UserPreferences,
+) -> String {
+    let wind_unit = preferences.wind_unit.unwrap_or_default();
+    let rose = WindRose::new(data, start, end);
+
+    let mut svg = String::new();
+    writeln!(
+        svg,
+        r##"<?xml version="1.0" encoding="UTF-8" standalone="no"?>
+<svg width="{WIDTH}" height="{HEIGHT}" viewBox="0 0 {WIDTH} {HEIGHT}" version="1.1" xmlns="http://www.w3.org/2000/svg">
+<rect x="0" y="0" width="{WIDTH}" height="{HEIGHT}" style="fill:#ffffff"/>"##
+    )
+    .expect("Writing to String should not fail");
+
+    // Rings for the frequency (percent of observations) scale.
+    let max_percent = (0..SECTORS)
+        .map(|sector| rose.sector_percent(sector))
+        .fold(5.0, f64::max);
+    let step = tick_step(max_percent);
+    let scale_max = (max_percent / step).ceil() * step;
+    let radius_for = |percent: f64| CALM_RADIUS + (percent / scale_max) * (RADIUS - CALM_RADIUS);
+    let rings = (scale_max / step).round() as u32;
+    for i in 1..=rings {
+        let percent = i as f64 * step;
+        let r = radius_for(percent);
+        writeln!(
+            svg,
+            r#"<circle cx="{CENTRE_X}" cy="{CENTRE_Y}" r="{r:.1}" style="fill:none;stroke:{GRID_COLOUR};stroke-width:1"/>"#
+        )
+        .expect("Writing to String should not fail");
+        let (x, y) = point(r, 22.5);
+        write_text(
+            &mut svg,
+            x + 2.0,
+            y,
+            "start",
+            10,
+            "#666666",
+            &format!("{percent}%"),
+        );
+    }
+    for sector in 0..SECTORS / 2 {
+        let (x1, y1) = point(RADIUS, sector as f64 * 22.5);
+        let (x2, y2) = point(RADIUS, sector as f64 * 22.5 + 180.0);
+        writeln!(
+            svg,
+            r#"<line x1="{x1:.1}" y1="{y1:.1}" x2="{x2:.1}" y2="{y2:.1}" style="stroke:{GRID_COLOUR};stroke-width:1"/>"#
+        )
+        .expect("Writing to String should not fail");
+    }
+    for (label, direction) in [("N", 0.0), ("E", 90.0), ("S", 180.0), ("W", 270.0)] {
+        let (x, y) = point(RADIUS + 16.0, direction);
+        write_text(&mut svg, x, y + 5.0, "middle", 14, "#000000", label);
+    }
+
+    // Stacked wedges for each sector, from the slowest speed class outwards.
+    let half_width = 360.0 / SECTORS as f64 / 2.0 * 0.9;
+    for (sector, counts) in rose.counts.iter().enumerate() {
+        let direction = sector as f64 * 360.0 / SECTORS as f64;
+        let mut cumulative = 0.0;
+        for (class, count) in counts.iter().enumerate() {
+            if *count == 0 {
+                continue;
+            }
+            let inner = radius_for(cumulative);
+            cumulative += *count as f64 * 100.0 / rose.total as f64;
+            let outer = radius_for(cumulative);
+            let (ax, ay) = point(inner, direction - half_width);
+            let (bx, by) = point(outer, direction - half_width);
+            let (cx, cy) = point(outer, direction + half_width);
+            let (dx, dy) = point(inner, direction + half_width);
+            writeln!(
+                svg,
+                r#"<path d="M {ax:.1} {ay:.1} L {bx:.1} {by:.1} A {outer:.1} {outer:.1} 0 0 1 {cx:.1} {cy:.1} L {dx:.1} {dy:.1} A {inner:.1} {inner:.1} 0 0 0 {ax:.1} {ay:.1} Z" style="fill:{colour};stroke:#ffffff;stroke-width:0.5"/>"#,
+                colour = SPEED_CLASS_COLOURS[class],
+            )
+            .expect("Writing to String should not fail");
+        }
+    }
+
+    // Calm observations in the centre.
+    writeln!(
+        svg,
+        r#"<circle cx="{CENTRE_X}" cy="{CENTRE_Y}" r="{CALM_RADIUS}" style="fill:#ffffff;stroke:#999999;stroke-width:1"/>"#
+    )
+    .expect("Writing to String should not fail");
+    if rose.total > 0 {
+        let calm_percent = (rose.calm as f64 * 100.0 / rose.total as f64).round();
+        write_text(
+            &mut svg,
+            CENTRE_X,
+            HEIGHT - 8.0,
+            "middle",
+            12,
+            "#000000",
+            &fl!(&**i18n, "wind-rose-calm-label", percent = calm_percent),
+        );
+    } else {
+        write_text(
+            &mut svg,
+            CENTRE_X,
+            CENTRE_Y - CALM_RADIUS - 10.0,
+            "middle",
+            16,
+            "#000000",
+            &fl!(&**i18n, "meteogram-no-data-message"),
+        );
+    }
+
+    // Legend of the speed classes.
+    write_text(
+        &mut svg,
+        LEGEND_X,
+        40.0,
+        "start",
+        13,
+        "#000000",
+        &format!(
+            "{} ({})",
+            fl!(&**i18n, "wind-speed-label"),
+            wind_unit.symbol()
+        ),
+    );
+    for (class, lower) in SPEED_CLASSES_MS.iter().enumerate() {
+        let y = 56.0 + class as f64 * 24.0;
+        let lower = wind_unit.convert_ms(*lower).round();
+        let label = match SPEED_CLASSES_MS.get(class + 1) {
+            Some(upper) => format!("{lower} - {}", wind_unit.convert_ms(*upper).round()),
+            None => format!("≥ {lower}"),
+        };
+        writeln!(
+            svg,
+            r#"<rect x="{LEGEND_X}" y="{y}" width="16" height="16" style="fill:{colour}"/>"#,
+            colour = SPEED_CLASS_COLOURS[class],
+        )
+        .expect("Writing to String should not fail");
+        write_text(
+            &mut svg,
+            LEGEND_X + 24.0,
+            y + 13.0,
+            "start",
+            12,
+            "#000000",
+            &label,
+        );
+    }
+
+    svg.push_str("</svg>\n");
+    svg
+}
+
+async fn generate_for_query(
+    query: Query,
+    database: &Database,
+    i18n: &I18nLoader,
+    preferences: &UserPreferences,
+) -> eyre::Result<String> {
+    let data = get_cached_data(database, &query.station).await?;
+    let end = time::OffsetDateTime::now_utc();
+    let start = end - time::Duration::hours(query.hours.clamp(1, MAX_HOURS).into());
+    Ok(generate_svg(&data, start, end, i18n, preferences))
+}
+
+pub async fn svg_handler(
+    extract::Query(query): extract::Query<Query>,
+    Extension(database): Extension<Database>,
+    Extension(i18n): Extension<I18nLoader>,
+    Extension(preferences): Extension<UserPreferences>,
+) -> axum::response::Result<impl IntoResponse> {
+    let mut headers = HeaderMap::new();
+    headers.insert(header::CONTENT_TYPE, "image/svg+xml".parse().unwrap());
+    let svg = generate_for_query(query, &database, &i18n, &preferences)
+        .await
+        .map_err(map_eyre_error)?;
+    Ok((headers, svg))
+}
+
+pub async fn png_handler(
+    extract::Query(query): extract::Query<Query>,
+    Extension(database): Extension<Database>,
+    Extension(i18n): Extension<I18nLoader>,
+    Extension(preferences): Extension<UserPreferences>,
+    request_headers: HeaderMap,
+) -> axum::response::Result<impl IntoResponse> {
+    let format = super::ImageFormat::negotiate(&request_headers);
+    let svg = generate_for_query(query, &database, &i18n, &preferences)
+        .await
+        .map_err(map_eyre_error)?;
+    let image_data = tokio::task::spawn_blocking(move || {
+        super::render_image(&svg, format).wrap_err("Error generating image")
+    })
+    .await
+    .map_err(map_std_error)?
+    .map_err(map_eyre_error)?;
+    Ok((super::image_headers(&image_data), image_data))
+}
+
+#[cfg(test)]
+mod test {
+    use time::macros::datetime;
+
+    use crate::current_weather::WeatherDataItem;
+
+    use super::WindRose;
+
+    fn item(
+        time: time::OffsetDateTime,
+        direction: Option<f64>,
+        speed: Option<f64>,
+    ) -> WeatherDataItem {
+        WeatherDataItem {
+            time,
+            temperature_celcius: None,
+            wind_direction_degrees: direction,
+            wind_speed_ms: speed,
+            humidity_percent: None,
+            precipitation_rate_mm_per_hour: None,
+        }
+    }
+
+    #[test]
+    fn test_wind_rose() {
+        let start = datetime!(2024-01-01 00:00 UTC);
+        let end = datetime!(2024-01-02 00:00 UTC);
+        let data = vec![
+            item(datetime!(2024-01-01 01:00 UTC), Some(355.0), Some(2.0)),
+            item(datetime!(2024-01-01 02:00 UTC), Some(10.0), Some(7.0)),
+            item(datetime!(2024-01-01 03:00 UTC), Some(180.0), Some(20.0)),
+            item(datetime!(2024-01-01 04:00 UTC), None, Some(0.2)),
+            item(datetime!(2024-01-01 05:00 UTC), Some(90.0), None),
+            // Outside of the time range.
+            item(datetime!(2023-12-31 23:00 UTC), Some(90.0), Some(5.0)),
+        ];
+        let rose = WindRose::new(&data, start, end);
+        assert_eq!(rose.total, 4);
+        assert_eq!(rose.calm, 1);
+        assert_eq!(rose.counts[0], [1, 0, 1, 0, 0]);
+        assert_eq!(rose.counts[8], [0, 0, 0, 0, 1]);
+        assert_eq!(rose.counts[4], [0; 5]);
+        assert_eq!(rose.sector_percent(0), 50.0);
+    }
+}
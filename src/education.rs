@@ -0,0 +1,104 @@
+//! Educational pages explaining each avalanche problem type and level of the danger scale, so
+//! that people new to avalanche forecasts can learn what the terms used in a forecast mean. The
+//! content comes from the localization resources (messages prefixed with `education-`, along
+//! with the descriptions already used in forecasts), illustrated using the [`crate::diagrams`].
+
+use axum::{extract::Path, response::Response, routing::get, Extension, Router};
+use forecast_spreadsheet::ProblemKind;
+use serde::Serialize;
+
+use crate::{
+    diagrams::elevation_hazard::HazardLevel, error::map_eyre_error, state::AppState,
+    templates::TemplatesWithContext,
+};
+
+pub fn router() -> Router<AppState> {
+    Router::new()
+        .route("/", get(index_handler))
+        .route("/problems/{kind}", get(problem_handler))
+        .route("/danger-levels/{level}", get(danger_level_handler))
+}
+
+#[derive(Serialize)]
+struct IndexContext {
+    problem_kinds: Vec<ProblemKind>,
+    danger_levels: Vec<HazardLevel>,
+}
+
+async fn index_handler(
+    Extension(templates): Extension<TemplatesWithContext>,
+) -> axum::response::Result<Response> {
+    let context = IndexContext {
+        problem_kinds: enum_iterator::all::<ProblemKind>().collect(),
+        danger_levels: enum_iterator::all::<HazardLevel>().collect(),
+    };
+    Ok(templates
+        .render("education/index.html", &context)
+        .map_err(map_eyre_error)?)
+}
+
+#[derive(Serialize)]
+struct ProblemContext {
+    kind: ProblemKind,
+}
+
+async fn problem_handler(
+    Path(kind): Path<ProblemKind>,
+    Extension(templates): Extension<TemplatesWithContext>,
+) -> axum::response::Result<Response> {
+    Ok(templates
+        .render("education/problem.html", &ProblemContext { kind })
+        .map_err(map_eyre_error)?)
+}
+
+#[derive(Serialize)]
+struct DangerLevelContext {
+    level: HazardLevel,
+    /// Number of the level on the danger scale, `None` for [`HazardLevel::NoRating`].
+    number: Option<usize>,
+}
+
+async fn danger_level_handler(
+    Path(level): Path<HazardLevel>,
+    Extension(templates): Extension<TemplatesWithContext>,
+) -> axum::response::Result<Response> {
+    let number = enum_iterator::all::<HazardLevel>().position(|other| other == level);
+    let context = DangerLevelContext {
+        level,
+        number: number.filter(|number| *number > 0),
+    };
+    Ok(templates
+        .render("education/danger_level.html", &context)
+        .map_err(map_eyre_error)?)
+}
+
+#[cfg(test)]
+mod test {
+    use forecast_spreadsheet::ProblemKind;
+
+    use crate::diagrams::elevation_hazard::HazardLevel;
+
+    const MESSAGES: &str = include_str!("../i18n/en-UK/avalanche_report.ftl");
+
+    fn has_message(id: &str) -> bool {
+        MESSAGES
+            .lines()
+            .any(|line| line.starts_with(&format!("{id} =")))
+    }
+
+    #[test]
+    fn test_messages_exist() {
+        for kind in enum_iterator::all::<ProblemKind>() {
+            let kind = serde_json::to_value(kind).unwrap();
+            let kind = kind.as_str().unwrap();
+            for suffix in ["description", "travel-advice"] {
+                let id = format!("education-problem-{kind}-{suffix}");
+                assert!(has_message(&id), "Missing message {id}");
+            }
+        }
+        for level in enum_iterator::all::<HazardLevel>() {
+            let id = format!("education-danger-level-{}-travel-advice", level.id());
+            assert!(has_message(&id), "Missing message {id}");
+        }
+    }
+}
@@ -0,0 +1,145 @@
+//! HTML emails, rendered from the templates in `emails/` (which share the components in
+//! `emails/components.html`) using the same template environment as the pages, localized in the
+//! language of the recipient. Each template sets the `subject` variable. The CSS in the
+//! `<style>` element is inlined into the `style` attributes of the elements because many email
+//! clients ignore stylesheets, and a plain text alternative is generated from the HTML. The
+//! emails can be previewed at `/admin/templates`.
+//!
+//! There is no way to send email yet, so this only provides the templates for the features which
+//! will: [`crate::alerts`] currently only notifies Slack and Matrix rooms, and there are no
+//! forecast notifications. Those need to render their emails with [`render`] once they are
+//! delivered by email.
+
+use std::sync::Arc;
+
+use eyre::{Context, ContextCompat};
+use forecast_spreadsheet::Forecast;
+use http::Uri;
+use i18n_embed::fluent::NegotiationStrategy;
+use serde::{Deserialize, Serialize};
+use unic_langid::LanguageIdentifier;
+
+use crate::{
+    i18n::{self, I18nLoader},
+    state::AppState,
+    templates::environment_with_context,
+    user_preferences::UserPreferences,
+    widget::ForecastSummaryContext,
+};
+
+/// Width in characters of the lines of the plain text alternative.
+const TEXT_WIDTH: usize = 80;
+
+/// The emails which can be rendered.
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+#[serde(rename_all = "kebab-case")]
+pub enum EmailTemplate {
+    /// A new forecast has been published, see [`ForecastNotificationContext`].
+    ForecastNotification,
+    /// A background task is failing, or has recovered, see [`AlertContext`].
+    Alert,
+}
+
+impl EmailTemplate {
+    pub const ALL: [Self; 2] = [Self::ForecastNotification, Self::Alert];
+
+    pub fn template_name(self) -> &'static str {
+        match self {
+            Self::ForecastNotification => "emails/forecast_notification.html",
+            Self::Alert => "emails/alert.html",
+        }
+    }
+}
+
+/// A rendered email.
+#[derive(Serialize, Debug)]
+pub struct Email {
+    pub subject: String,
+    /// HTML body, with the CSS inlined.
+    pub html: String,
+    /// Plain text alternative of the [`Email::html`] body.
+    pub text: String,
+}
+
+/// Context for [`EmailTemplate::ForecastNotification`].
+#[derive(Serialize)]
+pub struct ForecastNotificationContext {
+    /// Localized name of the area.
+    pub area_name: String,
+    pub forecast: ForecastSummaryContext,
+    /// Url of the page where the recipient can unsubscribe.
+    pub unsubscribe_url: Option<String>,
+}
+
+impl ForecastNotificationContext {
+    pub fn new(
+        forecast: &Forecast,
+        file_name: &str,
+        unsubscribe_url: Option<String>,
+        state: &AppState,
+        i18n: &I18nLoader,
+    ) -> eyre::Result<Self> {
+        let area = forecast.area.to_string();
+        Ok(Self {
+            area_name: i18n::message_or(
+                i18n,
+                &format!("forecast-area-{}", area.to_lowercase()),
+                &area,
+            ),
+            forecast: ForecastSummaryContext::new(forecast, file_name, state, i18n)?,
+            unsubscribe_url,
+        })
+    }
+}
+
+/// Context for [`EmailTemplate::Alert`].
+#[derive(Serialize)]
+pub struct AlertContext {
+    /// Identifies the site which the alert is from.
+    pub site: String,
+    /// The background task, e.g. `backup default`.
+    pub task: String,
+    /// `None` when the task has recovered.
+    pub consecutive_failures: Option<u32>,
+    pub error: Option<String>,
+}
+
+/// A loader for the messages in the `language`, falling back to the default language.
+pub fn language_loader(state: &AppState, language: &LanguageIdentifier) -> I18nLoader {
+    Arc::new(state.i18n.select_languages_negotiate(
+        std::slice::from_ref(language),
+        NegotiationStrategy::Filtering,
+    ))
+}
+
+/// Render the email `template` with the `context`, localized using `i18n`.
+pub fn render(
+    state: &AppState,
+    template: EmailTemplate,
+    i18n: &I18nLoader,
+    context: &dyn erased_serde::Serialize,
+) -> eyre::Result<Email> {
+    let name = template.template_name();
+    let environment = environment_with_context(
+        state,
+        i18n,
+        &UserPreferences::default(),
+        &Uri::from_static("/"),
+    )?;
+    let template = environment.get_template(name)?;
+    let (html, template_state) = template
+        .render_and_return_state(context)
+        .wrap_err_with(|| format!("Error rendering email template {name:?}"))?;
+    let subject = template_state
+        .lookup("subject")
+        .filter(|subject| !subject.is_undefined())
+        .wrap_err_with(|| format!("Email template {name:?} does not set the subject"))?
+        .to_string();
+    let html = css_inline::inline(&html).wrap_err("Error inlining the email CSS")?;
+    let text = html2text::from_read(html.as_bytes(), TEXT_WIDTH);
+    Ok(Email {
+        subject: subject.trim().to_owned(),
+        html,
+        text,
+    })
+}
@@ -6,7 +6,7 @@ use axum::{
 pub fn map_eyre_error(error: eyre::Error) -> Response {
     tracing::error!("{error:?}");
     let error = format!("{error:?}");
-    let mut html = ansi_to_html::convert_with_opts(&error, &ansi_to_html::Opts::default())
+    let mut html = ansi_to_html::convert(&error)
         .unwrap_or(error)
         .replace('\n', "<br>");
     html.insert_str(0, "<pre>");
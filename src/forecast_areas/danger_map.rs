@@ -0,0 +1,218 @@
+//! A GeoJSON `FeatureCollection` of the forecast area polygons, with the overall hazard rating
+//! of each area's current forecast, for shading the areas on a map using the EAWS colours.
+
+use std::collections::HashMap;
+
+use axum::{
+    extract::State,
+    http::{header::CONTENT_TYPE, HeaderValue},
+    response::{IntoResponse, Response},
+    Extension, Json,
+};
+use axum_extra::routing::TypedPath;
+use eyre::Context;
+use forecast_spreadsheet::{HazardRatingKind, HazardRatingValue};
+use serde_json::{json, Value};
+
+use crate::{
+    database::Database,
+    error::map_eyre_error,
+    forecasts::{
+        get_forecast_data, parse_forecast_name, ForecastData, ForecastsFilePath,
+        RequestedForecastData,
+    },
+    google_drive::ListFileMetadata,
+    state::AppState,
+};
+
+use super::{geojson_features, geometry_polygons, get_forecast_area, list_forecast_areas};
+
+/// The colour for the hazard rating, using the EAWS danger scale colours.
+pub(super) fn hazard_rgb(value: HazardRatingValue) -> [u8; 3] {
+    match value {
+        HazardRatingValue::NoRating => [0xcc, 0xcc, 0xcc],
+        HazardRatingValue::Low => [0xcc, 0xff, 0x66],
+        HazardRatingValue::Moderate => [0xff, 0xff, 0x00],
+        HazardRatingValue::Considerable => [0xff, 0x99, 0x00],
+        HazardRatingValue::High => [0xff, 0x00, 0x00],
+        HazardRatingValue::Extreme => [0x00, 0x00, 0x00],
+    }
+}
+
+/// The fill colour for the hazard rating as a CSS hex colour.
+fn hazard_colour(value: HazardRatingValue) -> String {
+    let [r, g, b] = hazard_rgb(value);
+    format!("#{r:02x}{g:02x}{b:02x}")
+}
+
+/// The current forecast for an area.
+pub struct CurrentForecast {
+    pub file_name: String,
+    pub forecast: forecast_spreadsheet::Forecast,
+}
+
+/// Find the current forecast for each area, keyed by the lowercase area id. Only the most recent
+/// published forecast spreadsheet for each area is considered, and only if it is still valid.
+/// Parsed forecasts are cached in the database until the file is modified, so the map reflects
+/// updates to forecasts as soon as they are published.
+pub async fn current_forecasts(
+    state: &AppState,
+    database: &Database,
+) -> eyre::Result<HashMap<String, CurrentForecast>> {
+    let file_list = state.published_files.list_files().await?;
+
+    let mut latest: HashMap<String, (time::OffsetDateTime, &ListFileMetadata)> = HashMap::new();
+    for file in file_list.iter().filter(|file| file.has_forecast_data()) {
+        let details = match parse_forecast_name(&file.name, state.forecast_spreadsheet_schema) {
+            Ok(details) => details,
+            Err(error) => {
+                tracing::warn!("Skipping file {:?} in danger map: {error}", file.name);
+                continue;
+            }
+        };
+        let area = details.forecast.area.to_lowercase();
+        let time = details.forecast.time;
+        match latest.get(&area) {
+            Some((latest_time, _)) if *latest_time >= time => {}
+            _ => {
+                latest.insert(area, (time, file));
+            }
+        }
+    }
+
+    let now = time::OffsetDateTime::now_utc();
+    let mut forecasts = HashMap::new();
+    for (area, (_, file)) in latest {
+        let forecast = match get_forecast_data(
+            file,
+            RequestedForecastData::Forecast,
+            &state.client,
+            database,
+            &state.blobs,
+            &state.options.google_drive,
+            state.forecast_spreadsheet_schemas,
+        )
+        .await
+        .wrap_err_with(|| format!("Error getting forecast data for {:?}", file.name))?
+        {
+            ForecastData::Forecast(forecast) => forecast,
+            ForecastData::File(_) => eyre::bail!("Expected ForecastData::Forecast"),
+        };
+        if now <= forecast.time + forecast.valid_for {
+            forecasts.insert(
+                area,
+                CurrentForecast {
+                    file_name: file.name.clone(),
+                    forecast,
+                },
+            );
+        }
+    }
+    Ok(forecasts)
+}
+
+/// Replace the properties of the area's polygon features with the area's danger rating.
+fn area_features(
+    area: &str,
+    geojson: Value,
+    current_forecast: Option<&CurrentForecast>,
+) -> Vec<Value> {
+    let hazard_rating = current_forecast
+        .and_then(|current| {
+            current
+                .forecast
+                .hazard_ratings
+                .get(&HazardRatingKind::Overall)
+        })
+        .and_then(|rating| rating.value)
+        .unwrap_or(HazardRatingValue::NoRating);
+    let properties = json!({
+        "area": area,
+        "hazard_rating": hazard_rating,
+        "danger_level": hazard_rating as u8,
+        "fill_colour": hazard_colour(hazard_rating),
+        "forecast_url": current_forecast.map(|current| {
+            ForecastsFilePath {
+                file_name: current.file_name.clone(),
+            }
+            .to_uri()
+            .to_string()
+        }),
+        "valid_until": current_forecast.and_then(|current| {
+            (current.forecast.time + current.forecast.valid_for)
+                .format(&time::format_description::well_known::Rfc3339)
+                .ok()
+        }),
+    });
+    geojson_features(geojson)
+        .into_iter()
+        // Skip the elevation band boundary lines.
+        .filter(|feature| {
+            feature
+                .get("geometry")
+                .is_some_and(|geometry| !geometry_polygons(geometry).is_empty())
+        })
+        .map(|mut feature| {
+            feature["properties"] = properties.clone();
+            feature
+        })
+        .collect()
+}
+
+async fn danger_map(state: &AppState, database: &Database) -> eyre::Result<Value> {
+    let forecasts = current_forecasts(state, database).await?;
+    let mut features = Vec::new();
+    for id in list_forecast_areas(database).await? {
+        let Some(area) = get_forecast_area(database, &id).await? else {
+            continue;
+        };
+        let area_id = area.id.to_string();
+        let current_forecast = forecasts.get(&area_id.to_lowercase());
+        features.extend(area_features(&area_id, area.geojson, current_forecast));
+    }
+    Ok(json!({
+        "type": "FeatureCollection",
+        "features": features,
+    }))
+}
+
+/// Handler for `danger-map.geojson`.
+pub async fn handler(
+    State(state): State<AppState>,
+    Extension(database): Extension<Database>,
+) -> axum::response::Result<Response> {
+    let danger_map = danger_map(&state, &database)
+        .await
+        .map_err(map_eyre_error)?;
+    let mut response = Json(danger_map).into_response();
+    response.headers_mut().insert(
+        CONTENT_TYPE,
+        "application/geo+json".parse::<HeaderValue>().unwrap(),
+    );
+    Ok(response)
+}
+
+#[cfg(test)]
+mod test {
+    use serde_json::json;
+
+    use super::area_features;
+
+    #[test]
+    fn test_area_features_without_forecast() {
+        let polygon =
+            json!({ "type": "Polygon", "coordinates": [[[0, 0], [1, 0], [1, 1], [0, 0]]] });
+        let features = area_features("gudauri", polygon, None);
+        assert_eq!(features.len(), 1);
+        insta::assert_json_snapshot!(features[0]["properties"], @r###"
+        {
+          "area": "gudauri",
+          "danger_level": 0,
+          "fill_colour": "#cccccc",
+          "forecast_url": null,
+          "hazard_rating": "no-rating",
+          "valid_until": null
+        }
+        "###);
+    }
+}
@@ -8,17 +8,34 @@ use axum::{
 use eyre::ContextCompat;
 use http::StatusCode;
 use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
 
-use crate::{database::Database, error::map_eyre_error};
+use crate::{database::Database, error::map_eyre_error, state::AppState};
 
-pub fn router<S>() -> Router<S>
-where
-    S: Clone + Send + Sync + 'static,
-{
-    Router::new().route("/{id}/area.geojson", get(handler))
+use self::validation::ValidatedGeoJson;
+
+pub mod danger_map;
+pub mod terrain;
+mod tiles;
+pub mod validation;
+
+pub fn router() -> Router<AppState> {
+    Router::new()
+        .route("/danger-map.geojson", get(danger_map::handler))
+        .route("/{id}/area.geojson", get(handler))
+        .route(
+            "/{id}/terrain-overlays.json",
+            get(terrain::overlays_handler),
+        )
+        .route(
+            "/{id}/elevation-bands.png",
+            get(terrain::elevation_bands_handler),
+        )
+        .route("/{id}/slope.png", get(terrain::slope_handler))
+        .route("/tiles/{z}/{x}/{y}", get(tiles::handler))
 }
 
-#[derive(sqlx::Type, Serialize, Deserialize, Debug)]
+#[derive(sqlx::Type, Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Hash)]
 #[serde(transparent)]
 #[sqlx(transparent)]
 pub struct ForecastAreaId(String);
@@ -50,17 +67,30 @@ pub async fn list_forecast_areas(database: &Database) -> eyre::Result<Vec<Foreca
         .collect::<Vec<_>>())
 }
 
+/// Validate the forecast area's GeoJSON (see [`validation::validate_forecast_area_geojson`])
+/// and store the normalized GeoJSON. Returns the stored GeoJSON and the problems which were fixed
+/// automatically, or a [`validation::ValidationErrors`] error if the GeoJSON is invalid.
 pub async fn upsert_forecast_area(
     database: &Database,
     forecast_area: ForecastArea,
-) -> eyre::Result<()> {
+) -> eyre::Result<ValidatedGeoJson> {
+    let validated = validation::validate_forecast_area_geojson(forecast_area.geojson)?;
     sqlx::query!(
         "INSERT INTO forecast_areas VALUES($1, $2) ON CONFLICT(id) DO UPDATE SET geojson=$2",
         forecast_area.id,
-        forecast_area.geojson,
+        validated.geojson,
     )
     .execute(database)
     .await?;
+    tiles::clear_tile_cache(database).await?;
+    Ok(validated)
+}
+
+pub async fn delete_forecast_area(database: &Database, id: &ForecastAreaId) -> eyre::Result<()> {
+    sqlx::query!("DELETE FROM forecast_areas WHERE id=$1", id)
+        .execute(database)
+        .await?;
+    tiles::clear_tile_cache(database).await?;
     Ok(())
 }
 
@@ -77,6 +107,52 @@ pub async fn get_forecast_area(
     .await?)
 }
 
+/// Rings of longitude, latitude positions, the first is the exterior ring.
+pub type GeoPolygon = geo::Polygon;
+
+/// Extract the features from a GeoJSON object, converting a bare geometry into a feature.
+pub fn geojson_features(geojson: Value) -> Vec<Value> {
+    match geojson.get("type").and_then(Value::as_str) {
+        Some("FeatureCollection") => match geojson.get("features") {
+            Some(Value::Array(features)) => features.clone(),
+            _ => Vec::new(),
+        },
+        Some("Feature") => vec![geojson],
+        Some(_) => vec![json!({
+            "type": "Feature",
+            "geometry": geojson,
+            "properties": {},
+        })],
+        None => Vec::new(),
+    }
+}
+
+/// Read the polygons (each a list of rings of longitude, latitude positions) from a GeoJSON
+/// `Polygon` or `MultiPolygon` geometry. Other geometry types are ignored.
+pub fn geometry_polygons(geometry: &Value) -> Vec<GeoPolygon> {
+    let coordinates = geometry.get("coordinates").cloned().unwrap_or(Value::Null);
+    let polygons: Vec<Vec<Vec<Vec<f64>>>> = match geometry.get("type").and_then(Value::as_str) {
+        Some("Polygon") => serde_json::from_value(coordinates).map(|polygon| vec![polygon]),
+        Some("MultiPolygon") => serde_json::from_value(coordinates),
+        _ => Ok(Vec::new()),
+    }
+    .unwrap_or_default();
+    polygons
+        .into_iter()
+        .map(|rings| {
+            rings
+                .into_iter()
+                .map(|ring| {
+                    ring.into_iter()
+                        .filter(|position| position.len() >= 2)
+                        .map(|position| (position[0], position[1]))
+                        .collect()
+                })
+                .collect()
+        })
+        .collect()
+}
+
 #[derive(Deserialize)]
 pub struct PathParams {
     id: ForecastAreaId,
@@ -102,3 +178,23 @@ pub async fn handler(
     );
     Ok(response)
 }
+
+#[cfg(test)]
+mod test {
+    use serde_json::json;
+
+    use super::geojson_features;
+
+    #[test]
+    fn test_geojson_features() {
+        let polygon =
+            json!({ "type": "Polygon", "coordinates": [[[0, 0], [1, 0], [1, 1], [0, 0]]] });
+        let feature = json!({ "type": "Feature", "geometry": polygon, "properties": { "fid": 1 } });
+        assert_eq!(geojson_features(polygon.clone()).len(), 1);
+        assert_eq!(geojson_features(feature.clone()), vec![feature.clone()]);
+        let collection =
+            json!({ "type": "FeatureCollection", "features": [feature.clone(), feature] });
+        assert_eq!(geojson_features(collection).len(), 2);
+        assert!(geojson_features(json!({})).is_empty());
+    }
+}
@@ -0,0 +1,589 @@
+//! Terrain overlays for the forecast areas, rendered from the digital elevation model (DEM)
+//! configured for each area in [`crate::options::Options::digital_elevation_models`]. Each
+//! overlay is a PNG image covering the bounds of the area (see `terrain-overlays.json`) for use
+//! as a Leaflet image overlay. The DEM is also used to summarise how much of the area's terrain
+//! is affected by the avalanche problems in a forecast.
+
+use std::{
+    collections::HashMap,
+    path::Path,
+    sync::{Arc, Mutex},
+};
+
+use axum::{
+    extract::{self, State},
+    http::{
+        header::{CACHE_CONTROL, CONTENT_TYPE},
+        HeaderValue,
+    },
+    response::{IntoResponse, Response},
+    Extension, Json,
+};
+use bytes::Bytes;
+use eyre::{Context, ContextCompat};
+use forecast_spreadsheet::{
+    Aspect, AspectElevation, ElevationBandId, ElevationRange, HazardRating, HazardRatingKind,
+    HazardRatingValue,
+};
+use geo::{ndarray::Array2, Dem, TerrainSummary};
+use http::StatusCode;
+use indexmap::IndexMap;
+use once_cell::sync::Lazy;
+use resvg::tiny_skia;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::{
+    database::Database,
+    error::{map_eyre_error, map_std_error},
+    options::Options,
+    state::AppState,
+};
+
+use super::{
+    danger_map::{current_forecasts, hazard_rgb},
+    geojson_features, geometry_polygons, get_forecast_area, ForecastAreaId, GeoPolygon,
+};
+
+/// Hillshade lit from the north west, the cartographic convention.
+const SUN_AZIMUTH: f64 = 315.0;
+const SUN_ALTITUDE: f64 = 45.0;
+/// Colour of terrain in elevations without a hazard rating.
+const UNRATED_RGB: [u8; 3] = [0xff, 0xff, 0xff];
+/// The minimum slope angle (degrees) and colour of each class in the slope overlay, steepest
+/// first. Slopes below the last class are not shaded.
+const SLOPE_CLASSES: [(f64, [u8; 3]); 3] = [
+    (40.0, [0xe0, 0x1e, 0x1e]),
+    (35.0, [0xff, 0x8c, 0x00]),
+    (30.0, [0xff, 0xe0, 0x00]),
+];
+/// The elevation bands overlay changes when a new forecast is published, so it is only cached by
+/// clients briefly.
+const ELEVATION_BANDS_CACHE_CONTROL: &str = "public, max-age=300";
+/// The slope overlay only changes when the forecast area is modified, which is rare but should
+/// still reach clients within the hour.
+const SLOPE_CACHE_CONTROL: &str = "public, max-age=3600";
+
+/// The DEM for a forecast area, cropped to the bounds of the area.
+pub struct AreaDem {
+    pub dem: Dem,
+    /// Whether each cell of the DEM is inside the forecast area.
+    pub mask: Array2<bool>,
+    /// Terrain summaries which have been calculated for the area, for each set of elevation band
+    /// boundaries.
+    summaries: Mutex<Vec<Arc<TerrainSummary>>>,
+    /// The most recently rendered elevation bands overlay, with the boundaries and ratings it
+    /// was rendered for.
+    elevation_bands_png: Mutex<Option<RenderedElevationBands>>,
+    /// The rendered slope overlay.
+    slope_png: Mutex<Option<Bytes>>,
+}
+
+/// Boundaries and ratings of the elevation bands, and the overlay rendered for them.
+type RenderedElevationBands = (Vec<f64>, Vec<Option<HazardRatingValue>>, Bytes);
+
+impl AreaDem {
+    /// Summarise the terrain of the area, see [`Dem::terrain_summary`].
+    fn terrain_summary(&self, boundaries: &[f64]) -> Arc<TerrainSummary> {
+        let mut summaries = self.summaries.lock().expect("Lock is poisoned");
+        if let Some(summary) = summaries
+            .iter()
+            .find(|summary| summary.boundaries == boundaries)
+        {
+            return summary.clone();
+        }
+        let summary = Arc::new(self.dem.terrain_summary(&self.mask, boundaries));
+        summaries.push(summary.clone());
+        summary
+    }
+
+    /// Render the elevation bands overlay, see [`render_elevation_bands`]. The rendered image is
+    /// reused until the boundaries or ratings change with the next forecast.
+    fn elevation_bands_png(
+        &self,
+        boundaries: &[f64],
+        ratings: &[Option<HazardRatingValue>],
+    ) -> eyre::Result<Bytes> {
+        let mut rendered = self.elevation_bands_png.lock().expect("Lock is poisoned");
+        if let Some((_, _, png_data)) =
+            rendered
+                .as_ref()
+                .filter(|(rendered_boundaries, rendered_ratings, _)| {
+                    rendered_boundaries == boundaries && rendered_ratings == ratings
+                })
+        {
+            return Ok(png_data.clone());
+        }
+        let png_data = Bytes::from(render_elevation_bands(self, boundaries, ratings)?);
+        *rendered = Some((boundaries.to_vec(), ratings.to_vec(), png_data.clone()));
+        Ok(png_data)
+    }
+
+    /// Render the slope overlay, see [`render_slope`]. It is only rendered once for the area.
+    fn slope_png(&self) -> eyre::Result<Bytes> {
+        let mut rendered = self.slope_png.lock().expect("Lock is poisoned");
+        if let Some(png_data) = rendered.as_ref() {
+            return Ok(png_data.clone());
+        }
+        let png_data = Bytes::from(render_slope(self)?);
+        *rendered = Some(png_data.clone());
+        Ok(png_data)
+    }
+}
+
+/// DEMs which have been loaded for each area, along with the area's GeoJSON that they were
+/// cropped to, so they are reloaded when the forecast area is modified.
+static AREA_DEMS: Lazy<Mutex<HashMap<ForecastAreaId, AreaDemEntry>>> = Lazy::new(Default::default);
+
+type AreaDemEntry = (Value, Arc<AreaDem>);
+
+fn load_area_dem(path: &Path, geojson: &Value) -> eyre::Result<AreaDem> {
+    let polygons: Vec<GeoPolygon> = geojson_features(geojson.clone())
+        .iter()
+        .filter_map(|feature| feature.get("geometry"))
+        .flat_map(geometry_polygons)
+        .collect();
+    let bounds = geo::polygons_bounds(&polygons).wrap_err("Forecast area has no polygons")?;
+    let dem = Dem::load(path)
+        .wrap_err_with(|| format!("Error loading digital elevation model {path:?}"))?
+        .crop(&bounds)
+        .wrap_err_with(|| format!("Digital elevation model {path:?} does not cover the area"))?;
+    let mask = dem.polygon_mask(&polygons);
+    Ok(AreaDem {
+        dem,
+        mask,
+        summaries: Mutex::default(),
+        elevation_bands_png: Mutex::default(),
+        slope_png: Mutex::default(),
+    })
+}
+
+fn cached_area_dem(id: &ForecastAreaId, geojson: &Value) -> Option<Arc<AreaDem>> {
+    let area_dems = AREA_DEMS.lock().expect("Lock is poisoned");
+    let (cached_geojson, area_dem) = area_dems.get(id)?;
+    (cached_geojson == geojson).then(|| area_dem.clone())
+}
+
+/// Get the DEM for the forecast area, or `None` if the area doesn't exist or has no DEM
+/// configured. DEMs are kept in memory after they are first loaded.
+pub async fn get_area_dem(
+    options: &Options,
+    database: &Database,
+    id: &ForecastAreaId,
+) -> eyre::Result<Option<Arc<AreaDem>>> {
+    let Some(path) = options.digital_elevation_models.get(id) else {
+        return Ok(None);
+    };
+    let Some(area) = get_forecast_area(database, id).await? else {
+        return Ok(None);
+    };
+    if let Some(area_dem) = cached_area_dem(id, &area.geojson) {
+        return Ok(Some(area_dem));
+    }
+
+    let path = path.clone();
+    let geojson = area.geojson.clone();
+    let area_dem = tokio::task::spawn_blocking(move || load_area_dem(&path, &geojson))
+        .await
+        .wrap_err("Error joining task")??;
+    let area_dem = Arc::new(area_dem);
+    AREA_DEMS
+        .lock()
+        .expect("Lock is poisoned")
+        .insert(id.clone(), (area.geojson, area_dem.clone()));
+    Ok(Some(area_dem))
+}
+
+/// The elevation band boundaries for [`Dem::elevation_bands`] from the forecast's elevation
+/// bands, and the forecast elevation band which contains each of the resulting bands (or `None`
+/// when it's not within any of them).
+pub fn forecast_bands(
+    elevation_bands: &IndexMap<ElevationBandId, ElevationRange>,
+) -> (Vec<f64>, Vec<Option<&ElevationBandId>>) {
+    let mut boundaries: Vec<f64> = elevation_bands
+        .values()
+        .flat_map(|range| [range.lower, range.upper])
+        .flatten()
+        .map(|elevation| elevation as f64)
+        .collect();
+    boundaries.sort_by(f64::total_cmp);
+    boundaries.dedup();
+
+    let band_ids = (0..=boundaries.len())
+        .map(|band| {
+            let lower = band.checked_sub(1).map(|i| boundaries[i]);
+            let upper = boundaries.get(band).copied();
+            elevation_bands
+                .iter()
+                .find(|(_, range)| {
+                    let above_lower = range.lower.is_none_or(|range_lower| {
+                        lower.is_some_and(|lower| lower >= range_lower as f64)
+                    });
+                    let below_upper = range.upper.is_none_or(|range_upper| {
+                        upper.is_some_and(|upper| upper <= range_upper as f64)
+                    });
+                    above_lower && below_upper
+                })
+                .map(|(id, _)| id)
+        })
+        .collect();
+    (boundaries, band_ids)
+}
+
+/// The elevation band boundaries for [`Dem::elevation_bands`], and the hazard rating of each
+/// of the resulting bands from the forecast's elevation specific hazard ratings. A band is
+/// `None` when it's not within any of the forecast's elevation bands.
+pub fn band_ratings(
+    elevation_bands: &IndexMap<ElevationBandId, ElevationRange>,
+    hazard_ratings: &IndexMap<HazardRatingKind, HazardRating>,
+) -> (Vec<f64>, Vec<Option<HazardRatingValue>>) {
+    let (boundaries, band_ids) = forecast_bands(elevation_bands);
+    let ratings = band_ids
+        .into_iter()
+        .map(|id| {
+            Some(
+                hazard_ratings
+                    .get(&HazardRatingKind::ElevationSpecific(id?.clone()))
+                    .and_then(|rating| rating.value)
+                    .unwrap_or(HazardRatingValue::NoRating),
+            )
+        })
+        .collect();
+    (boundaries, ratings)
+}
+
+pub fn geo_aspect(aspect: Aspect) -> geo::Aspect {
+    match aspect {
+        Aspect::N => geo::Aspect::N,
+        Aspect::NE => geo::Aspect::NE,
+        Aspect::E => geo::Aspect::E,
+        Aspect::SE => geo::Aspect::SE,
+        Aspect::S => geo::Aspect::S,
+        Aspect::SW => geo::Aspect::SW,
+        Aspect::W => geo::Aspect::W,
+        Aspect::NW => geo::Aspect::NW,
+    }
+}
+
+/// The terrain of a forecast area, classified by the forecast's elevation bands and aspect.
+pub struct AreaTerrainSummary {
+    summary: Arc<TerrainSummary>,
+    /// The forecast elevation band for each band of the summary.
+    band_ids: Vec<Option<ElevationBandId>>,
+}
+
+impl AreaTerrainSummary {
+    /// The fraction of the area's terrain which is in the elevation bands and aspects of an
+    /// avalanche problem.
+    pub fn affected_fraction(
+        &self,
+        aspect_elevation: &IndexMap<ElevationBandId, AspectElevation>,
+    ) -> Option<f64> {
+        let total_area = self.summary.total_area();
+        if total_area <= 0.0 {
+            return None;
+        }
+        let affected_area: f64 = self
+            .band_ids
+            .iter()
+            .enumerate()
+            .filter_map(|(band, id)| Some((band, aspect_elevation.get(id.as_ref()?)?)))
+            .flat_map(|(band, aspect_elevation)| {
+                aspect_elevation
+                    .aspects
+                    .iter()
+                    .map(move |aspect| self.summary.area(band, geo_aspect(*aspect)))
+            })
+            .sum();
+        Some(affected_area / total_area)
+    }
+}
+
+/// Summarise the terrain of the forecast area by the forecast's elevation bands and aspect, or
+/// `None` if the area has no DEM configured.
+pub async fn get_area_terrain_summary(
+    options: &Options,
+    database: &Database,
+    id: &ForecastAreaId,
+    elevation_bands: &IndexMap<ElevationBandId, ElevationRange>,
+) -> eyre::Result<Option<AreaTerrainSummary>> {
+    let Some(area_dem) = get_area_dem(options, database, id).await? else {
+        return Ok(None);
+    };
+    let (boundaries, band_ids) = forecast_bands(elevation_bands);
+    let band_ids = band_ids.into_iter().map(|id| id.cloned()).collect();
+    let summary = tokio::task::spawn_blocking(move || area_dem.terrain_summary(&boundaries))
+        .await
+        .wrap_err("Error joining task")?;
+    Ok(Some(AreaTerrainSummary { summary, band_ids }))
+}
+
+fn encode_png(data: Vec<u8>, width: usize, height: usize) -> eyre::Result<Vec<u8>> {
+    let size = tiny_skia::IntSize::from_wh(width.try_into()?, height.try_into()?)
+        .wrap_err("Invalid image size")?;
+    let pixmap = tiny_skia::Pixmap::from_vec(data, size).wrap_err("Unable to create pixmap")?;
+    pixmap.encode_png().map_err(eyre::Error::from)
+}
+
+/// Render the area's elevation bands coloured by their hazard rating over a hillshade of the
+/// terrain. Cells outside the area are transparent.
+fn render_elevation_bands(
+    area_dem: &AreaDem,
+    boundaries: &[f64],
+    ratings: &[Option<HazardRatingValue>],
+) -> eyre::Result<Vec<u8>> {
+    let dem = &area_dem.dem;
+    let bands = dem.elevation_bands(boundaries);
+    let hillshade = dem.hillshade(SUN_AZIMUTH, SUN_ALTITUDE);
+    let mut data: Vec<u8> = Vec::with_capacity(bands.len() * 4);
+    for ((index, band), shade) in bands.indexed_iter().zip(hillshade.iter()) {
+        if *band == geo::NO_DATA || !area_dem.mask[index] {
+            data.extend([0, 0, 0, 0]);
+            continue;
+        }
+        let rgb = ratings
+            .get(usize::from(*band))
+            .copied()
+            .flatten()
+            .map(hazard_rgb)
+            .unwrap_or(UNRATED_RGB);
+        // Keep some of the colour in the shadows so that the rating is still visible.
+        let light = 0.35 + 0.65 * f32::from(*shade) / 255.0;
+        data.extend(rgb.map(|channel| (f32::from(channel) * light).round() as u8));
+        data.push(u8::MAX);
+    }
+    encode_png(data, dem.columns(), dem.rows())
+}
+
+/// The colour of the slope angle class that `slope` (degrees) falls into.
+fn slope_rgb(slope: f64) -> Option<[u8; 3]> {
+    SLOPE_CLASSES
+        .iter()
+        .find(|(minimum, _)| slope >= *minimum)
+        .map(|(_, rgb)| *rgb)
+}
+
+/// Render the area's slopes shaded by slope angle class. Cells outside the area or below the
+/// least steep class are transparent.
+fn render_slope(area_dem: &AreaDem) -> eyre::Result<Vec<u8>> {
+    let dem = &area_dem.dem;
+    let slopes = dem.slopes();
+    let mut data: Vec<u8> = Vec::with_capacity(slopes.len() * 4);
+    for (index, slope) in slopes.indexed_iter() {
+        match slope_rgb(f64::from(*slope)).filter(|_| area_dem.mask[index]) {
+            Some(rgb) => {
+                data.extend(rgb);
+                data.push(u8::MAX);
+            }
+            None => data.extend([0, 0, 0, 0]),
+        }
+    }
+    encode_png(data, dem.columns(), dem.rows())
+}
+
+#[derive(Deserialize)]
+pub struct PathParams {
+    id: ForecastAreaId,
+}
+
+/// The terrain overlays available for a forecast area.
+#[derive(Serialize)]
+pub struct TerrainOverlays {
+    /// `[[south, west], [north, east]]`, as expected by Leaflet.
+    bounds: [[f64; 2]; 2],
+    /// URL of the elevation bands overlay.
+    elevation_bands: String,
+    /// URL of the slope angle overlay.
+    slope: String,
+}
+
+/// Handler for `{id}/terrain-overlays.json`.
+pub async fn overlays_handler(
+    State(state): State<AppState>,
+    extract::Path(path): extract::Path<PathParams>,
+    Extension(database): Extension<Database>,
+) -> axum::response::Result<Json<TerrainOverlays>> {
+    let area_dem = get_area_dem(state.options, &database, &path.id)
+        .await
+        .map_err(map_eyre_error)?
+        .ok_or(StatusCode::NOT_FOUND)?;
+    let bounds = area_dem.dem.bounds();
+    Ok(Json(TerrainOverlays {
+        bounds: [[bounds.south, bounds.west], [bounds.north, bounds.east]],
+        elevation_bands: format!("/forecast-areas/{}/elevation-bands.png", path.id),
+        slope: format!("/forecast-areas/{}/slope.png", path.id),
+    }))
+}
+
+/// Handler for `{id}/elevation-bands.png`, coloured using the area's current forecast.
+pub async fn elevation_bands_handler(
+    State(state): State<AppState>,
+    extract::Path(path): extract::Path<PathParams>,
+    Extension(database): Extension<Database>,
+) -> axum::response::Result<Response> {
+    let area_dem = get_area_dem(state.options, &database, &path.id)
+        .await
+        .map_err(map_eyre_error)?
+        .ok_or(StatusCode::NOT_FOUND)?;
+    let (boundaries, ratings) = current_forecasts(&state, &database)
+        .await
+        .map_err(map_eyre_error)?
+        .remove(&path.id.to_string().to_lowercase())
+        .map(|current| {
+            band_ratings(
+                &current.forecast.elevation_bands,
+                &current.forecast.hazard_ratings,
+            )
+        })
+        .unwrap_or_default();
+    let png_data = tokio::task::spawn_blocking(move || {
+        area_dem
+            .elevation_bands_png(&boundaries, &ratings)
+            .wrap_err("Error rendering elevation bands")
+    })
+    .await
+    .map_err(map_std_error)?
+    .map_err(map_eyre_error)?;
+    let mut response = png_data.into_response();
+    let headers = response.headers_mut();
+    headers.insert(CONTENT_TYPE, HeaderValue::from_static("image/png"));
+    headers.insert(
+        CACHE_CONTROL,
+        HeaderValue::from_static(ELEVATION_BANDS_CACHE_CONTROL),
+    );
+    Ok(response)
+}
+
+/// Handler for `{id}/slope.png`.
+pub async fn slope_handler(
+    State(state): State<AppState>,
+    extract::Path(path): extract::Path<PathParams>,
+    Extension(database): Extension<Database>,
+) -> axum::response::Result<Response> {
+    let area_dem = get_area_dem(state.options, &database, &path.id)
+        .await
+        .map_err(map_eyre_error)?
+        .ok_or(StatusCode::NOT_FOUND)?;
+    let png_data =
+        tokio::task::spawn_blocking(move || area_dem.slope_png().wrap_err("Error rendering slope"))
+            .await
+            .map_err(map_std_error)?
+            .map_err(map_eyre_error)?;
+    let mut response = png_data.into_response();
+    let headers = response.headers_mut();
+    headers.insert(CONTENT_TYPE, HeaderValue::from_static("image/png"));
+    headers.insert(CACHE_CONTROL, HeaderValue::from_static(SLOPE_CACHE_CONTROL));
+    Ok(response)
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::Arc;
+
+    use forecast_spreadsheet::{
+        Aspect, AspectElevation, ElevationBandId, ElevationRange, HazardRating, HazardRatingKind,
+        HazardRatingValue,
+    };
+    use geo::TerrainSummary;
+    use indexmap::IndexMap;
+
+    use super::{band_ratings, slope_rgb, AreaTerrainSummary, SLOPE_CLASSES};
+
+    #[test]
+    fn test_band_ratings() {
+        let elevation_bands: IndexMap<ElevationBandId, ElevationRange> = [
+            (
+                "high-alpine".into(),
+                ElevationRange {
+                    upper: None,
+                    lower: Some(2700),
+                },
+            ),
+            (
+                "alpine".into(),
+                ElevationRange {
+                    upper: Some(2700),
+                    lower: Some(1800),
+                },
+            ),
+            (
+                "sub-alpine".into(),
+                ElevationRange {
+                    upper: Some(1800),
+                    lower: None,
+                },
+            ),
+        ]
+        .into_iter()
+        .collect();
+        let hazard_ratings: IndexMap<HazardRatingKind, HazardRating> = [
+            (HazardRatingKind::Overall, HazardRatingValue::Considerable),
+            (
+                HazardRatingKind::ElevationSpecific("high-alpine".into()),
+                HazardRatingValue::Considerable,
+            ),
+            (
+                HazardRatingKind::ElevationSpecific("alpine".into()),
+                HazardRatingValue::Moderate,
+            ),
+        ]
+        .into_iter()
+        .map(|(kind, value)| {
+            (
+                kind,
+                HazardRating {
+                    value: Some(value),
+                    trend: None,
+                    confidence: None,
+                },
+            )
+        })
+        .collect();
+
+        let (boundaries, ratings) = band_ratings(&elevation_bands, &hazard_ratings);
+        assert_eq!(boundaries, vec![1800.0, 2700.0]);
+        assert_eq!(
+            ratings,
+            vec![
+                Some(HazardRatingValue::NoRating),
+                Some(HazardRatingValue::Moderate),
+                Some(HazardRatingValue::Considerable),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_slope_rgb() {
+        assert_eq!(slope_rgb(29.9), None);
+        assert_eq!(slope_rgb(30.0), Some(SLOPE_CLASSES[2].1));
+        assert_eq!(slope_rgb(37.0), Some(SLOPE_CLASSES[1].1));
+        assert_eq!(slope_rgb(55.0), Some(SLOPE_CLASSES[0].1));
+        assert_eq!(slope_rgb(f64::NAN), None);
+    }
+
+    #[test]
+    fn test_affected_fraction() {
+        let mut aspect_area = vec![[10.0; 8], [20.0; 8]];
+        aspect_area[1][geo::Aspect::N as usize] = 100.0;
+        let summary = AreaTerrainSummary {
+            summary: Arc::new(TerrainSummary {
+                boundaries: vec![2000.0],
+                aspect_area,
+                flat_area: vec![20.0, 0.0],
+            }),
+            band_ids: vec![Some("low".into()), Some("high".into())],
+        };
+        let aspect_elevation: IndexMap<ElevationBandId, AspectElevation> = [(
+            "high".into(),
+            AspectElevation {
+                aspects: [Aspect::N, Aspect::NE].into_iter().collect(),
+            },
+        )]
+        .into_iter()
+        .collect();
+        // (100 + 20) / (80 + 20 + 7 * 20 + 100)
+        let fraction = summary.affected_fraction(&aspect_elevation).unwrap();
+        assert!((fraction - 120.0 / 340.0).abs() < 1e-9, "{fraction}");
+        assert_eq!(summary.affected_fraction(&IndexMap::new()), Some(0.0));
+    }
+}
@@ -0,0 +1,201 @@
+//! Mapbox Vector Tiles <https://github.com/mapbox/vector-tile-spec> of the forecast area
+//! polygons, which are much lighter than the full GeoJSON for web maps of large regions. Tiles
+//! are generated on demand, and those containing a forecast area are cached in the database
+//! until a forecast area is modified.
+
+use axum::{
+    extract::Path,
+    http::{header::CONTENT_TYPE, HeaderValue},
+    response::{IntoResponse, Response},
+    Extension,
+};
+use eyre::Context;
+use geo::mvt::{self, Feature, TileId};
+use http::StatusCode;
+use serde::Deserialize;
+use serde_json::Value;
+
+use crate::{database::Database, error::map_eyre_error, types};
+
+use super::{
+    geojson_features, geometry_polygons, get_forecast_area, list_forecast_areas, GeoPolygon,
+};
+
+/// Tiles at higher zoom levels are not served, clients should overzoom the tiles of this level.
+const MAX_ZOOM: u8 = 16;
+/// Maximum number of tiles kept in the cache, the oldest are evicted first.
+const MAX_CACHED_TILES: i64 = 10_000;
+const LAYER_NAME: &str = "forecast_areas";
+
+/// A forecast area with its polygons.
+pub struct TileArea {
+    pub id: String,
+    pub polygons: Vec<GeoPolygon>,
+}
+
+impl TileArea {
+    fn new(id: String, geojson: Value) -> Self {
+        let polygons = geojson_features(geojson)
+            .iter()
+            .filter_map(|feature| feature.get("geometry"))
+            .flat_map(geometry_polygons)
+            .collect();
+        Self { id, polygons }
+    }
+}
+
+/// Generate the vector tile containing the `areas` in a layer named [`LAYER_NAME`], with the
+/// area's id in the `area` property of each feature.
+pub fn generate_tile(tile: &TileId, areas: &[TileArea]) -> Vec<u8> {
+    let features: Vec<Feature> = areas
+        .iter()
+        .map(|area| Feature {
+            polygons: &area.polygons,
+            value: &area.id,
+        })
+        .collect();
+    mvt::encode_tile(tile, LAYER_NAME, "area", &features)
+}
+
+async fn get_cached_tile(database: &Database, tile: &TileId) -> eyre::Result<Option<Vec<u8>>> {
+    let (z, x, y) = (i64::from(tile.z), i64::from(tile.x), i64::from(tile.y));
+    Ok(sqlx::query!(
+        "SELECT data FROM forecast_area_tile_cache WHERE z=$1 AND x=$2 AND y=$3",
+        z,
+        x,
+        y
+    )
+    .fetch_optional(database)
+    .await
+    .wrap_err("Error fetching cached tile")?
+    .map(|record| record.data))
+}
+
+/// Store the tile in the cache, evicting the oldest tiles beyond [`MAX_CACHED_TILES`].
+async fn store_cached_tile(database: &Database, tile: &TileId, data: &[u8]) -> eyre::Result<()> {
+    let (z, x, y) = (i64::from(tile.z), i64::from(tile.x), i64::from(tile.y));
+    let cached_at = types::Time::now_utc();
+    sqlx::query!(
+        "INSERT INTO forecast_area_tile_cache VALUES($1, $2, $3, $4, $5) ON CONFLICT(z, x, y) DO UPDATE SET data=excluded.data, cached_at=excluded.cached_at",
+        z,
+        x,
+        y,
+        data,
+        cached_at,
+    )
+    .execute(database)
+    .await
+    .wrap_err("Error storing cached tile")?;
+    sqlx::query!(
+        "DELETE FROM forecast_area_tile_cache WHERE rowid IN (SELECT rowid FROM forecast_area_tile_cache ORDER BY cached_at DESC LIMIT -1 OFFSET $1)",
+        MAX_CACHED_TILES,
+    )
+    .execute(database)
+    .await
+    .wrap_err("Error evicting cached tiles")?;
+    Ok(())
+}
+
+/// Remove all the cached tiles, they need to be regenerated after a forecast area is modified.
+pub async fn clear_tile_cache(database: &Database) -> eyre::Result<()> {
+    sqlx::query!("DELETE FROM forecast_area_tile_cache")
+        .execute(database)
+        .await
+        .wrap_err("Error clearing tile cache")?;
+    Ok(())
+}
+
+async fn get_tile(database: &Database, tile: &TileId) -> eyre::Result<Vec<u8>> {
+    if let Some(data) = get_cached_tile(database, tile).await? {
+        return Ok(data);
+    }
+    let mut areas = Vec::new();
+    for id in list_forecast_areas(database).await? {
+        if let Some(area) = get_forecast_area(database, &id).await? {
+            areas.push(TileArea::new(area.id.to_string(), area.geojson));
+        }
+    }
+    let bounds = tile.bounds();
+    areas.retain(|area| {
+        geo::polygons_bounds(&area.polygons)
+            .is_some_and(|area_bounds| area_bounds.intersects(&bounds))
+    });
+    if areas.is_empty() {
+        return Ok(Vec::new());
+    }
+    let data = generate_tile(tile, &areas);
+    // Only the tiles containing a forecast area are cached, the empty tiles are cheap to
+    // generate and there can be a very large number of them.
+    if !data.is_empty() {
+        store_cached_tile(database, tile, &data).await?;
+    }
+    Ok(data)
+}
+
+#[derive(Deserialize)]
+pub struct PathParams {
+    z: u8,
+    x: u32,
+    /// The y coordinate with the `.mvt` extension.
+    y: String,
+}
+
+/// Handler for `tiles/{z}/{x}/{y}.mvt`.
+pub async fn handler(
+    Path(path): Path<PathParams>,
+    Extension(database): Extension<Database>,
+) -> axum::response::Result<Response> {
+    let tile = path
+        .y
+        .strip_suffix(".mvt")
+        .and_then(|y| y.parse().ok())
+        .map(|y| TileId {
+            z: path.z,
+            x: path.x,
+            y,
+        })
+        .filter(|tile| tile.is_valid(MAX_ZOOM))
+        .ok_or(StatusCode::NOT_FOUND)?;
+    let data = get_tile(&database, &tile).await.map_err(map_eyre_error)?;
+    let mut response = data.into_response();
+    response.headers_mut().insert(
+        CONTENT_TYPE,
+        "application/vnd.mapbox-vector-tile"
+            .parse::<HeaderValue>()
+            .unwrap(),
+    );
+    Ok(response)
+}
+
+#[cfg(test)]
+mod test {
+    use geo::mvt::TileId;
+    use serde_json::json;
+
+    use super::{generate_tile, TileArea};
+
+    #[test]
+    fn test_generate_tile() {
+        let areas = [TileArea::new(
+            "gudauri".to_owned(),
+            json!({
+                "type": "FeatureCollection",
+                "features": [{
+                    "type": "Feature",
+                    "properties": {},
+                    "geometry": {
+                        "type": "MultiPolygon",
+                        "coordinates": [[[[40.0, 40.0], [50.0, 40.0], [50.0, 50.0], [40.0, 40.0]]]]
+                    }
+                }]
+            }),
+        )];
+        assert_eq!(areas[0].polygons.len(), 1);
+        let tile = TileId { z: 0, x: 0, y: 0 };
+        let data = generate_tile(&tile, &areas);
+        assert!(data
+            .windows(b"gudauri".len())
+            .any(|window| window == b"gudauri"));
+        assert!(generate_tile(&TileId { z: 10, x: 0, y: 0 }, &areas).is_empty());
+    }
+}
@@ -0,0 +1,589 @@
+//! Validation of the GeoJSON for a forecast area before it is stored. The area is made up of
+//! `Polygon` and `MultiPolygon` features, along with optional `LineString` features which mark
+//! the boundaries between elevation bands (see [`ELEVATION_BAND_BOUNDARY_PROPERTY`]).
+//!
+//! Problems which can be fixed without changing the shape of the area (unclosed rings,
+//! duplicate positions, ring winding, projected coordinates) are fixed automatically, anything
+//! else is rejected with a [`ValidationErrors`] listing each problem.
+
+use axum::{
+    response::{IntoResponse, Response},
+    Json,
+};
+use geo::Projection;
+use http::StatusCode;
+use serde::Serialize;
+use serde_json::{json, Map, Value};
+
+use super::geojson_features;
+
+/// The property of a `LineString` feature which marks it as the boundary between two elevation
+/// bands, the value is the elevation of the boundary in metres.
+pub const ELEVATION_BAND_BOUNDARY_PROPERTY: &str = "elevation_band_boundary_metres";
+
+/// Maximum size of an uploaded forecast area GeoJSON file in bytes.
+pub const MAX_GEOJSON_BYTES: usize = 10 * 1024 * 1024;
+/// Maximum number of features in a forecast area.
+const MAX_FEATURES: usize = 1_000;
+/// Maximum number of positions in all the geometries of a forecast area.
+const MAX_POSITIONS: usize = 100_000;
+/// Maximum number of problems reported, further problems are ignored.
+const MAX_ISSUES: usize = 100;
+
+/// Names of coordinate reference systems which are equivalent to WGS84 longitude, latitude.
+const WGS84_CRS_NAMES: &[&str] = &[
+    "urn:ogc:def:crs:OGC:1.3:CRS84",
+    "urn:ogc:def:crs:OGC::CRS84",
+    "urn:ogc:def:crs:EPSG::4326",
+    "EPSG:4326",
+];
+
+type Ring = Vec<(f64, f64)>;
+
+/// A problem with a forecast area's GeoJSON.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ValidationIssue {
+    /// JSON pointer to the problem in the validated `FeatureCollection`, e.g.
+    /// `/features/0/geometry/coordinates/0`.
+    pub pointer: String,
+    pub message: String,
+}
+
+impl std::fmt::Display for ValidationIssue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.pointer.is_empty() {
+            self.message.fmt(f)
+        } else {
+            write!(f, "{}: {}", self.pointer, self.message)
+        }
+    }
+}
+
+/// The problems which prevent a forecast area's GeoJSON from being stored.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ValidationErrors {
+    pub errors: Vec<ValidationIssue>,
+}
+
+impl std::fmt::Display for ValidationErrors {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("Invalid forecast area GeoJSON")?;
+        for (index, issue) in self.errors.iter().enumerate() {
+            let separator = if index == 0 { ": " } else { ", " };
+            write!(f, "{separator}{issue}")?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for ValidationErrors {}
+
+impl ValidationErrors {
+    pub fn single(pointer: impl Into<String>, message: impl Into<String>) -> Self {
+        Self {
+            errors: vec![ValidationIssue {
+                pointer: pointer.into(),
+                message: message.into(),
+            }],
+        }
+    }
+}
+
+impl IntoResponse for ValidationErrors {
+    fn into_response(self) -> Response {
+        (StatusCode::UNPROCESSABLE_ENTITY, Json(self)).into_response()
+    }
+}
+
+/// GeoJSON which has passed validation.
+#[derive(Debug)]
+pub struct ValidatedGeoJson {
+    /// The normalized `FeatureCollection`.
+    pub geojson: Value,
+    /// Problems which were fixed automatically.
+    pub fixes: Vec<ValidationIssue>,
+}
+
+/// The projection of the GeoJSON's (deprecated) `crs` member, `None` for WGS84.
+fn crs_projection(crs: &Value) -> Result<Option<Projection>, String> {
+    let name = crs
+        .pointer("/properties/name")
+        .and_then(Value::as_str)
+        .ok_or("Only named coordinate reference systems are supported")?;
+    if WGS84_CRS_NAMES.contains(&name) {
+        return Ok(None);
+    }
+    name.contains("EPSG")
+        .then(|| name.rsplit(':').next()?.parse::<u32>().ok())
+        .flatten()
+        .and_then(Projection::from_epsg)
+        .map(Some)
+        .ok_or_else(|| format!("Unsupported coordinate reference system {name:?}"))
+}
+
+#[derive(Default)]
+struct Validator {
+    projection: Option<Projection>,
+    positions: usize,
+    errors: Vec<ValidationIssue>,
+    fixes: Vec<ValidationIssue>,
+}
+
+impl Validator {
+    fn error(&mut self, pointer: &str, message: impl Into<String>) {
+        if self.errors.len() < MAX_ISSUES {
+            self.errors.push(ValidationIssue {
+                pointer: pointer.to_owned(),
+                message: message.into(),
+            });
+        }
+    }
+
+    fn fix(&mut self, pointer: &str, message: impl Into<String>) {
+        if self.fixes.len() < MAX_ISSUES {
+            self.fixes.push(ValidationIssue {
+                pointer: pointer.to_owned(),
+                message: message.into(),
+            });
+        }
+    }
+
+    fn position(&mut self, pointer: &str, position: &Value) -> Option<(f64, f64)> {
+        let values = position.as_array().map(Vec::as_slice).unwrap_or_default();
+        let (Some(x), Some(y)) = (
+            values.first().and_then(Value::as_f64),
+            values.get(1).and_then(Value::as_f64),
+        ) else {
+            self.error(pointer, "Position must be an array of at least two numbers");
+            return None;
+        };
+        let (longitude, latitude) = match self.projection {
+            Some(projection) => projection.to_wgs84(x, y),
+            None => (x, y),
+        };
+        if !(-180.0..=180.0).contains(&longitude) || !(-90.0..=90.0).contains(&latitude) {
+            self.error(
+                pointer,
+                format!(
+                    "Position ({longitude}, {latitude}) is outside the range of longitude and \
+                     latitude"
+                ),
+            );
+            return None;
+        }
+        Some((longitude, latitude))
+    }
+
+    fn positions(&mut self, pointer: &str, positions: &Value) -> Option<Vec<(f64, f64)>> {
+        let Some(positions) = positions.as_array() else {
+            self.error(pointer, "Expected an array of positions");
+            return None;
+        };
+        let within_limit = self.positions <= MAX_POSITIONS;
+        self.positions += positions.len();
+        if self.positions > MAX_POSITIONS {
+            // Only report the limit once.
+            if within_limit {
+                self.error("", format!("More than {MAX_POSITIONS} positions"));
+            }
+            return None;
+        }
+        let parsed: Vec<Option<(f64, f64)>> = positions
+            .iter()
+            .enumerate()
+            .map(|(index, position)| self.position(&format!("{pointer}/{index}"), position))
+            .collect();
+        parsed.into_iter().collect()
+    }
+
+    /// Validate a linear ring, fixing it where possible and winding it anticlockwise for an
+    /// exterior ring or clockwise for a hole, as required by RFC 7946.
+    fn ring(&mut self, pointer: &str, ring: &Value, exterior: bool) -> Option<Ring> {
+        let mut ring = self.positions(pointer, ring)?;
+        let length = ring.len();
+        ring.dedup();
+        if ring.len() < length {
+            self.fix(
+                pointer,
+                format!("Removed {} duplicate positions", length - ring.len()),
+            );
+        }
+        if ring.len() > 1 && ring.first() != ring.last() {
+            ring.push(ring[0]);
+            self.fix(pointer, "Closed ring");
+        }
+        if ring.len() < 4 {
+            self.error(pointer, "Ring must have at least 4 positions");
+            return None;
+        }
+        if geo::ring_self_intersects(&ring) {
+            self.error(pointer, "Ring intersects itself");
+            return None;
+        }
+        let area = geo::ring_signed_area(&ring);
+        if area == 0.0 {
+            self.error(pointer, "Ring has no area");
+            return None;
+        }
+        if (area > 0.0) != exterior {
+            ring.reverse();
+            self.fix(pointer, "Reversed ring winding to follow RFC 7946");
+        }
+        Some(ring)
+    }
+
+    fn polygon(&mut self, pointer: &str, rings: &Value) -> Option<Vec<Ring>> {
+        let rings = match rings.as_array() {
+            Some(rings) if !rings.is_empty() => rings,
+            _ => {
+                self.error(pointer, "Polygon must be a non-empty array of rings");
+                return None;
+            }
+        };
+        let rings: Vec<Option<Ring>> = rings
+            .iter()
+            .enumerate()
+            .map(|(index, ring)| self.ring(&format!("{pointer}/{index}"), ring, index == 0))
+            .collect();
+        rings.into_iter().collect()
+    }
+
+    /// Validate a feature and normalize its geometry. Returns whether the feature is a polygon
+    /// along with the normalized feature.
+    fn feature(&mut self, pointer: &str, feature: &Value) -> Option<(bool, Value)> {
+        let properties = match feature.get("properties") {
+            Some(Value::Object(properties)) => properties.clone(),
+            _ => Map::new(),
+        };
+        let Some(geometry) = feature
+            .get("geometry")
+            .filter(|geometry| !geometry.is_null())
+        else {
+            self.error(pointer, "Feature has no geometry");
+            return None;
+        };
+        let pointer = format!("{pointer}/geometry");
+        let Some(coordinates) = geometry.get("coordinates") else {
+            self.error(&pointer, "Geometry has no coordinates");
+            return None;
+        };
+        let coordinates_pointer = format!("{pointer}/coordinates");
+        let (is_polygon, geometry) = match geometry.get("type").and_then(Value::as_str) {
+            Some("Polygon") => {
+                let polygon = self.polygon(&coordinates_pointer, coordinates)?;
+                (
+                    true,
+                    json!({ "type": "Polygon", "coordinates": polygon_json(&polygon) }),
+                )
+            }
+            Some("MultiPolygon") => {
+                let Some(polygons) = coordinates.as_array() else {
+                    self.error(&coordinates_pointer, "Expected an array of polygons");
+                    return None;
+                };
+                let polygons: Vec<Option<Value>> = polygons
+                    .iter()
+                    .enumerate()
+                    .map(|(index, polygon)| {
+                        self.polygon(&format!("{coordinates_pointer}/{index}"), polygon)
+                            .map(|polygon| polygon_json(&polygon))
+                    })
+                    .collect();
+                let polygons: Vec<Value> = polygons.into_iter().collect::<Option<_>>()?;
+                (
+                    true,
+                    json!({ "type": "MultiPolygon", "coordinates": polygons }),
+                )
+            }
+            Some("LineString") => {
+                if !properties
+                    .get(ELEVATION_BAND_BOUNDARY_PROPERTY)
+                    .is_some_and(Value::is_number)
+                {
+                    let message = format!(
+                        "LineString is missing the {ELEVATION_BAND_BOUNDARY_PROPERTY:?} property"
+                    );
+                    self.error(&pointer, message);
+                    return None;
+                }
+                let mut positions = self.positions(&coordinates_pointer, coordinates)?;
+                positions.dedup();
+                if positions.len() < 2 {
+                    self.error(
+                        &coordinates_pointer,
+                        "LineString must have at least 2 distinct positions",
+                    );
+                    return None;
+                }
+                (
+                    false,
+                    json!({ "type": "LineString", "coordinates": positions_json(&positions) }),
+                )
+            }
+            Some(other) => {
+                self.error(&pointer, format!("Unsupported geometry type {other:?}"));
+                return None;
+            }
+            None => {
+                self.error(&pointer, "Geometry has no type");
+                return None;
+            }
+        };
+        Some((
+            is_polygon,
+            json!({
+                "type": "Feature",
+                "geometry": geometry,
+                "properties": properties,
+            }),
+        ))
+    }
+}
+
+fn positions_json(positions: &[(f64, f64)]) -> Value {
+    positions
+        .iter()
+        .map(|(longitude, latitude)| json!([longitude, latitude]))
+        .collect()
+}
+
+fn polygon_json(rings: &[Ring]) -> Value {
+    rings.iter().map(|ring| positions_json(ring)).collect()
+}
+
+/// Validate the GeoJSON for a forecast area and normalize it into a `FeatureCollection` of
+/// WGS84 features with RFC 7946 polygon winding. It must contain at least one polygon.
+pub fn validate_forecast_area_geojson(
+    geojson: Value,
+) -> Result<ValidatedGeoJson, ValidationErrors> {
+    let mut validator = Validator::default();
+    if let Some(crs) = geojson.get("crs") {
+        match crs_projection(crs) {
+            Ok(None) => {}
+            Ok(Some(projection)) => {
+                validator.projection = Some(projection);
+                validator.fix("/crs", "Converted coordinates to WGS84 longitude, latitude");
+            }
+            Err(message) => return Err(ValidationErrors::single("/crs", message)),
+        }
+    }
+
+    let features = geojson_features(geojson);
+    if features.len() > MAX_FEATURES {
+        return Err(ValidationErrors::single(
+            "/features",
+            format!("More than {MAX_FEATURES} features"),
+        ));
+    }
+    let mut has_polygon = false;
+    let mut normalized = Vec::with_capacity(features.len());
+    for (index, feature) in features.iter().enumerate() {
+        if let Some((is_polygon, feature)) =
+            validator.feature(&format!("/features/{index}"), feature)
+        {
+            has_polygon |= is_polygon;
+            normalized.push(feature);
+        }
+    }
+    if !has_polygon && validator.errors.is_empty() {
+        validator.error(
+            "/features",
+            "Forecast area must contain at least one Polygon or MultiPolygon",
+        );
+    }
+    if !validator.errors.is_empty() {
+        return Err(ValidationErrors {
+            errors: validator.errors,
+        });
+    }
+    Ok(ValidatedGeoJson {
+        geojson: json!({
+            "type": "FeatureCollection",
+            "features": normalized,
+        }),
+        fixes: validator.fixes,
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use serde_json::json;
+
+    use super::{validate_forecast_area_geojson, ELEVATION_BAND_BOUNDARY_PROPERTY};
+
+    #[test]
+    fn test_validate_rewinds_polygons() {
+        // Exterior ring is clockwise, the hole is anticlockwise.
+        let geojson = json!({
+            "type": "Polygon",
+            "coordinates": [
+                [[0.0, 0.0], [0.0, 4.0], [4.0, 4.0], [4.0, 0.0], [0.0, 0.0]],
+                [[1.0, 1.0], [2.0, 1.0], [2.0, 2.0], [1.0, 2.0], [1.0, 1.0]],
+            ],
+        });
+        let validated = validate_forecast_area_geojson(geojson).unwrap();
+        assert_eq!(
+            validated.geojson,
+            json!({
+                "type": "FeatureCollection",
+                "features": [{
+                    "type": "Feature",
+                    "geometry": {
+                        "type": "Polygon",
+                        "coordinates": [
+                            [[0.0, 0.0], [4.0, 0.0], [4.0, 4.0], [0.0, 4.0], [0.0, 0.0]],
+                            [[1.0, 1.0], [1.0, 2.0], [2.0, 2.0], [2.0, 1.0], [1.0, 1.0]],
+                        ],
+                    },
+                    "properties": {},
+                }],
+            })
+        );
+        assert_eq!(validated.fixes.len(), 2);
+    }
+
+    #[test]
+    fn test_validate_fixes_rings() {
+        // Not closed, with a duplicate position.
+        let geojson = json!({
+            "type": "Polygon",
+            "coordinates": [[[0.0, 0.0], [4.0, 0.0], [4.0, 0.0], [4.0, 4.0], [0.0, 4.0]]],
+        });
+        let validated = validate_forecast_area_geojson(geojson).unwrap();
+        assert_eq!(
+            validated.geojson["features"][0]["geometry"]["coordinates"],
+            json!([[[0.0, 0.0], [4.0, 0.0], [4.0, 4.0], [0.0, 4.0], [0.0, 0.0]]])
+        );
+        insta::assert_json_snapshot!(validated.fixes, @r###"
+        [
+          {
+            "pointer": "/features/0/geometry/coordinates/0",
+            "message": "Removed 1 duplicate positions"
+          },
+          {
+            "pointer": "/features/0/geometry/coordinates/0",
+            "message": "Closed ring"
+          }
+        ]
+        "###);
+    }
+
+    #[test]
+    fn test_validate_reprojects() {
+        let geojson = json!({
+            "type": "FeatureCollection",
+            "crs": { "type": "name", "properties": { "name": "urn:ogc:def:crs:EPSG::3857" } },
+            "features": [{
+                "type": "Feature",
+                "geometry": {
+                    "type": "Polygon",
+                    "coordinates": [[[0.0, 0.0], [111319.49, 0.0], [0.0, 111325.14], [0.0, 0.0]]],
+                },
+                "properties": {},
+            }],
+        });
+        let validated = validate_forecast_area_geojson(geojson).unwrap();
+        let ring = validated.geojson["features"][0]["geometry"]["coordinates"][0]
+            .as_array()
+            .unwrap()
+            .clone();
+        let corner = ring[2].as_array().unwrap();
+        assert!(corner[0].as_f64().unwrap().abs() < 1e-9);
+        assert!((corner[1].as_f64().unwrap() - 1.0).abs() < 1e-4);
+        assert_eq!(validated.fixes[0].pointer, "/crs");
+    }
+
+    #[test]
+    fn test_validate_elevation_band_boundary() {
+        let polygon = json!({
+            "type": "Feature",
+            "geometry": {
+                "type": "Polygon",
+                "coordinates": [[[0.0, 0.0], [4.0, 0.0], [4.0, 4.0], [0.0, 0.0]]],
+            },
+            "properties": { "name": "area" },
+        });
+        let line = json!({
+            "type": "Feature",
+            "geometry": { "type": "LineString", "coordinates": [[0.0, 1.0], [4.0, 1.0]] },
+            "properties": { ELEVATION_BAND_BOUNDARY_PROPERTY: 1800 },
+        });
+        let geojson = json!({ "type": "FeatureCollection", "features": [polygon, line] });
+        let validated = validate_forecast_area_geojson(geojson.clone()).unwrap();
+        assert_eq!(validated.geojson, geojson);
+        assert!(validated.fixes.is_empty());
+
+        let unlabelled = json!({
+            "type": "FeatureCollection",
+            "features": [
+                polygon,
+                { "type": "Feature", "geometry": line["geometry"], "properties": {} },
+            ],
+        });
+        assert!(validate_forecast_area_geojson(unlabelled).is_err());
+
+        let only_line = json!({ "type": "FeatureCollection", "features": [line] });
+        assert!(validate_forecast_area_geojson(only_line).is_err());
+    }
+
+    #[test]
+    fn test_validate_errors() {
+        let geojson = json!({
+            "type": "FeatureCollection",
+            "features": [
+                {
+                    "type": "Feature",
+                    "geometry": {
+                        "type": "Polygon",
+                        "coordinates": [
+                            [[0.0, 0.0], [2.0, 2.0], [2.0, 0.0], [0.0, 2.0], [0.0, 0.0]],
+                        ],
+                    },
+                },
+                {
+                    "type": "Feature",
+                    "geometry": { "type": "Point", "coordinates": [0.0, 0.0] },
+                },
+                {
+                    "type": "Feature",
+                    "geometry": {
+                        "type": "Polygon",
+                        "coordinates": [[[0.0, 0.0], [200.0, 0.0], [0.0, 4.0], [0.0, 0.0]]],
+                    },
+                },
+            ],
+        });
+        let errors = validate_forecast_area_geojson(geojson).unwrap_err();
+        insta::assert_json_snapshot!(errors, @r###"
+        {
+          "errors": [
+            {
+              "pointer": "/features/0/geometry/coordinates/0",
+              "message": "Ring intersects itself"
+            },
+            {
+              "pointer": "/features/1/geometry",
+              "message": "Unsupported geometry type \"Point\""
+            },
+            {
+              "pointer": "/features/2/geometry/coordinates/0/1",
+              "message": "Position (200, 0) is outside the range of longitude and latitude"
+            }
+          ]
+        }
+        "###);
+
+        let projected = json!({
+            "type": "Polygon",
+            "crs": { "type": "name", "properties": { "name": "urn:ogc:def:crs:EPSG::2193" } },
+            "coordinates": [[[0.0, 0.0], [4.0, 0.0], [4.0, 4.0], [0.0, 0.0]]],
+        });
+        assert_eq!(
+            validate_forecast_area_geojson(projected)
+                .unwrap_err()
+                .errors[0]
+                .pointer,
+            "/crs"
+        );
+    }
+}
@@ -0,0 +1,277 @@
+//! The directory of forecasters, managed at `/admin/forecasters`, crediting the team at
+//! `/forecasters`. Forecasters are identified by the initials used in the forecast file names
+//! (see [`crate::forecasts::parse_forecast_name`]), which are resolved to their full names on the
+//! forecast pages and the index.
+
+use std::collections::HashMap;
+
+use axum::{
+    extract::{Path, State},
+    response::{IntoResponse, Response},
+    routing::get,
+    Extension, Router,
+};
+use eyre::ContextCompat;
+use http::StatusCode;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    database::{
+        blob::{BlobHash, BlobStore},
+        Database,
+    },
+    error::map_eyre_error,
+    news::Translations,
+    observations::{photo_response, Photo},
+    state::AppState,
+    templates::TemplatesWithContext,
+    types,
+};
+
+pub fn router() -> Router<AppState> {
+    Router::new()
+        .route("/", get(handler))
+        .route("/{initials}/photo", get(photo_handler))
+}
+
+/// The initials of a forecaster, as used in the forecast file names, e.g. `LF`.
+#[derive(sqlx::Type, Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Hash)]
+#[serde(transparent)]
+#[sqlx(transparent)]
+pub struct ForecasterInitials(String);
+
+impl ForecasterInitials {
+    /// Whether the initials only contain letters and numbers, so they can be used in a forecast
+    /// file name.
+    pub fn is_valid(&self) -> bool {
+        !self.0.is_empty() && self.0.chars().all(|c| c.is_ascii_alphanumeric())
+    }
+}
+
+impl From<String> for ForecasterInitials {
+    fn from(value: String) -> Self {
+        Self(value)
+    }
+}
+
+impl std::fmt::Display for ForecasterInitials {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct Forecaster {
+    pub initials: ForecasterInitials,
+    pub updated_at: types::Time,
+    /// Full name, shown instead of the initials.
+    pub name: String,
+    pub organisation: Option<String>,
+    /// Markdown.
+    pub bio: sqlx::types::Json<Translations>,
+    pub photo_content_type: Option<String>,
+    pub photo_blob_hash: Option<BlobHash>,
+}
+
+impl Forecaster {
+    /// Path of the forecaster's photo, if they have one.
+    pub fn photo_path(&self) -> Option<String> {
+        self.photo_blob_hash
+            .as_ref()
+            .map(|_| format!("/forecasters/{}/photo", self.initials))
+    }
+}
+
+/// A new photo for a forecaster, which has already been stored in the [`BlobStore`].
+pub struct NewPhoto {
+    pub content_type: String,
+    pub blob_hash: BlobHash,
+}
+
+pub async fn list_forecasters(database: &Database) -> eyre::Result<Vec<Forecaster>> {
+    Ok(sqlx::query_as!(
+        Forecaster,
+        r#"SELECT
+            initials as "initials: ForecasterInitials",
+            updated_at as "updated_at: types::Time",
+            name,
+            organisation,
+            bio as "bio: sqlx::types::Json<Translations>",
+            photo_content_type,
+            photo_blob_hash as "photo_blob_hash: BlobHash"
+        FROM forecasters ORDER BY name"#,
+    )
+    .fetch_all(database)
+    .await?)
+}
+
+pub async fn get_forecaster(
+    database: &Database,
+    initials: &ForecasterInitials,
+) -> eyre::Result<Option<Forecaster>> {
+    Ok(sqlx::query_as!(
+        Forecaster,
+        r#"SELECT
+            initials as "initials: ForecasterInitials",
+            updated_at as "updated_at: types::Time",
+            name,
+            organisation,
+            bio as "bio: sqlx::types::Json<Translations>",
+            photo_content_type,
+            photo_blob_hash as "photo_blob_hash: BlobHash"
+        FROM forecasters WHERE initials = $1"#,
+        initials
+    )
+    .fetch_optional(database)
+    .await?)
+}
+
+/// The full names of the forecasters, keyed by their initials.
+pub async fn names(database: &Database) -> eyre::Result<HashMap<String, String>> {
+    Ok(sqlx::query!("SELECT initials, name FROM forecasters")
+        .fetch_all(database)
+        .await?
+        .into_iter()
+        .map(|record| (record.initials, record.name))
+        .collect())
+}
+
+/// Create the forecaster, or replace an existing forecaster with the same initials. The existing
+/// photo is kept if there is no new `photo`, unless `remove_photo` is set.
+pub async fn save_forecaster(
+    database: &Database,
+    initials: &ForecasterInitials,
+    name: &str,
+    organisation: Option<&str>,
+    bio: &Translations,
+    photo: Option<NewPhoto>,
+    remove_photo: bool,
+) -> eyre::Result<()> {
+    let now: types::Time = time::OffsetDateTime::now_utc().into();
+    let bio = sqlx::types::Json(bio);
+    let mut transaction = database.begin().await?;
+    sqlx::query!(
+        "INSERT INTO forecasters(initials, updated_at, name, organisation, bio) VALUES($1, $2, $3, $4, $5) ON CONFLICT(initials) DO UPDATE SET updated_at=excluded.updated_at, name=excluded.name, organisation=excluded.organisation, bio=excluded.bio",
+        initials,
+        now,
+        name,
+        organisation,
+        bio,
+    )
+    .execute(&mut *transaction)
+    .await?;
+    if let Some(photo) = photo {
+        sqlx::query!(
+            "UPDATE forecasters SET photo_content_type = $1, photo_blob_hash = $2 WHERE initials = $3",
+            photo.content_type,
+            photo.blob_hash,
+            initials,
+        )
+        .execute(&mut *transaction)
+        .await?;
+    } else if remove_photo {
+        sqlx::query!(
+            "UPDATE forecasters SET photo_content_type = NULL, photo_blob_hash = NULL WHERE initials = $1",
+            initials,
+        )
+        .execute(&mut *transaction)
+        .await?;
+    }
+    transaction.commit().await?;
+    Ok(())
+}
+
+/// Delete the forecaster, their photo is removed with the next blob garbage collection.
+pub async fn delete_forecaster(
+    database: &Database,
+    initials: &ForecasterInitials,
+) -> eyre::Result<()> {
+    sqlx::query!("DELETE FROM forecasters WHERE initials = $1", initials)
+        .execute(database)
+        .await?;
+    Ok(())
+}
+
+pub async fn get_photo(
+    database: &Database,
+    blobs: &BlobStore,
+    initials: &ForecasterInitials,
+) -> eyre::Result<Option<Photo>> {
+    let Some(forecaster) = get_forecaster(database, initials).await? else {
+        return Ok(None);
+    };
+    let (Some(content_type), Some(blob_hash)) =
+        (forecaster.photo_content_type, forecaster.photo_blob_hash)
+    else {
+        return Ok(None);
+    };
+    let data = blobs.read(&blob_hash).await?.wrap_err_with(|| {
+        format!("Blob {blob_hash} for the photo of forecaster {initials} is missing")
+    })?;
+    Ok(Some(Photo { content_type, data }))
+}
+
+#[derive(Serialize)]
+struct ForecasterContext {
+    #[serde(flatten)]
+    forecaster: Forecaster,
+    photo_path: Option<String>,
+}
+
+impl From<Forecaster> for ForecasterContext {
+    fn from(forecaster: Forecaster) -> Self {
+        Self {
+            photo_path: forecaster.photo_path(),
+            forecaster,
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct PageContext {
+    forecasters: Vec<ForecasterContext>,
+}
+
+async fn handler(
+    Extension(templates): Extension<TemplatesWithContext>,
+    Extension(database): Extension<Database>,
+) -> axum::response::Result<Response> {
+    let forecasters = list_forecasters(&database)
+        .await
+        .map_err(map_eyre_error)?
+        .into_iter()
+        .map(ForecasterContext::from)
+        .collect();
+    templates
+        .render("forecasters.html", &PageContext { forecasters })
+        .map_err(map_eyre_error)
+        .map_err(Into::into)
+}
+
+async fn photo_handler(
+    Path(initials): Path<ForecasterInitials>,
+    Extension(database): Extension<Database>,
+    State(state): State<AppState>,
+) -> axum::response::Result<Response> {
+    match get_photo(&database, &state.blobs, &initials)
+        .await
+        .map_err(map_eyre_error)?
+    {
+        Some(photo) => Ok(photo_response(photo).map_err(map_eyre_error)?),
+        None => Ok(StatusCode::NOT_FOUND.into_response()),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::ForecasterInitials;
+
+    #[test]
+    fn test_forecaster_initials_is_valid() {
+        assert!(ForecasterInitials::from("LF".to_owned()).is_valid());
+        assert!(ForecasterInitials::from("AB2".to_owned()).is_valid());
+        assert!(!ForecasterInitials::from(String::new()).is_valid());
+        assert!(!ForecasterInitials::from("L_F".to_owned()).is_valid());
+        assert!(!ForecasterInitials::from("L.F".to_owned()).is_valid());
+    }
+}
@@ -0,0 +1,269 @@
+//! A compact summary image of a forecast, suitable for Open Graph previews and posting to social
+//! media.
+
+use std::fmt::Write;
+
+use axum::{
+    extract::State,
+    http::{header, HeaderMap},
+    response::{IntoResponse, Response},
+    Extension,
+};
+use axum_extra::routing::TypedPath;
+use eyre::Context;
+use forecast_spreadsheet::HazardRatingKind;
+use http::StatusCode;
+use serde::Deserialize;
+
+use crate::{
+    database::Database,
+    diagrams::{self, elevation_hazard::HazardLevel, escape_xml, problem_icon},
+    error::{map_eyre_error, map_std_error},
+    google_drive,
+    i18n::{self, I18nLoader},
+    state::AppState,
+};
+
+use super::{get_forecast_data, variant_id, ForecastData, RequestedForecastData};
+
+#[derive(Deserialize, TypedPath)]
+#[typed_path("/forecasts/{file_name}/card.png")]
+pub struct ForecastCardPath {
+    pub file_name: String,
+}
+
+const WIDTH: u32 = 1200;
+const HEIGHT: u32 = 630;
+const MARGIN: u32 = 48;
+const HEADER_HEIGHT: u32 = 150;
+const BAND_ROW_HEIGHT: u32 = 80;
+const PROBLEM_ICON_SIZE: u32 = 130;
+
+fn hazard_block(
+    svg: &mut String,
+    x: u32,
+    y: u32,
+    width: u32,
+    height: u32,
+    level: HazardLevel,
+    i18n: &I18nLoader,
+) {
+    let colour = level.colour_hex();
+    let text_colour = match level {
+        HazardLevel::Extreme => "#ffffff",
+        _ => "#000000",
+    };
+    let level_id = level.id();
+    let label = escape_xml(&i18n.get(&format!("avalanche-hazard-{level_id}")));
+    let text_x = x + width / 2;
+    let text_y = y + height / 2 + 12;
+    write!(
+        svg,
+        r##"<rect x="{x}" y="{y}" width="{width}" height="{height}" rx="8" style="fill:{colour};stroke:#000000;stroke-width:2"/>
+<text x="{text_x}" y="{text_y}" text-anchor="middle" font-family="Noto Sans" font-size="34" fill="{text_colour}">{label}</text>
+"##
+    )
+    .expect("Writing to String should not fail");
+}
+
+/// Generate the SVG for the forecast card.
+pub fn generate_svg(forecast: &forecast_spreadsheet::Forecast, i18n: &I18nLoader) -> String {
+    let area_id = forecast.area.to_lowercase();
+    let area = escape_xml(&i18n::message_or(
+        i18n,
+        &format!("forecast-area-{area_id}"),
+        &forecast.area,
+    ));
+    let heading = escape_xml(&i18n.get("avalanche-forecast-heading"));
+    let time = escape_xml(&i18n::format_time(forecast.time, i18n));
+
+    let mut svg = String::new();
+    write!(
+        svg,
+        r##"<?xml version="1.0" encoding="UTF-8" standalone="no"?>
+<svg width="{WIDTH}" height="{HEIGHT}" viewBox="0 0 {WIDTH} {HEIGHT}" version="1.1" xmlns="http://www.w3.org/2000/svg">
+<rect x="0" y="0" width="{WIDTH}" height="{HEIGHT}" style="fill:#ffffff"/>
+<rect x="0" y="0" width="{WIDTH}" height="{HEADER_HEIGHT}" style="fill:#1e3a5f"/>
+<text x="{MARGIN}" y="70" font-family="Noto Sans" font-size="48" fill="#ffffff">{area} {heading}</text>
+<text x="{MARGIN}" y="120" font-family="Noto Sans" font-size="32" fill="#dbe4ee">{time}</text>
+"##
+    )
+    .expect("Writing to String should not fail");
+
+    let content_top = HEADER_HEIGHT + MARGIN / 2;
+    let column_width = (WIDTH - 3 * MARGIN) / 2;
+
+    // Left column: hazard rating for each elevation band, falling back to the overall rating
+    // when there are no elevation specific ratings.
+    let band_ratings: Vec<(String, HazardLevel)> = forecast
+        .elevation_bands
+        .keys()
+        .filter_map(|band| {
+            let rating = forecast
+                .hazard_ratings
+                .get(&HazardRatingKind::ElevationSpecific(band.clone()))?;
+            let level = rating
+                .value
+                .map(HazardLevel::from)
+                .unwrap_or(HazardLevel::NoRating);
+            let band_id: &str = band;
+            let label = i18n::message_or(i18n, &format!("elevation-band-{band_id}"), band_id);
+            Some((label, level))
+        })
+        .collect();
+
+    if band_ratings.is_empty() {
+        let level = forecast
+            .hazard_ratings
+            .get(&HazardRatingKind::Overall)
+            .and_then(|rating| rating.value)
+            .map(HazardLevel::from)
+            .unwrap_or(HazardLevel::NoRating);
+        hazard_block(
+            &mut svg,
+            MARGIN,
+            content_top,
+            column_width,
+            BAND_ROW_HEIGHT * 2,
+            level,
+            i18n,
+        );
+    } else {
+        let label_width = column_width / 2;
+        for (i, (label, level)) in band_ratings.into_iter().enumerate() {
+            let y = content_top + i as u32 * (BAND_ROW_HEIGHT + 12);
+            let label = escape_xml(&label);
+            let text_y = y + BAND_ROW_HEIGHT / 2 + 12;
+            writeln!(
+                svg,
+                r##"<text x="{MARGIN}" y="{text_y}" font-family="Noto Sans" font-size="30" fill="#000000">{label}</text>"##
+            )
+            .expect("Writing to String should not fail");
+            hazard_block(
+                &mut svg,
+                MARGIN + label_width,
+                y,
+                column_width - label_width,
+                BAND_ROW_HEIGHT,
+                level,
+                i18n,
+            );
+        }
+    }
+
+    // Right column: the avalanche problems.
+    let problems_x = 2 * MARGIN + column_width;
+    if !forecast.avalanche_problems.is_empty() {
+        let problems_heading = escape_xml(&i18n.get("avalanche-problems-heading"));
+        let heading_y = content_top + 30;
+        writeln!(
+            svg,
+            r##"<text x="{problems_x}" y="{heading_y}" font-family="Noto Sans" font-size="30" fill="#000000">{problems_heading}</text>"##
+        )
+        .expect("Writing to String should not fail");
+    }
+    // Up to four problems in a two by two grid, each icon with its name below.
+    let cell_width = column_width / 2;
+    for (i, problem) in forecast.avalanche_problems.iter().take(4).enumerate() {
+        let (column, row) = (i as u32 % 2, i as u32 / 2);
+        let cell_x = problems_x + column * cell_width;
+        let icon_x = cell_x + (cell_width - PROBLEM_ICON_SIZE) / 2;
+        let icon_y = content_top + 50 + row * (PROBLEM_ICON_SIZE + 44);
+        let text_x = cell_x + cell_width / 2;
+        let text_y = icon_y + PROBLEM_ICON_SIZE + 28;
+        let icon = problem_icon::icon_elements(problem.kind, problem_icon::DEFAULT_COLOUR);
+        let kind_id = variant_id(&problem.kind);
+        let label = escape_xml(&i18n.get(&format!("problem-type-{kind_id}")));
+        write!(
+            svg,
+            r##"<svg x="{icon_x}" y="{icon_y}" width="{PROBLEM_ICON_SIZE}" height="{PROBLEM_ICON_SIZE}" viewBox="0 0 100 100">
+{icon}</svg>
+<text x="{text_x}" y="{text_y}" text-anchor="middle" font-family="Noto Sans" font-size="24" fill="#1e3a5f">{label}</text>
+"##
+        )
+        .expect("Writing to String should not fail");
+    }
+
+    svg.push_str("</svg>\n");
+    svg
+}
+
+/// Handler for the forecast card PNG image.
+pub async fn handler(
+    ForecastCardPath { file_name }: ForecastCardPath,
+    State(state): State<AppState>,
+    Extension(database): Extension<Database>,
+    Extension(i18n): Extension<I18nLoader>,
+) -> axum::response::Result<Response> {
+    // Check that file exists in published folder, and not attempting to access a file outside
+    // that.
+    let file_list = state
+        .published_files
+        .list_files()
+        .await
+        .map_err(map_eyre_error)?;
+    let file_metadata = match google_drive::get_file_in_list(&file_name, &file_list) {
+        Some(file_metadata) if file_metadata.has_forecast_data() => file_metadata,
+        _ => return Ok(StatusCode::NOT_FOUND.into_response()),
+    };
+
+    let forecast = match get_forecast_data(
+        file_metadata,
+        RequestedForecastData::Forecast,
+        &state.client,
+        &database,
+        &state.blobs,
+        &state.options.google_drive,
+        state.forecast_spreadsheet_schemas,
+    )
+    .await
+    .map_err(map_eyre_error)?
+    {
+        ForecastData::Forecast(forecast) => forecast,
+        ForecastData::File(_) => {
+            return Err(map_eyre_error(eyre::eyre!("Expected ForecastData::Forecast")).into())
+        }
+    };
+
+    let svg = generate_svg(&forecast, &i18n);
+    let png_data = tokio::task::spawn_blocking(move || {
+        diagrams::render_png(&svg).wrap_err("Error generating forecast card png")
+    })
+    .await
+    .map_err(map_std_error)?
+    .map_err(map_eyre_error)?;
+
+    let mut headers = HeaderMap::new();
+    headers.insert(header::CONTENT_TYPE, "image/png".parse().unwrap());
+    Ok((headers, png_data).into_response())
+}
+
+#[cfg(test)]
+mod test {
+    use std::path::Path;
+
+    use crate::i18n::test_loader;
+
+    use super::generate_svg;
+
+    #[test]
+    fn test_generate_svg() {
+        let fixtures = Path::new(env!("CARGO_MANIFEST_DIR")).join("forecast-spreadsheet/fixtures");
+        let spreadsheet_bytes =
+            std::fs::read(fixtures.join("forecasts/Gudauri_2023_02_07T19 00_LS.xlsx")).unwrap();
+        let options: forecast_spreadsheet::options::Options = serde_json::from_str(
+            &std::fs::read_to_string(fixtures.join("options/options.gudauri.0.3.1.json")).unwrap(),
+        )
+        .unwrap();
+        let forecast =
+            forecast_spreadsheet::parse_excel_spreadsheet(&spreadsheet_bytes, &options).unwrap();
+        assert!(!forecast.avalanche_problems.is_empty());
+
+        let svg = generate_svg(&forecast, &test_loader());
+        // Each problem is drawn with its icon.
+        let icons = svg.matches(r#"viewBox="0 0 100 100""#).count();
+        assert_eq!(icons, forecast.avalanche_problems.len().min(4));
+        let png = crate::diagrams::render_png(&svg).unwrap();
+        assert!(png.starts_with(b"\x89PNG"));
+    }
+}
@@ -0,0 +1,379 @@
+//! Comparison between two archived forecasts for an area, showing how the hazard ratings and
+//! avalanche problems changed, for forecaster briefings and education. The forecast compared for
+//! each date is the latest one issued on or before that date (in the area's time zone).
+
+use std::collections::HashSet;
+
+use axum::{
+    extract::{Query, State},
+    response::{IntoResponse, Response},
+    Extension, Json,
+};
+use axum_extra::routing::TypedPath;
+use forecast_spreadsheet::{
+    AreaId, Distribution, Forecast, HazardRatingKind, HazardRatingValue, ProblemKind, Sensitivity,
+    Size,
+};
+use http::StatusCode;
+use serde::{Deserialize, Serialize};
+use time::{Date, OffsetDateTime};
+use time_tz::{Offset, TimeZone};
+
+use crate::{
+    database::Database,
+    error::{map_eyre_error, map_std_error},
+    i18n::{self, I18nLoader},
+    state::AppState,
+    templates::TemplatesWithContext,
+};
+
+use super::ForecastSpreadsheetSchema;
+
+#[derive(Deserialize, TypedPath)]
+#[typed_path("/forecasts/compare")]
+pub struct ForecastComparePath;
+
+#[derive(Deserialize, TypedPath)]
+#[typed_path("/forecasts/compare.json")]
+pub struct ForecastCompareJsonPath;
+
+#[derive(Deserialize)]
+pub struct CompareQuery {
+    /// Id of the forecast area.
+    area: String,
+    /// Date of the earlier forecast, e.g. `2024-01-20`.
+    from: String,
+    /// Date of the later forecast, e.g. `2024-01-24`.
+    to: String,
+}
+
+/// The change in a value between the forecasts.
+#[derive(Serialize, Debug, PartialEq)]
+pub struct ValueChange<T> {
+    pub from: Option<T>,
+    pub to: Option<T>,
+}
+
+impl<T: PartialEq> ValueChange<T> {
+    /// `None` if the value didn't change.
+    fn new(from: Option<T>, to: Option<T>) -> Option<Self> {
+        (from != to).then_some(Self { from, to })
+    }
+}
+
+#[derive(Serialize, Debug, PartialEq)]
+pub struct RatingChange {
+    /// `overall`, or the id of the elevation band.
+    pub kind: HazardRatingKind,
+    pub from: Option<HazardRatingValue>,
+    pub to: Option<HazardRatingValue>,
+    /// Number of levels the rating increased by (negative when it decreased), `None` unless both
+    /// forecasts have a rating.
+    pub change: Option<i8>,
+}
+
+#[derive(Serialize, Debug, PartialEq)]
+pub struct ProblemChange {
+    pub kind: ProblemKind,
+    pub size: Option<ValueChange<Size>>,
+    pub sensitivity: Option<ValueChange<Sensitivity>>,
+    pub distribution: Option<ValueChange<Distribution>>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct ForecastComparison {
+    pub area: AreaId,
+    #[serde(with = "time::serde::rfc3339")]
+    pub from_time: OffsetDateTime,
+    #[serde(with = "time::serde::rfc3339")]
+    pub to_time: OffsetDateTime,
+    pub hazard_ratings: Vec<RatingChange>,
+    /// Problems in the later forecast which weren't in the earlier one.
+    pub problems_added: Vec<ProblemKind>,
+    /// Problems in the earlier forecast which aren't in the later one.
+    pub problems_removed: Vec<ProblemKind>,
+    /// Problems in both forecasts, with the changes to their characteristics.
+    pub problems_continued: Vec<ProblemChange>,
+}
+
+fn rating_level(value: Option<HazardRatingValue>) -> Option<i8> {
+    match value? {
+        HazardRatingValue::NoRating => None,
+        value => Some(value as i8),
+    }
+}
+
+pub fn compare(from: &Forecast, to: &Forecast) -> ForecastComparison {
+    let mut kinds: Vec<&HazardRatingKind> = from.hazard_ratings.keys().collect();
+    kinds.extend(
+        to.hazard_ratings
+            .keys()
+            .filter(|kind| !from.hazard_ratings.contains_key(*kind)),
+    );
+    let hazard_ratings = kinds
+        .into_iter()
+        .map(|kind| {
+            let from = from
+                .hazard_ratings
+                .get(kind)
+                .and_then(|rating| rating.value);
+            let to = to.hazard_ratings.get(kind).and_then(|rating| rating.value);
+            RatingChange {
+                kind: kind.clone(),
+                from,
+                to,
+                change: Option::zip(rating_level(from), rating_level(to)).map(|(a, b)| b - a),
+            }
+        })
+        .collect();
+
+    let from_kinds: HashSet<ProblemKind> = from.avalanche_problems.iter().map(|p| p.kind).collect();
+    let to_kinds: HashSet<ProblemKind> = to.avalanche_problems.iter().map(|p| p.kind).collect();
+    let problems_added = to
+        .avalanche_problems
+        .iter()
+        .map(|problem| problem.kind)
+        .filter(|kind| !from_kinds.contains(kind))
+        .collect();
+    let problems_removed = from
+        .avalanche_problems
+        .iter()
+        .map(|problem| problem.kind)
+        .filter(|kind| !to_kinds.contains(kind))
+        .collect();
+    let problems_continued = to
+        .avalanche_problems
+        .iter()
+        .filter_map(|to_problem| {
+            let from_problem = from
+                .avalanche_problems
+                .iter()
+                .find(|problem| problem.kind == to_problem.kind)?;
+            Some(ProblemChange {
+                kind: to_problem.kind,
+                size: ValueChange::new(from_problem.size, to_problem.size),
+                sensitivity: ValueChange::new(from_problem.sensitivity, to_problem.sensitivity),
+                distribution: ValueChange::new(from_problem.distribution, to_problem.distribution),
+            })
+        })
+        .collect();
+
+    ForecastComparison {
+        area: to.area.clone(),
+        from_time: from.time,
+        to_time: to.time,
+        hazard_ratings,
+        problems_added,
+        problems_removed,
+        problems_continued,
+    }
+}
+
+/// The latest of the `forecasts` issued on or before the `date` in the `time_zone`.
+fn select_forecast<'a>(
+    forecasts: &'a [Forecast],
+    date: Date,
+    time_zone: &time_tz::Tz,
+) -> Option<&'a Forecast> {
+    forecasts
+        .iter()
+        .filter(|forecast| {
+            let offset = time_zone.get_offset_utc(&forecast.time).to_utc();
+            forecast.time.to_offset(offset).date() <= date
+        })
+        .max_by_key(|forecast| forecast.time)
+}
+
+enum CompareError {
+    BadRequest(String),
+    NotFound(String),
+}
+
+impl IntoResponse for CompareError {
+    fn into_response(self) -> Response {
+        match self {
+            Self::BadRequest(message) => (StatusCode::BAD_REQUEST, message).into_response(),
+            Self::NotFound(message) => (StatusCode::NOT_FOUND, message).into_response(),
+        }
+    }
+}
+
+async fn load_comparison(
+    query: &CompareQuery,
+    database: &Database,
+    schema: &ForecastSpreadsheetSchema,
+) -> axum::response::Result<ForecastComparison> {
+    let area = AreaId::from(query.area.clone());
+    let Some(definition) = schema.area_definitions.get(&area) else {
+        return Err(CompareError::NotFound(format!("Unknown area {:?}", query.area)).into());
+    };
+    let format = time::macros::format_description!("[year]-[month]-[day]");
+    let parse_date = |value: &str| {
+        Date::parse(value, &format)
+            .map_err(|_| CompareError::BadRequest(format!("Invalid date {value:?}")))
+    };
+    let from_date = parse_date(&query.from)?;
+    let to_date = parse_date(&query.to)?;
+
+    let forecasts: Vec<Forecast> = sqlx::query_scalar!(
+        r#"SELECT parsed_forecast as "parsed_forecast!: sqlx::types::Json<Forecast>" FROM forecast_files WHERE parsed_forecast IS NOT NULL AND json_extract(parsed_forecast, "$.area") = $1"#,
+        query.area
+    )
+    .fetch_all(database)
+    .await
+    .map_err(map_std_error)?
+    .into_iter()
+    .map(|forecast| forecast.0)
+    .collect();
+
+    let select = |date: Date| {
+        select_forecast(&forecasts, date, definition.time_zone)
+            .ok_or_else(|| CompareError::NotFound(format!("No forecast on or before {date}")))
+    };
+    let from = select(from_date)?;
+    let to = select(to_date)?;
+    Ok(compare(from, to))
+}
+
+pub async fn json_handler(
+    _: ForecastCompareJsonPath,
+    Query(query): Query<CompareQuery>,
+    State(state): State<AppState>,
+) -> axum::response::Result<Json<ForecastComparison>> {
+    Ok(Json(
+        load_comparison(&query, &state.database, state.forecast_spreadsheet_schema).await?,
+    ))
+}
+
+#[derive(Serialize)]
+struct CompareContext {
+    area_name: String,
+    formatted_from_time: String,
+    formatted_to_time: String,
+    json_url: String,
+    comparison: ForecastComparison,
+}
+
+pub async fn handler(
+    _: ForecastComparePath,
+    Query(query): Query<CompareQuery>,
+    State(state): State<AppState>,
+    Extension(templates): Extension<TemplatesWithContext>,
+    Extension(i18n): Extension<I18nLoader>,
+) -> axum::response::Result<Response> {
+    let comparison =
+        load_comparison(&query, &state.database, state.forecast_spreadsheet_schema).await?;
+    let json_url = format!(
+        "{}?{}",
+        ForecastCompareJsonPath.to_uri(),
+        serde_urlencoded::to_string([
+            ("area", &query.area),
+            ("from", &query.from),
+            ("to", &query.to),
+        ])
+        .map_err(map_std_error)?
+    );
+    let context = CompareContext {
+        area_name: i18n::message_or(
+            &i18n,
+            &format!("forecast-area-{}", comparison.area.to_lowercase()),
+            &comparison.area,
+        ),
+        formatted_from_time: i18n::format_time(comparison.from_time, &i18n),
+        formatted_to_time: i18n::format_time(comparison.to_time, &i18n),
+        json_url,
+        comparison,
+    };
+    Ok(templates
+        .render("forecast_compare.html", &context)
+        .map_err(map_eyre_error)?)
+}
+
+#[cfg(test)]
+mod test {
+    use forecast_spreadsheet::{
+        Forecast, HazardRatingKind, HazardRatingValue, ProblemKind, Sensitivity,
+    };
+    use time::macros::{date, datetime};
+
+    use super::{compare, select_forecast, ValueChange};
+
+    fn forecast(time: &str, overall: &str, problems: serde_json::Value) -> Forecast {
+        serde_json::from_value(serde_json::json!({
+            "template_version": { "major": 0, "minor": 3, "patch": 1 },
+            "area": "gudauri",
+            "forecaster": { "name": "Luke Frisken", "organisation": null },
+            "time": time,
+            "valid_for": 86400,
+            "hazard_ratings": { "overall": { "value": overall } },
+            "avalanche_problems": problems,
+            "elevation_bands": {}
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_compare() {
+        let from = forecast(
+            "2024-01-20T04:00:00Z",
+            "moderate",
+            serde_json::json!([
+                { "kind": "wind-slab", "aspect_elevation": {}, "sensitivity": "stubborn", "size": 2 },
+                { "kind": "loose-dry", "aspect_elevation": {} },
+            ]),
+        );
+        let to = forecast(
+            "2024-01-24T04:00:00Z",
+            "considerable",
+            serde_json::json!([
+                { "kind": "wind-slab", "aspect_elevation": {}, "sensitivity": "reactive", "size": 2 },
+                { "kind": "persistent-slab", "aspect_elevation": {} },
+            ]),
+        );
+        let comparison = compare(&from, &to);
+        assert_eq!(comparison.hazard_ratings.len(), 1);
+        let overall = &comparison.hazard_ratings[0];
+        assert_eq!(overall.kind, HazardRatingKind::Overall);
+        assert_eq!(overall.from, Some(HazardRatingValue::Moderate));
+        assert_eq!(overall.to, Some(HazardRatingValue::Considerable));
+        assert_eq!(overall.change, Some(1));
+        assert!(matches!(
+            comparison.problems_added[..],
+            [ProblemKind::PersistentSlab]
+        ));
+        assert!(matches!(
+            comparison.problems_removed[..],
+            [ProblemKind::LooseDry]
+        ));
+        assert_eq!(comparison.problems_continued.len(), 1);
+        let wind_slab = &comparison.problems_continued[0];
+        assert_eq!(wind_slab.size, None);
+        assert_eq!(
+            wind_slab.sensitivity,
+            Some(ValueChange {
+                from: Some(Sensitivity::Stubborn),
+                to: Some(Sensitivity::Reactive),
+            })
+        );
+    }
+
+    #[test]
+    fn test_select_forecast() {
+        let forecasts = [
+            forecast("2024-01-20T04:00:00Z", "low", serde_json::json!([])),
+            // 2024-01-22 in Tbilisi (UTC+4).
+            forecast("2024-01-21T21:00:00Z", "low", serde_json::json!([])),
+        ];
+        let tz = time_tz::timezones::get_by_name("Asia/Tbilisi").unwrap();
+        let time = |date| select_forecast(&forecasts, date, tz).map(|forecast| forecast.time);
+        assert_eq!(time(date!(2024 - 01 - 19)), None);
+        assert_eq!(
+            time(date!(2024 - 01 - 21)),
+            Some(datetime!(2024-01-20 04:00 UTC))
+        );
+        assert_eq!(
+            time(date!(2024 - 01 - 22)),
+            Some(datetime!(2024-01-21 21:00 UTC))
+        );
+    }
+}
@@ -1,6 +1,7 @@
 use std::{
     collections::{HashMap, HashSet},
     ffi::OsStr,
+    path::PathBuf,
 };
 
 use axum::{
@@ -9,6 +10,7 @@ use axum::{
     Extension, Json,
 };
 use axum_extra::routing::TypedPath;
+use bytes::Bytes;
 use eyre::{Context, ContextCompat};
 use forecast_spreadsheet::{
     options::AreaDefinition, AreaId, Aspect, AspectElevation, Confidence, Distribution,
@@ -16,48 +18,66 @@ use forecast_spreadsheet::{
     TimeOfDay, Trend,
 };
 use headers::{ContentType, HeaderMapExt};
-use http::{header::CONTENT_TYPE, HeaderValue, StatusCode};
+use i18n_embed::LanguageLoader;
+use http::StatusCode;
 use indexmap::IndexMap;
-use once_cell::sync::Lazy;
-use secrecy::SecretString;
 use serde::{Deserialize, Serialize};
 use time::{OffsetDateTime, PrimitiveDateTime};
 use time_tz::{Offset, TimeZone};
+use tower::ServiceExt;
+use tower_http::services::ServeFile;
 use tracing::instrument;
 use unic_langid::LanguageIdentifier;
 use utils::serde::duration_seconds;
 
 use crate::{
-    database::Database,
+    database::{
+        blob::{BlobHash, BlobStore},
+        Database,
+    },
     diagrams,
     error::map_eyre_error,
+    forecast_areas::{terrain, ForecastAreaId},
+    forecasters::{self, ForecasterInitials},
     google_drive::{self, ListFileMetadata},
     i18n::{self, I18nLoader},
     index::ForecastFileView,
-    options::Map,
+    machine_translation::{self, MachineTranslated},
+    options::{GoogleDrive, Map},
+    page_metadata::PageMetadata,
+    short_links,
     state::AppState,
     templates::{render, TemplatesWithContext},
     types,
     user_preferences::UserPreferences,
 };
 
+pub mod card;
+pub mod compare;
+pub mod pdf;
 pub mod probability;
+pub mod published;
 
 use probability::Probability;
+use published::PublishedFiles;
 
+/// A cached forecast file.
 #[derive(Clone)]
 pub struct ForecastFile {
     pub google_drive_id: String,
     pub last_modified: types::Time,
-    pub file_blob: Vec<u8>,
+    /// The blob containing the file fetched from Google Drive, see [`BlobStore`].
+    pub blob_hash: Option<BlobHash>,
     pub parsed_forecast: Option<forecast_spreadsheet::Forecast>,
     pub schema_version: Option<forecast_spreadsheet::Version>,
 }
 
 pub type ForecastSpreadsheetSchema = forecast_spreadsheet::options::Options;
 
-pub static GUDUAURI_FORECAST_SCHEMA: Lazy<ForecastSpreadsheetSchema> =
-    Lazy::new(|| serde_json::from_str(include_str!("./schemas/gudauri.0.3.1.json")).unwrap());
+pub type ForecastSpreadsheetSchemas = forecast_spreadsheet::registry::SchemaRegistry;
+
+/// Source of the default schema for Gudauri forecast spreadsheets.
+pub const GUDAURI_FORECAST_SCHEMA_JSON: &str = include_str!("./schemas/gudauri.0.3.1.json");
 
 #[derive(Serialize, PartialEq, Eq, Clone)]
 pub struct ForecastDetails {
@@ -89,6 +109,10 @@ fn parse_forecast_name_impl(
     area_name_map: &HashMap<String, AreaId>,
     area_definitions: &IndexMap<AreaId, AreaDefinition>,
 ) -> eyre::Result<ForecastFileDetails> {
+    let file_name = [".ods", ".xlsx", ".caaml"]
+        .iter()
+        .find_map(|extension| file_name.strip_suffix(extension))
+        .unwrap_or(file_name);
     let mut name_parts = file_name.split('.');
     let details = name_parts
         .next()
@@ -140,6 +164,44 @@ fn parse_forecast_name_impl(
     })
 }
 
+/// The name of a forecast file following the naming convention read by [`parse_forecast_name`],
+/// e.g. `Gudauri_2023-01-24T17:00_LF.xlsx`, using the forecaster's initials.
+pub fn canonical_forecast_name(
+    forecast: &forecast_spreadsheet::Forecast,
+    forecast_schema: &ForecastSpreadsheetSchema,
+    extension: &str,
+) -> eyre::Result<String> {
+    let area = forecast_schema
+        .area
+        .map
+        .iter()
+        .filter(|(_, area_id)| **area_id == forecast.area)
+        .map(|(area, _)| area)
+        .min()
+        .wrap_err_with(|| format!("Cannot find area name for {}", forecast.area))?;
+    let tz = forecast_schema
+        .area_definitions
+        .get(&forecast.area)
+        .wrap_err_with(|| format!("Cannot find area definition for {}", forecast.area))?
+        .time_zone;
+    let local_time = forecast
+        .time
+        .to_offset(tz.get_offset_utc(&forecast.time).to_utc());
+    let format = time::macros::format_description!("[year]-[month]-[day]T[hour]:[minute]");
+    let time = local_time.format(&format)?;
+    let initials: String = forecast
+        .forecaster
+        .name
+        .split_whitespace()
+        .filter_map(|word| word.chars().next())
+        .flat_map(char::to_uppercase)
+        .collect();
+    if initials.is_empty() {
+        eyre::bail!("Forecaster name is empty");
+    }
+    Ok(format!("{area}_{time}_{initials}.{extension}"))
+}
+
 #[derive(Deserialize, TypedPath)]
 #[typed_path("/forecasts/{file_name}")]
 pub struct ForecastsFilePath {
@@ -155,17 +217,20 @@ pub async fn handler(
     Extension(preferences): Extension<UserPreferences>,
     request: axum::extract::Request,
 ) -> axum::response::Result<Response> {
-    let requested_content_type = request.headers().typed_get::<headers::ContentType>();
     Ok(handler_impl(
-        requested_content_type,
+        request,
         file_name,
-        &state.options,
+        state.options,
+        &state.reloadable_options.load_full(),
         &state.client,
+        &state.published_files,
         &database,
+        &state.blobs,
         &templates,
         &i18n,
         &preferences,
         state.forecast_spreadsheet_schema,
+        state.forecast_spreadsheet_schemas,
     )
     .await
     .map_err(map_eyre_error)?)
@@ -190,20 +255,80 @@ pub struct Forecast {
     pub hazard_ratings: IndexMap<HazardRatingKind, HazardRating>,
     pub avalanche_problems: Vec<AvalancheProblem>,
     pub elevation_bands: IndexMap<ElevationBandId, ElevationRange>,
+    /// See [`ForecastContext::with_machine_translations`].
+    #[serde(default)]
+    pub machine_translated: MachineTranslated,
 }
 
 impl Forecast {
     pub fn is_current(&self) -> bool {
         let valid_until = self.time + self.valid_for;
 
-        if time::OffsetDateTime::now_utc() <= valid_until {
-            true
+        time::OffsetDateTime::now_utc() <= valid_until
+    }
+
+    /// The validity of the forecast at `now`, a current forecast which expires within
+    /// `expiring_soon` is [`ForecastValidity::ExpiringSoon`]. Doesn't consider whether the
+    /// forecast has been superseded, see [`ForecastContext::with_superseded`].
+    pub fn validity(&self, now: OffsetDateTime, expiring_soon: time::Duration) -> ForecastValidity {
+        let valid_until = self.time + self.valid_for;
+        if now > valid_until {
+            ForecastValidity::Expired
+        } else if valid_until - now <= expiring_soon {
+            ForecastValidity::ExpiringSoon
         } else {
-            false
+            ForecastValidity::Current
+        }
+    }
+
+    /// The union of the aspects and elevation bands of all the avalanche problems, showing where
+    /// the danger lies. Elevation bands without any affected aspects are omitted, and the rest
+    /// are in the order of [`Forecast::elevation_bands`].
+    pub fn combined_aspect_elevation(&self) -> IndexMap<ElevationBandId, AspectElevation> {
+        let mut combined: IndexMap<ElevationBandId, AspectElevation> = IndexMap::new();
+        for (band, aspect_elevation) in self
+            .avalanche_problems
+            .iter()
+            .flat_map(|problem| &problem.aspect_elevation)
+        {
+            combined
+                .entry(band.clone())
+                .or_insert_with(|| AspectElevation {
+                    aspects: Default::default(),
+                })
+                .aspects
+                .extend(aspect_elevation.aspects.iter().copied());
+        }
+        combined.retain(|_, aspect_elevation| !aspect_elevation.aspects.is_empty());
+        let band_index = |band: &ElevationBandId| {
+            self.elevation_bands
+                .get_index_of(band)
+                .unwrap_or(usize::MAX)
+        };
+        combined.sort_by(|a, _, b, _| band_index(a).cmp(&band_index(b)));
+        combined
+    }
+
+    /// Path of the compact aspect elevation diagram for the
+    /// [`Forecast::combined_aspect_elevation`], `None` if there are no avalanche problems.
+    pub fn combined_aspect_elevation_chart(&self) -> eyre::Result<Option<String>> {
+        if self.avalanche_problems.is_empty() {
+            return Ok(None);
         }
+        let elevation_band_ids: Vec<ElevationBandId> =
+            self.elevation_bands.keys().cloned().collect();
+        let query =
+            diagram_aspect_elevation(&self.combined_aspect_elevation(), &elevation_band_ids)
+                .into_query();
+        let query_string = serde_urlencoded::to_string(query)?;
+        Ok(Some(format!(
+            "/diagrams/aspect_elevation_compact.svg?{query_string}"
+        )))
     }
 
     pub fn try_new(value: forecast_spreadsheet::Forecast) -> eyre::Result<Self> {
+        let elevation_band_ids: Vec<ElevationBandId> =
+            value.elevation_bands.keys().cloned().collect();
         Ok(Self {
             area: value.area,
             forecaster: value.forecaster,
@@ -217,17 +342,51 @@ impl Forecast {
             avalanche_problems: value
                 .avalanche_problems
                 .into_iter()
-                .map(TryInto::try_into)
+                .map(|problem| AvalancheProblem::try_new(problem, &elevation_band_ids))
                 .collect::<eyre::Result<_>>()?,
             elevation_bands: value
                 .elevation_bands
                 .into_iter()
                 .map(|(k, v)| (k, v.into()))
                 .collect(),
+            machine_translated: MachineTranslated::default(),
         })
     }
 }
 
+/// Whether a forecast can still be relied upon.
+#[derive(Debug, Serialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum ForecastValidity {
+    /// The forecast is valid.
+    Current,
+    /// The forecast is valid, but expires within
+    /// [`crate::options::ForecastPublication::expiring_soon_minutes`].
+    ExpiringSoon,
+    /// The forecast's validity period has ended.
+    Expired,
+    /// A newer forecast has been published for the same area.
+    Superseded,
+}
+
+/// Whether a forecast for the same area as the forecast published as `file_name`, but with a later
+/// time, has been published in `files`.
+pub fn is_superseded(
+    file_name: &str,
+    files: &[ListFileMetadata],
+    forecast_schema: &ForecastSpreadsheetSchema,
+) -> bool {
+    let Ok(details) = parse_forecast_name(file_name, forecast_schema) else {
+        return false;
+    };
+    files.iter().any(|file| {
+        parse_forecast_name(&file.name, forecast_schema).is_ok_and(|other| {
+            other.forecast.area == details.forecast.area
+                && other.forecast.time > details.forecast.time
+        })
+    })
+}
+
 #[derive(Debug, Serialize, Clone)]
 pub struct ElevationRange {
     pub upper: Option<i64>,
@@ -253,29 +412,334 @@ pub struct ForecastContext {
     pub formatted_valid_until: String,
     pub map: Map,
     pub is_current: bool,
+    pub validity: ForecastValidity,
+    /// When the forecast expires, for displaying a countdown.
+    #[serde(with = "time::serde::rfc3339")]
+    pub valid_until: OffsetDateTime,
+    /// Seconds until the forecast expires, negative if it has already expired.
+    pub seconds_until_expiry: i64,
     pub external_weather: crate::weather::Context,
+    pub weather_model: Option<crate::weather_forecast::WeatherModelSummary>,
+    pub page_metadata: Option<PageMetadata>,
+    /// See [`ForecastContext::with_short_link`].
+    pub short_link: Option<ShortLinkContext>,
+    /// See [`ForecastContext::with_forecaster_profile`].
+    pub forecaster_profile: Option<ForecasterProfileContext>,
+    /// See [`ForecastContext::with_localized_text`].
+    pub localized_text: LocalizedForecastText,
+}
+
+/// One of the forecast's free-text fields in the language selected for the user, see
+/// [`ForecastContext::with_localized_text`].
+#[derive(Serialize, Clone, Debug)]
+pub struct LocalizedText {
+    pub text: String,
+    /// Language of the [`LocalizedText::text`].
+    pub language: String,
+    /// Name of the language, in the user's language.
+    pub language_name: String,
+    /// The text isn't available in the user's language, so it is shown in another language.
+    pub fallback: bool,
+    /// The text was machine translated, see [`ForecastContext::with_machine_translations`].
+    pub machine_translated: bool,
+}
+
+impl LocalizedText {
+    fn new(
+        text: &HashMap<unic_langid::LanguageIdentifier, String>,
+        machine_translated: &HashSet<unic_langid::LanguageIdentifier>,
+        i18n: &I18nLoader,
+        options: &crate::Options,
+        reloadable_options: &crate::options::ReloadableOptions,
+    ) -> Option<Self> {
+        let requested_languages = i18n.current_languages();
+        let (language, text) = i18n::select_translation(
+            text,
+            &requested_languages,
+            &reloadable_options.default_language_order,
+        )?;
+        let fallback = requested_languages
+            .first()
+            .is_some_and(|requested| requested.language != language.language);
+        Some(Self {
+            text: text.to_owned(),
+            language: language.to_string(),
+            language_name: i18n::language_name(language, i18n, &options.languages),
+            fallback,
+            machine_translated: machine_translated.contains(language),
+        })
+    }
+}
+
+/// The free-text fields of the forecast, each `None` if the forecast has no text for it.
+#[derive(Serialize, Clone, Debug, Default)]
+pub struct LocalizedForecastText {
+    pub description: Option<LocalizedText>,
+    pub recent_observations: Option<LocalizedText>,
+    pub forecast_changes: Option<LocalizedText>,
+    pub weather_forecast: Option<LocalizedText>,
+    /// The description of each of the [`Forecast::avalanche_problems`], in the same order.
+    pub avalanche_problems: Vec<Option<LocalizedText>>,
+}
+
+/// The forecaster from the [`crate::forecasters`] directory, identified by the initials in the
+/// forecast file name.
+#[derive(Serialize, Clone, Debug)]
+pub struct ForecasterProfileContext {
+    /// Full name of the forecaster, shown instead of [`Forecast::forecaster`].
+    pub name: String,
+    pub organisation: Option<String>,
+    /// Path of the forecaster on the `/forecasters` page.
+    pub path: String,
+}
+
+/// The [`crate::short_links`] link for a forecast.
+#[derive(Serialize, Clone, Debug)]
+pub struct ShortLinkContext {
+    pub url: String,
+    /// Path of the QR code image for the link.
+    pub qr_code_path: String,
 }
 
 impl ForecastContext {
     pub fn format(
         forecast: Forecast,
         i18n: &I18nLoader,
-        options: &crate::Options,
+        reloadable_options: &crate::options::ReloadableOptions,
         preferences: &UserPreferences,
     ) -> Self {
         let formatted_time = i18n::format_time(forecast.time, i18n);
         let valid_until_time = forecast.time + forecast.valid_for;
         let formatted_valid_until = i18n::format_time(valid_until_time, i18n);
         let is_current = forecast.is_current();
+        let now = OffsetDateTime::now_utc();
+        let validity = forecast.validity(now, reloadable_options.forecast_expiring_soon);
 
         Self {
             forecast,
             formatted_time,
             formatted_valid_until,
-            map: options.map.clone(),
+            map: reloadable_options.map.clone(),
             is_current,
-            external_weather: crate::weather::Context::new(options, preferences),
+            validity,
+            valid_until: valid_until_time,
+            seconds_until_expiry: (valid_until_time - now).whole_seconds(),
+            external_weather: crate::weather::Context::new(reloadable_options, preferences),
+            weather_model: None,
+            page_metadata: None,
+            short_link: None,
+            forecaster_profile: None,
+            localized_text: LocalizedForecastText::default(),
+        }
+    }
+
+    /// Mark the forecast as [`ForecastValidity::Superseded`] if a newer forecast has been
+    /// published for the same area (see [`is_superseded`]).
+    pub fn with_superseded(mut self, superseded: bool) -> Self {
+        if superseded {
+            self.validity = ForecastValidity::Superseded;
+        }
+        self
+    }
+
+    /// Include the cached weather model forecast for the forecast's area.
+    pub async fn with_weather_model(
+        mut self,
+        database: &Database,
+        forecast_schema: &ForecastSpreadsheetSchema,
+    ) -> Self {
+        self.weather_model = crate::weather_forecast::get_area_summary(
+            database,
+            &self.forecast.area,
+            forecast_schema,
+        )
+        .await;
+        self
+    }
+
+    /// Include the [`PageMetadata`] for the page of the forecast published as `file_name`.
+    pub fn with_page_metadata(
+        mut self,
+        file_name: &str,
+        i18n: &I18nLoader,
+        options: &crate::Options,
+        reloadable_options: &crate::options::ReloadableOptions,
+    ) -> Self {
+        self.page_metadata = Some(PageMetadata::forecast(
+            &self.forecast,
+            file_name,
+            i18n,
+            options,
+            reloadable_options,
+        ));
+        self
+    }
+
+    /// Fill in the languages missing from the forecast's free-text sections using machine
+    /// translation, when [`crate::Options::machine_translation`] is configured.
+    pub async fn with_machine_translations(
+        mut self,
+        client: &reqwest::Client,
+        database: &Database,
+        options: &crate::Options,
+        reloadable_options: &crate::options::ReloadableOptions,
+    ) -> Self {
+        let Some(config) = &options.machine_translation else {
+            return self;
+        };
+        let forecast = &mut self.forecast;
+        forecast.machine_translated = MachineTranslated {
+            description: machine_translation::fill_missing(
+                client,
+                database,
+                options,
+                &reloadable_options.default_language_order,
+                config,
+                &mut forecast.description,
+            )
+            .await,
+            recent_observations: machine_translation::fill_missing(
+                client,
+                database,
+                options,
+                &reloadable_options.default_language_order,
+                config,
+                &mut forecast.recent_observations,
+            )
+            .await,
+            weather_forecast: machine_translation::fill_missing(
+                client,
+                database,
+                options,
+                &reloadable_options.default_language_order,
+                config,
+                &mut forecast.weather_forecast,
+            )
+            .await,
+        };
+        self
+    }
+
+    /// Select the language shown for each of the forecast's free-text fields, recording which
+    /// language it is in so that templates can point out text which isn't in the user's
+    /// language. Call after [`ForecastContext::with_machine_translations`] so that the machine
+    /// translated text is taken into account.
+    pub fn with_localized_text(
+        mut self,
+        i18n: &I18nLoader,
+        options: &crate::Options,
+        reloadable_options: &crate::options::ReloadableOptions,
+    ) -> Self {
+        let forecast = &self.forecast;
+        let machine_translated = &forecast.machine_translated;
+        let localize = |text, machine_translated| {
+            LocalizedText::new(text, machine_translated, i18n, options, reloadable_options)
+        };
+        let no_machine_translations = HashSet::new();
+        self.localized_text = LocalizedForecastText {
+            description: localize(&forecast.description, &machine_translated.description),
+            recent_observations: localize(
+                &forecast.recent_observations,
+                &machine_translated.recent_observations,
+            ),
+            forecast_changes: localize(&forecast.forecast_changes, &no_machine_translations),
+            weather_forecast: localize(
+                &forecast.weather_forecast,
+                &machine_translated.weather_forecast,
+            ),
+            avalanche_problems: forecast
+                .avalanche_problems
+                .iter()
+                .map(|problem| localize(&problem.description, &no_machine_translations))
+                .collect(),
+        };
+        self
+    }
+
+    /// Include the short link for the forecast file with `file_name`, if it has one.
+    pub async fn with_short_link(
+        mut self,
+        file_name: &str,
+        options: &crate::Options,
+        database: &Database,
+    ) -> Self {
+        match short_links::get_token(database, file_name).await {
+            Ok(Some(token)) => {
+                self.short_link = short_links::short_url(&options.base_url(), &token).map(|url| {
+                    ShortLinkContext {
+                        url: url.to_string(),
+                        qr_code_path: format!("/f/{token}/qr.png"),
+                    }
+                });
+            }
+            Ok(None) => {}
+            Err(error) => tracing::error!("Error getting short link for {file_name:?}: {error:?}"),
+        }
+        self
+    }
+
+    /// Include the forecaster from the [`crate::forecasters`] directory, if the initials in the
+    /// `file_name` have been added.
+    pub async fn with_forecaster_profile(
+        mut self,
+        file_name: &str,
+        forecast_schema: &ForecastSpreadsheetSchema,
+        database: &Database,
+    ) -> Self {
+        let initials = match parse_forecast_name(file_name, forecast_schema) {
+            Ok(details) => ForecasterInitials::from(details.forecast.forecaster),
+            Err(error) => {
+                tracing::warn!("Error parsing forecaster from {file_name:?}: {error:?}");
+                return self;
+            }
+        };
+        match forecasters::get_forecaster(database, &initials).await {
+            Ok(Some(forecaster)) => {
+                self.forecaster_profile = Some(ForecasterProfileContext {
+                    path: format!("/forecasters#{initials}"),
+                    name: forecaster.name,
+                    organisation: forecaster.organisation,
+                });
+            }
+            Ok(None) => {}
+            Err(error) => tracing::error!("Error getting forecaster {initials}: {error:?}"),
         }
+        self
+    }
+
+    /// Include the percentage of the forecast area's terrain affected by each avalanche problem,
+    /// when a digital elevation model is configured for the area.
+    pub async fn with_terrain_summary(
+        mut self,
+        options: &crate::Options,
+        database: &Database,
+    ) -> Self {
+        let id = ForecastAreaId::from(self.forecast.area.to_string().to_lowercase());
+        let elevation_bands = self
+            .forecast
+            .elevation_bands
+            .iter()
+            .map(|(id, range)| {
+                let range = forecast_spreadsheet::ElevationRange {
+                    upper: range.upper,
+                    lower: range.lower,
+                };
+                (id.clone(), range)
+            })
+            .collect();
+        match terrain::get_area_terrain_summary(options, database, &id, &elevation_bands).await
+        {
+            Ok(Some(summary)) => {
+                for problem in &mut self.forecast.avalanche_problems {
+                    problem.terrain_percent = summary
+                        .affected_fraction(&problem.aspect_elevation)
+                        .map(|fraction| (fraction * 100.0).round() as u8);
+                }
+            }
+            Ok(None) => {}
+            Err(error) => tracing::error!("Error summarising terrain for area {id}: {error:?}"),
+        }
+        self
     }
 }
 
@@ -294,6 +758,20 @@ pub struct AvalancheProblem {
     #[serde(default)]
     pub description: HashMap<unic_langid::LanguageIdentifier, String>,
     pub probability: Option<Probability>,
+    /// Percentage of the forecast area's terrain in the elevation bands and aspects of the
+    /// problem, see [`ForecastContext::with_terrain_summary`].
+    pub terrain_percent: Option<u8>,
+    /// See [`forecast_spreadsheet::AvalancheProblem::is_primary`].
+    pub is_primary: bool,
+}
+
+/// The id of a kebab-case serialized enum variant, e.g. `wind-slab` for [`ProblemKind::WindSlab`].
+/// Useful for constructing localization message ids.
+pub(crate) fn variant_id<T: Serialize>(value: &T) -> String {
+    serde_json::to_value(value)
+        .ok()
+        .and_then(|value| value.as_str().map(ToOwned::to_owned))
+        .unwrap_or_default()
 }
 
 fn into_diagram_aspect(aspect: &Aspect) -> diagrams::aspect_elevation::Aspect {
@@ -309,35 +787,42 @@ fn into_diagram_aspect(aspect: &Aspect) -> diagrams::aspect_elevation::Aspect {
     }
 }
 
-impl TryFrom<forecast_spreadsheet::AvalancheProblem> for AvalancheProblem {
-    type Error = eyre::Error;
-
-    fn try_from(value: forecast_spreadsheet::AvalancheProblem) -> eyre::Result<Self> {
-        let aspect_elevation = value.aspect_elevation;
-
-        fn map_aspects(
-            aspect_elevation: &IndexMap<ElevationBandId, AspectElevation>,
-            elevation_band: &ElevationBandId,
-        ) -> HashSet<diagrams::aspect_elevation::Aspect> {
-            aspect_elevation
-                .get(elevation_band)
-                .map(|aspect_elevation| {
-                    aspect_elevation
-                        .aspects
-                        .iter()
-                        .map(into_diagram_aspect)
-                        .collect::<HashSet<_>>()
-                })
-                .unwrap_or(HashSet::new())
+/// The aspect elevation diagram for an avalanche problem, with a band for each of the
+/// `elevation_bands` of the forecast (ordered from lowest to highest). Uses the default bands if
+/// the forecast has none.
+pub(crate) fn diagram_aspect_elevation(
+    aspect_elevation: &IndexMap<ElevationBandId, AspectElevation>,
+    elevation_bands: &[ElevationBandId],
+) -> diagrams::aspect_elevation::AspectElevation {
+    let mut diagram = diagrams::aspect_elevation::AspectElevation::default();
+    if !elevation_bands.is_empty() {
+        diagram.bands = elevation_bands
+            .iter()
+            .rev()
+            .map(|band_id| diagrams::aspect_elevation::Band::new(band_id.to_string()))
+            .collect();
+    }
+    for band in &mut diagram.bands {
+        if let Some(aspect_elevation) = aspect_elevation.get(&ElevationBandId::from(&*band.id)) {
+            band.aspects = aspect_elevation
+                .aspects
+                .iter()
+                .map(into_diagram_aspect)
+                .collect();
         }
+    }
+    diagram
+}
 
-        let query = diagrams::aspect_elevation::AspectElevation {
-            high_alpine: map_aspects(&aspect_elevation, &ElevationBandId::from("high-alpine")),
-            alpine: map_aspects(&aspect_elevation, &ElevationBandId::from("alpine")),
-            sub_alpine: map_aspects(&aspect_elevation, &ElevationBandId::from("sub-alpine")),
-            ..diagrams::aspect_elevation::AspectElevation::default()
-        }
-        .into_query();
+impl AvalancheProblem {
+    /// `elevation_bands` are the ids of the forecast's elevation bands, ordered from lowest to
+    /// highest.
+    fn try_new(
+        value: forecast_spreadsheet::AvalancheProblem,
+        elevation_bands: &[ElevationBandId],
+    ) -> eyre::Result<Self> {
+        let aspect_elevation = value.aspect_elevation;
+        let query = diagram_aspect_elevation(&aspect_elevation, elevation_bands).into_query();
 
         let query_string = serde_urlencoded::to_string(query)?;
         let aspect_elevation_chart = format!("/diagrams/aspect_elevation.svg?{query_string}");
@@ -358,111 +843,478 @@ impl TryFrom<forecast_spreadsheet::AvalancheProblem> for AvalancheProblem {
             sensitivity: value.sensitivity,
             description: value.description,
             probability,
+            terrain_percent: None,
+            is_primary: value.is_primary,
         })
     }
 }
 
 #[instrument(level = "error", skip_all)]
+#[allow(clippy::too_many_arguments)]
+/// The context for rendering the `forecast` (published as `file_name`) using the `forecast.html`
+/// template.
+pub(crate) async fn forecast_page_context(
+    forecast: forecast_spreadsheet::Forecast,
+    file_name: &str,
+    file_list: &[ListFileMetadata],
+    options: &crate::Options,
+    reloadable_options: &crate::options::ReloadableOptions,
+    client: &reqwest::Client,
+    database: &Database,
+    i18n: &I18nLoader,
+    preferences: &UserPreferences,
+    forecast_schema: &ForecastSpreadsheetSchema,
+) -> eyre::Result<ForecastContext> {
+    let forecast =
+        Forecast::try_new(forecast).wrap_err("Error converting forecast into template data")?;
+    Ok(
+        ForecastContext::format(forecast, i18n, reloadable_options, preferences)
+            .with_superseded(is_superseded(file_name, file_list, forecast_schema))
+            .with_weather_model(database, forecast_schema)
+            .await
+            .with_terrain_summary(options, database)
+            .await
+            .with_short_link(file_name, options, database)
+            .await
+            .with_forecaster_profile(file_name, forecast_schema, database)
+            .await
+            .with_machine_translations(client, database, options, reloadable_options)
+            .await
+            .with_localized_text(i18n, options, reloadable_options)
+            .with_page_metadata(file_name, i18n, options, reloadable_options),
+    )
+}
+
+#[allow(clippy::too_many_arguments)]
 async fn handler_impl(
-    requested_content_type: Option<ContentType>,
+    request: axum::extract::Request,
     file_name: String,
     options: &crate::Options,
+    reloadable_options: &crate::options::ReloadableOptions,
     client: &reqwest::Client,
+    published_files: &PublishedFiles,
     database: &Database,
+    blobs: &BlobStore,
     templates: &TemplatesWithContext,
     i18n: &I18nLoader,
     preferences: &UserPreferences,
     forecast_schema: &ForecastSpreadsheetSchema,
+    forecast_schemas: &ForecastSpreadsheetSchemas,
 ) -> eyre::Result<Response> {
-    let (requested_json, file_name) = {
-        let path = std::path::Path::new(&file_name);
-        if let Some("json") = path.extension().map(OsStr::to_str).flatten() {
-            (
-                true,
-                path.file_stem()
-                    .wrap_err("Expected file {file_name} to have a stem")?
-                    .to_str()
-                    .wrap_err("Unable to convert file path")?
-                    .to_owned(),
-            )
-        } else {
-            (
-                requested_content_type
-                    .map(|content_type| content_type == ContentType::json())
-                    .unwrap_or(false),
-                file_name,
-            )
+    // Check that file exists in published folder, and not attempting to access a file outside
+    // that.
+    let file_list = published_files.list_files().await?;
+
+    let path = std::path::Path::new(&file_name);
+    let extension = path.extension().and_then(OsStr::to_str);
+    let file_stem = || -> eyre::Result<String> {
+        Ok(path
+            .file_stem()
+            .wrap_err("Expected file {file_name} to have a stem")?
+            .to_str()
+            .wrap_err("Unable to convert file path")?
+            .to_owned())
+    };
+    let (requested_view, file_name) = match extension {
+        Some("json") => (Some(ForecastFileView::Json), file_stem()?),
+        // Published PDF files take precedence over a generated one.
+        Some("pdf") if google_drive::get_file_in_list(&file_name, &file_list).is_none() => {
+            (Some(ForecastFileView::Pdf), file_stem()?)
+        }
+        _ => {
+            let requested_json = request
+                .headers()
+                .typed_get::<ContentType>()
+                .map(|content_type| content_type == ContentType::json())
+                .unwrap_or(false);
+            (requested_json.then_some(ForecastFileView::Json), file_name)
         }
     };
 
-    // Check that file exists in published folder, and not attempting to access a file outside
-    // that.
-    let file_list = google_drive::list_files(
-        &options.google_drive.published_folder_id,
-        &options.google_drive.api_key,
-        client,
-    )
-    .await?;
     let file_metadata = match google_drive::get_file_in_list(&file_name, &file_list) {
         Some(file_metadata) => file_metadata,
         None => return Ok(StatusCode::NOT_FOUND.into_response()),
     };
 
-    let view = if requested_json {
-        ForecastFileView::Json
+    let view = if let Some(view) = requested_view {
+        view
     } else {
         match file_metadata.mime_type.as_str() {
             "application/pdf" => ForecastFileView::Download,
             "application/vnd.google-apps.spreadsheet" => ForecastFileView::Html,
+            mime_type if google_drive::SPREADSHEET_MIME_TYPES.contains(&mime_type) => {
+                ForecastFileView::Html
+            }
+            google_drive::CAAML_MIME_TYPE => ForecastFileView::Html,
             unexpected => eyre::bail!("Unsupported file mime type {unexpected}"),
         }
     };
 
     let requested = match view {
-        ForecastFileView::Html | ForecastFileView::Json => RequestedForecastData::Forecast,
+        ForecastFileView::Html | ForecastFileView::Json | ForecastFileView::Pdf => {
+            RequestedForecastData::Forecast
+        }
         ForecastFileView::Download => RequestedForecastData::File,
     };
 
     match get_forecast_data(
-        &file_metadata,
+        file_metadata,
         requested,
         client,
         database,
-        &options.google_drive.api_key,
-        forecast_schema,
+        blobs,
+        &options.google_drive,
+        forecast_schemas,
     )
     .await?
     {
         ForecastData::Forecast(forecast) => match view {
             ForecastFileView::Html => {
-                let forecast = Forecast::try_new(forecast)
-                    .wrap_err("Error converting forecast into template data")?;
-                let formatted_forecast =
-                    ForecastContext::format(forecast, &i18n, options, preferences);
+                let formatted_forecast = forecast_page_context(
+                    forecast,
+                    &file_name,
+                    &file_list,
+                    options,
+                    reloadable_options,
+                    client,
+                    database,
+                    i18n,
+                    preferences,
+                    forecast_schema,
+                )
+                .await?;
                 render(&templates.environment, "forecast.html", &formatted_forecast)
             }
             ForecastFileView::Json => Ok(Json(forecast).into_response()),
+            ForecastFileView::Pdf => {
+                let default_language = reloadable_options
+                    .default_language_order
+                    .first()
+                    .cloned()
+                    .unwrap_or_default();
+                let key = pdf::GeneratedPdfKey {
+                    file_name: &file_name,
+                    modified_time: file_metadata.modified_time,
+                    language: i18n.current_language().to_string(),
+                    default_language: default_language.to_string(),
+                };
+                let path = match pdf::get_generated(&key, database, blobs).await? {
+                    Some(path) => path,
+                    None => {
+                        let forecast = Forecast::try_new(forecast)
+                            .wrap_err("Error converting forecast into bulletin data")?;
+                        let i18n = i18n.clone();
+                        let pdf_data = tokio::task::spawn_blocking(move || {
+                            pdf::generate_pdf(&forecast, &i18n, &default_language)
+                        })
+                        .await??;
+                        pdf::store_generated(&key, pdf_data, database, blobs).await?
+                    }
+                };
+                serve_file(path, &mime::APPLICATION_PDF, request).await
+            }
             _ => unreachable!(),
         },
-        ForecastData::File(file_bytes) => {
-            let mut response = file_bytes.into_response();
-            let header_value = HeaderValue::from_str(&file_metadata.mime_type)?;
-            response.headers_mut().insert(CONTENT_TYPE, header_value);
-            Ok(response)
+        ForecastData::File(path) => {
+            let mime_type: mime::Mime = file_metadata.mime_type.parse()?;
+            serve_file(path, &mime_type, request).await
         }
     }
 }
 
+/// Respond with the file at `path`, streaming it from disk. Range requests are supported so that
+/// large PDFs can be viewed before they are completely downloaded.
+async fn serve_file(
+    path: PathBuf,
+    mime_type: &mime::Mime,
+    request: axum::extract::Request,
+) -> eyre::Result<Response> {
+    let response = ServeFile::new_with_mime(path, mime_type)
+        .oneshot(request)
+        .await?;
+    Ok(response.map(axum::body::Body::new))
+}
+
 pub enum RequestedForecastData {
-    /// Request the forecast as parsed forecast data. File must be a spreadsheet.
+    /// Request the forecast as parsed forecast data. File must be a spreadsheet, or imported from
+    /// CAAML.
     Forecast,
     /// Request the forecast as a file to download.
     File,
 }
 
+#[allow(clippy::large_enum_variant)]
 pub enum ForecastData {
     Forecast(forecast_spreadsheet::Forecast),
-    File(Vec<u8>),
+    /// Path of the blob containing the file, see [`BlobStore`].
+    File(PathBuf),
+}
+
+/// Get the cached parsed forecast for the file, if it was parsed from the current version of the
+/// file (by `last_modified`) using the current schema version. This avoids loading the file blob
+/// and re-parsing the spreadsheet.
+async fn get_cached_parsed_forecast(
+    file_metadata: &ListFileMetadata,
+    database: &Database,
+    forecast_schemas: &ForecastSpreadsheetSchemas,
+) -> eyre::Result<Option<forecast_spreadsheet::Forecast>> {
+    let Some(record) = sqlx::query!(
+        r#"SELECT last_modified as "last_modified: types::Time", parsed_forecast as "parsed_forecast: sqlx::types::Json<forecast_spreadsheet::Forecast>", schema_version FROM forecast_files WHERE google_drive_id=$1 AND parsed_forecast IS NOT NULL"#,
+        file_metadata.id
+    )
+    .fetch_optional(database)
+    .await?
+    else {
+        return Ok(None);
+    };
+
+    let last_modified: OffsetDateTime = record.last_modified.into();
+    if last_modified != file_metadata.modified_time {
+        return Ok(None);
+    }
+    let schema_version: Option<forecast_spreadsheet::Version> =
+        Option::transpose(record.schema_version.map(|sv| sv.parse()))?;
+    let Some(forecast) = record.parsed_forecast.map(|forecast| forecast.0) else {
+        return Ok(None);
+    };
+    if schema_version != selected_schema_version(&forecast, forecast_schemas) {
+        return Ok(None);
+    }
+    Ok(Some(forecast))
+}
+
+/// The parsed forecast for the file if it is available without fetching the file, either from
+/// the cache of parsed spreadsheets (see [`get_forecast_data`]), or imported from CAAML.
+pub async fn get_cached_forecast(
+    file_metadata: &ListFileMetadata,
+    database: &Database,
+    forecast_schemas: &ForecastSpreadsheetSchemas,
+) -> eyre::Result<Option<forecast_spreadsheet::Forecast>> {
+    if file_metadata.is_caaml() {
+        return get_imported_forecast(file_metadata, database)
+            .await
+            .map(Some);
+    }
+    get_cached_parsed_forecast(file_metadata, database, forecast_schemas).await
+}
+
+/// Get the forecast for a file imported from a CAAML document (see
+/// [`crate::admin::forecast_files`]), which is only stored in the database, and isn't parsed
+/// using a schema.
+async fn get_imported_forecast(
+    file_metadata: &ListFileMetadata,
+    database: &Database,
+) -> eyre::Result<forecast_spreadsheet::Forecast> {
+    sqlx::query_scalar!(
+        r#"SELECT parsed_forecast as "parsed_forecast!: sqlx::types::Json<forecast_spreadsheet::Forecast>" FROM forecast_files WHERE google_drive_id=$1 AND parsed_forecast IS NOT NULL"#,
+        file_metadata.id
+    )
+    .fetch_optional(database)
+    .await?
+    .map(|forecast| forecast.0)
+    .wrap_err_with(|| format!("Imported forecast not found: {file_metadata:?}"))
+}
+
+/// The version of the schema that would currently be selected to parse the `forecast`'s
+/// spreadsheet, if there is one.
+fn selected_schema_version(
+    forecast: &forecast_spreadsheet::Forecast,
+    forecast_schemas: &ForecastSpreadsheetSchemas,
+) -> Option<forecast_spreadsheet::Version> {
+    forecast_schemas
+        .select(&forecast.template_version)
+        .ok()
+        .map(|schema| schema.schema_version)
+}
+
+/// Parse the forecast from the `file_blob` of a cached [`ForecastFile`], which contains either
+/// the spreadsheet file, or the [`google_drive::SheetValues`] serialized as JSON when the
+/// spreadsheet was read using the Google Sheets API.
+pub fn parse_forecast_file_blob(
+    file_blob: &[u8],
+    forecast_schemas: &ForecastSpreadsheetSchemas,
+) -> eyre::Result<forecast_spreadsheet::Forecast> {
+    if file_blob.first() == Some(&b'{') {
+        let sheet_values: google_drive::SheetValues = serde_json::from_slice(file_blob)
+            .wrap_err("Error deserializing cached sheet values")?;
+        forecast_schemas.parse_cell_source(&mut sheet_values.into_value_sheets())
+    } else {
+        forecast_schemas.parse_excel_spreadsheet(file_blob)
+    }
+}
+
+/// Like [`parse_forecast_file_blob`], but parses using the specified `forecast_schema` instead of
+/// the one selected by the spreadsheet's template version.
+pub fn parse_forecast_file_blob_with_schema(
+    file_blob: &[u8],
+    forecast_schema: &ForecastSpreadsheetSchema,
+) -> eyre::Result<forecast_spreadsheet::Forecast> {
+    if file_blob.first() == Some(&b'{') {
+        let sheet_values: google_drive::SheetValues = serde_json::from_slice(file_blob)
+            .wrap_err("Error deserializing cached sheet values")?;
+        forecast_spreadsheet::parse_cell_source(
+            &mut sheet_values.into_value_sheets(),
+            forecast_schema,
+        )
+    } else {
+        forecast_spreadsheet::parse_excel_spreadsheet(file_blob, forecast_schema)
+    }
+}
+
+/// Fetch the file from Google Drive into the blob store, and update the cached forecast file.
+async fn fetch_forecast_file(
+    file_metadata: &ListFileMetadata,
+    requested: &RequestedForecastData,
+    client: &reqwest::Client,
+    database: &Database,
+    blobs: &BlobStore,
+    google_drive_options: &GoogleDrive,
+    forecast_schemas: &ForecastSpreadsheetSchemas,
+) -> eyre::Result<ForecastFile> {
+    tracing::debug!("Fetching updated/new forecast file");
+    let google_drive = google_drive::Client::new(client, google_drive_options)?;
+    let limit = google_drive_options.max_file_bytes;
+    let (blob_hash, forecast): (
+        BlobHash,
+        Option<(
+            forecast_spreadsheet::Forecast,
+            forecast_spreadsheet::Version,
+        )>,
+    ) = match requested {
+        RequestedForecastData::Forecast => {
+            let forecast_file_bytes: Bytes = if !file_metadata.is_google_sheet() {
+                google_drive
+                    .get_file(&file_metadata.id)
+                    .await?
+                    .bytes(limit)
+                    .await?
+            } else if google_drive_options.sheets_api {
+                let sheet_values = google_drive.get_sheet_values(&file_metadata.id).await?;
+                serde_json::to_vec(&sheet_values)?.into()
+            } else {
+                google_drive
+                    .export_file(
+                        &file_metadata.id,
+                        "application/vnd.openxmlformats-officedocument.spreadsheetml.sheet",
+                    )
+                    .await?
+                    .bytes(limit)
+                    .await?
+            };
+            let forecast: forecast_spreadsheet::Forecast =
+                parse_forecast_file_blob(&forecast_file_bytes, forecast_schemas).with_context(
+                    || format!("Error parsing forecast spreadsheet: {file_metadata:?}"),
+                )?;
+            let schema_version = selected_schema_version(&forecast, forecast_schemas)
+                .wrap_err("Expected a schema to be selected for the parsed forecast")?;
+            let blob_hash = blobs.put(forecast_file_bytes).await?;
+
+            (blob_hash, Some((forecast, schema_version)))
+        }
+        RequestedForecastData::File => {
+            // Streamed to disk, so that large files aren't held in memory.
+            let file = google_drive.get_file(&file_metadata.id).await?;
+            (blobs.put_stream(file.bytes_stream(limit)?).await?, None)
+        }
+    };
+    let forecast_file_db = ForecastFile {
+        google_drive_id: file_metadata.id.clone(),
+        last_modified: file_metadata.modified_time.into(),
+        blob_hash: Some(blob_hash),
+        parsed_forecast: forecast.as_ref().map(|f| f.0.clone()),
+        schema_version: forecast.as_ref().map(|f| f.1),
+    };
+    let parsed_forecast = forecast_file_db
+        .parsed_forecast
+        .clone()
+        .map(sqlx::types::Json);
+    let schema_version = forecast_file_db.schema_version.map(|v| v.to_string());
+    tracing::debug!("Updating cached forecast file");
+    sqlx::query!(
+        "INSERT INTO forecast_files(google_drive_id, last_modified, file_blob, blob_hash, parsed_forecast, schema_version) VALUES($1, $2, x'', $3, $4, $5) ON CONFLICT(google_drive_id) DO UPDATE SET last_modified=excluded.last_modified, file_blob=excluded.file_blob, blob_hash=excluded.blob_hash, parsed_forecast=excluded.parsed_forecast, schema_version=excluded.schema_version, parse_error=NULL",
+        forecast_file_db.google_drive_id,
+        forecast_file_db.last_modified,
+        forecast_file_db.blob_hash,
+        parsed_forecast,
+        schema_version,
+    ).execute(database).await?;
+
+    Ok(forecast_file_db)
+}
+
+/// The contents of the cached forecast file with `google_drive_id`, which are stored in the
+/// [`BlobStore`]. `None` if the file isn't cached.
+pub async fn load_file_blob(
+    google_drive_id: &str,
+    database: &Database,
+    blobs: &BlobStore,
+) -> eyre::Result<Option<Vec<u8>>> {
+    let blob_hash = sqlx::query_scalar!(
+        r#"SELECT blob_hash as "blob_hash: BlobHash" FROM forecast_files WHERE google_drive_id=$1"#,
+        google_drive_id
+    )
+    .fetch_optional(database)
+    .await?
+    .flatten();
+    match blob_hash {
+        Some(blob_hash) => blobs.read(&blob_hash).await,
+        None => Ok(None),
+    }
+}
+
+/// Move the uploaded forecast files which are still stored in the `file_blob` column of
+/// `forecast_files` into the blob store.
+pub async fn migrate_uploaded_files_to_blobs(
+    database: &Database,
+    blobs: &BlobStore,
+) -> eyre::Result<()> {
+    let ids = sqlx::query_scalar!(
+        "SELECT google_drive_id FROM forecast_files WHERE length(file_blob) > 0"
+    )
+    .fetch_all(database)
+    .await?;
+    if ids.is_empty() {
+        return Ok(());
+    }
+    tracing::info!("Moving {} forecast files into the blob store", ids.len());
+    for id in ids {
+        let file_blob = sqlx::query_scalar!(
+            "SELECT file_blob FROM forecast_files WHERE google_drive_id = $1",
+            id
+        )
+        .fetch_one(database)
+        .await?;
+        let blob_hash = blobs.put(file_blob.into()).await?;
+        sqlx::query!(
+            "UPDATE forecast_files SET file_blob = x'', blob_hash = $1 WHERE google_drive_id = $2",
+            blob_hash,
+            id
+        )
+        .execute(database)
+        .await
+        .wrap_err_with(|| format!("Error moving forecast file {id} into the blob store"))?;
+    }
+    Ok(())
+}
+
+/// `None` if the `forecast_file` is requested as a file to download, but its blob is no longer
+/// stored.
+async fn filter_stored(
+    forecast_file: Option<ForecastFile>,
+    requested: &RequestedForecastData,
+    blobs: &BlobStore,
+) -> eyre::Result<Option<ForecastFile>> {
+    let Some(forecast_file) = forecast_file else {
+        return Ok(None);
+    };
+    if matches!(requested, RequestedForecastData::Forecast) {
+        return Ok(Some(forecast_file));
+    }
+    let stored = match &forecast_file.blob_hash {
+        Some(blob_hash) => blobs.contains(blob_hash).await?,
+        None => false,
+    };
+    Ok(stored.then_some(forecast_file))
 }
 
 /// Get the forecast data for a given file in the published directory.
@@ -474,138 +1326,154 @@ pub async fn get_forecast_data(
     requested: RequestedForecastData,
     client: &reqwest::Client,
     database: &Database,
-    google_drive_api_key: &SecretString,
-    forecast_schema: &ForecastSpreadsheetSchema,
+    blobs: &BlobStore,
+    google_drive_options: &GoogleDrive,
+    forecast_schemas: &ForecastSpreadsheetSchemas,
 ) -> eyre::Result<ForecastData> {
     if matches!(requested, RequestedForecastData::Forecast) {
-        if !file_metadata.is_google_sheet() {
+        if file_metadata.is_caaml() {
+            return get_imported_forecast(file_metadata, database)
+                .await
+                .map(ForecastData::Forecast);
+        }
+        if !file_metadata.is_spreadsheet() {
             eyre::bail!("Unsupported mime type for requested data Forecast: {file_metadata:?}");
         }
     }
     let google_drive_id = file_metadata.id.clone();
-    let cached_forecast_file: Option<ForecastFile> = Option::transpose(sqlx::query!(
-        r#"SELECT google_drive_id, last_modified as "last_modified: types::Time", file_blob, parsed_forecast as "parsed_forecast: sqlx::types::Json<forecast_spreadsheet::Forecast>", schema_version FROM forecast_files WHERE google_drive_id=$1"#,
+    if matches!(requested, RequestedForecastData::Forecast) {
+        if let Some(forecast) =
+            get_cached_parsed_forecast(file_metadata, database, forecast_schemas).await?
+        {
+            tracing::debug!("Using cached parsed forecast");
+            metrics::counter!("forecast_cache_hits_total").increment(1);
+            return Ok(ForecastData::Forecast(forecast));
+        }
+    }
+    let stored_forecast_file: Option<ForecastFile> = Option::transpose(sqlx::query!(
+        r#"SELECT google_drive_id, last_modified as "last_modified: types::Time", blob_hash as "blob_hash: BlobHash", parsed_forecast as "parsed_forecast: sqlx::types::Json<forecast_spreadsheet::Forecast>", schema_version FROM forecast_files WHERE google_drive_id=$1"#,
         google_drive_id
     ).fetch_optional(database).await?.map(|record| {
             eyre::Ok(ForecastFile {
                 google_drive_id: record.google_drive_id,
                 last_modified: record.last_modified,
-                file_blob: record.file_blob,
+                blob_hash: record.blob_hash,
                 parsed_forecast: record.parsed_forecast.map(|f| f.0),
                 schema_version: Option::transpose(record.schema_version.map(|sv| sv.parse()))?
 
             })
-    }))?
-        .and_then(|cached_forecast_file| {
-            let cached_last_modified: OffsetDateTime = cached_forecast_file.last_modified.into();
+    }))?;
+    let (cached_forecast_file, outdated_forecast_file) = match stored_forecast_file {
+        Some(stored_forecast_file) => {
+            let cached_last_modified: OffsetDateTime = stored_forecast_file.last_modified.into();
             let server_last_modified: &OffsetDateTime = &file_metadata.modified_time;
             tracing::debug!("cached last modified {cached_last_modified}, server last modified {server_last_modified}");
             // This logic is a bit buggy on google's side it seems, sometimes they change document
             // but don't update modified time.
             if cached_last_modified == *server_last_modified {
-                Some(cached_forecast_file)
+                (Some(stored_forecast_file), None)
             } else {
                 tracing::debug!("Found cached forecast file, but it's outdated");
-                None
+                (None, Some(stored_forecast_file))
             }
-        });
+        }
+        None => (None, None),
+    };
+    let cached_forecast_file = filter_stored(cached_forecast_file, &requested, blobs).await?;
+    let outdated_forecast_file = filter_stored(outdated_forecast_file, &requested, blobs).await?;
+    // Uploaded files can't be fetched from Google Drive.
+    if cached_forecast_file.is_none() && google_drive_id.starts_with(published::UPLOADED_ID_PREFIX)
+    {
+        eyre::bail!("Uploaded forecast file is missing: {file_metadata:?}");
+    }
 
+    let fetch = || {
+        fetch_forecast_file(
+            file_metadata,
+            &requested,
+            client,
+            database,
+            blobs,
+            google_drive_options,
+            forecast_schemas,
+        )
+    };
     let forecast_file: ForecastFile = if let Some(cached_forecast_file) = cached_forecast_file {
         tracing::debug!("Using cached forecast file");
+        metrics::counter!("forecast_cache_hits_total").increment(1);
         cached_forecast_file
     } else {
-        tracing::debug!("Fetching updated/new forecast file");
-        let (forecast_file_bytes, forecast): (
-            Vec<u8>,
-            Option<(
-                forecast_spreadsheet::Forecast,
-                forecast_spreadsheet::Version,
-            )>,
-        ) = match requested {
-            RequestedForecastData::Forecast => {
-                let file = google_drive::export_file(
-                    &file_metadata.id,
-                    "application/vnd.openxmlformats-officedocument.spreadsheetml.sheet",
-                    google_drive_api_key,
-                    client,
-                )
-                .await?;
-                let forecast_file_bytes: Vec<u8> = file.bytes().await?.into();
-                let forecast: forecast_spreadsheet::Forecast =
-                    forecast_spreadsheet::parse_excel_spreadsheet(
-                        &forecast_file_bytes,
-                        forecast_schema,
-                    )
-                    .with_context(|| {
-                        format!("Error parsing forecast spreadsheet: {file_metadata:?}")
-                    })?;
-
-                (
-                    forecast_file_bytes,
-                    Some((forecast, forecast_schema.schema_version.clone())),
-                )
-            }
-            RequestedForecastData::File => {
-                let file =
-                    google_drive::get_file(&file_metadata.id, google_drive_api_key, client).await?;
-                (file.bytes().await?.into(), None)
-            }
-        };
-        let forecast_file_db = ForecastFile {
-            google_drive_id: file_metadata.id.clone(),
-            last_modified: file_metadata.modified_time.clone().into(),
-            file_blob: forecast_file_bytes.clone(),
-            parsed_forecast: forecast.as_ref().map(|f| f.0.clone()),
-            schema_version: forecast.as_ref().map(|f| f.1.clone()),
-        };
-        let parsed_forecast = forecast_file_db
-            .parsed_forecast
-            .clone()
-            .map(sqlx::types::Json);
-        let schema_version = forecast_file_db.schema_version.map(|v| v.to_string());
-        tracing::debug!("Updating cached forecast file");
-        sqlx::query!(
-            "INSERT INTO forecast_files VALUES($1, $2, $3, $4, $5) ON CONFLICT(google_drive_id) DO UPDATE SET last_modified=excluded.last_modified, file_blob=excluded.file_blob, parsed_forecast=excluded.parsed_forecast, schema_version=excluded.schema_version",
-            forecast_file_db.google_drive_id,
-            forecast_file_db.last_modified,
-            forecast_file_db.file_blob,
-            parsed_forecast,
-            schema_version,
-        ).execute(database).await?;
-
-        forecast_file_db
+        metrics::counter!("forecast_cache_misses_total").increment(1);
+        match fetch().await {
+            Ok(forecast_file) => forecast_file,
+            // Continue serving the outdated file while Google Drive is unreachable.
+            Err(error) => match outdated_forecast_file {
+                Some(outdated_forecast_file) => {
+                    tracing::warn!(
+                        "Using outdated cached forecast file {:?}: {error:?}",
+                        file_metadata.name
+                    );
+                    outdated_forecast_file
+                }
+                None => return Err(error),
+            },
+        }
     };
 
     match requested {
         RequestedForecastData::Forecast => {
             if let Some(forecast) = forecast_file.parsed_forecast {
-                if forecast_file.schema_version == Some(forecast_schema.schema_version) {
+                let selected_schema_version = selected_schema_version(&forecast, forecast_schemas);
+                if forecast_file.schema_version.is_some()
+                    && forecast_file.schema_version == selected_schema_version
+                {
                     tracing::debug!("Re-using parsed forecast");
                     return Ok(ForecastData::Forecast(forecast));
                 } else {
                     tracing::warn!(
                         "Cached forecast schema version {:?} doesn't match current {:?}",
                         forecast_file.schema_version,
-                        forecast_schema.schema_version
+                        selected_schema_version
                     );
                 }
             }
+            let Some(file_blob) =
+                load_file_blob(&forecast_file.google_drive_id, database, blobs).await?
+            else {
+                tracing::debug!("Stored forecast file is missing, fetching it again");
+                return fetch()
+                    .await?
+                    .parsed_forecast
+                    .map(ForecastData::Forecast)
+                    .wrap_err("Expected the fetched forecast file to be parsed");
+            };
             tracing::debug!("Re-parsing forecast");
             let forecast: forecast_spreadsheet::Forecast =
-                forecast_spreadsheet::parse_excel_spreadsheet(
-                    &forecast_file.file_blob,
-                    forecast_schema,
-                )
-                .with_context(|| {
-                    format!("Error parsing forecast spreadsheet: {file_metadata:?}")
-                })?;
+                match parse_forecast_file_blob(&file_blob, forecast_schemas) {
+                    Ok(forecast) => forecast,
+                    Err(error) => {
+                        // Shown on the admin forecast files page.
+                        let parse_error = format!("{error:#}");
+                        sqlx::query!(
+                            "UPDATE forecast_files SET parse_error=$1 WHERE google_drive_id=$2",
+                            parse_error,
+                            forecast_file.google_drive_id
+                        )
+                        .execute(database)
+                        .await?;
+                        return Err(error.wrap_err(format!(
+                            "Error parsing forecast spreadsheet: {file_metadata:?}"
+                        )));
+                    }
+                };
 
             tracing::debug!("Updating cached parsed forecast and schema version");
 
             let parsed_forecast = Some(sqlx::types::Json(forecast.clone()));
-            let schema_version = Some(forecast_schema.schema_version.to_string());
+            let schema_version = selected_schema_version(&forecast, forecast_schemas)
+                .map(|version| version.to_string());
             sqlx::query!(
-                "UPDATE forecast_files SET parsed_forecast=$1, schema_version=$2 WHERE google_drive_id=$3",
+                "UPDATE forecast_files SET parsed_forecast=$1, schema_version=$2, parse_error=NULL WHERE google_drive_id=$3",
                 parsed_forecast,
                 schema_version,
                 forecast_file.google_drive_id
@@ -613,7 +1481,14 @@ pub async fn get_forecast_data(
 
             Ok(ForecastData::Forecast(forecast))
         }
-        RequestedForecastData::File => Ok(ForecastData::File(forecast_file.file_blob)),
+        RequestedForecastData::File => Ok(ForecastData::File(
+            blobs.path(
+                forecast_file
+                    .blob_hash
+                    .as_ref()
+                    .wrap_err("Expected the forecast file to be stored in a blob")?,
+            ),
+        )),
     }
 }
 
@@ -624,17 +1499,19 @@ mod test {
     use forecast_spreadsheet::{options::AreaDefinition, AreaId};
     use indexmap::IndexMap;
 
-    use crate::forecasts::GUDUAURI_FORECAST_SCHEMA;
+    use crate::forecasts::{ForecastSpreadsheetSchema, GUDAURI_FORECAST_SCHEMA_JSON};
 
-    use super::{parse_forecast_name, parse_forecast_name_impl};
+    use super::{
+        canonical_forecast_name, is_superseded, parse_forecast_name, parse_forecast_name_impl,
+        Forecast, ForecastValidity,
+    };
 
     #[test]
     fn test_parse_forecast_name() {
-        let forecast_details = parse_forecast_name(
-            "Gudauri_2023-01-24T17:00_LF.en.pdf",
-            &GUDUAURI_FORECAST_SCHEMA,
-        )
-        .unwrap();
+        let schema: ForecastSpreadsheetSchema =
+            serde_json::from_str(GUDAURI_FORECAST_SCHEMA_JSON).unwrap();
+        let forecast_details =
+            parse_forecast_name("Gudauri_2023-01-24T17:00_LF.en.pdf", &schema).unwrap();
         insta::assert_json_snapshot!(forecast_details, @r###"
         {
           "forecast": {
@@ -647,6 +1524,27 @@ mod test {
         "###);
     }
 
+    #[test]
+    fn test_canonical_forecast_name() {
+        let schema: ForecastSpreadsheetSchema =
+            serde_json::from_str(GUDAURI_FORECAST_SCHEMA_JSON).unwrap();
+        let forecast: forecast_spreadsheet::Forecast = serde_json::from_value(serde_json::json!({
+            "template_version": { "major": 0, "minor": 3, "patch": 1 },
+            "area": "gudauri",
+            "forecaster": { "name": "Luke Frisken", "organisation": null },
+            "time": "2023-01-24T13:00:00Z",
+            "valid_for": 86400,
+            "hazard_ratings": {},
+            "avalanche_problems": [],
+            "elevation_bands": {}
+        }))
+        .unwrap();
+        let name = canonical_forecast_name(&forecast, &schema, "xlsx").unwrap();
+        assert_eq!(name, "Gudauri_2023-01-24T17:00_LF.xlsx");
+        let details = parse_forecast_name(&name, &schema).unwrap();
+        assert_eq!(details.forecast.time, forecast.time);
+    }
+
     #[test]
     fn test_parse_forecast_name_pre_dst() {
         let mut area_name_map = HashMap::new();
@@ -708,4 +1606,122 @@ mod test {
         }
         "###);
     }
+
+    #[test]
+    fn test_forecast_validity() {
+        let forecast: forecast_spreadsheet::Forecast = serde_json::from_value(serde_json::json!({
+            "template_version": { "major": 0, "minor": 3, "patch": 1 },
+            "area": "gudauri",
+            "forecaster": { "name": "Luke Frisken", "organisation": null },
+            "time": "2023-01-24T13:00:00Z",
+            "valid_for": 86400,
+            "hazard_ratings": {},
+            "avalanche_problems": [],
+            "elevation_bands": {}
+        }))
+        .unwrap();
+        let forecast = Forecast::try_new(forecast).unwrap();
+        let expiring_soon = time::Duration::hours(2);
+        assert_eq!(
+            forecast.validity(time::macros::datetime!(2023-01-24 14:00 UTC), expiring_soon),
+            ForecastValidity::Current
+        );
+        assert_eq!(
+            forecast.validity(time::macros::datetime!(2023-01-25 12:00 UTC), expiring_soon),
+            ForecastValidity::ExpiringSoon
+        );
+        assert_eq!(
+            forecast.validity(time::macros::datetime!(2023-01-25 13:01 UTC), expiring_soon),
+            ForecastValidity::Expired
+        );
+    }
+
+    #[test]
+    fn test_combined_aspect_elevation() {
+        let mut forecast: forecast_spreadsheet::Forecast = serde_json::from_value(serde_json::json!({
+            "template_version": { "major": 0, "minor": 3, "patch": 1 },
+            "area": "gudauri",
+            "forecaster": { "name": "Luke Frisken", "organisation": null },
+            "time": "2023-01-24T13:00:00Z",
+            "valid_for": 86400,
+            "hazard_ratings": {},
+            "avalanche_problems": [
+                {
+                    "kind": "wind-slab",
+                    "aspect_elevation": {
+                        "high-alpine": { "aspects": ["N", "NE"] },
+                        "alpine": { "aspects": [] }
+                    }
+                },
+                {
+                    "kind": "loose-wet",
+                    "aspect_elevation": {
+                        "sub-alpine": { "aspects": ["S"] },
+                        "high-alpine": { "aspects": ["NE", "E"] }
+                    }
+                }
+            ],
+            "elevation_bands": {}
+        }))
+        .unwrap();
+        // The keys of `json!` objects are sorted, so the elevation bands (ordered from lowest to
+        // highest) are added separately.
+        forecast.elevation_bands = [
+            ("sub-alpine", Some(2000), None),
+            ("alpine", Some(2600), Some(2000)),
+            ("high-alpine", None, Some(2600)),
+        ]
+        .into_iter()
+        .map(|(id, upper, lower)| (id.into(), forecast_spreadsheet::ElevationRange { upper, lower }))
+        .collect();
+        let forecast = Forecast::try_new(forecast).unwrap();
+        let combined: Vec<(String, Vec<String>)> = forecast
+            .combined_aspect_elevation()
+            .into_iter()
+            .map(|(band, aspect_elevation)| {
+                (
+                    band.to_string(),
+                    aspect_elevation
+                        .aspects
+                        .iter()
+                        .map(|aspect| format!("{aspect:?}"))
+                        .collect(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            combined,
+            vec![
+                ("sub-alpine".to_owned(), vec!["S".to_owned()]),
+                (
+                    "high-alpine".to_owned(),
+                    vec!["N".to_owned(), "NE".to_owned(), "E".to_owned()]
+                ),
+            ]
+        );
+        assert_eq!(
+            forecast.combined_aspect_elevation_chart().unwrap().as_deref(),
+            Some("/diagrams/aspect_elevation_compact.svg?bands=high-alpine%3AN%2CNE%2CE%3Balpine%3A%3Bsub-alpine%3AS")
+        );
+    }
+
+    #[test]
+    fn test_is_superseded() {
+        let schema: ForecastSpreadsheetSchema =
+            serde_json::from_str(GUDAURI_FORECAST_SCHEMA_JSON).unwrap();
+        let files: Vec<crate::google_drive::ListFileMetadata> = [
+            "Gudauri_2023-01-24T17:00_LF.en.pdf",
+            "Gudauri_2023-01-25T17:00_LF.en.pdf",
+        ]
+        .into_iter()
+        .map(|name| crate::google_drive::ListFileMetadata {
+            mime_type: "application/pdf".to_owned(),
+            id: name.to_owned(),
+            name: name.to_owned(),
+            modified_time: time::OffsetDateTime::UNIX_EPOCH,
+        })
+        .collect();
+        assert!(is_superseded(&files[0].name, &files, &schema));
+        assert!(!is_superseded(&files[1].name, &files, &schema));
+    }
 }
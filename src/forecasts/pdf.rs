@@ -0,0 +1,459 @@
+//! Generation of a print-ready PDF bulletin from parsed forecast data.
+//!
+//! The bulletin is laid out as a series of A4 SVG pages (re-using the diagrams from
+//! [`crate::diagrams`]), which are then converted into a single PDF document using `svg2pdf`.
+
+use std::{collections::HashMap, path::PathBuf, sync::Arc};
+
+use base64::Engine;
+use eyre::Context;
+use forecast_spreadsheet::HazardRatingKind;
+use pdf_writer::{Chunk, Content, Finish, Name, Pdf, Rect, Ref};
+use svg2pdf::usvg::{self, TreeParsing, TreePostProc};
+use time::OffsetDateTime;
+use unic_langid::LanguageIdentifier;
+
+use crate::{
+    database::{
+        blob::{BlobHash, BlobStore},
+        Database,
+    },
+    diagrams::{
+        aspect_elevation, danger_scale,
+        elevation_hazard::{ElevationBand, HazardLevel},
+        escape_xml, FONT_DB,
+    },
+    i18n::{self, I18nLoader},
+    types, version,
+};
+
+use super::{diagram_aspect_elevation, variant_id, Forecast};
+
+/// A4 page width in points.
+const PAGE_WIDTH: f32 = 595.0;
+/// A4 page height in points.
+const PAGE_HEIGHT: f32 = 842.0;
+const MARGIN: f32 = 40.0;
+const CONTENT_WIDTH: f32 = PAGE_WIDTH - 2.0 * MARGIN;
+const BODY_FONT_SIZE: f32 = 10.0;
+const LINE_HEIGHT: f32 = BODY_FONT_SIZE * 1.4;
+/// Approximate average glyph width as a proportion of font size, used for line wrapping.
+const GLYPH_WIDTH_RATIO: f32 = 0.52;
+const DIAGRAM_SIZE: f32 = 120.0;
+const BANNER_WIDTH: f32 = CONTENT_WIDTH * 0.6;
+/// Height of a danger scale banner, keeping its aspect ratio.
+const BANNER_HEIGHT: f32 = BANNER_WIDTH * 120.0 / 500.0;
+
+/// Lays out content top to bottom, starting a new page when the current one is full.
+struct PageBuilder {
+    pages: Vec<String>,
+    current: String,
+    y: f32,
+}
+
+impl PageBuilder {
+    fn new() -> Self {
+        Self {
+            pages: Vec::new(),
+            current: String::new(),
+            y: MARGIN,
+        }
+    }
+
+    /// Ensure there is at least `height` of vertical space available on the current page.
+    fn reserve(&mut self, height: f32) {
+        if self.y + height > PAGE_HEIGHT - MARGIN && !self.current.is_empty() {
+            self.new_page();
+        }
+    }
+
+    fn new_page(&mut self) {
+        self.pages.push(std::mem::take(&mut self.current));
+        self.y = MARGIN;
+    }
+
+    fn push(&mut self, element: &str) {
+        self.current.push_str(element);
+        self.current.push('\n');
+    }
+
+    fn heading(&mut self, text: &str, font_size: f32) {
+        self.reserve(font_size * 2.0);
+        self.y += font_size * 1.2;
+        let text = escape_xml(text);
+        let y = self.y;
+        self.push(&format!(
+            r##"<text x="{MARGIN}" y="{y}" font-family="Noto Sans" font-size="{font_size}" font-weight="bold" fill="#1e3a5f">{text}</text>"##
+        ));
+        self.y += font_size * 0.6;
+    }
+
+    fn paragraph(&mut self, text: &str) {
+        self.paragraph_at(MARGIN, CONTENT_WIDTH, text);
+        self.y += LINE_HEIGHT * 0.5;
+    }
+
+    fn paragraph_at(&mut self, x: f32, width: f32, text: &str) {
+        for line in wrap_text(text, width, BODY_FONT_SIZE) {
+            self.reserve(LINE_HEIGHT);
+            self.y += LINE_HEIGHT;
+            let line = escape_xml(&line);
+            let y = self.y;
+            self.push(&format!(
+                r##"<text x="{x}" y="{y}" font-family="Noto Sans" font-size="{BODY_FONT_SIZE}" fill="#000000">{line}</text>"##
+            ));
+        }
+    }
+
+    fn finish(mut self) -> Vec<String> {
+        if !self.current.is_empty() {
+            self.pages.push(self.current);
+        }
+        self.pages
+            .into_iter()
+            .map(|content| {
+                format!(
+                    r##"<?xml version="1.0" encoding="UTF-8" standalone="no"?>
+<svg width="{PAGE_WIDTH}" height="{PAGE_HEIGHT}" viewBox="0 0 {PAGE_WIDTH} {PAGE_HEIGHT}" version="1.1" xmlns="http://www.w3.org/2000/svg">
+<rect x="0" y="0" width="{PAGE_WIDTH}" height="{PAGE_HEIGHT}" style="fill:#ffffff"/>
+{content}</svg>
+"##
+                )
+            })
+            .collect()
+    }
+}
+
+/// Greedy word wrapping using an approximate glyph width.
+fn wrap_text(text: &str, width: f32, font_size: f32) -> Vec<String> {
+    let max_chars = ((width / (font_size * GLYPH_WIDTH_RATIO)) as usize).max(1);
+    let mut lines = Vec::new();
+    for paragraph in text.lines() {
+        let mut line = String::new();
+        for word in paragraph.split_whitespace() {
+            if !line.is_empty() && line.chars().count() + 1 + word.chars().count() > max_chars {
+                lines.push(std::mem::take(&mut line));
+            }
+            if !line.is_empty() {
+                line.push(' ');
+            }
+            line.push_str(word);
+        }
+        lines.push(line);
+    }
+    lines
+}
+
+/// Select the best translation of a free text field for the current language.
+fn translated<'a>(
+    text: &'a HashMap<LanguageIdentifier, String>,
+    i18n: &I18nLoader,
+    default_language: &'a LanguageIdentifier,
+) -> Option<&'a str> {
+    i18n::negotiate_translated_string(&i18n.current_languages(), default_language, text)
+        .map(|(_, text)| text)
+        .filter(|text| !text.trim().is_empty())
+}
+
+fn svg_data_url(svg: &str) -> String {
+    let data = base64::engine::general_purpose::STANDARD.encode(svg);
+    format!("data:image/svg+xml;base64,{data}")
+}
+
+/// Lay out the pages of the bulletin as SVG documents.
+pub fn generate_pages(
+    forecast: &Forecast,
+    i18n: &I18nLoader,
+    default_language: &LanguageIdentifier,
+) -> Vec<String> {
+    let mut pages = PageBuilder::new();
+
+    let area_id = forecast.area.to_lowercase();
+    pages.heading(
+        &format!(
+            "{} - {}",
+            i18n.get(&format!("forecast-area-{area_id}")),
+            i18n.get("avalanche-forecast-heading")
+        ),
+        20.0,
+    );
+    let issued = i18n::format_time(forecast.time, i18n);
+    let valid_until = i18n::format_time(forecast.time + forecast.valid_for, i18n);
+    pages.paragraph(&format!(
+        "{}: {issued}\n{}: {valid_until}\n{}: {}",
+        i18n.get("forecast-issued-at-label"),
+        i18n.get("forecast-valid-until-label"),
+        i18n.get("forecast-forecaster-heading"),
+        forecast.forecaster.name,
+    ));
+
+    // Hazard ratings.
+    pages.heading(&i18n.get("avalanche-hazard-heading"), 14.0);
+    let mut ratings: Vec<(Option<ElevationBand>, String, HazardLevel)> = forecast
+        .elevation_bands
+        .keys()
+        .filter_map(|band| {
+            let rating = forecast
+                .hazard_ratings
+                .get(&HazardRatingKind::ElevationSpecific(band.clone()))?;
+            let band_id: &str = band;
+            Some((
+                ElevationBand::from_id(band_id),
+                i18n.get(&format!("elevation-band-{band_id}")),
+                rating
+                    .value
+                    .map(HazardLevel::from)
+                    .unwrap_or(HazardLevel::NoRating),
+            ))
+        })
+        .collect();
+    if ratings.is_empty() {
+        if let Some(rating) = forecast.hazard_ratings.get(&HazardRatingKind::Overall) {
+            ratings.push((
+                None,
+                i18n.get("avalanche-hazard-heading"),
+                rating
+                    .value
+                    .map(HazardLevel::from)
+                    .unwrap_or(HazardLevel::NoRating),
+            ));
+        }
+    }
+    for (band, label, level) in ratings {
+        pages.reserve(BANNER_HEIGHT + 4.0);
+        let y = pages.y + 4.0;
+        let level_id = level.id();
+        let label = format!(
+            "{label}: {}",
+            i18n.get(&format!("avalanche-hazard-{level_id}"))
+        );
+        let banner = danger_scale::generate_svg_with_label(band, level, &label);
+        let href = svg_data_url(&banner);
+        pages.push(&format!(
+            r##"<image x="{MARGIN}" y="{y}" width="{BANNER_WIDTH}" height="{BANNER_HEIGHT}" href="{href}"/>"##
+        ));
+        pages.y = y + BANNER_HEIGHT;
+    }
+    pages.y += LINE_HEIGHT;
+
+    if let Some(description) = translated(&forecast.description, i18n, default_language) {
+        pages.paragraph(description);
+    }
+
+    // Avalanche problems, each with its aspect/elevation diagram.
+    if !forecast.avalanche_problems.is_empty() {
+        pages.heading(&i18n.get("avalanche-problems-heading"), 14.0);
+    }
+    let elevation_band_ids: Vec<_> = forecast.elevation_bands.keys().cloned().collect();
+    for problem in &forecast.avalanche_problems {
+        let kind_id = variant_id(&problem.kind);
+        pages.reserve(DIAGRAM_SIZE + 30.0);
+        let mut heading = i18n.get(&format!("problem-type-{kind_id}"));
+        if problem.is_primary && forecast.avalanche_problems.len() > 1 {
+            heading = format!("{heading} ({})", i18n.get("problem-primary-label"));
+        }
+        pages.heading(&heading, 12.0);
+
+        let diagram = aspect_elevation::generate_svg(
+            diagram_aspect_elevation(&problem.aspect_elevation, &elevation_band_ids),
+            Arc::clone(i18n),
+        );
+        let diagram_y = pages.y;
+        let href = svg_data_url(&diagram);
+        pages.push(&format!(
+            r##"<image x="{MARGIN}" y="{diagram_y}" width="{DIAGRAM_SIZE}" height="{DIAGRAM_SIZE}" href="{href}"/>"##
+        ));
+
+        let mut details = Vec::new();
+        if let Some(size) = problem.size {
+            details.push(format!("{}: {size}", i18n.get("avalanche-size-heading")));
+        }
+        if let Some(sensitivity) = problem.sensitivity {
+            details.push(format!(
+                "{}: {}",
+                i18n.get("sensitivity-heading"),
+                i18n.get(&format!("sensitivity-{}", variant_id(&sensitivity)))
+            ));
+        }
+        if let Some(distribution) = problem.distribution {
+            details.push(format!(
+                "{}: {}",
+                i18n.get("distribution-heading"),
+                i18n.get(&format!("distribution-{}", variant_id(&distribution)))
+            ));
+        }
+        if let Some(time_of_day) = problem.time_of_day {
+            details.push(format!(
+                "{}: {}",
+                i18n.get("problem-time-of-day-heading"),
+                i18n.get(&format!("time-of-day-{}", variant_id(&time_of_day)))
+            ));
+        }
+        if let Some(trend) = problem.trend {
+            details.push(format!(
+                "{}: {}",
+                i18n.get("trend-heading"),
+                i18n.get(&format!("trend-{}", variant_id(&trend)))
+            ));
+        }
+        if let Some(confidence) = problem.confidence {
+            details.push(format!(
+                "{}: {}",
+                i18n.get("confidence-heading"),
+                i18n.get(&format!("confidence-{}", variant_id(&confidence)))
+            ));
+        }
+        if let Some(description) = translated(&problem.description, i18n, default_language) {
+            details.push(description.to_owned());
+        }
+
+        let text_x = MARGIN + DIAGRAM_SIZE + 16.0;
+        let text_width = CONTENT_WIDTH - DIAGRAM_SIZE - 16.0;
+        pages.paragraph_at(text_x, text_width, &details.join("\n"));
+        pages.y = f32::max(pages.y, diagram_y + DIAGRAM_SIZE) + LINE_HEIGHT;
+    }
+
+    if let Some(recent_observations) =
+        translated(&forecast.recent_observations, i18n, default_language)
+    {
+        pages.heading(&i18n.get("recent-relevant-observations-heading"), 14.0);
+        pages.paragraph(recent_observations);
+    }
+    if let Some(weather_forecast) = translated(&forecast.weather_forecast, i18n, default_language) {
+        pages.heading(&i18n.get("weather-forecast-heading"), 14.0);
+        pages.paragraph(weather_forecast);
+    }
+
+    pages.finish()
+}
+
+/// Convert SVG pages into a single PDF document.
+pub fn render_pdf(pages: &[String]) -> eyre::Result<Vec<u8>> {
+    let mut alloc = Ref::new(1);
+    let catalog_id = alloc.bump();
+    let page_tree_id = alloc.bump();
+    let mut pdf = Pdf::new();
+    let mut page_ids = Vec::with_capacity(pages.len());
+    let svg_name = Name(b"S1");
+
+    for (i, page) in pages.iter().enumerate() {
+        let mut tree = usvg::Tree::from_str(page, &usvg::Options::default())
+            .wrap_err_with(|| format!("Error parsing svg for page {i}"))?;
+        tree.postprocess(usvg::PostProcessingSteps::default(), &FONT_DB);
+        let mut chunk = Chunk::new();
+        let svg_id = alloc;
+        // The svg is written using references from `svg_id`, returning the next unused one.
+        alloc = svg2pdf::convert_tree_into(&tree, svg2pdf::Options::default(), &mut chunk, svg_id);
+        pdf.extend(&chunk);
+
+        let page_id = alloc.bump();
+        let content_id = alloc.bump();
+        let mut pdf_page = pdf.page(page_id);
+        pdf_page.media_box(Rect::new(0.0, 0.0, PAGE_WIDTH, PAGE_HEIGHT));
+        pdf_page.parent(page_tree_id);
+        pdf_page.contents(content_id);
+        pdf_page.resources().x_objects().pair(svg_name, svg_id);
+        pdf_page.finish();
+
+        let mut content = Content::new();
+        content.transform([PAGE_WIDTH, 0.0, 0.0, PAGE_HEIGHT, 0.0, 0.0]);
+        content.x_object(svg_name);
+        pdf.stream(content_id, &content.finish());
+
+        page_ids.push(page_id);
+    }
+
+    pdf.catalog(catalog_id).pages(page_tree_id);
+    let page_count = page_ids.len() as i32;
+    pdf.pages(page_tree_id).kids(page_ids).count(page_count);
+    Ok(pdf.finish())
+}
+
+/// Generate the PDF bulletin for a forecast.
+pub fn generate_pdf(
+    forecast: &Forecast,
+    i18n: &I18nLoader,
+    default_language: &LanguageIdentifier,
+) -> eyre::Result<Vec<u8>> {
+    let pages = generate_pages(forecast, i18n, default_language);
+    render_pdf(&pages).wrap_err("Error rendering forecast pdf")
+}
+
+/// Identifies a PDF generated for a forecast file. Generated PDFs are kept in the blob store
+/// until the file is modified or the application is updated.
+pub struct GeneratedPdfKey<'a> {
+    pub file_name: &'a str,
+    pub modified_time: OffsetDateTime,
+    pub language: String,
+    pub default_language: String,
+}
+
+/// Path of the previously generated PDF for the `key`, if it is still valid.
+pub async fn get_generated(
+    key: &GeneratedPdfKey<'_>,
+    database: &Database,
+    blobs: &BlobStore,
+) -> eyre::Result<Option<PathBuf>> {
+    let Some(record) = sqlx::query!(
+        r#"SELECT modified_time as "modified_time: types::Time", version, blob_hash as "blob_hash: BlobHash" FROM generated_pdfs WHERE file_name=$1 AND language=$2 AND default_language=$3"#,
+        key.file_name,
+        key.language,
+        key.default_language,
+    )
+    .fetch_optional(database)
+    .await?
+    else {
+        return Ok(None);
+    };
+
+    let modified_time: OffsetDateTime = record.modified_time.into();
+    if modified_time != key.modified_time
+        || record.version != version::VERSION
+        || !blobs.contains(&record.blob_hash).await?
+    {
+        return Ok(None);
+    }
+    Ok(Some(blobs.path(&record.blob_hash)))
+}
+
+/// Store the `pdf` generated for the `key`, returning the path of its blob.
+pub async fn store_generated(
+    key: &GeneratedPdfKey<'_>,
+    pdf: Vec<u8>,
+    database: &Database,
+    blobs: &BlobStore,
+) -> eyre::Result<PathBuf> {
+    let blob_hash = blobs.put(pdf.into()).await?;
+    let modified_time = types::Time::from(key.modified_time);
+    sqlx::query!(
+        "INSERT INTO generated_pdfs(file_name, language, default_language, modified_time, version, blob_hash) VALUES($1, $2, $3, $4, $5, $6) ON CONFLICT(file_name, language, default_language) DO UPDATE SET modified_time=excluded.modified_time, version=excluded.version, blob_hash=excluded.blob_hash",
+        key.file_name,
+        key.language,
+        key.default_language,
+        modified_time,
+        version::VERSION,
+        blob_hash,
+    )
+    .execute(database)
+    .await?;
+    Ok(blobs.path(&blob_hash))
+}
+
+#[cfg(test)]
+mod test {
+    use super::wrap_text;
+
+    #[test]
+    fn test_wrap_text() {
+        let lines = wrap_text("The quick brown fox jumps over the lazy dog", 60.0, 10.0);
+        insta::assert_json_snapshot!(lines, @r###"
+        [
+          "The quick",
+          "brown fox",
+          "jumps over",
+          "the lazy",
+          "dog"
+        ]
+        "###);
+    }
+}
@@ -26,15 +26,6 @@ const MATRIX: &[&[Probability]] = &[
 ];
 
 impl Probability {
-    pub fn id(&self) -> &'static str {
-        match self {
-            VeryLikely => "very-likely",
-            Likely => "likely",
-            Possible => "possible",
-            Unlikely => "unlikely",
-        }
-    }
-
     /// Calculate a new [`Probability`].
     pub fn calculate(sensitivity: Sensitivity, distribution: Distribution) -> Self {
         MATRIX[sensitivity as usize][distribution as usize]
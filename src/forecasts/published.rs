@@ -0,0 +1,358 @@
+//! The listing of the published forecast files, which is refreshed in the background by
+//! [`PublishedFilesService`] so that pages don't need to wait for the Google Drive API. The last
+//! successful listing is also stored in the database, so that the site continues to work using
+//! the cached forecasts while Google Drive is unreachable (even across restarts).
+
+use std::{collections::HashMap, sync::RwLock};
+
+use eyre::Context;
+use serde::Serialize;
+use time::OffsetDateTime;
+use tracing::Instrument;
+
+use crate::{
+    aggregators::Aggregators,
+    alerts::Alerts,
+    database::{blob::BlobStore, Database},
+    google_drive::{self, ListFileMetadata},
+    options::GoogleDrive,
+    short_links, types,
+    webhooks::{self, Webhooks},
+};
+
+use super::{get_forecast_data, ForecastSpreadsheetSchemas, RequestedForecastData};
+
+#[derive(Clone)]
+struct Listing {
+    files: Vec<ListFileMetadata>,
+    /// When the listing was fetched, or confirmed to be unchanged.
+    fetched_at: OffsetDateTime,
+    validators: google_drive::Validators,
+}
+
+/// How up to date the listing of the published files is.
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct Freshness {
+    /// When the listing was fetched from Google Drive.
+    #[serde(with = "time::serde::rfc3339::option")]
+    pub fetched_at: Option<OffsetDateTime>,
+    /// Whether the most recent attempt to fetch the listing failed, in which case the previous
+    /// listing and cached forecasts are used, and may be out of date.
+    pub stale: bool,
+}
+
+impl Freshness {
+    /// Time since the listing was fetched.
+    pub fn age(&self) -> Option<time::Duration> {
+        self.fetched_at
+            .map(|fetched_at| OffsetDateTime::now_utc() - fetched_at)
+    }
+}
+
+pub struct PublishedFiles {
+    google_drive: &'static GoogleDrive,
+    client: reqwest::Client,
+    database: Database,
+    /// The most recent successful listing of the published folder.
+    listing: RwLock<Option<Listing>>,
+    /// Whether the most recent attempt to refresh the listing failed.
+    stale: std::sync::atomic::AtomicBool,
+}
+
+impl PublishedFiles {
+    pub fn new(
+        google_drive: &'static GoogleDrive,
+        client: reqwest::Client,
+        database: Database,
+    ) -> Self {
+        Self {
+            google_drive,
+            client,
+            database,
+            listing: RwLock::new(None),
+            stale: std::sync::atomic::AtomicBool::new(false),
+        }
+    }
+
+    /// List the files in the published folder, along with the forecast files uploaded directly
+    /// (see [`list_uploaded_files`]). Uses the listing fetched by the background service, and only
+    /// queries Google Drive if there is no listing yet, falling back to the listing stored in the
+    /// database if Google Drive is unreachable.
+    pub async fn list_files(&self) -> eyre::Result<Vec<ListFileMetadata>> {
+        let mut files = self.list_google_drive_files().await?;
+        files.extend(list_uploaded_files(&self.database).await?);
+        Ok(files)
+    }
+
+    async fn list_google_drive_files(&self) -> eyre::Result<Vec<ListFileMetadata>> {
+        if let Some(listing) = self.listing() {
+            return Ok(listing.files);
+        }
+        match self.refresh().await {
+            Ok(files) => Ok(files),
+            Err(error) => match self.load_stored().await? {
+                Some(listing) => {
+                    tracing::warn!(
+                        "Using stored published files listing from {}: {error:?}",
+                        listing.fetched_at
+                    );
+                    Ok(listing.files)
+                }
+                None => Err(error),
+            },
+        }
+    }
+
+    pub fn freshness(&self) -> Freshness {
+        Freshness {
+            fetched_at: self.listing().map(|listing| listing.fetched_at),
+            stale: self.stale.load(std::sync::atomic::Ordering::Relaxed),
+        }
+    }
+
+    fn listing(&self) -> Option<Listing> {
+        self.listing
+            .read()
+            .expect("Published files lock poisoned")
+            .clone()
+    }
+
+    fn set_listing(&self, listing: Listing) {
+        *self.listing.write().expect("Published files lock poisoned") = Some(listing);
+    }
+
+    /// Fetch the listing of the published folder from Google Drive, replacing the stored one.
+    /// The request is conditional on the current listing having changed, see
+    /// [`google_drive::Validators`].
+    pub async fn refresh(&self) -> eyre::Result<Vec<ListFileMetadata>> {
+        let current = self.listing();
+        let result = match google_drive::Client::new(&self.client, self.google_drive) {
+            Ok(client) => client
+                .list_files(
+                    &self.google_drive.published_folder_id,
+                    current.as_ref().map(|listing| &listing.validators),
+                )
+                .await
+                .wrap_err("Error listing google drive files"),
+            Err(error) => Err(error),
+        };
+        self.stale
+            .store(result.is_err(), std::sync::atomic::Ordering::Relaxed);
+        let fetched_at = OffsetDateTime::now_utc();
+        let listing = match (result?, current) {
+            (google_drive::ListFiles::Modified { files, validators }, _) => Listing {
+                files,
+                fetched_at,
+                validators,
+            },
+            (google_drive::ListFiles::NotModified, Some(current)) => Listing {
+                fetched_at,
+                ..current
+            },
+            (google_drive::ListFiles::NotModified, None) => {
+                eyre::bail!("Listing is not modified, but there is no current listing")
+            }
+        };
+        if let Err(error) = self.store(&listing).await {
+            tracing::error!("Error storing published files listing: {error:?}");
+        }
+        self.set_listing(listing.clone());
+        Ok(listing.files)
+    }
+
+    async fn store(&self, listing: &Listing) -> eyre::Result<()> {
+        let fetched_at: types::Time = listing.fetched_at.into();
+        let files = sqlx::types::Json(&listing.files);
+        sqlx::query!(
+            "INSERT INTO published_files_cache VALUES($1, $2, $3, $4, $5) ON CONFLICT(folder_id) DO UPDATE SET fetched_at=excluded.fetched_at, files=excluded.files, etag=excluded.etag, last_modified=excluded.last_modified",
+            self.google_drive.published_folder_id,
+            fetched_at,
+            files,
+            listing.validators.etag,
+            listing.validators.last_modified,
+        )
+        .execute(&self.database)
+        .await?;
+        Ok(())
+    }
+
+    /// Load the listing stored in the database, and use it until the next successful refresh.
+    async fn load_stored(&self) -> eyre::Result<Option<Listing>> {
+        let Some(record) = sqlx::query!(
+            r#"SELECT fetched_at as "fetched_at: types::Time", files as "files!: sqlx::types::Json<Vec<ListFileMetadata>>", etag, last_modified FROM published_files_cache WHERE folder_id = $1"#,
+            self.google_drive.published_folder_id,
+        )
+        .fetch_optional(&self.database)
+        .await
+        .wrap_err("Error loading stored published files listing")?
+        else {
+            return Ok(None);
+        };
+        let listing = Listing {
+            files: record.files.0,
+            fetched_at: record.fetched_at.into(),
+            validators: google_drive::Validators {
+                etag: record.etag,
+                last_modified: record.last_modified,
+            },
+        };
+        self.set_listing(listing.clone());
+        Ok(Some(listing))
+    }
+}
+
+/// Prefix of the ids of uploaded forecast files, which distinguishes them from Google Drive ids.
+pub const UPLOADED_ID_PREFIX: &str = "upload-";
+
+/// List the forecast files which were uploaded via the admin interface instead of being
+/// published to Google Drive. Their contents are only stored in the blob store, referenced from
+/// the `forecast_files` cache.
+pub async fn list_uploaded_files(database: &Database) -> eyre::Result<Vec<ListFileMetadata>> {
+    let records = sqlx::query!(
+        r#"SELECT id, name, mime_type, modified_time as "modified_time: types::Time" FROM uploaded_forecast_files"#
+    )
+    .fetch_all(database)
+    .await
+    .wrap_err("Error listing uploaded forecast files")?;
+    Ok(records
+        .into_iter()
+        .map(|record| ListFileMetadata {
+            mime_type: record.mime_type,
+            id: record.id,
+            name: record.name,
+            modified_time: record.modified_time.into(),
+        })
+        .collect())
+}
+
+pub struct PublishedFilesServiceConfig {
+    pub interval: std::time::Duration,
+    pub published_files: std::sync::Arc<PublishedFiles>,
+    pub client: reqwest::Client,
+    pub database: Database,
+    pub blobs: std::sync::Arc<BlobStore>,
+    pub forecast_spreadsheet_schemas: &'static ForecastSpreadsheetSchemas,
+    /// Sent the events for forecasts which have been published or updated, see
+    /// [`webhooks::forecast_events`].
+    pub webhooks: Webhooks,
+    /// Sent the forecasts which have been published or updated.
+    pub aggregators: Aggregators,
+    /// See [`crate::options::Options::base_url`].
+    pub base_url: url::Url,
+    /// Notified when refreshing the published files fails repeatedly.
+    pub alerts: Alerts,
+}
+
+/// Name of the [`PublishedFilesService`] task in alerts.
+const ALERTS_TASK: &str = "Google Drive published files refresh";
+
+/// Service for refreshing the published files listing, and the cached forecast spreadsheets
+/// (see [`get_forecast_data`]) of any files that have been modified.
+pub struct PublishedFilesService {
+    config: PublishedFilesServiceConfig,
+}
+
+impl PublishedFilesService {
+    pub fn new(config: PublishedFilesServiceConfig) -> Self {
+        Self { config }
+    }
+
+    async fn refresh(&self) -> eyre::Result<()> {
+        // Compare with the stored listing after a restart, so that forecasts published while the
+        // server was down still send events.
+        let previous = match self.config.published_files.listing() {
+            Some(listing) => Some(listing),
+            None => self
+                .config
+                .published_files
+                .load_stored()
+                .await
+                .unwrap_or_else(|error| {
+                    tracing::warn!("{error:?}");
+                    None
+                }),
+        };
+        let files = match self.config.published_files.refresh().await {
+            Ok(files) => files,
+            Err(error) => {
+                if self.config.published_files.listing().is_none() {
+                    self.config.published_files.load_stored().await?;
+                }
+                match self.config.published_files.freshness().age() {
+                    Some(age) => tracing::warn!(
+                        "Using stale published files listing, last fetched {} ago",
+                        humantime::format_duration(std::time::Duration::from_secs(
+                            age.whole_seconds().unsigned_abs()
+                        ))
+                    ),
+                    None => tracing::warn!("No published files listing available"),
+                }
+                return Err(error);
+            }
+        };
+        let mut short_link_tokens = HashMap::new();
+        for file in files.iter() {
+            match short_links::get_or_create_token(&self.config.database, &file.name).await {
+                Ok(token) => {
+                    short_link_tokens.insert(file.name.clone(), token);
+                }
+                Err(error) => {
+                    tracing::warn!("Error creating short link for {:?}: {error:?}", file.name)
+                }
+            }
+        }
+        if let Some(previous) = previous {
+            for event in webhooks::forecast_events(
+                &previous.files,
+                &files,
+                &short_link_tokens,
+                &self.config.base_url,
+            ) {
+                if let webhooks::Event::ForecastPublished { name, .. }
+                | webhooks::Event::ForecastUpdated { name, .. } = &event
+                {
+                    self.config.aggregators.publish(name).await;
+                }
+                self.config.webhooks.send(event);
+            }
+        }
+        for file in files.iter().filter(|file| file.is_spreadsheet()) {
+            if let Err(error) = get_forecast_data(
+                file,
+                RequestedForecastData::Forecast,
+                &self.config.client,
+                &self.config.database,
+                &self.config.blobs,
+                self.config.published_files.google_drive,
+                self.config.forecast_spreadsheet_schemas,
+            )
+            .await
+            {
+                tracing::warn!("Error prefetching forecast {:?}: {error:?}", file.name);
+            }
+        }
+        Ok(())
+    }
+
+    pub fn spawn(self) {
+        tokio::spawn(
+            async move {
+                tracing::info!("Spawned published files service");
+                loop {
+                    let before_requests_time = std::time::Instant::now();
+                    match self.refresh().await {
+                        Ok(()) => self.config.alerts.success(ALERTS_TASK),
+                        Err(error) => {
+                            tracing::error!("Error refreshing published files: {error:?}");
+                            self.config.alerts.failure(ALERTS_TASK, &error);
+                        }
+                    }
+                    let requests_duration = before_requests_time.elapsed();
+                    tokio::time::sleep(self.config.interval.saturating_sub(requests_duration))
+                        .await;
+                }
+            }
+            .instrument(tracing::error_span!("published_files")),
+        );
+    }
+}
@@ -0,0 +1,199 @@
+//! A glossary of the avalanche terms used in forecasts, so that readers who aren't avalanche
+//! professionals can see what they mean. Each of the [`TERMS`] has the localization messages
+//! `glossary-{id}` (the term as it appears in text) and `glossary-{id}-definition`, which can be
+//! edited for a deployment at `/admin/translations`. The terms are served at
+//! `/glossary.json?lang={language}`, and the `glossary` template filter marks them up in the
+//! forecasts' free text so that the definition is shown as a tooltip.
+
+use axum::{
+    extract::{Query, State},
+    response::{IntoResponse, Response},
+    Extension, Json,
+};
+use i18n_embed::{
+    fluent::{FluentLanguageLoader, NegotiationStrategy},
+    LanguageLoader,
+};
+use serde::{Deserialize, Serialize};
+use unic_langid::LanguageIdentifier;
+
+use crate::{diagrams::escape_xml, i18n::I18nLoader, state::AppState};
+
+/// Ids of the terms in the glossary.
+pub const TERMS: &[&str] = &[
+    "aspect",
+    "elevation-band",
+    "slab",
+    "weak-layer",
+    "persistent-weak-layer",
+    "wind-loading",
+    "cornice",
+    "whumpf",
+    "shooting-cracks",
+    "terrain-trap",
+    "runout",
+    "surface-hoar",
+    "depth-hoar",
+    "crust",
+];
+
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct Term {
+    pub id: &'static str,
+    /// The term, as it appears in text.
+    pub term: String,
+    pub definition: String,
+}
+
+/// The [`TERMS`] in the current language of `i18n`, skipping any which aren't translated.
+pub fn terms(i18n: &FluentLanguageLoader) -> Vec<Term> {
+    TERMS
+        .iter()
+        .filter_map(|id| {
+            let message_id = format!("glossary-{id}");
+            let definition_id = format!("glossary-{id}-definition");
+            (i18n.has(&message_id) && i18n.has(&definition_id)).then(|| Term {
+                id,
+                term: i18n.get(&message_id),
+                definition: i18n.get(&definition_id),
+            })
+        })
+        .collect()
+}
+
+/// A loader for the glossary in the `language`, falling back to the default language.
+pub fn language_loader(i18n: &FluentLanguageLoader, language: &LanguageIdentifier) -> I18nLoader {
+    std::sync::Arc::new(i18n.select_languages_negotiate(
+        std::slice::from_ref(language),
+        NegotiationStrategy::Filtering,
+    ))
+}
+
+/// Whether `c` is part of a word, for matching whole terms.
+fn is_word_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '-' || c == '_'
+}
+
+/// The length in bytes of `term` at the start of `text` if it matches case insensitively.
+fn match_len(text: &str, term: &str) -> Option<usize> {
+    let mut text_chars = text.char_indices();
+    for term_char in term.chars() {
+        let (_, text_char) = text_chars.next()?;
+        if !text_char.to_lowercase().eq(term_char.to_lowercase()) {
+            return None;
+        }
+    }
+    Some(text_chars.next().map_or(text.len(), |(index, _)| index))
+}
+
+/// Wrap the first occurrence of each of the `terms` in the `html` with markup which shows its
+/// definition as a tooltip. Only whole words in text are matched, not the contents of tags, and
+/// longer terms take precedence (e.g. `persistent weak layer` over `weak layer`).
+pub fn annotate(html: &str, terms: &[Term]) -> String {
+    let mut terms: Vec<&Term> = terms.iter().filter(|term| !term.term.is_empty()).collect();
+    terms.sort_by_key(|term| std::cmp::Reverse(term.term.chars().count()));
+    let mut annotated: Vec<&str> = Vec::new();
+    let mut output = String::with_capacity(html.len());
+    let mut in_tag = false;
+    let mut previous: Option<char> = None;
+    let mut index = 0;
+    while let Some(c) = html[index..].chars().next() {
+        if in_tag || c == '<' {
+            in_tag = c != '>';
+            output.push(c);
+            index += c.len_utf8();
+            previous = None;
+            continue;
+        }
+        let at_word_start = !previous.is_some_and(is_word_char);
+        let matched = at_word_start
+            .then(|| {
+                terms.iter().find_map(|term| {
+                    if annotated.contains(&term.id) {
+                        return None;
+                    }
+                    let len = match_len(&html[index..], &term.term)?;
+                    let next = html[index + len..].chars().next();
+                    (!next.is_some_and(is_word_char)).then_some((term, len))
+                })
+            })
+            .flatten();
+        if let Some((term, len)) = matched {
+            output.push_str(&format!(
+                r#"<span class="glossary-term" tabindex="0" data-glossary-term="{}" title="{}">{}</span>"#,
+                term.id,
+                escape_xml(&term.definition),
+                &html[index..index + len]
+            ));
+            annotated.push(term.id);
+            previous = html[..index + len].chars().next_back();
+            index += len;
+            continue;
+        }
+        output.push(c);
+        previous = Some(c);
+        index += c.len_utf8();
+    }
+    output
+}
+
+#[derive(Deserialize)]
+pub struct GlossaryQuery {
+    /// The language of the glossary, defaults to the language of the request.
+    lang: Option<LanguageIdentifier>,
+}
+
+#[derive(Serialize)]
+struct GlossaryResponse {
+    language: String,
+    terms: Vec<Term>,
+}
+
+pub async fn json_handler(
+    Query(query): Query<GlossaryQuery>,
+    Extension(i18n): Extension<I18nLoader>,
+    State(state): State<AppState>,
+) -> Response {
+    let i18n = match &query.lang {
+        Some(language) => language_loader(&state.i18n, language),
+        None => i18n,
+    };
+    Json(GlossaryResponse {
+        language: i18n.current_language().to_string(),
+        terms: terms(&i18n),
+    })
+    .into_response()
+}
+
+#[cfg(test)]
+mod test {
+    use crate::i18n::test_loader;
+
+    use super::{annotate, terms, Term, TERMS};
+
+    fn term(id: &'static str, term: &str) -> Term {
+        Term {
+            id,
+            term: term.to_owned(),
+            definition: format!("Definition of \"{term}\""),
+        }
+    }
+
+    #[test]
+    fn test_terms_translated() {
+        let terms = terms(&test_loader());
+        assert_eq!(terms.len(), TERMS.len());
+    }
+
+    #[test]
+    fn test_annotate() {
+        let terms = [
+            term("weak-layer", "weak layer"),
+            term("persistent-weak-layer", "persistent weak layer"),
+            term("slab", "slab"),
+        ];
+        let html = r#"<p>A <a href="/slab">Slab</a> over a persistent weak layer, slabs and a weak layer.</p>"#;
+        insta::assert_snapshot!(annotate(html, &terms), @r#"<p>A <a href="/slab"><span class="glossary-term" tabindex="0" data-glossary-term="slab" title="Definition of &quot;slab&quot;">Slab</span></a> over a <span class="glossary-term" tabindex="0" data-glossary-term="persistent-weak-layer" title="Definition of &quot;persistent weak layer&quot;">persistent weak layer</span>, slabs and a <span class="glossary-term" tabindex="0" data-glossary-term="weak-layer" title="Definition of &quot;weak layer&quot;">weak layer</span>.</p>"#);
+        assert_eq!(annotate("ქარი", &terms), "ქარი");
+    }
+}
@@ -0,0 +1,779 @@
+//! Client for the Google Drive API, used to list and download the published forecast
+//! spreadsheets, see [`Client`].
+
+use std::time::Duration;
+
+use bytes::{Bytes, BytesMut};
+use futures::{Stream, StreamExt, TryStreamExt};
+use http::{header, HeaderMap, HeaderValue, StatusCode};
+use secrecy::{ExposeSecret, SecretString};
+use serde::{Deserialize, Serialize};
+use tracing::instrument;
+
+use crate::options::GoogleDrive;
+
+pub use service_account::ServiceAccount;
+
+mod service_account;
+
+/// Truncated version of <https://developers.google.com/drive/api/reference/rest/v3/files#File>
+/// that appears to be returned while listing files with [`Client::list_files`].
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ListFileMetadata {
+    /// The MIME type of the file.
+    ///
+    /// Google Drive attempts to automatically detect an appropriate value from uploaded content,
+    /// if no value is provided. The value cannot be changed unless a new revision is uploaded.
+    ///
+    /// If a file is created with a Google Doc MIME type, the uploaded content is imported, if
+    /// possible. The supported import formats are published in the About resource.
+    pub mime_type: String,
+    /// The ID of the file.
+    pub id: String,
+    /// The name of the file. This is not necessarily unique within a folder. Note that for
+    /// immutable items such as the top level folders of shared drives, My Drive root folder, and
+    /// Application Data folder the name is constant.
+    pub name: String,
+    /// The last time the file was modified by anyone.
+    #[serde(with = "time::serde::rfc3339")]
+    pub modified_time: time::OffsetDateTime,
+}
+
+/// MIME types of spreadsheet files (other than Google Sheets) which forecasts can be parsed from.
+pub const SPREADSHEET_MIME_TYPES: &[&str] = &[
+    "application/vnd.oasis.opendocument.spreadsheet",
+    "application/vnd.openxmlformats-officedocument.spreadsheetml.sheet",
+];
+
+/// MIME type of uploaded forecast files containing a CAAML document, which has been imported
+/// using [`forecast_spreadsheet::caaml::import`].
+pub const CAAML_MIME_TYPE: &str = "application/vnd.caaml";
+
+impl ListFileMetadata {
+    pub fn is_google_sheet(&self) -> bool {
+        self.mime_type == "application/vnd.google-apps.spreadsheet"
+    }
+
+    /// Whether this file is a Google Sheet, or an uploaded spreadsheet file (see
+    /// [`SPREADSHEET_MIME_TYPES`]), which a forecast can be parsed from.
+    pub fn is_spreadsheet(&self) -> bool {
+        self.is_google_sheet() || SPREADSHEET_MIME_TYPES.contains(&self.mime_type.as_str())
+    }
+
+    /// Whether this file is a forecast imported from a CAAML document, see
+    /// [`CAAML_MIME_TYPE`].
+    pub fn is_caaml(&self) -> bool {
+        self.mime_type == CAAML_MIME_TYPE
+    }
+
+    /// Whether forecast data is available for this file, either parsed from a spreadsheet or
+    /// imported from CAAML.
+    pub fn has_forecast_data(&self) -> bool {
+        self.is_spreadsheet() || self.is_caaml()
+    }
+}
+
+/// Base URL of the Google Drive files API.
+const FILES_URL: &str = "https://www.googleapis.com/drive/v3/files";
+/// Base URL of the Google Sheets spreadsheets API.
+const SPREADSHEETS_URL: &str = "https://sheets.googleapis.com/v4/spreadsheets";
+/// Maximum number of attempts for a request which fails with a retryable error (see
+/// [`Error::is_retryable`]).
+const MAX_ATTEMPTS: u32 = 4;
+/// Delay before the first retry, doubled for each subsequent retry.
+const RETRY_BASE_DELAY: Duration = Duration::from_millis(500);
+/// Maximum number of files returned per page when listing files.
+const LIST_FILES_PAGE_SIZE: u32 = 1000;
+
+/// An error returned by the Google Drive or Google Sheets API.
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct GoogleDriveError {
+    pub code: u16,
+    #[serde(default)]
+    pub errors: Vec<serde_json::Value>,
+    pub message: String,
+}
+
+impl std::fmt::Display for GoogleDriveError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "code: {}, message: {}", self.code, self.message)
+    }
+}
+
+/// The body of an error response, see
+/// <https://developers.google.com/drive/api/guides/handle-errors>.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum ErrorResponse {
+    Wrapped { error: GoogleDriveError },
+    Error(GoogleDriveError),
+}
+
+impl ErrorResponse {
+    fn into_error(self) -> GoogleDriveError {
+        match self {
+            Self::Wrapped { error } | Self::Error(error) => error,
+        }
+    }
+}
+
+/// An error making a request to the Google Drive (or Google Sheets) API.
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error("Error sending request for {operation}")]
+    Request {
+        operation: &'static str,
+        #[source]
+        source: reqwest::Error,
+    },
+    #[error("Request for {operation} failed with status {status}: {message}")]
+    Status {
+        operation: &'static str,
+        status: StatusCode,
+        /// The message from the body of the response, if it could be read.
+        message: String,
+    },
+    #[error("Error reading response for {operation}")]
+    Response {
+        operation: &'static str,
+        #[source]
+        source: reqwest::Error,
+    },
+    #[error("Unexpected response for {operation}: {message}")]
+    Unexpected {
+        operation: &'static str,
+        message: String,
+    },
+    #[error("Response for {operation} is larger than the limit of {limit} bytes")]
+    TooLarge { operation: &'static str, limit: u64 },
+}
+
+impl Error {
+    /// Whether the request may succeed if it is retried, i.e. it was rate limited (see
+    /// [`Error::is_rate_limited`]), the server had an error (status `5xx`), or the connection
+    /// failed.
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            Self::Request { source, .. } => source.is_connect() || source.is_timeout(),
+            Self::Status { status, .. } => status.is_server_error() || self.is_rate_limited(),
+            Self::Response { .. } | Self::Unexpected { .. } | Self::TooLarge { .. } => false,
+        }
+    }
+
+    /// Whether the request was rejected because the quota of the API key was exceeded, which is
+    /// either status `429`, or `403` with a rate limit reason.
+    pub fn is_rate_limited(&self) -> bool {
+        match self {
+            Self::Status {
+                status, message, ..
+            } => {
+                *status == StatusCode::TOO_MANY_REQUESTS
+                    || (*status == StatusCode::FORBIDDEN
+                        && message.to_lowercase().contains("rate limit"))
+            }
+            _ => false,
+        }
+    }
+}
+
+/// Delay before the retry following the failed `attempt` (starting at `0`), with up to 50%
+/// random jitter so that concurrent requests don't retry in lock step.
+fn retry_delay(attempt: u32) -> Duration {
+    let delay = RETRY_BASE_DELAY * 2u32.pow(attempt);
+    delay + delay.mul_f64(fastrand::f64() * 0.5)
+}
+
+/// The values of the `ETag` and `Last-Modified` headers of a response, used to make a
+/// conditional request which returns `304 Not Modified` if nothing has changed.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct Validators {
+    pub etag: Option<String>,
+    pub last_modified: Option<String>,
+}
+
+impl Validators {
+    fn from_headers(headers: &HeaderMap) -> Self {
+        let header = |name| {
+            headers
+                .get(name)
+                .and_then(|value: &HeaderValue| value.to_str().ok())
+                .map(ToOwned::to_owned)
+        };
+        Self {
+            etag: header(header::ETAG),
+            last_modified: header(header::LAST_MODIFIED),
+        }
+    }
+
+    fn apply(&self, mut request: reqwest::RequestBuilder) -> reqwest::RequestBuilder {
+        if let Some(etag) = &self.etag {
+            request = request.header(header::IF_NONE_MATCH, etag);
+        }
+        if let Some(last_modified) = &self.last_modified {
+            request = request.header(header::IF_MODIFIED_SINCE, last_modified);
+        }
+        request
+    }
+}
+
+/// The result of [`Client::list_files`].
+pub enum ListFiles {
+    Modified {
+        files: Vec<ListFileMetadata>,
+        /// To pass to the next request, see [`Validators`].
+        validators: Validators,
+    },
+    /// The listing is unchanged since the request which returned the validators.
+    NotModified,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ListFilesPage {
+    next_page_token: Option<String>,
+    files: Vec<ListFileMetadata>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ListFilesQuery<'a> {
+    q: &'a str,
+    fields: &'a str,
+    page_size: u32,
+    page_token: Option<&'a str>,
+}
+
+/// How requests are authenticated, see [`GoogleDrive`].
+#[derive(Clone, Copy)]
+enum Credentials<'a> {
+    ApiKey(&'a SecretString),
+    ServiceAccount(&'a ServiceAccount),
+}
+
+/// Client for the Google Drive API (and the Google Sheets API), authenticating using either an
+/// API key or a service account. Requests which fail with a retryable error (see
+/// [`Error::is_retryable`]) are retried with an exponential backoff. Cheap to construct.
+#[derive(Clone, Copy)]
+pub struct Client<'a> {
+    http: &'a reqwest::Client,
+    credentials: Credentials<'a>,
+}
+
+impl<'a> Client<'a> {
+    /// Create a client using the credentials configured in the `options`, preferring the service
+    /// account if both are configured.
+    pub fn new(http: &'a reqwest::Client, options: &'a GoogleDrive) -> eyre::Result<Self> {
+        let credentials = match (options.service_account()?, &options.api_key) {
+            (Some(service_account), _) => Credentials::ServiceAccount(service_account),
+            (None, Some(api_key)) => Credentials::ApiKey(api_key),
+            (None, None) => eyre::bail!(
+                "Either google_drive.api_key or google_drive.service_account_key_file needs to be configured"
+            ),
+        };
+        Ok(Self { http, credentials })
+    }
+
+    async fn authenticate(
+        &self,
+        request: reqwest::RequestBuilder,
+    ) -> Result<reqwest::RequestBuilder, Error> {
+        Ok(match self.credentials {
+            Credentials::ApiKey(api_key) => request.query(&[("key", api_key.expose_secret())]),
+            Credentials::ServiceAccount(service_account) => request.bearer_auth(
+                service_account
+                    .access_token(self.http)
+                    .await?
+                    .expose_secret(),
+            ),
+        })
+    }
+
+    /// Send the `request`, retrying if it fails with a retryable error. Returns an error if the
+    /// response status is not successful (other than `304 Not Modified`).
+    async fn send(
+        &self,
+        operation: &'static str,
+        request: reqwest::RequestBuilder,
+    ) -> Result<reqwest::Response, Error> {
+        let mut attempt = 0;
+        loop {
+            let attempt_request = request.try_clone().ok_or_else(|| Error::Unexpected {
+                operation,
+                message: "Request cannot be retried".to_owned(),
+            })?;
+            let attempt_request = self.authenticate(attempt_request).await?;
+            let result = send_once(operation, attempt_request).await;
+            let error = match result {
+                Ok(response) => return Ok(response),
+                Err(error) => error,
+            };
+            if error.is_rate_limited() {
+                metrics::counter!("google_drive_rate_limited_total", "operation" => operation)
+                    .increment(1);
+            }
+            attempt += 1;
+            if !error.is_retryable() || attempt >= MAX_ATTEMPTS {
+                return Err(error);
+            }
+            let delay = retry_delay(attempt - 1);
+            tracing::warn!(
+                "Retrying {operation} in {}: {error}",
+                humantime::format_duration(delay)
+            );
+            metrics::counter!("google_drive_retries_total", "operation" => operation).increment(1);
+            tokio::time::sleep(delay).await;
+        }
+    }
+
+    /// As per
+    /// [stackoverflow](https://stackoverflow.com/questions/18116152/how-do-i-get-a-file-list-for-a-google-drive-public-hosted-folder),
+    /// obtain a list of the files in a google drive folder, following all the pages of the
+    /// listing. If `validators` from a previous listing are provided, and the listing has not
+    /// changed since, [`ListFiles::NotModified`] is returned. Validators are only returned for a
+    /// listing with a single page, because those of the first page don't cover the changes to
+    /// the following pages.
+    #[instrument(skip_all, fields(folder_id))]
+    pub async fn list_files(
+        &self,
+        folder_id: &str,
+        validators: Option<&Validators>,
+    ) -> Result<ListFiles, Error> {
+        let q = format!("'{folder_id}' in parents and trashed = false");
+        let mut files = Vec::new();
+        let mut page_token: Option<String> = None;
+        let mut new_validators = None;
+        loop {
+            let query = ListFilesQuery {
+                q: &q,
+                fields: "nextPageToken, files(mimeType, id, name, modifiedTime)",
+                page_size: LIST_FILES_PAGE_SIZE,
+                page_token: page_token.as_deref(),
+            };
+            let mut request = self.http.get(FILES_URL).query(&query);
+            // Only the first page is conditional, the following pages are requested when it
+            // has changed.
+            let first_page = new_validators.is_none();
+            if let (true, Some(validators)) = (first_page, validators) {
+                request = validators.apply(request);
+            }
+            let response = self.send("list_files", request).await?;
+            if response.status() == StatusCode::NOT_MODIFIED {
+                if first_page {
+                    return Ok(ListFiles::NotModified);
+                }
+                return Err(Error::Unexpected {
+                    operation: "list_files",
+                    message: "Status 304 Not Modified for a following page".to_owned(),
+                });
+            }
+            if first_page {
+                new_validators = Some(Validators::from_headers(response.headers()));
+            }
+            let page: ListFilesPage = read_json("list_files", response).await?;
+            files.extend(page.files);
+            match page.next_page_token {
+                Some(next_page_token) => page_token = Some(next_page_token),
+                None => break,
+            }
+        }
+        let validators = match page_token {
+            // The listing has more than one page, the next listing needs to be unconditional.
+            Some(_) => Validators::default(),
+            None => new_validators.unwrap_or_default(),
+        };
+        Ok(ListFiles::Modified { files, validators })
+    }
+
+    #[instrument(skip_all, fields(file_id))]
+    pub async fn get_file(&self, file_id: &str) -> Result<File, Error> {
+        let query = GetFileQuery {
+            alt: Some("media"),
+            ..GetFileQuery::default()
+        };
+        let request = self
+            .http
+            .get(format!("{FILES_URL}/{file_id}"))
+            .query(&query);
+        Ok(File {
+            operation: "get_file",
+            response: self.send("get_file", request).await?,
+        })
+    }
+
+    /// Mime type from <https://developers.google.com/drive/api/guides/ref-export-formats>
+    #[instrument(skip_all, fields(file_id))]
+    pub async fn export_file(&self, file_id: &str, mime_type: &str) -> Result<File, Error> {
+        let query = ExportFileQuery { mime_type };
+        let request = self
+            .http
+            .get(format!("{FILES_URL}/{file_id}/export"))
+            .query(&query);
+        Ok(File {
+            operation: "export_file",
+            response: self.send("export_file", request).await?,
+        })
+    }
+
+    /// Read the values of all the sheets of a Google Sheets spreadsheet using
+    /// [values.batchGet](https://developers.google.com/sheets/api/reference/rest/v4/spreadsheets.values/batchGet).
+    /// Dates and times are returned as serial numbers, the same as they are stored in xlsx files.
+    #[instrument(skip_all, fields(spreadsheet_id))]
+    pub async fn get_sheet_values(&self, spreadsheet_id: &str) -> Result<SheetValues, Error> {
+        let operation = "get_sheet_values";
+        let request = self
+            .http
+            .get(format!("{SPREADSHEETS_URL}/{spreadsheet_id}"))
+            .query(&[(
+                "fields",
+                "sheets.properties(sheetId,title),namedRanges(name,range)",
+            )]);
+        let spreadsheet: Spreadsheet =
+            read_json(operation, self.send(operation, request).await?).await?;
+
+        let mut query: Vec<(&str, String)> = vec![
+            ("valueRenderOption", "UNFORMATTED_VALUE".to_owned()),
+            ("dateTimeRenderOption", "SERIAL_NUMBER".to_owned()),
+        ];
+        query.extend(
+            spreadsheet
+                .sheets
+                .iter()
+                .map(|sheet| ("ranges", quote_sheet_title(&sheet.properties.title))),
+        );
+        let request = self
+            .http
+            .get(format!(
+                "{SPREADSHEETS_URL}/{spreadsheet_id}/values:batchGet"
+            ))
+            .query(&query);
+        let response: BatchGetValuesResponse =
+            read_json(operation, self.send(operation, request).await?).await?;
+
+        if response.value_ranges.len() != spreadsheet.sheets.len() {
+            return Err(Error::Unexpected {
+                operation,
+                message: format!(
+                    "Expected {} value ranges in response, got {}",
+                    spreadsheet.sheets.len(),
+                    response.value_ranges.len()
+                ),
+            });
+        }
+
+        let named_ranges = spreadsheet
+            .named_ranges
+            .iter()
+            .filter_map(|named_range| {
+                let sheet = spreadsheet
+                    .sheets
+                    .iter()
+                    .find(|sheet| sheet.properties.sheet_id == named_range.range.sheet_id)?;
+                let position = forecast_spreadsheet::position::CellPosition {
+                    column: named_range.range.start_column_index,
+                    row: named_range.range.start_row_index,
+                };
+                Some((
+                    named_range.name.clone(),
+                    format!("{}!{position}", quote_sheet_title(&sheet.properties.title)),
+                ))
+            })
+            .collect();
+        let sheets = spreadsheet
+            .sheets
+            .into_iter()
+            .zip(response.value_ranges)
+            .map(|(sheet, value_range)| SheetValueRange {
+                title: sheet.properties.title,
+                values: value_range.values,
+            })
+            .collect();
+
+        Ok(SheetValues {
+            sheets,
+            named_ranges,
+        })
+    }
+}
+
+/// Send the `request` once, recording its duration (until the response headers are received)
+/// and status for the metrics. Every request counts towards the quota of the API key.
+async fn send_once(
+    operation: &'static str,
+    request: reqwest::RequestBuilder,
+) -> Result<reqwest::Response, Error> {
+    let start = std::time::Instant::now();
+    let result = request.send().await;
+    metrics::histogram!("google_drive_request_duration_seconds", "operation" => operation)
+        .record(start.elapsed().as_secs_f64());
+    let status = match &result {
+        Ok(response) => response.status().as_u16().to_string(),
+        Err(_) => "error".to_owned(),
+    };
+    metrics::counter!("google_drive_requests_total", "operation" => operation, "status" => status)
+        .increment(1);
+    let response = result.map_err(|source| Error::Request { operation, source })?;
+    let status = response.status();
+    if status.is_success() || status == StatusCode::NOT_MODIFIED {
+        return Ok(response);
+    }
+    let body = response.text().await.unwrap_or_default();
+    Err(Error::Status {
+        operation,
+        status,
+        message: error_message(&body),
+    })
+}
+
+/// The message from the body of an error response, or the body itself if it isn't in the
+/// expected format.
+fn error_message(body: &str) -> String {
+    match serde_json::from_str::<ErrorResponse>(body) {
+        Ok(response) => response.into_error().message,
+        Err(_) => body.chars().take(200).collect(),
+    }
+}
+
+async fn read_json<T: serde::de::DeserializeOwned>(
+    operation: &'static str,
+    response: reqwest::Response,
+) -> Result<T, Error> {
+    response
+        .json()
+        .await
+        .map_err(|source| Error::Response { operation, source })
+}
+
+pub fn get_file_in_list<'a>(
+    file_name: &str,
+    file_list: &'a [ListFileMetadata],
+) -> Option<&'a ListFileMetadata> {
+    file_list
+        .iter()
+        .find(|file_metadata| file_metadata.name == file_name)
+}
+
+pub struct File {
+    operation: &'static str,
+    response: reqwest::Response,
+}
+
+impl File {
+    /// Read the whole file, failing with [`Error::TooLarge`] if it is larger than `limit` bytes.
+    pub async fn bytes(self, limit: u64) -> Result<Bytes, Error> {
+        let mut stream = std::pin::pin!(self.bytes_stream(limit)?);
+        let mut bytes = BytesMut::new();
+        while let Some(chunk) = stream.try_next().await? {
+            bytes.extend_from_slice(&chunk);
+        }
+        Ok(bytes.freeze())
+    }
+
+    /// Stream the file without reading it into memory, failing with [`Error::TooLarge`] as soon
+    /// as it is known to be larger than `limit` bytes.
+    pub fn bytes_stream(
+        self,
+        limit: u64,
+    ) -> Result<impl Stream<Item = Result<Bytes, Error>> + Send + 'static, Error> {
+        let operation = self.operation;
+        if self
+            .response
+            .content_length()
+            .is_some_and(|length| length > limit)
+        {
+            return Err(Error::TooLarge { operation, limit });
+        }
+        let mut received: u64 = 0;
+        Ok(self.response.bytes_stream().map(move |chunk| {
+            let chunk = chunk.map_err(|source| Error::Response { operation, source })?;
+            received += chunk.len() as u64;
+            if received > limit {
+                return Err(Error::TooLarge { operation, limit });
+            }
+            Ok(chunk)
+        }))
+    }
+}
+
+#[derive(Serialize, Default)]
+#[serde(rename_all = "camelCase")]
+#[non_exhaustive]
+struct GetFileQuery<'a> {
+    alt: Option<&'a str>,
+    fields: Option<&'a str>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ExportFileQuery<'a> {
+    mime_type: &'a str,
+}
+
+/// The values of the cells of a Google Sheets spreadsheet, read using
+/// [`Client::get_sheet_values`].
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SheetValues {
+    pub sheets: Vec<SheetValueRange>,
+    /// Pairs of named range name, and the formula for its first cell (e.g. `'Form'!B3`).
+    pub named_ranges: Vec<(String, String)>,
+}
+
+/// The values of a sheet, starting from cell `A1`.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SheetValueRange {
+    pub title: String,
+    pub values: Vec<Vec<serde_json::Value>>,
+}
+
+impl SheetValues {
+    pub fn into_value_sheets(self) -> forecast_spreadsheet::source::ValueSheets {
+        let mut value_sheets = forecast_spreadsheet::source::ValueSheets::new();
+        for sheet in self.sheets {
+            let rows = sheet
+                .values
+                .iter()
+                .map(|row| {
+                    row.iter()
+                        .map(forecast_spreadsheet::source::data_type_from_json)
+                        .collect()
+                })
+                .collect();
+            value_sheets.insert_sheet(sheet.title, rows);
+        }
+        for (name, formula) in self.named_ranges {
+            value_sheets.insert_defined_name(name, formula);
+        }
+        value_sheets
+    }
+}
+
+/// <https://developers.google.com/sheets/api/reference/rest/v4/spreadsheets#Spreadsheet>
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Spreadsheet {
+    sheets: Vec<Sheet>,
+    #[serde(default)]
+    named_ranges: Vec<NamedRange>,
+}
+
+#[derive(Deserialize)]
+struct Sheet {
+    properties: SheetProperties,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct SheetProperties {
+    sheet_id: i64,
+    title: String,
+}
+
+#[derive(Deserialize)]
+struct NamedRange {
+    name: String,
+    range: GridRange,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct GridRange {
+    #[serde(default)]
+    sheet_id: i64,
+    #[serde(default)]
+    start_row_index: u32,
+    #[serde(default)]
+    start_column_index: u32,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct BatchGetValuesResponse {
+    value_ranges: Vec<ValueRange>,
+}
+
+#[derive(Deserialize)]
+struct ValueRange {
+    #[serde(default)]
+    values: Vec<Vec<serde_json::Value>>,
+}
+
+/// Quote a sheet title for use in A1 notation, e.g. `'Forecast Translation'`.
+fn quote_sheet_title(title: &str) -> String {
+    format!("'{}'", title.replace('\'', "''"))
+}
+
+#[cfg(test)]
+mod test {
+    use super::{error_message, retry_delay, Error, ListFilesPage, RETRY_BASE_DELAY};
+    use http::StatusCode;
+    use serde_json::json;
+
+    #[test]
+    fn parse_list_files_page() {
+        let body = json!({
+            "kind": "drive#fileList",
+            "incompleteSearch": false,
+            "files": []
+        });
+        let page: ListFilesPage = serde_json::from_value(body).unwrap();
+        assert!(page.next_page_token.is_none());
+    }
+
+    #[test]
+    fn parse_list_files_page_next_page() {
+        let body = json!({
+            "nextPageToken": "TOKEN",
+            "kind": "drive#fileList",
+            "incompleteSearch": false,
+            "files": []
+        });
+        let page: ListFilesPage = serde_json::from_value(body).unwrap();
+        assert_eq!(page.next_page_token.as_deref(), Some("TOKEN"));
+    }
+
+    #[test]
+    fn parse_error_message() {
+        let body = json!({
+            "code": 500,
+            "errors": [{}],
+            "message": "Some Error Message",
+        });
+        assert_eq!(error_message(&body.to_string()), "Some Error Message");
+        let body = json!({
+            "error": {
+                "code": 403,
+                "errors": [{"reason": "userRateLimitExceeded"}],
+                "message": "User Rate Limit Exceeded",
+            }
+        });
+        assert_eq!(error_message(&body.to_string()), "User Rate Limit Exceeded");
+        assert_eq!(error_message("Bad Gateway"), "Bad Gateway");
+    }
+
+    #[test]
+    fn retryable_errors() {
+        let error = |status, message: &str| Error::Status {
+            operation: "list_files",
+            status,
+            message: message.to_owned(),
+        };
+        assert!(error(StatusCode::TOO_MANY_REQUESTS, "").is_retryable());
+        assert!(error(StatusCode::SERVICE_UNAVAILABLE, "").is_retryable());
+        assert!(!error(StatusCode::NOT_FOUND, "").is_retryable());
+        assert!(error(StatusCode::FORBIDDEN, "User Rate Limit Exceeded").is_retryable());
+        assert!(
+            !error(StatusCode::FORBIDDEN, "The caller does not have permission").is_rate_limited()
+        );
+    }
+
+    #[test]
+    fn retry_delay_increases() {
+        for attempt in 0..3 {
+            let delay = retry_delay(attempt);
+            let base = RETRY_BASE_DELAY * 2u32.pow(attempt);
+            assert!(delay >= base);
+            assert!(delay <= base.mul_f64(1.5));
+        }
+    }
+}
@@ -9,13 +9,17 @@ use i18n_embed::{
     fluent::{fluent_language_loader, FluentLanguageLoader, NegotiationStrategy},
     AssetsMultiplexor, FileSystemAssets, I18nAssets, LanguageLoader, RustEmbedNotifyAssets,
 };
-use once_cell::sync::Lazy;
 use once_cell::sync::OnceCell;
 use rust_embed::RustEmbed;
+use serde::Serialize;
 use std::{any::Any, collections::HashMap, path::PathBuf, sync::Arc};
 use time::OffsetDateTime;
 
-use crate::{state::AppState, user_preferences::UserPreferences};
+use crate::{
+    options::{is_language_enabled, Language, TextDirection},
+    state::AppState,
+    user_preferences::UserPreferences,
+};
 
 #[derive(RustEmbed)]
 #[folder = "i18n/"]
@@ -29,18 +33,6 @@ pub fn try_get_localizations() -> eyre::Result<&'static AssetsMultiplexor> {
         .ok_or_eyre("LOCALIZATIONS have not yet been initialized")
 }
 
-pub static LANGUAGE_DISPLAY_NAMES: Lazy<HashMap<unic_langid::LanguageIdentifier, String>> =
-    Lazy::new(|| {
-        vec![
-            ("en-UK", "English"),
-            ("ka-GE", "ქართული"),
-            ("bg-BG", "български"),
-        ]
-        .into_iter()
-        .map(|(id, name)| (id.parse().unwrap(), name.to_owned()))
-        .collect()
-    });
-
 #[derive(Clone, Debug)]
 pub struct RequestedLanguages(pub Vec<unic_langid::LanguageIdentifier>);
 
@@ -59,13 +51,12 @@ pub fn display_languages(languages: &[unic_langid::LanguageIdentifier]) -> Strin
     format!("[{languages}]")
 }
 
-fn parse_accept_language(accept_language: &HeaderValue) -> RequestedLanguages {
+pub fn parse_accept_language(accept_language: &HeaderValue) -> RequestedLanguages {
     RequestedLanguages(
         accept_language
             .to_str()
             .unwrap_or("")
             .split(',')
-            .into_iter()
             .filter_map(|lang| lang.trim().parse::<unic_langid::LanguageIdentifier>().ok())
             .collect(),
     )
@@ -90,6 +81,47 @@ pub fn negotiate_translated_string<'a>(
     first.and_then(|first| text.get(first).map(|text| (**first, text.as_str())))
 }
 
+/// Select which of the translations in `text` to show, in the same way as the `translated_string`
+/// template function: the best match for the `requested_languages`, otherwise the first
+/// available language in `language_order`.
+pub fn select_translation<'a>(
+    text: &'a HashMap<unic_langid::LanguageIdentifier, String>,
+    requested_languages: &[unic_langid::LanguageIdentifier],
+    language_order: &[unic_langid::LanguageIdentifier],
+) -> Option<(&'a unic_langid::LanguageIdentifier, &'a str)> {
+    let available_languages =
+        order_languages(text.keys().collect(), language_order, |a, l| *a == l);
+    let default_language = *available_languages.first()?;
+    let selected = fluent_langneg::negotiate_languages(
+        requested_languages,
+        &available_languages,
+        Some(&default_language),
+        fluent_langneg::NegotiationStrategy::Filtering,
+    );
+    let language: &unic_langid::LanguageIdentifier = selected.first()?;
+    text.get_key_value(language)
+        .map(|(language, text)| (language, text.as_str()))
+}
+
+/// The name of the `language` in the current language of `i18n` (`language-name-{language}`),
+/// falling back to its native name in `languages`, or its tag.
+pub fn language_name(
+    language: &unic_langid::LanguageIdentifier,
+    i18n: &I18nLoader,
+    languages: &[Language],
+) -> String {
+    let fallback = languages
+        .iter()
+        .find(|other| other.id.language == language.language)
+        .map(|other| other.native_name.clone())
+        .unwrap_or_else(|| language.to_string());
+    message_or(
+        i18n,
+        &format!("language-name-{}", language.language),
+        &fallback,
+    )
+}
+
 pub type I18nLoader = Arc<FluentLanguageLoader>;
 
 /// Returns the loader, and a reload watcher (which we must hold for the duration of the program.
@@ -116,12 +148,11 @@ pub fn initialize(options: &crate::options::I18n) -> eyre::Result<(I18nLoader, B
     let changed_loader = loader.clone();
     let watcher = localizations
         .subscribe_changed(std::sync::Arc::new(move || {
-            if let eyre::Result::Err(error) = (|| {
-                tracing::debug!("Reloading localizations detected change");
-                changed_loader
-                    .reload(localizations)
-                    .wrap_err("Error reloading localizations")
-            })() {
+            tracing::debug!("Reloading localizations detected change");
+            if let Err(error) = changed_loader
+                .reload(localizations)
+                .wrap_err("Error reloading localizations")
+            {
                 tracing::error!("Error autoreloading localizations: {error:?}");
             }
         }))
@@ -130,14 +161,35 @@ pub fn initialize(options: &crate::options::I18n) -> eyre::Result<(I18nLoader, B
     Ok((loader, Box::new(watcher)))
 }
 
-/// Create an ordered version of [`LANGUAGE_DISPLAY_NAMES`].
-pub fn ordered_language_display_names(
+/// A language which users can select, see [`crate::options::Language`].
+#[derive(Serialize, Clone, Debug)]
+pub struct LanguageContext {
+    pub id: unic_langid::LanguageIdentifier,
+    pub native_name: String,
+    pub icon: Option<String>,
+    pub direction: TextDirection,
+}
+
+/// The `languages` which users can select, containing only the `enabled_languages` (or all
+/// languages if `None`), ordered by `language_order`.
+pub fn ordered_languages(
+    languages: &[Language],
     language_order: &[unic_langid::LanguageIdentifier],
-) -> Vec<(unic_langid::LanguageIdentifier, String)> {
+    enabled_languages: Option<&[unic_langid::LanguageIdentifier]>,
+) -> Vec<LanguageContext> {
     order_languages(
-        LANGUAGE_DISPLAY_NAMES.clone().into_iter().collect(),
+        languages
+            .iter()
+            .filter(|language| is_language_enabled(enabled_languages, &language.id))
+            .map(|language| LanguageContext {
+                id: language.id.clone(),
+                native_name: language.native_name.clone(),
+                icon: language.icon.clone(),
+                direction: language.direction(),
+            })
+            .collect(),
         language_order,
-        |(id, _), order_id| id == order_id,
+        |language, order_id| &language.id == order_id,
     )
 }
 
@@ -155,24 +207,39 @@ pub fn order_languages<T>(
             ordered.push(unordered.remove(i));
         }
     }
-    ordered.extend(unordered.into_iter());
+    ordered.extend(unordered);
     ordered
 }
 
-pub fn load_available_languages<'a>(
+/// Load the available languages (restricted to `enabled_languages` if specified), the loader's
+/// fallback language is always loaded.
+pub fn load_available_languages(
     loader: &I18nLoader,
     language_order: &[unic_langid::LanguageIdentifier],
+    enabled_languages: Option<&[unic_langid::LanguageIdentifier]>,
 ) -> eyre::Result<()> {
     let localizations = try_get_localizations()?;
-    let available_languages = loader.available_languages(&*localizations)?;
+    let fallback_language = loader.fallback_language().clone();
+    let available_languages: Vec<_> = loader
+        .available_languages(localizations)?
+        .into_iter()
+        .filter(|language| {
+            language == &fallback_language || is_language_enabled(enabled_languages, language)
+        })
+        .collect();
     let languages = order_languages(available_languages, language_order, |al, l| al == l);
-    loader.load_languages(&*localizations, &languages)?;
+    loader.load_languages(localizations, &languages)?;
 
     let languages_display: String = display_languages(&languages);
     tracing::debug!("Localizations loaded, languages: {languages_display}");
     Ok(())
 }
 
+#[derive(serde::Deserialize)]
+struct LangQuery {
+    lang: Option<unic_langid::LanguageIdentifier>,
+}
+
 pub async fn middleware(
     State(state): State<AppState>,
     headers: HeaderMap,
@@ -185,9 +252,17 @@ pub async fn middleware(
         .expect("Expected user_preferences middleware to be installed before this middleware");
 
     let accept_language = headers.get("Accept-Language").map(parse_accept_language);
-    let requested_languages = preferences
-        .lang
+    // A `lang` query parameter (e.g. for the embedded widgets in [`crate::widget`]) takes priority
+    // over the preferences.
+    let query_lang = request
+        .uri()
+        .query()
+        .and_then(|query| serde_urlencoded::from_str::<LangQuery>(query).ok())
+        .and_then(|query| query.lang);
+    let requested_languages = query_lang
         .as_ref()
+        .or(preferences.lang.as_ref())
+        .filter(|lang| state.options.language_enabled(lang))
         .map(|lang| {
             let mut requested_languages = RequestedLanguages(vec![lang.clone()]);
             if let Some(accept_language) = &accept_language {
@@ -216,7 +291,102 @@ pub async fn middleware(
     next.run(request).await
 }
 
+/// Get a localized message, falling back to `fallback` if the message doesn't exist.
+pub fn message_or(i18n: &I18nLoader, message_id: &str, fallback: &str) -> String {
+    if i18n.has(message_id) {
+        i18n.get(message_id)
+    } else {
+        fallback.to_owned()
+    }
+}
+
+/// The ICU locale for the current language of the `i18n` loader.
+fn icu_locale(i18n: &I18nLoader) -> icu::locid::Locale {
+    let mut language = i18n.current_language();
+    // `UK` is not an ISO 3166 region code, ICU knows the United Kingdom as `GB`.
+    if language.region.as_ref().map(|region| region.as_str()) == Some("UK") {
+        language.region = "GB".parse().ok();
+    }
+    language
+        .to_string()
+        .parse()
+        .unwrap_or(icu::locid::Locale::UND)
+}
+
+fn icu_date(date: time::Date) -> eyre::Result<icu::calendar::Date<icu::calendar::Gregorian>> {
+    Ok(icu::calendar::Date::try_new_gregorian_date(
+        date.year(),
+        date.month() as u8,
+        date.day(),
+    )?)
+}
+
+/// Format the date and time (in its own offset) for the current language of `i18n`, e.g.
+/// `24 January 2023 at 17:00`.
 pub fn format_time(time: OffsetDateTime, i18n: &I18nLoader) -> String {
+    let format = || -> eyre::Result<String> {
+        let options = icu::datetime::options::length::Bag::from_date_time_style(
+            icu::datetime::options::length::Date::Long,
+            icu::datetime::options::length::Time::Short,
+        );
+        let formatter = icu::datetime::TypedDateTimeFormatter::<icu::calendar::Gregorian>::try_new(
+            &icu_locale(i18n).into(),
+            options.into(),
+        )?;
+        let datetime = icu::calendar::DateTime::try_new_gregorian_datetime(
+            time.year(),
+            time.month() as u8,
+            time.day(),
+            time.hour(),
+            time.minute(),
+            time.second(),
+        )?;
+        Ok(formatter.format_to_string(&datetime))
+    };
+    format().unwrap_or_else(|error| {
+        tracing::warn!("Error formatting time {time}: {error}");
+        format_time_fallback(time, i18n)
+    })
+}
+
+/// Format the date for the current language of `i18n`, e.g. `24 Jan 2023`.
+pub fn format_date(date: time::Date, i18n: &I18nLoader) -> String {
+    let format = || -> eyre::Result<String> {
+        let formatter =
+            icu::datetime::TypedDateFormatter::<icu::calendar::Gregorian>::try_new_with_length(
+                &icu_locale(i18n).into(),
+                icu::datetime::options::length::Date::Medium,
+            )?;
+        Ok(formatter.format_to_string(&icu_date(date)?))
+    };
+    format().unwrap_or_else(|error| {
+        tracing::warn!("Error formatting date {date}: {error}");
+        date.to_string()
+    })
+}
+
+/// Format the number rounded to `decimals` decimal places for the current language of `i18n`,
+/// e.g. `1,234.5`.
+pub fn format_number(value: f64, decimals: u8, i18n: &I18nLoader) -> String {
+    let format = || -> eyre::Result<String> {
+        let formatter = icu::decimal::FixedDecimalFormatter::try_new(
+            &icu_locale(i18n).into(),
+            Default::default(),
+        )?;
+        let scale = 10f64.powi(decimals.into());
+        let mut decimal = fixed_decimal::FixedDecimal::from((value * scale).round() as i64);
+        decimal.multiply_pow10(-i16::from(decimals));
+        Ok(formatter.format_to_string(&decimal))
+    };
+    format().unwrap_or_else(|error| {
+        tracing::warn!("Error formatting number {value}: {error}");
+        format!("{value:.*}", usize::from(decimals))
+    })
+}
+
+/// Format the time using the month names from the localization resources, used if ICU is unable
+/// to format the time.
+fn format_time_fallback(time: OffsetDateTime, i18n: &I18nLoader) -> String {
     let day = time.day();
     let month = time.month() as u8;
     let month_name = i18n.get(&format!("month-{month}"));
@@ -225,3 +395,95 @@ pub fn format_time(time: OffsetDateTime, i18n: &I18nLoader) -> String {
     let minute = time.minute();
     format!("{day} {month_name} {year} {hour:0>2}:{minute:0>2}")
 }
+
+/// A loader with the `en-UK` language loaded, for use in tests. [`initialize`] can only be called
+/// once.
+#[cfg(test)]
+pub fn test_loader() -> I18nLoader {
+    static LOADER: once_cell::sync::Lazy<I18nLoader> = once_cell::sync::Lazy::new(|| {
+        let (loader, _) = initialize(&crate::options::I18n::default()).unwrap();
+        load_available_languages(&loader, &["en-UK".parse().unwrap()], None).unwrap();
+        loader
+    });
+    LOADER.clone()
+}
+
+#[cfg(test)]
+mod test {
+    use unic_langid::LanguageIdentifier;
+
+    use super::{
+        format_date, format_number, format_time, ordered_languages, select_translation, test_loader,
+    };
+    use crate::options::{Language, TextDirection};
+
+    fn ids(languages: &[&str]) -> Vec<LanguageIdentifier> {
+        languages
+            .iter()
+            .map(|language| language.parse().unwrap())
+            .collect()
+    }
+
+    fn languages(languages: &[&str]) -> Vec<Language> {
+        ids(languages)
+            .into_iter()
+            .map(|id| Language {
+                native_name: id.to_string(),
+                id,
+                icon: None,
+                direction: None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_ordered_languages_enabled() {
+        let available = languages(&["en-UK", "ka-GE", "ar-EG"]);
+        let order = ids(&["ka-GE", "en-UK"]);
+        let all = ordered_languages(&available, &order, None);
+        let all_ids: Vec<_> = all.iter().map(|language| language.id.clone()).collect();
+        assert_eq!(&all_ids[..2], &order[..]);
+        assert_eq!(all.len(), 3);
+        assert_eq!(all[0].direction, TextDirection::Ltr);
+        assert_eq!(all[2].direction, TextDirection::Rtl);
+
+        let enabled = ids(&["en-UK", "ka-GE"]);
+        let names: Vec<_> = ordered_languages(&available, &order, Some(&enabled))
+            .into_iter()
+            .map(|language| language.id)
+            .collect();
+        assert_eq!(names, order);
+    }
+
+    #[test]
+    fn test_select_translation() {
+        let text: std::collections::HashMap<LanguageIdentifier, String> = [
+            ("en-UK".parse().unwrap(), "English".to_owned()),
+            ("ka-GE".parse().unwrap(), "Georgian".to_owned()),
+        ]
+        .into_iter()
+        .collect();
+        let order = ids(&["ka-GE", "en-UK"]);
+        let (language, value) = select_translation(&text, &ids(&["en-UK"]), &order).unwrap();
+        assert_eq!(language.to_string(), "en-UK");
+        assert_eq!(value, "English");
+        let (language, _) = select_translation(&text, &ids(&["bg-BG"]), &order).unwrap();
+        assert_eq!(language.to_string(), "ka-GE");
+        assert!(select_translation(&Default::default(), &order, &order).is_none());
+    }
+
+    #[test]
+    fn test_format_en_uk() {
+        let loader = test_loader();
+        assert_eq!(
+            format_time(time::macros::datetime!(2023-01-24 17:00 +4), &loader),
+            "24 January 2023, 17:00"
+        );
+        assert_eq!(
+            format_date(time::macros::date!(2023 - 01 - 24), &loader),
+            "24 Jan 2023"
+        );
+        assert_eq!(format_number(1234.56, 1, &loader), "1,234.6");
+        assert_eq!(format_number(-3.0, 0, &loader), "-3");
+    }
+}
@@ -8,8 +8,8 @@ use axum::{
 use axum_extra::routing::TypedPath;
 use color_eyre::Help;
 use eyre::{eyre, Context, ContextCompat};
-use forecast_spreadsheet::{HazardRating, HazardRatingKind};
-use futures::{stream, StreamExt, TryStreamExt};
+use forecast_spreadsheet::{AspectElevation, ElevationBandId, HazardRating, HazardRatingKind};
+use futures::{stream, StreamExt};
 use headers::{CacheControl, HeaderMapExt};
 use i18n_embed::{fluent::FluentLanguageLoader, LanguageLoader};
 use indexmap::IndexMap;
@@ -19,18 +19,27 @@ use unic_langid::LanguageIdentifier;
 use crate::{
     database::Database,
     error::map_eyre_error,
+    forecasters,
     forecasts::{
-        get_forecast_data, parse_forecast_name, Forecast, ForecastContext, ForecastData,
-        ForecastDetails, ForecastFileDetails, ForecastsFilePath, RequestedForecastData,
+        get_cached_forecast, get_forecast_data, parse_forecast_name, published::Freshness,
+        Forecast, ForecastContext, ForecastData, ForecastDetails, ForecastFileDetails,
+        ForecastValidity, ForecastsFilePath, RequestedForecastData,
     },
-    google_drive::{self, ListFileMetadata},
+    google_drive::ListFileMetadata,
     i18n::{self, I18nLoader},
     options::{WeatherMaps, WeatherStationId},
+    page_metadata::PageMetadata,
     state::AppState,
     templates::{render, TemplatesWithContext},
-    user_preferences::{UserPreferences, WindUnit},
+    user_preferences::{TemperatureUnit, UserPreferences, WindUnit},
+    webcams::WebcamContext,
 };
 
+/// Maximum number of forecasts which are loaded concurrently for the index page.
+const FORECAST_CONCURRENCY: usize = 8;
+/// Timeout for loading each forecast for the index page.
+const FORECAST_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(10);
+
 #[derive(Clone, Serialize, Debug)]
 pub enum ForecastFileView {
     /// Forecast file is viewed by being parsed and rendered as HTML.
@@ -39,6 +48,8 @@ pub enum ForecastFileView {
     Json,
     /// Forecast file is downloaded to be viewed (PDF).
     Download,
+    /// Forecast file is parsed, and rendered as a PDF bulletin.
+    Pdf,
 }
 
 #[derive(Clone, Debug)]
@@ -84,7 +95,11 @@ pub struct FormattedForecastDetails {
     pub formatted_time: String,
     #[serde(with = "time::serde::rfc3339")]
     pub time: time::OffsetDateTime,
+    /// Initials of the forecaster, from the file name.
     pub forecaster: String,
+    /// Full name of the forecaster from the [`crate::forecasters`] directory, if they have been
+    /// added.
+    pub forecaster_name: Option<String>,
 }
 
 impl FormattedForecastDetails {
@@ -95,6 +110,7 @@ impl FormattedForecastDetails {
             formatted_time,
             time: details.time,
             forecaster: details.forecaster,
+            forecaster_name: None,
         }
     }
 }
@@ -111,21 +127,58 @@ pub struct IndexSummaryForecastContext {
     pub details: FormattedForecastDetails,
     pub file: ForecastFileContext,
     pub hazard_ratings: IndexMap<HazardRatingKind, HazardRating>,
+    /// See [`Forecast::combined_aspect_elevation`].
+    pub aspect_elevation: IndexMap<ElevationBandId, AspectElevation>,
+    /// See [`Forecast::combined_aspect_elevation_chart`].
+    pub aspect_elevation_chart: Option<String>,
+    /// `None` for forecasts which were not published as a spreadsheet.
+    pub validity: Option<ForecastValidity>,
 }
 
-impl From<IndexFullForecastContext> for IndexSummaryForecastContext {
-    fn from(forecast: IndexFullForecastContext) -> Self {
+impl IndexSummaryForecastContext {
+    fn new(
+        details: FormattedForecastDetails,
+        file: ForecastFileContext,
+        forecast: Option<&Forecast>,
+        validity: Option<ForecastValidity>,
+    ) -> Self {
+        let aspect_elevation_chart = forecast.and_then(|forecast| {
+            forecast
+                .combined_aspect_elevation_chart()
+                .unwrap_or_else(|error| {
+                    tracing::error!("Error creating aspect elevation chart: {error:?}");
+                    None
+                })
+        });
         Self {
-            details: forecast.details,
-            file: forecast.file,
+            details,
+            file,
             hazard_ratings: forecast
-                .forecast
-                .map(|forecast| forecast.forecast.hazard_ratings)
+                .map(|forecast| forecast.hazard_ratings.clone())
+                .unwrap_or_default(),
+            aspect_elevation: forecast
+                .map(Forecast::combined_aspect_elevation)
                 .unwrap_or_default(),
+            aspect_elevation_chart,
+            validity,
         }
     }
 }
 
+impl From<IndexFullForecastContext> for IndexSummaryForecastContext {
+    fn from(forecast: IndexFullForecastContext) -> Self {
+        Self::new(
+            forecast.details,
+            forecast.file,
+            forecast
+                .forecast
+                .as_ref()
+                .map(|forecast| &forecast.forecast),
+            forecast.forecast.as_ref().map(|forecast| forecast.validity),
+        )
+    }
+}
+
 pub struct ForecastAccumulator {
     pub details: FormattedForecastDetails,
     pub files: Vec<ForecastFile>,
@@ -134,8 +187,47 @@ pub struct ForecastAccumulator {
 #[derive(Serialize, Debug)]
 struct WeatherContext {
     wind_unit: WindUnit,
+    temperature_unit: TemperatureUnit,
     weather_maps: WeatherMaps,
     weather_station_ids: Vec<WeatherStationId>,
+    webcams: Vec<WebcamContext>,
+}
+
+/// Areas that forecasts are available for, used to switch [`UserPreferences::area`].
+#[derive(Serialize, Debug)]
+struct AreasContext {
+    /// The area that forecasts are being shown for, or `None` if showing all areas.
+    selected: Option<String>,
+    available: Vec<AreaContext>,
+}
+
+#[derive(Serialize, Debug)]
+struct AreaContext {
+    /// Name of the area used in the forecast file names.
+    id: String,
+    /// Localized name of the area.
+    name: String,
+}
+
+/// How up to date the forecasts are, see [`Freshness`].
+#[derive(Serialize, Debug)]
+struct FreshnessContext {
+    /// Google Drive is currently unreachable and the forecasts may be out of date.
+    stale: bool,
+    formatted_fetched_at: Option<String>,
+    age_seconds: Option<i64>,
+}
+
+impl FreshnessContext {
+    fn format(freshness: Freshness, i18n: &I18nLoader) -> Self {
+        Self {
+            stale: freshness.stale,
+            formatted_fetched_at: freshness
+                .fetched_at
+                .map(|fetched_at| i18n::format_time(fetched_at, i18n)),
+            age_seconds: freshness.age().map(|age| age.whole_seconds()),
+        }
+    }
 }
 
 #[derive(Serialize, Debug)]
@@ -144,6 +236,9 @@ struct IndexContext {
     forecasts: Vec<IndexSummaryForecastContext>,
     errors: Vec<String>,
     weather: WeatherContext,
+    freshness: FreshnessContext,
+    page_metadata: PageMetadata,
+    areas: AreasContext,
 }
 
 pub async fn handler(
@@ -202,14 +297,9 @@ async fn index_context(
     preferences: UserPreferences,
     state: AppState,
 ) -> eyre::Result<IndexContext> {
-    let file_list = google_drive::list_files(
-        &state.options.google_drive.published_folder_id,
-        &state.options.google_drive.api_key,
-        &state.client,
-    )
-    .await
-    .wrap_err("Error listing google drive files")?;
-    let (forecasts, mut errors): (Vec<ForecastAccumulator>, Vec<String>) = file_list
+    let reloadable_options = state.reloadable_options.load_full();
+    let file_list = state.published_files.list_files().await?;
+    let (mut forecasts, mut errors): (Vec<ForecastAccumulator>, Vec<String>) = file_list
         .iter()
         .map(|file| {
             let filename = &file.name;
@@ -250,101 +340,279 @@ async fn index_context(
             acc
         });
 
-    let (mut forecasts, errors_2): (Vec<IndexFullForecastContext>, Vec<String>) =
-        stream::iter(forecasts)
-            .map::<eyre::Result<ForecastAccumulator>, _>(eyre::Result::Ok)
-            .and_then(|forecast_acc| async {
-                tracing::debug!("forecast_acc.files {:?}", forecast_acc.files);
-                let file: ForecastFile = forecast_acc
-                    .files
-                    .iter()
-                    .filter(|file| {
-                        if let Some(language) = &file.details.language {
-                            return language.language == i18n.current_language().language;
-                        }
-                        true
-                    })
-                    .cloned()
-                    .next()
-                    .or_else(|| forecast_acc.files.into_iter().next())
-                    .wrap_err_with(|| {
-                        format!(
-                        "Expected there to be at least one forecast file for this forecast {:#?}",
-                        forecast_acc.details
-                    )
-                    })?;
-
-                let forecast = if file.file.is_google_sheet() {
-                    match get_forecast_data(
-                        &file.file,
-                        RequestedForecastData::Forecast,
-                        &state.client,
-                        &database,
-                        &state.options.google_drive.api_key,
-                        &state.forecast_spreadsheet_schema,
-                    )
-                    .await?
-                    {
-                        ForecastData::Forecast(forecast) => {
-                            let forecast = Forecast::try_new(forecast)?;
-                            let formatted_forecast: ForecastContext = ForecastContext::format(
-                                forecast,
-                                &i18n,
-                                &state.options,
-                                &preferences,
-                            );
-                            Some(formatted_forecast)
-                        }
-                        ForecastData::File(_) => {
-                            return Err(eyre::eyre!("Expected ForecastData::Forecast").into())
-                        }
-                    }
-                } else {
-                    None
-                };
+    forecasts.sort_by_key(|forecast| std::cmp::Reverse(forecast.details.time));
 
-                eyre::Result::Ok(IndexFullForecastContext {
-                    details: forecast_acc.details,
-                    file: file.into(),
-                    forecast,
-                })
-            })
-            .map_err(|error| error.wrap_err("Error converting accumulated forecast"))
-            .fold((Vec::new(), Vec::new()), |mut acc, result| async move {
-                match result {
-                    Ok(ok) => acc.0.push(ok),
-                    Err(error) => acc.1.push(format!("{error:?}")),
-                }
-                acc
+    let mut area_ids: Vec<String> = forecasts
+        .iter()
+        .map(|forecast| forecast.details.area.clone())
+        .collect();
+    area_ids.sort();
+    area_ids.dedup();
+    let selected_area = preferences
+        .area
+        .clone()
+        .filter(|area| area_ids.contains(area));
+    if let Some(area) = &selected_area {
+        forecasts.retain(|forecast| &forecast.details.area == area);
+    }
+    let areas = AreasContext {
+        selected: selected_area,
+        available: area_ids
+            .into_iter()
+            .map(|id| AreaContext {
+                name: i18n::message_or(&i18n, &format!("forecast-area-{}", id.to_lowercase()), &id),
+                id,
             })
-            .await;
-
-    errors.extend(errors_2.into_iter());
+            .collect(),
+    };
 
-    forecasts.sort_by(|a, b| b.details.time.cmp(&a.details.time));
+    // Only the most recent forecast is shown in full, the others are summarised using their
+    // cached parsed forecasts.
+    let mut forecasts = forecasts.into_iter();
+    let latest = forecasts.next();
+    let full_forecast = async {
+        match latest {
+            Some(forecast_acc) => {
+                let details = forecast_acc.details.clone();
+                Some(
+                    with_timeout(
+                        &details,
+                        full_forecast(forecast_acc, &i18n, &database, &preferences, &state),
+                    )
+                    .await,
+                )
+            }
+            None => None,
+        }
+    };
+    let summaries = stream::iter(forecasts)
+        .map(|forecast_acc| async {
+            let details = forecast_acc.details.clone();
+            with_timeout(
+                &details,
+                summary_forecast(forecast_acc, &i18n, &database, &state),
+            )
+            .await
+        })
+        .buffer_unordered(FORECAST_CONCURRENCY)
+        .collect::<Vec<_>>();
+    let (full_forecast, summaries) = futures::join!(full_forecast, summaries);
 
-    let current_forecast = forecasts.first().and_then(|forecast| {
-        let f = &forecast.forecast.as_ref()?.forecast;
-        if f.is_current() {
-            Some(forecast.clone())
-        } else {
+    let mut forecasts: Vec<IndexSummaryForecastContext> = Vec::with_capacity(summaries.len() + 1);
+    let current_forecast = match full_forecast {
+        Some(Ok(full_forecast)) => {
+            forecasts.push(full_forecast.clone().into());
+            let is_current = full_forecast
+                .forecast
+                .as_ref()
+                .is_some_and(|forecast| forecast.forecast.is_current());
+            is_current.then_some(full_forecast)
+        }
+        Some(Err(error)) => {
+            errors.push(format!("{error:?}"));
             None
         }
-    });
+        None => None,
+    };
+    for summary in summaries {
+        match summary {
+            Ok(summary) => forecasts.push(summary),
+            Err(error) => errors.push(format!("{error:?}")),
+        }
+    }
+    forecasts.sort_by_key(|forecast| std::cmp::Reverse(forecast.details.time));
 
-    let forecasts = forecasts
-        .into_iter()
-        .map(IndexSummaryForecastContext::from)
-        .collect();
+    match forecasters::names(&database).await {
+        Ok(names) => {
+            for forecast in &mut forecasts {
+                forecast.details.forecaster_name = names.get(&forecast.details.forecaster).cloned();
+            }
+        }
+        Err(error) => tracing::error!("Error getting forecaster names: {error:?}"),
+    }
+
+    // Every forecast after the first for an area has been superseded by a newer one.
+    let mut seen_areas = std::collections::HashSet::new();
+    for forecast in &mut forecasts {
+        if !seen_areas.insert(forecast.details.area.clone()) && forecast.validity.is_some() {
+            forecast.validity = Some(ForecastValidity::Superseded);
+        }
+    }
+
+    let page_metadata = PageMetadata::index(
+        &i18n,
+        state.options,
+        current_forecast
+            .as_ref()
+            .and_then(|forecast| forecast.forecast.as_ref()?.page_metadata.as_ref()),
+    );
 
     Ok(IndexContext {
         current_forecast,
         forecasts,
         errors,
+        freshness: FreshnessContext::format(state.published_files.freshness(), &i18n),
+        page_metadata,
+        areas,
         weather: WeatherContext {
             wind_unit: preferences.wind_unit.unwrap_or_default(),
+            temperature_unit: preferences.temperature_unit.unwrap_or_default(),
             weather_station_ids: state.options.weather_stations.keys().cloned().collect(),
-            weather_maps: state.options.weather_maps.clone(),
+            weather_maps: reloadable_options.weather_maps.clone(),
+            webcams: WebcamContext::from_options(&reloadable_options),
         },
     })
 }
+
+/// Select the file for the forecast in the user's language, or otherwise the first file.
+fn select_file(forecast_acc: ForecastAccumulator, i18n: &I18nLoader) -> eyre::Result<ForecastFile> {
+    tracing::debug!("forecast_acc.files {:?}", forecast_acc.files);
+    let position = forecast_acc
+        .files
+        .iter()
+        .position(|file| match &file.details.language {
+            Some(language) => language.language == i18n.current_language().language,
+            None => true,
+        })
+        .unwrap_or(0);
+    forecast_acc
+        .files
+        .into_iter()
+        .nth(position)
+        .wrap_err_with(|| {
+            format!(
+                "Expected there to be at least one forecast file for this forecast {:#?}",
+                forecast_acc.details
+            )
+        })
+}
+
+/// Load the forecast to be shown in full on the index page.
+async fn full_forecast(
+    forecast_acc: ForecastAccumulator,
+    i18n: &I18nLoader,
+    database: &Database,
+    preferences: &UserPreferences,
+    state: &AppState,
+) -> eyre::Result<IndexFullForecastContext> {
+    let details = forecast_acc.details.clone();
+    let file = select_file(forecast_acc, i18n)?;
+    let forecast = if file.file.has_forecast_data() {
+        let reloadable_options = state.reloadable_options.load_full();
+        match get_forecast_data(
+            &file.file,
+            RequestedForecastData::Forecast,
+            &state.client,
+            database,
+            &state.blobs,
+            &state.options.google_drive,
+            state.forecast_spreadsheet_schemas,
+        )
+        .await?
+        {
+            ForecastData::Forecast(forecast) => {
+                let forecast = Forecast::try_new(forecast)?;
+                let formatted_forecast: ForecastContext =
+                    ForecastContext::format(forecast, i18n, &reloadable_options, preferences)
+                        .with_weather_model(database, state.forecast_spreadsheet_schema)
+                        .await
+                        .with_machine_translations(
+                            &state.client,
+                            database,
+                            state.options,
+                            &reloadable_options,
+                        )
+                        .await
+                        .with_localized_text(i18n, state.options, &reloadable_options)
+                        .with_page_metadata(
+                            &file.file.name,
+                            i18n,
+                            state.options,
+                            &reloadable_options,
+                        );
+                Some(formatted_forecast)
+            }
+            ForecastData::File(_) => eyre::bail!("Expected ForecastData::Forecast"),
+        }
+    } else {
+        None
+    };
+
+    Ok(IndexFullForecastContext {
+        details,
+        file: file.into(),
+        forecast,
+    })
+}
+
+/// Load the summary of a forecast for the list of forecasts on the index page, using the cached
+/// parsed forecast if it is available.
+async fn summary_forecast(
+    forecast_acc: ForecastAccumulator,
+    i18n: &I18nLoader,
+    database: &Database,
+    state: &AppState,
+) -> eyre::Result<IndexSummaryForecastContext> {
+    let details = forecast_acc.details.clone();
+    let file = select_file(forecast_acc, i18n)?;
+    let forecast = if file.file.has_forecast_data() {
+        let forecast =
+            match get_cached_forecast(&file.file, database, state.forecast_spreadsheet_schemas)
+                .await?
+            {
+                Some(forecast) => forecast,
+                None => match get_forecast_data(
+                    &file.file,
+                    RequestedForecastData::Forecast,
+                    &state.client,
+                    database,
+                    &state.blobs,
+                    &state.options.google_drive,
+                    state.forecast_spreadsheet_schemas,
+                )
+                .await?
+                {
+                    ForecastData::Forecast(forecast) => forecast,
+                    ForecastData::File(_) => eyre::bail!("Expected ForecastData::Forecast"),
+                },
+            };
+        Some(Forecast::try_new(forecast)?)
+    } else {
+        None
+    };
+    let validity = forecast.as_ref().map(|forecast| {
+        forecast.validity(
+            time::OffsetDateTime::now_utc(),
+            state.reloadable_options.load().forecast_expiring_soon,
+        )
+    });
+
+    Ok(IndexSummaryForecastContext::new(
+        details,
+        file.into(),
+        forecast.as_ref(),
+        validity,
+    ))
+}
+
+/// Fail loading the forecast with `details` if it takes longer than [`FORECAST_TIMEOUT`], so
+/// that one slow file doesn't hold up the page.
+async fn with_timeout<T>(
+    details: &FormattedForecastDetails,
+    future: impl std::future::Future<Output = eyre::Result<T>>,
+) -> eyre::Result<T> {
+    tokio::time::timeout(FORECAST_TIMEOUT, future)
+        .await
+        .unwrap_or_else(|_| {
+            Err(eyre!(
+                "Timed out after {} loading forecast",
+                humantime::format_duration(FORECAST_TIMEOUT)
+            ))
+        })
+        .wrap_err_with(|| {
+            format!(
+                "Error loading forecast for {} at {}",
+                details.area, details.formatted_time
+            )
+        })
+}
@@ -15,8 +15,7 @@ impl IsBot {
     }
 }
 
-static BOTS: once_cell::sync::Lazy<isbot::Bots> =
-    once_cell::sync::Lazy::new(|| isbot::Bots::default());
+static BOTS: once_cell::sync::Lazy<isbot::Bots> = once_cell::sync::Lazy::new(isbot::Bots::default);
 
 pub fn is_bot(headers: &HeaderMap) -> bool {
     headers
@@ -0,0 +1,237 @@
+//! Machine translation of the free-text sections of forecasts into languages that the forecaster
+//! did not write them in, see [`crate::options::MachineTranslation`]. Translations are cached in
+//! the database so that each text is only translated once.
+
+use std::collections::{HashMap, HashSet};
+
+use eyre::{Context, ContextCompat};
+use secrecy::ExposeSecret;
+use serde::{Deserialize, Serialize};
+use unic_langid::LanguageIdentifier;
+
+use crate::{
+    database::Database,
+    i18n,
+    options::{MachineTranslation, MachineTranslationProvider, Options},
+    types,
+};
+
+/// The languages of each free-text section of a [`crate::forecasts::Forecast`] which were
+/// machine translated.
+#[derive(Debug, Default, Serialize, Deserialize, Clone)]
+pub struct MachineTranslated {
+    pub description: HashSet<LanguageIdentifier>,
+    pub recent_observations: HashSet<LanguageIdentifier>,
+    pub weather_forecast: HashSet<LanguageIdentifier>,
+}
+
+/// The languages to translate into, see [`MachineTranslation::languages`].
+fn target_languages<'a>(
+    config: &'a MachineTranslation,
+    options: &'a Options,
+    default_language_order: &'a [LanguageIdentifier],
+) -> &'a [LanguageIdentifier] {
+    config
+        .languages
+        .as_deref()
+        .or(options.enabled_languages.as_deref())
+        .unwrap_or(default_language_order)
+}
+
+/// Fill in the languages missing from `text` using machine translation, returning the languages
+/// which were translated. The source is the first available language in
+/// `default_language_order` (see [`Options::default_language_order`]). Languages which fail to
+/// translate are left missing.
+pub async fn fill_missing(
+    client: &reqwest::Client,
+    database: &Database,
+    options: &Options,
+    default_language_order: &[LanguageIdentifier],
+    config: &MachineTranslation,
+    text: &mut HashMap<LanguageIdentifier, String>,
+) -> HashSet<LanguageIdentifier> {
+    let mut translated = HashSet::new();
+    let available: Vec<LanguageIdentifier> = text
+        .iter()
+        .filter(|(_, value)| !value.trim().is_empty())
+        .map(|(language, _)| language.clone())
+        .collect();
+    let Some(source_language) =
+        i18n::order_languages(available, default_language_order, |a, b| a == b)
+            .into_iter()
+            .next()
+    else {
+        return translated;
+    };
+    let source_text = text[&source_language].clone();
+
+    for target_language in target_languages(config, options, default_language_order) {
+        if text
+            .get(target_language)
+            .is_some_and(|value| !value.trim().is_empty())
+        {
+            continue;
+        }
+        match translate_cached(
+            client,
+            database,
+            config,
+            &source_text,
+            &source_language,
+            target_language,
+        )
+        .await
+        {
+            Ok(value) => {
+                text.insert(target_language.clone(), value);
+                translated.insert(target_language.clone());
+            }
+            Err(error) => tracing::error!(
+                "Error translating text from {source_language} to {target_language}: {error:?}"
+            ),
+        }
+    }
+    translated
+}
+
+/// Translate the `text`, using the cached translation if there is one.
+async fn translate_cached(
+    client: &reqwest::Client,
+    database: &Database,
+    config: &MachineTranslation,
+    text: &str,
+    source_language: &LanguageIdentifier,
+    target_language: &LanguageIdentifier,
+) -> eyre::Result<String> {
+    let source = source_language.to_string();
+    let target = target_language.to_string();
+    if let Some(translated) = sqlx::query_scalar!(
+        "SELECT translated_text FROM machine_translation_cache WHERE source_language = $1 AND target_language = $2 AND source_text = $3",
+        source,
+        target,
+        text,
+    )
+    .fetch_optional(database)
+    .await
+    .wrap_err("Error fetching cached machine translation")?
+    {
+        return Ok(translated);
+    }
+
+    let translated = translate(client, config, text, source_language, target_language).await?;
+    let translated_at = types::Time::now_utc();
+    sqlx::query!(
+        "INSERT INTO machine_translation_cache VALUES($1, $2, $3, $4, $5) ON CONFLICT(source_language, target_language, source_text) DO UPDATE SET translated_text=excluded.translated_text, translated_at=excluded.translated_at",
+        source,
+        target,
+        text,
+        translated,
+        translated_at,
+    )
+    .execute(database)
+    .await
+    .wrap_err("Error storing cached machine translation")?;
+    Ok(translated)
+}
+
+/// Translate the `text` using the configured provider.
+async fn translate(
+    client: &reqwest::Client,
+    config: &MachineTranslation,
+    text: &str,
+    source_language: &LanguageIdentifier,
+    target_language: &LanguageIdentifier,
+) -> eyre::Result<String> {
+    match &config.provider {
+        MachineTranslationProvider::DeepL { api_key, url } => {
+            #[derive(Deserialize)]
+            struct Translation {
+                text: String,
+            }
+            #[derive(Deserialize)]
+            struct Response {
+                translations: Vec<Translation>,
+            }
+            let response: Response = client
+                .post(url.join("v2/translate")?)
+                .header(
+                    http::header::AUTHORIZATION,
+                    format!("DeepL-Auth-Key {}", api_key.expose_secret()),
+                )
+                .json(&serde_json::json!({
+                    "text": [text],
+                    "source_lang": deepl_language(source_language, false),
+                    "target_lang": deepl_language(target_language, true),
+                }))
+                .send()
+                .await?
+                .error_for_status()
+                .wrap_err("Status code of response is an error")?
+                .json()
+                .await
+                .wrap_err("Error deserializing response body")?;
+            Ok(response
+                .translations
+                .into_iter()
+                .next()
+                .wrap_err("Expected a translation in the response")?
+                .text)
+        }
+        MachineTranslationProvider::LibreTranslate { url, api_key } => {
+            #[derive(Deserialize)]
+            #[serde(rename_all = "camelCase")]
+            struct Response {
+                translated_text: String,
+            }
+            let response: Response = client
+                .post(url.join("translate")?)
+                .json(&serde_json::json!({
+                    "q": text,
+                    "source": source_language.language.as_str(),
+                    "target": target_language.language.as_str(),
+                    "format": "text",
+                    "api_key": api_key.as_ref().map(|api_key| api_key.expose_secret()),
+                }))
+                .send()
+                .await?
+                .error_for_status()
+                .wrap_err("Status code of response is an error")?
+                .json()
+                .await
+                .wrap_err("Error deserializing response body")?;
+            Ok(response.translated_text)
+        }
+    }
+}
+
+/// The DeepL language code for the `language`. DeepL requires a regional variant for some target
+/// languages (e.g. `EN-GB`), but only accepts the language for source languages.
+fn deepl_language(language: &LanguageIdentifier, target: bool) -> String {
+    let code = language.language.as_str().to_uppercase();
+    if !target {
+        return code;
+    }
+    let region = language.region.as_ref().map(|region| region.as_str());
+    match (code.as_str(), region) {
+        ("EN", Some("US")) => "EN-US".to_owned(),
+        ("EN", _) => "EN-GB".to_owned(),
+        ("PT", Some("BR")) => "PT-BR".to_owned(),
+        ("PT", _) => "PT-PT".to_owned(),
+        _ => code,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::deepl_language;
+
+    #[test]
+    fn test_deepl_language() {
+        let language = |language: &str| language.parse().unwrap();
+        assert_eq!(deepl_language(&language("en-UK"), true), "EN-GB");
+        assert_eq!(deepl_language(&language("en-US"), true), "EN-US");
+        assert_eq!(deepl_language(&language("en-UK"), false), "EN");
+        assert_eq!(deepl_language(&language("ka-GE"), true), "KA");
+        assert_eq!(deepl_language(&language("pt"), true), "PT-PT");
+    }
+}
@@ -1,3 +1,6 @@
+// Handlers return `axum::response::Result`, with a `Response` as the error.
+#![allow(clippy::result_large_err)]
+
 use axum::{
     handler::HandlerWithoutStateExt,
     http::{header, StatusCode, Uri},
@@ -8,51 +11,82 @@ use axum::{
 };
 use axum_extra::routing::RouterExt;
 use bytes::Bytes;
-use error::map_std_error;
+use error::{map_eyre_error, map_std_error};
 use eyre::Context;
 use rust_embed::RustEmbed;
 use std::marker::PhantomData;
 use templates::TemplatesWithContext;
-use tower_http::{services::ServeDir, trace::TraceLayer};
+use tower_http::{compression::CompressionLayer, services::ServeDir, trace::TraceLayer};
 
 use crate::{
     analytics::CompactionConfig,
     current_weather::{
         CurrentWeatherCacheService, CurrentWeatherCacheServiceConfig, CurrentWeatherService,
     },
-    database::backup,
-    forecasts::{ForecastSpreadsheetSchema, GUDUAURI_FORECAST_SCHEMA},
+    database::{backup, blob::BlobStore, maintenance, Database},
+    forecasts::{
+        published::{PublishedFiles, PublishedFilesService, PublishedFilesServiceConfig},
+        ForecastSpreadsheetSchema, ForecastSpreadsheetSchemas, GUDAURI_FORECAST_SCHEMA_JSON,
+    },
     options::Options,
+    shutdown::ShutdownController,
     state::AppState,
     templates::Templates,
+    weather_forecast::{WeatherForecastCacheService, WeatherForecastCacheServiceConfig},
 };
 
 mod admin;
+mod aggregators;
+mod alerts;
 mod analytics;
+mod api;
+mod areas;
+mod assets;
 mod auth;
 mod cache_control;
+mod client_ip;
 mod current_weather;
 mod database;
 mod diagrams;
 mod disclaimer;
+mod education;
+mod email;
 mod error;
 mod forecast_areas;
+mod forecasters;
 mod forecasts;
 mod fs;
+mod glossary;
 mod google_drive;
 mod i18n;
 mod index;
 mod isbot;
+mod machine_translation;
+mod news;
 mod observations;
 mod options;
+mod page_metadata;
+mod pages;
+mod prometheus;
+mod route_exposure;
+mod search;
 mod serde;
+mod short_links;
+mod shutdown;
 mod state;
+mod statistics;
+mod status;
 mod templates;
+mod tls;
 mod types;
 mod user_preferences;
-mod utilities;
+mod users;
 mod version;
 mod weather;
+mod weather_forecast;
+mod webcams;
+mod webhooks;
+mod widget;
 
 #[tokio::main]
 async fn main() -> eyre::Result<()> {
@@ -60,6 +94,31 @@ async fn main() -> eyre::Result<()> {
 
     let options: &'static Options = Box::leak(Box::new(Options::initialize().await?));
 
+    let mut schemas: Vec<ForecastSpreadsheetSchema> =
+        Vec::with_capacity(options.forecast_spreadsheet_schemas.len() + 1);
+    if let Some(schema_path) = &options.forecast_spreadsheet_schema {
+        schemas.push(read_forecast_spreadsheet_schema(schema_path).await?);
+    } else {
+        schemas.push(
+            serde_json::from_str(GUDAURI_FORECAST_SCHEMA_JSON)
+                .wrap_err("Error parsing default forecast spreadsheet schema")?,
+        );
+    }
+    for schema_path in &options.forecast_spreadsheet_schemas {
+        schemas.push(read_forecast_spreadsheet_schema(schema_path).await?);
+    }
+    for schema in &schemas {
+        schema.validate()?;
+    }
+    options.validate(&schemas)?;
+    if check_config() {
+        println!("Configuration is valid");
+        return Ok(());
+    }
+
+    let metrics_handle =
+        Option::transpose(options.metrics.as_ref().map(|_| prometheus::initialize()))?;
+
     fs::create_dir_if_not_exists(&options.data_dir)
         .wrap_err_with(|| format!("Unable to create data directory {:?}", options.data_dir))?;
 
@@ -78,8 +137,12 @@ async fn main() -> eyre::Result<()> {
 
     let (i18n, _watcher_guard) =
         i18n::initialize(&options.i18n).wrap_err("Error initializing i18n")?;
-    crate::i18n::load_available_languages(&i18n, &options.default_language_order)
-        .wrap_err("Error loading languages")?;
+    crate::i18n::load_available_languages(
+        &i18n,
+        &options.default_language_order,
+        options.enabled_languages.as_deref(),
+    )
+    .wrap_err("Error loading languages")?;
 
     let templates = Templates::initialize(&options.templates)?;
 
@@ -87,69 +150,184 @@ async fn main() -> eyre::Result<()> {
         .await
         .wrap_err("Error initializing database")?;
 
-    if let Some(backup) = &options.backup {
-        backup::spawn_backup_task(backup::Config {
-            client: client.clone(),
-            backup,
-            aws_secret_access_key: &backup.aws_secret_access_key,
-            database: database.clone(),
-        });
+    users::bootstrap_admin(&database, options.admin_password_hash.as_ref())
+        .await
+        .wrap_err("Error creating the admin user")?;
+
+    let blobs = std::sync::Arc::new(
+        BlobStore::new(&options.data_dir, database.clone())
+            .wrap_err("Error creating blobs directory")?,
+    );
+    observations::migrate_photos_to_blobs(&database, &blobs)
+        .await
+        .wrap_err("Error moving observation photos into the blob store")?;
+    forecasts::migrate_uploaded_files_to_blobs(&database, &blobs)
+        .await
+        .wrap_err("Error moving uploaded forecast files into the blob store")?;
+    // Forecast files were previously stored in this directory, they are now fetched again into the
+    // blob store. It is left for the operator to remove, in case some of the files can no longer
+    // be fetched from Google Drive.
+    let legacy_forecast_files = options.data_dir.join("forecast_files");
+    if legacy_forecast_files.is_dir() {
+        tracing::warn!(
+            "The directory {legacy_forecast_files:?} is no longer used, forecast files are now stored in the blob store. It can be removed once the forecasts have been fetched again."
+        );
     }
 
-    analytics::spawn_compaction_task(CompactionConfig {
-        schedule: options.analytics.compaction_schedule.clone(),
-        database: database.clone(),
-    });
+    let shutdown_controller = ShutdownController::new();
+
+    let alerts = alerts::Alerts::new(options.alerts.as_ref(), client.clone(), &options.base_url());
+
+    for config in backup::Config::all(options, client.clone(), database.clone(), blobs.clone()) {
+        backup::spawn_backup_task(config, shutdown_controller.subscribe(), alerts.clone());
+    }
+
+    maintenance::spawn_maintenance_task(database.clone(), blobs.clone(), &options.maintenance);
+
+    analytics::spawn_compaction_task(
+        CompactionConfig {
+            schedule: options.analytics.compaction_schedule.clone(),
+            window: options.analytics.compaction_window(),
+            keep: options.analytics.compaction_keep(),
+            database: database.clone(),
+            retention: options.analytics.retention.clone(),
+        },
+        shutdown_controller.subscribe(),
+    );
+
+    let reloadable_options = options::reloadable_options_handle(options);
 
     let (analytics_sx, analytics_rx) = analytics::channel();
     let database_analytics = database.clone();
+    let analytics_reloadable_options = reloadable_options.clone();
+    let analytics_shutdown = shutdown_controller.subscribe();
     tokio::spawn(async move {
         analytics::process_analytics(
             database_analytics,
             analytics_rx,
-            options.analytics.event_batch_rate,
+            analytics_reloadable_options,
+            analytics_shutdown,
         )
         .await
     });
 
+    let webhooks = webhooks::spawn(
+        &options.webhooks,
+        client.clone(),
+        shutdown_controller.subscribe(),
+    );
+    webhooks::spawn_weather_station_stale_check(
+        &options.weather_stations,
+        options.webhooks.weather_station_stale_minutes,
+        database.clone(),
+        webhooks.clone(),
+        shutdown_controller.subscribe(),
+    );
+
     let current_weather = std::sync::Arc::new(CurrentWeatherService::new(
         database.clone(),
         options.weather_stations.clone(),
     ));
 
     CurrentWeatherCacheService::try_new(CurrentWeatherCacheServiceConfig {
-        interval: std::time::Duration::from_secs(60),
-        each_station_interval: std::time::Duration::from_secs(2),
+        interval: current_weather::CACHE_INTERVAL,
+        each_station_interval: current_weather::CACHE_EACH_STATION_INTERVAL,
         weather_stations: &options.weather_stations,
         client: client.clone(),
         database: database.clone(),
+        alerts: alerts.clone(),
     })
     .wrap_err("Unable to create CurrentWeatherCacheService")?
+    .spawn(shutdown_controller.subscribe());
+
+    WeatherForecastCacheService::new(WeatherForecastCacheServiceConfig {
+        interval: std::time::Duration::from_secs(60 * 60),
+        locations: &options.weather_forecasts,
+        client: client.clone(),
+        database: database.clone(),
+    })
     .spawn();
 
+    let forecast_spreadsheet_schemas: &'static ForecastSpreadsheetSchemas =
+        Box::leak(Box::new(ForecastSpreadsheetSchemas::new(schemas)));
     let forecast_spreadsheet_schema: &'static ForecastSpreadsheetSchema =
-        if let Some(schema_path) = &options.forecast_spreadsheet_schema {
-            let schema: ForecastSpreadsheetSchema =
-                serde_json::from_str(&tokio::fs::read_to_string(schema_path).await.wrap_err_with(
-                    || format!("Error reading forecast spreadsheet schema file {schema_path:?}"),
-                )?)
-                .wrap_err("Error parsing forecast spreadsheet schema")?;
-            Box::leak(Box::new(schema))
-        } else {
-            &*GUDUAURI_FORECAST_SCHEMA
-        };
+        forecast_spreadsheet_schemas
+            .latest()
+            .expect("Expected at least one forecast spreadsheet schema");
+
+    let published_files = std::sync::Arc::new(PublishedFiles::new(
+        &options.google_drive,
+        client.clone(),
+        database.clone(),
+    ));
+    let aggregators = aggregators::spawn(
+        aggregators::Config {
+            aggregators: &options.aggregators,
+            client: client.clone(),
+            database: database.clone(),
+            published_files: published_files.clone(),
+            blobs: blobs.clone(),
+            google_drive: &options.google_drive,
+            forecast_spreadsheet_schema,
+            forecast_spreadsheet_schemas,
+        },
+        shutdown_controller.subscribe(),
+    );
+    PublishedFilesService::new(PublishedFilesServiceConfig {
+        interval: std::time::Duration::from_secs(options.google_drive.refresh_interval_seconds),
+        published_files: published_files.clone(),
+        client: client.clone(),
+        database: database.clone(),
+        blobs: blobs.clone(),
+        forecast_spreadsheet_schemas,
+        webhooks: webhooks.clone(),
+        aggregators: aggregators.clone(),
+        base_url: options.base_url(),
+        alerts,
+    })
+    .spawn();
+
+    #[cfg(unix)]
+    options::spawn_reload_on_hangup(reloadable_options.clone(), forecast_spreadsheet_schemas)
+        .wrap_err("Error listening for SIGHUP")?;
 
+    let api_key_usage = api::keys::UsageCounter::default();
     let state = AppState {
         options,
+        reloadable_options: reloadable_options.clone(),
         forecast_spreadsheet_schema,
+        forecast_spreadsheet_schemas,
         client: client.clone(),
         i18n,
         templates,
         database,
         analytics_sx,
         current_weather,
+        session_key: auth::SessionKey::new(options.session_secret.as_ref()),
+        basic_auth_cache: auth::BasicAuthCache::default(),
+        login_throttle: auth::LoginThrottle::default(),
+        observation_throttle: observations::SubmissionThrottle::default(),
+        diagram_cache: std::sync::Arc::new(diagrams::cache::DiagramCache::new(
+            options.diagram_cache_capacity,
+        )),
+        published_files: published_files.clone(),
+        blobs,
+        webcams: std::sync::Arc::new(webcams::Webcams::new(
+            reloadable_options.clone(),
+            client.clone(),
+        )),
+        webhooks,
+        aggregators,
+        api_key_rate_limiters: api::keys::RateLimiters::default(),
+        api_key_usage: api_key_usage.clone(),
     };
+    api::keys::spawn_usage_flush_task(
+        api_key_usage,
+        state.database.clone(),
+        shutdown_controller.subscribe(),
+    );
 
+    let features = options.features;
     // build our application with a route
     let router = Router::new()
         // All these pages are dynamic and should have the Cache-Control: no-store header set
@@ -164,31 +342,82 @@ async fn main() -> eyre::Result<()> {
                     get(user_preferences::query_set_redirect_handler),
                 )
                 .route("/disclaimer", post(disclaimer::handler))
-                .route("/weather", get(weather::handler))
+                .merge(auth::router())
+                .merge(feature(
+                    features.weather,
+                    Router::new().route("/weather", get(weather::handler)),
+                ))
+                .route("/status", get(status::handler))
+                .route("/search", get(search::handler))
+                .route("/statistics", get(statistics::handler))
+                .route("/statistics.json", get(statistics::json_handler))
+                .route("/glossary.json", get(glossary::json_handler))
+                .nest("/forecasters", forecasters::router())
+                .merge(feature(
+                    features.news,
+                    Router::new().nest("/news", news::router()),
+                ))
+                .merge(feature(
+                    features.education,
+                    Router::new().nest("/education", education::router()),
+                ))
+                .merge(feature(
+                    features.widget,
+                    Router::new().nest("/widget", widget::router()),
+                ))
+                .nest("/f", short_links::router())
                 // These routes expose public forecast information and thus have the disclaimer middleware
                 // applied to them.
                 .merge(
                     Router::new()
                         .route("/", get(index::handler))
+                        .nest("/areas", areas::router())
                         .typed_get(forecasts::handler)
-                        .nest("/observations", observations::router())
+                        .typed_get(forecasts::card::handler)
+                        .typed_get(forecasts::compare::handler)
+                        .typed_get(forecasts::compare::json_handler)
+                        .merge(feature(
+                            features.observations,
+                            Router::new().nest("/observations", observations::router()),
+                        ))
+                        .nest("/route-exposure", route_exposure::router())
                         .layer(middleware::from_fn(disclaimer::middleware)),
                 )
                 .route("/json", get(index::json_handler))
+                .nest(
+                    "/api",
+                    api::router(features).layer(middleware::from_fn_with_state(
+                        state.clone(),
+                        api::keys::middleware,
+                    )),
+                )
                 .nest(
                     "/admin",
                     admin::router(admin::Config {
                         reporting: reporting_options,
-                        admin_password_hash: &options.admin_password_hash,
+                        allowlist: options.admin_allowlist.as_ref(),
+                        webhooks: state.webhooks.clone(),
                     }),
                 )
                 .layer(middleware::from_fn(cache_control::no_store_middleware)),
         )
-        .nest("/current-weather", current_weather::router())
+        .merge(feature(
+            features.weather,
+            Router::new().nest("/current-weather", current_weather::router()),
+        ))
         .nest("/diagrams", diagrams::router())
         .nest("/forecast-areas", forecast_areas::router())
+        .merge(feature(
+            features.weather,
+            Router::new().nest("/webcams", webcams::router()),
+        ))
         .route_service("/dist/{*file}", dist_handler.into_service());
 
+    let router = match (metrics_handle, &options.metrics) {
+        (Some(handle), Some(config)) => router.merge(prometheus::router(handle, config)),
+        _ => router,
+    };
+
     let router = if let Some(override_directory) = &options.static_files.directory {
         router.nest_service(
             "/static",
@@ -200,6 +429,8 @@ async fn main() -> eyre::Result<()> {
 
     let app = router
         .fallback(not_found_handler)
+        .layer(middleware::from_fn(widget::frame_options_middleware))
+        .layer(middleware::from_fn(prometheus::middleware))
         .layer(middleware::from_fn_with_state(
             state.clone(),
             templates::middleware,
@@ -209,6 +440,10 @@ async fn main() -> eyre::Result<()> {
             i18n::middleware,
         ))
         .layer(middleware::from_fn(user_preferences::middleware))
+        .layer(middleware::from_fn_with_state(
+            state.clone(),
+            auth::middleware,
+        ))
         .layer(middleware::from_fn_with_state(
             state.clone(),
             analytics::middleware,
@@ -218,17 +453,63 @@ async fn main() -> eyre::Result<()> {
             database::middleware,
         ))
         .layer(middleware::from_fn(isbot::middleware))
+        .layer(CompressionLayer::new())
         .layer(TraceLayer::new_for_http())
         .with_state(state);
 
     let url = &options.base_url();
     tracing::info!("listening on {url}");
-    let listener = tokio::net::TcpListener::bind(&options.listen_address).await?;
-    axum::serve(listener, app).await?;
+    if let Some(tls) = &options.tls {
+        tls::serve(app, options, tls).await?;
+    } else {
+        let listener = tokio::net::TcpListener::bind(&options.listen_address).await?;
+        axum::serve(
+            listener,
+            app.into_make_service_with_connect_info::<std::net::SocketAddr>(),
+        )
+        .with_graceful_shutdown(shutdown::signal())
+        .await?;
+    }
+
+    shutdown_controller
+        .shutdown(std::time::Duration::from_secs(
+            options.shutdown_timeout_seconds,
+        ))
+        .await;
 
     Ok(())
 }
 
+/// Whether to only validate the configuration and exit without starting the server, enabled with
+/// the `--check-config` argument or the `AVALANCHE_REPORT_CHECK_CONFIG=true` environment variable.
+fn check_config() -> bool {
+    std::env::args().any(|arg| arg == "--check-config")
+        || std::env::var("AVALANCHE_REPORT_CHECK_CONFIG").is_ok_and(|value| value == "true")
+}
+
+/// The `router` if its feature is `enabled` (see [`options::Features`]), otherwise no routes, so
+/// that the pages of a disabled feature are not found.
+fn feature(enabled: bool, router: Router<AppState>) -> Router<AppState> {
+    if enabled {
+        router
+    } else {
+        Router::new()
+    }
+}
+
+async fn read_forecast_spreadsheet_schema(
+    schema_path: &std::path::Path,
+) -> eyre::Result<ForecastSpreadsheetSchema> {
+    serde_json::from_str(
+        &tokio::fs::read_to_string(schema_path)
+            .await
+            .wrap_err_with(|| {
+                format!("Error reading forecast spreadsheet schema file {schema_path:?}")
+            })?,
+    )
+    .wrap_err_with(|| format!("Error parsing forecast spreadsheet schema {schema_path:?}"))
+}
+
 async fn dist_handler(uri: Uri) -> impl IntoResponse {
     let mut path = uri.path().trim_start_matches('/').to_string();
 
@@ -249,13 +530,22 @@ async fn static_handler(uri: Uri) -> impl IntoResponse {
     StaticFile::get(path)
 }
 
-/// Create a 404 not found response
+/// Serves the [`pages`] managed in the admin interface, or the 404 page.
 async fn not_found_handler(
     Extension(templates): Extension<TemplatesWithContext>,
-) -> axum::response::Result<impl IntoResponse> {
-    not_found(templates)
+    Extension(database): Extension<Database>,
+    uri: Uri,
+) -> axum::response::Result<Response> {
+    if let Some(response) = pages::page_response(&uri, &templates, &database)
+        .await
+        .map_err(map_eyre_error)?
+    {
+        return Ok(response);
+    }
+    Ok(not_found(templates)?.into_response())
 }
 
+/// Create a 404 not found response
 fn not_found(templates: TemplatesWithContext) -> axum::response::Result<impl IntoResponse> {
     let template = templates
         .environment
@@ -296,15 +586,33 @@ where
 {
     fn into_response(self) -> Response {
         let path: &str = self.path.as_ref();
-        match E::get(path) {
+        // Files requested using their content hashed path (see `assets::hashed_url`) can be
+        // cached forever. If the hash is out of date the current file is still served, but
+        // without the immutable cache control.
+        let (content, immutable) = match E::get(path) {
+            Some(content) => (Some(content), false),
+            None => match assets::split_hash(path) {
+                Some((path, hash)) => match E::get(&path) {
+                    Some(content) => {
+                        let immutable = assets::content_hash(&content) == hash;
+                        (Some(content), immutable)
+                    }
+                    None => (None, false),
+                },
+                None => (None, false),
+            },
+        };
+        match content {
             Some(content) => {
                 let bytes = Bytes::from(content.data.to_vec());
                 let body = axum::body::Body::from(bytes);
                 let mime = mime_guess::from_path(path).first_or_octet_stream();
-                Response::builder()
-                    .header(header::CONTENT_TYPE, mime.as_ref())
-                    .body(body)
-                    .unwrap()
+                let mut builder = Response::builder().header(header::CONTENT_TYPE, mime.as_ref());
+                if immutable {
+                    builder =
+                        builder.header(header::CACHE_CONTROL, assets::IMMUTABLE_CACHE_CONTROL);
+                }
+                builder.body(body).unwrap()
             }
             None => StatusCode::NOT_FOUND.into_response(),
         }
@@ -0,0 +1,234 @@
+//! News posts, for publishing advisories (e.g. early season conditions) and season summaries
+//! outside of the forecasts. Posts are written in markdown, with a title and body for each
+//! language, and are edited in the admin interface (see [`crate::admin`]).
+
+use std::collections::HashMap;
+
+use axum::{
+    extract::Path,
+    response::{IntoResponse, Response},
+    routing::get,
+    Extension, Router,
+};
+use http::StatusCode;
+use serde::{Deserialize, Serialize};
+use unic_langid::LanguageIdentifier;
+
+use crate::{
+    database::Database,
+    error::map_eyre_error,
+    i18n::{self, I18nLoader},
+    state::AppState,
+    templates::TemplatesWithContext,
+    types,
+};
+
+pub fn router() -> Router<AppState> {
+    Router::new()
+        .route("/", get(index_handler))
+        .route("/{id}", get(post_handler))
+}
+
+/// Identifier of a post used in its url, e.g. `early-season-advisory-2024`.
+#[derive(sqlx::Type, Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Hash)]
+#[serde(transparent)]
+#[sqlx(transparent)]
+pub struct NewsPostId(String);
+
+impl NewsPostId {
+    /// Whether the id only contains lowercase letters, numbers and `-`, so it can be used in urls
+    /// without escaping.
+    pub fn is_valid(&self) -> bool {
+        !self.0.is_empty()
+            && self
+                .0
+                .chars()
+                .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-')
+    }
+}
+
+impl From<String> for NewsPostId {
+    fn from(value: String) -> Self {
+        Self(value)
+    }
+}
+
+impl std::fmt::Display for NewsPostId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+/// Text translated into multiple languages, as used by the `translated_string` template function.
+pub type Translations = HashMap<LanguageIdentifier, String>;
+
+#[derive(Serialize, Debug, Clone)]
+pub struct NewsPost {
+    pub id: NewsPostId,
+    pub created_at: types::Time,
+    pub updated_at: types::Time,
+    /// `None` while the post is a draft, which is only visible in the admin interface.
+    pub published_at: Option<types::Time>,
+    pub title: sqlx::types::Json<Translations>,
+    /// Markdown.
+    pub body: sqlx::types::Json<Translations>,
+}
+
+/// List posts, most recently published first followed by drafts, optionally only the published
+/// ones.
+pub async fn list_posts(database: &Database, published_only: bool) -> eyre::Result<Vec<NewsPost>> {
+    Ok(sqlx::query_as!(
+        NewsPost,
+        r#"SELECT
+            id as "id: NewsPostId",
+            created_at as "created_at: types::Time",
+            updated_at as "updated_at: types::Time",
+            published_at as "published_at: types::Time",
+            title as "title: sqlx::types::Json<Translations>",
+            body as "body: sqlx::types::Json<Translations>"
+        FROM news_posts
+        WHERE $1 = 0 OR published_at IS NOT NULL
+        ORDER BY published_at IS NULL, published_at DESC, created_at DESC"#,
+        published_only,
+    )
+    .fetch_all(database)
+    .await?)
+}
+
+pub async fn get_post(database: &Database, id: &NewsPostId) -> eyre::Result<Option<NewsPost>> {
+    Ok(sqlx::query_as!(
+        NewsPost,
+        r#"SELECT
+            id as "id: NewsPostId",
+            created_at as "created_at: types::Time",
+            updated_at as "updated_at: types::Time",
+            published_at as "published_at: types::Time",
+            title as "title: sqlx::types::Json<Translations>",
+            body as "body: sqlx::types::Json<Translations>"
+        FROM news_posts WHERE id = $1"#,
+        id
+    )
+    .fetch_optional(database)
+    .await?)
+}
+
+/// Create the post, or replace the title and body of an existing post with the same id.
+pub async fn save_post(
+    database: &Database,
+    id: &NewsPostId,
+    title: &Translations,
+    body: &Translations,
+) -> eyre::Result<()> {
+    let now: types::Time = time::OffsetDateTime::now_utc().into();
+    let title = sqlx::types::Json(title);
+    let body = sqlx::types::Json(body);
+    sqlx::query!(
+        "INSERT INTO news_posts(id, created_at, updated_at, title, body) VALUES($1, $2, $2, $3, $4) ON CONFLICT(id) DO UPDATE SET updated_at=excluded.updated_at, title=excluded.title, body=excluded.body",
+        id,
+        now,
+        title,
+        body,
+    )
+    .execute(database)
+    .await?;
+    Ok(())
+}
+
+/// Publish the post now, or return it to being a draft.
+pub async fn set_published(
+    database: &Database,
+    id: &NewsPostId,
+    published: bool,
+) -> eyre::Result<()> {
+    let published_at: Option<types::Time> =
+        published.then(|| time::OffsetDateTime::now_utc().into());
+    sqlx::query!(
+        "UPDATE news_posts SET published_at = $1 WHERE id = $2",
+        published_at,
+        id
+    )
+    .execute(database)
+    .await?;
+    Ok(())
+}
+
+pub async fn delete_post(database: &Database, id: &NewsPostId) -> eyre::Result<()> {
+    sqlx::query!("DELETE FROM news_posts WHERE id = $1", id)
+        .execute(database)
+        .await?;
+    Ok(())
+}
+
+/// A [`NewsPost`] with values formatted for display in templates.
+#[derive(Serialize)]
+pub struct NewsPostContext {
+    #[serde(flatten)]
+    pub post: NewsPost,
+    pub formatted_published_at: Option<String>,
+}
+
+impl NewsPostContext {
+    pub fn format(post: NewsPost, i18n: &I18nLoader) -> Self {
+        Self {
+            formatted_published_at: post
+                .published_at
+                .map(|published_at| i18n::format_time(published_at.into(), i18n)),
+            post,
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct IndexContext {
+    posts: Vec<NewsPostContext>,
+}
+
+async fn index_handler(
+    Extension(templates): Extension<TemplatesWithContext>,
+    Extension(database): Extension<Database>,
+    Extension(i18n): Extension<I18nLoader>,
+) -> axum::response::Result<Response> {
+    let posts = list_posts(&database, true)
+        .await
+        .map_err(map_eyre_error)?
+        .into_iter()
+        .map(|post| NewsPostContext::format(post, &i18n))
+        .collect();
+    Ok(templates
+        .render("news/index.html", &IndexContext { posts })
+        .map_err(map_eyre_error)?)
+}
+
+#[derive(Serialize)]
+struct PostPageContext {
+    post: NewsPostContext,
+}
+
+async fn post_handler(
+    Path(id): Path<NewsPostId>,
+    Extension(templates): Extension<TemplatesWithContext>,
+    Extension(database): Extension<Database>,
+    Extension(i18n): Extension<I18nLoader>,
+) -> axum::response::Result<Response> {
+    let post = match get_post(&database, &id).await.map_err(map_eyre_error)? {
+        Some(post) if post.published_at.is_some() => post,
+        _ => return Ok(StatusCode::NOT_FOUND.into_response()),
+    };
+    let post = NewsPostContext::format(post, &i18n);
+    Ok(templates
+        .render("news/post.html", &PostPageContext { post })
+        .map_err(map_eyre_error)?)
+}
+
+#[cfg(test)]
+mod test {
+    use super::NewsPostId;
+
+    #[test]
+    fn test_news_post_id_is_valid() {
+        assert!(NewsPostId("early-season-2024".to_owned()).is_valid());
+        assert!(!NewsPostId(String::new()).is_valid());
+        assert!(!NewsPostId("Early Season".to_owned()).is_valid());
+        assert!(!NewsPostId("../admin".to_owned()).is_valid());
+    }
+}
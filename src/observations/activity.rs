@@ -0,0 +1,384 @@
+//! Summaries of the avalanches reported in the approved observations for each [`Season`], by day,
+//! by size and by avalanche problem, like the charts avalanche centres publish at the end of a
+//! season. Shown at `/observations/activity` with charts from
+//! [`crate::diagrams::avalanche_activity`], and available from the public API at
+//! `/api/v1/avalanche-activity`.
+
+use std::collections::{BTreeMap, HashMap};
+
+use axum::{extract::Query, response::Response, Extension};
+use eyre::Context;
+use forecast_spreadsheet::ProblemKind;
+use serde::{Deserialize, Serialize};
+use time::Date;
+
+use crate::{
+    database::Database, error::map_eyre_error, statistics::Season, templates::TemplatesWithContext,
+};
+
+use super::{parse_kebab_case, AvalancheActivity, ObservationStatus};
+
+/// Largest size on the avalanche size scale.
+pub const MAX_SIZE: i64 = 5;
+
+time::serde::format_description!(date_format, Date, "[year]-[month]-[day]");
+
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct DayActivity {
+    #[serde(with = "date_format")]
+    pub date: Date,
+    pub natural: usize,
+    pub triggered: usize,
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct SizeActivity {
+    pub size: i64,
+    pub count: usize,
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct ProblemActivity {
+    pub kind: ProblemKind,
+    pub count: usize,
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct SeasonActivity {
+    pub season: Season,
+    pub label: String,
+    /// Number of observations which reported an avalanche.
+    pub avalanches: usize,
+    pub natural: usize,
+    pub triggered: usize,
+    /// Days with at least one avalanche (in UTC), in chronological order.
+    pub days: Vec<DayActivity>,
+    /// Every size from 1 to [`MAX_SIZE`], including sizes with no avalanches.
+    pub sizes: Vec<SizeActivity>,
+    /// Avalanches reported without a size.
+    pub unknown_size: usize,
+    /// Most frequent first, omitting problems with no avalanches.
+    pub problems: Vec<ProblemActivity>,
+    /// Avalanches reported without an avalanche problem.
+    pub unknown_problem: usize,
+}
+
+impl SeasonActivity {
+    /// A season in which no avalanches were reported.
+    pub fn empty(season: Season) -> Self {
+        SeasonAccumulator::default().finish(season)
+    }
+}
+
+#[derive(Default)]
+struct SeasonAccumulator {
+    natural: usize,
+    triggered: usize,
+    days: BTreeMap<Date, (usize, usize)>,
+    sizes: HashMap<i64, usize>,
+    unknown_size: usize,
+    problems: HashMap<ProblemKind, usize>,
+    unknown_problem: usize,
+}
+
+impl SeasonAccumulator {
+    fn finish(mut self, season: Season) -> SeasonActivity {
+        let mut problems: Vec<ProblemActivity> = enum_iterator::all::<ProblemKind>()
+            .filter_map(|kind| {
+                let count = self.problems.remove(&kind)?;
+                Some(ProblemActivity { kind, count })
+            })
+            .collect();
+        problems.sort_by_key(|problem| std::cmp::Reverse(problem.count));
+        SeasonActivity {
+            season,
+            label: season.label(),
+            avalanches: self.natural + self.triggered,
+            natural: self.natural,
+            triggered: self.triggered,
+            days: self
+                .days
+                .into_iter()
+                .map(|(date, (natural, triggered))| DayActivity {
+                    date,
+                    natural,
+                    triggered,
+                })
+                .collect(),
+            sizes: (1..=MAX_SIZE)
+                .map(|size| SizeActivity {
+                    size,
+                    count: self.sizes.get(&size).copied().unwrap_or_default(),
+                })
+                .collect(),
+            unknown_size: self.unknown_size,
+            problems,
+            unknown_problem: self.unknown_problem,
+        }
+    }
+}
+
+/// The number of avalanches reported on the same day with the same activity, size and problem.
+struct ActivityCount {
+    date: Date,
+    activity: AvalancheActivity,
+    size: Option<i64>,
+    problem: Option<String>,
+    count: usize,
+}
+
+/// Summarize the avalanches in the `counts` for each season with at least one avalanche, most
+/// recent season first.
+fn summarize_counts(counts: impl IntoIterator<Item = ActivityCount>) -> Vec<SeasonActivity> {
+    let mut seasons: BTreeMap<Season, SeasonAccumulator> = BTreeMap::new();
+    for activity_count in counts {
+        let count = activity_count.count;
+        let (natural, triggered) = match activity_count.activity {
+            AvalancheActivity::None => continue,
+            AvalancheActivity::Natural => (count, 0),
+            AvalancheActivity::Triggered => (0, count),
+        };
+        let season = Season::containing(activity_count.date.midnight().assume_utc());
+        let season = seasons.entry(season).or_default();
+        season.natural += natural;
+        season.triggered += triggered;
+        let day = season.days.entry(activity_count.date).or_default();
+        day.0 += natural;
+        day.1 += triggered;
+        match activity_count.size {
+            Some(size) if (1..=MAX_SIZE).contains(&size) => {
+                *season.sizes.entry(size).or_default() += count
+            }
+            _ => season.unknown_size += count,
+        }
+        match activity_count
+            .problem
+            .as_deref()
+            .and_then(|problem| parse_kebab_case::<ProblemKind>(problem).ok())
+        {
+            Some(kind) => *season.problems.entry(kind).or_default() += count,
+            None => season.unknown_problem += count,
+        }
+    }
+    seasons
+        .into_iter()
+        .rev()
+        .map(|(season, accumulator)| accumulator.finish(season))
+        .collect()
+}
+
+/// Summarize the avalanches reported in the approved observations. The observations are counted
+/// by day in the database, rather than loading each of them.
+pub async fn load_activity(database: &Database) -> eyre::Result<Vec<SeasonActivity>> {
+    let status = ObservationStatus::Approved;
+    let none = AvalancheActivity::None;
+    // The stored times start with the date, see `DATETIME_FORMAT`.
+    let records = sqlx::query!(
+        r#"SELECT
+            substr(observed_at, 1, 10) as "day!: String",
+            avalanche_activity as "avalanche_activity: AvalancheActivity",
+            avalanche_size,
+            avalanche_problem,
+            COUNT(*) as "count!: i64"
+        FROM observations
+        WHERE status = $1 AND avalanche_activity != $2
+        GROUP BY 1, 2, 3, 4"#,
+        status,
+        none,
+    )
+    .fetch_all(database)
+    .await
+    .wrap_err("Error counting avalanche activity")?;
+    let format = time::macros::format_description!("[year]-[month]-[day]");
+    let counts = records
+        .into_iter()
+        .map(|record| {
+            Ok(ActivityCount {
+                date: Date::parse(&record.day, &format)
+                    .wrap_err_with(|| format!("Error parsing observation day {:?}", record.day))?,
+                activity: record.avalanche_activity,
+                size: record.avalanche_size,
+                problem: record.avalanche_problem,
+                count: usize::try_from(record.count).unwrap_or_default(),
+            })
+        })
+        .collect::<eyre::Result<Vec<_>>>()?;
+    Ok(summarize_counts(counts))
+}
+
+/// The activity for the `season`, or the most recent season with activity if it's not specified.
+/// `None` if no avalanches were reported in the season.
+pub fn select_season(
+    activity: Vec<SeasonActivity>,
+    season: Option<Season>,
+) -> Option<SeasonActivity> {
+    activity
+        .into_iter()
+        .find(|activity| season.is_none_or(|season| activity.season == season))
+}
+
+#[derive(Deserialize)]
+pub struct ActivityQuery {
+    /// The year that the season to show starts in, defaults to the most recent season.
+    pub season: Option<Season>,
+}
+
+#[derive(Serialize)]
+struct SeasonOption {
+    season: Season,
+    label: String,
+}
+
+#[derive(Serialize)]
+struct ActivityContext {
+    seasons: Vec<SeasonOption>,
+    selected: Option<SeasonActivity>,
+}
+
+pub async fn handler(
+    Query(query): Query<ActivityQuery>,
+    Extension(templates): Extension<TemplatesWithContext>,
+    Extension(database): Extension<Database>,
+) -> axum::response::Result<Response> {
+    let activity = load_activity(&database).await.map_err(map_eyre_error)?;
+    let seasons = activity
+        .iter()
+        .map(|activity| SeasonOption {
+            season: activity.season,
+            label: activity.label.clone(),
+        })
+        .collect();
+    let context = ActivityContext {
+        seasons,
+        selected: select_season(activity, query.season),
+    };
+    Ok(templates
+        .render("observations/activity.html", &context)
+        .map_err(map_eyre_error)?)
+}
+
+#[cfg(test)]
+mod test {
+    use forecast_spreadsheet::ProblemKind;
+    use time::macros::{date, datetime};
+
+    use crate::{
+        observations::{AvalancheActivity, Observation, ObservationId, ObservationStatus},
+        statistics::Season,
+    };
+
+    use super::{summarize_counts, ActivityCount, DayActivity, ProblemActivity, SeasonActivity};
+
+    fn summarize(observations: &[Observation]) -> Vec<SeasonActivity> {
+        summarize_counts(observations.iter().map(|observation| ActivityCount {
+            date: observation.observed_at.date(),
+            activity: observation.avalanche_activity,
+            size: observation.avalanche_size,
+            problem: observation.avalanche_problem.clone(),
+            count: 1,
+        }))
+    }
+
+    fn observation(
+        observed_at: time::OffsetDateTime,
+        avalanche_activity: AvalancheActivity,
+        avalanche_size: Option<i64>,
+        avalanche_problem: Option<&str>,
+    ) -> Observation {
+        Observation {
+            id: ObservationId::generate(),
+            created_at: observed_at.into(),
+            observed_at: observed_at.into(),
+            latitude: 42.47,
+            longitude: 44.47,
+            elevation_metres: None,
+            aspect: None,
+            avalanche_activity,
+            avalanche_size,
+            avalanche_problem: avalanche_problem.map(ToOwned::to_owned),
+            description: String::new(),
+            observer_name: None,
+            status: ObservationStatus::Approved,
+            snow_profile: None,
+        }
+    }
+
+    #[test]
+    fn test_summarize() {
+        let observations = [
+            observation(
+                datetime!(2024-01-10 09:00 UTC),
+                AvalancheActivity::Natural,
+                Some(2),
+                Some("wind-slab"),
+            ),
+            observation(
+                datetime!(2024-01-10 15:00 UTC),
+                AvalancheActivity::Triggered,
+                None,
+                Some("wind-slab"),
+            ),
+            observation(
+                datetime!(2024-01-05 12:00 UTC),
+                AvalancheActivity::Natural,
+                Some(3),
+                None,
+            ),
+            observation(
+                datetime!(2024-01-06 12:00 UTC),
+                AvalancheActivity::None,
+                None,
+                None,
+            ),
+            // A season with no avalanches is omitted.
+            observation(
+                datetime!(2022-12-01 12:00 UTC),
+                AvalancheActivity::None,
+                None,
+                None,
+            ),
+        ];
+        let activity = summarize(&observations);
+        assert_eq!(activity.len(), 1);
+        let season = &activity[0];
+        assert_eq!(
+            season.season,
+            Season::containing(datetime!(2023-12-01 0:00 UTC))
+        );
+        assert_eq!(season.avalanches, 3);
+        assert_eq!(season.natural, 2);
+        assert_eq!(season.triggered, 1);
+        assert_eq!(
+            season.days,
+            [
+                DayActivity {
+                    date: date!(2024 - 01 - 05),
+                    natural: 1,
+                    triggered: 0
+                },
+                DayActivity {
+                    date: date!(2024 - 01 - 10),
+                    natural: 1,
+                    triggered: 1
+                },
+            ]
+        );
+        assert_eq!(
+            season
+                .sizes
+                .iter()
+                .map(|size| (size.size, size.count))
+                .collect::<Vec<_>>(),
+            [(1, 0), (2, 1), (3, 1), (4, 0), (5, 0)]
+        );
+        assert_eq!(season.unknown_size, 1);
+        assert_eq!(
+            season.problems,
+            [ProblemActivity {
+                kind: ProblemKind::WindSlab,
+                count: 2
+            }]
+        );
+        assert_eq!(season.unknown_problem, 1);
+    }
+}
@@ -0,0 +1,111 @@
+//! Public observations as a GeoJSON `FeatureCollection`, for display on maps.
+
+use axum::{
+    extract::Query,
+    http::{header::CONTENT_TYPE, HeaderValue},
+    response::{IntoResponse, Response},
+    Extension, Json,
+};
+use eyre::Context;
+use http::StatusCode;
+use serde::Deserialize;
+use serde_json::json;
+
+use crate::{database::Database, error::map_eyre_error, types};
+
+use super::{query_observations, BoundingBox, Observation, ObservationFilter, ObservationStatus};
+
+/// Number of days of observations to include when no `from` date is specified.
+const DEFAULT_DAYS: i64 = 30;
+
+#[derive(Deserialize)]
+pub struct GeoJsonQuery {
+    /// Include observations from the start of this date (UTC), in the format `YYYY-MM-DD`.
+    from: Option<String>,
+    /// Include observations until the end of this date (UTC), in the format `YYYY-MM-DD`.
+    to: Option<String>,
+    /// Bounding box in the format `min_longitude,min_latitude,max_longitude,max_latitude`.
+    bbox: Option<String>,
+}
+
+fn parse_date(date: &str) -> eyre::Result<time::Date> {
+    let format = time::macros::format_description!("[year]-[month]-[day]");
+    time::Date::parse(date, &format).wrap_err_with(|| format!("Invalid date {date:?}"))
+}
+
+impl GeoJsonQuery {
+    fn into_filter(self) -> eyre::Result<ObservationFilter> {
+        let observed_after = match &self.from {
+            Some(from) => types::Time::from(parse_date(from)?.midnight().assume_utc()),
+            None => types::Time::now_utc() - time::Duration::days(DEFAULT_DAYS),
+        };
+        let observed_before = self
+            .to
+            .as_deref()
+            .map(|to| {
+                parse_date(to)
+                    .map(|to| types::Time::from(to.midnight().assume_utc() + time::Duration::DAY))
+            })
+            .transpose()?;
+        let bounding_box = self
+            .bbox
+            .as_deref()
+            .map(str::parse::<BoundingBox>)
+            .transpose()?;
+        Ok(ObservationFilter {
+            status: ObservationStatus::Approved,
+            observed_after: Some(observed_after),
+            observed_before,
+            bounding_box,
+        })
+    }
+}
+
+fn feature(observation: Observation) -> serde_json::Value {
+    json!({
+        "type": "Feature",
+        "geometry": {
+            "type": "Point",
+            "coordinates": [observation.longitude, observation.latitude],
+        },
+        "properties": {
+            "id": observation.id,
+            "url": format!("/observations/{}", observation.id),
+            "type": observation.avalanche_activity,
+            "time": observation.observed_at,
+            "elevation_metres": observation.elevation_metres,
+            "aspect": observation.aspect,
+            "avalanche_size": observation.avalanche_size,
+            "avalanche_problem": observation.avalanche_problem,
+        },
+    })
+}
+
+/// Handler for `observations.geojson`, which serves approved observations filtered by the
+/// [`GeoJsonQuery`].
+pub async fn handler(
+    Query(query): Query<GeoJsonQuery>,
+    Extension(database): Extension<Database>,
+) -> axum::response::Result<Response> {
+    let filter = query.into_filter().map_err(|error| {
+        let mut response = map_eyre_error(error);
+        *response.status_mut() = StatusCode::BAD_REQUEST;
+        response
+    })?;
+    let features: Vec<serde_json::Value> = query_observations(&database, &filter)
+        .await
+        .map_err(map_eyre_error)?
+        .into_iter()
+        .map(feature)
+        .collect();
+    let mut response = Json(json!({
+        "type": "FeatureCollection",
+        "features": features,
+    }))
+    .into_response();
+    response.headers_mut().insert(
+        CONTENT_TYPE,
+        "application/geo+json".parse::<HeaderValue>().unwrap(),
+    );
+    Ok(response)
+}
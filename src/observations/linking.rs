@@ -0,0 +1,319 @@
+//! Linking observations to the forecast which was in force where and when they were made. The
+//! observation's forecast area is found from the forecast areas' GeoJSON, and the forecast is the
+//! latest one published for that area with a time at or before the observation. Links are stored
+//! when an observation is submitted, and updated whenever they are displayed in case a forecast
+//! was published afterwards, or the forecast areas were modified.
+
+use std::collections::HashMap;
+
+use serde::Serialize;
+use time::OffsetDateTime;
+
+use crate::{
+    database::Database,
+    forecast_areas::{
+        geojson_features, geometry_polygons, get_forecast_area, list_forecast_areas,
+        ForecastAreaId, GeoPolygon,
+    },
+    forecasts::parse_forecast_name,
+    state::AppState,
+    types,
+};
+
+use super::{query_observations, Observation, ObservationFilter, ObservationId, ObservationStatus};
+
+/// A forecast which has been published, possibly as several files.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PublishedForecast {
+    pub area: ForecastAreaId,
+    pub time: OffsetDateTime,
+    /// One of the forecast's files, preferring one with forecast data so that it can be viewed as a
+    /// page.
+    pub file_name: String,
+}
+
+/// The forecast which was in force where and when an observation was made.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct ForecastLink {
+    /// `None` when the observation is outside of all the forecast areas.
+    pub forecast_area: Option<ForecastAreaId>,
+    /// `None` when no forecast for the area had been published at the time of the observation.
+    pub forecast_file_name: Option<String>,
+    pub forecast_time: Option<types::Time>,
+}
+
+struct AreaPolygons {
+    id: ForecastAreaId,
+    polygons: Vec<GeoPolygon>,
+}
+
+/// The forecast areas and published forecasts which observations are linked to.
+pub struct Linker {
+    areas: Vec<AreaPolygons>,
+    forecasts: Vec<PublishedForecast>,
+}
+
+impl Linker {
+    pub async fn load(database: &Database, state: &AppState) -> eyre::Result<Self> {
+        let mut areas = Vec::new();
+        for id in list_forecast_areas(database).await? {
+            let Some(area) = get_forecast_area(database, &id).await? else {
+                continue;
+            };
+            let polygons = geojson_features(area.geojson)
+                .iter()
+                .filter_map(|feature| feature.get("geometry"))
+                .flat_map(geometry_polygons)
+                .collect();
+            areas.push(AreaPolygons { id, polygons });
+        }
+        Ok(Self {
+            areas,
+            forecasts: published_forecasts(state).await?,
+        })
+    }
+
+    /// The forecast area containing the position.
+    fn area_at(&self, longitude: f64, latitude: f64) -> Option<&ForecastAreaId> {
+        self.areas
+            .iter()
+            .find(|area| {
+                area.polygons
+                    .iter()
+                    .any(|polygon| geo::polygon_contains(polygon, longitude, latitude))
+            })
+            .map(|area| &area.id)
+    }
+
+    pub fn link(&self, observation: &Observation) -> ForecastLink {
+        let forecast_area = self.area_at(observation.longitude, observation.latitude);
+        let forecast = forecast_area
+            .and_then(|area| forecast_in_force(&self.forecasts, area, *observation.observed_at));
+        ForecastLink {
+            forecast_area: forecast_area.cloned(),
+            forecast_file_name: forecast.map(|forecast| forecast.file_name.clone()),
+            forecast_time: forecast.map(|forecast| forecast.time.into()),
+        }
+    }
+
+    /// The latest published forecast for each area.
+    pub fn latest_forecasts(&self) -> Vec<&PublishedForecast> {
+        let mut latest: Vec<&PublishedForecast> = Vec::new();
+        for forecast in &self.forecasts {
+            match latest.iter_mut().find(|other| other.area == forecast.area) {
+                Some(other) if other.time < forecast.time => *other = forecast,
+                Some(_) => {}
+                None => latest.push(forecast),
+            }
+        }
+        latest.sort_by_key(|a| a.area.to_string());
+        latest
+    }
+}
+
+/// The forecasts which have been published, grouping the files published for each forecast.
+async fn published_forecasts(state: &AppState) -> eyre::Result<Vec<PublishedForecast>> {
+    let schema = state.forecast_spreadsheet_schema;
+    let mut forecasts: Vec<(PublishedForecast, bool)> = Vec::new();
+    for file in state.published_files.list_files().await? {
+        let Ok(details) = parse_forecast_name(&file.name, schema) else {
+            continue;
+        };
+        let Some(area_id) = schema.area.map.get(&details.forecast.area) else {
+            continue;
+        };
+        let area = ForecastAreaId::from(area_id.to_string().to_lowercase());
+        let has_forecast_data = file.has_forecast_data();
+        match forecasts
+            .iter_mut()
+            .find(|(forecast, _)| forecast.area == area && forecast.time == details.forecast.time)
+        {
+            Some((forecast, has_data)) => {
+                if has_forecast_data && !*has_data {
+                    forecast.file_name = file.name;
+                    *has_data = true;
+                }
+            }
+            None => forecasts.push((
+                PublishedForecast {
+                    area,
+                    time: details.forecast.time,
+                    file_name: file.name,
+                },
+                has_forecast_data,
+            )),
+        }
+    }
+    Ok(forecasts
+        .into_iter()
+        .map(|(forecast, _)| forecast)
+        .collect())
+}
+
+/// The latest of the `forecasts` for the `area` with a time at or before `time`.
+fn forecast_in_force<'a>(
+    forecasts: &'a [PublishedForecast],
+    area: &ForecastAreaId,
+    time: OffsetDateTime,
+) -> Option<&'a PublishedForecast> {
+    forecasts
+        .iter()
+        .filter(|forecast| &forecast.area == area && forecast.time <= time)
+        .max_by_key(|forecast| forecast.time)
+}
+
+async fn stored_links(database: &Database) -> eyre::Result<HashMap<ObservationId, ForecastLink>> {
+    Ok(sqlx::query!(
+        r#"SELECT
+            observation_id as "observation_id: ObservationId",
+            forecast_area as "forecast_area: ForecastAreaId",
+            forecast_file_name,
+            forecast_time as "forecast_time: types::Time"
+        FROM observation_forecasts"#
+    )
+    .fetch_all(database)
+    .await?
+    .into_iter()
+    .map(|record| {
+        (
+            record.observation_id,
+            ForecastLink {
+                forecast_area: record.forecast_area,
+                forecast_file_name: record.forecast_file_name,
+                forecast_time: record.forecast_time,
+            },
+        )
+    })
+    .collect())
+}
+
+/// Link the `observations`, storing the links which have changed. Returns the link for each of the
+/// `observations`.
+pub async fn update_links(
+    database: &Database,
+    linker: &Linker,
+    observations: &[Observation],
+) -> eyre::Result<HashMap<ObservationId, ForecastLink>> {
+    let stored = stored_links(database).await?;
+    let now = types::Time::now_utc();
+    let mut links = HashMap::with_capacity(observations.len());
+    for observation in observations {
+        let link = linker.link(observation);
+        if stored.get(&observation.id) != Some(&link) {
+            sqlx::query!(
+                "INSERT INTO observation_forecasts VALUES($1, $2, $3, $4, $5) ON CONFLICT(observation_id) DO UPDATE SET linked_at=excluded.linked_at, forecast_area=excluded.forecast_area, forecast_file_name=excluded.forecast_file_name, forecast_time=excluded.forecast_time",
+                observation.id,
+                now,
+                link.forecast_area,
+                link.forecast_file_name,
+                link.forecast_time,
+            )
+            .execute(database)
+            .await?;
+        }
+        links.insert(observation.id.clone(), link);
+    }
+    Ok(links)
+}
+
+/// The number of observations made since the latest forecast for an area, to aid the next
+/// forecast.
+#[derive(Serialize, Debug)]
+pub struct NewObservations {
+    pub area: ForecastAreaId,
+    pub forecast_file_name: String,
+    #[serde(with = "time::serde::rfc3339")]
+    pub forecast_time: OffsetDateTime,
+    pub pending: usize,
+    pub approved: usize,
+}
+
+pub async fn new_observations(
+    database: &Database,
+    state: &AppState,
+) -> eyre::Result<Vec<NewObservations>> {
+    let linker = Linker::load(database, state).await?;
+    let latest = linker.latest_forecasts();
+    let Some(since) = latest.iter().map(|forecast| forecast.time).min() else {
+        return Ok(Vec::new());
+    };
+    let mut observations = Vec::new();
+    for status in [ObservationStatus::Pending, ObservationStatus::Approved] {
+        let filter = ObservationFilter {
+            observed_after: Some(since.into()),
+            ..ObservationFilter::status(status)
+        };
+        observations.extend(query_observations(database, &filter).await?);
+    }
+    let links = update_links(database, &linker, &observations).await?;
+    Ok(latest
+        .into_iter()
+        .map(|forecast| {
+            let count = |status: ObservationStatus| {
+                observations
+                    .iter()
+                    .filter(|observation| {
+                        observation.status == status
+                            && links.get(&observation.id).is_some_and(|link| {
+                                link.forecast_file_name.as_ref() == Some(&forecast.file_name)
+                            })
+                    })
+                    .count()
+            };
+            NewObservations {
+                area: forecast.area.clone(),
+                forecast_file_name: forecast.file_name.clone(),
+                forecast_time: forecast.time,
+                pending: count(ObservationStatus::Pending),
+                approved: count(ObservationStatus::Approved),
+            }
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod test {
+    use time::macros::datetime;
+
+    use crate::forecast_areas::ForecastAreaId;
+
+    use super::{forecast_in_force, PublishedForecast};
+
+    fn forecast(area: &str, time: time::OffsetDateTime) -> PublishedForecast {
+        PublishedForecast {
+            area: ForecastAreaId::from(area.to_owned()),
+            time,
+            file_name: format!("{area}_{time}.xlsx"),
+        }
+    }
+
+    #[test]
+    fn test_forecast_in_force() {
+        let forecasts = [
+            forecast("gudauri", datetime!(2024-01-01 17:00 UTC)),
+            forecast("gudauri", datetime!(2024-01-02 17:00 UTC)),
+            forecast("bansko", datetime!(2024-01-02 12:00 UTC)),
+        ];
+        let gudauri = ForecastAreaId::from("gudauri".to_owned());
+        assert_eq!(
+            forecast_in_force(&forecasts, &gudauri, datetime!(2024-01-02 09:00 UTC)),
+            Some(&forecasts[0])
+        );
+        assert_eq!(
+            forecast_in_force(&forecasts, &gudauri, datetime!(2024-01-02 17:00 UTC)),
+            Some(&forecasts[1])
+        );
+        assert_eq!(
+            forecast_in_force(&forecasts, &gudauri, datetime!(2024-01-01 09:00 UTC)),
+            None
+        );
+        assert_eq!(
+            forecast_in_force(
+                &forecasts,
+                &ForecastAreaId::from("bansko".to_owned()),
+                datetime!(2024-01-05 09:00 UTC)
+            ),
+            Some(&forecasts[2])
+        );
+    }
+}
@@ -0,0 +1,927 @@
+//! Submission, storage and public listing of field observations.
+
+use axum::{
+    extract::{DefaultBodyLimit, Path, State},
+    http::{header, HeaderMap, HeaderValue},
+    response::{IntoResponse, Response},
+    routing::get,
+    Extension, Router,
+};
+use eyre::{Context, ContextCompat};
+use forecast_spreadsheet::{Aspect, ProblemKind};
+use http::StatusCode;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    client_ip,
+    database::{
+        blob::{BlobHash, BlobStore},
+        Database,
+    },
+    diagrams::snow_profile::{GrainType, Hardness, SnowProfile},
+    error::map_eyre_error,
+    forecasts::variant_id,
+    i18n::{self, I18nLoader},
+    state::AppState,
+    templates::TemplatesWithContext,
+    types, webhooks,
+};
+
+pub mod activity;
+pub mod geojson;
+pub mod linking;
+mod throttle;
+
+pub use throttle::SubmissionThrottle;
+
+/// Maximum number of photos that can be attached to a single observation.
+pub const MAX_PHOTOS: usize = 5;
+/// Maximum size of a single photo in bytes.
+pub const MAX_PHOTO_BYTES: usize = 10 * 1024 * 1024;
+/// Maximum size of the whole submission request in bytes.
+const MAX_SUBMISSION_BYTES: usize = MAX_PHOTOS * MAX_PHOTO_BYTES + 1024 * 1024;
+/// Content types accepted for photos.
+pub const PHOTO_CONTENT_TYPES: &[&str] = &["image/jpeg", "image/png", "image/webp"];
+/// Maximum number of photos attached to observations which are waiting for moderation, further
+/// submissions with photos are refused until some have been moderated.
+const MAX_PENDING_PHOTOS: i64 = 200;
+
+pub fn router() -> Router<AppState> {
+    Router::new()
+        .route("/", get(index_handler))
+        .route(
+            "/submit",
+            get(submit_form_handler)
+                .post(submit_handler)
+                .layer(DefaultBodyLimit::max(MAX_SUBMISSION_BYTES)),
+        )
+        .route("/observations.geojson", get(geojson::handler))
+        .route("/activity", get(activity::handler))
+        .route("/{id}", get(observation_handler))
+        .route("/photos/{id}", get(photo_handler))
+}
+
+#[derive(sqlx::Type, Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Hash)]
+#[serde(transparent)]
+#[sqlx(transparent)]
+pub struct ObservationId(String);
+
+impl ObservationId {
+    pub fn generate() -> Self {
+        Self(uuid::Uuid::new_v4().to_string())
+    }
+}
+
+impl std::fmt::Display for ObservationId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+#[derive(sqlx::Type, Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Hash)]
+#[serde(transparent)]
+#[sqlx(transparent)]
+pub struct PhotoId(String);
+
+impl std::fmt::Display for PhotoId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+/// Moderation status of an observation. Only [`ObservationStatus::Approved`] observations are
+/// shown publicly.
+#[derive(sqlx::Type, Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+#[sqlx(rename_all = "kebab-case")]
+pub enum ObservationStatus {
+    Pending,
+    Approved,
+    Rejected,
+}
+
+/// Avalanche activity reported with an observation.
+#[derive(sqlx::Type, Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+#[sqlx(rename_all = "kebab-case")]
+pub enum AvalancheActivity {
+    /// No avalanches were observed.
+    None,
+    /// Avalanches that released naturally were observed.
+    Natural,
+    /// An avalanche was triggered by a person or by explosives.
+    Triggered,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct Observation {
+    pub id: ObservationId,
+    pub created_at: types::Time,
+    pub observed_at: types::Time,
+    pub latitude: f64,
+    pub longitude: f64,
+    pub elevation_metres: Option<i64>,
+    /// See [`Aspect`].
+    pub aspect: Option<String>,
+    pub avalanche_activity: AvalancheActivity,
+    pub avalanche_size: Option<i64>,
+    /// See [`ProblemKind`].
+    pub avalanche_problem: Option<String>,
+    pub description: String,
+    pub observer_name: Option<String>,
+    pub status: ObservationStatus,
+    pub snow_profile: Option<sqlx::types::Json<SnowProfile>>,
+}
+
+pub struct Photo {
+    pub content_type: String,
+    pub data: Vec<u8>,
+}
+
+pub struct NewPhoto {
+    pub content_type: String,
+    pub data: Vec<u8>,
+}
+
+pub async fn insert_observation(
+    database: &Database,
+    blobs: &BlobStore,
+    observation: &Observation,
+    photos: Vec<NewPhoto>,
+) -> eyre::Result<()> {
+    let mut photo_blobs = Vec::with_capacity(photos.len());
+    for photo in photos {
+        let blob_hash = blobs.put(photo.data.into()).await?;
+        photo_blobs.push((photo.content_type, blob_hash));
+    }
+
+    let mut transaction = database.begin().await?;
+    sqlx::query!(
+        "INSERT INTO observations VALUES($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14)",
+        observation.id,
+        observation.created_at,
+        observation.observed_at,
+        observation.latitude,
+        observation.longitude,
+        observation.elevation_metres,
+        observation.aspect,
+        observation.avalanche_activity,
+        observation.avalanche_size,
+        observation.avalanche_problem,
+        observation.description,
+        observation.observer_name,
+        observation.status,
+        observation.snow_profile,
+    )
+    .execute(&mut *transaction)
+    .await?;
+
+    for (content_type, blob_hash) in photo_blobs {
+        let photo_id = uuid::Uuid::new_v4().to_string();
+        sqlx::query!(
+            "INSERT INTO observation_photos(id, observation_id, content_type, data, blob_hash) VALUES($1, $2, $3, x'', $4)",
+            photo_id,
+            observation.id,
+            content_type,
+            blob_hash,
+        )
+        .execute(&mut *transaction)
+        .await?;
+    }
+    transaction.commit().await?;
+    Ok(())
+}
+
+/// A geographic bounding box in WGS84 coordinates.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BoundingBox {
+    pub min_longitude: f64,
+    pub min_latitude: f64,
+    pub max_longitude: f64,
+    pub max_latitude: f64,
+}
+
+impl std::str::FromStr for BoundingBox {
+    type Err = eyre::Error;
+
+    /// Parse a bounding box in the GeoJSON `bbox` order:
+    /// `min_longitude,min_latitude,max_longitude,max_latitude`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let values = s
+            .split(',')
+            .map(|value| value.trim().parse::<f64>())
+            .collect::<Result<Vec<f64>, _>>()
+            .wrap_err_with(|| format!("Invalid bounding box {s:?}"))?;
+        let [min_longitude, min_latitude, max_longitude, max_latitude] = values[..] else {
+            eyre::bail!("Bounding box {s:?} should contain exactly 4 values");
+        };
+        if min_longitude > max_longitude || min_latitude > max_latitude {
+            eyre::bail!("Bounding box {s:?} minimum exceeds maximum");
+        }
+        Ok(Self {
+            min_longitude,
+            min_latitude,
+            max_longitude,
+            max_latitude,
+        })
+    }
+}
+
+/// Criteria for selecting observations with [`query_observations`].
+pub struct ObservationFilter {
+    pub status: ObservationStatus,
+    /// Only include observations made at or after this time.
+    pub observed_after: Option<types::Time>,
+    /// Only include observations made before this time.
+    pub observed_before: Option<types::Time>,
+    /// Only include observations positioned within this bounding box.
+    pub bounding_box: Option<BoundingBox>,
+}
+
+impl ObservationFilter {
+    pub fn status(status: ObservationStatus) -> Self {
+        Self {
+            status,
+            observed_after: None,
+            observed_before: None,
+            bounding_box: None,
+        }
+    }
+}
+
+/// Query observations matching the `filter`, most recently observed first.
+pub async fn query_observations(
+    database: &Database,
+    filter: &ObservationFilter,
+) -> eyre::Result<Vec<Observation>> {
+    let (min_longitude, min_latitude, max_longitude, max_latitude) = match filter.bounding_box {
+        Some(bounding_box) => (
+            Some(bounding_box.min_longitude),
+            Some(bounding_box.min_latitude),
+            Some(bounding_box.max_longitude),
+            Some(bounding_box.max_latitude),
+        ),
+        None => (None, None, None, None),
+    };
+    Ok(sqlx::query_as!(
+        Observation,
+        r#"SELECT
+            id as "id: ObservationId",
+            created_at as "created_at: types::Time",
+            observed_at as "observed_at: types::Time",
+            latitude,
+            longitude,
+            elevation_metres,
+            aspect,
+            avalanche_activity as "avalanche_activity: AvalancheActivity",
+            avalanche_size,
+            avalanche_problem,
+            description,
+            observer_name,
+            status as "status: ObservationStatus",
+            snow_profile as "snow_profile: sqlx::types::Json<SnowProfile>"
+        FROM observations
+        WHERE status = $1
+            AND ($2 IS NULL OR observed_at >= $2)
+            AND ($3 IS NULL OR observed_at < $3)
+            AND ($4 IS NULL OR longitude >= $4)
+            AND ($5 IS NULL OR latitude >= $5)
+            AND ($6 IS NULL OR longitude <= $6)
+            AND ($7 IS NULL OR latitude <= $7)
+        ORDER BY observed_at DESC"#,
+        filter.status,
+        filter.observed_after,
+        filter.observed_before,
+        min_longitude,
+        min_latitude,
+        max_longitude,
+        max_latitude,
+    )
+    .fetch_all(database)
+    .await?)
+}
+
+pub async fn list_observations(
+    database: &Database,
+    status: ObservationStatus,
+) -> eyre::Result<Vec<Observation>> {
+    query_observations(database, &ObservationFilter::status(status)).await
+}
+
+pub async fn get_observation(
+    database: &Database,
+    id: &ObservationId,
+) -> eyre::Result<Option<Observation>> {
+    Ok(sqlx::query_as!(
+        Observation,
+        r#"SELECT
+            id as "id: ObservationId",
+            created_at as "created_at: types::Time",
+            observed_at as "observed_at: types::Time",
+            latitude,
+            longitude,
+            elevation_metres,
+            aspect,
+            avalanche_activity as "avalanche_activity: AvalancheActivity",
+            avalanche_size,
+            avalanche_problem,
+            description,
+            observer_name,
+            status as "status: ObservationStatus",
+            snow_profile as "snow_profile: sqlx::types::Json<SnowProfile>"
+        FROM observations WHERE id = $1"#,
+        id
+    )
+    .fetch_optional(database)
+    .await?)
+}
+
+pub async fn list_photo_ids(
+    database: &Database,
+    observation_id: &ObservationId,
+) -> eyre::Result<Vec<PhotoId>> {
+    Ok(sqlx::query!(
+        r#"SELECT id as "id: PhotoId" FROM observation_photos WHERE observation_id = $1"#,
+        observation_id
+    )
+    .fetch_all(database)
+    .await?
+    .into_iter()
+    .map(|record| record.id)
+    .collect())
+}
+
+/// Get a photo, along with the moderation status of the observation it belongs to.
+pub async fn get_photo(
+    database: &Database,
+    blobs: &BlobStore,
+    id: &PhotoId,
+) -> eyre::Result<Option<(Photo, ObservationStatus)>> {
+    let Some(record) = sqlx::query!(
+        r#"SELECT
+            observation_photos.content_type,
+            observation_photos.data,
+            observation_photos.blob_hash as "blob_hash: BlobHash",
+            observations.status as "status: ObservationStatus"
+        FROM observation_photos
+        INNER JOIN observations ON observations.id = observation_photos.observation_id
+        WHERE observation_photos.id = $1"#,
+        id
+    )
+    .fetch_optional(database)
+    .await?
+    else {
+        return Ok(None);
+    };
+
+    let data = match record.blob_hash {
+        Some(blob_hash) => blobs
+            .read(&blob_hash)
+            .await?
+            .wrap_err_with(|| format!("Blob {blob_hash} for photo {id} is missing"))?,
+        None => record.data,
+    };
+    Ok(Some((
+        Photo {
+            content_type: record.content_type,
+            data,
+        },
+        record.status,
+    )))
+}
+
+/// Move the photos which are still stored in the `data` column of `observation_photos` into the
+/// blob store.
+pub async fn migrate_photos_to_blobs(database: &Database, blobs: &BlobStore) -> eyre::Result<()> {
+    let ids = sqlx::query_scalar!("SELECT id FROM observation_photos WHERE blob_hash IS NULL")
+        .fetch_all(database)
+        .await?;
+    if ids.is_empty() {
+        return Ok(());
+    }
+    tracing::info!(
+        "Moving {} observation photos into the blob store",
+        ids.len()
+    );
+    for id in ids {
+        let data = sqlx::query_scalar!("SELECT data FROM observation_photos WHERE id = $1", id)
+            .fetch_one(database)
+            .await?;
+        let blob_hash = blobs.put(data.into()).await?;
+        sqlx::query!(
+            "UPDATE observation_photos SET data = x'', blob_hash = $1 WHERE id = $2",
+            blob_hash,
+            id
+        )
+        .execute(database)
+        .await
+        .wrap_err_with(|| format!("Error moving observation photo {id} into the blob store"))?;
+    }
+    Ok(())
+}
+
+pub async fn set_observation_status(
+    database: &Database,
+    id: &ObservationId,
+    status: ObservationStatus,
+) -> eyre::Result<()> {
+    sqlx::query!(
+        "UPDATE observations SET status = $1 WHERE id = $2",
+        status,
+        id
+    )
+    .execute(database)
+    .await?;
+    Ok(())
+}
+
+pub async fn delete_observation(database: &Database, id: &ObservationId) -> eyre::Result<()> {
+    sqlx::query!("DELETE FROM observations WHERE id = $1", id)
+        .execute(database)
+        .await?;
+    Ok(())
+}
+
+/// An [`Observation`] with values formatted for display in templates.
+#[derive(Serialize)]
+pub struct ObservationContext {
+    #[serde(flatten)]
+    pub observation: Observation,
+    pub formatted_observed_at: String,
+    pub photo_ids: Vec<PhotoId>,
+    /// Path to the diagram of the [`Observation::snow_profile`].
+    pub snow_profile_diagram: Option<String>,
+}
+
+impl ObservationContext {
+    pub async fn format(
+        observation: Observation,
+        database: &Database,
+        i18n: &I18nLoader,
+    ) -> eyre::Result<Self> {
+        let photo_ids = list_photo_ids(database, &observation.id).await?;
+        let snow_profile_diagram = observation
+            .snow_profile
+            .as_ref()
+            .map(|snow_profile| -> eyre::Result<String> {
+                let query_string = serde_urlencoded::to_string(snow_profile.to_query()?)?;
+                Ok(format!("/diagrams/snow_profile.svg?{query_string}"))
+            })
+            .transpose()?;
+        Ok(Self {
+            formatted_observed_at: i18n::format_time(observation.observed_at.into(), i18n),
+            observation,
+            photo_ids,
+            snow_profile_diagram,
+        })
+    }
+}
+
+#[derive(Serialize)]
+struct ObservationPageContext {
+    observation: ObservationContext,
+}
+
+#[derive(Serialize)]
+struct IndexContext {
+    observations: Vec<ObservationContext>,
+}
+
+async fn index_handler(
+    Extension(templates): Extension<TemplatesWithContext>,
+    Extension(database): Extension<Database>,
+    Extension(i18n): Extension<I18nLoader>,
+) -> axum::response::Result<Response> {
+    let mut observations = Vec::new();
+    for observation in list_observations(&database, ObservationStatus::Approved)
+        .await
+        .map_err(map_eyre_error)?
+    {
+        observations.push(
+            ObservationContext::format(observation, &database, &i18n)
+                .await
+                .map_err(map_eyre_error)?,
+        );
+    }
+    Ok(templates
+        .render("observations/index.html", &IndexContext { observations })
+        .map_err(map_eyre_error)?)
+}
+
+async fn observation_handler(
+    Path(id): Path<ObservationId>,
+    Extension(templates): Extension<TemplatesWithContext>,
+    Extension(database): Extension<Database>,
+    Extension(i18n): Extension<I18nLoader>,
+) -> axum::response::Result<Response> {
+    let observation = match get_observation(&database, &id)
+        .await
+        .map_err(map_eyre_error)?
+    {
+        Some(observation) if observation.status == ObservationStatus::Approved => observation,
+        _ => return Ok(StatusCode::NOT_FOUND.into_response()),
+    };
+    let observation = ObservationContext::format(observation, &database, &i18n)
+        .await
+        .map_err(map_eyre_error)?;
+    Ok(templates
+        .render(
+            "observations/observation.html",
+            &ObservationPageContext { observation },
+        )
+        .map_err(map_eyre_error)?)
+}
+
+/// The content type of the photo `data`, identified using its signature, or `None` if it is not
+/// one of the [`PHOTO_CONTENT_TYPES`].
+pub fn photo_content_type(data: &[u8]) -> Option<&'static str> {
+    if data.starts_with(&[0xff, 0xd8, 0xff]) {
+        Some("image/jpeg")
+    } else if data.starts_with(b"\x89PNG\r\n\x1a\n") {
+        Some("image/png")
+    } else if data.starts_with(b"RIFF") && data.get(8..12) == Some(b"WEBP".as_slice()) {
+        Some("image/webp")
+    } else {
+        None
+    }
+}
+
+pub fn photo_response(photo: Photo) -> eyre::Result<Response> {
+    let mut headers = HeaderMap::new();
+    headers.insert(
+        header::CONTENT_TYPE,
+        HeaderValue::from_str(&photo.content_type)?,
+    );
+    headers.insert(
+        header::X_CONTENT_TYPE_OPTIONS,
+        HeaderValue::from_static("nosniff"),
+    );
+    Ok((headers, photo.data).into_response())
+}
+
+async fn photo_handler(
+    Path(id): Path<PhotoId>,
+    Extension(database): Extension<Database>,
+    State(state): State<AppState>,
+) -> axum::response::Result<Response> {
+    match get_photo(&database, &state.blobs, &id)
+        .await
+        .map_err(map_eyre_error)?
+    {
+        Some((photo, ObservationStatus::Approved)) => {
+            Ok(photo_response(photo).map_err(map_eyre_error)?)
+        }
+        _ => Ok(StatusCode::NOT_FOUND.into_response()),
+    }
+}
+
+#[derive(Serialize)]
+struct SubmitFormContext {
+    aspects: Vec<String>,
+    problem_kinds: Vec<String>,
+    hardnesses: Vec<&'static str>,
+    grain_types: Vec<&'static str>,
+    max_photos: usize,
+}
+
+async fn submit_form_handler(
+    Extension(templates): Extension<TemplatesWithContext>,
+) -> axum::response::Result<Response> {
+    let context = SubmitFormContext {
+        aspects: ["N", "NE", "E", "SE", "S", "SW", "W", "NW"]
+            .into_iter()
+            .map(ToOwned::to_owned)
+            .collect(),
+        problem_kinds: [
+            ProblemKind::LooseDry,
+            ProblemKind::LooseWet,
+            ProblemKind::StormSlab,
+            ProblemKind::WindSlab,
+            ProblemKind::WetSlab,
+            ProblemKind::PersistentSlab,
+            ProblemKind::DeepSlab,
+            ProblemKind::Cornice,
+            ProblemKind::Glide,
+        ]
+        .iter()
+        .map(variant_id)
+        .collect(),
+        hardnesses: Hardness::ALL.iter().map(Hardness::id).collect(),
+        grain_types: GrainType::ALL.iter().map(GrainType::id).collect(),
+        max_photos: MAX_PHOTOS,
+    };
+    Ok(templates
+        .render("observations/submit.html", &context)
+        .map_err(map_eyre_error)?)
+}
+
+/// Parse a position in the format `latitude,longitude`.
+fn parse_position(position: &str) -> eyre::Result<(f64, f64)> {
+    let (latitude, longitude) = position
+        .split_once(',')
+        .wrap_err_with(|| format!("Invalid position {position:?}"))?;
+    let latitude: f64 = latitude.trim().parse().wrap_err("Invalid latitude")?;
+    let longitude: f64 = longitude.trim().parse().wrap_err("Invalid longitude")?;
+    if !(-90.0..=90.0).contains(&latitude) || !(-180.0..=180.0).contains(&longitude) {
+        eyre::bail!("Position {position:?} is out of range");
+    }
+    Ok((latitude, longitude))
+}
+
+/// Parse the value of a `datetime-local` input, which is in the user's local time, using the
+/// offset from UTC supplied by the browser (as returned by `Date.getTimezoneOffset()`).
+fn parse_observed_at(
+    observed_at: &str,
+    timezone_offset_minutes: i64,
+) -> eyre::Result<time::OffsetDateTime> {
+    let format = time::macros::format_description!("[year]-[month]-[day]T[hour]:[minute]");
+    let local = time::PrimitiveDateTime::parse(observed_at, &format)
+        .wrap_err_with(|| format!("Invalid observation time {observed_at:?}"))?;
+    Ok(local.assume_utc() + time::Duration::minutes(timezone_offset_minutes))
+}
+
+/// Parse a form value into one of the `kebab-case` serialized enums.
+fn parse_kebab_case<T: serde::de::DeserializeOwned>(value: &str) -> serde_json::Result<T> {
+    serde_json::from_value(serde_json::Value::String(value.to_owned()))
+}
+
+fn non_empty(value: String) -> Option<String> {
+    let value = value.trim();
+    if value.is_empty() {
+        None
+    } else {
+        Some(value.to_owned())
+    }
+}
+
+/// Read the submission form, returning the observation and its photos.
+async fn read_submission(
+    mut multipart: axum::extract::Multipart,
+) -> eyre::Result<(Observation, Vec<NewPhoto>)> {
+    let mut position = None;
+    let mut observed_at = None;
+    let mut timezone_offset_minutes: i64 = 0;
+    let mut elevation_metres = None;
+    let mut aspect = None;
+    let mut avalanche_activity = None;
+    let mut avalanche_size = None;
+    let mut avalanche_problem = None;
+    let mut description = None;
+    let mut observer_name = None;
+    let mut snow_profile = None;
+    let mut photos = Vec::new();
+
+    while let Some(field) = multipart.next_field().await? {
+        let name = field.name().map(ToOwned::to_owned);
+        match name.as_deref() {
+            Some("position") => position = Some(parse_position(&field.text().await?)?),
+            Some("observed_at") => observed_at = Some(field.text().await?),
+            Some("timezone_offset_minutes") => {
+                if let Some(offset) = non_empty(field.text().await?) {
+                    timezone_offset_minutes =
+                        offset.parse().wrap_err("Invalid timezone_offset_minutes")?;
+                }
+            }
+            Some("elevation_metres") => {
+                elevation_metres = Option::transpose(
+                    non_empty(field.text().await?).map(|elevation| elevation.parse::<i64>()),
+                )
+                .wrap_err("Invalid elevation")?;
+            }
+            Some("aspect") => {
+                aspect = Option::transpose(
+                    non_empty(field.text().await?)
+                        .map(|aspect| aspect.parse::<Aspect>().map(|aspect| variant_id(&aspect))),
+                )
+                .wrap_err("Invalid aspect")?;
+            }
+            Some("avalanche_activity") => {
+                let value = field.text().await?;
+                avalanche_activity = Some(
+                    parse_kebab_case::<AvalancheActivity>(&value)
+                        .wrap_err_with(|| format!("Invalid avalanche activity {value:?}"))?,
+                );
+            }
+            Some("avalanche_size") => {
+                avalanche_size = Option::transpose(
+                    non_empty(field.text().await?).map(|size| size.parse::<i64>()),
+                )
+                .wrap_err("Invalid avalanche size")?;
+                if let Some(size) = avalanche_size {
+                    if !(1..=5).contains(&size) {
+                        eyre::bail!("Avalanche size {size} must be between 1 and 5");
+                    }
+                }
+            }
+            Some("avalanche_problem") => {
+                avalanche_problem =
+                    Option::transpose(non_empty(field.text().await?).map(|problem| {
+                        parse_kebab_case::<ProblemKind>(&problem)
+                            .map(|problem| variant_id(&problem))
+                    }))
+                    .wrap_err("Invalid avalanche problem")?;
+            }
+            Some("description") => description = Some(field.text().await?),
+            Some("observer_name") => observer_name = non_empty(field.text().await?),
+            Some("snow_profile") => {
+                if let Some(data) = non_empty(field.text().await?) {
+                    let profile: SnowProfile =
+                        serde_json::from_str(&data).wrap_err("Invalid snow profile")?;
+                    profile.validate()?;
+                    snow_profile = Some(sqlx::types::Json(profile));
+                }
+            }
+            Some("photos") => {
+                // Browsers submit an empty file field when no files are selected.
+                if field.file_name().map(str::is_empty).unwrap_or(true) {
+                    continue;
+                }
+                let data = field.bytes().await?;
+                if data.len() > MAX_PHOTO_BYTES {
+                    eyre::bail!("Photo exceeds maximum size of {MAX_PHOTO_BYTES} bytes");
+                }
+                // The content type declared by the client is not trusted, it is served with the
+                // photo.
+                let content_type =
+                    photo_content_type(&data).wrap_err("Photo is not a JPEG, PNG or WebP image")?;
+                photos.push(NewPhoto {
+                    content_type: content_type.to_owned(),
+                    data: data.into(),
+                });
+                if photos.len() > MAX_PHOTOS {
+                    eyre::bail!("A maximum of {MAX_PHOTOS} photos may be submitted");
+                }
+            }
+            _ => {}
+        }
+    }
+
+    let (latitude, longitude) = position.wrap_err("position field was not specified")?;
+    let observed_at = parse_observed_at(
+        &observed_at.wrap_err("observed_at field was not specified")?,
+        timezone_offset_minutes,
+    )?;
+    let observation = Observation {
+        id: ObservationId::generate(),
+        created_at: types::Time::now_utc(),
+        observed_at: observed_at.into(),
+        latitude,
+        longitude,
+        elevation_metres,
+        aspect,
+        avalanche_activity: avalanche_activity
+            .wrap_err("avalanche_activity field was not specified")?,
+        avalanche_size,
+        avalanche_problem,
+        description: description.wrap_err("description field was not specified")?,
+        observer_name,
+        status: ObservationStatus::Pending,
+        snow_profile,
+    };
+    Ok((observation, photos))
+}
+
+/// The number of photos attached to observations which are waiting for moderation.
+async fn pending_photos(database: &Database) -> eyre::Result<i64> {
+    let status = ObservationStatus::Pending;
+    Ok(sqlx::query_scalar!(
+        r#"SELECT COUNT(*) AS "count!: i64" FROM observation_photos
+        INNER JOIN observations ON observations.id = observation_photos.observation_id
+        WHERE observations.status = $1"#,
+        status
+    )
+    .fetch_one(database)
+    .await?)
+}
+
+async fn submit_handler(
+    Extension(templates): Extension<TemplatesWithContext>,
+    Extension(database): Extension<Database>,
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    extensions: http::Extensions,
+    multipart: axum::extract::Multipart,
+) -> axum::response::Result<Response> {
+    let client_ip = client_ip::client_ip(
+        &headers,
+        &extensions,
+        state.options.analytics.client_ip_header.as_deref(),
+    )
+    .map(|ip| ip.to_string());
+    if !state.observation_throttle.try_submit(client_ip.as_deref()) {
+        tracing::warn!("Throttled observation submission from {client_ip:?}");
+        return Ok((
+            StatusCode::TOO_MANY_REQUESTS,
+            "Too many observations have been submitted, try again later",
+        )
+            .into_response());
+    }
+    let (observation, photos) = match read_submission(multipart).await {
+        Ok(submission) => submission,
+        Err(error) => {
+            tracing::warn!("Invalid observation submission: {error:?}");
+            let mut response = map_eyre_error(error);
+            *response.status_mut() = StatusCode::BAD_REQUEST;
+            return Err(response.into());
+        }
+    };
+    if !photos.is_empty() {
+        let pending_photos = pending_photos(&database)
+            .await
+            .wrap_err("Error counting pending photos")
+            .map_err(map_eyre_error)?;
+        if pending_photos + photos.len() as i64 > MAX_PENDING_PHOTOS {
+            tracing::warn!(
+                "Refused observation submission, {pending_photos} photos are pending moderation"
+            );
+            return Ok((
+                StatusCode::SERVICE_UNAVAILABLE,
+                "Too many photos are waiting for moderation, try again later or submit the observation without photos",
+            )
+                .into_response());
+        }
+    }
+    insert_observation(&database, &state.blobs, &observation, photos)
+        .await
+        .wrap_err("Error storing observation")
+        .map_err(map_eyre_error)?;
+    // The link is updated when the observation is reviewed, so it's not an error for the
+    // submission if it can't be linked now.
+    match linking::Linker::load(&database, &state).await {
+        Ok(linker) => {
+            if let Err(error) =
+                linking::update_links(&database, &linker, std::slice::from_ref(&observation)).await
+            {
+                tracing::error!("Error linking observation {}: {error:?}", observation.id);
+            }
+        }
+        Err(error) => tracing::error!("Error loading forecasts to link observations: {error:?}"),
+    }
+    if let Ok(moderation_url) = state.options.base_url().join("admin/observations") {
+        state.webhooks.send(webhooks::Event::ObservationSubmitted {
+            id: observation.id.to_string(),
+            observed_at: *observation.observed_at,
+            latitude: observation.latitude,
+            longitude: observation.longitude,
+            moderation_url,
+        });
+    }
+    Ok(templates
+        .render("observations/submitted.html", &())
+        .map_err(map_eyre_error)?)
+}
+
+#[cfg(test)]
+mod test {
+    use super::{parse_observed_at, parse_position, photo_content_type, BoundingBox};
+
+    #[test]
+    fn test_parse_position() {
+        assert_eq!(
+            parse_position("42.47588, 44.47518").unwrap(),
+            (42.47588, 44.47518)
+        );
+        assert!(parse_position("42.47588").is_err());
+        assert!(parse_position("95.0,44.0").is_err());
+    }
+
+    #[test]
+    fn test_photo_content_type() {
+        assert_eq!(
+            photo_content_type(&[0xff, 0xd8, 0xff, 0xe0, 0x00]),
+            Some("image/jpeg")
+        );
+        assert_eq!(
+            photo_content_type(b"\x89PNG\r\n\x1a\n\x00\x00"),
+            Some("image/png")
+        );
+        assert_eq!(
+            photo_content_type(b"RIFF\x00\x00\x00\x00WEBPVP8 "),
+            Some("image/webp")
+        );
+        assert_eq!(photo_content_type(b"<html><script>"), None);
+        assert_eq!(photo_content_type(b""), None);
+    }
+
+    #[test]
+    fn test_parse_observed_at() {
+        // Georgia is UTC+4, for which the browser reports an offset of -240 minutes.
+        let observed_at = parse_observed_at("2024-01-24T17:00", -240).unwrap();
+        assert_eq!(observed_at, time::macros::datetime!(2024-01-24 13:00 UTC));
+    }
+
+    #[test]
+    fn test_parse_bounding_box() {
+        assert_eq!(
+            "44.3,42.4,44.6,42.6".parse::<BoundingBox>().unwrap(),
+            BoundingBox {
+                min_longitude: 44.3,
+                min_latitude: 42.4,
+                max_longitude: 44.6,
+                max_latitude: 42.6,
+            }
+        );
+        assert!("44.3,42.4,44.6".parse::<BoundingBox>().is_err());
+        assert!("44.6,42.4,44.3,42.6".parse::<BoundingBox>().is_err());
+    }
+}
@@ -0,0 +1,75 @@
+//! Throttling of observation submissions for each client IP address, to limit how much can be
+//! submitted (and stored in the blob store) anonymously before it has been moderated.
+
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+/// Number of submissions from a client IP address after which further submissions are refused
+/// until the [`WINDOW`] has passed.
+const MAX_SUBMISSIONS: u32 = 10;
+/// Period after the first of a series of submissions in which they are counted.
+const WINDOW: Duration = Duration::from_secs(60 * 60);
+
+struct Submissions {
+    count: u32,
+    since: Instant,
+}
+
+impl Submissions {
+    fn is_expired(&self, now: Instant) -> bool {
+        now.duration_since(self.since) >= WINDOW
+    }
+}
+
+/// Recent submissions for each client IP address, cheap to clone.
+#[derive(Clone, Default)]
+pub struct SubmissionThrottle(Arc<Mutex<HashMap<String, Submissions>>>);
+
+impl SubmissionThrottle {
+    /// Record a submission from the `client_ip`, returning `false` if it is refused because of
+    /// too many recent submissions. Clients without a known IP address share the same limit.
+    pub fn try_submit(&self, client_ip: Option<&str>) -> bool {
+        self.try_submit_at(client_ip, Instant::now())
+    }
+
+    fn try_submit_at(&self, client_ip: Option<&str>, now: Instant) -> bool {
+        let mut submissions = self.0.lock().expect("Submission throttle lock poisoned");
+        submissions.retain(|_, submissions| !submissions.is_expired(now));
+        let submissions = submissions
+            .entry(client_ip.unwrap_or("unknown").to_owned())
+            .or_insert(Submissions {
+                count: 0,
+                since: now,
+            });
+        if submissions.count >= MAX_SUBMISSIONS {
+            return false;
+        }
+        submissions.count += 1;
+        true
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::{Duration, Instant};
+
+    use super::{SubmissionThrottle, MAX_SUBMISSIONS, WINDOW};
+
+    #[test]
+    fn test_submission_throttle() {
+        let throttle = SubmissionThrottle::default();
+        let now = Instant::now();
+        for _ in 0..MAX_SUBMISSIONS {
+            assert!(throttle.try_submit_at(Some("203.0.113.7"), now));
+        }
+        assert!(!throttle.try_submit_at(Some("203.0.113.7"), now));
+        assert!(throttle.try_submit_at(Some("198.51.100.2"), now));
+
+        let later = now + WINDOW + Duration::from_secs(1);
+        assert!(throttle.try_submit_at(Some("203.0.113.7"), later));
+        assert_eq!(throttle.0.lock().unwrap().len(), 1);
+    }
+}
@@ -1,12 +1,26 @@
-use std::{collections::HashMap, net::SocketAddr, num::NonZeroU32, path::PathBuf};
+use std::{
+    collections::HashMap,
+    net::{IpAddr, SocketAddr},
+    num::NonZeroU32,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
 
-use crate::serde::hide_secret;
+use crate::{
+    forecast_areas::ForecastAreaId,
+    forecasts::{ForecastSpreadsheetSchema, ForecastSpreadsheetSchemas},
+    google_drive,
+    serde::hide_secret,
+    users::Role,
+};
+use arc_swap::ArcSwap;
 use cronchik::CronSchedule;
 use eyre::ContextCompat;
 use nonzero_ext::nonzero;
+use once_cell::sync::OnceCell;
 use secrecy::SecretString;
 use serde::{ser::Error, Deserialize, Serialize};
-use serde_with::{serde_as, EnumMap};
+use serde_with::{serde_as, DisplayFromStr, EnumMap};
 use toml_env::AutoMapEnvArgs;
 use url::Url;
 
@@ -21,7 +35,8 @@ pub struct Options {
     /// Base url used for http server.
     /// Can be specified by setting the environment variable `BASE_URL`.
     ///
-    /// Default is `http://{listen_address}/`.
+    /// Default is `http://{listen_address}/`, or `https://{listen_address}/` when [`Options::tls`]
+    /// is specified.
     #[serde(default)]
     base_url: Option<url::Url>,
     /// Address by the http server for listening.
@@ -29,32 +44,98 @@ pub struct Options {
     /// Default is `127.0.0.1:3000`.
     #[serde(default = "default_listen_address")]
     pub listen_address: SocketAddr,
+    /// See [`Tls`].
+    #[serde(default)]
+    pub tls: Option<Tls>,
     /// The default selected langauge for the page (used when the user has not yet set a language
     /// or when their browser does not provide an Accept-Language header).
     ///
     /// Default is `["en-UK"]`.
+    ///
+    /// Reloaded without restarting, see [`ReloadableOptions`].
     #[serde(default = "default_default_language_order")]
     pub default_language_order: Vec<unic_langid::LanguageIdentifier>,
+    /// Restrict the languages that are available to users (in the language selector and when
+    /// negotiating the language using the browser's Accept-Language header) to these languages.
+    ///
+    /// Default is `None`, all languages with translations are available.
+    #[serde(default)]
+    pub enabled_languages: Option<Vec<unic_langid::LanguageIdentifier>>,
+    /// The languages shown in the language selector (and listed at `/api/v1/languages`), see
+    /// [`Language`]. Languages without an entry are still available using the browser's
+    /// Accept-Language header.
+    ///
+    /// Default is English, Georgian and Bulgarian.
+    #[serde(default = "default_languages")]
+    pub languages: Vec<Language>,
     /// See [`Map`].
+    ///
+    /// Reloaded without restarting, see [`ReloadableOptions`].
     #[serde(default)]
     pub map: Map,
     /// See [`Backup`].
     #[serde(default)]
     pub backup: Option<Backup>,
+    /// Additional destinations that backups are sent to, each with their own schedule, keyed by
+    /// a name shown on the admin backups page. See [`BackupDestination`].
+    #[serde(default)]
+    pub backup_destinations: indexmap::IndexMap<String, BackupDestination>,
     /// See [`Analytics`].
     #[serde(default)]
     pub analytics: Analytics,
+    /// See [`Maintenance`].
+    #[serde(default)]
+    pub maintenance: Maintenance,
     /// See [`GoogleDrive`].
     pub google_drive: GoogleDrive,
-    #[serde(serialize_with = "hide_secret::serialize")]
-    /// (REQUIRED) Hash of the `admin` user password, used to access `/admin/*` routes.
-    pub admin_password_hash: SecretString,
+    /// Hash of the `admin` user password (hashed using bcrypt, see the `admin-password-hash`
+    /// tool). Used to create the `admin` user when there are no users yet, further users can
+    /// then be created by the `admin` user at `/admin/users`.
+    #[serde(default, serialize_with = "hide_secret::serialize_option")]
+    pub admin_password_hash: Option<SecretString>,
+    /// Secret used to sign login session cookies.
+    ///
+    /// Default is a randomly generated secret, which means that users need to log in again
+    /// whenever the server restarts.
+    #[serde(default, serialize_with = "hide_secret::serialize_option")]
+    pub session_secret: Option<SecretString>,
+    /// See [`Oidc`].
+    #[serde(default)]
+    pub oidc: Option<Oidc>,
+    /// Maximum number of rendered diagrams kept in memory, `0` disables the cache.
+    ///
+    /// Default is `512`.
+    #[serde(default = "default_diagram_cache_capacity")]
+    pub diagram_cache_capacity: usize,
+    /// Maximum time to wait for the background tasks to finish their pending work (e.g. writing
+    /// the accumulated analytics events) when the server is shutting down.
+    ///
+    /// Default is `10`.
+    #[serde(default = "default_shutdown_timeout_seconds")]
+    pub shutdown_timeout_seconds: u64,
     /// See [`WeatherMap`].
+    ///
+    /// Reloaded without restarting, see [`ReloadableOptions`].
     #[serde(default)]
     pub weather_maps: WeatherMaps,
     /// See [`WeatherStation`].
     #[serde(default)]
     pub weather_stations: HashMap<WeatherStationId, WeatherStation>,
+    /// Webcams displayed on the index and weather pages, keyed by an id used in the image url
+    /// `/webcams/{id}/latest`. See [`Webcam`].
+    ///
+    /// Reloaded without restarting, see [`ReloadableOptions`].
+    #[serde(default)]
+    pub webcams: indexmap::IndexMap<String, Webcam>,
+    /// Locations for which to fetch weather model forecasts, for each forecast area.
+    /// See [`WeatherForecastLocation`].
+    #[serde(default)]
+    pub weather_forecasts: HashMap<forecast_spreadsheet::AreaId, WeatherForecastLocation>,
+    /// Paths to digital elevation models (single band GeoTIFF in longitude/latitude coordinates)
+    /// covering each forecast area, keyed by forecast area id. Used to render the terrain
+    /// overlays on the forecast map.
+    #[serde(default)]
+    pub digital_elevation_models: HashMap<ForecastAreaId, PathBuf>,
     /// See [`I18n`].
     #[serde(default)]
     pub i18n: I18n,
@@ -65,9 +146,526 @@ pub struct Options {
     /// Gudauri schema.
     #[serde(default)]
     pub forecast_spreadsheet_schema: Option<PathBuf>,
+    /// Paths to schemas for other (e.g. older) versions of the forecast spreadsheet template.
+    /// Each spreadsheet is parsed using the schema matching the template version specified in
+    /// the spreadsheet.
+    #[serde(default)]
+    pub forecast_spreadsheet_schemas: Vec<PathBuf>,
     /// See [`StaticFiles`].
     #[serde(default)]
     pub static_files: StaticFiles,
+    /// See [`Metrics`].
+    #[serde(default)]
+    pub metrics: Option<Metrics>,
+    /// See [`AdminAllowlist`].
+    #[serde(default)]
+    pub admin_allowlist: Option<AdminAllowlist>,
+    /// See [`MachineTranslation`].
+    #[serde(default)]
+    pub machine_translation: Option<MachineTranslation>,
+    /// See [`Webhooks`].
+    #[serde(default)]
+    pub webhooks: Webhooks,
+    /// See [`Alerts`].
+    #[serde(default)]
+    pub alerts: Option<Alerts>,
+    /// See [`ForecastPublication`].
+    #[serde(default)]
+    pub forecast_publication: ForecastPublication,
+    /// See [`Api`].
+    #[serde(default)]
+    pub api: Api,
+    /// See [`Aggregator`].
+    #[serde(default)]
+    pub aggregators: Vec<Aggregator>,
+    /// See [`Features`].
+    #[serde(default)]
+    pub features: Features,
+    /// Items of the navigation menu shown at the top of the public pages, in order. See
+    /// [`NavigationItem`].
+    ///
+    /// Default is a link to each of the enabled pages, see [`Options::navigation`].
+    #[serde(default)]
+    pub navigation: Option<Vec<NavigationItem>>,
+}
+
+/// Features which can be disabled by deployments which don't use them. The routes of a disabled
+/// feature aren't served, and links to it are hidden.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Features {
+    /// Submission and listing of field observations at `/observations`.
+    ///
+    /// Default is `true`.
+    pub observations: bool,
+    /// The weather stations, weather maps and webcams shown on the index and forecast pages
+    /// (`/weather`, `/current-weather`, `/webcams` and `/api/v1/weather-stations/{id}`).
+    ///
+    /// Default is `true`.
+    pub weather: bool,
+    /// News posts at `/news`.
+    ///
+    /// Default is `true`.
+    pub news: bool,
+    /// The avalanche problem and danger level pages at `/education`, which forecasts link to.
+    ///
+    /// Default is `true`.
+    pub education: bool,
+    /// The embeddable forecast widget at `/widget`.
+    ///
+    /// Default is `true`.
+    pub widget: bool,
+}
+
+impl Default for Features {
+    fn default() -> Self {
+        Self {
+            observations: true,
+            weather: true,
+            news: true,
+            education: true,
+            widget: true,
+        }
+    }
+}
+
+impl Features {
+    /// Whether the page that the `link` refers to is enabled.
+    pub fn link_enabled(&self, link: &NavigationLink) -> bool {
+        match link {
+            NavigationLink::Observations => self.observations,
+            NavigationLink::News => self.news,
+            NavigationLink::Education => self.education,
+            NavigationLink::Forecasts
+            | NavigationLink::Statistics
+            | NavigationLink::Search
+            | NavigationLink::Forecasters
+            | NavigationLink::Url(_) => true,
+        }
+    }
+}
+
+/// An item in the navigation menu, see [`Options::navigation`].
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NavigationItem {
+    /// See [`NavigationLink`].
+    pub link: NavigationLink,
+    /// Label of the item for each language, keyed by language id (e.g. `en-UK`), displayed in
+    /// the user's language if it is available, otherwise in the first available language of the
+    /// `default_language_order`.
+    ///
+    /// Default is the translated name of the page, required for [`NavigationLink::Url`].
+    #[serde(default)]
+    pub label: Option<indexmap::IndexMap<String, String>>,
+}
+
+impl NavigationItem {
+    pub fn new(link: NavigationLink) -> Self {
+        Self { link, label: None }
+    }
+}
+
+/// The page that a [`NavigationItem`] links to.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum NavigationLink {
+    /// The index page, `/`.
+    Forecasts,
+    Observations,
+    News,
+    Education,
+    Statistics,
+    Search,
+    /// The page crediting the [`crate::forecasters`].
+    Forecasters,
+    /// Any other url, e.g. a page managed at `/admin/pages`.
+    Url(String),
+}
+
+impl NavigationLink {
+    pub fn href(&self) -> &str {
+        match self {
+            Self::Forecasts => "/",
+            Self::Observations => "/observations",
+            Self::News => "/news",
+            Self::Education => "/education",
+            Self::Statistics => "/statistics",
+            Self::Search => "/search",
+            Self::Forecasters => "/forecasters",
+            Self::Url(url) => url,
+        }
+    }
+
+    /// Fluent message used as the default label of the page.
+    pub fn message_id(&self) -> Option<&'static str> {
+        Some(match self {
+            Self::Forecasts => "navigation-forecasts",
+            Self::Observations => "navigation-observations",
+            Self::News => "navigation-news",
+            Self::Education => "navigation-education",
+            Self::Statistics => "navigation-statistics",
+            Self::Search => "navigation-search",
+            Self::Forecasters => "navigation-forecasters",
+            Self::Url(_) => return None,
+        })
+    }
+}
+
+/// An external aggregator (e.g. avalanche.org or the EAWS) which newly published and updated
+/// forecasts are pushed to, so that the forecast areas appear on their regional danger maps, see
+/// [`crate::aggregators`].
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Aggregator {
+    /// Name of the aggregator, shown in the delivery log at `/admin/aggregators`.
+    pub name: String,
+    /// Url that the forecasts are sent to in a `POST` request.
+    pub url: Url,
+    /// Token sent in the `Authorization: Bearer {token}` header.
+    ///
+    /// Default is `None`, no `Authorization` header is sent.
+    #[serde(default, serialize_with = "hide_secret::serialize_option")]
+    pub token: Option<SecretString>,
+    /// See [`AggregatorFormat`].
+    ///
+    /// Default is [`AggregatorFormat::Caaml`].
+    #[serde(default)]
+    pub format: AggregatorFormat,
+    /// The region ids used by the aggregator for the forecast areas, keyed by the area name used
+    /// in forecast file names (e.g. `Gudauri`).
+    ///
+    /// Default is to use the area name.
+    #[serde(default)]
+    pub regions: HashMap<String, String>,
+}
+
+/// Format of the forecasts sent to an [`Aggregator`].
+#[derive(Debug, Default, Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum AggregatorFormat {
+    /// A CAAML v6 JSON document containing a bulletin for the forecast.
+    #[default]
+    Caaml,
+    /// The forecast as returned by the `/api/v1/forecasts/{id}` API.
+    Json,
+}
+
+/// Options for the public API (`/api/v1`), and the keys issued for it at `/admin/api-keys`, see
+/// [`crate::api::keys`].
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Api {
+    /// Whether requests to the API must provide a key. Requests without a key are not rate
+    /// limited.
+    ///
+    /// Default is `false`.
+    pub require_key: bool,
+    /// Number of requests per hour initially suggested when issuing a new key.
+    ///
+    /// Default is `1000`.
+    pub default_requests_per_hour: NonZeroU32,
+}
+
+impl Default for Api {
+    fn default() -> Self {
+        Self {
+            require_key: false,
+            default_requests_per_hour: nonzero!(1000u32),
+        }
+    }
+}
+
+/// When forecasts are expected to be published, used to compute the
+/// [`crate::forecasts::ForecastValidity`] of forecasts, and the next expected publication for
+/// each area.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ForecastPublication {
+    /// A current forecast is considered to be expiring soon when it is valid for less than this
+    /// many minutes.
+    ///
+    /// Default is `120`.
+    pub expiring_soon_minutes: u64,
+    /// The schedule that forecasts are expected to be published on for each area, keyed by the
+    /// area name used in forecast file names (e.g. `Gudauri`).
+    ///
+    /// Default is no schedules.
+    pub schedules: HashMap<String, PublicationSchedule>,
+}
+
+impl Default for ForecastPublication {
+    fn default() -> Self {
+        Self {
+            expiring_soon_minutes: 120,
+            schedules: HashMap::new(),
+        }
+    }
+}
+
+impl ForecastPublication {
+    pub fn expiring_soon(&self) -> time::Duration {
+        time::Duration::minutes(i64::try_from(self.expiring_soon_minutes).unwrap_or(i64::MAX))
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PublicationSchedule {
+    /// When forecasts for the area are published (in cron format, UTC), e.g. `0 13 * * *` for
+    /// every day at 13:00 UTC.
+    #[serde(with = "serde_cron")]
+    pub schedule: CronSchedule,
+}
+
+/// Outgoing webhooks, which are sent a JSON `POST` request when events happen (see
+/// [`crate::webhooks::Event`]), for integrating with chat platforms or custom automation.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Webhooks {
+    /// See [`Webhook`].
+    #[serde(default)]
+    pub endpoints: Vec<Webhook>,
+    /// A weather station is considered stale when it has no data newer than this many minutes,
+    /// which sends the `weather-station-stale` event.
+    ///
+    /// Default is `120`.
+    #[serde(default = "default_weather_station_stale_minutes")]
+    pub weather_station_stale_minutes: u64,
+}
+
+impl Default for Webhooks {
+    fn default() -> Self {
+        Self {
+            endpoints: Vec::new(),
+            weather_station_stale_minutes: default_weather_station_stale_minutes(),
+        }
+    }
+}
+
+fn default_weather_station_stale_minutes() -> u64 {
+    120
+}
+
+/// A url which is sent the webhook events.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Webhook {
+    pub url: Url,
+    /// Secret used to sign the request body with HMAC-SHA256, the signature is sent in the
+    /// `X-Avalanche-Report-Signature` header as `sha256={hex encoded signature}`.
+    ///
+    /// Default is `None`, requests are not signed.
+    #[serde(default, serialize_with = "hide_secret::serialize_option")]
+    pub secret: Option<SecretString>,
+    /// Only send these events.
+    ///
+    /// Default is `None`, all events are sent.
+    #[serde(default)]
+    pub events: Option<Vec<crate::webhooks::EventKind>>,
+}
+
+/// Notify operators in a Slack or Matrix room when background tasks (weather station data
+/// fetching, Google Drive refreshes, backups) fail repeatedly, see [`crate::alerts`].
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Alerts {
+    /// See [`SlackAlerts`].
+    #[serde(default)]
+    pub slack: Option<SlackAlerts>,
+    /// See [`MatrixAlerts`].
+    #[serde(default)]
+    pub matrix: Option<MatrixAlerts>,
+    /// Send an alert once a task has failed this many times in a row.
+    ///
+    /// Default is `3`.
+    #[serde(default = "default_alerts_failure_threshold")]
+    pub failure_threshold: u32,
+    /// While a task keeps failing, repeat the alert at most once every this many minutes.
+    ///
+    /// Default is `360`.
+    #[serde(default = "default_alerts_repeat_minutes")]
+    pub repeat_minutes: u64,
+}
+
+fn default_alerts_failure_threshold() -> u32 {
+    3
+}
+
+fn default_alerts_repeat_minutes() -> u64 {
+    360
+}
+
+/// Send alerts to a Slack incoming webhook.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SlackAlerts {
+    #[serde(serialize_with = "hide_secret::serialize")]
+    pub webhook_url: SecretString,
+}
+
+/// Send alerts to a Matrix room, as the user that the `access_token` belongs to (who needs to
+/// have joined the room).
+#[derive(Debug, Serialize, Deserialize)]
+pub struct MatrixAlerts {
+    /// e.g. `https://matrix.org`.
+    pub homeserver_url: Url,
+    /// e.g. `!abcdefghijklmnop:matrix.org`.
+    pub room_id: String,
+    #[serde(serialize_with = "hide_secret::serialize")]
+    pub access_token: SecretString,
+}
+
+/// Automatically translate the free-text sections of forecasts (description, recent
+/// observations and weather forecast) into the languages they were not written in. Machine
+/// translated text is marked as such on the forecast page.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct MachineTranslation {
+    /// See [`MachineTranslationProvider`].
+    #[serde(flatten)]
+    pub provider: MachineTranslationProvider,
+    /// Languages to translate into.
+    ///
+    /// Default is [`Options::enabled_languages`] if specified, otherwise
+    /// [`Options::default_language_order`].
+    #[serde(default)]
+    pub languages: Option<Vec<unic_langid::LanguageIdentifier>>,
+}
+
+/// The service used to perform machine translation.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "provider", rename_all = "kebab-case")]
+pub enum MachineTranslationProvider {
+    /// <https://www.deepl.com/pro-api>
+    #[serde(rename = "deepl")]
+    DeepL {
+        #[serde(serialize_with = "hide_secret::serialize")]
+        api_key: SecretString,
+        /// Default is `https://api-free.deepl.com/`, use `https://api.deepl.com/` for the Pro
+        /// API.
+        #[serde(default = "default_deepl_url")]
+        url: Url,
+    },
+    /// <https://libretranslate.com>, which can also be self-hosted.
+    LibreTranslate {
+        url: Url,
+        #[serde(default, serialize_with = "hide_secret::serialize_option")]
+        api_key: Option<SecretString>,
+    },
+}
+
+fn default_deepl_url() -> Url {
+    "https://api-free.deepl.com/"
+        .parse()
+        .expect("Invalid default DeepL url")
+}
+
+/// Serve HTTPS directly on [`Options::listen_address`], for deployments without a reverse proxy
+/// to terminate TLS.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Tls {
+    /// See [`TlsCertificate`].
+    #[serde(flatten)]
+    pub certificate: TlsCertificate,
+    /// Address for a plain HTTP server which redirects all requests to HTTPS at
+    /// [`Options::base_url`], e.g. `[::]:80`.
+    ///
+    /// Default is `None`, no redirect server.
+    #[serde(default)]
+    pub redirect_listen_address: Option<SocketAddr>,
+}
+
+/// Where the TLS certificate comes from.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "certificate", rename_all = "kebab-case")]
+pub enum TlsCertificate {
+    /// PEM encoded certificate chain and private key files, e.g. obtained using certbot. The files
+    /// are read again on `SIGHUP`, so that a renewed certificate can be used without restarting.
+    Files {
+        certificate_path: PathBuf,
+        key_path: PathBuf,
+    },
+    /// Obtain and renew the certificate automatically from Let's Encrypt using ACME (with the
+    /// TLS-ALPN-01 challenge, so [`Options::listen_address`] needs to be reachable on port 443).
+    Acme {
+        /// Domains the certificate is for.
+        domains: Vec<String>,
+        /// Email addresses that Let's Encrypt can use to notify about problems with the
+        /// certificate.
+        #[serde(default)]
+        contact_emails: Vec<String>,
+        /// Directory where the account and certificate are cached between restarts.
+        ///
+        /// Default is `{data_dir}/acme`.
+        #[serde(default)]
+        cache_directory: Option<PathBuf>,
+        /// Use the Let's Encrypt staging environment, which has much higher rate limits but
+        /// issues certificates which are not trusted by browsers. Useful for testing.
+        ///
+        /// Default is `false`.
+        #[serde(default)]
+        staging: bool,
+    },
+}
+
+/// Configuration for the Prometheus metrics endpoint at `/metrics`, which is only enabled when
+/// this is specified.
+#[derive(Debug, Serialize, Deserialize, Default)]
+pub struct Metrics {
+    /// Client IP addresses which can access the metrics without logging in. Other clients need to
+    /// log in (e.g. using HTTP basic authentication) as a user with permission to view analytics.
+    #[serde(default)]
+    pub allowed_addresses: Vec<std::net::IpAddr>,
+}
+
+/// Restricts the most sensitive administration pages (logs, backups, blobs and database
+/// maintenance) to clients in trusted networks, which is only enabled when this is specified.
+/// Users still need to log in with the required permission. Denied attempts by logged in users are
+/// recorded, shown on the admin page, and sent to webhooks as
+/// [`crate::webhooks::EventKind::AdminAccessDenied`].
+#[serde_as]
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AdminAllowlist {
+    /// Networks in CIDR notation (e.g. `10.0.0.0/8`) or single addresses (e.g. `127.0.0.1`) of
+    /// the clients which can access the pages.
+    #[serde_as(as = "Vec<DisplayFromStr>")]
+    pub networks: Vec<AllowedNetwork>,
+    /// Header containing the client IP address, set this when running behind a proxy (e.g.
+    /// `Fly-Client-IP` or `X-Forwarded-For`). The proxy must overwrite the header, otherwise
+    /// clients can set it themselves. For `X-Forwarded-For` the last address is used, which is
+    /// the one added by the proxy.
+    ///
+    /// Default is the address of the connection.
+    #[serde(default)]
+    pub client_ip_header: Option<String>,
+}
+
+impl AdminAllowlist {
+    pub fn allows(&self, address: &IpAddr) -> bool {
+        self.networks
+            .iter()
+            .any(|network| network.contains(address))
+    }
+}
+
+/// A network in CIDR notation, or a single address.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AllowedNetwork(ipnet::IpNet);
+
+impl AllowedNetwork {
+    pub fn contains(&self, address: &IpAddr) -> bool {
+        self.0.contains(address)
+    }
+}
+
+impl std::str::FromStr for AllowedNetwork {
+    type Err = ipnet::AddrParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.parse::<IpAddr>() {
+            Ok(address) => Ok(Self(address.into())),
+            Err(_) => s.parse().map(Self),
+        }
+    }
+}
+
+impl std::fmt::Display for AllowedNetwork {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.0.fmt(f)
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize, Default)]
@@ -80,8 +678,27 @@ pub struct StaticFiles {
 /// Configuration for the HTML templates.
 #[derive(Debug, Serialize, Deserialize, Default)]
 pub struct Templates {
-    /// The path to the directory containing overrides for templates.
+    /// The path to the directory containing overrides for templates. Takes priority over the
+    /// [`Templates::layers`].
     pub directory: Option<PathBuf>,
+    /// Further directories containing overrides for templates, in order of increasing priority,
+    /// e.g. a base theme shared by several deployments, then the organisation's branding, then
+    /// seasonal changes. Each template is loaded from the highest priority directory containing
+    /// it, falling back to the embedded template.
+    ///
+    /// Default is no layers.
+    #[serde(default)]
+    pub layers: Vec<PathBuf>,
+}
+
+impl Templates {
+    /// The directories containing overrides for templates, highest priority first.
+    pub fn directories(&self) -> impl Iterator<Item = &Path> {
+        self.directory
+            .iter()
+            .chain(self.layers.iter().rev())
+            .map(PathBuf::as_path)
+    }
 }
 
 /// Configuration for application localization.
@@ -108,18 +725,106 @@ impl std::fmt::Display for WeatherStationId {
     }
 }
 
+/// Log in to `/admin` using an OpenID Connect provider, for example with Google Workspace
+/// accounts using the `issuer_url` `https://accounts.google.com`. The provider needs to allow
+/// the redirect URI `{base_url}login/oidc/callback`. When this is not configured users can only
+/// log in using their password.
+///
+/// Users are matched to the user with the username equal to their verified email address.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Oidc {
+    pub issuer_url: Url,
+    pub client_id: String,
+    #[serde(serialize_with = "hide_secret::serialize")]
+    pub client_secret: SecretString,
+    /// Name of the provider displayed on the login page.
+    ///
+    /// Default is `Single Sign-On`.
+    #[serde(default = "default_oidc_display_name")]
+    pub display_name: String,
+    /// Only allow email addresses in these domains to log in, for example the Google Workspace
+    /// domain.
+    ///
+    /// Default is to allow all domains.
+    #[serde(default)]
+    pub allowed_domains: Vec<String>,
+    /// Role given to users which are created the first time they log in. Requires
+    /// [`Oidc::allowed_domains`] to be specified.
+    ///
+    /// Default is to not create users, only existing users can log in.
+    #[serde(default)]
+    pub default_role: Option<Role>,
+}
+
+fn default_oidc_display_name() -> String {
+    "Single Sign-On".to_owned()
+}
+
 /// Configuration for using Google Drive.
 #[derive(Debug, Serialize, Deserialize)]
 pub struct GoogleDrive {
     /// The identifier for the folder in Google Drive where the published forecasts are stored.
     ///
-    /// **WARNING:** This folder needs to have the General Access sharing option set to "Anyone with
-    /// the link" due to some strange Google Drive limitation, otherwise the folder will appear
-    /// empty when using the `api_key` for authentication.
+    /// **WARNING:** When using the `api_key` for authentication, this folder needs to have the
+    /// General Access sharing option set to "Anyone with the link" due to some strange Google
+    /// Drive limitation, otherwise the folder will appear empty. When using a service account,
+    /// the folder only needs to be shared with the service account's email address.
     pub published_folder_id: String,
-    /// Google Drive API key, used to access forecast spreadsheets.
-    #[serde(serialize_with = "hide_secret::serialize")]
-    pub api_key: SecretString,
+    /// Google Drive API key, used to access forecast spreadsheets. Either this or
+    /// `service_account_key_file` needs to be configured.
+    #[serde(default, serialize_with = "hide_secret::serialize_option")]
+    pub api_key: Option<SecretString>,
+    /// Path to the JSON key file of a Google Cloud service account, used to access forecast
+    /// spreadsheets instead of the `api_key`. This allows the published folder to be restricted
+    /// to only be shared with the service account's email address, instead of with anyone with
+    /// the link. The Google Drive API (and the Google Sheets API when using `sheets_api`) needs
+    /// to be enabled for the service account's project.
+    ///
+    /// Default is to use the `api_key`.
+    #[serde(default)]
+    pub service_account_key_file: Option<PathBuf>,
+    /// The service account loaded from `service_account_key_file` when it is first used.
+    #[serde(skip)]
+    service_account: OnceCell<google_drive::ServiceAccount>,
+    /// Interval in seconds between refreshing the listing of published forecasts and their cached
+    /// spreadsheets in the background.
+    ///
+    /// Default is `60`.
+    #[serde(default = "default_refresh_interval_seconds")]
+    pub refresh_interval_seconds: u64,
+    /// Read the values of forecast spreadsheets directly using the Google Sheets API, instead of
+    /// exporting them as xlsx files. This avoids quirks of the export and is faster for large
+    /// spreadsheets. The Google Sheets API needs to be enabled for the `api_key`.
+    ///
+    /// Default is `false`.
+    #[serde(default)]
+    pub sheets_api: bool,
+    /// Maximum size in bytes of a file fetched from Google Drive. Larger files are not cached or
+    /// served.
+    ///
+    /// Default is `104857600` (100 MiB).
+    #[serde(default = "default_max_file_bytes")]
+    pub max_file_bytes: u64,
+}
+
+impl GoogleDrive {
+    /// The service account configured using `service_account_key_file`, if any.
+    pub fn service_account(&self) -> eyre::Result<Option<&google_drive::ServiceAccount>> {
+        let Some(path) = &self.service_account_key_file else {
+            return Ok(None);
+        };
+        self.service_account
+            .get_or_try_init(|| google_drive::ServiceAccount::load(path))
+            .map(Some)
+    }
+}
+
+fn default_refresh_interval_seconds() -> u64 {
+    60
+}
+
+fn default_max_file_bytes() -> u64 {
+    100 * 1024 * 1024
 }
 
 #[serde_as]
@@ -170,6 +875,48 @@ pub struct Backup {
     pub s3_bucket_name: String,
     pub s3_bucket_region: String,
     pub aws_access_key_id: String,
+    /// Key used to encrypt backups (using AES-256-GCM) before they leave the server, 32 bytes
+    /// encoded as base64 (e.g. generated using `openssl rand -base64 32`). Keep a copy of this
+    /// key somewhere other than the server, the backups can't be restored without it.
+    ///
+    /// Default is `None`, backups are not encrypted.
+    #[serde(default, serialize_with = "hide_secret::serialize_option")]
+    pub encryption_key: Option<SecretString>,
+}
+
+/// An additional destination for backups, see [`Options::backup_destinations`].
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "kebab-case")]
+pub enum BackupDestination {
+    /// An amazon s3 compatible storage API, configured the same way as [`Options::backup`].
+    S3(Backup),
+    /// A directory on the server (e.g. a mounted network drive or external disk).
+    Local(LocalBackup),
+}
+
+/// Backups saved to a directory on the server, see [`BackupDestination::Local`].
+#[derive(Debug, Serialize, Deserialize)]
+pub struct LocalBackup {
+    /// Schedule for when the backup is performed.
+    ///
+    /// Default is `0 0 * * *`.
+    #[serde(with = "serde_cron", default = "default_backup_schedule")]
+    pub schedule: CronSchedule,
+    /// Directory where the backups are saved, each backup is saved to a new file named with the
+    /// time of the backup.
+    pub directory: PathBuf,
+    /// Number of backups to keep in the directory, older backups are deleted.
+    ///
+    /// Default is `7`.
+    #[serde(default = "default_local_backup_keep")]
+    pub keep: usize,
+    /// See [`Backup::encryption_key`].
+    #[serde(default, serialize_with = "hide_secret::serialize_option")]
+    pub encryption_key: Option<SecretString>,
+}
+
+fn default_local_backup_keep() -> usize {
+    7
 }
 
 fn default_backup_schedule() -> CronSchedule {
@@ -209,6 +956,34 @@ mod serde_cron {
     }
 }
 
+/// Scheduled database maintenance: an integrity check, followed by `ANALYZE` and `VACUUM` to
+/// update the query planner statistics and reclaim the space fragmented by deleted rows (e.g.
+/// from analytics compaction). The results are shown on the admin page.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Maintenance {
+    /// Schedule for when maintenance is performed (in cron format).
+    ///
+    /// Default is `0 3 * * 0` (once per week on Sunday at 03:00 UTC).
+    #[serde(with = "serde_cron")]
+    pub schedule: CronSchedule,
+    /// Whether to `VACUUM` the database. This rewrites the entire database file, which blocks
+    /// writes while it runs and temporarily needs up to twice the size of the database in disk
+    /// space.
+    ///
+    /// Default is `true`.
+    pub vacuum: bool,
+}
+
+impl Default for Maintenance {
+    fn default() -> Self {
+        Self {
+            schedule: CronSchedule::parse_str("0 3 * * 0").expect("Invalid cron schedule"),
+            vacuum: true,
+        }
+    }
+}
+
 /// `avalanche-report` has a built-in server-side analytics collection mechanism.
 #[derive(Debug, Serialize, Deserialize)]
 #[serde(default)]
@@ -220,8 +995,45 @@ pub struct Analytics {
     pub compaction_schedule: CronSchedule,
     /// Number of analytics event batches that will be submited to the database per hour.
     ///
-    /// Default is 60 (one time per minute).
+    /// Default is 60 (one time per minute). Reloaded without restarting, see
+    /// [`ReloadableOptions`].
     pub event_batch_rate: NonZeroU32,
+    /// Duration (in seconds) of the window within which entries for the same uri are combined
+    /// during compaction.
+    ///
+    /// Default is `86400` (1 day).
+    pub compaction_window_seconds: NonZeroU32,
+    /// Duration (in seconds) before the most recent entry for which entries are kept at full
+    /// resolution during compaction.
+    ///
+    /// Default is `604800` (7 days).
+    pub compaction_keep_seconds: u32,
+    /// Header containing the client IP address, used to estimate the number of unique visitors
+    /// and to throttle failed logins. Set this when running behind a proxy (e.g. `Fly-Client-IP`
+    /// or `X-Forwarded-For`), otherwise the address of the connection is used. The proxy must
+    /// overwrite the header, for `X-Forwarded-For` the last address is used.
+    pub client_ip_header: Option<String>,
+    /// See [`AnalyticsRetention`].
+    pub retention: Option<AnalyticsRetention>,
+}
+
+/// Policy for analytics entries older than [`AnalyticsRetention::days`], enforced after each
+/// compaction.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct AnalyticsRetention {
+    /// Age of entries (in days) to which the policy applies.
+    pub days: u32,
+    /// See [`AnalyticsRetentionAction`].
+    pub action: AnalyticsRetentionAction,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+pub enum AnalyticsRetentionAction {
+    /// Delete the entries (and the estimated unique visitors).
+    Delete,
+    /// Combine the entries for each uri into one entry per 30 days.
+    Aggregate,
 }
 
 impl Default for Analytics {
@@ -230,15 +1042,35 @@ impl Default for Analytics {
             compaction_schedule: CronSchedule::parse_str("0 1 * * *")
                 .expect("Invalid cron schedule"),
             event_batch_rate: default_analytics_batch_rate(),
+            compaction_window_seconds: nonzero!(86400u32),
+            compaction_keep_seconds: 604800,
+            client_ip_header: None,
+            retention: None,
         }
     }
 }
 
+impl Analytics {
+    pub fn compaction_window(&self) -> time::Duration {
+        time::Duration::seconds(self.compaction_window_seconds.get().into())
+    }
+
+    pub fn compaction_keep(&self) -> time::Duration {
+        time::Duration::seconds(self.compaction_keep_seconds.into())
+    }
+}
+
 impl Options {
+    /// Whether the `language` is enabled, see [`Options::enabled_languages`].
+    pub fn language_enabled(&self, language: &unic_langid::LanguageIdentifier) -> bool {
+        is_language_enabled(self.enabled_languages.as_deref(), language)
+    }
+
     pub fn base_url(&self) -> url::Url {
         self.base_url.clone().unwrap_or_else(|| {
             format!(
-                "http://{0}:{1}/",
+                "{0}://{1}:{2}/",
+                if self.tls.is_some() { "https" } else { "http" },
                 self.listen_address.ip(),
                 self.listen_address.port()
             )
@@ -299,10 +1131,81 @@ fn default_listen_address() -> SocketAddr {
     SocketAddr::from(([127, 0, 0, 1], 3000))
 }
 
+fn default_diagram_cache_capacity() -> usize {
+    512
+}
+
+fn default_shutdown_timeout_seconds() -> u64 {
+    10
+}
+
 fn default_analytics_batch_rate() -> NonZeroU32 {
     nonzero!(60u32)
 }
 
+/// Whether the `language` is in `enabled_languages`, or all languages are enabled (`None`).
+pub fn is_language_enabled(
+    enabled_languages: Option<&[unic_langid::LanguageIdentifier]>,
+    language: &unic_langid::LanguageIdentifier,
+) -> bool {
+    enabled_languages.is_none_or(|enabled_languages| enabled_languages.contains(language))
+}
+
+/// Metadata for a language which users can select, see [`Options::languages`].
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Language {
+    pub id: unic_langid::LanguageIdentifier,
+    /// Name of the language in the language itself, e.g. `ქართული`.
+    pub native_name: String,
+    /// A flag emoji (e.g. `🇬🇪`) or the name of an icon, shown next to the name.
+    #[serde(default)]
+    pub icon: Option<String>,
+    /// Default is [`TextDirection::Rtl`] for languages written from right to left (e.g. Arabic,
+    /// Hebrew, Persian), otherwise [`TextDirection::Ltr`].
+    #[serde(default)]
+    pub direction: Option<TextDirection>,
+}
+
+/// Languages written from right to left.
+const RTL_LANGUAGES: [&str; 8] = ["ar", "dv", "fa", "he", "ps", "sd", "ur", "yi"];
+
+impl Language {
+    /// See [`Language::direction`].
+    pub fn direction(&self) -> TextDirection {
+        self.direction.unwrap_or_else(|| {
+            if RTL_LANGUAGES.contains(&self.id.language.as_str()) {
+                TextDirection::Rtl
+            } else {
+                TextDirection::Ltr
+            }
+        })
+    }
+}
+
+/// Direction in which the text of a [`Language`] is written.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum TextDirection {
+    Ltr,
+    Rtl,
+}
+
+fn default_languages() -> Vec<Language> {
+    [
+        ("en-UK", "English", "🇬🇧"),
+        ("ka-GE", "ქართული", "🇬🇪"),
+        ("bg-BG", "български", "🇧🇬"),
+    ]
+    .into_iter()
+    .map(|(id, native_name, icon)| Language {
+        id: id.parse().expect("Unable to parse language identifier"),
+        native_name: native_name.to_owned(),
+        icon: Some(icon.to_owned()),
+        direction: None,
+    })
+    .collect()
+}
+
 fn default_default_language_order() -> Vec<unic_langid::LanguageIdentifier> {
     vec!["en-UK"
         .parse()
@@ -330,6 +1233,349 @@ impl Options {
         })?
         .wrap_err("No configuration specified")
     }
+
+    /// Items of the navigation menu, the configured [`Options::navigation`], or a link to each
+    /// of the enabled pages.
+    pub fn navigation(&self) -> Vec<NavigationItem> {
+        if let Some(navigation) = &self.navigation {
+            return navigation.clone();
+        }
+        [
+            NavigationLink::Forecasts,
+            NavigationLink::Observations,
+            NavigationLink::News,
+            NavigationLink::Education,
+            NavigationLink::Statistics,
+            NavigationLink::Search,
+        ]
+        .into_iter()
+        .filter(|link| self.features.link_enabled(link))
+        .map(NavigationItem::new)
+        .collect()
+    }
+
+    /// Check for semantic problems with the options which deserialization doesn't catch, so that
+    /// they are all reported together at startup (or with `--check-config`), instead of one at a
+    /// time or when the feature is first used. `schemas` are the loaded forecast spreadsheet
+    /// schemas, which declare the forecast areas.
+    pub fn validate(&self, schemas: &[ForecastSpreadsheetSchema]) -> Result<(), ValidationError> {
+        let mut problems = Vec::new();
+
+        if self.default_language_order.is_empty() {
+            problems.push("default_language_order needs at least one language".to_owned());
+        }
+        for language in &self.default_language_order {
+            if !self.language_enabled(language) {
+                problems.push(format!(
+                    "default_language_order contains {language} which is not in enabled_languages"
+                ));
+            }
+        }
+        for (i, language) in self.languages.iter().enumerate() {
+            if self.languages[..i]
+                .iter()
+                .any(|other| other.id == language.id)
+            {
+                problems.push(format!("languages contains {} more than once", language.id));
+            }
+        }
+        if let Some(machine_translation) = &self.machine_translation {
+            for language in machine_translation.languages.iter().flatten() {
+                if !self.language_enabled(language) {
+                    problems.push(format!(
+                        "machine_translation.languages contains {language} which is not in enabled_languages"
+                    ));
+                }
+            }
+        }
+
+        if let Some(tls) = &self.tls {
+            match &tls.certificate {
+                TlsCertificate::Files {
+                    certificate_path,
+                    key_path,
+                } => {
+                    for (name, path) in [
+                        ("tls.certificate_path", certificate_path),
+                        ("tls.key_path", key_path),
+                    ] {
+                        if !path.is_file() {
+                            problems.push(format!("{name} file {path:?} does not exist"));
+                        }
+                    }
+                }
+                TlsCertificate::Acme { domains, .. } => {
+                    if domains.is_empty() {
+                        problems.push("tls.domains needs at least one domain".to_owned());
+                    }
+                }
+            }
+            if tls.redirect_listen_address.is_some() && self.base_url().scheme() != "https" {
+                problems.push(
+                    "base_url needs to be an https url when tls.redirect_listen_address is specified"
+                        .to_owned(),
+                );
+            }
+        }
+
+        if let Some(oidc) = &self.oidc {
+            if oidc.default_role.is_some() && oidc.allowed_domains.is_empty() {
+                problems.push(
+                    "oidc.default_role needs oidc.allowed_domains to be specified, otherwise anyone with an account at the provider would be given the role"
+                        .to_owned(),
+                );
+            }
+        }
+
+        if self.webhooks.weather_station_stale_minutes == 0 {
+            problems.push(
+                "webhooks.weather_station_stale_minutes needs to be greater than 0".to_owned(),
+            );
+        }
+
+        if let Some(alerts) = &self.alerts {
+            if alerts.slack.is_none() && alerts.matrix.is_none() {
+                problems.push("alerts needs slack or matrix to be specified".to_owned());
+            }
+            if alerts.failure_threshold == 0 {
+                problems.push("alerts.failure_threshold needs to be greater than 0".to_owned());
+            }
+        }
+
+        if self.google_drive.refresh_interval_seconds == 0 {
+            problems.push(
+                "google_drive.refresh_interval_seconds needs to be greater than 0".to_owned(),
+            );
+        }
+        match (
+            &self.google_drive.api_key,
+            &self.google_drive.service_account_key_file,
+        ) {
+            (None, None) => problems.push(
+                "Either google_drive.api_key or google_drive.service_account_key_file needs to be specified"
+                    .to_owned(),
+            ),
+            (_, Some(path)) if !path.is_file() => problems.push(format!(
+                "google_drive.service_account_key_file file {path:?} does not exist"
+            )),
+            _ => {}
+        }
+
+        let backups = self
+            .backup
+            .iter()
+            .map(|backup| ("backup".to_owned(), backup.encryption_key.as_ref()))
+            .chain(self.backup_destinations.iter().map(|(name, destination)| {
+                let encryption_key = match destination {
+                    BackupDestination::S3(backup) => backup.encryption_key.as_ref(),
+                    BackupDestination::Local(backup) => backup.encryption_key.as_ref(),
+                };
+                (format!("backup_destinations.{name}"), encryption_key)
+            }));
+        for (name, encryption_key) in backups {
+            if let Some(Err(error)) =
+                encryption_key.map(crate::database::backup::validate_encryption_key)
+            {
+                problems.push(format!("{name}.encryption_key: {error}"));
+            }
+        }
+        for (name, destination) in &self.backup_destinations {
+            if let BackupDestination::Local(backup) = destination {
+                if backup.keep == 0 {
+                    problems.push(format!(
+                        "backup_destinations.{name}.keep needs to be greater than 0"
+                    ));
+                }
+            }
+        }
+
+        let stations = u32::try_from(self.weather_stations.len()).unwrap_or(u32::MAX);
+        if crate::current_weather::CACHE_EACH_STATION_INTERVAL.saturating_mul(stations)
+            > crate::current_weather::CACHE_INTERVAL
+        {
+            problems.push(format!(
+                "Too many weather_stations ({stations}), there is only time to fetch the data for {} stations",
+                crate::current_weather::CACHE_INTERVAL.as_secs()
+                    / crate::current_weather::CACHE_EACH_STATION_INTERVAL.as_secs()
+            ));
+        }
+        for (id, station) in &self.weather_stations {
+            if let Err(error) = crate::current_weather::validate_source(&station.source) {
+                problems.push(format!("weather_stations.{id}.source: {error:#}"));
+            }
+        }
+
+        for (id, webcam) in &self.webcams {
+            if id.is_empty()
+                || !id
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+            {
+                problems.push(format!(
+                    "webcams.{id} needs an id containing only letters, numbers, `-` and `_`"
+                ));
+            }
+            if webcam.refresh_interval_seconds == 0 {
+                problems.push(format!(
+                    "webcams.{id}.refresh_interval_seconds needs to be greater than 0"
+                ));
+            }
+        }
+
+        let area_declared = |area: &str| {
+            schemas
+                .iter()
+                .any(|schema| schema.area_definitions.keys().any(|id| id.as_str() == area))
+        };
+        for area in self.weather_forecasts.keys() {
+            if !area_declared(area) {
+                problems.push(format!(
+                    "weather_forecasts.{area} is not an area declared in a forecast spreadsheet schema"
+                ));
+            }
+        }
+        for area in self.forecast_publication.schedules.keys() {
+            if !schemas
+                .iter()
+                .any(|schema| schema.area.map.contains_key(area))
+            {
+                problems.push(format!(
+                    "forecast_publication.schedules.{area} is not an area name declared in a forecast spreadsheet schema"
+                ));
+            }
+        }
+        for (area, path) in &self.digital_elevation_models {
+            if !area_declared(&area.to_string()) {
+                problems.push(format!(
+                    "digital_elevation_models.{area} is not an area declared in a forecast spreadsheet schema"
+                ));
+            }
+            if !path.is_file() {
+                problems.push(format!(
+                    "digital_elevation_models.{area} file {path:?} does not exist"
+                ));
+            }
+        }
+
+        for (name, directory) in [
+            ("templates.directory", &self.templates.directory),
+            ("static_files.directory", &self.static_files.directory),
+        ] {
+            if let Some(directory) = directory {
+                if !directory.is_dir() {
+                    problems.push(format!("{name} {directory:?} is not a directory"));
+                }
+            }
+        }
+
+        for (i, directory) in self.templates.layers.iter().enumerate() {
+            if !directory.is_dir() {
+                problems.push(format!(
+                    "templates.layers[{i}] {directory:?} is not a directory"
+                ));
+            }
+        }
+
+        for (i, item) in self.navigation.iter().flatten().enumerate() {
+            if !self.features.link_enabled(&item.link) {
+                problems.push(format!(
+                    "navigation[{i}] links to {}, which is disabled in features",
+                    item.link.href()
+                ));
+            }
+            if item.link.message_id().is_none()
+                && item.label.as_ref().is_none_or(|label| label.is_empty())
+            {
+                problems.push(format!("navigation[{i}] needs a label for its url"));
+            }
+        }
+
+        if problems.is_empty() {
+            Ok(())
+        } else {
+            Err(ValidationError { problems })
+        }
+    }
+}
+
+/// Problems with the [`Options`], found by [`Options::validate`].
+#[derive(Debug, thiserror::Error)]
+#[error(
+    "Invalid configuration:\n{}",
+    .problems.iter().map(|problem| format!("  - {problem}")).collect::<Vec<_>>().join("\n")
+)]
+pub struct ValidationError {
+    pub problems: Vec<String>,
+}
+
+/// The subset of the [`Options`] which can be reloaded without restarting the server, on `SIGHUP`
+/// or from the admin page (see [`reload`]). Everything else is only read at startup.
+#[derive(Debug, Clone)]
+pub struct ReloadableOptions {
+    /// See [`Options::weather_maps`].
+    pub weather_maps: WeatherMaps,
+    /// See [`Options::default_language_order`].
+    pub default_language_order: Vec<unic_langid::LanguageIdentifier>,
+    /// See [`Options::webcams`].
+    pub webcams: indexmap::IndexMap<String, Webcam>,
+    /// See [`Options::map`].
+    pub map: Map,
+    /// See [`Analytics::event_batch_rate`].
+    pub analytics_event_batch_rate: NonZeroU32,
+    /// See [`ForecastPublication::expiring_soon_minutes`].
+    pub forecast_expiring_soon: time::Duration,
+}
+
+impl From<&Options> for ReloadableOptions {
+    fn from(options: &Options) -> Self {
+        Self {
+            weather_maps: options.weather_maps.clone(),
+            default_language_order: options.default_language_order.clone(),
+            webcams: options.webcams.clone(),
+            map: options.map.clone(),
+            analytics_event_batch_rate: options.analytics.event_batch_rate,
+            forecast_expiring_soon: options.forecast_publication.expiring_soon(),
+        }
+    }
+}
+
+/// Shared handle to the current [`ReloadableOptions`].
+pub type ReloadableOptionsHandle = Arc<ArcSwap<ReloadableOptions>>;
+
+pub fn reloadable_options_handle(options: &Options) -> ReloadableOptionsHandle {
+    Arc::new(ArcSwap::from_pointee(ReloadableOptions::from(options)))
+}
+
+/// Read the configuration again, and if it is valid (see [`Options::validate`]) replace the
+/// [`ReloadableOptions`] in the `handle`. Changes to other options are ignored until the server
+/// is restarted.
+pub async fn reload(
+    handle: &ReloadableOptionsHandle,
+    schemas: &[ForecastSpreadsheetSchema],
+) -> eyre::Result<()> {
+    let options = Options::initialize().await?;
+    options.validate(schemas)?;
+    handle.store(Arc::new(ReloadableOptions::from(&options)));
+    tracing::info!("Reloaded configuration");
+    Ok(())
+}
+
+/// Reload the options (see [`reload`]) whenever the process receives `SIGHUP`.
+#[cfg(unix)]
+pub fn spawn_reload_on_hangup(
+    handle: ReloadableOptionsHandle,
+    schemas: &'static ForecastSpreadsheetSchemas,
+) -> eyre::Result<()> {
+    let mut hangup = tokio::signal::unix::signal(tokio::signal::unix::SignalKind::hangup())?;
+    tokio::spawn(async move {
+        while hangup.recv().await.is_some() {
+            tracing::info!("Received SIGHUP, reloading configuration");
+            if let Err(error) = reload(&handle, schemas.schemas()).await {
+                tracing::error!("Error reloading configuration: {error:?}");
+            }
+        }
+    });
+    Ok(())
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -337,6 +1583,34 @@ pub enum WeatherStationSource {
     /// See [`AmbientWeatherSource`].
     #[serde(alias = "ambient_weather")]
     AmbientWeather(AmbientWeatherSource),
+    /// See [`DavisWeatherLinkSource`].
+    #[serde(alias = "davis_weatherlink")]
+    DavisWeatherLink(DavisWeatherLinkSource),
+    /// See [`EcowittSource`].
+    #[serde(alias = "ecowitt")]
+    Ecowitt(EcowittSource),
+    /// See [`HttpJsonSource`].
+    #[serde(alias = "http_json")]
+    HttpJson(HttpJsonSource),
+    /// See [`PushSource`].
+    #[serde(alias = "push")]
+    Push(PushSource),
+}
+
+impl WeatherStationSource {
+    /// Whether data for this source is pushed to the server instead of being polled.
+    pub fn is_push(&self) -> bool {
+        matches!(self, Self::Push(_))
+    }
+}
+
+/// Weather source for stations that push their data to the
+/// `/current-weather/ingest/{station_id}` endpoint.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct PushSource {
+    /// Token the station must supply in the `Authorization: Bearer {token}` header.
+    #[serde(serialize_with = "hide_secret::serialize")]
+    pub token: SecretString,
 }
 
 /// Weather source from <https://ambientweather.net>
@@ -349,8 +1623,290 @@ pub struct AmbientWeatherSource {
     pub application_key: SecretString,
 }
 
+/// Weather source from the Davis WeatherLink v2 API <https://weatherlink.github.io/v2-api/>.
+///
+/// Only current conditions are available from this API, so a history is accumulated in the
+/// cache with each request.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct DavisWeatherLinkSource {
+    /// Station id, or station UUID, as listed by the `/stations` endpoint.
+    pub station_id: String,
+    #[serde(serialize_with = "hide_secret::serialize")]
+    pub api_key: SecretString,
+    #[serde(serialize_with = "hide_secret::serialize")]
+    pub api_secret: SecretString,
+}
+
+/// Weather source from the Ecowitt cloud API <https://doc.ecowitt.net/>.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct EcowittSource {
+    pub device_mac_address: String,
+    #[serde(serialize_with = "hide_secret::serialize")]
+    pub api_key: SecretString,
+    #[serde(serialize_with = "hide_secret::serialize")]
+    pub application_key: SecretString,
+}
+
+/// Weather source from any HTTP API that responds with JSON, using a mapping from paths within
+/// the response to the weather data fields.
+///
+/// Paths are a subset of JSONPath: `$` for the root, `.name` for object members, `[0]` for array
+/// elements and `[*]` for all array elements.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct HttpJsonSource {
+    /// URL to request. The placeholders `{start}` and `{end}` are replaced with the unix
+    /// timestamps (in seconds) for the start and end of the last 24 hours.
+    pub url: String,
+    /// Extra headers to send with the request, for example for authentication.
+    #[serde(default)]
+    pub headers: Vec<HttpHeader>,
+    /// Path to the records in the response. Default is `$`, for a response that is a single
+    /// record. Use for example `$.observations[*]` to select each element of an array.
+    #[serde(default = "default_http_json_records")]
+    pub records: String,
+    /// Paths within each record to the weather data fields.
+    pub fields: HttpJsonFields,
+}
+
+fn default_http_json_records() -> String {
+    "$".to_owned()
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct HttpHeader {
+    pub name: String,
+    #[serde(serialize_with = "hide_secret::serialize")]
+    pub value: SecretString,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct HttpJsonFields {
+    pub time: HttpJsonTimeField,
+    #[serde(default)]
+    pub temperature: Option<HttpJsonField<TemperatureUnit>>,
+    /// Path to the relative humidity in percent.
+    #[serde(default)]
+    pub humidity: Option<String>,
+    #[serde(default)]
+    pub wind_speed: Option<HttpJsonField<SpeedUnit>>,
+    /// Path to the wind direction in degrees.
+    #[serde(default)]
+    pub wind_direction: Option<String>,
+    /// Path to the precipitation rate in millimetres per hour.
+    #[serde(default)]
+    pub precipitation_rate: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct HttpJsonTimeField {
+    pub path: String,
+    pub format: TimeFormat,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct HttpJsonField<U> {
+    pub path: String,
+    pub unit: U,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+pub enum TimeFormat {
+    Rfc3339,
+    UnixSeconds,
+    UnixMilliseconds,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+pub enum TemperatureUnit {
+    Celsius,
+    Fahrenheit,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+pub enum SpeedUnit {
+    MetresPerSecond,
+    KilometresPerHour,
+    MilesPerHour,
+    Knots,
+}
+
+/// A location for fetching weather model forecasts from <https://open-meteo.com/>.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct WeatherForecastLocation {
+    pub latitude: f64,
+    pub longitude: f64,
+    /// Elevation of the ridge tops in the area, used to select the model level for ridge-top
+    /// wind.
+    ///
+    /// Default is `3000`.
+    #[serde(default = "default_ridge_top_elevation_metres")]
+    pub ridge_top_elevation_metres: f64,
+}
+
+fn default_ridge_top_elevation_metres() -> f64 {
+    3000.0
+}
+
+/// A webcam, whose latest image is fetched and cached by the server (instead of being linked to
+/// directly), so that it can be displayed without hotlinking or cross-origin issues.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Webcam {
+    /// Name displayed with the image.
+    pub name: String,
+    /// Url of the still image or MJPEG stream, see [`WebcamFormat`].
+    pub url: Url,
+    /// See [`WebcamFormat`].
+    ///
+    /// Default is `still`.
+    #[serde(default)]
+    pub format: WebcamFormat,
+    /// How long the fetched image is used for before fetching a new one.
+    ///
+    /// Default is `300`.
+    #[serde(default = "default_webcam_refresh_interval_seconds")]
+    pub refresh_interval_seconds: u64,
+}
+
+fn default_webcam_refresh_interval_seconds() -> u64 {
+    300
+}
+
+/// The format of the image served at [`Webcam::url`].
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum WebcamFormat {
+    /// A still image (e.g. JPEG or PNG), which is updated periodically by the webcam.
+    #[default]
+    Still,
+    /// A Motion JPEG stream (`multipart/x-mixed-replace`), the first frame is used.
+    Mjpeg,
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct WeatherStation {
     /// Where the weather station data is pulled from.
     pub source: WeatherStationSource,
+    /// Name of the forecast area (as used in the forecast file names) that the weather station
+    /// is located in, used to show it on the area's page at `/areas/{area}`. Weather stations
+    /// without an area are shown for every area. Default is `None`.
+    #[serde(default)]
+    pub area: Option<String>,
+}
+
+#[cfg(test)]
+mod test {
+    use crate::forecasts::{ForecastSpreadsheetSchema, GUDAURI_FORECAST_SCHEMA_JSON};
+
+    use super::Options;
+
+    fn options(toml: &str) -> Options {
+        toml::from_str(&format!(
+            "{toml}\n[google_drive]\npublished_folder_id=\"folder\"\napi_key=\"key\"\n"
+        ))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_validate() {
+        let schemas: Vec<ForecastSpreadsheetSchema> =
+            vec![serde_json::from_str(GUDAURI_FORECAST_SCHEMA_JSON).unwrap()];
+        options("").validate(&schemas).unwrap();
+
+        let error = options(
+            r#"
+            default_language_order=["ka-GE"]
+            enabled_languages=["en-UK"]
+            [[languages]]
+            id="en-UK"
+            native_name="English"
+            [[languages]]
+            id="en-UK"
+            native_name="English (UK)"
+            [backup_destinations.onsite]
+            type="local"
+            directory="backups"
+            keep=0
+            encryption_key="c2hvcnQ="
+            [webcams."a b"]
+            name="Webcam"
+            url="https://example.com/webcam.jpg"
+            [weather_forecasts.unknown-area]
+            latitude=42.0
+            longitude=44.0
+            [tls]
+            certificate="acme"
+            domains=[]
+            [features]
+            news=false
+            [[navigation]]
+            link="news"
+            [[navigation]]
+            link={url="/about"}
+            "#,
+        )
+        .validate(&schemas)
+        .unwrap_err();
+        assert_eq!(error.problems.len(), 9, "{error}");
+    }
+
+    #[test]
+    fn test_validate_oidc_default_role() {
+        let schemas: Vec<ForecastSpreadsheetSchema> =
+            vec![serde_json::from_str(GUDAURI_FORECAST_SCHEMA_JSON).unwrap()];
+        let oidc = r#"
+            [oidc]
+            issuer_url="https://accounts.google.com"
+            client_id="client"
+            client_secret="secret"
+            default_role="observer"
+            "#;
+        let error = options(oidc).validate(&schemas).unwrap_err();
+        assert_eq!(error.problems.len(), 1, "{error}");
+        options(&format!("{oidc}allowed_domains=[\"example.org\"]\n"))
+            .validate(&schemas)
+            .unwrap();
+    }
+
+    #[test]
+    fn test_navigation() {
+        let links = |options: Options| {
+            options
+                .navigation()
+                .into_iter()
+                .map(|item| item.link.href().to_owned())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            links(options("[features]\nobservations=false\nnews=false")),
+            ["/", "/education", "/statistics", "/search"]
+        );
+        assert_eq!(
+            links(options(
+                r#"
+                [[navigation]]
+                link={url="/about"}
+                label={en-UK="About"}
+                [[navigation]]
+                link="forecasts"
+                "#
+            )),
+            ["/about", "/"]
+        );
+    }
+
+    #[test]
+    fn test_admin_allowlist() {
+        let allowlist = options(r#"admin_allowlist.networks=["10.0.0.0/8", "127.0.0.1", "::1"]"#)
+            .admin_allowlist
+            .unwrap();
+        let allows = |address: &str| allowlist.allows(&address.parse().unwrap());
+        assert!(allows("10.1.2.3"));
+        assert!(allows("127.0.0.1"));
+        assert!(allows("::1"));
+        assert!(!allows("127.0.0.2"));
+        assert!(!allows("192.168.1.1"));
+    }
 }
@@ -0,0 +1,217 @@
+//! Metadata describing a page, which is rendered into the page's `<head>` by the
+//! `macros/page_metadata.html` template macro as Open Graph tags (for link previews), and as
+//! schema.org JSON-LD structured data (for search engines).
+
+use axum_extra::routing::TypedPath;
+use forecast_spreadsheet::HazardRatingKind;
+use serde::Serialize;
+use time::{format_description::well_known::Rfc3339, OffsetDateTime};
+
+use crate::{
+    diagrams::elevation_hazard::HazardLevel,
+    forecasts::{card::ForecastCardPath, Forecast, ForecastsFilePath},
+    i18n::{self, I18nLoader},
+};
+
+/// Maximum length (in characters) of [`PageMetadata::description`], longer descriptions are
+/// truncated by link previews anyway.
+const MAX_DESCRIPTION_LENGTH: usize = 300;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct PageMetadata {
+    pub title: String,
+    pub description: Option<String>,
+    /// Absolute URL of the page.
+    pub url: String,
+    /// Absolute URL of an image to display in link previews.
+    pub image: Option<String>,
+    /// schema.org structured data for the page.
+    pub json_ld: Option<serde_json::Value>,
+}
+
+impl PageMetadata {
+    /// Metadata for the page of a forecast published with the file name `file_name`.
+    pub fn forecast(
+        forecast: &Forecast,
+        file_name: &str,
+        i18n: &I18nLoader,
+        options: &crate::Options,
+        reloadable_options: &crate::options::ReloadableOptions,
+    ) -> Self {
+        let base_url = options.base_url();
+        let absolute = |path: String| {
+            base_url
+                .join(&path)
+                .map(String::from)
+                .unwrap_or_else(|_| path)
+        };
+        let url = absolute(
+            ForecastsFilePath {
+                file_name: file_name.to_owned(),
+            }
+            .to_uri()
+            .to_string(),
+        );
+        let image = absolute(
+            ForecastCardPath {
+                file_name: file_name.to_owned(),
+            }
+            .to_uri()
+            .to_string(),
+        );
+
+        let area_id = forecast.area.to_lowercase();
+        let area = i18n::message_or(i18n, &format!("forecast-area-{area_id}"), &forecast.area);
+        let title = format!(
+            "{} - {area} - {}",
+            i18n::format_time(forecast.time, i18n),
+            i18n.get("avalanche-forecast-heading"),
+        );
+
+        let hazard_level = forecast
+            .hazard_ratings
+            .get(&HazardRatingKind::Overall)
+            .and_then(|rating| rating.value)
+            .map(HazardLevel::from)
+            .unwrap_or(HazardLevel::NoRating);
+        let mut description = format!(
+            "{}: {}.",
+            i18n.get("avalanche-hazard-heading"),
+            i18n.get(&format!("avalanche-hazard-{}", hazard_level.id())),
+        );
+        let default_language = reloadable_options
+            .default_language_order
+            .first()
+            .cloned()
+            .unwrap_or_default();
+        if let Some((_, text)) = i18n::negotiate_translated_string(
+            &i18n.current_languages(),
+            &default_language,
+            &forecast.description,
+        ) {
+            let text = text.split_whitespace().collect::<Vec<_>>().join(" ");
+            if !text.is_empty() {
+                description.push(' ');
+                description.push_str(&text);
+            }
+        }
+        let description = truncate(&description, MAX_DESCRIPTION_LENGTH);
+
+        let json_ld = special_announcement(
+            &title,
+            &description,
+            &url,
+            &image,
+            &area,
+            forecast.time,
+            forecast.time + forecast.valid_for,
+        );
+
+        Self {
+            title,
+            description: Some(description),
+            url,
+            image: Some(image),
+            json_ld: Some(json_ld),
+        }
+    }
+
+    /// Metadata for the index page, previewing the `current_forecast` if there is one.
+    pub fn index(
+        i18n: &I18nLoader,
+        options: &crate::Options,
+        current_forecast: Option<&PageMetadata>,
+    ) -> Self {
+        Self {
+            title: i18n.get("index-title"),
+            description: current_forecast.and_then(|metadata| metadata.description.clone()),
+            url: options.base_url().to_string(),
+            image: current_forecast.and_then(|metadata| metadata.image.clone()),
+            json_ld: current_forecast.and_then(|metadata| metadata.json_ld.clone()),
+        }
+    }
+}
+
+/// A schema.org [SpecialAnnouncement](https://schema.org/SpecialAnnouncement) for a forecast,
+/// valid between `date_posted` and `expires`.
+fn special_announcement(
+    name: &str,
+    text: &str,
+    url: &str,
+    image: &str,
+    area: &str,
+    date_posted: OffsetDateTime,
+    expires: OffsetDateTime,
+) -> serde_json::Value {
+    let format = |time: OffsetDateTime| time.format(&Rfc3339).ok();
+    serde_json::json!({
+        "@context": "https://schema.org",
+        "@type": "SpecialAnnouncement",
+        "name": name,
+        "text": text,
+        "url": url,
+        "image": image,
+        "datePosted": format(date_posted),
+        "expires": format(expires),
+        "spatialCoverage": {
+            "@type": "Place",
+            "name": area,
+        },
+    })
+}
+
+/// Truncate `text` to at most `max_length` characters, at a word boundary where possible.
+fn truncate(text: &str, max_length: usize) -> String {
+    if text.chars().count() <= max_length {
+        return text.to_owned();
+    }
+    let truncated: String = text.chars().take(max_length - 1).collect();
+    let truncated = match truncated.rfind(char::is_whitespace) {
+        Some(index) => truncated[..index].trim_end(),
+        None => &truncated,
+    };
+    format!("{truncated}…")
+}
+
+#[cfg(test)]
+mod test {
+    use time::macros::datetime;
+
+    use super::{special_announcement, truncate};
+
+    #[test]
+    fn test_special_announcement() {
+        let json_ld = special_announcement(
+            "Tuesday 24 January 2023 17:00 - Gudauri - Avalanche Forecast",
+            "Avalanche Hazard: Considerable.",
+            "https://avalanche.ge/forecasts/Gudauri_2023-01-24T17:00_LF",
+            "https://avalanche.ge/forecasts/Gudauri_2023-01-24T17:00_LF/card.png",
+            "Gudauri",
+            datetime!(2023-01-24 17:00 +4),
+            datetime!(2023-01-25 17:00 +4),
+        );
+        insta::assert_json_snapshot!(json_ld, @r###"
+        {
+          "@context": "https://schema.org",
+          "@type": "SpecialAnnouncement",
+          "datePosted": "2023-01-24T17:00:00+04:00",
+          "expires": "2023-01-25T17:00:00+04:00",
+          "image": "https://avalanche.ge/forecasts/Gudauri_2023-01-24T17:00_LF/card.png",
+          "name": "Tuesday 24 January 2023 17:00 - Gudauri - Avalanche Forecast",
+          "spatialCoverage": {
+            "@type": "Place",
+            "name": "Gudauri"
+          },
+          "text": "Avalanche Hazard: Considerable.",
+          "url": "https://avalanche.ge/forecasts/Gudauri_2023-01-24T17:00_LF"
+        }
+        "###);
+    }
+
+    #[test]
+    fn test_truncate() {
+        assert_eq!(truncate("short text", 20), "short text");
+        assert_eq!(truncate("some longer text here", 15), "some longer…");
+        assert_eq!(truncate("abcdefghij", 5), "abcd…");
+    }
+}
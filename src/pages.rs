@@ -0,0 +1,153 @@
+//! Pages with content managed in the admin interface (e.g. about, contact or education pages),
+//! served at `/{slug}` for any slug that doesn't match another route. Each page has a title and
+//! markdown content for each language, and is rendered using `page.html` unless another template
+//! is specified (e.g. one added using [`crate::options::Templates::directory`]).
+
+use axum::{http::Uri, response::Response};
+use serde::{Deserialize, Serialize};
+
+use crate::{database::Database, news::Translations, templates::TemplatesWithContext, types};
+
+/// Template used to render pages which don't specify one.
+pub const DEFAULT_TEMPLATE: &str = "page.html";
+
+/// The path that a page is served at, without the leading `/`, e.g. `about`.
+#[derive(sqlx::Type, Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Hash)]
+#[serde(transparent)]
+#[sqlx(transparent)]
+pub struct PageSlug(String);
+
+impl PageSlug {
+    /// Whether the slug only contains lowercase letters, numbers and `-`, so it is a single url
+    /// path segment.
+    pub fn is_valid(&self) -> bool {
+        !self.0.is_empty()
+            && self
+                .0
+                .chars()
+                .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-')
+    }
+}
+
+impl From<String> for PageSlug {
+    fn from(value: String) -> Self {
+        Self(value)
+    }
+}
+
+impl std::fmt::Display for PageSlug {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct Page {
+    pub slug: PageSlug,
+    pub updated_at: types::Time,
+    pub title: sqlx::types::Json<Translations>,
+    /// Markdown.
+    pub content: sqlx::types::Json<Translations>,
+    /// Name of the template used to render the page, instead of [`DEFAULT_TEMPLATE`].
+    pub template: Option<String>,
+}
+
+pub async fn list_pages(database: &Database) -> eyre::Result<Vec<Page>> {
+    Ok(sqlx::query_as!(
+        Page,
+        r#"SELECT
+            slug as "slug: PageSlug",
+            updated_at as "updated_at: types::Time",
+            title as "title: sqlx::types::Json<Translations>",
+            content as "content: sqlx::types::Json<Translations>",
+            template
+        FROM pages ORDER BY slug"#,
+    )
+    .fetch_all(database)
+    .await?)
+}
+
+pub async fn get_page(database: &Database, slug: &PageSlug) -> eyre::Result<Option<Page>> {
+    Ok(sqlx::query_as!(
+        Page,
+        r#"SELECT
+            slug as "slug: PageSlug",
+            updated_at as "updated_at: types::Time",
+            title as "title: sqlx::types::Json<Translations>",
+            content as "content: sqlx::types::Json<Translations>",
+            template
+        FROM pages WHERE slug = $1"#,
+        slug
+    )
+    .fetch_optional(database)
+    .await?)
+}
+
+/// Create the page, or replace an existing page with the same slug.
+pub async fn save_page(
+    database: &Database,
+    slug: &PageSlug,
+    title: &Translations,
+    content: &Translations,
+    template: Option<&str>,
+) -> eyre::Result<()> {
+    let now: types::Time = time::OffsetDateTime::now_utc().into();
+    let title = sqlx::types::Json(title);
+    let content = sqlx::types::Json(content);
+    sqlx::query!(
+        "INSERT INTO pages VALUES($1, $2, $3, $4, $5) ON CONFLICT(slug) DO UPDATE SET updated_at=excluded.updated_at, title=excluded.title, content=excluded.content, template=excluded.template",
+        slug,
+        now,
+        title,
+        content,
+        template,
+    )
+    .execute(database)
+    .await?;
+    Ok(())
+}
+
+pub async fn delete_page(database: &Database, slug: &PageSlug) -> eyre::Result<()> {
+    sqlx::query!("DELETE FROM pages WHERE slug = $1", slug)
+        .execute(database)
+        .await?;
+    Ok(())
+}
+
+#[derive(Serialize)]
+struct PageContext<'a> {
+    page: &'a Page,
+}
+
+/// Render the page at the `uri`, or `None` if there is no page with that slug.
+pub async fn page_response(
+    uri: &Uri,
+    templates: &TemplatesWithContext,
+    database: &Database,
+) -> eyre::Result<Option<Response>> {
+    let slug = PageSlug::from(uri.path().trim_start_matches('/').to_owned());
+    if !slug.is_valid() {
+        return Ok(None);
+    }
+    let Some(page) = get_page(database, &slug).await? else {
+        return Ok(None);
+    };
+    let template = page.template.as_deref().unwrap_or(DEFAULT_TEMPLATE);
+    templates
+        .render(template, &PageContext { page: &page })
+        .map(Some)
+}
+
+#[cfg(test)]
+mod test {
+    use super::PageSlug;
+
+    #[test]
+    fn test_page_slug_is_valid() {
+        assert!(PageSlug::from("about".to_owned()).is_valid());
+        assert!(PageSlug::from("avalanche-education-2".to_owned()).is_valid());
+        assert!(!PageSlug::from(String::new()).is_valid());
+        assert!(!PageSlug::from("about/team".to_owned()).is_valid());
+        assert!(!PageSlug::from("About".to_owned()).is_valid());
+    }
+}
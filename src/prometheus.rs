@@ -0,0 +1,98 @@
+//! Metrics about the running service, exported in the Prometheus text format at `/metrics` when
+//! enabled with [`crate::options::Metrics`]. Metrics are recorded throughout the application
+//! using the [`metrics`] macros, which do nothing when the exporter is not installed.
+
+use std::{net::SocketAddr, time::Instant};
+
+use axum::{
+    extract::{ConnectInfo, MatchedPath, Request, State},
+    middleware::{self, Next},
+    response::{IntoResponse, Response},
+    routing::get,
+    Extension, Router,
+};
+use eyre::Context;
+use metrics::Label;
+use metrics_exporter_prometheus::{Matcher, PrometheusBuilder, PrometheusHandle};
+
+use crate::{auth, options, state::AppState, users::Permission};
+
+/// Buckets (in seconds) for the histograms of durations.
+const DURATION_BUCKETS: &[f64] = &[
+    0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0, 30.0,
+];
+
+/// Install the Prometheus metrics recorder.
+pub fn initialize() -> eyre::Result<PrometheusHandle> {
+    PrometheusBuilder::new()
+        .set_buckets_for_metric(
+            Matcher::Suffix("duration_seconds".to_owned()),
+            DURATION_BUCKETS,
+        )?
+        .install_recorder()
+        .wrap_err("Error installing Prometheus metrics recorder")
+}
+
+pub fn router(handle: PrometheusHandle, config: &'static options::Metrics) -> Router<AppState> {
+    Router::new()
+        .route("/metrics", get(handler))
+        .route_layer(middleware::from_fn(move |request: Request, next: Next| {
+            access_middleware(config, request, next)
+        }))
+        .layer(Extension(handle))
+}
+
+/// Allow access to clients with addresses in [`options::Metrics::allowed_addresses`], other
+/// clients need to log in as a user with the [`Permission::ViewAnalytics`] permission (e.g. using
+/// HTTP basic authentication).
+async fn access_middleware(
+    config: &'static options::Metrics,
+    request: Request,
+    next: Next,
+) -> Response {
+    let allowed = request
+        .extensions()
+        .get::<ConnectInfo<SocketAddr>>()
+        .is_some_and(|ConnectInfo(address)| config.allowed_addresses.contains(&address.ip()));
+    if allowed {
+        next.run(request).await
+    } else {
+        auth::require_permission(Permission::ViewAnalytics, request, next).await
+    }
+}
+
+async fn handler(
+    Extension(handle): Extension<PrometheusHandle>,
+    State(state): State<AppState>,
+) -> impl IntoResponse {
+    metrics::gauge!("analytics_queue_depth")
+        .set((state.analytics_sx.max_capacity() - state.analytics_sx.capacity()) as f64);
+    let freshness = state.published_files.freshness();
+    if let Some(age) = freshness.age() {
+        metrics::gauge!("published_files_age_seconds").set(age.as_seconds_f64());
+    }
+    metrics::gauge!("published_files_stale").set(if freshness.stale { 1.0 } else { 0.0 });
+    handle.render()
+}
+
+/// Middleware which records the count and duration of requests for each route.
+pub async fn middleware(request: Request, next: Next) -> Response {
+    let route = request
+        .extensions()
+        .get::<MatchedPath>()
+        .map(|path| path.as_str().to_owned())
+        // Avoid a label for every unmatched uri.
+        .unwrap_or_else(|| "unmatched".to_owned());
+    let method = request.method().to_string();
+    let start = Instant::now();
+    let response = next.run(request).await;
+    let labels = vec![
+        Label::new("method", method),
+        Label::new("route", route),
+        Label::new("status", response.status().as_u16().to_string()),
+    ];
+    metrics::counter!("http_requests_total", labels.clone()).increment(1);
+    metrics::histogram!("http_request_duration_seconds", labels)
+        .record(start.elapsed().as_secs_f64());
+    response
+}
@@ -0,0 +1,438 @@
+//! Upload a route as a GPX or KML file to see which elevation bands and aspects of a forecast
+//! area it crosses, using the area's digital elevation model (see
+//! [`crate::forecast_areas::terrain`]), cross-referenced with the avalanche problems of the
+//! area's current forecast.
+
+use axum::{
+    extract::{DefaultBodyLimit, State},
+    response::{IntoResponse, Response},
+    routing::{get, post},
+    Extension, Json, Router,
+};
+use axum_extra::routing::TypedPath;
+use eyre::{Context, ContextCompat};
+use forecast_spreadsheet::{
+    Aspect, AspectElevation, ElevationBandId, HazardRatingValue, ProblemKind,
+};
+use geo::{RouteExposure, Track};
+use http::StatusCode;
+use indexmap::IndexMap;
+use serde::Serialize;
+
+use crate::{
+    database::Database,
+    error::map_eyre_error,
+    forecast_areas::{
+        danger_map::current_forecasts,
+        terrain::{band_ratings, forecast_bands, geo_aspect, get_area_dem},
+        ForecastAreaId,
+    },
+    forecasts::ForecastsFilePath,
+    state::AppState,
+    templates::TemplatesWithContext,
+};
+
+/// Maximum size of an uploaded route file in bytes.
+const MAX_ROUTE_BYTES: usize = 5 * 1024 * 1024;
+/// Maximum number of positions in an uploaded route.
+const MAX_ROUTE_POSITIONS: usize = 50_000;
+/// Maximum length of an uploaded route in metres, which limits the number of samples taken along
+/// it by [`geo::Dem::route_exposure`].
+const MAX_ROUTE_METRES: f64 = 200_000.0;
+/// Aspects in the order of [`geo::Aspect::ALL`].
+const ASPECTS: [Aspect; 8] = [
+    Aspect::N,
+    Aspect::NE,
+    Aspect::E,
+    Aspect::SE,
+    Aspect::S,
+    Aspect::SW,
+    Aspect::W,
+    Aspect::NW,
+];
+
+pub fn router() -> Router<AppState> {
+    Router::new()
+        .route(
+            "/",
+            get(form_handler)
+                .post(report_handler)
+                .layer(DefaultBodyLimit::max(MAX_ROUTE_BYTES)),
+        )
+        .route(
+            "/report.json",
+            post(json_handler).layer(DefaultBodyLimit::max(MAX_ROUTE_BYTES)),
+        )
+}
+
+/// The distance of the route on an aspect within an elevation band.
+#[derive(Serialize)]
+pub struct AspectExposure {
+    aspect: Aspect,
+    distance_metres: f64,
+    /// Distance on slopes steep enough to avalanche, see [`geo::STEEP_SLOPE_DEGREES`].
+    steep_distance_metres: f64,
+}
+
+/// The distance of the route within an elevation band.
+#[derive(Serialize)]
+pub struct BandExposure {
+    /// Lower elevation of the band in metres, `None` for the lowest band.
+    lower_metres: Option<f64>,
+    /// Upper elevation of the band in metres, `None` for the highest band.
+    upper_metres: Option<f64>,
+    /// The forecast's elevation band which contains this band.
+    elevation_band: Option<ElevationBandId>,
+    hazard_rating: Option<HazardRatingValue>,
+    distance_metres: f64,
+    flat_distance_metres: f64,
+    aspects: Vec<AspectExposure>,
+}
+
+/// The distance of the route within the elevation bands and aspects of an avalanche problem.
+#[derive(Serialize)]
+pub struct ProblemExposure {
+    kind: ProblemKind,
+    distance_metres: f64,
+    steep_distance_metres: f64,
+    /// Percentage of the route's total distance.
+    percent: u8,
+}
+
+/// Report of the terrain crossed by a route in a forecast area.
+#[derive(Serialize)]
+pub struct RouteExposureReport {
+    area: ForecastAreaId,
+    /// URL of the area's current forecast, `None` when there is no current forecast.
+    forecast_url: Option<String>,
+    total_distance_metres: f64,
+    /// Distance of the route outside the area's digital elevation model.
+    outside_distance_metres: f64,
+    max_slope_degrees: f64,
+    /// Highest elevation band first.
+    elevation_bands: Vec<BandExposure>,
+    avalanche_problems: Vec<ProblemExposure>,
+}
+
+/// The `(distance, steep distance)` of the route within the elevation bands and aspects of an
+/// avalanche problem. `band_ids` is the forecast elevation band for each band of the exposure,
+/// see [`forecast_bands`].
+fn problem_exposure(
+    exposure: &RouteExposure,
+    band_ids: &[Option<&ElevationBandId>],
+    aspect_elevation: &IndexMap<ElevationBandId, AspectElevation>,
+) -> (f64, f64) {
+    band_ids
+        .iter()
+        .enumerate()
+        .filter_map(|(band, id)| Some((band, aspect_elevation.get((*id)?)?)))
+        .flat_map(|(band, aspect_elevation)| {
+            aspect_elevation.aspects.iter().map(move |aspect| {
+                let aspect = geo_aspect(*aspect) as usize;
+                (
+                    exposure.aspect_distance[band][aspect],
+                    exposure.steep_distance[band][aspect],
+                )
+            })
+        })
+        .fold(
+            (0.0, 0.0),
+            |(distance, steep), (band_distance, band_steep)| {
+                (distance + band_distance, steep + band_steep)
+            },
+        )
+}
+
+/// The forecast area with a digital elevation model that contains the most of the route's
+/// positions.
+async fn find_route_area(
+    state: &AppState,
+    database: &Database,
+    tracks: &[Track],
+) -> eyre::Result<Option<ForecastAreaId>> {
+    let mut best: Option<(usize, ForecastAreaId)> = None;
+    for id in state.options.digital_elevation_models.keys() {
+        let Some(area_dem) = get_area_dem(state.options, database, id).await? else {
+            continue;
+        };
+        let count = tracks
+            .iter()
+            .flatten()
+            .filter(|(longitude, latitude)| {
+                area_dem
+                    .dem
+                    .cell(*longitude, *latitude)
+                    .is_some_and(|cell| area_dem.mask[cell])
+            })
+            .count();
+        if count > 0
+            && best
+                .as_ref()
+                .is_none_or(|(best_count, _)| count > *best_count)
+        {
+            best = Some((count, id.clone()));
+        }
+    }
+    Ok(best.map(|(_, id)| id))
+}
+
+/// Create the report for the route, or `None` if the route isn't within a forecast area with a
+/// digital elevation model.
+async fn route_exposure_report(
+    state: &AppState,
+    database: &Database,
+    tracks: Vec<Track>,
+) -> eyre::Result<Option<RouteExposureReport>> {
+    let Some(area) = find_route_area(state, database, &tracks).await? else {
+        return Ok(None);
+    };
+    let area_dem = get_area_dem(state.options, database, &area)
+        .await?
+        .wrap_err("Forecast area DEM is missing")?;
+    let current_forecast = current_forecasts(state, database)
+        .await?
+        .remove(&area.to_string().to_lowercase());
+
+    let empty_bands = IndexMap::new();
+    let forecast = current_forecast.as_ref().map(|current| &current.forecast);
+    let elevation_bands = forecast.map_or(&empty_bands, |forecast| &forecast.elevation_bands);
+    let (boundaries, band_ids) = forecast_bands(elevation_bands);
+    let ratings = match forecast {
+        Some(forecast) => band_ratings(elevation_bands, &forecast.hazard_ratings).1,
+        None => vec![None; band_ids.len()],
+    };
+
+    let exposure = {
+        let boundaries = boundaries.clone();
+        tokio::task::spawn_blocking(move || area_dem.dem.route_exposure(&tracks, &boundaries))
+            .await
+            .wrap_err("Error joining task")?
+    };
+    let total_distance = exposure.total_distance();
+
+    let elevation_bands = band_ids
+        .iter()
+        .zip(ratings)
+        .enumerate()
+        .rev()
+        .map(|(band, (id, hazard_rating))| BandExposure {
+            lower_metres: band.checked_sub(1).map(|i| boundaries[i]),
+            upper_metres: boundaries.get(band).copied(),
+            elevation_band: id.cloned(),
+            hazard_rating,
+            distance_metres: exposure.aspect_distance[band].iter().sum::<f64>()
+                + exposure.flat_distance[band],
+            flat_distance_metres: exposure.flat_distance[band],
+            aspects: ASPECTS
+                .iter()
+                .map(|aspect| AspectExposure {
+                    aspect: *aspect,
+                    distance_metres: exposure.aspect_distance[band][geo_aspect(*aspect) as usize],
+                    steep_distance_metres: exposure.steep_distance[band]
+                        [geo_aspect(*aspect) as usize],
+                })
+                .collect(),
+        })
+        .collect();
+
+    let avalanche_problems = forecast
+        .map(|forecast| forecast.avalanche_problems.as_slice())
+        .unwrap_or_default()
+        .iter()
+        .map(|problem| {
+            let (distance, steep_distance) =
+                problem_exposure(&exposure, &band_ids, &problem.aspect_elevation);
+            ProblemExposure {
+                kind: problem.kind,
+                distance_metres: distance,
+                steep_distance_metres: steep_distance,
+                percent: if total_distance > 0.0 {
+                    (distance * 100.0 / total_distance).round() as u8
+                } else {
+                    0
+                },
+            }
+        })
+        .collect();
+
+    Ok(Some(RouteExposureReport {
+        area,
+        forecast_url: current_forecast.map(|current| {
+            ForecastsFilePath {
+                file_name: current.file_name,
+            }
+            .to_uri()
+            .to_string()
+        }),
+        total_distance_metres: total_distance,
+        outside_distance_metres: exposure.outside_distance,
+        max_slope_degrees: exposure.max_slope_degrees,
+        elevation_bands,
+        avalanche_problems,
+    }))
+}
+
+/// Check that the route is small enough to be processed, see [`MAX_ROUTE_POSITIONS`] and
+/// [`MAX_ROUTE_METRES`].
+fn validate_tracks(tracks: &[Track]) -> eyre::Result<()> {
+    let positions: usize = tracks.iter().map(Vec::len).sum();
+    if positions > MAX_ROUTE_POSITIONS {
+        eyre::bail!("Route exceeds the maximum of {MAX_ROUTE_POSITIONS} positions");
+    }
+    let length: f64 = tracks
+        .iter()
+        .flat_map(|track| track.iter().zip(track.iter().skip(1)))
+        .map(|(from, to)| geo::distance_metres(*from, *to))
+        .sum();
+    // NaN when the positions are invalid.
+    if length.is_nan() || length > MAX_ROUTE_METRES {
+        eyre::bail!(
+            "Route exceeds the maximum length of {} km",
+            MAX_ROUTE_METRES / 1000.0
+        );
+    }
+    Ok(())
+}
+
+/// Read the uploaded route file from the `route` field of the form.
+async fn read_route(mut multipart: axum::extract::Multipart) -> eyre::Result<Vec<Track>> {
+    while let Some(field) = multipart.next_field().await? {
+        if field.name() != Some("route") {
+            continue;
+        }
+        let data = field.bytes().await?;
+        let xml = std::str::from_utf8(&data).wrap_err("Route file is not valid UTF-8")?;
+        let tracks = geo::parse_tracks(xml).wrap_err("Invalid route file")?;
+        validate_tracks(&tracks)?;
+        return Ok(tracks);
+    }
+    eyre::bail!("route field was not specified")
+}
+
+fn bad_request(error: eyre::Error) -> axum::response::ErrorResponse {
+    tracing::warn!("Invalid route exposure request: {error:?}");
+    let mut response = map_eyre_error(error);
+    *response.status_mut() = StatusCode::BAD_REQUEST;
+    response.into()
+}
+
+/// Read the route and create its report, see [`route_exposure_report`].
+async fn report(
+    state: &AppState,
+    database: &Database,
+    multipart: axum::extract::Multipart,
+) -> axum::response::Result<(RouteExposureReport, Vec<Track>)> {
+    let tracks = read_route(multipart).await.map_err(bad_request)?;
+    let report = route_exposure_report(state, database, tracks.clone())
+        .await
+        .map_err(map_eyre_error)?
+        .ok_or_else(|| {
+            bad_request(eyre::eyre!(
+                "Route is not within a forecast area with terrain data"
+            ))
+        })?;
+    Ok((report, tracks))
+}
+
+async fn form_handler(
+    Extension(templates): Extension<TemplatesWithContext>,
+) -> axum::response::Result<Response> {
+    Ok(templates
+        .render("route_exposure/upload.html", &())
+        .map_err(map_eyre_error)?)
+}
+
+#[derive(Serialize)]
+struct ReportContext {
+    report: RouteExposureReport,
+    /// The route as `[latitude, longitude]` lines, as expected by Leaflet.
+    route: Vec<Vec<[f64; 2]>>,
+}
+
+async fn report_handler(
+    State(state): State<AppState>,
+    Extension(templates): Extension<TemplatesWithContext>,
+    Extension(database): Extension<Database>,
+    multipart: axum::extract::Multipart,
+) -> axum::response::Result<Response> {
+    let (report, tracks) = report(&state, &database, multipart).await?;
+    let route = tracks
+        .into_iter()
+        .map(|track| {
+            track
+                .into_iter()
+                .map(|(longitude, latitude)| [latitude, longitude])
+                .collect()
+        })
+        .collect();
+    Ok(templates
+        .render(
+            "route_exposure/report.html",
+            &ReportContext { report, route },
+        )
+        .map_err(map_eyre_error)?)
+}
+
+async fn json_handler(
+    State(state): State<AppState>,
+    Extension(database): Extension<Database>,
+    multipart: axum::extract::Multipart,
+) -> axum::response::Result<Response> {
+    let (report, _) = report(&state, &database, multipart).await?;
+    Ok(Json(report).into_response())
+}
+
+#[cfg(test)]
+mod test {
+    use forecast_spreadsheet::{Aspect, AspectElevation, ElevationBandId};
+    use geo::RouteExposure;
+    use indexmap::IndexMap;
+
+    use super::{problem_exposure, validate_tracks};
+
+    #[test]
+    fn test_problem_exposure() {
+        let mut aspect_distance = vec![[10.0; 8], [20.0; 8]];
+        aspect_distance[1][geo::Aspect::N as usize] = 100.0;
+        let mut steep_distance = vec![[0.0; 8], [0.0; 8]];
+        steep_distance[1][geo::Aspect::N as usize] = 40.0;
+        let exposure = RouteExposure {
+            boundaries: vec![2000.0],
+            aspect_distance,
+            steep_distance,
+            flat_distance: vec![5.0, 0.0],
+            outside_distance: 0.0,
+            max_slope_degrees: 35.0,
+        };
+        let low: ElevationBandId = "low".into();
+        let high: ElevationBandId = "high".into();
+        let aspect_elevation: IndexMap<ElevationBandId, AspectElevation> = [(
+            high.clone(),
+            AspectElevation {
+                aspects: [Aspect::N, Aspect::NE].into_iter().collect(),
+            },
+        )]
+        .into_iter()
+        .collect();
+        assert_eq!(
+            problem_exposure(&exposure, &[Some(&low), Some(&high)], &aspect_elevation),
+            (120.0, 40.0)
+        );
+        assert_eq!(
+            problem_exposure(&exposure, &[Some(&low), None], &aspect_elevation),
+            (0.0, 0.0)
+        );
+    }
+
+    #[test]
+    fn test_validate_tracks() {
+        validate_tracks(&[vec![(44.5, 42.4), (44.51, 42.41)]]).unwrap();
+        // About 111 km for each degree of latitude.
+        assert!(validate_tracks(&[
+            vec![(44.5, 40.0), (44.5, 41.0)],
+            vec![(44.5, 42.0), (44.5, 43.5)],
+        ])
+        .is_err());
+        assert!(validate_tracks(&[vec![(44.5, f64::NAN), (44.5, 42.0)]]).is_err());
+    }
+}
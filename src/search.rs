@@ -0,0 +1,305 @@
+//! Full text search across the forecasts, approved observations and published news posts, using
+//! the SQLite FTS5 `search_index` table which is kept up to date by triggers on the tables being
+//! searched. Each language of a document is indexed separately, and results are shown in the
+//! current language where possible.
+
+use std::collections::HashMap;
+
+use axum::{
+    extract::{Query, State},
+    response::Response,
+    Extension,
+};
+use axum_extra::routing::TypedPath;
+use i18n_embed::LanguageLoader;
+use indexmap::IndexMap;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    database::Database,
+    error::map_eyre_error,
+    forecasts::{parse_forecast_name, ForecastsFilePath},
+    i18n::{self, I18nLoader},
+    state::AppState,
+    templates::TemplatesWithContext,
+};
+
+/// Maximum number of results shown for a search.
+const MAX_RESULTS: i64 = 50;
+/// Marks the start of a matching term in a snippet.
+const HIGHLIGHT_START: &str = "\u{2}";
+/// Marks the end of a matching term in a snippet.
+const HIGHLIGHT_END: &str = "\u{3}";
+
+#[derive(sqlx::Type, Serialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[serde(rename_all = "kebab-case")]
+#[sqlx(rename_all = "kebab-case")]
+pub enum SearchResultKind {
+    Forecast,
+    Observation,
+    News,
+}
+
+#[derive(Debug)]
+struct SearchRow {
+    kind: SearchResultKind,
+    /// Google Drive id of forecasts, or the id of observations and news posts.
+    id: String,
+    /// Language of the row, empty for observations, which are written in a single unknown
+    /// language.
+    language: String,
+    title: String,
+    snippet: String,
+}
+
+/// Convert the text entered by the user into an FTS5 query matching documents containing all of
+/// the words, treating each word as a string so that FTS5 syntax in the text is ignored. The last
+/// word is matched as a prefix, so results are found while still typing a word. Returns `None`
+/// if there are no words to search for.
+fn fts_query(text: &str) -> Option<String> {
+    let words: Vec<String> = text
+        .split_whitespace()
+        .map(|word| format!("\"{}\"", word.replace('"', "\"\"")))
+        .collect();
+    if words.is_empty() {
+        return None;
+    }
+    Some(format!("{}*", words.join(" ")))
+}
+
+/// Search the index, returning rows ranked by relevance, with matching titles weighted more
+/// highly than matching content.
+async fn search(database: &Database, query: &str) -> eyre::Result<Vec<SearchRow>> {
+    Ok(sqlx::query_as!(
+        SearchRow,
+        r#"SELECT
+            search_index.kind as "kind!: SearchResultKind",
+            search_index.id as "id!: String",
+            search_index.language as "language!: String",
+            search_index.title as "title!: String",
+            snippet(search_index, 4, $2, $3, '…', 24) as "snippet!: String"
+        FROM search_index
+        LEFT JOIN news_posts ON search_index.kind = 'news' AND news_posts.id = search_index.id
+        LEFT JOIN observations ON search_index.kind = 'observation' AND observations.id = search_index.id
+        WHERE search_index MATCH $1
+            AND (search_index.kind = 'forecast' OR news_posts.published_at IS NOT NULL OR observations.status = 'approved')
+        ORDER BY bm25(search_index, 0.0, 0.0, 0.0, 2.0, 1.0)
+        LIMIT $4"#,
+        query,
+        HIGHLIGHT_START,
+        HIGHLIGHT_END,
+        MAX_RESULTS,
+    )
+    .fetch_all(database)
+    .await?)
+}
+
+/// Keep the best ranked row for each document, preferring the row in the `language` if the
+/// document matched in multiple languages.
+fn select_languages(rows: Vec<SearchRow>, language: &str) -> Vec<SearchRow> {
+    let mut documents: IndexMap<(SearchResultKind, String), SearchRow> = IndexMap::new();
+    for row in rows {
+        match documents.entry((row.kind, row.id.clone())) {
+            indexmap::map::Entry::Occupied(mut entry) => {
+                if entry.get().language != language && row.language == language {
+                    entry.insert(row);
+                }
+            }
+            indexmap::map::Entry::Vacant(entry) => {
+                entry.insert(row);
+            }
+        }
+    }
+    documents.into_values().collect()
+}
+
+#[derive(Serialize, Debug, PartialEq)]
+struct SnippetSegment {
+    text: String,
+    /// The text matched the search.
+    highlighted: bool,
+}
+
+/// Split a snippet returned by [`search`] into the segments between the highlight markers.
+fn snippet_segments(snippet: &str) -> Vec<SnippetSegment> {
+    let mut segments = Vec::new();
+    for (i, part) in snippet.split(HIGHLIGHT_START).enumerate() {
+        let (highlighted, rest) = match part.split_once(HIGHLIGHT_END) {
+            Some((highlighted, rest)) if i > 0 => (highlighted, rest),
+            _ => ("", part),
+        };
+        if !highlighted.is_empty() {
+            segments.push(SnippetSegment {
+                text: highlighted.to_owned(),
+                highlighted: true,
+            });
+        }
+        if !rest.is_empty() {
+            segments.push(SnippetSegment {
+                text: rest.to_owned(),
+                highlighted: false,
+            });
+        }
+    }
+    segments
+}
+
+#[derive(Serialize)]
+struct SearchResultContext {
+    kind: SearchResultKind,
+    url: String,
+    title: String,
+    /// Language of the result, if known.
+    language: Option<String>,
+    snippet: Vec<SnippetSegment>,
+}
+
+#[derive(Serialize)]
+struct SearchContext {
+    query: String,
+    results: Vec<SearchResultContext>,
+}
+
+#[derive(Deserialize)]
+pub struct SearchQuery {
+    #[serde(default)]
+    q: String,
+}
+
+pub async fn handler(
+    Query(SearchQuery { q }): Query<SearchQuery>,
+    State(state): State<AppState>,
+    Extension(templates): Extension<TemplatesWithContext>,
+    Extension(database): Extension<Database>,
+    Extension(i18n): Extension<I18nLoader>,
+) -> axum::response::Result<Response> {
+    let rows = match fts_query(&q) {
+        Some(query) => search(&database, &query).await.map_err(map_eyre_error)?,
+        None => Vec::new(),
+    };
+    let language = i18n.current_language().to_string();
+    let rows = select_languages(rows, &language);
+
+    // Forecasts are linked to using the name of their file.
+    let forecast_file_names: HashMap<String, String> = if rows
+        .iter()
+        .any(|row| row.kind == SearchResultKind::Forecast)
+    {
+        state
+            .published_files
+            .list_files()
+            .await
+            .map_err(map_eyre_error)?
+            .into_iter()
+            .map(|file| (file.id, file.name))
+            .collect()
+    } else {
+        HashMap::new()
+    };
+
+    let features = &state.options.features;
+    let results = rows
+        .into_iter()
+        .filter_map(|row| {
+            let (url, title) = match row.kind {
+                // Disabled features aren't served.
+                SearchResultKind::Observation if !features.observations => return None,
+                SearchResultKind::News if !features.news => return None,
+                SearchResultKind::Forecast => {
+                    // Forecasts which are no longer published are not shown.
+                    let file_name = forecast_file_names.get(&row.id)?;
+                    let details =
+                        parse_forecast_name(file_name, state.forecast_spreadsheet_schema).ok()?;
+                    let area = i18n::message_or(
+                        &i18n,
+                        &format!("forecast-area-{}", details.forecast.area.to_lowercase()),
+                        &details.forecast.area,
+                    );
+                    let url = ForecastsFilePath {
+                        file_name: file_name.clone(),
+                    }
+                    .to_uri()
+                    .to_string();
+                    let time = i18n::format_time(details.forecast.time, &i18n);
+                    (url, format!("{area} {time}"))
+                }
+                SearchResultKind::Observation => (
+                    format!("/observations/{}", row.id),
+                    i18n.get("search-observation-title"),
+                ),
+                SearchResultKind::News => (format!("/news/{}", row.id), row.title),
+            };
+            Some(SearchResultContext {
+                kind: row.kind,
+                url,
+                title,
+                language: (!row.language.is_empty()).then_some(row.language),
+                snippet: snippet_segments(&row.snippet),
+            })
+        })
+        .collect();
+
+    let context = SearchContext { query: q, results };
+    Ok(templates
+        .render("search.html", &context)
+        .map_err(map_eyre_error)?)
+}
+
+#[cfg(test)]
+mod test {
+    use super::{
+        fts_query, select_languages, snippet_segments, SearchResultKind, SearchRow, SnippetSegment,
+    };
+
+    #[test]
+    fn test_fts_query() {
+        assert_eq!(fts_query("  "), None);
+        assert_eq!(
+            fts_query("wind slab").as_deref(),
+            Some("\"wind\" \"slab\"*")
+        );
+        assert_eq!(
+            fts_query("\"slab OR").as_deref(),
+            Some("\"\"\"slab\" \"OR\"*")
+        );
+    }
+
+    #[test]
+    fn test_select_languages() {
+        let row = |id: &str, language: &str| SearchRow {
+            kind: SearchResultKind::News,
+            id: id.to_owned(),
+            language: language.to_owned(),
+            title: String::new(),
+            snippet: String::new(),
+        };
+        let rows = select_languages(
+            vec![row("a", "ka"), row("b", "en-UK"), row("a", "en-UK")],
+            "en-UK",
+        );
+        let rows: Vec<_> = rows
+            .iter()
+            .map(|row| (row.id.as_str(), row.language.as_str()))
+            .collect();
+        assert_eq!(rows, [("a", "en-UK"), ("b", "en-UK")]);
+    }
+
+    #[test]
+    fn test_snippet_segments() {
+        let segment = |text: &str, highlighted: bool| SnippetSegment {
+            text: text.to_owned(),
+            highlighted,
+        };
+        assert_eq!(
+            snippet_segments("Cracking in \u{2}wind\u{3} \u{2}slab\u{3}…"),
+            [
+                segment("Cracking in ", false),
+                segment("wind", true),
+                segment(" ", false),
+                segment("slab", true),
+                segment("…", false),
+            ]
+        );
+        assert!(snippet_segments("").is_empty());
+    }
+}
@@ -34,6 +34,19 @@ pub mod hide_secret {
     where
         S: serde::Serializer,
     {
-        serializer.serialize_str(&"<SECRET>")
+        serializer.serialize_str("<SECRET>")
+    }
+
+    pub fn serialize_option<S>(
+        value: &Option<SecretString>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        match value {
+            Some(value) => serialize(value, serializer),
+            None => serializer.serialize_none(),
+        }
     }
 }
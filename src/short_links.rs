@@ -0,0 +1,180 @@
+//! Short links for the published forecast files (e.g. `/f/ab12cd`), for sharing in messages and
+//! posts where the full file name is unwieldy. Each file is given a token the first time it is
+//! seen by [`crate::forecasts::published::PublishedFilesService`], which is stored so that the
+//! link continues to work for as long as the file is published. `/f/{token}/qr.png` is a QR code
+//! for the link, for printing on posters (e.g. at trailheads).
+
+use axum::{
+    extract::{Path, State},
+    http::header,
+    response::{IntoResponse, Redirect, Response},
+    routing::get,
+    Extension, Router,
+};
+use axum_extra::routing::TypedPath;
+use eyre::Context;
+use http::StatusCode;
+use qrcode::{render::svg, QrCode};
+use url::Url;
+
+use crate::{
+    database::Database,
+    diagrams,
+    error::{map_eyre_error, map_std_error},
+    forecasts::ForecastsFilePath,
+    state::AppState,
+    types,
+};
+
+/// Characters used in the tokens, excluding those which are easily confused with each other
+/// (`0`/`o`, `1`/`l`/`i`) when a link is typed in from a printed QR code poster.
+const TOKEN_ALPHABET: &[u8] = b"23456789abcdefghjkmnpqrstuvwxyz";
+/// Number of characters in a token.
+const TOKEN_LEN: usize = 6;
+/// Number of attempts at generating a token which isn't already in use.
+const MAX_GENERATE_ATTEMPTS: usize = 10;
+/// Minimum width and height of the QR code image in pixels.
+const QR_CODE_SIZE: u32 = 512;
+
+pub fn router() -> Router<AppState> {
+    Router::new()
+        .route("/{token}", get(handler))
+        .route("/{token}/qr.png", get(qr_code_handler))
+}
+
+/// Generate a new random token.
+fn generate_token() -> String {
+    uuid::Uuid::new_v4()
+        .as_bytes()
+        .iter()
+        .take(TOKEN_LEN)
+        .map(|byte| char::from(TOKEN_ALPHABET[usize::from(*byte) % TOKEN_ALPHABET.len()]))
+        .collect()
+}
+
+/// The url of the short link with `token`.
+pub fn short_url(base_url: &Url, token: &str) -> Option<Url> {
+    let mut url = base_url.join("f/").ok()?;
+    url.path_segments_mut().ok()?.pop_if_empty().push(token);
+    Some(url)
+}
+
+/// Get the token for the file with `file_name`, if it has one.
+pub async fn get_token(database: &Database, file_name: &str) -> eyre::Result<Option<String>> {
+    Ok(sqlx::query_scalar!(
+        "SELECT token FROM short_links WHERE file_name=$1",
+        file_name
+    )
+    .fetch_optional(database)
+    .await?)
+}
+
+/// Get the token for the file with `file_name`, creating one if it doesn't already have one.
+pub async fn get_or_create_token(database: &Database, file_name: &str) -> eyre::Result<String> {
+    for _ in 0..MAX_GENERATE_ATTEMPTS {
+        if let Some(token) = get_token(database, file_name).await? {
+            return Ok(token);
+        }
+        let token = generate_token();
+        let now = types::Time::now_utc();
+        // Conflicts on `file_name` if a token was created for the file concurrently, or on
+        // `token` if it has already been used for another file, in which case try again.
+        sqlx::query!(
+            "INSERT INTO short_links VALUES($1, $2, $3) ON CONFLICT DO NOTHING",
+            token,
+            file_name,
+            now,
+        )
+        .execute(database)
+        .await?;
+    }
+    get_token(database, file_name).await?.ok_or_else(|| {
+        eyre::eyre!(
+            "Unable to generate a unique short link token for {file_name:?} after \
+            {MAX_GENERATE_ATTEMPTS} attempts"
+        )
+    })
+}
+
+/// Get the name of the file that the `token` links to.
+async fn get_file_name(database: &Database, token: &str) -> eyre::Result<Option<String>> {
+    Ok(
+        sqlx::query_scalar!("SELECT file_name FROM short_links WHERE token=$1", token)
+            .fetch_optional(database)
+            .await?,
+    )
+}
+
+/// Render a QR code containing the `data` as a PNG image.
+fn qr_code_png(data: &str) -> eyre::Result<Vec<u8>> {
+    let svg = QrCode::new(data.as_bytes())
+        .wrap_err("Error encoding QR code")?
+        .render::<svg::Color>()
+        .min_dimensions(QR_CODE_SIZE, QR_CODE_SIZE)
+        .build();
+    diagrams::render_png(&svg)
+}
+
+/// Redirect to the page for the forecast file that the token links to.
+async fn handler(
+    Path(token): Path<String>,
+    Extension(database): Extension<Database>,
+) -> axum::response::Result<Response> {
+    let Some(file_name) = get_file_name(&database, &token)
+        .await
+        .map_err(map_eyre_error)?
+    else {
+        return Ok(StatusCode::NOT_FOUND.into_response());
+    };
+    let path = ForecastsFilePath { file_name }.to_uri().to_string();
+    Ok(Redirect::to(&path).into_response())
+}
+
+/// A QR code PNG image of the short link for the token.
+async fn qr_code_handler(
+    Path(token): Path<String>,
+    State(state): State<AppState>,
+    Extension(database): Extension<Database>,
+) -> axum::response::Result<Response> {
+    if get_file_name(&database, &token)
+        .await
+        .map_err(map_eyre_error)?
+        .is_none()
+    {
+        return Ok(StatusCode::NOT_FOUND.into_response());
+    }
+    let url = short_url(&state.options.base_url(), &token)
+        .ok_or_else(|| map_eyre_error(eyre::eyre!("Unable to create short link url")))?;
+    let png_data = tokio::task::spawn_blocking(move || qr_code_png(url.as_str()))
+        .await
+        .map_err(map_std_error)?
+        .map_err(map_eyre_error)?;
+    Ok(([(header::CONTENT_TYPE, "image/png")], png_data).into_response())
+}
+
+#[cfg(test)]
+mod test {
+    use super::{generate_token, qr_code_png, short_url, TOKEN_ALPHABET, TOKEN_LEN};
+
+    #[test]
+    fn test_generate_token() {
+        let token = generate_token();
+        assert_eq!(token.len(), TOKEN_LEN);
+        assert!(token.bytes().all(|byte| TOKEN_ALPHABET.contains(&byte)));
+    }
+
+    #[test]
+    fn test_short_url() {
+        let base_url = url::Url::parse("https://example.com/").unwrap();
+        assert_eq!(
+            short_url(&base_url, "ab23cd").unwrap().as_str(),
+            "https://example.com/f/ab23cd"
+        );
+    }
+
+    #[test]
+    fn test_qr_code_png() {
+        let png = qr_code_png("https://example.com/f/ab23cd").unwrap();
+        assert!(png.starts_with(b"\x89PNG"));
+    }
+}
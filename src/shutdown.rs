@@ -0,0 +1,115 @@
+//! Graceful shutdown. When the server receives `SIGTERM` (or Ctrl+C) it stops accepting
+//! connections and finishes the in-flight requests, and then signals the background tasks to stop
+//! (see [`Shutdown`]), waiting for them to flush any pending work (e.g. the accumulated analytics
+//! events) before exiting.
+
+use std::time::Duration;
+
+use tokio::sync::{mpsc, watch};
+
+/// Held by a background task to be notified when the server is shutting down. The server waits
+/// for all the [`Shutdown`]s (and their clones) to be dropped before exiting, so a task should
+/// hold it until it has finished flushing its pending work.
+#[derive(Clone)]
+pub struct Shutdown {
+    requested: watch::Receiver<bool>,
+    _complete: mpsc::Sender<()>,
+}
+
+impl Shutdown {
+    /// Completes when shutdown has been requested.
+    pub async fn requested(&mut self) {
+        // An error means the controller was dropped without requesting shutdown, in which case
+        // this will never complete.
+        if self
+            .requested
+            .wait_for(|requested| *requested)
+            .await
+            .is_err()
+        {
+            std::future::pending::<()>().await;
+        }
+    }
+}
+
+/// Creates the [`Shutdown`]s for the background tasks, and signals them when the server is
+/// shutting down.
+pub struct ShutdownController {
+    requested: watch::Sender<bool>,
+    complete_tx: mpsc::Sender<()>,
+    complete_rx: mpsc::Receiver<()>,
+}
+
+impl Default for ShutdownController {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ShutdownController {
+    pub fn new() -> Self {
+        let (requested, _) = watch::channel(false);
+        let (complete_tx, complete_rx) = mpsc::channel(1);
+        Self {
+            requested,
+            complete_tx,
+            complete_rx,
+        }
+    }
+
+    pub fn subscribe(&self) -> Shutdown {
+        Shutdown {
+            requested: self.requested.subscribe(),
+            _complete: self.complete_tx.clone(),
+        }
+    }
+
+    /// Signal the background tasks to stop, and wait up to `timeout` for them to finish.
+    pub async fn shutdown(self, timeout: Duration) {
+        let Self {
+            requested,
+            complete_tx,
+            mut complete_rx,
+        } = self;
+        tracing::info!("Stopping background tasks...");
+        requested.send_replace(true);
+        drop(complete_tx);
+        // Nothing is ever sent, this completes when all the senders have been dropped.
+        match tokio::time::timeout(timeout, complete_rx.recv()).await {
+            Ok(_) => tracing::info!("Stopped background tasks"),
+            Err(_) => tracing::warn!(
+                "Background tasks did not stop within {}, exiting anyway",
+                humantime::format_duration(timeout)
+            ),
+        }
+    }
+}
+
+/// Completes when the process receives `SIGTERM` or Ctrl+C.
+pub async fn signal() {
+    let ctrl_c = async {
+        if let Err(error) = tokio::signal::ctrl_c().await {
+            tracing::error!("Error listening for Ctrl+C: {error}");
+            std::future::pending::<()>().await;
+        }
+    };
+    #[cfg(unix)]
+    let terminate = async {
+        match tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate()) {
+            Ok(mut terminate) => {
+                terminate.recv().await;
+            }
+            Err(error) => {
+                tracing::error!("Error listening for SIGTERM: {error}");
+                std::future::pending::<()>().await;
+            }
+        }
+    };
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = ctrl_c => tracing::info!("Received Ctrl+C, shutting down..."),
+        _ = terminate => tracing::info!("Received SIGTERM, shutting down..."),
+    }
+}
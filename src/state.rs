@@ -2,21 +2,55 @@ use axum::extract::FromRef;
 use tokio::sync::mpsc;
 
 use crate::{
-    analytics, current_weather::CurrentWeatherService, database::Database,
-    forecasts::ForecastSpreadsheetSchema, i18n::I18nLoader, options::Options, templates::Templates,
+    aggregators::Aggregators,
+    analytics, api,
+    auth::{BasicAuthCache, LoginThrottle, SessionKey},
+    current_weather::CurrentWeatherService,
+    database::{blob::BlobStore, Database},
+    diagrams::cache::DiagramCache,
+    forecasts::{published::PublishedFiles, ForecastSpreadsheetSchema, ForecastSpreadsheetSchemas},
+    i18n::I18nLoader,
+    observations,
+    options::{Options, ReloadableOptionsHandle},
+    templates::Templates,
+    webcams::Webcams,
+    webhooks::Webhooks,
 };
 
 /// App state is designed to be cheap to clone.
 #[derive(Clone)]
 pub struct AppState {
     pub options: &'static Options,
+    /// The options which can be reloaded without restarting, see
+    /// [`crate::options::ReloadableOptions`].
+    pub reloadable_options: ReloadableOptionsHandle,
+    /// The schema for the newest version of the forecast spreadsheet template.
     pub forecast_spreadsheet_schema: &'static ForecastSpreadsheetSchema,
+    pub forecast_spreadsheet_schemas: &'static ForecastSpreadsheetSchemas,
     pub client: reqwest::Client,
     pub i18n: I18nLoader,
     pub templates: Templates,
     pub database: Database,
     pub analytics_sx: mpsc::Sender<analytics::Event>,
     pub current_weather: std::sync::Arc<CurrentWeatherService>,
+    pub session_key: SessionKey,
+    pub basic_auth_cache: BasicAuthCache,
+    pub login_throttle: LoginThrottle,
+    pub observation_throttle: observations::SubmissionThrottle,
+    pub diagram_cache: std::sync::Arc<DiagramCache>,
+    pub published_files: std::sync::Arc<PublishedFiles>,
+    pub blobs: std::sync::Arc<BlobStore>,
+    pub webcams: std::sync::Arc<Webcams>,
+    pub webhooks: Webhooks,
+    pub aggregators: Aggregators,
+    pub api_key_rate_limiters: api::keys::RateLimiters,
+    pub api_key_usage: api::keys::UsageCounter,
+}
+
+impl FromRef<AppState> for std::sync::Arc<DiagramCache> {
+    fn from_ref(state: &AppState) -> Self {
+        state.diagram_cache.clone()
+    }
 }
 
 impl FromRef<AppState> for std::sync::Arc<CurrentWeatherService> {
@@ -0,0 +1,446 @@
+//! Statistics for each season, derived from the archive of parsed forecasts and the approved
+//! observations: the number of forecasts for each area, the distribution of the overall danger
+//! ratings, the most frequent avalanche problems and the number of observations. Shown at
+//! `/statistics` with charts, and available as JSON at `/statistics.json`.
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+use axum::{
+    extract::{Query, State},
+    response::Response,
+    Extension, Json,
+};
+use forecast_spreadsheet::{HazardRatingKind, ProblemKind};
+use serde::{Deserialize, Serialize};
+use time::{Month, OffsetDateTime};
+
+use crate::{
+    database::Database,
+    diagrams::elevation_hazard::HazardLevel,
+    error::map_eyre_error,
+    i18n::{self, I18nLoader},
+    observations::ObservationStatus,
+    state::AppState,
+    templates::TemplatesWithContext,
+    types,
+};
+
+/// Month that each season starts in.
+const SEASON_START_MONTH: Month = Month::September;
+/// Width of the longest bar in the charts.
+const CHART_BAR_WIDTH: f64 = 200.0;
+const CHART_DEFAULT_COLOUR: &str = "#1e3a5f";
+
+/// A season, starting in [`SEASON_START_MONTH`] of the year.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct Season(i32);
+
+impl Season {
+    pub fn containing(time: OffsetDateTime) -> Self {
+        if u8::from(time.month()) >= u8::from(SEASON_START_MONTH) {
+            Self(time.year())
+        } else {
+            Self(time.year() - 1)
+        }
+    }
+
+    /// The year that the season starts in.
+    pub fn year(&self) -> i32 {
+        self.0
+    }
+
+    /// The label for the season, e.g. `2023/24`.
+    pub fn label(&self) -> String {
+        format!("{}/{:02}", self.0, (self.0 + 1).rem_euclid(100))
+    }
+}
+
+#[derive(Serialize, Debug, PartialEq)]
+pub struct DangerRatingCount {
+    pub level: HazardLevel,
+    pub count: usize,
+}
+
+#[derive(Serialize, Debug, PartialEq)]
+pub struct ProblemCount {
+    pub kind: ProblemKind,
+    pub count: usize,
+}
+
+#[derive(Serialize, Debug, PartialEq)]
+pub struct SeasonStatistics {
+    pub season: Season,
+    pub label: String,
+    pub forecasts: usize,
+    /// Number of forecasts for each area id.
+    pub forecasts_per_area: BTreeMap<String, usize>,
+    /// Number of forecasts with each overall danger rating, for every level of the danger scale.
+    pub danger_ratings: Vec<DangerRatingCount>,
+    /// Number of forecasts including each avalanche problem, most frequent first, omitting
+    /// problems which were never forecast.
+    pub problems: Vec<ProblemCount>,
+    pub observations: usize,
+}
+
+#[derive(Default)]
+struct SeasonAccumulator {
+    forecasts: usize,
+    forecasts_per_area: BTreeMap<String, usize>,
+    danger_ratings: HashMap<HazardLevel, usize>,
+    problems: HashMap<ProblemKind, usize>,
+    observations: usize,
+}
+
+impl SeasonAccumulator {
+    fn finish(mut self, season: Season) -> SeasonStatistics {
+        let danger_ratings = enum_iterator::all::<HazardLevel>()
+            .map(|level| DangerRatingCount {
+                level,
+                count: self.danger_ratings.get(&level).copied().unwrap_or_default(),
+            })
+            .collect();
+        let mut problems: Vec<ProblemCount> = enum_iterator::all::<ProblemKind>()
+            .filter_map(|kind| {
+                let count = self.problems.remove(&kind)?;
+                Some(ProblemCount { kind, count })
+            })
+            .collect();
+        problems.sort_by_key(|problem| std::cmp::Reverse(problem.count));
+        SeasonStatistics {
+            season,
+            label: season.label(),
+            forecasts: self.forecasts,
+            forecasts_per_area: self.forecasts_per_area,
+            danger_ratings,
+            problems,
+            observations: self.observations,
+        }
+    }
+}
+
+/// Compute the statistics for each season which has forecasts or observations, most recent
+/// season first. Forecasts for the same area and time (e.g. the same forecast uploaded in
+/// multiple files) are only counted once.
+pub fn compute_statistics(
+    forecasts: &[forecast_spreadsheet::Forecast],
+    observation_times: &[OffsetDateTime],
+) -> Vec<SeasonStatistics> {
+    let mut seasons: BTreeMap<Season, SeasonAccumulator> = BTreeMap::new();
+    let mut counted: HashSet<(String, OffsetDateTime)> = HashSet::new();
+    for forecast in forecasts {
+        if !counted.insert((forecast.area.to_string(), forecast.time)) {
+            continue;
+        }
+        let season = seasons
+            .entry(Season::containing(forecast.time))
+            .or_default();
+        season.forecasts += 1;
+        *season
+            .forecasts_per_area
+            .entry(forecast.area.to_string())
+            .or_default() += 1;
+        let level = forecast
+            .hazard_ratings
+            .get(&HazardRatingKind::Overall)
+            .and_then(|rating| rating.value)
+            .map(HazardLevel::from)
+            .unwrap_or(HazardLevel::NoRating);
+        *season.danger_ratings.entry(level).or_default() += 1;
+        let kinds: HashSet<ProblemKind> = forecast
+            .avalanche_problems
+            .iter()
+            .map(|problem| problem.kind)
+            .collect();
+        for kind in kinds {
+            *season.problems.entry(kind).or_default() += 1;
+        }
+    }
+    for time in observation_times {
+        seasons
+            .entry(Season::containing(*time))
+            .or_default()
+            .observations += 1;
+    }
+    seasons
+        .into_iter()
+        .rev()
+        .map(|(season, accumulator)| accumulator.finish(season))
+        .collect()
+}
+
+async fn load_statistics(database: &Database) -> eyre::Result<Vec<SeasonStatistics>> {
+    let forecasts: Vec<forecast_spreadsheet::Forecast> = sqlx::query_scalar!(
+        r#"SELECT parsed_forecast as "parsed_forecast!: sqlx::types::Json<forecast_spreadsheet::Forecast>" FROM forecast_files WHERE parsed_forecast IS NOT NULL"#
+    )
+    .fetch_all(database)
+    .await?
+    .into_iter()
+    .map(|forecast| forecast.0)
+    .collect();
+    let observation_times: Vec<OffsetDateTime> = sqlx::query_scalar!(
+        r#"SELECT observed_at as "observed_at: types::Time" FROM observations WHERE status = $1"#,
+        ObservationStatus::Approved
+    )
+    .fetch_all(database)
+    .await?
+    .into_iter()
+    .map(OffsetDateTime::from)
+    .collect();
+    Ok(compute_statistics(&forecasts, &observation_times))
+}
+
+pub async fn json_handler(
+    Extension(database): Extension<Database>,
+) -> axum::response::Result<Json<Vec<SeasonStatistics>>> {
+    Ok(Json(
+        load_statistics(&database).await.map_err(map_eyre_error)?,
+    ))
+}
+
+#[derive(Serialize)]
+struct ChartBar {
+    label: String,
+    count: usize,
+    /// Width of the bar, proportional to the largest count in the chart.
+    width: f64,
+    colour: &'static str,
+}
+
+/// Bars for a chart of the `counts`, scaled so that the largest count is [`CHART_BAR_WIDTH`].
+fn chart_bars(counts: impl IntoIterator<Item = (String, usize, &'static str)>) -> Vec<ChartBar> {
+    let counts: Vec<_> = counts.into_iter().collect();
+    let max = counts.iter().map(|(_, count, _)| *count).max().unwrap_or(0);
+    counts
+        .into_iter()
+        .map(|(label, count, colour)| ChartBar {
+            label,
+            count,
+            width: if max == 0 {
+                0.0
+            } else {
+                CHART_BAR_WIDTH * count as f64 / max as f64
+            },
+            colour,
+        })
+        .collect()
+}
+
+#[derive(Serialize)]
+struct SeasonOption {
+    season: Season,
+    label: String,
+}
+
+#[derive(Serialize)]
+struct SelectedSeasonContext {
+    label: String,
+    forecasts: usize,
+    observations: usize,
+    areas: Vec<ChartBar>,
+    danger_ratings: Vec<ChartBar>,
+    problems: Vec<ChartBar>,
+}
+
+#[derive(Serialize)]
+struct StatisticsContext {
+    seasons: Vec<SeasonOption>,
+    selected: Option<SelectedSeasonContext>,
+}
+
+#[derive(Deserialize)]
+pub struct StatisticsQuery {
+    /// The year that the season to show starts in, defaults to the most recent season.
+    season: Option<Season>,
+}
+
+pub async fn handler(
+    Query(query): Query<StatisticsQuery>,
+    State(state): State<AppState>,
+    Extension(templates): Extension<TemplatesWithContext>,
+    Extension(i18n): Extension<I18nLoader>,
+) -> axum::response::Result<Response> {
+    let statistics = load_statistics(&state.database)
+        .await
+        .map_err(map_eyre_error)?;
+    let seasons = statistics
+        .iter()
+        .map(|statistics| SeasonOption {
+            season: statistics.season,
+            label: statistics.label.clone(),
+        })
+        .collect();
+    let selected =
+        statistics
+            .into_iter()
+            .find(|statistics| {
+                query
+                    .season
+                    .is_none_or(|season| statistics.season == season)
+            })
+            .map(|statistics| SelectedSeasonContext {
+                label: statistics.label,
+                forecasts: statistics.forecasts,
+                observations: statistics.observations,
+                areas: chart_bars(statistics.forecasts_per_area.into_iter().map(
+                    |(area, count)| {
+                        let name = i18n::message_or(
+                            &i18n,
+                            &format!("forecast-area-{}", area.to_lowercase()),
+                            &area,
+                        );
+                        (name, count, CHART_DEFAULT_COLOUR)
+                    },
+                )),
+                danger_ratings: chart_bars(statistics.danger_ratings.into_iter().map(|rating| {
+                    (
+                        i18n.get(&format!("avalanche-hazard-{}", rating.level.id())),
+                        rating.count,
+                        rating.level.colour_hex(),
+                    )
+                })),
+                problems: chart_bars(statistics.problems.into_iter().map(|problem| {
+                    let kind = serde_json::to_value(problem.kind)
+                        .ok()
+                        .and_then(|value| value.as_str().map(ToOwned::to_owned))
+                        .unwrap_or_default();
+                    (
+                        i18n.get(&format!("problem-type-{kind}")),
+                        problem.count,
+                        CHART_DEFAULT_COLOUR,
+                    )
+                })),
+            });
+
+    let context = StatisticsContext { seasons, selected };
+    Ok(templates
+        .render("statistics.html", &context)
+        .map_err(map_eyre_error)?)
+}
+
+#[cfg(test)]
+mod test {
+    use forecast_spreadsheet::{
+        Forecast, HazardRating, HazardRatingKind, HazardRatingValue, ProblemKind,
+    };
+    use time::macros::datetime;
+
+    use crate::diagrams::elevation_hazard::HazardLevel;
+
+    use super::{compute_statistics, ProblemCount, Season};
+
+    #[test]
+    fn test_season() {
+        assert_eq!(
+            Season::containing(datetime!(2023-12-01 0:00 UTC)),
+            Season(2023)
+        );
+        assert_eq!(
+            Season::containing(datetime!(2024-03-01 0:00 UTC)),
+            Season(2023)
+        );
+        assert_eq!(
+            Season::containing(datetime!(2024-09-01 0:00 UTC)),
+            Season(2024)
+        );
+        assert_eq!(Season(2023).label(), "2023/24");
+        assert_eq!(Season(1999).label(), "1999/00");
+    }
+
+    fn forecast(
+        time: time::OffsetDateTime,
+        level: HazardRatingValue,
+        problems: &[ProblemKind],
+    ) -> Forecast {
+        let mut forecast: Forecast = serde_json::from_value(serde_json::json!({
+            "template_version": { "major": 0, "minor": 3, "patch": 1 },
+            "area": "Gudauri",
+            "forecaster": { "name": "Forecaster" },
+            "time": "2023-01-01T00:00:00Z",
+            "valid_for": 86400,
+            "hazard_ratings": {},
+            "avalanche_problems": [],
+            "elevation_bands": {},
+        }))
+        .unwrap();
+        forecast.time = time;
+        forecast.hazard_ratings.insert(
+            HazardRatingKind::Overall,
+            HazardRating {
+                value: Some(level),
+                trend: None,
+                confidence: None,
+            },
+        );
+        forecast.avalanche_problems = problems
+            .iter()
+            .map(|kind| {
+                serde_json::from_value(serde_json::json!({
+                    "kind": kind,
+                    "aspect_elevation": {},
+                }))
+                .unwrap()
+            })
+            .collect();
+        forecast
+    }
+
+    #[test]
+    fn test_compute_statistics() {
+        let forecasts = [
+            forecast(
+                datetime!(2023-12-01 8:00 UTC),
+                HazardRatingValue::Moderate,
+                &[ProblemKind::WindSlab],
+            ),
+            // Duplicate of the first forecast, uploaded as another file.
+            forecast(
+                datetime!(2023-12-01 8:00 UTC),
+                HazardRatingValue::Moderate,
+                &[ProblemKind::WindSlab],
+            ),
+            forecast(
+                datetime!(2023-12-02 8:00 UTC),
+                HazardRatingValue::Considerable,
+                &[ProblemKind::WindSlab, ProblemKind::PersistentSlab],
+            ),
+            forecast(datetime!(2022-12-02 8:00 UTC), HazardRatingValue::Low, &[]),
+        ];
+        let observations = [datetime!(2024-01-01 12:00 UTC)];
+        let statistics = compute_statistics(&forecasts, &observations);
+        assert_eq!(statistics.len(), 2);
+
+        let season = &statistics[0];
+        assert_eq!(season.season, Season(2023));
+        assert_eq!(season.forecasts, 2);
+        assert_eq!(season.forecasts_per_area["Gudauri"], 2);
+        assert_eq!(season.observations, 1);
+        let count = |level: HazardLevel| {
+            season
+                .danger_ratings
+                .iter()
+                .find(|rating| rating.level == level)
+                .unwrap()
+                .count
+        };
+        assert_eq!(count(HazardLevel::Moderate), 1);
+        assert_eq!(count(HazardLevel::Considerable), 1);
+        assert_eq!(count(HazardLevel::High), 0);
+        assert_eq!(
+            season.problems,
+            [
+                ProblemCount {
+                    kind: ProblemKind::WindSlab,
+                    count: 2
+                },
+                ProblemCount {
+                    kind: ProblemKind::PersistentSlab,
+                    count: 1
+                },
+            ]
+        );
+
+        assert_eq!(statistics[1].season, Season(2022));
+        assert_eq!(statistics[1].observations, 0);
+    }
+}
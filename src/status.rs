@@ -0,0 +1,190 @@
+//! Public status page, showing how up to date the forecasts and weather station data are, and
+//! whether the site is currently unable to reach Google Drive and is showing cached forecasts.
+//! Uses the same data as the `published_files_*` metrics (see [`crate::prometheus`]) and the
+//! `weather-station-stale` webhook event, without any details about the internals.
+
+use std::collections::BTreeMap;
+
+use axum::{extract::State, response::Response, Extension};
+use serde::Serialize;
+use time::OffsetDateTime;
+
+use crate::{
+    current_weather::get_cached_data,
+    error::map_eyre_error,
+    forecasts::{parse_forecast_name, ForecastSpreadsheetSchema},
+    google_drive::ListFileMetadata,
+    i18n::{self, I18nLoader},
+    state::AppState,
+    templates::{render, TemplatesWithContext},
+};
+
+#[derive(Serialize)]
+struct StatusContext {
+    /// Google Drive is currently unreachable and the forecasts shown may be out of date.
+    degraded: bool,
+    /// When the forecasts were last checked for updates.
+    formatted_checked_at: Option<String>,
+    areas: Vec<AreaStatusContext>,
+    weather_stations: Vec<WeatherStationStatusContext>,
+}
+
+#[derive(Serialize)]
+struct AreaStatusContext {
+    /// Localized name of the area.
+    name: String,
+    /// Time of the latest forecast for the area.
+    formatted_forecast_time: String,
+    /// When a forecast file for the area was last modified.
+    formatted_updated_at: String,
+}
+
+#[derive(Serialize)]
+struct WeatherStationStatusContext {
+    /// Localized name of the weather station.
+    name: String,
+    formatted_latest_data_time: Option<String>,
+    /// The weather station hasn't reported for longer than
+    /// [`crate::options::Webhooks::weather_station_stale_minutes`].
+    stale: bool,
+}
+
+/// The latest forecast for an area.
+#[derive(Debug, PartialEq)]
+struct AreaStatus {
+    forecast_time: OffsetDateTime,
+    updated_at: OffsetDateTime,
+}
+
+/// Latest forecast for each area of the forecast `files`, ignoring files which aren't named like a
+/// forecast.
+fn area_statuses(
+    files: &[ListFileMetadata],
+    schema: &ForecastSpreadsheetSchema,
+) -> BTreeMap<String, AreaStatus> {
+    let mut areas: BTreeMap<String, AreaStatus> = BTreeMap::new();
+    for file in files {
+        let Ok(details) = parse_forecast_name(&file.name, schema) else {
+            continue;
+        };
+        let forecast = details.forecast;
+        let status = areas.entry(forecast.area).or_insert(AreaStatus {
+            forecast_time: forecast.time,
+            updated_at: file.modified_time,
+        });
+        status.forecast_time = status.forecast_time.max(forecast.time);
+        status.updated_at = status.updated_at.max(file.modified_time);
+    }
+    areas
+}
+
+pub async fn handler(
+    State(state): State<AppState>,
+    Extension(templates): Extension<TemplatesWithContext>,
+    Extension(i18n): Extension<I18nLoader>,
+) -> axum::response::Result<Response> {
+    let freshness = state.published_files.freshness();
+    let files = state
+        .published_files
+        .list_files()
+        .await
+        .map_err(map_eyre_error)?;
+    let areas = area_statuses(&files, state.forecast_spreadsheet_schema)
+        .into_iter()
+        .map(|(area, status)| AreaStatusContext {
+            name: i18n::message_or(
+                &i18n,
+                &format!("forecast-area-{}", area.to_lowercase()),
+                &area,
+            ),
+            formatted_forecast_time: i18n::format_time(status.forecast_time, &i18n),
+            formatted_updated_at: i18n::format_time(status.updated_at, &i18n),
+        })
+        .collect();
+
+    let now = OffsetDateTime::now_utc();
+    let stale_after = time::Duration::minutes(
+        i64::try_from(state.options.webhooks.weather_station_stale_minutes).unwrap_or(i64::MAX),
+    );
+    let mut ids: Vec<_> = state.options.weather_stations.keys().collect();
+    ids.sort_by_key(|id| id.to_string());
+    let mut weather_stations = Vec::with_capacity(ids.len());
+    for id in ids {
+        let latest_data_time = get_cached_data(&state.database, id)
+            .await
+            .map_err(map_eyre_error)?
+            .iter()
+            .map(|item| item.time)
+            .max();
+        weather_stations.push(WeatherStationStatusContext {
+            name: i18n::message_or(
+                &i18n,
+                &format!("weather-station-{id}-label"),
+                &id.to_string(),
+            ),
+            formatted_latest_data_time: latest_data_time.map(|time| i18n::format_time(time, &i18n)),
+            stale: latest_data_time.is_none_or(|time| now - time > stale_after),
+        });
+    }
+
+    let context = StatusContext {
+        degraded: freshness.stale,
+        formatted_checked_at: freshness
+            .fetched_at
+            .map(|fetched_at| i18n::format_time(fetched_at, &i18n)),
+        areas,
+        weather_stations,
+    };
+    Ok(render(&templates.environment, "status.html", &context).map_err(map_eyre_error)?)
+}
+
+#[cfg(test)]
+mod test {
+    use time::macros::datetime;
+
+    use crate::{
+        forecasts::{ForecastSpreadsheetSchema, GUDAURI_FORECAST_SCHEMA_JSON},
+        google_drive::ListFileMetadata,
+    };
+
+    use super::{area_statuses, AreaStatus};
+
+    fn file(name: &str, modified_time: time::OffsetDateTime) -> ListFileMetadata {
+        ListFileMetadata {
+            mime_type: "application/pdf".to_owned(),
+            id: name.to_owned(),
+            name: name.to_owned(),
+            modified_time,
+        }
+    }
+
+    #[test]
+    fn test_area_statuses() {
+        let schema: ForecastSpreadsheetSchema =
+            serde_json::from_str(GUDAURI_FORECAST_SCHEMA_JSON).unwrap();
+        let files = [
+            file(
+                "Gudauri_2023-01-24T17:00_LF.en.pdf",
+                datetime!(2023-01-24 17:30 UTC),
+            ),
+            file(
+                "Gudauri_2023-01-25T17:00_LF.en.pdf",
+                datetime!(2023-01-25 17:10 UTC),
+            ),
+            file(
+                "Gudauri_2023-01-24T17:00_LF.ka.pdf",
+                datetime!(2023-01-26 09:00 UTC),
+            ),
+            file("notes.txt", datetime!(2023-01-27 09:00 UTC)),
+        ];
+        let areas = area_statuses(&files, &schema);
+        assert_eq!(areas.len(), 1);
+        assert_eq!(
+            areas["Gudauri"],
+            AreaStatus {
+                forecast_time: datetime!(2023-01-25 17:00 +04:00),
+                updated_at: datetime!(2023-01-26 09:00 UTC),
+            }
+        );
+    }
+}
@@ -1,8 +1,6 @@
 use std::{
     borrow::Cow,
     collections::{BTreeMap, HashMap},
-    future::{self, Future},
-    pin::Pin,
     sync::Arc,
 };
 
@@ -14,18 +12,21 @@ use axum::{
 };
 use eyre::Context;
 use fluent::{types::FluentNumber, FluentValue};
-use http::{header::CONTENT_TYPE, StatusCode};
+use http::{header::CONTENT_TYPE, Uri};
 use minijinja::{
     value::{Value, ValueKind},
     Error, ErrorKind,
 };
 use pulldown_cmark::{Event, Tag, TagEnd};
 use rust_embed::{EmbeddedFile, RustEmbed};
+use serde::Serialize;
 use uuid::Uuid;
 
 use crate::{
     error::map_eyre_error,
-    i18n::{order_languages, ordered_language_display_names, I18nLoader},
+    glossary,
+    i18n::{self, order_languages, ordered_languages, I18nLoader},
+    user_preferences::UserPreferences,
     AppState,
 };
 
@@ -57,31 +58,9 @@ impl TemplatesWithContext {
 
 impl Templates {
     pub fn initialize(options: &'static crate::options::Templates) -> eyre::Result<Self> {
-        let reloader = minijinja_autoreload::AutoReloader::new(|notifier| {
+        let reloader = minijinja_autoreload::AutoReloader::new(move |notifier| {
             let mut environment = minijinja::Environment::new();
-            environment.set_loader(|name: &str| {
-                if let Some(directory) = &options.directory {
-                    let name_sanitized = name.replace("..", "");
-                    let path = directory.join(name_sanitized);
-                    if path.exists() {
-                        return Ok(Some(std::fs::read_to_string(path).map_err(|error| {
-                            Error::new(
-                                ErrorKind::TemplateNotFound,
-                                format!("Error loading template {name}: {error}"),
-                            )
-                        })?));
-                    }
-                }
-
-                Option::transpose(EmbeddedTemplates::get(name).map(|file: EmbeddedFile| {
-                    String::from_utf8(file.data.to_vec()).map_err(|error| {
-                        Error::new(
-                            ErrorKind::SyntaxError,
-                            format!("Template {name} is not valid UTF-8: {error}"),
-                        )
-                    })
-                }))
-            });
+            environment.set_loader(move |name: &str| load_template(options, name));
 
             // RustEmbed only loads from files in debug mode (unless the debug embed feature is
             // enabled).
@@ -89,8 +68,8 @@ impl Templates {
             {
                 notifier.watch_path("src/templates", true);
             }
-            if let Some(path) = &options.directory {
-                notifier.watch_path(path, true);
+            for directory in options.directories() {
+                notifier.watch_path(directory, true);
             }
             Ok(environment)
         });
@@ -100,6 +79,69 @@ impl Templates {
     }
 }
 
+/// Load the source of the template with `name`, preferring the override in the highest priority
+/// of the [`crate::options::Templates::directories`] over the embedded template.
+pub fn load_template(
+    options: &crate::options::Templates,
+    name: &str,
+) -> Result<Option<String>, Error> {
+    if let Some(path) = override_path(options, name) {
+        return Ok(Some(std::fs::read_to_string(path).map_err(|error| {
+            Error::new(
+                ErrorKind::TemplateNotFound,
+                format!("Error loading template {name}: {error}"),
+            )
+        })?));
+    }
+
+    Option::transpose(EmbeddedTemplates::get(name).map(|file: EmbeddedFile| {
+        String::from_utf8(file.data.to_vec()).map_err(|error| {
+            Error::new(
+                ErrorKind::SyntaxError,
+                format!("Template {name} is not valid UTF-8: {error}"),
+            )
+        })
+    }))
+}
+
+/// Whether the template `name` is a relative path without `..` or `.` components, so that it can't
+/// refer to a file outside of the [`crate::options::Templates::directories`].
+fn is_valid_name(name: &str) -> bool {
+    !name.is_empty()
+        && std::path::Path::new(name)
+            .components()
+            .all(|component| matches!(component, std::path::Component::Normal(_)))
+}
+
+/// Path of the file which overrides the template with `name`, in the highest priority directory
+/// which contains it.
+fn override_path(options: &crate::options::Templates, name: &str) -> Option<std::path::PathBuf> {
+    overriding_directory(options, name).map(|directory| directory.join(name))
+}
+
+/// The highest priority of the [`crate::options::Templates::directories`] which contains a file
+/// overriding the template with `name`, if any.
+pub fn overriding_directory<'a>(
+    options: &'a crate::options::Templates,
+    name: &str,
+) -> Option<&'a std::path::Path> {
+    if !is_valid_name(name) {
+        return None;
+    }
+    options
+        .directories()
+        .find(|directory| directory.join(name).is_file())
+}
+
+/// Names of the embedded templates, sorted.
+pub fn template_names() -> Vec<String> {
+    let mut names: Vec<String> = EmbeddedTemplates::iter()
+        .map(|name| name.into_owned())
+        .collect();
+    names.sort();
+    names
+}
+
 fn jinja_to_fluent_args<'source>(
     args: Value,
 ) -> Result<HashMap<String, FluentValue<'source>>, Error> {
@@ -140,7 +182,7 @@ fn jinja_to_fluent_args<'source>(
                 Ok((key.to_string(), fluent_value))
             }).collect()
         },
-        kind => return Err(
+        kind => Err(
             Error::new(
                 ErrorKind::InvalidOperation,
                 format!("Invalid argument type {kind} for {args}. Expected a Map.")
@@ -149,6 +191,49 @@ fn jinja_to_fluent_args<'source>(
     }
 }
 
+/// Convert a numeric `value` using `convert`, and format it rounded to a whole number (for the
+/// current language of `i18n`) followed by the unit's `symbol`. `none` and undefined values are
+/// passed through.
+fn format_converted(
+    value: Value,
+    convert: impl Fn(f64) -> f64,
+    symbol: &str,
+    i18n: &I18nLoader,
+) -> Result<Value, Error> {
+    if value.is_none() || value.is_undefined() {
+        return Ok(value);
+    }
+    let number = f64::try_from(value)?;
+    Ok(Value::from(format!(
+        "{} {symbol}",
+        i18n::format_number(convert(number), 0, i18n)
+    )))
+}
+
+/// Parse a date (`2023-01-24`) or RFC 3339 date time string.
+fn parse_date_value(value: &Value) -> Result<(time::Date, Option<time::OffsetDateTime>), Error> {
+    let string = value.as_str().ok_or_else(|| {
+        Error::new(
+            ErrorKind::InvalidOperation,
+            format!("Expected a date string, found {:?}", value.kind()),
+        )
+    })?;
+    if let Ok(date) = time::Date::parse(
+        string,
+        time::macros::format_description!("[year]-[month]-[day]"),
+    ) {
+        return Ok((date, None));
+    }
+    let time = time::OffsetDateTime::parse(string, &time::format_description::well_known::Rfc3339)
+        .map_err(|error| {
+            Error::new(
+                ErrorKind::InvalidOperation,
+                format!("Unable to parse {string:?} as a date: {error}"),
+            )
+        })?;
+    Ok((time.date(), Some(time)))
+}
+
 /// Convert a [Value] into a query string: e.g.
 /// `param=something&other_param=5` This supports a `Map<String, Value>`, and a `Seq<Seq<Value>>`
 /// (where the length of the inner `Seq` is 2, the first element is `String` and the second element
@@ -179,7 +264,7 @@ fn querystring(query: Value) -> Result<minijinja::value::Value, Error> {
                     .map(|value| {
                         match value.kind() {
                             ValueKind::Undefined | ValueKind::None => None,
-                            _ => Some(format!("{}={}", key.to_string(), urlencoding::encode(&value.to_string()))),
+                            _ => Some(format!("{}={}", key, urlencoding::encode(&value.to_string()))),
                         }
                     }))
             })
@@ -255,25 +340,137 @@ pub fn mapremove(map: Value, key: Cow<'_, str>) -> Result<Value, Error> {
     }
 }
 
+/// Negotiate which language of the `translations` (a map from language to text) to display,
+/// based on the `requested_languages`. Returns `None` if there are no translations.
+fn negotiate_translation_language(
+    translations: &Value,
+    requested_languages: &[unic_langid::LanguageIdentifier],
+    language_order: &[unic_langid::LanguageIdentifier],
+) -> Result<Option<String>, minijinja::Error> {
+    if translations.len().unwrap_or(0) == 0 {
+        return Ok(None);
+    }
+
+    let available_languages: Vec<unic_langid::LanguageIdentifier> = translations
+        .try_iter()?
+        .map(|key| {
+            let key = match key.as_str() {
+                Some(key) => key,
+                None => {
+                    return Err(minijinja::Error::new(
+                        ErrorKind::InvalidOperation,
+                        "key is not a string",
+                    ))
+                }
+            };
+
+            let language: unic_langid::LanguageIdentifier = key.parse().map_err(|e| {
+                minijinja::Error::new(
+                    ErrorKind::InvalidOperation,
+                    format!("unable to parse key as language: {e:?}"),
+                )
+            })?;
+
+            Ok(language)
+        })
+        .collect::<Result<_, minijinja::Error>>()?;
+
+    let available_languages = order_languages(available_languages, language_order, |al, l| al == l);
+
+    let selected_languages = fluent_langneg::negotiate_languages(
+        requested_languages,
+        &available_languages,
+        Some(available_languages.first().ok_or_else(|| {
+            minijinja::Error::new(
+                minijinja::ErrorKind::InvalidOperation,
+                "Unexpected error: expected at least one langauage to be available",
+            )
+        })?),
+        fluent_langneg::NegotiationStrategy::Filtering,
+    );
+
+    let selected_language = match selected_languages.first() {
+        Some(language) => language.to_string(),
+        None => {
+            return Err(minijinja::Error::new(
+                minijinja::ErrorKind::InvalidOperation,
+                "Unexpected error, no language selected",
+            ))
+        }
+    };
+    Ok(Some(selected_language))
+}
+
 /// Middleware that provides access to all available templates with context injected.
 pub async fn middleware(
     State(state): State<AppState>,
     Extension(i18n): Extension<I18nLoader>,
+    Extension(preferences): Extension<UserPreferences>,
     mut request: Request,
     next: Next,
 ) -> axum::response::Result<impl IntoResponse> {
-    let mut environment = (*state.templates.reloader.acquire_env().map_err(|error| {
-        (
-            StatusCode::INTERNAL_SERVER_ERROR,
-            format!("Error acquiring template environment: {error}"),
-        )
-    })?)
+    let environment = environment_with_context(&state, &i18n, &preferences, request.uri())
+        .map_err(map_eyre_error)?;
+    request.extensions_mut().insert(TemplatesWithContext {
+        environment: Arc::new(environment),
+    });
+
+    Ok(next.run(request).await)
+}
+
+/// An item of the navigation menu (see [`crate::options::NavigationItem`]), for the `NAVIGATION`
+/// global.
+#[derive(Serialize)]
+struct NavigationContext {
+    href: String,
+    /// Translations of the label, displayed using `translated_string`.
+    label: Option<indexmap::IndexMap<String, String>>,
+    /// Fluent message for the label when there are no `label` translations.
+    message_id: Option<&'static str>,
+    /// Whether the current page is within this item.
+    active: bool,
+}
+
+fn navigation_context(state: &AppState, path: &str) -> Vec<NavigationContext> {
+    state
+        .options
+        .navigation()
+        .into_iter()
+        .map(|item| {
+            let href = item.link.href().to_owned();
+            let active = if href == "/" {
+                path == "/"
+            } else {
+                path == href || path.starts_with(&format!("{}/", href.trim_end_matches('/')))
+            };
+            NavigationContext {
+                message_id: item.link.message_id(),
+                label: item.label,
+                href,
+                active,
+            }
+        })
+        .collect()
+}
+
+/// The template environment with the functions, filters and globals for rendering in the
+/// language of `i18n`, with the user's `preferences`, for the request with `uri`.
+pub fn environment_with_context(
+    state: &AppState,
+    i18n: &I18nLoader,
+    preferences: &UserPreferences,
+    uri: &Uri,
+) -> eyre::Result<minijinja::Environment<'static>> {
+    let mut environment = (*state
+        .templates
+        .reloader
+        .acquire_env()
+        .wrap_err("Error acquiring template environment")?)
     .clone();
     let language = i18n
         .current_languages()
-        .get(0)
-        .ok_or_else(|| eyre::eyre!("No current language"))
-        .map_err(map_eyre_error)?
+        .first()
+        .ok_or_else(|| eyre::eyre!("No current language"))?
         .clone();
 
     let language_short = language.language.to_string();
@@ -282,74 +479,55 @@ pub async fn middleware(
     let i18n_fl = i18n.clone();
     let i18n_fl_md = i18n.clone();
     let i18n_negotiate_translation = i18n.clone();
-
-    let language_display_names = minijinja::value::Value::from_serializable(
-        &ordered_language_display_names(&state.options.default_language_order),
+    let i18n_temperature = i18n.clone();
+    let i18n_elevation = i18n.clone();
+    let i18n_datetime = i18n.clone();
+    let i18n_date = i18n.clone();
+    let i18n_number = i18n.clone();
+    let i18n_glossary = i18n.clone();
+    let i18n_all_languages = state.i18n.clone();
+
+    let reloadable_options = state.reloadable_options.load_full();
+    let languages = ordered_languages(
+        &state.options.languages,
+        &reloadable_options.default_language_order,
+        state.options.enabled_languages.as_deref(),
     );
-
+    // `(id, native_name)` pairs, for templates which predate `LANGUAGES`.
+    let language_display_names: Vec<(String, String)> = languages
+        .iter()
+        .map(|language| (language.id.to_string(), language.native_name.clone()))
+        .collect();
+
+    let i18n_negotiate_translation_language = i18n.clone();
+    let reloadable_options_translated_string = reloadable_options.clone();
     environment.add_function("translated_string", move |translations: Value| {
         tracing::debug!("translations: {translations:?}");
 
-        if translations.len().unwrap_or(0) == 0 {
+        let Some(selected_language) = negotiate_translation_language(
+            &translations,
+            &i18n_negotiate_translation.current_languages(),
+            &reloadable_options_translated_string.default_language_order,
+        )?
+        else {
             return Ok(minijinja::Value::from_safe_string(String::new()));
-        }
-
-        let available_languages: Vec<unic_langid::LanguageIdentifier> = translations
-            .try_iter()?
-            .map(|key| {
-                let key = match key.as_str() {
-                    Some(key) => key,
-                    None => {
-                        return Err(minijinja::Error::new(
-                            ErrorKind::InvalidOperation,
-                            "key is not a string",
-                        ))
-                    }
-                };
-
-                let language: unic_langid::LanguageIdentifier = key.parse().map_err(|e| {
-                    minijinja::Error::new(
-                        ErrorKind::InvalidOperation,
-                        format!("unable to parse key as language: {e:?}"),
-                    )
-                })?;
-
-                Ok(language)
-            })
-            .collect::<Result<_, minijinja::Error>>()?;
-
-        let available_languages = order_languages(
-            available_languages,
-            &state.options.default_language_order,
-            |al, l| al == l,
-        );
-
-        let requested_languages = i18n_negotiate_translation.current_languages();
-
-        let selected_languages = fluent_langneg::negotiate_languages(
-            &requested_languages,
-            &available_languages,
-            Some(available_languages.first().ok_or_else(|| {
-                minijinja::Error::new(
-                    minijinja::ErrorKind::InvalidOperation,
-                    "Unexpected error: expected at least one langauage to be available",
-                )
-            })?),
-            fluent_langneg::NegotiationStrategy::Filtering,
-        );
-
-        let selected_language = match selected_languages.first() {
-            Some(language) => language.to_string(),
-            None => {
-                return Err(minijinja::Error::new(
-                    minijinja::ErrorKind::InvalidOperation,
-                    "Unexpected error, no language selected",
-                ))
-            }
         };
         // Return Value because this value is optional.
         translations.get_item(&Value::from(selected_language))
     });
+    // The language of the `translations` that `translated_string` displays.
+    environment.add_function(
+        "translated_string_language",
+        move |translations: Value| -> Result<Value, minijinja::Error> {
+            Ok(negotiate_translation_language(
+                &translations,
+                &i18n_negotiate_translation_language.current_languages(),
+                &reloadable_options.default_language_order,
+            )?
+            .map(Value::from)
+            .unwrap_or_else(|| Value::from(())))
+        },
+    );
     // Render a fluent message.
     environment.add_function("fl", move |message_id: &str, args: Option<Value>| {
         Ok(if let Some(args) = args {
@@ -429,7 +607,6 @@ pub async fn middleware(
                 .with_source(error)
             })
     });
-    let uri = request.uri();
     let query_value: Value = uri
         .query()
         .and_then(|query| match serde_urlencoded::from_str(query) {
@@ -441,6 +618,10 @@ pub async fn middleware(
         })
         .unwrap_or(().into());
     environment.add_function("uuid", || Uuid::new_v4().to_string());
+    let static_files_directory = state.options.static_files.directory.as_deref();
+    environment.add_function("asset", move |url: &str| {
+        crate::assets::hashed_url(url, static_files_directory)
+    });
     environment.add_filter("md", |value: Value| {
         if value.is_none() || value.is_undefined() {
             return value;
@@ -459,15 +640,109 @@ pub async fn middleware(
     environment.add_filter("mapremove", mapremove);
     environment.add_global("LANGUAGE_SHORT", language_short);
     environment.add_global("LANGUAGE", language_full);
-    environment.add_global("LANGUAGE_DISPLAY_NAMES", language_display_names);
+    environment.add_global(
+        "LANGUAGE_DISPLAY_NAMES",
+        Value::from_serializable(&language_display_names),
+    );
+    environment.add_global("LANGUAGES", Value::from_serializable(&languages));
+    environment.add_global(
+        "THEME",
+        Value::from_serializable(&preferences.theme.unwrap_or_default()),
+    );
+    let temperature_unit = preferences.temperature_unit.unwrap_or_default();
+    let elevation_unit = preferences.elevation_unit.unwrap_or_default();
+    environment.add_global(
+        "TEMPERATURE_UNIT",
+        Value::from_serializable(&temperature_unit),
+    );
+    environment.add_global("ELEVATION_UNIT", Value::from_serializable(&elevation_unit));
+    // Format a temperature in degrees celsius using the user's preferred unit.
+    environment.add_filter("temperature", move |value: Value| {
+        format_converted(
+            value,
+            |value| temperature_unit.convert_celsius(value),
+            temperature_unit.symbol(),
+            &i18n_temperature,
+        )
+    });
+    // Format an elevation in metres using the user's preferred unit.
+    environment.add_filter("elevation", move |value: Value| {
+        format_converted(
+            value,
+            |value| elevation_unit.convert_metres(value),
+            elevation_unit.symbol(),
+            &i18n_elevation,
+        )
+    });
+    // Format an RFC 3339 date time for the current language.
+    environment.add_filter("datetime", move |value: Value| {
+        if value.is_none() || value.is_undefined() {
+            return Ok(value);
+        }
+        match parse_date_value(&value)? {
+            (_, Some(time)) => Ok(Value::from(i18n::format_time(time, &i18n_datetime))),
+            (date, None) => Ok(Value::from(i18n::format_date(date, &i18n_datetime))),
+        }
+    });
+    // Format a date (or the date of an RFC 3339 date time) for the current language.
+    environment.add_filter("date", move |value: Value| {
+        if value.is_none() || value.is_undefined() {
+            return Ok(value);
+        }
+        let (date, _) = parse_date_value(&value)?;
+        Ok(Value::from(i18n::format_date(date, &i18n_date)))
+    });
+    // Format a number for the current language, rounded to `decimals` (default `0`) decimal
+    // places.
+    environment.add_filter(
+        "number",
+        move |value: Value, decimals: Option<u8>| -> Result<Value, Error> {
+            if value.is_none() || value.is_undefined() {
+                return Ok(value);
+            }
+            let number = f64::try_from(value)?;
+            Ok(Value::from(i18n::format_number(
+                number,
+                decimals.unwrap_or(0),
+                &i18n_number,
+            )))
+        },
+    );
+    // Mark up the terms from the glossary in HTML (e.g. the output of `md`) so that their
+    // definitions are shown as tooltips. `language` is the language of the text, which defaults
+    // to the current language.
+    environment.add_filter(
+        "glossary",
+        move |value: Value, language: Option<String>| -> Result<Value, Error> {
+            if value.is_none() || value.is_undefined() {
+                return Ok(value);
+            }
+            let html = if value.is_safe() {
+                value.to_string()
+            } else {
+                crate::diagrams::escape_xml(&value.to_string())
+            };
+            let terms = match language.and_then(|language| language.parse().ok()) {
+                Some(language) => {
+                    glossary::terms(&glossary::language_loader(&i18n_all_languages, &language))
+                }
+                None => glossary::terms(&i18n_glossary),
+            };
+            Ok(Value::from_safe_string(glossary::annotate(&html, &terms)))
+        },
+    );
+    environment.add_global(
+        "FEATURES",
+        Value::from_serializable(&state.options.features),
+    );
+    environment.add_global(
+        "NAVIGATION",
+        Value::from_serializable(&navigation_context(state, uri.path())),
+    );
     environment.add_global("URI", uri.to_string());
     environment.add_global("PATH", uri.path().to_string());
     environment.add_global("QUERY", query_value);
-    request.extensions_mut().insert(TemplatesWithContext {
-        environment: Arc::new(environment),
-    });
-
-    Ok(next.run(request).await)
+    Ok(environment)
 }
 
 /// Render a template into a response. `Content-Type` header is guessed using the file extension of
@@ -565,37 +840,62 @@ pub fn render<'env>(
 //     }
 // }
 
-/// Create a handler which renders a response using the template at `template_key`.
-pub fn create_handler(
-    template_key: &str,
-) -> impl (Fn(
-    Extension<TemplatesWithContext>,
-) -> Pin<Box<dyn Future<Output = axum::response::Result<Response>> + Send>>)
-       + Clone
-       + Send
-       + 'static {
-    fn render_impl(
-        templates: &TemplatesWithContext,
-        template_key: &str,
-    ) -> axum::response::Result<Response> {
-        Ok(templates
-            .render(template_key, &())
-            .map_err(map_eyre_error)?)
-    }
-    let template_key: String = template_key.to_owned();
-    move |Extension(templates): Extension<TemplatesWithContext>| {
-        let result = render_impl(&templates, &template_key);
-        Box::pin(future::ready(result))
-    }
-}
-
 #[cfg(test)]
 mod test {
     use std::collections::HashMap;
 
     use minijinja::value::Value;
 
-    use super::querystring;
+    use super::{load_template, overriding_directory, querystring};
+    use crate::options::Templates;
+
+    #[test]
+    fn test_template_layers() {
+        let base = tempfile::tempdir().unwrap();
+        let organisation = tempfile::tempdir().unwrap();
+        let seasonal = tempfile::tempdir().unwrap();
+        std::fs::write(base.path().join("base.html"), "base").unwrap();
+        std::fs::write(base.path().join("index.html"), "base index").unwrap();
+        std::fs::write(organisation.path().join("index.html"), "organisation index").unwrap();
+        std::fs::write(seasonal.path().join("search.html"), "seasonal search").unwrap();
+        std::fs::write(seasonal.path().join("index.html"), "seasonal index").unwrap();
+        let options = Templates {
+            directory: Some(seasonal.path().to_owned()),
+            layers: vec![base.path().to_owned(), organisation.path().to_owned()],
+        };
+
+        let load = |name: &str| load_template(&options, name).unwrap().unwrap();
+        assert_eq!(load("base.html"), "base");
+        assert_eq!(load("index.html"), "seasonal index");
+        assert_eq!(load("search.html"), "seasonal search");
+        assert_eq!(
+            overriding_directory(&options, "base.html"),
+            Some(base.path())
+        );
+        assert_eq!(overriding_directory(&options, "404.html"), None);
+        // Names which could refer to files outside of the directories.
+        let outside = base.path().join("base.html");
+        assert_eq!(
+            overriding_directory(&options, outside.to_str().unwrap()),
+            None
+        );
+        assert_eq!(overriding_directory(&options, "../base.html"), None);
+        assert_eq!(overriding_directory(&options, "./base.html"), None);
+        assert_eq!(
+            load_template(&options, outside.to_str().unwrap()).unwrap(),
+            None
+        );
+
+        let options = Templates {
+            directory: None,
+            layers: vec![base.path().to_owned(), organisation.path().to_owned()],
+        };
+        assert_eq!(
+            load_template(&options, "index.html").unwrap().unwrap(),
+            "organisation index"
+        );
+    }
+
     #[test]
     fn test_query_filter() {
         let mut map = HashMap::new();
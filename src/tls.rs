@@ -0,0 +1,142 @@
+//! Built-in TLS termination, for deployments without a reverse proxy (see
+//! [`crate::options::Tls`]). The certificate is either read from files, or obtained and renewed
+//! automatically from Let's Encrypt using ACME. Optionally a plain HTTP server redirects all
+//! requests to HTTPS.
+
+use std::net::SocketAddr;
+
+use axum::{http::Uri, response::Redirect, Router};
+use axum_server::{tls_rustls::RustlsConfig, Handle};
+use eyre::Context;
+use futures::StreamExt;
+use rustls_acme::{caches::DirCache, AcmeConfig};
+
+use crate::{
+    options::{Options, Tls, TlsCertificate},
+    shutdown,
+};
+
+/// Serve `app` over HTTPS on [`Options::listen_address`] until the server receives a shutdown
+/// signal (see [`shutdown::signal`]), along with the redirect server if it is enabled.
+pub async fn serve(app: Router, options: &'static Options, tls: &'static Tls) -> eyre::Result<()> {
+    // Both the ring and aws-lc-rs providers are enabled by dependencies, so rustls can't pick one
+    // automatically.
+    let _ = rustls::crypto::ring::default_provider().install_default();
+
+    let handle = Handle::new();
+    let shutdown_handle = handle.clone();
+    tokio::spawn(async move {
+        shutdown::signal().await;
+        shutdown_handle.graceful_shutdown(None);
+    });
+
+    let make_service = app.into_make_service_with_connect_info::<SocketAddr>();
+    let server = async {
+        match &tls.certificate {
+            TlsCertificate::Files {
+                certificate_path,
+                key_path,
+            } => {
+                let config = RustlsConfig::from_pem_file(certificate_path, key_path)
+                    .await
+                    .wrap_err("Error reading TLS certificate")?;
+                #[cfg(unix)]
+                spawn_reload_on_hangup(config.clone(), tls)
+                    .wrap_err("Error listening for SIGHUP")?;
+                axum_server::bind_rustls(options.listen_address, config)
+                    .handle(handle.clone())
+                    .serve(make_service)
+                    .await
+                    .wrap_err("Error running HTTPS server")
+            }
+            TlsCertificate::Acme {
+                domains,
+                contact_emails,
+                cache_directory,
+                staging,
+            } => {
+                let cache_directory = cache_directory
+                    .clone()
+                    .unwrap_or_else(|| options.data_dir.join("acme"));
+                let mut state = AcmeConfig::new(domains)
+                    .contact(contact_emails.iter().map(|email| format!("mailto:{email}")))
+                    .cache(DirCache::new(cache_directory))
+                    .directory_lets_encrypt(!staging)
+                    .state();
+                // Offer HTTP/2 as well as HTTP/1.1 to clients using ALPN.
+                let mut rustls_config = (*state.default_rustls_config()).clone();
+                rustls_config.alpn_protocols = vec![b"h2".to_vec(), b"http/1.1".to_vec()];
+                let acceptor = state.axum_acceptor(std::sync::Arc::new(rustls_config));
+                tokio::spawn(async move {
+                    while let Some(event) = state.next().await {
+                        match event {
+                            Ok(event) => tracing::info!("ACME: {event:?}"),
+                            Err(error) => tracing::error!("ACME error: {error:?}"),
+                        }
+                    }
+                });
+                axum_server::bind(options.listen_address)
+                    .acceptor(acceptor)
+                    .handle(handle.clone())
+                    .serve(make_service)
+                    .await
+                    .wrap_err("Error running HTTPS server")
+            }
+        }
+    };
+
+    match tls.redirect_listen_address {
+        Some(redirect_listen_address) => {
+            let base_url = options.base_url();
+            tracing::info!("redirecting http://{redirect_listen_address} to {base_url}");
+            let redirect = axum_server::bind(redirect_listen_address)
+                .handle(handle.clone())
+                .serve(redirect_router(base_url).into_make_service());
+            let redirect = async {
+                redirect
+                    .await
+                    .wrap_err("Error running HTTP redirect server")
+            };
+            tokio::try_join!(server, redirect)?;
+        }
+        None => server.await?,
+    }
+
+    Ok(())
+}
+
+/// Router which redirects all requests to the same path and query at `base_url`.
+fn redirect_router(base_url: url::Url) -> Router {
+    Router::new().fallback(move |uri: Uri| {
+        let mut url = base_url.clone();
+        url.set_path(uri.path());
+        url.set_query(uri.query());
+        async move { Redirect::permanent(url.as_str()) }
+    })
+}
+
+/// Read the certificate files again whenever the process receives `SIGHUP`, e.g. after they have
+/// been renewed.
+#[cfg(unix)]
+fn spawn_reload_on_hangup(config: RustlsConfig, tls: &'static Tls) -> eyre::Result<()> {
+    let TlsCertificate::Files {
+        certificate_path,
+        key_path,
+    } = &tls.certificate
+    else {
+        return Ok(());
+    };
+    let mut hangup = tokio::signal::unix::signal(tokio::signal::unix::SignalKind::hangup())?;
+    tokio::spawn(async move {
+        while hangup.recv().await.is_some() {
+            match config
+                .reload_from_pem_file(certificate_path, key_path)
+                .await
+            {
+                Ok(()) => tracing::info!("Reloaded TLS certificate"),
+                Err(error) => tracing::error!("Error reloading TLS certificate: {error:?}"),
+            }
+        }
+    });
+    Ok(())
+}
@@ -89,9 +89,9 @@ impl From<OffsetDateTime> for Time {
     }
 }
 
-impl Into<OffsetDateTime> for Time {
-    fn into(self) -> OffsetDateTime {
-        self.0
+impl From<Time> for OffsetDateTime {
+    fn from(time: Time) -> Self {
+        time.0
     }
 }
 
@@ -138,9 +138,9 @@ impl From<http::Uri> for Uri {
     }
 }
 
-impl Into<http::Uri> for Uri {
-    fn into(self) -> http::Uri {
-        self.0
+impl From<Uri> for http::Uri {
+    fn from(uri: Uri) -> Self {
+        uri.0
     }
 }
 
@@ -9,7 +9,14 @@ use eyre::{Context, ContextCompat};
 use http::{header::SET_COOKIE, HeaderMap, HeaderValue, StatusCode};
 use serde::{Deserialize, Serialize};
 
-use crate::error::map_eyre_error;
+use crate::{
+    auth::CurrentUser,
+    database::Database,
+    error::map_eyre_error,
+    i18n::{self, I18nLoader},
+    types,
+    users::UserId,
+};
 
 #[derive(Serialize, Deserialize, Default, Clone, Debug)]
 #[serde(default)]
@@ -18,6 +25,15 @@ pub struct UserPreferences {
     pub lang: Option<unic_langid::LanguageIdentifier>,
     /// What wind unit to use in weather information dispay.
     pub wind_unit: Option<WindUnit>,
+    /// What temperature unit to use in weather information display.
+    pub temperature_unit: Option<TemperatureUnit>,
+    /// What unit to use when displaying elevations.
+    pub elevation_unit: Option<ElevationUnit>,
+    /// What colour theme to display the pages in.
+    pub theme: Option<Theme>,
+    /// Which forecast area to show forecasts for on the index page, for deployments which
+    /// publish forecasts for multiple areas. Set to an empty string to show all areas.
+    pub area: Option<String>,
 }
 
 #[derive(Debug, Serialize, Deserialize, Default, Clone, Copy)]
@@ -27,6 +43,89 @@ pub enum WindUnit {
     KilometersPerHour,
 }
 
+impl WindUnit {
+    /// Convert a speed in metres per second into this unit.
+    pub fn convert_ms(self, speed: f64) -> f64 {
+        match self {
+            Self::MetersPerSecond => speed,
+            Self::KilometersPerHour => speed * 3.6,
+        }
+    }
+
+    pub fn symbol(self) -> &'static str {
+        match self {
+            Self::MetersPerSecond => "m/s",
+            Self::KilometersPerHour => "km/h",
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Default, Clone, Copy, PartialEq, Eq)]
+pub enum TemperatureUnit {
+    #[default]
+    Celsius,
+    Fahrenheit,
+}
+
+impl TemperatureUnit {
+    /// Convert a temperature in degrees celsius into this unit.
+    pub fn convert_celsius(self, temperature: f64) -> f64 {
+        match self {
+            Self::Celsius => temperature,
+            Self::Fahrenheit => temperature * 9.0 / 5.0 + 32.0,
+        }
+    }
+
+    pub fn symbol(self) -> &'static str {
+        match self {
+            Self::Celsius => "°C",
+            Self::Fahrenheit => "°F",
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Default, Clone, Copy, PartialEq, Eq)]
+pub enum ElevationUnit {
+    #[default]
+    Metres,
+    Feet,
+}
+
+impl ElevationUnit {
+    /// Convert an elevation in metres into this unit.
+    pub fn convert_metres(self, elevation: f64) -> f64 {
+        match self {
+            Self::Metres => elevation,
+            Self::Feet => elevation / 0.3048,
+        }
+    }
+
+    /// Format an elevation in metres in this unit, e.g. `2,600 m`.
+    pub fn format_metres(self, elevation: f64, i18n: &I18nLoader) -> String {
+        format!(
+            "{} {}",
+            i18n::format_number(self.convert_metres(elevation), 0, i18n),
+            self.symbol()
+        )
+    }
+
+    pub fn symbol(self) -> &'static str {
+        match self {
+            Self::Metres => "m",
+            Self::Feet => "ft",
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Theme {
+    Light,
+    Dark,
+    /// Follow the theme preferred by the browser/operating system.
+    #[default]
+    Auto,
+}
+
 impl UserPreferences {
     /// Merge right into left, skipping any fields that are `None` on right.
     fn merge(mut left: Self, right: Self) -> Self {
@@ -36,6 +135,18 @@ impl UserPreferences {
         if right.wind_unit.is_some() {
             left.wind_unit = right.wind_unit;
         }
+        if right.temperature_unit.is_some() {
+            left.temperature_unit = right.temperature_unit;
+        }
+        if right.elevation_unit.is_some() {
+            left.elevation_unit = right.elevation_unit;
+        }
+        if right.theme.is_some() {
+            left.theme = right.theme;
+        }
+        if let Some(area) = right.area {
+            left.area = (!area.is_empty()).then_some(area);
+        }
 
         left
     }
@@ -61,22 +172,67 @@ pub fn set_preferences_cookie(
     current_preferences: UserPreferences,
 ) -> eyre::Result<SetPreferencesCookie> {
     let new_preferences = UserPreferences::merge(current_preferences, set_preferences);
-    let preferences_data = serde_urlencoded::to_string(new_preferences.clone())
-        .context("Error serializing preferences")?;
-    let value = HeaderValue::from_str(&format!(
-        "{COOKIE_NAME}={preferences_data}; Max-Age={COOKIE_MAX_AGE_SECONDS}"
-    ))?;
+    let value = cookie_value(&new_preferences)?;
     Ok(SetPreferencesCookie {
         new_preferences,
         value,
     })
 }
 
+fn cookie_value(preferences: &UserPreferences) -> eyre::Result<HeaderValue> {
+    let preferences_data =
+        serde_urlencoded::to_string(preferences).context("Error serializing preferences")?;
+    Ok(HeaderValue::from_str(&format!(
+        "{COOKIE_NAME}={preferences_data}; Max-Age={COOKIE_MAX_AGE_SECONDS}"
+    ))?)
+}
+
+/// Load the preferences stored for the user with `user_id`, if any.
+async fn load_preferences(
+    database: &Database,
+    user_id: &UserId,
+) -> eyre::Result<Option<UserPreferences>> {
+    Ok(sqlx::query_scalar!(
+        r#"SELECT preferences as "preferences: sqlx::types::Json<UserPreferences>" FROM user_preferences WHERE user_id=$1"#,
+        user_id
+    )
+    .fetch_optional(database)
+    .await?
+    .map(|preferences| preferences.0))
+}
+
+/// Store the `preferences` for the logged in user, so that they follow the user to other
+/// browsers. Does nothing if there is no `current_user`.
+pub async fn save_preferences(
+    database: &Database,
+    current_user: Option<&CurrentUser>,
+    preferences: &UserPreferences,
+) -> eyre::Result<()> {
+    let Some(current_user) = current_user else {
+        return Ok(());
+    };
+    let preferences = sqlx::types::Json(preferences);
+    let now = types::Time::now_utc();
+    sqlx::query!(
+        "INSERT INTO user_preferences VALUES($1, $2, $3) ON CONFLICT(user_id) DO UPDATE SET preferences=excluded.preferences, updated_at=excluded.updated_at",
+        current_user.user.id,
+        preferences,
+        now
+    )
+    .execute(database)
+    .await
+    .wrap_err("Error saving user preferences")?;
+    Ok(())
+}
+
 /// Handler for setting user preferences using a query, and redirecting to the referrer URL
-/// provided in the request. This merges with what has currently been set.
+/// provided in the request. This merges with what has currently been set, and is also saved for
+/// the logged in user.
 pub async fn query_set_redirect_handler(
     Query(set_preferences): Query<UserPreferences>,
     Extension(current_preferences): Extension<UserPreferences>,
+    Extension(database): Extension<Database>,
+    Extension(current_user): Extension<Option<CurrentUser>>,
     headers: HeaderMap,
 ) -> axum::response::Result<impl IntoResponse> {
     let referer_str = headers
@@ -88,14 +244,30 @@ pub async fn query_set_redirect_handler(
         .map_err(map_eyre_error)?;
     let mut response = Redirect::to(referer_str).into_response();
 
-    set_preferences_cookie(set_preferences, current_preferences)
-        .map_err(map_eyre_error)?
-        .set_cookie(response.headers_mut());
+    let set_preferences_cookie =
+        set_preferences_cookie(set_preferences, current_preferences).map_err(map_eyre_error)?;
+    save_preferences(
+        &database,
+        current_user.as_ref(),
+        &set_preferences_cookie.new_preferences,
+    )
+    .await
+    .map_err(map_eyre_error)?;
+    set_preferences_cookie.set_cookie(response.headers_mut());
     Ok(response)
 }
 
 /// Middleware for extracting user preferences from cookie that was set using  [`set_handler`].
-pub async fn middleware(mut request: Request, next: Next) -> Response {
+///
+/// For a logged in user the preferences stored in the database take precedence, and the cookie
+/// is updated to match them. If nothing has been stored for the user yet, the preferences from
+/// the cookie are stored.
+pub async fn middleware(
+    Extension(database): Extension<Database>,
+    Extension(current_user): Extension<Option<CurrentUser>>,
+    mut request: Request,
+    next: Next,
+) -> Response {
     let cookies = CookieJar::from_headers(request.headers());
     let preferences: UserPreferences =
         match Option::transpose(cookies.get(COOKIE_NAME).map(|cookie| {
@@ -111,6 +283,73 @@ pub async fn middleware(mut request: Request, next: Next) -> Response {
             }
         };
 
+    let (preferences, sync_cookie) =
+        match sync_preferences(&database, current_user.as_ref(), preferences).await {
+            Ok(synced) => synced,
+            Err(error) => {
+                tracing::error!("Error synchronizing user preferences: {error:?}");
+                return (
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    "Unable to load user preferences",
+                )
+                    .into_response();
+            }
+        };
+
     request.extensions_mut().insert(preferences);
-    next.run(request).await
+    let mut response = next.run(request).await;
+    // Handlers which set the preferences also set the cookie.
+    if let Some(value) = sync_cookie {
+        if !response.headers().contains_key(SET_COOKIE) {
+            response.headers_mut().insert(SET_COOKIE, value);
+        }
+    }
+    response
+}
+
+/// Synchronize the `cookie_preferences` with those stored for the `current_user`, returning the
+/// preferences to use, and a new value for the cookie if it is out of date.
+async fn sync_preferences(
+    database: &Database,
+    current_user: Option<&CurrentUser>,
+    cookie_preferences: UserPreferences,
+) -> eyre::Result<(UserPreferences, Option<HeaderValue>)> {
+    let Some(user) = current_user else {
+        return Ok((cookie_preferences, None));
+    };
+    match load_preferences(database, &user.user.id).await? {
+        Some(stored_preferences) => {
+            let cookie_data = serde_urlencoded::to_string(&cookie_preferences)?;
+            let stored_data = serde_urlencoded::to_string(&stored_preferences)?;
+            let sync_cookie = if cookie_data == stored_data {
+                None
+            } else {
+                Some(cookie_value(&stored_preferences)?)
+            };
+            Ok((stored_preferences, sync_cookie))
+        }
+        None => {
+            let cookie_data = serde_urlencoded::to_string(&cookie_preferences)?;
+            if !cookie_data.is_empty() {
+                save_preferences(database, current_user, &cookie_preferences).await?;
+            }
+            Ok((cookie_preferences, None))
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{ElevationUnit, TemperatureUnit, WindUnit};
+
+    #[test]
+    fn test_unit_conversions() {
+        assert_eq!(TemperatureUnit::Celsius.convert_celsius(-10.0), -10.0);
+        assert_eq!(TemperatureUnit::Fahrenheit.convert_celsius(-10.0), 14.0);
+        assert_eq!(TemperatureUnit::Fahrenheit.convert_celsius(100.0), 212.0);
+        assert_eq!(ElevationUnit::Metres.convert_metres(2000.0), 2000.0);
+        assert_eq!(ElevationUnit::Feet.convert_metres(3048.0).round(), 10000.0);
+        assert_eq!(WindUnit::KilometersPerHour.convert_ms(10.0), 36.0);
+        assert_eq!(WindUnit::MetersPerSecond.convert_ms(10.0), 10.0);
+    }
 }
@@ -0,0 +1,285 @@
+//! User accounts for accessing `/admin`, and the roles which determine what each user is
+//! permitted to do.
+
+use eyre::Context;
+use secrecy::{ExposeSecret, SecretString};
+use serde::{Deserialize, Serialize};
+
+use crate::{database::Database, types};
+
+/// Username of the user created from [`crate::options::Options::admin_password_hash`] when there
+/// are no users yet.
+pub const BOOTSTRAP_ADMIN_USERNAME: &str = "admin";
+/// bcrypt cost used when hashing passwords, see
+/// <https://cheatsheetseries.owasp.org/cheatsheets/Password_Storage_Cheat_Sheet.html#bcrypt>.
+const PASSWORD_HASH_COST: u32 = 10;
+
+#[derive(sqlx::Type, Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Hash)]
+#[serde(transparent)]
+#[sqlx(transparent)]
+pub struct UserId(String);
+
+impl UserId {
+    pub fn generate() -> Self {
+        Self(uuid::Uuid::new_v4().to_string())
+    }
+}
+
+impl std::fmt::Display for UserId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+/// The role of a user, which determines their [`Permission`]s.
+#[derive(
+    sqlx::Type, Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, enum_iterator::Sequence,
+)]
+#[serde(rename_all = "kebab-case")]
+#[sqlx(rename_all = "kebab-case")]
+pub enum Role {
+    /// Has every permission, including managing users.
+    Admin,
+    /// Publishes forecasts and news, and maintains forecast areas.
+    Forecaster,
+    /// Moderates submitted observations.
+    Observer,
+    /// Maintains the translations.
+    Translator,
+}
+
+/// Something a user can be permitted to do.
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum Permission {
+    ManageUsers,
+    ViewAnalytics,
+    ViewLogs,
+    EditForecasts,
+    EditForecastAreas,
+    ModerateObservations,
+    EditTranslations,
+    ManageDatabase,
+    ManageConfiguration,
+    EditNews,
+    EditPages,
+    ManageApiKeys,
+}
+
+impl Role {
+    pub fn permissions(self) -> &'static [Permission] {
+        match self {
+            Self::Admin => &[
+                Permission::ManageUsers,
+                Permission::ViewAnalytics,
+                Permission::ViewLogs,
+                Permission::EditForecasts,
+                Permission::EditForecastAreas,
+                Permission::ModerateObservations,
+                Permission::EditTranslations,
+                Permission::ManageDatabase,
+                Permission::ManageConfiguration,
+                Permission::EditNews,
+                Permission::EditPages,
+                Permission::ManageApiKeys,
+            ],
+            Self::Forecaster => &[
+                Permission::EditForecasts,
+                Permission::EditForecastAreas,
+                Permission::ModerateObservations,
+                Permission::EditNews,
+            ],
+            Self::Observer => &[Permission::ModerateObservations],
+            Self::Translator => &[Permission::EditTranslations],
+        }
+    }
+
+    pub fn has_permission(self, permission: Permission) -> bool {
+        self.permissions().contains(&permission)
+    }
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct User {
+    pub id: UserId,
+    pub username: String,
+    /// Password hashed using bcrypt. Empty for users created by logging in with OpenID Connect,
+    /// who can't log in with a password.
+    #[serde(skip)]
+    pub password_hash: String,
+    pub role: Role,
+    pub created_at: types::Time,
+}
+
+/// Hash a password using bcrypt, this is slow so it is performed on a blocking thread.
+pub async fn hash_password(password: SecretString) -> eyre::Result<String> {
+    tokio::task::spawn_blocking(move || {
+        bcrypt::hash(password.expose_secret(), PASSWORD_HASH_COST)
+            .wrap_err("Error hashing password")
+    })
+    .await?
+}
+
+/// Check the `password` against the user's password hash, this is slow so it is performed on a
+/// blocking thread. An invalid password hash never matches.
+pub async fn verify_password(user: &User, password: SecretString) -> eyre::Result<bool> {
+    let password_hash = user.password_hash.clone();
+    Ok(tokio::task::spawn_blocking(move || {
+        bcrypt::verify(password.expose_secret(), &password_hash).unwrap_or(false)
+    })
+    .await?)
+}
+
+pub async fn get_user(database: &Database, id: &UserId) -> eyre::Result<Option<User>> {
+    Ok(sqlx::query_as!(
+        User,
+        r#"SELECT
+            id as "id: UserId",
+            username,
+            password_hash,
+            role as "role: Role",
+            created_at as "created_at: types::Time"
+        FROM users WHERE id=$1"#,
+        id
+    )
+    .fetch_optional(database)
+    .await?)
+}
+
+pub async fn get_user_by_username(
+    database: &Database,
+    username: &str,
+) -> eyre::Result<Option<User>> {
+    Ok(sqlx::query_as!(
+        User,
+        r#"SELECT
+            id as "id: UserId",
+            username,
+            password_hash,
+            role as "role: Role",
+            created_at as "created_at: types::Time"
+        FROM users WHERE username=$1"#,
+        username
+    )
+    .fetch_optional(database)
+    .await?)
+}
+
+/// List all users, ordered by username.
+pub async fn list_users(database: &Database) -> eyre::Result<Vec<User>> {
+    Ok(sqlx::query_as!(
+        User,
+        r#"SELECT
+            id as "id: UserId",
+            username,
+            password_hash,
+            role as "role: Role",
+            created_at as "created_at: types::Time"
+        FROM users ORDER BY username"#
+    )
+    .fetch_all(database)
+    .await?)
+}
+
+pub async fn insert_user(database: &Database, user: &User) -> eyre::Result<()> {
+    sqlx::query!(
+        "INSERT INTO users VALUES($1, $2, $3, $4, $5)",
+        user.id,
+        user.username,
+        user.password_hash,
+        user.role,
+        user.created_at,
+    )
+    .execute(database)
+    .await?;
+    Ok(())
+}
+
+pub async fn update_user_role(database: &Database, id: &UserId, role: Role) -> eyre::Result<()> {
+    sqlx::query!("UPDATE users SET role=$1 WHERE id=$2", role, id)
+        .execute(database)
+        .await?;
+    Ok(())
+}
+
+/// Update the user's password, this also ends all of their login sessions.
+pub async fn update_user_password(
+    database: &Database,
+    id: &UserId,
+    password_hash: &str,
+) -> eyre::Result<()> {
+    let mut transaction = database.begin().await?;
+    sqlx::query!(
+        "UPDATE users SET password_hash=$1 WHERE id=$2",
+        password_hash,
+        id
+    )
+    .execute(&mut *transaction)
+    .await?;
+    sqlx::query!("DELETE FROM sessions WHERE user_id=$1", id)
+        .execute(&mut *transaction)
+        .await?;
+    transaction.commit().await?;
+    Ok(())
+}
+
+/// Delete the user, along with their login sessions.
+pub async fn delete_user(database: &Database, id: &UserId) -> eyre::Result<()> {
+    sqlx::query!("DELETE FROM users WHERE id=$1", id)
+        .execute(database)
+        .await?;
+    Ok(())
+}
+
+/// When there are no users yet, create the [`BOOTSTRAP_ADMIN_USERNAME`] user with the
+/// [`Role::Admin`] role and the `admin_password_hash` (from
+/// [`crate::options::Options::admin_password_hash`]), so that the first users can be created
+/// using `/admin/users`.
+pub async fn bootstrap_admin(
+    database: &Database,
+    admin_password_hash: Option<&SecretString>,
+) -> eyre::Result<()> {
+    let user_count = sqlx::query_scalar!("SELECT COUNT(*) FROM users")
+        .fetch_one(database)
+        .await?;
+    if user_count > 0 {
+        return Ok(());
+    }
+    let Some(admin_password_hash) = admin_password_hash else {
+        tracing::warn!(
+            "There are no users and admin_password_hash is not set, nobody will be able to log in"
+        );
+        return Ok(());
+    };
+    tracing::info!("Creating the {BOOTSTRAP_ADMIN_USERNAME:?} user from admin_password_hash");
+    insert_user(
+        database,
+        &User {
+            id: UserId::generate(),
+            username: BOOTSTRAP_ADMIN_USERNAME.to_owned(),
+            password_hash: admin_password_hash.expose_secret().clone(),
+            role: Role::Admin,
+            created_at: types::Time::now_utc(),
+        },
+    )
+    .await
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Permission, Role};
+
+    #[test]
+    fn test_role_permissions() {
+        for role in enum_iterator::all::<Role>() {
+            for permission in role.permissions() {
+                assert!(Role::Admin.has_permission(*permission));
+            }
+        }
+        assert!(Role::Forecaster.has_permission(Permission::EditForecasts));
+        assert!(Role::Observer.has_permission(Permission::ModerateObservations));
+        assert!(!Role::Observer.has_permission(Permission::EditForecasts));
+        assert!(!Role::Forecaster.has_permission(Permission::ManageUsers));
+        assert!(!Role::Translator.has_permission(Permission::ModerateObservations));
+    }
+}
@@ -1,3 +1,6 @@
+/// Version of the application, derived from the git repository it was built from.
+pub const VERSION: &str = git_version::git_version!();
+
 pub async fn handler() -> &'static str {
-    git_version::git_version!()
+    VERSION
 }
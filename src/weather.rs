@@ -2,41 +2,52 @@ use axum::{extract::State, response::IntoResponse, Extension};
 use serde::{Deserialize, Serialize};
 
 use crate::{
+    auth::CurrentUser,
+    database::Database,
     error::map_eyre_error,
     state::AppState,
     templates::{render, TemplatesWithContext},
-    user_preferences::{self, UserPreferences, WindUnit},
+    user_preferences::{self, TemperatureUnit, UserPreferences, WindUnit},
+    webcams::WebcamContext,
 };
 
-#[derive(Deserialize)]
+#[derive(Deserialize, Default)]
 #[serde(default)]
 pub struct Query {
     wind_unit: Option<WindUnit>,
+    temperature_unit: Option<TemperatureUnit>,
     include_forecast: bool,
-}
-
-impl Default for Query {
-    fn default() -> Self {
-        Self {
-            wind_unit: None,
-            include_forecast: false,
-        }
-    }
+    area: Option<String>,
 }
 
 #[derive(Serialize, Clone, Debug)]
 pub struct Context {
     weather_maps: crate::options::WeatherMaps,
+    webcams: Vec<WebcamContext>,
     wind_unit: WindUnit,
+    temperature_unit: TemperatureUnit,
+    /// Only show the current weather for the weather stations in this forecast area.
+    area: Option<String>,
 }
 
 impl Context {
-    pub fn new(options: &crate::Options, preferences: &UserPreferences) -> Self {
+    pub fn new(
+        reloadable_options: &crate::options::ReloadableOptions,
+        preferences: &UserPreferences,
+    ) -> Self {
         Self {
-            weather_maps: options.weather_maps.clone(),
+            weather_maps: reloadable_options.weather_maps.clone(),
+            webcams: WebcamContext::from_options(reloadable_options),
             wind_unit: preferences.wind_unit.unwrap_or_default(),
+            temperature_unit: preferences.temperature_unit.unwrap_or_default(),
+            area: None,
         }
     }
+
+    pub fn with_area(mut self, area: Option<String>) -> Self {
+        self.area = area;
+        self
+    }
 }
 
 pub async fn handler(
@@ -44,15 +55,32 @@ pub async fn handler(
     State(state): State<AppState>,
     Extension(templates): Extension<TemplatesWithContext>,
     Extension(current_preferences): Extension<UserPreferences>,
+    Extension(database): Extension<Database>,
+    Extension(current_user): Extension<Option<CurrentUser>>,
 ) -> axum::response::Result<impl IntoResponse> {
+    let changed_units = query.wind_unit.is_some() || query.temperature_unit.is_some();
     let set_preferences = UserPreferences {
         wind_unit: query.wind_unit,
+        temperature_unit: query.temperature_unit,
         ..UserPreferences::default()
     };
     let set_preferences_cookie =
         user_preferences::set_preferences_cookie(set_preferences, current_preferences)
             .map_err(map_eyre_error)?;
-    let context = Context::new(state.options, &set_preferences_cookie.new_preferences);
+    if changed_units {
+        user_preferences::save_preferences(
+            &database,
+            current_user.as_ref(),
+            &set_preferences_cookie.new_preferences,
+        )
+        .await
+        .map_err(map_eyre_error)?;
+    }
+    let context = Context::new(
+        &state.reloadable_options.load(),
+        &set_preferences_cookie.new_preferences,
+    )
+    .with_area(query.area);
     let mut response =
         render(&templates.environment, "weather.html", &context).map_err(map_eyre_error)?;
 
@@ -0,0 +1,367 @@
+//! Module for fetching and caching weather model point forecasts from <https://open-meteo.com/>
+//! for each forecast area, see [`crate::options::WeatherForecastLocation`].
+
+use std::collections::{BTreeMap, HashMap};
+
+use eyre::{Context, ContextCompat};
+use forecast_spreadsheet::AreaId;
+use serde::{Deserialize, Serialize};
+use time_tz::{Offset, TimeZone};
+use tracing::Instrument;
+
+use crate::{
+    database::Database, forecasts::ForecastSpreadsheetSchema, options::WeatherForecastLocation,
+    types,
+};
+
+/// Number of days of forecast to request.
+const FORECAST_DAYS: u8 = 3;
+
+/// Hourly forecast data for a location, normalized from the open-meteo response. Each of the
+/// value vectors is the same length as [`HourlyForecast::time`].
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct HourlyForecast {
+    /// Unix timestamps in seconds.
+    pub time: Vec<i64>,
+    pub snowfall_cm: Vec<Option<f64>>,
+    pub freezing_level_metres: Vec<Option<f64>>,
+    pub ridge_wind_speed_ms: Vec<Option<f64>>,
+    pub ridge_wind_direction_degrees: Vec<Option<f64>>,
+}
+
+#[derive(Deserialize, Debug)]
+struct OpenMeteoResponse {
+    hourly: OpenMeteoHourly,
+}
+
+#[derive(Deserialize, Debug)]
+struct OpenMeteoHourly {
+    time: Vec<i64>,
+    #[serde(flatten)]
+    variables: HashMap<String, Vec<Option<f64>>>,
+}
+
+/// Select the pressure level (hPa) closest to the ridge-top elevation, using the standard
+/// atmosphere.
+fn pressure_level(ridge_top_elevation_metres: f64) -> u16 {
+    match ridge_top_elevation_metres {
+        elevation if elevation < 2200.0 => 850,
+        elevation if elevation < 3600.0 => 700,
+        elevation if elevation < 4900.0 => 600,
+        _ => 500,
+    }
+}
+
+fn hourly_variable(hourly: &mut OpenMeteoHourly, name: &str) -> eyre::Result<Vec<Option<f64>>> {
+    let values = hourly
+        .variables
+        .remove(name)
+        .wrap_err_with(|| format!("Response is missing hourly variable {name:?}"))?;
+    if values.len() != hourly.time.len() {
+        eyre::bail!(
+            "Hourly variable {name:?} has {} values, expected {}",
+            values.len(),
+            hourly.time.len()
+        );
+    }
+    Ok(values)
+}
+
+impl TryFrom<(OpenMeteoResponse, u16)> for HourlyForecast {
+    type Error = eyre::Error;
+
+    fn try_from((response, level): (OpenMeteoResponse, u16)) -> Result<Self, Self::Error> {
+        let mut hourly = response.hourly;
+        Ok(Self {
+            snowfall_cm: hourly_variable(&mut hourly, "snowfall")?,
+            freezing_level_metres: hourly_variable(&mut hourly, "freezing_level_height")?,
+            ridge_wind_speed_ms: hourly_variable(&mut hourly, &format!("wind_speed_{level}hPa"))?,
+            ridge_wind_direction_degrees: hourly_variable(
+                &mut hourly,
+                &format!("wind_direction_{level}hPa"),
+            )?,
+            time: hourly.time,
+        })
+    }
+}
+
+pub async fn fetch_forecast(
+    client: &reqwest::Client,
+    location: &WeatherForecastLocation,
+) -> eyre::Result<HourlyForecast> {
+    let level = pressure_level(location.ridge_top_elevation_metres);
+    let hourly =
+        format!("snowfall,freezing_level_height,wind_speed_{level}hPa,wind_direction_{level}hPa");
+    let response = client
+        .get("https://api.open-meteo.com/v1/forecast")
+        .query(&[
+            ("latitude", location.latitude.to_string()),
+            ("longitude", location.longitude.to_string()),
+            ("hourly", hourly),
+            ("wind_speed_unit", "ms".to_owned()),
+            ("timeformat", "unixtime".to_owned()),
+            ("forecast_days", FORECAST_DAYS.to_string()),
+        ])
+        .send()
+        .await?
+        .error_for_status()
+        .wrap_err("Status code of response is an error")?
+        .json::<OpenMeteoResponse>()
+        .await
+        .wrap_err("Error deserializing response body")?;
+    (response, level).try_into()
+}
+
+pub struct WeatherForecastCache {
+    pub fetched_at: types::Time,
+    pub data: sqlx::types::Json<HourlyForecast>,
+}
+
+pub async fn get_cached_forecast(
+    database: &Database,
+    area_id: &AreaId,
+) -> eyre::Result<Option<WeatherForecastCache>> {
+    let area_id: &str = area_id;
+    // Type override to workaround https://github.com/launchbadge/sqlx/issues/1979
+    sqlx::query_as!(
+        WeatherForecastCache,
+        r#"SELECT fetched_at as "fetched_at: types::Time", data as "data!: sqlx::types::Json<HourlyForecast>" FROM weather_forecast_cache WHERE area_id = $1"#,
+        area_id,
+    )
+    .fetch_optional(database)
+    .await
+    .wrap_err("Error fetching weather forecast cache item")
+}
+
+async fn store_cached_forecast(
+    database: &Database,
+    area_id: &AreaId,
+    forecast: HourlyForecast,
+) -> eyre::Result<()> {
+    let area_id: &str = area_id;
+    let fetched_at = types::Time::now_utc();
+    let data = sqlx::types::Json(forecast);
+    sqlx::query!(
+        "INSERT INTO weather_forecast_cache VALUES($1, $2, $3) ON CONFLICT(area_id) DO UPDATE SET fetched_at=excluded.fetched_at, data=excluded.data",
+        area_id,
+        fetched_at,
+        data,
+    )
+    .execute(database)
+    .await?;
+    Ok(())
+}
+
+/// Weather model forecast for a single day in the forecast area's time zone.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct DaySummary {
+    /// Date in the format `YYYY-MM-DD`.
+    pub date: String,
+    pub snowfall_cm: f64,
+    pub freezing_level_min_metres: Option<f64>,
+    pub freezing_level_max_metres: Option<f64>,
+    pub ridge_wind_speed_max_ms: Option<f64>,
+    /// Direction of the wind at the time of the maximum speed.
+    pub ridge_wind_direction_degrees: Option<f64>,
+}
+
+/// Daily summary of the weather model forecast for display in templates.
+#[derive(Serialize, Debug, Clone)]
+pub struct WeatherModelSummary {
+    pub fetched_at: types::Time,
+    pub days: Vec<DaySummary>,
+}
+
+fn min_option(a: Option<f64>, b: Option<f64>) -> Option<f64> {
+    match (a, b) {
+        (Some(a), Some(b)) => Some(a.min(b)),
+        (a, b) => a.or(b),
+    }
+}
+
+fn max_option(a: Option<f64>, b: Option<f64>) -> Option<f64> {
+    match (a, b) {
+        (Some(a), Some(b)) => Some(a.max(b)),
+        (a, b) => a.or(b),
+    }
+}
+
+/// Summarize the hourly forecast into days, in the specified `time_zone`. Hours before the
+/// start of the current day are excluded.
+pub fn summarize(
+    forecast: &HourlyForecast,
+    time_zone: &time_tz::Tz,
+    now: time::OffsetDateTime,
+) -> Vec<DaySummary> {
+    let local_date = |time: time::OffsetDateTime| {
+        let offset = time_zone.get_offset_utc(&time).to_utc();
+        time.to_offset(offset).date()
+    };
+    let today = local_date(now);
+    let mut days: BTreeMap<time::Date, DaySummary> = BTreeMap::new();
+    for (i, timestamp) in forecast.time.iter().enumerate() {
+        let Ok(time) = time::OffsetDateTime::from_unix_timestamp(*timestamp) else {
+            continue;
+        };
+        let date = local_date(time);
+        if date < today {
+            continue;
+        }
+        let day = days.entry(date).or_insert_with(|| DaySummary {
+            date: date.to_string(),
+            snowfall_cm: 0.0,
+            freezing_level_min_metres: None,
+            freezing_level_max_metres: None,
+            ridge_wind_speed_max_ms: None,
+            ridge_wind_direction_degrees: None,
+        });
+        let value = |values: &Vec<Option<f64>>| values.get(i).copied().flatten();
+        day.snowfall_cm += value(&forecast.snowfall_cm).unwrap_or(0.0);
+        let freezing_level = value(&forecast.freezing_level_metres);
+        day.freezing_level_min_metres = min_option(day.freezing_level_min_metres, freezing_level);
+        day.freezing_level_max_metres = max_option(day.freezing_level_max_metres, freezing_level);
+        if let Some(wind_speed) = value(&forecast.ridge_wind_speed_ms) {
+            if day
+                .ridge_wind_speed_max_ms
+                .map(|max| wind_speed > max)
+                .unwrap_or(true)
+            {
+                day.ridge_wind_speed_max_ms = Some(wind_speed);
+                day.ridge_wind_direction_degrees = value(&forecast.ridge_wind_direction_degrees);
+            }
+        }
+    }
+    days.into_values()
+        .map(|mut day| {
+            // Avoid displaying floating point noise from summing hourly values.
+            day.snowfall_cm = (day.snowfall_cm * 10.0).round() / 10.0;
+            day
+        })
+        .collect()
+}
+
+/// Get the summary of the cached weather model forecast for the area, if there is one.
+pub async fn get_summary(
+    database: &Database,
+    area_id: &AreaId,
+    time_zone: &time_tz::Tz,
+) -> eyre::Result<Option<WeatherModelSummary>> {
+    Ok(get_cached_forecast(database, area_id)
+        .await?
+        .map(|cached| WeatherModelSummary {
+            days: summarize(&cached.data, time_zone, time::OffsetDateTime::now_utc()),
+            fetched_at: cached.fetched_at,
+        }))
+}
+
+/// Get the summary for the forecast area using its configured time zone, logging any errors
+/// rather than failing, the weather model forecast is supplementary to the avalanche forecast.
+pub async fn get_area_summary(
+    database: &Database,
+    area_id: &AreaId,
+    forecast_schema: &ForecastSpreadsheetSchema,
+) -> Option<WeatherModelSummary> {
+    let time_zone = forecast_schema.area_definitions.get(area_id)?.time_zone;
+    get_summary(database, area_id, time_zone)
+        .await
+        .map_err(|error| {
+            tracing::error!("Error getting weather forecast summary for area {area_id}: {error:?}")
+        })
+        .ok()
+        .flatten()
+}
+
+pub struct WeatherForecastCacheServiceConfig {
+    pub interval: std::time::Duration,
+    pub locations: &'static HashMap<AreaId, WeatherForecastLocation>,
+    pub client: reqwest::Client,
+    pub database: Database,
+}
+
+/// Service for periodically fetching and caching the weather model forecasts.
+pub struct WeatherForecastCacheService {
+    config: WeatherForecastCacheServiceConfig,
+}
+
+impl WeatherForecastCacheService {
+    pub fn new(config: WeatherForecastCacheServiceConfig) -> Self {
+        Self { config }
+    }
+
+    async fn fetch_and_cache(&self) {
+        for (area_id, location) in self.config.locations {
+            let result = async {
+                let forecast = fetch_forecast(&self.config.client, location).await?;
+                store_cached_forecast(&self.config.database, area_id, forecast).await
+            }
+            .await;
+            if let Err(error) = result {
+                tracing::error!("Error fetching weather forecast for area {area_id}: {error:?}");
+                metrics::counter!("weather_forecast_fetch_failures_total", "area" => area_id.to_string())
+                    .increment(1);
+            }
+        }
+    }
+
+    pub fn spawn(self) {
+        if self.config.locations.is_empty() {
+            return;
+        }
+        tokio::spawn(
+            async move {
+                tracing::info!("Spawned weather forecast cache service");
+                loop {
+                    self.fetch_and_cache().await;
+                    tokio::time::sleep(self.config.interval).await;
+                }
+            }
+            .instrument(tracing::error_span!("weather_forecast_cache")),
+        );
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{summarize, HourlyForecast, OpenMeteoResponse};
+
+    #[test]
+    fn test_parse_and_summarize() {
+        // 2024-01-24T00:00:00Z, with hourly values until 2024-01-25T23:00:00Z.
+        let start = 1706054400;
+        let hours = 48;
+        let time: Vec<i64> = (0..hours).map(|hour| start + hour * 3600).collect();
+        let response: OpenMeteoResponse = serde_json::from_value(serde_json::json!({
+            "latitude": 42.5,
+            "longitude": 44.5,
+            "hourly_units": {},
+            "hourly": {
+                "time": time,
+                "snowfall": (0..hours).map(|hour| if hour < 24 { 0.5 } else { 0.0 }).collect::<Vec<_>>(),
+                "freezing_level_height": (0..hours).map(|hour| 1000.0 + (hour as f64) * 10.0).collect::<Vec<_>>(),
+                "wind_speed_700hPa": (0..hours).map(|hour| (hour % 24) as f64).collect::<Vec<_>>(),
+                "wind_direction_700hPa": (0..hours).map(|hour| (hour * 5) as f64).collect::<Vec<_>>(),
+            }
+        }))
+        .unwrap();
+        let forecast = HourlyForecast::try_from((response, 700)).unwrap();
+
+        let now = time::macros::datetime!(2024-01-24 10:00 UTC);
+        let days = summarize(&forecast, time_tz::timezones::db::UTC, now);
+        assert_eq!(days.len(), 2);
+        assert_eq!(days[0].date, "2024-01-24");
+        assert_eq!(days[0].snowfall_cm, 12.0);
+        assert_eq!(days[0].freezing_level_min_metres, Some(1000.0));
+        assert_eq!(days[0].freezing_level_max_metres, Some(1230.0));
+        assert_eq!(days[0].ridge_wind_speed_max_ms, Some(23.0));
+        assert_eq!(days[0].ridge_wind_direction_degrees, Some(115.0));
+        assert_eq!(days[1].snowfall_cm, 0.0);
+        assert_eq!(days[1].ridge_wind_direction_degrees, Some(235.0));
+
+        // The first day is excluded once it has passed.
+        let now = time::macros::datetime!(2024-01-25 01:00 UTC);
+        assert_eq!(
+            summarize(&forecast, time_tz::timezones::db::UTC, now).len(),
+            1
+        );
+    }
+}
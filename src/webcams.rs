@@ -0,0 +1,235 @@
+//! Proxy for the webcams configured in [`ReloadableOptions::webcams`]. The latest image from each
+//! webcam is fetched on demand and cached in memory for its refresh interval, so that pages don't
+//! hotlink the webcam (which may not allow it, or may not handle many viewers) and the images
+//! aren't subject to cross-origin restrictions.
+
+use std::{
+    collections::HashMap,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use axum::{
+    extract::{Path, State},
+    http::{header, HeaderMap, HeaderValue, StatusCode},
+    response::IntoResponse,
+    routing::get,
+    Router,
+};
+use bytes::{Bytes, BytesMut};
+use eyre::{bail, Context};
+use serde::Serialize;
+
+use crate::{
+    error::map_eyre_error,
+    options::{ReloadableOptions, ReloadableOptionsHandle, Webcam, WebcamFormat},
+    state::AppState,
+};
+
+/// Maximum size of an image (or the data read from an MJPEG stream while looking for a frame).
+const MAX_IMAGE_BYTES: usize = 10 * 1024 * 1024;
+const FETCH_TIMEOUT: Duration = Duration::from_secs(30);
+
+#[derive(Clone)]
+struct CachedImage {
+    data: Bytes,
+    content_type: String,
+    fetched_at: Instant,
+    /// The [`Webcam::url`] the image was fetched from, which may have changed since if the
+    /// options were reloaded.
+    url: url::Url,
+}
+
+pub struct Webcams {
+    client: reqwest::Client,
+    reloadable_options: ReloadableOptionsHandle,
+    images: std::sync::Mutex<HashMap<String, Arc<tokio::sync::Mutex<Option<CachedImage>>>>>,
+}
+
+impl Webcams {
+    pub fn new(reloadable_options: ReloadableOptionsHandle, client: reqwest::Client) -> Self {
+        Self {
+            client,
+            reloadable_options,
+            images: std::sync::Mutex::default(),
+        }
+    }
+
+    /// The latest image from the webcam with the specified `id`, or `None` if there is no such
+    /// webcam. If fetching a new image fails, the previous image is used until the next attempt.
+    async fn latest(&self, id: &str) -> eyre::Result<Option<CachedImage>> {
+        let Some(webcam) = self.reloadable_options.load().webcams.get(id).cloned() else {
+            return Ok(None);
+        };
+        let entry = self
+            .images
+            .lock()
+            .expect("Webcam images lock is poisoned")
+            .entry(id.to_owned())
+            .or_default()
+            .clone();
+        let refresh_interval = Duration::from_secs(webcam.refresh_interval_seconds);
+        // Holding the lock while fetching, so that concurrent requests wait for the same fetch.
+        let mut image = entry.lock().await;
+        if let Some(cached) = &*image {
+            if cached.url == webcam.url && cached.fetched_at.elapsed() < refresh_interval {
+                return Ok(Some(cached.clone()));
+            }
+        }
+        match fetch_image(&webcam, &self.client).await {
+            Ok(fetched) => {
+                *image = Some(fetched.clone());
+                Ok(Some(fetched))
+            }
+            Err(error) => match &*image {
+                Some(cached) => {
+                    tracing::warn!("Using previous image for webcam {id:?}: {error:?}");
+                    Ok(Some(cached.clone()))
+                }
+                None => Err(error.wrap_err(format!("Error fetching image for webcam {id:?}"))),
+            },
+        }
+    }
+}
+
+async fn fetch_image(webcam: &Webcam, client: &reqwest::Client) -> eyre::Result<CachedImage> {
+    let mut response = client
+        .get(webcam.url.clone())
+        .timeout(FETCH_TIMEOUT)
+        .send()
+        .await?
+        .error_for_status()?;
+
+    let (data, content_type) = match webcam.format {
+        WebcamFormat::Still => {
+            let content_type = response
+                .headers()
+                .get(header::CONTENT_TYPE)
+                .and_then(|value| value.to_str().ok())
+                .unwrap_or("image/jpeg")
+                .to_owned();
+            if !content_type.starts_with("image/") {
+                bail!("Expected an image but the content type is {content_type:?}");
+            }
+            let mut data = BytesMut::new();
+            while let Some(chunk) = response.chunk().await? {
+                data.extend_from_slice(&chunk);
+                if data.len() > MAX_IMAGE_BYTES {
+                    bail!("Image is larger than {MAX_IMAGE_BYTES} bytes");
+                }
+            }
+            (data.freeze(), content_type)
+        }
+        WebcamFormat::Mjpeg => {
+            let mut data = BytesMut::new();
+            let frame = loop {
+                if let Some(frame) = first_jpeg_frame(&data) {
+                    break Bytes::copy_from_slice(frame);
+                }
+                if data.len() > MAX_IMAGE_BYTES {
+                    bail!("No frame found in the first {MAX_IMAGE_BYTES} bytes of the stream");
+                }
+                match response.chunk().await? {
+                    Some(chunk) => data.extend_from_slice(&chunk),
+                    None => bail!("Stream ended before a complete frame was received"),
+                }
+            };
+            (frame, "image/jpeg".to_owned())
+        }
+    };
+
+    Ok(CachedImage {
+        data,
+        content_type,
+        fetched_at: Instant::now(),
+        url: webcam.url.clone(),
+    })
+}
+
+/// Find the first complete JPEG image in the `data` of a Motion JPEG stream, from the start of
+/// image marker to the following end of image marker.
+fn first_jpeg_frame(data: &[u8]) -> Option<&[u8]> {
+    const START_OF_IMAGE: [u8; 2] = [0xFF, 0xD8];
+    const END_OF_IMAGE: [u8; 2] = [0xFF, 0xD9];
+    let start = data
+        .windows(2)
+        .position(|window| window == START_OF_IMAGE)?;
+    let end = data[start + 2..]
+        .windows(2)
+        .position(|window| window == END_OF_IMAGE)?;
+    Some(&data[start..start + 2 + end + 2])
+}
+
+/// A webcam for display in templates.
+#[derive(Serialize, Debug, Clone)]
+pub struct WebcamContext {
+    pub name: String,
+    pub image_path: String,
+}
+
+impl WebcamContext {
+    pub fn from_options(options: &ReloadableOptions) -> Vec<Self> {
+        options
+            .webcams
+            .iter()
+            .map(|(id, webcam)| Self {
+                name: webcam.name.clone(),
+                image_path: format!("/webcams/{}/latest", urlencoding::encode(id)),
+            })
+            .collect()
+    }
+}
+
+pub fn router() -> Router<AppState> {
+    Router::new().route("/{id}/latest", get(latest_handler))
+}
+
+async fn latest_handler(
+    Path(id): Path<String>,
+    State(state): State<AppState>,
+) -> axum::response::Result<impl IntoResponse> {
+    let image = state
+        .webcams
+        .latest(&id)
+        .await
+        .map_err(map_eyre_error)?
+        .ok_or(StatusCode::NOT_FOUND)?;
+    let mut headers = HeaderMap::new();
+    headers.insert(
+        header::CONTENT_TYPE,
+        HeaderValue::from_str(&image.content_type)
+            .wrap_err("Invalid content type")
+            .map_err(map_eyre_error)?,
+    );
+    let max_age = state
+        .reloadable_options
+        .load()
+        .webcams
+        .get(&id)
+        .map(|webcam| webcam.refresh_interval_seconds)
+        .unwrap_or_default()
+        .saturating_sub(image.fetched_at.elapsed().as_secs());
+    headers.insert(
+        header::CACHE_CONTROL,
+        format!("public, max-age={max_age}")
+            .parse()
+            .expect("Invalid cache control header"),
+    );
+    Ok((headers, image.data))
+}
+
+#[cfg(test)]
+mod test {
+    use super::first_jpeg_frame;
+
+    #[test]
+    fn test_first_jpeg_frame() {
+        let stream = b"--boundary\r\nContent-Type: image/jpeg\r\n\r\n\xFF\xD8\x01\x02\xFF\xD9\r\n--boundary\r\n\xFF\xD8\x03";
+        assert_eq!(
+            first_jpeg_frame(stream),
+            Some(&b"\xFF\xD8\x01\x02\xFF\xD9"[..])
+        );
+        assert_eq!(first_jpeg_frame(b"--boundary\r\n\xFF\xD8\x01\x02"), None);
+        assert_eq!(first_jpeg_frame(b""), None);
+    }
+}
@@ -0,0 +1,497 @@
+//! Outgoing webhooks (see [`crate::options::Webhooks`]). Events are queued using [`Webhooks::send`]
+//! and delivered in the background as a JSON `POST` request to each configured endpoint which
+//! subscribes to the event, retrying a few times if the request fails.
+
+use std::{
+    collections::{HashMap, HashSet},
+    time::Duration,
+};
+
+use eyre::Context;
+use hmac::{Hmac, Mac};
+use secrecy::ExposeSecret;
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use time::OffsetDateTime;
+use tokio::sync::mpsc;
+use tracing::Instrument;
+use url::Url;
+
+use crate::{
+    current_weather::get_cached_data,
+    database::Database,
+    google_drive::ListFileMetadata,
+    options::{self, WeatherStation, WeatherStationId},
+    short_links,
+    shutdown::Shutdown,
+};
+
+/// Header containing the HMAC-SHA256 signature of the request body, see
+/// [`options::Webhook::secret`].
+const SIGNATURE_HEADER: &str = "X-Avalanche-Report-Signature";
+/// Header containing the [`EventKind`].
+const EVENT_HEADER: &str = "X-Avalanche-Report-Event";
+/// Delays before retrying a failed delivery.
+const RETRY_DELAYS: [Duration; 3] = [
+    Duration::from_secs(5),
+    Duration::from_secs(30),
+    Duration::from_secs(120),
+];
+/// Interval between checking whether the weather stations are stale.
+const WEATHER_STATION_CHECK_INTERVAL: Duration = Duration::from_secs(5 * 60);
+
+/// The kinds of [`Event`], used to select which events an endpoint is sent.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum EventKind {
+    ForecastPublished,
+    ForecastUpdated,
+    ObservationSubmitted,
+    WeatherStationStale,
+    AdminAccessDenied,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "event", rename_all = "kebab-case")]
+pub enum Event {
+    /// A new forecast file was published to Google Drive or uploaded.
+    ForecastPublished {
+        name: String,
+        url: Url,
+        /// See [`crate::short_links`].
+        short_url: Option<Url>,
+    },
+    /// A published forecast file was modified.
+    ForecastUpdated {
+        name: String,
+        url: Url,
+        /// See [`crate::short_links`].
+        short_url: Option<Url>,
+    },
+    /// An observation was submitted, and is waiting for moderation at `moderation_url`.
+    ObservationSubmitted {
+        id: String,
+        #[serde(with = "time::serde::rfc3339")]
+        observed_at: OffsetDateTime,
+        latitude: f64,
+        longitude: f64,
+        moderation_url: Url,
+    },
+    /// A weather station has had no new data for
+    /// [`options::Webhooks::weather_station_stale_minutes`].
+    WeatherStationStale {
+        weather_station_id: WeatherStationId,
+        #[serde(with = "time::serde::rfc3339::option")]
+        latest_data_time: Option<OffsetDateTime>,
+    },
+    /// A user was denied access to an administration page because their client IP address is not
+    /// in the [`options::AdminAllowlist`].
+    AdminAccessDenied {
+        path: String,
+        username: String,
+        /// `None` when the client IP address is unknown.
+        client_ip: Option<String>,
+    },
+}
+
+impl Event {
+    pub fn kind(&self) -> EventKind {
+        match self {
+            Self::ForecastPublished { .. } => EventKind::ForecastPublished,
+            Self::ForecastUpdated { .. } => EventKind::ForecastUpdated,
+            Self::ObservationSubmitted { .. } => EventKind::ObservationSubmitted,
+            Self::WeatherStationStale { .. } => EventKind::WeatherStationStale,
+            Self::AdminAccessDenied { .. } => EventKind::AdminAccessDenied,
+        }
+    }
+
+    /// A human readable summary of the event, sent as the `text` field so that the payload can be
+    /// used directly with Slack and Matrix (hookshot) incoming webhooks.
+    fn text(&self) -> String {
+        match self {
+            Self::ForecastPublished {
+                name,
+                url,
+                short_url,
+            } => format!(
+                "Forecast published: {name} {}",
+                short_url.as_ref().unwrap_or(url)
+            ),
+            Self::ForecastUpdated {
+                name,
+                url,
+                short_url,
+            } => format!(
+                "Forecast updated: {name} {}",
+                short_url.as_ref().unwrap_or(url)
+            ),
+            Self::ObservationSubmitted { moderation_url, .. } => {
+                format!("Observation submitted, waiting for moderation: {moderation_url}")
+            }
+            Self::WeatherStationStale {
+                weather_station_id,
+                latest_data_time,
+            } => match latest_data_time {
+                Some(time) => {
+                    format!("Weather station {weather_station_id} has had no new data since {time}")
+                }
+                None => format!("Weather station {weather_station_id} has no data"),
+            },
+            Self::AdminAccessDenied {
+                path,
+                username,
+                client_ip,
+            } => format!(
+                "User {username:?} was denied access to {path} from {}",
+                client_ip.as_deref().unwrap_or("an unknown address")
+            ),
+        }
+    }
+}
+
+/// The request body sent to the endpoints.
+#[derive(Serialize)]
+struct Payload<'a> {
+    #[serde(flatten)]
+    event: &'a Event,
+    text: String,
+    #[serde(with = "time::serde::rfc3339")]
+    time: OffsetDateTime,
+}
+
+/// Queue for the events to be delivered, see [`spawn`]. Cheap to clone.
+#[derive(Clone)]
+pub struct Webhooks {
+    /// `None` when there are no endpoints configured.
+    tx: Option<mpsc::Sender<Event>>,
+}
+
+impl Webhooks {
+    /// Queue the `event` to be delivered to the endpoints in the background.
+    pub fn send(&self, event: Event) {
+        let Some(tx) = &self.tx else {
+            return;
+        };
+        if let Err(error) = tx.try_send(event) {
+            metrics::counter!("webhook_events_dropped_total").increment(1);
+            tracing::error!("Unable to queue webhook event, it was dropped: {error}");
+        }
+    }
+}
+
+/// Spawn the task delivering the events sent to the returned [`Webhooks`]. Events which are
+/// queued when `shutdown` is requested are still delivered (without retrying), deliveries which
+/// are waiting to be retried are abandoned.
+pub fn spawn(
+    options: &'static options::Webhooks,
+    client: reqwest::Client,
+    mut shutdown: Shutdown,
+) -> Webhooks {
+    if options.endpoints.is_empty() {
+        return Webhooks { tx: None };
+    }
+    let (tx, mut rx) = mpsc::channel::<Event>(100);
+    tokio::spawn(
+        async move {
+            loop {
+                let event = tokio::select! {
+                    event = rx.recv() => event,
+                    _ = shutdown.requested() => break,
+                };
+                let Some(event) = event else {
+                    return;
+                };
+                // Each event is delivered in its own task so that one waiting to be retried
+                // doesn't hold up the others.
+                let client = client.clone();
+                let shutdown = shutdown.clone();
+                tokio::spawn(
+                    async move { deliver(&event, options, &client, &RETRY_DELAYS, shutdown).await }
+                        .in_current_span(),
+                );
+            }
+            while let Ok(event) = rx.try_recv() {
+                deliver(&event, options, &client, &[], shutdown.clone()).await;
+            }
+        }
+        .instrument(tracing::error_span!("webhooks")),
+    );
+    Webhooks { tx: Some(tx) }
+}
+
+/// Deliver the `event` to each endpoint which subscribes to it, waiting for each of the
+/// `retry_delays` before retrying a failed delivery, unless `shutdown` is requested.
+async fn deliver(
+    event: &Event,
+    options: &options::Webhooks,
+    client: &reqwest::Client,
+    retry_delays: &[Duration],
+    mut shutdown: Shutdown,
+) {
+    let kind = event.kind();
+    let body = match serde_json::to_vec(&Payload {
+        event,
+        text: event.text(),
+        time: OffsetDateTime::now_utc(),
+    }) {
+        Ok(body) => body,
+        Err(error) => {
+            tracing::error!("Error serializing webhook event {kind:?}: {error}");
+            return;
+        }
+    };
+    let endpoints = options.endpoints.iter().filter(|endpoint| {
+        endpoint
+            .events
+            .as_ref()
+            .is_none_or(|events| events.contains(&kind))
+    });
+    for endpoint in endpoints {
+        let mut attempt = 0;
+        loop {
+            match post(client, endpoint, kind, &body).await {
+                Ok(()) => break,
+                Err(error) => {
+                    metrics::counter!("webhook_delivery_failures_total").increment(1);
+                    match retry_delays.get(attempt) {
+                        Some(delay) => {
+                            tracing::warn!(
+                                "Error delivering webhook event {kind:?} to {}, retrying in {}: {error:?}",
+                                endpoint.url,
+                                humantime::format_duration(*delay)
+                            );
+                            tokio::select! {
+                                _ = tokio::time::sleep(*delay) => {}
+                                _ = shutdown.requested() => return,
+                            }
+                            attempt += 1;
+                        }
+                        None => {
+                            tracing::error!(
+                                "Error delivering webhook event {kind:?} to {}: {error:?}",
+                                endpoint.url
+                            );
+                            break;
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+async fn post(
+    client: &reqwest::Client,
+    endpoint: &options::Webhook,
+    kind: EventKind,
+    body: &[u8],
+) -> eyre::Result<()> {
+    let event = serde_json::to_value(kind)?;
+    let mut request = client
+        .post(endpoint.url.clone())
+        .timeout(Duration::from_secs(10))
+        .header(http::header::CONTENT_TYPE, "application/json")
+        .header(EVENT_HEADER, event.as_str().unwrap_or_default());
+    if let Some(secret) = &endpoint.secret {
+        request = request.header(SIGNATURE_HEADER, signature(secret.expose_secret(), body));
+    }
+    request
+        .body(body.to_vec())
+        .send()
+        .await?
+        .error_for_status()
+        .wrap_err("Status code of response is an error")?;
+    Ok(())
+}
+
+/// The value of the [`SIGNATURE_HEADER`] for the request `body`.
+fn signature(secret: &str, body: &[u8]) -> String {
+    let mut mac =
+        Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC accepts keys of any length");
+    mac.update(body);
+    let signature: String = mac
+        .finalize()
+        .into_bytes()
+        .iter()
+        .map(|byte| format!("{byte:02x}"))
+        .collect();
+    format!("sha256={signature}")
+}
+
+/// The events for the forecast files which were added or modified between the `previous` and
+/// `current` listings of the published files. `short_link_tokens` are the
+/// [`crate::short_links`] tokens of the files by name.
+pub fn forecast_events(
+    previous: &[ListFileMetadata],
+    current: &[ListFileMetadata],
+    short_link_tokens: &HashMap<String, String>,
+    base_url: &Url,
+) -> Vec<Event> {
+    current
+        .iter()
+        .filter(|file| file.is_spreadsheet())
+        .filter_map(|file| {
+            let previous = previous.iter().find(|previous| previous.id == file.id);
+            let url = forecast_url(base_url, &file.name)?;
+            let short_url = short_link_tokens
+                .get(&file.name)
+                .and_then(|token| short_links::short_url(base_url, token));
+            let name = file.name.clone();
+            match previous {
+                None => Some(Event::ForecastPublished {
+                    name,
+                    url,
+                    short_url,
+                }),
+                Some(previous) if previous.modified_time != file.modified_time => {
+                    Some(Event::ForecastUpdated {
+                        name,
+                        url,
+                        short_url,
+                    })
+                }
+                Some(_) => None,
+            }
+        })
+        .collect()
+}
+
+/// The url of the page for the forecast file with `name`.
+pub fn forecast_url(base_url: &Url, name: &str) -> Option<Url> {
+    let mut url = base_url.join("forecasts/").ok()?;
+    url.path_segments_mut().ok()?.pop_if_empty().push(name);
+    Some(url)
+}
+
+/// Spawn a task which periodically checks the latest data of each of the `weather_stations`,
+/// sending [`Event::WeatherStationStale`] when a station becomes stale.
+pub fn spawn_weather_station_stale_check(
+    weather_stations: &'static std::collections::HashMap<WeatherStationId, WeatherStation>,
+    stale_minutes: u64,
+    database: Database,
+    webhooks: Webhooks,
+    mut shutdown: Shutdown,
+) {
+    if webhooks.tx.is_none() || weather_stations.is_empty() {
+        return;
+    }
+    let stale_after = time::Duration::minutes(i64::try_from(stale_minutes).unwrap_or(i64::MAX));
+    tokio::spawn(
+        async move {
+            let mut stale: HashSet<WeatherStationId> = HashSet::new();
+            loop {
+                tokio::select! {
+                    _ = tokio::time::sleep(WEATHER_STATION_CHECK_INTERVAL) => {}
+                    _ = shutdown.requested() => return,
+                }
+                let now = OffsetDateTime::now_utc();
+                for id in weather_stations.keys() {
+                    let latest_data_time = match get_cached_data(&database, id).await {
+                        Ok(data) => data.iter().map(|item| item.time).max(),
+                        Err(error) => {
+                            tracing::error!("{error:?}");
+                            continue;
+                        }
+                    };
+                    let is_stale = latest_data_time.is_none_or(|time| now - time > stale_after);
+                    if !is_stale {
+                        stale.remove(id);
+                    } else if stale.insert(id.clone()) {
+                        webhooks.send(Event::WeatherStationStale {
+                            weather_station_id: id.clone(),
+                            latest_data_time,
+                        });
+                    }
+                }
+            }
+        }
+        .instrument(tracing::error_span!("weather_station_stale_check")),
+    );
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::HashMap;
+
+    use time::macros::datetime;
+
+    use crate::google_drive::ListFileMetadata;
+
+    use super::{forecast_events, signature, Event};
+
+    fn file(id: &str, name: &str, modified_time: time::OffsetDateTime) -> ListFileMetadata {
+        ListFileMetadata {
+            mime_type: "application/vnd.openxmlformats-officedocument.spreadsheetml.sheet"
+                .to_owned(),
+            id: id.to_owned(),
+            name: name.to_owned(),
+            modified_time,
+        }
+    }
+
+    #[test]
+    fn test_forecast_events() {
+        let base_url = "https://avalanche.ge/".parse().unwrap();
+        let previous = vec![
+            file(
+                "a",
+                "gudauri_2024-01-01T09:00_LS.xlsx",
+                datetime!(2024-01-01 09:00 UTC),
+            ),
+            file(
+                "b",
+                "gudauri_2024-01-02T09:00_LS.xlsx",
+                datetime!(2024-01-02 09:00 UTC),
+            ),
+        ];
+        let current = vec![
+            file(
+                "a",
+                "gudauri_2024-01-01T09:00_LS.xlsx",
+                datetime!(2024-01-01 09:00 UTC),
+            ),
+            file(
+                "b",
+                "gudauri_2024-01-02T09:00_LS.xlsx",
+                datetime!(2024-01-02 10:00 UTC),
+            ),
+            file(
+                "c",
+                "gudauri_2024-01-03T09:00_LS.xlsx",
+                datetime!(2024-01-03 09:00 UTC),
+            ),
+        ];
+        let short_link_tokens = HashMap::from([(
+            "gudauri_2024-01-03T09:00_LS.xlsx".to_owned(),
+            "ab23cd".to_owned(),
+        )]);
+        let events = forecast_events(&previous, &current, &short_link_tokens, &base_url);
+        assert_eq!(events.len(), 2);
+        assert!(matches!(
+            &events[0],
+            Event::ForecastUpdated { name, short_url: None, .. }
+                if name == "gudauri_2024-01-02T09:00_LS.xlsx"
+        ));
+        match &events[1] {
+            Event::ForecastPublished { url, short_url, .. } => {
+                assert_eq!(
+                    url.as_str(),
+                    "https://avalanche.ge/forecasts/gudauri_2024-01-03T09:00_LS.xlsx"
+                );
+                assert_eq!(
+                    short_url.as_ref().map(|url| url.as_str()),
+                    Some("https://avalanche.ge/f/ab23cd")
+                );
+            }
+            event => panic!("Unexpected event {event:?}"),
+        }
+    }
+
+    #[test]
+    fn test_signature() {
+        assert_eq!(
+            signature("secret", br#"{"event":"forecast-published"}"#),
+            "sha256=7054f037f945149fe4a71af8f02023ee094c07e709c7aec1ae4abd8fdef4b930"
+        );
+    }
+}
@@ -0,0 +1,274 @@
+//! A small summary of the current forecast for an area, for embedding on third-party websites
+//! (e.g. tourism sites). `/widget/{area}.html` is a self contained page suitable for an
+//! `<iframe>`, and `/widget/{area}.js` is a script which inserts that `<iframe>` where the
+//! `<script>` tag is placed. The language can be chosen using the `lang` query parameter (see
+//! [`crate::i18n::middleware`]). These are the only pages which may be framed by other websites,
+//! see [`frame_options_middleware`].
+
+use axum::{
+    extract::{Path, Query, Request, State},
+    http::{header, HeaderValue},
+    middleware::Next,
+    response::{IntoResponse, Response},
+    routing::get,
+    Extension, Router,
+};
+use axum_extra::routing::TypedPath;
+use forecast_spreadsheet::{Forecast, HazardRatingKind, HazardRatingValue};
+use http::StatusCode;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    database::Database,
+    diagrams::elevation_hazard::HazardLevel,
+    error::{map_eyre_error, map_std_error},
+    forecasts::{
+        get_forecast_data, parse_forecast_name, ForecastData, ForecastsFilePath,
+        RequestedForecastData,
+    },
+    i18n::{self, I18nLoader},
+    state::AppState,
+    templates::TemplatesWithContext,
+};
+
+pub fn router() -> Router<AppState> {
+    Router::new().route("/{file}", get(handler))
+}
+
+/// Prevent pages other than the widgets from being embedded in frames on other websites.
+pub async fn frame_options_middleware(request: Request, next: Next) -> Response {
+    let widget = request.uri().path().starts_with("/widget/");
+    let mut response = next.run(request).await;
+    let headers = response.headers_mut();
+    if widget {
+        headers.insert(
+            header::CONTENT_SECURITY_POLICY,
+            HeaderValue::from_static("frame-ancestors *"),
+        );
+    } else {
+        headers.insert(
+            header::X_FRAME_OPTIONS,
+            HeaderValue::from_static("SAMEORIGIN"),
+        );
+    }
+    response
+}
+
+#[derive(Serialize)]
+pub struct RatingContext {
+    /// Localized name of the elevation band, or `None` for the overall rating.
+    name: Option<String>,
+    /// The level, used for the `avalanche-hazard-{level}` message.
+    level: HazardLevel,
+    /// Number of the level on the danger scale, `None` for [`HazardLevel::NoRating`].
+    number: Option<u8>,
+    colour: &'static str,
+}
+
+impl RatingContext {
+    fn new(name: Option<String>, value: Option<HazardRatingValue>) -> Self {
+        let value = value.unwrap_or(HazardRatingValue::NoRating);
+        let level = HazardLevel::from(value);
+        Self {
+            name,
+            level,
+            number: (value != HazardRatingValue::NoRating).then_some(value as u8),
+            colour: level.colour_hex(),
+        }
+    }
+}
+
+/// A summary of a forecast, also used for the forecast notification emails, see
+/// [`crate::email`].
+#[derive(Serialize)]
+pub struct ForecastSummaryContext {
+    formatted_time: String,
+    /// The forecast is no longer valid, a new forecast hasn't been published yet.
+    expired: bool,
+    overall: RatingContext,
+    bands: Vec<RatingContext>,
+    url: String,
+}
+
+impl ForecastSummaryContext {
+    /// Summarize the `forecast` published in the file with `file_name`.
+    pub fn new(
+        forecast: &Forecast,
+        file_name: &str,
+        state: &AppState,
+        i18n: &I18nLoader,
+    ) -> eyre::Result<Self> {
+        let bands = forecast
+            .elevation_bands
+            .keys()
+            .map(|band_id| {
+                let value = forecast
+                    .hazard_ratings
+                    .get(&HazardRatingKind::ElevationSpecific(band_id.clone()))
+                    .and_then(|rating| rating.value);
+                let band_id: &str = band_id;
+                let name = i18n::message_or(i18n, &format!("elevation-band-{band_id}"), band_id);
+                RatingContext::new(Some(name), value)
+            })
+            .collect();
+        let overall = forecast
+            .hazard_ratings
+            .get(&HazardRatingKind::Overall)
+            .and_then(|rating| rating.value);
+        let path = ForecastsFilePath {
+            file_name: file_name.to_owned(),
+        }
+        .to_uri()
+        .to_string();
+        Ok(Self {
+            formatted_time: i18n::format_time(forecast.time, i18n),
+            expired: time::OffsetDateTime::now_utc() > forecast.time + forecast.valid_for,
+            overall: RatingContext::new(None, overall),
+            bands,
+            url: state.options.base_url().join(&path)?.to_string(),
+        })
+    }
+}
+
+#[derive(Serialize)]
+struct WidgetContext {
+    /// Localized name of the area.
+    area_name: String,
+    /// `None` if there is no forecast for the area.
+    forecast: Option<ForecastSummaryContext>,
+    site_url: String,
+}
+
+/// The latest forecast for the `area` as a summary for the widget.
+async fn forecast_summary(
+    area: &str,
+    state: &AppState,
+    database: &Database,
+    i18n: &I18nLoader,
+) -> eyre::Result<Option<ForecastSummaryContext>> {
+    let files = state.published_files.list_files().await?;
+    let Some(file) = files
+        .iter()
+        .filter(|file| file.has_forecast_data())
+        .filter_map(|file| {
+            let details =
+                parse_forecast_name(&file.name, state.forecast_spreadsheet_schema).ok()?;
+            (details.forecast.area == area).then_some((details.forecast.time, file))
+        })
+        .max_by_key(|(time, _)| *time)
+        .map(|(_, file)| file)
+    else {
+        return Ok(None);
+    };
+    let forecast = match get_forecast_data(
+        file,
+        RequestedForecastData::Forecast,
+        &state.client,
+        database,
+        &state.blobs,
+        &state.options.google_drive,
+        state.forecast_spreadsheet_schemas,
+    )
+    .await?
+    {
+        ForecastData::Forecast(forecast) => forecast,
+        ForecastData::File(_) => eyre::bail!("Expected ForecastData::Forecast"),
+    };
+
+    Ok(Some(ForecastSummaryContext::new(
+        &forecast, &file.name, state, i18n,
+    )?))
+}
+
+#[derive(Deserialize)]
+pub struct WidgetQuery {
+    lang: Option<String>,
+}
+
+/// The script which inserts an `<iframe>` containing the widget for the `area`.
+fn widget_script(html_url: &url::Url, title: &str) -> String {
+    let html_url = serde_json::Value::String(html_url.to_string());
+    let title = serde_json::Value::String(title.to_owned());
+    format!(
+        r#"(function () {{
+  var script = document.currentScript;
+  var iframe = document.createElement("iframe");
+  iframe.src = {html_url};
+  iframe.title = {title};
+  iframe.loading = "lazy";
+  iframe.style.border = "0";
+  iframe.style.width = "100%";
+  iframe.style.maxWidth = "360px";
+  iframe.style.height = "260px";
+  script.parentNode.insertBefore(iframe, script);
+}})();
+"#
+    )
+}
+
+async fn handler(
+    Path(file): Path<String>,
+    Query(query): Query<WidgetQuery>,
+    State(state): State<AppState>,
+    Extension(templates): Extension<TemplatesWithContext>,
+    Extension(database): Extension<Database>,
+    Extension(i18n): Extension<I18nLoader>,
+) -> axum::response::Result<Response> {
+    let (area, extension) = file.rsplit_once('.').unwrap_or((&file, ""));
+    let area_known = state
+        .forecast_spreadsheet_schema
+        .area
+        .map
+        .contains_key(area);
+    if !area_known || !matches!(extension, "html" | "js") {
+        return Ok(StatusCode::NOT_FOUND.into_response());
+    }
+    let area_name = i18n::message_or(
+        &i18n,
+        &format!("forecast-area-{}", area.to_lowercase()),
+        area,
+    );
+
+    if extension == "js" {
+        let mut html_url = state
+            .options
+            .base_url()
+            .join(&format!("/widget/{area}.html"))
+            .map_err(map_std_error)?;
+        if let Some(lang) = &query.lang {
+            html_url.query_pairs_mut().append_pair("lang", lang);
+        }
+        let title = format!("{} - {area_name}", i18n.get("index-title"));
+        return Ok((
+            [(header::CONTENT_TYPE, "text/javascript; charset=utf-8")],
+            widget_script(&html_url, &title),
+        )
+            .into_response());
+    }
+
+    let context = WidgetContext {
+        forecast: forecast_summary(area, &state, &database, &i18n)
+            .await
+            .map_err(map_eyre_error)?,
+        area_name,
+        site_url: state.options.base_url().to_string(),
+    };
+    Ok(templates
+        .render("widget.html", &context)
+        .map_err(map_eyre_error)?)
+}
+
+#[cfg(test)]
+mod test {
+    use super::widget_script;
+
+    #[test]
+    fn test_widget_script_escapes_strings() {
+        let url = url::Url::parse("https://example.com/widget/Gudauri.html?lang=ka").unwrap();
+        let script = widget_script(&url, "Avalanche \"Report\"");
+        assert!(
+            script.contains(r#"iframe.src = "https://example.com/widget/Gudauri.html?lang=ka";"#)
+        );
+        assert!(script.contains(r#"iframe.title = "Avalanche \"Report\"";"#));
+    }
+}
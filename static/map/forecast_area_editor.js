@@ -0,0 +1,154 @@
+/**
+ * Editor for a forecast area's polygons and elevation band boundary lines, using Leaflet.draw.
+ *
+ * @param {Object} options
+ * @param {?string} options.forecastAreaId The id of the forecast area being edited, or null
+ * when drawing a new forecast area (the id is read from the #id input).
+ * @param {string} options.boundaryProperty The feature property holding the elevation (metres)
+ * of an elevation band boundary line.
+ */
+function forecastAreaEditor(options) {
+    const editorMap = L.map('editor-map').setView([42.4758793, 44.4751789], 11);
+
+    L.tileLayer("https://tile.opentopomap.org/{z}/{x}/{y}.png", {
+        maxZoom: 17,
+        attribution: "<a href=\"https://opentopomap.org\" target=\"_blank\">&copy; OpenTopoMap</a> <a href=\"https://www.openstreetmap.org/copyright\" target=\"_blank\">&copy; OpenStreetMap contributors</a>",
+        crossOrigin: true
+    }).addTo(editorMap);
+
+    const drawnItems = new L.FeatureGroup().addTo(editorMap);
+    editorMap.addControl(new L.Control.Draw({
+        edit: { featureGroup: drawnItems },
+        draw: {
+            polygon: { allowIntersection: false, showArea: true },
+            polyline: { shapeOptions: { color: "#7c3aed", dashArray: "6 6" } },
+            rectangle: false,
+            circle: false,
+            circlemarker: false,
+            marker: false,
+        },
+    }));
+
+    const errorElement = document.getElementById("editor-error");
+
+    function geometryUrl() {
+        const id = options.forecastAreaId || document.getElementById("id").value.trim();
+        if (!id) {
+            throw new Error("A forecast area ID is required");
+        }
+        return `/admin/forecast-areas/${encodeURIComponent(id)}/geometry`;
+    }
+
+    function promptElevation(current) {
+        const value = window.prompt("Elevation of the boundary between elevation bands (metres)", current ?? "");
+        if (value === null) {
+            return null;
+        }
+        const elevation = Number(value);
+        if (value.trim() === "" || !Number.isFinite(elevation)) {
+            window.alert(`Invalid elevation ${value}`);
+            return null;
+        }
+        return elevation;
+    }
+
+    function setElevation(layer, elevation) {
+        layer.feature = layer.feature || { type: "Feature", properties: {} };
+        layer.feature.properties[options.boundaryProperty] = elevation;
+        layer.unbindTooltip();
+        layer.bindTooltip(`${elevation} m`, { permanent: true });
+    }
+
+    function addBoundaryLine(layer, elevation) {
+        setElevation(layer, elevation);
+        layer.on("click", () => {
+            const elevation = promptElevation(layer.feature.properties[options.boundaryProperty]);
+            if (elevation !== null) {
+                setElevation(layer, elevation);
+            }
+        });
+        drawnItems.addLayer(layer);
+    }
+
+    editorMap.on(L.Draw.Event.CREATED, event => {
+        if (event.layerType === "polyline") {
+            const elevation = promptElevation();
+            if (elevation !== null) {
+                addBoundaryLine(event.layer, elevation);
+            }
+        } else {
+            drawnItems.addLayer(event.layer);
+        }
+    });
+
+    if (options.forecastAreaId) {
+        fetch(geometryUrl())
+            .then(response => response.json())
+            .then(geojson => {
+                L.geoJSON(geojson, {
+                    style: feature => feature.geometry.type === "LineString"
+                        ? { color: "#7c3aed", dashArray: "6 6" }
+                        : {},
+                    onEachFeature: (feature, layer) => {
+                        const elevation = feature.properties[options.boundaryProperty];
+                        if (elevation !== undefined) {
+                            addBoundaryLine(layer, elevation);
+                        } else {
+                            drawnItems.addLayer(layer);
+                        }
+                    },
+                });
+                if (drawnItems.getLayers().length > 0) {
+                    editorMap.fitBounds(drawnItems.getBounds());
+                }
+            })
+            .catch(err => { throw err });
+    }
+
+    document.getElementById("editor-save").addEventListener("click", () => {
+        errorElement.textContent = "";
+        let url;
+        try {
+            url = geometryUrl();
+        } catch (error) {
+            errorElement.textContent = error.message;
+            return;
+        }
+        fetch(url, {
+            method: "PUT",
+            headers: { "Content-Type": "application/json" },
+            body: JSON.stringify(drawnItems.toGeoJSON()),
+        })
+            .then(async response => {
+                if (response.ok) {
+                    window.location.href = "/admin/forecast-areas";
+                } else if (response.status === 422) {
+                    const body = await response.json();
+                    errorElement.textContent = body.errors
+                        .map(error => error.pointer ? `${error.pointer}: ${error.message}` : error.message)
+                        .join("\n");
+                } else {
+                    errorElement.textContent = await response.text();
+                }
+            })
+            .catch(error => { errorElement.textContent = error.message });
+    });
+
+    const deleteButton = document.getElementById("editor-delete");
+    if (deleteButton) {
+        deleteButton.addEventListener("click", () => {
+            if (!window.confirm("Delete this forecast area?")) {
+                return;
+            }
+            fetch(geometryUrl(), { method: "DELETE" })
+                .then(async response => {
+                    if (response.ok) {
+                        window.location.href = "/admin/forecast-areas";
+                    } else {
+                        errorElement.textContent = await response.text();
+                    }
+                })
+                .catch(error => { errorElement.textContent = error.message });
+        });
+    }
+}
@@ -0,0 +1,38 @@
+const observationsMap = L.map('observations-map').setView([42.4758793, 44.4751789], 10);
+
+L.tileLayer("https://api.maptiler.com/maps/winter-v2/{z}/{x}/{y}.png?key=PAwU5jOhvl7JaAABfVB0", {
+    maxZoom: 19,
+    attribution: "<a href=\"https://www.maptiler.com/copyright/\" target=\"_blank\">&copy; MapTiler</a> <a href=\"https://www.openstreetmap.org/copyright\" target=\"_blank\">&copy; OpenStreetMap contributors</a>",
+    tileSize: 512,
+    zoomOffset: -1,
+    minZoom: 1,
+    crossOrigin: true
+}).addTo(observationsMap);
+
+const observationMarkers = L.markerClusterGroup();
+observationsMap.addLayer(observationMarkers);
+
+function observationPopup(properties) {
+    const lines = [
+        `<a href="${properties.url}"><b>${new Date(properties.time).toLocaleString()}</b></a>`,
+        `Avalanche activity: ${properties.type}`,
+    ];
+    if (properties.elevation_metres != null) {
+        lines.push(`Elevation: ${properties.elevation_metres} m`);
+    }
+    if (properties.avalanche_size != null) {
+        lines.push(`Size: ${properties.avalanche_size}`);
+    }
+    return lines.join("<br>");
+}
+
+fetch("/observations/observations.geojson")
+    .then(response => response.json())
+    .then(geojson => {
+        observationMarkers.addLayer(L.geoJSON(geojson, {
+            onEachFeature: (feature, layer) => {
+                layer.bindPopup(observationPopup(feature.properties));
+            }
+        }));
+    })
+    .catch(err => { throw err });
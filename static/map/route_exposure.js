@@ -0,0 +1,29 @@
+function showRouteExposure(route, area) {
+    const routeMap = L.map('route-exposure-map');
+
+    L.tileLayer("https://api.maptiler.com/maps/winter-v2/{z}/{x}/{y}.png?key=PAwU5jOhvl7JaAABfVB0", {
+        maxZoom: 19,
+        attribution: "<a href=\"https://www.maptiler.com/copyright/\" target=\"_blank\">&copy; MapTiler</a> <a href=\"https://www.openstreetmap.org/copyright\" target=\"_blank\">&copy; OpenStreetMap contributors</a>",
+        tileSize: 512,
+        zoomOffset: -1,
+        minZoom: 1,
+        crossOrigin: true
+    }).addTo(routeMap);
+
+    const routeLine = L.polyline(route, { color: "#1d4ed8", weight: 4 }).addTo(routeMap);
+    routeMap.fitBounds(routeLine.getBounds());
+
+    fetch(`/forecast-areas/${area}/terrain-overlays.json`)
+        .then(response => response.json())
+        .then(overlays => {
+            const elevationBands = L.imageOverlay(overlays.elevation_bands, overlays.bounds, { opacity: 0.5 });
+            const slope = L.imageOverlay(overlays.slope, overlays.bounds, { opacity: 0.6 });
+            elevationBands.addTo(routeMap);
+            routeLine.bringToFront();
+            L.control.layers(null, {
+                "Hazard by Elevation": elevationBands,
+                "Slope Angle": slope,
+            }).addTo(routeMap);
+        })
+        .catch(err => { throw err });
+}
@@ -0,0 +1,45 @@
+// Builds the snow profile JSON submitted with an observation from the layer and temperature
+// tables on the observation submission form.
+
+function addRow(tableId, templateId) {
+    const template = document.getElementById(templateId);
+    document.getElementById(tableId).appendChild(template.content.cloneNode(true));
+}
+
+function readRows(tableId) {
+    return Array.from(document.querySelectorAll(`#${tableId} tr`))
+        .filter(row => row.querySelector("[data-field]"))
+        .map(row => {
+            const values = {};
+            row.querySelectorAll("[data-field]").forEach(input => {
+                if (input.value === "") {
+                    return;
+                }
+                values[input.dataset.field] = input.type === "number" ? Number(input.value) : input.value;
+            });
+            return values;
+        });
+}
+
+document.getElementById("snow_profile_add_layer").addEventListener("click", () => {
+    addRow("snow_profile_layers", "snow_profile_layer_template");
+});
+document.getElementById("snow_profile_add_temperature").addEventListener("click", () => {
+    addRow("snow_profile_temperatures", "snow_profile_temperature_template");
+});
+
+document.getElementById("snow_profile").form.addEventListener("submit", () => {
+    const layers = readRows("snow_profile_layers");
+    const temperatures = readRows("snow_profile_temperatures");
+    const heightInput = document.getElementById("snow_profile_height_cm");
+    const profileField = document.getElementById("snow_profile");
+    if (layers.length === 0 && temperatures.length === 0) {
+        profileField.value = "";
+        return;
+    }
+    const profile = { layers, temperatures };
+    if (heightInput.value !== "") {
+        profile.height_cm = Number(heightInput.value);
+    }
+    profileField.value = JSON.stringify(profile);
+});
@@ -1,6 +1,12 @@
 /** @type {import('tailwindcss').Config} */
 module.exports = {
   content: ["./src/**/*.{html,rs}", ],
+  // Follow the browser's preferred color scheme, unless the user has selected a theme (see
+  // `Theme` in src/user_preferences.rs), which sets the `light` or `dark` class on the root element.
+  darkMode: ['variant', [
+    '@media (prefers-color-scheme: dark) { &:not(.light *) }',
+    '&:is(.dark *)',
+  ]],
   theme: {
     extend: {},
   },